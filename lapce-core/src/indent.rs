@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use xi_rope::Rope;
 
 use crate::chars::{char_is_line_ending, char_is_whitespace};
@@ -43,6 +45,15 @@ impl IndentStyle {
             }
         }
     }
+
+    /// The name of the style, with how wide tabs are shown when indenting
+    /// with them.
+    pub fn display_name(&self, tab_width: usize) -> String {
+        match self {
+            IndentStyle::Tabs => format!("Tab Size: {tab_width}"),
+            IndentStyle::Spaces(n) => format!("Spaces: {n}"),
+        }
+    }
 }
 
 /// Attempts to detect the indentation style used in a document.
@@ -164,3 +175,85 @@ pub fn auto_detect_indent_style(document_text: &Rope) -> Option<IndentStyle> {
         None
     }
 }
+
+/// The leading whitespace of the lines of `text` that changes when it's
+/// written with `style`, as the range to replace and what replaces it. The
+/// whitespace keeps its width, counting a tab as `tab_width` columns, so
+/// converting to tabs leaves spaces for what doesn't fill a whole tab.
+pub fn reindent(
+    text: &Rope,
+    style: IndentStyle,
+    tab_width: usize,
+) -> Vec<(Range<usize>, String)> {
+    let tab_width = tab_width.max(1);
+    let mut edits = Vec::new();
+    let mut offset = 0;
+    for line in text.lines_raw(..text.len()) {
+        let mut len = 0;
+        let mut width = 0;
+        for c in line.chars() {
+            match c {
+                ' ' => width += 1,
+                '\t' => width += tab_width - width % tab_width,
+                _ => break,
+            }
+            len += 1;
+        }
+        let indent = match style {
+            IndentStyle::Spaces(_) => " ".repeat(width),
+            IndentStyle::Tabs => {
+                "\t".repeat(width / tab_width) + &" ".repeat(width % tab_width)
+            }
+        };
+        if line[..len] != indent {
+            edits.push((offset..offset + len, indent));
+        }
+        offset += line.len();
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use xi_rope::DeltaBuilder;
+
+    use super::*;
+
+    fn apply(text: &str, style: IndentStyle, tab_width: usize) -> String {
+        let text = Rope::from(text);
+        let mut builder = DeltaBuilder::new(text.len());
+        for (range, indent) in reindent(&text, style, tab_width) {
+            builder.replace(range, Rope::from(indent));
+        }
+        builder.build().apply(&text).to_string()
+    }
+
+    #[test]
+    fn test_tabs_to_spaces() {
+        assert_eq!(
+            "fn a() {\n    if b {\n        c(\"\\t\");\n    }\n}\n",
+            apply(
+                "fn a() {\n\tif b {\n\t\tc(\"\\t\");\n\t}\n}\n",
+                IndentStyle::Spaces(4),
+                4
+            )
+        );
+        // Only the leading whitespace changes, and a tab after spaces only
+        // goes up to the next tab stop.
+        assert_eq!(
+            "      a\tb\n\n        \n",
+            apply("  \t  a\tb\n\n\t\t\n", IndentStyle::Spaces(4), 4)
+        );
+        assert!(
+            reindent(&Rope::from("a\n  b\n"), IndentStyle::Spaces(2), 4).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_spaces_to_tabs() {
+        assert_eq!(
+            "a\n\tb\n\t\t  c\n",
+            apply("a\n    b\n          c\n", IndentStyle::Tabs, 4)
+        );
+    }
+}
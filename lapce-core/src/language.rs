@@ -1,5 +1,6 @@
 use std::{collections::HashSet, path::Path};
 
+use strum_macros::EnumIter;
 use tree_sitter::{Parser, TreeCursor};
 
 use crate::style::HighlightConfiguration;
@@ -33,7 +34,7 @@ const PYTHON_CODE_LENS_IGNORE_LIST: &[&str] =
 const JAVASCRIPT_CODE_LENS_LIST: &[&str] = &["source_file", "program"];
 const JAVASCRIPT_CODE_LENS_IGNORE_LIST: &[&str] = &["source_file"];
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, EnumIter)]
 pub enum LapceLanguage {
     Rust,
     Go,
@@ -81,6 +82,77 @@ impl LapceLanguage {
         })
     }
 
+    /// The language a code block is marked as, such as after the fence of
+    /// one in markdown.
+    pub fn from_name(name: &str) -> Option<LapceLanguage> {
+        Some(match name.to_lowercase().as_str() {
+            "rust" | "rs" => LapceLanguage::Rust,
+            "javascript" | "js" => LapceLanguage::Javascript,
+            "jsx" => LapceLanguage::Jsx,
+            "typescript" | "ts" => LapceLanguage::Typescript,
+            "tsx" => LapceLanguage::Tsx,
+            "go" => LapceLanguage::Go,
+            "python" | "py" => LapceLanguage::Python,
+            "toml" => LapceLanguage::Toml,
+            "php" => LapceLanguage::Php,
+            "elixir" | "ex" => LapceLanguage::Elixir,
+            "c" => LapceLanguage::C,
+            "cpp" | "c++" => LapceLanguage::Cpp,
+            "json" => LapceLanguage::Json,
+            "markdown" | "md" => LapceLanguage::Markdown,
+            "ruby" | "rb" => LapceLanguage::Ruby,
+            "html" => LapceLanguage::Html,
+            "java" => LapceLanguage::Java,
+            _ => return None,
+        })
+    }
+
+    /// The name the language goes by in the settings
+    pub fn name(&self) -> &'static str {
+        match self {
+            LapceLanguage::Rust => "rust",
+            LapceLanguage::Go => "go",
+            LapceLanguage::Javascript => "javascript",
+            LapceLanguage::Jsx => "jsx",
+            LapceLanguage::Typescript => "typescript",
+            LapceLanguage::Tsx => "tsx",
+            LapceLanguage::Python => "python",
+            LapceLanguage::Toml => "toml",
+            LapceLanguage::Php => "php",
+            LapceLanguage::Elixir => "elixir",
+            LapceLanguage::C => "c",
+            LapceLanguage::Cpp => "cpp",
+            LapceLanguage::Json => "json",
+            LapceLanguage::Markdown => "markdown",
+            LapceLanguage::Ruby => "ruby",
+            LapceLanguage::Html => "html",
+            LapceLanguage::Java => "java",
+        }
+    }
+
+    /// The name the language is shown with
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            LapceLanguage::Rust => "Rust",
+            LapceLanguage::Go => "Go",
+            LapceLanguage::Javascript => "JavaScript",
+            LapceLanguage::Jsx => "JavaScript JSX",
+            LapceLanguage::Typescript => "TypeScript",
+            LapceLanguage::Tsx => "TypeScript JSX",
+            LapceLanguage::Python => "Python",
+            LapceLanguage::Toml => "TOML",
+            LapceLanguage::Php => "PHP",
+            LapceLanguage::Elixir => "Elixir",
+            LapceLanguage::C => "C",
+            LapceLanguage::Cpp => "C++",
+            LapceLanguage::Json => "JSON",
+            LapceLanguage::Markdown => "Markdown",
+            LapceLanguage::Ruby => "Ruby",
+            LapceLanguage::Html => "HTML",
+            LapceLanguage::Java => "Java",
+        }
+    }
+
     pub fn comment_token(&self) -> &str {
         match self {
             LapceLanguage::Rust => "//",
@@ -2,5 +2,6 @@ pub mod chars;
 pub mod indent;
 pub mod language;
 pub mod lens;
+pub mod line_ending;
 pub mod style;
 pub mod syntax;
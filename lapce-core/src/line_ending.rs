@@ -0,0 +1,109 @@
+use std::ops::Range;
+
+use xi_rope::Rope;
+
+/// The characters that end the lines of a text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The line ending most lines of `text` have, LF when it has no lines
+    /// to tell.
+    pub fn detect(text: &Rope) -> LineEnding {
+        let mut crlf = 0;
+        let mut lf = 0;
+        for line in text.lines_raw(..text.len()) {
+            if line.ends_with("\r\n") {
+                crlf += 1;
+            } else if line.ends_with('\n') {
+                lf += 1;
+            }
+        }
+        if crlf > lf {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+
+    /// The ranges of the line endings of `text` that aren't this one, which
+    /// are replaced with it to convert the text.
+    pub fn ranges_to_convert(&self, text: &Rope) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        for line in text.lines_raw(..text.len()) {
+            offset += line.len();
+            match self {
+                LineEnding::Lf if line.ends_with("\r\n") => {
+                    ranges.push(offset - 2..offset);
+                }
+                LineEnding::CrLf
+                    if line.ends_with('\n') && !line.ends_with("\r\n") =>
+                {
+                    ranges.push(offset - 1..offset);
+                }
+                _ => {}
+            }
+        }
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xi_rope::DeltaBuilder;
+
+    use super::*;
+
+    fn convert(text: &Rope, ending: LineEnding) -> Rope {
+        let mut builder = DeltaBuilder::new(text.len());
+        for range in ending.ranges_to_convert(text) {
+            builder.replace(range, Rope::from(ending.as_str()));
+        }
+        builder.build().apply(text)
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(LineEnding::Lf, LineEnding::detect(&Rope::from("a")));
+        assert_eq!(
+            LineEnding::Lf,
+            LineEnding::detect(&Rope::from("a\nb\r\nc\n"))
+        );
+        assert_eq!(
+            LineEnding::CrLf,
+            LineEnding::detect(&Rope::from("a\r\nb\r\nc\n"))
+        );
+    }
+
+    #[test]
+    fn test_convert() {
+        let text = Rope::from("fn main() {\r\n    let a = 1;\r\n\r\n}\r\n");
+        let lf = convert(&text, LineEnding::Lf);
+        assert_eq!("fn main() {\n    let a = 1;\n\n}\n", lf.to_string());
+        assert!(LineEnding::Lf.ranges_to_convert(&lf).is_empty());
+
+        // Converting back gives the same text, without doubling the line
+        // endings that already were CRLF.
+        assert_eq!(text.to_string(), convert(&lf, LineEnding::CrLf).to_string());
+        let mixed = Rope::from("a\r\nb\nc");
+        assert_eq!("a\r\nb\r\nc", convert(&mixed, LineEnding::CrLf).to_string());
+    }
+}
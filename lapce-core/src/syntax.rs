@@ -7,10 +7,10 @@ use std::{
 
 use itertools::Itertools;
 use lapce_rpc::style::Style;
-use tree_sitter::{Node, Parser, Point, Tree};
+use tree_sitter::{InputEdit, Node, Parser, Point, Tree};
 use xi_rope::{
     spans::{Spans, SpansBuilder},
-    Interval, Rope, RopeDelta,
+    DeltaElement, Interval, Rope, RopeDelta,
 };
 
 use crate::{
@@ -54,9 +54,13 @@ impl std::fmt::Debug for Syntax {
 
 impl Syntax {
     pub fn init(path: &Path) -> Option<Syntax> {
-        LapceLanguage::from_path(path).map(|l| Syntax {
+        LapceLanguage::from_path(path).map(Syntax::from_language)
+    }
+
+    pub fn from_language(language: LapceLanguage) -> Syntax {
+        Syntax {
             rev: 0,
-            language: l,
+            language,
             text: Rope::from(""),
             tree: None,
             lens: Self::lens_from_normal_lines(0, 0, 0, &Vec::new()),
@@ -64,7 +68,7 @@ impl Syntax {
             lens_height: 0,
             normal_lines: Vec::new(),
             styles: None,
-        })
+        }
     }
 
     pub fn parse(
@@ -73,71 +77,21 @@ impl Syntax {
         new_text: Rope,
         delta: Option<RopeDelta>,
     ) -> Syntax {
-        let mut old_tree = None;
-        if new_rev == self.rev + 1 {
-            if let Some(delta) = delta {
-                fn point_at_offset(text: &Rope, offset: usize) -> Point {
-                    let line = text.line_of_offset(offset);
-                    let col = text.offset_of_line(line + 1) - offset;
-                    Point::new(line, col)
-                }
-                let (interval, _) = delta.summary();
-                let (start, end) = interval.start_end();
-                if let Some(inserted) = delta.as_simple_insert() {
-                    fn traverse(point: Point, text: &str) -> Point {
-                        let Point {
-                            mut row,
-                            mut column,
-                        } = point;
-
-                        for ch in text.chars() {
-                            if ch == '\n' {
-                                row += 1;
-                                column = 0;
-                            } else {
-                                column += 1;
-                            }
-                        }
-                        Point { row, column }
+        // With the edits made since the last parse applied to the old tree,
+        // tree-sitter only reparses what changed and reuses the rest.
+        let old_tree = if new_rev == self.rev + 1 {
+            delta
+                .and_then(|delta| edits_from_delta(&self.text, &new_text, &delta))
+                .and_then(|edits| {
+                    let mut tree = self.tree.clone()?;
+                    for edit in edits.iter() {
+                        tree.edit(edit);
                     }
-
-                    let start_position = point_at_offset(&self.text, start);
-
-                    let edit = tree_sitter::InputEdit {
-                        start_byte: start,
-                        old_end_byte: start,
-                        new_end_byte: start + inserted.len(),
-                        start_position,
-                        old_end_position: start_position,
-                        new_end_position: traverse(
-                            start_position,
-                            &inserted.slice_to_cow(0..inserted.len()),
-                        ),
-                    };
-                    old_tree = self.tree.as_ref().map(|tree| {
-                        let mut tree = tree.clone();
-                        tree.edit(&edit);
-                        tree
-                    });
-                } else if delta.is_simple_delete() {
-                    let start_position = point_at_offset(&self.text, start);
-                    let end_position = point_at_offset(&self.text, end);
-                    let edit = tree_sitter::InputEdit {
-                        start_byte: start,
-                        old_end_byte: end,
-                        new_end_byte: start,
-                        start_position,
-                        old_end_position: end_position,
-                        new_end_position: start_position,
-                    };
-                    old_tree = self.tree.as_ref().map(|tree| {
-                        let mut tree = tree.clone();
-                        tree.edit(&edit);
-                        tree
-                    });
-                };
-            }
-        }
+                    Some(tree)
+                })
+        } else {
+            None
+        };
 
         let new_tree = PARSER.with(|parsers| {
             let mut parsers = parsers.borrow_mut();
@@ -353,6 +307,90 @@ impl Syntax {
     }
 }
 
+/// The edits a delta makes, in the order tree-sitter applies them, so that
+/// each is given in offsets of the text with the previous ones made.
+/// Returns `None` for a delta that moves text around rather than only
+/// inserting and deleting.
+fn edits_from_delta(
+    old_text: &Rope,
+    new_text: &Rope,
+    delta: &RopeDelta,
+) -> Option<Vec<InputEdit>> {
+    fn point_at_offset(text: &Rope, offset: usize) -> Point {
+        let line = text.line_of_offset(offset);
+        Point::new(line, offset - text.offset_of_line(line))
+    }
+
+    fn traverse(point: Point, text: &str) -> Point {
+        let Point {
+            mut row,
+            mut column,
+        } = point;
+        for c in text.chars() {
+            if c == '\n' {
+                row += 1;
+                column = 0;
+            } else {
+                column += c.len_utf8();
+            }
+        }
+        Point { row, column }
+    }
+
+    // Deletes the old text from `deleted` and inserts `inserted` at `offset`
+    // of the new text.
+    let edit = |offset: usize, deleted: Interval, inserted: &str| {
+        let start_position = point_at_offset(new_text, offset);
+        InputEdit {
+            start_byte: offset,
+            old_end_byte: offset + deleted.size(),
+            new_end_byte: offset + inserted.len(),
+            start_position,
+            old_end_position: traverse(
+                start_position,
+                &old_text.slice_to_cow(deleted.start..deleted.end),
+            ),
+            new_end_position: traverse(start_position, inserted),
+        }
+    };
+
+    let mut edits = Vec::new();
+    let mut old_offset = 0;
+    let mut new_offset = 0;
+    let mut inserted = String::new();
+    for el in delta.els.iter() {
+        match el {
+            DeltaElement::Copy(start, end) => {
+                if *start < old_offset {
+                    return None;
+                }
+                if *start > old_offset || !inserted.is_empty() {
+                    edits.push(edit(
+                        new_offset,
+                        Interval::new(old_offset, *start),
+                        &inserted,
+                    ));
+                    new_offset += inserted.len();
+                    inserted.clear();
+                }
+                new_offset += end - start;
+                old_offset = *end;
+            }
+            DeltaElement::Insert(node) => {
+                inserted.push_str(&String::from(node));
+            }
+        }
+    }
+    if delta.base_len > old_offset || !inserted.is_empty() {
+        edits.push(edit(
+            new_offset,
+            Interval::new(old_offset, delta.base_len),
+            &inserted,
+        ));
+    }
+    Some(edits)
+}
+
 pub fn matching_char(c: char) -> Option<char> {
     Some(match c {
         '{' => '}',
@@ -367,8 +405,67 @@ pub fn matching_char(c: char) -> Option<char> {
 
 #[cfg(test)]
 mod tests {
+    use xi_rope::DeltaBuilder;
+
     use super::*;
 
+    fn sexp(syntax: &Syntax) -> String {
+        syntax.tree.as_ref().unwrap().root_node().to_sexp()
+    }
+
+    #[test]
+    fn test_incremental_parse() {
+        let text = Rope::from("fn main() {\n    let a = 1;\n}\n");
+        let syntax =
+            Syntax::init(Path::new("main.rs"))
+                .unwrap()
+                .parse(0, text.clone(), None);
+
+        // Replacing text is neither a simple insert nor a simple delete.
+        let delta = RopeDelta::simple_edit(
+            Interval::new(24, 25),
+            Rope::from("foo(2,\n 3)"),
+            text.len(),
+        );
+        let edits = edits_from_delta(&text, &delta.apply(&text), &delta).unwrap();
+        assert_eq!(1, edits.len());
+        assert_eq!(24, edits[0].start_byte);
+        assert_eq!(25, edits[0].old_end_byte);
+        assert_eq!(35, edits[0].new_end_byte);
+        assert_eq!(Point::new(1, 12), edits[0].start_position);
+        assert_eq!(Point::new(1, 13), edits[0].old_end_position);
+        assert_eq!(Point::new(2, 3), edits[0].new_end_position);
+
+        let new_text = delta.apply(&text);
+        let edited = syntax.parse(1, new_text.clone(), Some(delta));
+        let reparsed = syntax.parse(2, new_text, None);
+        assert_eq!(sexp(&reparsed), sexp(&edited));
+
+        // Several edits at once, each offset by the ones before it.
+        let mut builder = DeltaBuilder::new(text.len());
+        builder.replace(Interval::new(3, 7), Rope::from("start"));
+        builder.delete(Interval::new(20, 22));
+        builder.replace(Interval::new(24, 25), Rope::from("\"a\""));
+        let delta = builder.build();
+        let new_text = delta.apply(&text);
+        let edits = edits_from_delta(&text, &new_text, &delta).unwrap();
+        assert_eq!(
+            vec![(3, 7, 8), (21, 23, 21), (23, 24, 26)],
+            edits
+                .iter()
+                .map(|e| (e.start_byte, e.old_end_byte, e.new_end_byte))
+                .collect::<Vec<_>>()
+        );
+
+        let edited = syntax.parse(1, new_text.clone(), Some(delta));
+        let reparsed = syntax.parse(2, new_text, None);
+        assert_eq!(
+            "fn start() {\n    let = \"a\";\n}\n",
+            edited.text.to_string()
+        );
+        assert_eq!(sexp(&reparsed), sexp(&edited));
+    }
+
     #[test]
     fn test_lens() {
         let lens = Syntax::lens_from_normal_lines(5, 25, 2, &[4]);
@@ -5,13 +5,17 @@ use druid::{
     Data, ExtEventSink, Target, WidgetId, WindowId,
 };
 use lapce_core::indent::{auto_detect_indent_style, IndentStyle};
+use lapce_core::language::LapceLanguage;
+use lapce_core::line_ending::LineEnding;
 use lapce_core::style::line_styles;
 use lapce_core::syntax::Syntax;
-use lapce_rpc::buffer::{BufferHeadResponse, BufferId, NewBufferResponse};
+use lapce_rpc::buffer::{
+    BufferHeadResponse, BufferId, FileEncoding, NewBufferResponse,
+};
 use lapce_rpc::style::{LineStyle, LineStyles, Style};
 use lsp_types::SemanticTokensLegend;
 use lsp_types::SemanticTokensServerCapabilities;
-use lsp_types::{CodeActionResponse, Position};
+use lsp_types::{CodeActionResponse, DocumentSymbol, Position};
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::cmp;
@@ -28,8 +32,10 @@ use xi_rope::{
 };
 use xi_unicode::EmojiExt;
 
+use crate::buffer::code_actions::CodeActions;
 use crate::buffer::data::{BufferData, BufferDataListener, EditableBufferData};
 use crate::buffer::decoration::BufferDecoration;
+use crate::buffer::folding::{Folds, VisualLines};
 use crate::config::{Config, LapceTheme};
 use crate::editor::EditorLocationNew;
 use crate::find::FindProgress;
@@ -42,8 +48,11 @@ use crate::{
     state::Mode,
 };
 
+pub mod code_actions;
 pub mod data;
 pub mod decoration;
+pub mod folding;
+pub mod position;
 
 #[allow(dead_code)]
 const FIND_BATCH_SIZE: usize = 500000;
@@ -155,6 +164,9 @@ struct Revision {
 #[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
 pub enum LocalBufferKind {
     Search,
+    Replace,
+    SearchGlobs,
+    PluginFilter,
     SourceControl,
     Empty,
     FilePicker,
@@ -175,6 +187,9 @@ impl BufferContent {
             BufferContent::File(_) => false,
             BufferContent::Local(local) => match local {
                 LocalBufferKind::Search
+                | LocalBufferKind::Replace
+                | LocalBufferKind::SearchGlobs
+                | LocalBufferKind::PluginFilter
                 | LocalBufferKind::SourceControl
                 | LocalBufferKind::FilePicker
                 | LocalBufferKind::Settings
@@ -190,6 +205,9 @@ impl BufferContent {
             BufferContent::File(_) => false,
             BufferContent::Local(local) => match local {
                 LocalBufferKind::Search
+                | LocalBufferKind::Replace
+                | LocalBufferKind::SearchGlobs
+                | LocalBufferKind::PluginFilter
                 | LocalBufferKind::FilePicker
                 | LocalBufferKind::Settings
                 | LocalBufferKind::Keymap => true,
@@ -208,6 +226,30 @@ impl BufferContent {
     }
 }
 
+/// The language picked for a buffer over the one detected from its path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LanguageOverride {
+    PlainText,
+    Language(LapceLanguage),
+}
+
+impl LanguageOverride {
+    /// The id the language servers know the language by
+    pub fn language_id(&self) -> &'static str {
+        match self {
+            LanguageOverride::PlainText => "plaintext",
+            LanguageOverride::Language(language) => language.name(),
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            LanguageOverride::PlainText => "Plain Text",
+            LanguageOverride::Language(language) => language.display_name(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Buffer {
     data: BufferData,
@@ -220,13 +262,34 @@ pub struct Buffer {
     pub cursor_offset: usize,
     pub scroll_offset: Vec2,
 
-    pub code_actions: im::HashMap<usize, CodeActionResponse>,
+    code_actions: CodeActions,
+    /// The occurrences of the symbol under the cursor, with the revision
+    /// they were found at
+    pub document_highlights: Option<(u64, Arc<Vec<(usize, usize)>>)>,
+    /// The first and last line of each range that can be folded, with the
+    /// revision they were found at
+    pub folding_ranges: Option<(u64, Arc<Vec<(usize, usize)>>)>,
+    /// The symbols of the document, with the revision they were found at
+    pub document_symbols: Option<(u64, Arc<Vec<DocumentSymbol>>)>,
+    /// The folds saved for the file, applied once it's loaded
+    restored_folds: Vec<(usize, usize)>,
+    /// The language picked for the buffer, if it isn't the detected one
+    language_override: Option<LanguageOverride>,
+    /// The encoding the file is read and written in
+    encoding: FileEncoding,
+    /// The line ending most lines of the file have, which new lines get
+    line_ending: LineEnding,
+    /// The indentation picked for the buffer, if it isn't the detected one
+    indent_override: Option<IndentStyle>,
+    /// The tab width picked for the buffer, if it isn't the configured one
+    tab_width_override: Option<usize>,
 
     pub decoration: BufferDecoration,
 }
 
 pub struct BufferEditListener<'a> {
     decoration: &'a mut BufferDecoration,
+    code_actions: &'a mut CodeActions,
     proxy: &'a LapceProxy,
 }
 
@@ -241,6 +304,8 @@ impl BufferDataListener for BufferEditListener<'_> {
         }
 
         self.decoration.update_styles(delta);
+        self.decoration.folds.apply_delta(delta);
+        self.code_actions.apply_delta(delta, buffer.rev);
         self.decoration.find.borrow_mut().unset();
         *self.decoration.find_progress.borrow_mut() = FindProgress::Started;
         self.decoration.notify_update(buffer, Some(delta));
@@ -271,6 +336,7 @@ impl Buffer {
                 loaded: false,
                 local: false,
                 histories: im::HashMap::new(),
+                folds: Folds::default(),
                 tab_id,
                 event_sink,
             },
@@ -282,10 +348,24 @@ impl Buffer {
             cursor_offset: 0,
             scroll_offset: Vec2::ZERO,
 
-            code_actions: im::HashMap::new(),
+            code_actions: CodeActions::default(),
+            document_highlights: None,
+            folding_ranges: None,
+            document_symbols: None,
+            restored_folds: Vec::new(),
+            language_override: None,
+            encoding: FileEncoding::default(),
+            line_ending: LineEnding::Lf,
+            indent_override: None,
+            tab_width_override: None,
         }
     }
 
+    /// Points the buffer at where its file is after being moved.
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.data.content = BufferContent::File(path);
+    }
+
     pub fn data(&self) -> &BufferData {
         &self.data
     }
@@ -314,6 +394,132 @@ impl Buffer {
         self.data.num_lines
     }
 
+    /// The highlighted occurrences, as long as the text hasn't changed since
+    /// and `offset` is still on one of them.
+    pub fn document_highlights_at(
+        &self,
+        offset: usize,
+    ) -> Option<&Arc<Vec<(usize, usize)>>> {
+        let (rev, ranges) = self.document_highlights.as_ref()?;
+        if *rev != self.rev()
+            || !ranges
+                .iter()
+                .any(|(start, end)| *start <= offset && offset <= *end)
+        {
+            return None;
+        }
+        Some(ranges)
+    }
+
+    /// The ranges that can be folded, if they're up to date with the text.
+    pub fn current_folding_ranges(&self) -> Option<&Arc<Vec<(usize, usize)>>> {
+        let (rev, ranges) = self.folding_ranges.as_ref()?;
+        if *rev != self.rev() {
+            return None;
+        }
+        Some(ranges)
+    }
+
+    /// The symbols of the document, if they're up to date with the text.
+    pub fn current_document_symbols(&self) -> Option<&Arc<Vec<DocumentSymbol>>> {
+        let (rev, symbols) = self.document_symbols.as_ref()?;
+        if *rev != self.rev() {
+            return None;
+        }
+        Some(symbols)
+    }
+
+    /// The innermost range containing `line` that isn't folded yet.
+    pub fn folding_range_to_fold(&self, line: usize) -> Option<(usize, usize)> {
+        self.current_folding_ranges()?
+            .iter()
+            .filter(|(start, end)| {
+                *start <= line && line <= *end && !self.is_folded(*start)
+            })
+            .min_by_key(|(start, end)| end - start)
+            .copied()
+    }
+
+    /// The outermost range that can be folded and starts at `line`.
+    pub fn folding_range_starting(&self, line: usize) -> Option<(usize, usize)> {
+        self.current_folding_ranges()?
+            .iter()
+            .filter(|(start, _)| *start == line)
+            .max_by_key(|(_, end)| *end)
+            .copied()
+    }
+
+    /// Folds away the lines after `start_line` up to `end_line`.
+    pub fn fold(&mut self, start_line: usize, end_line: usize) {
+        self.decoration
+            .folds
+            .fold(&self.data.rope, start_line, end_line);
+    }
+
+    /// Unfolds the regions that start at or hide `line`, returning whether
+    /// there were any.
+    pub fn unfold(&mut self, line: usize) -> bool {
+        self.decoration.folds.unfold(&self.data.rope, line)
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.decoration.folds.clear();
+    }
+
+    /// Whether a folded region starts at `line`.
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.decoration.folds.is_folded(&self.data.rope, line)
+    }
+
+    /// The first and last line of each folded region.
+    pub fn folded_lines(&self) -> Vec<(usize, usize)> {
+        self.decoration.folds.lines(&self.data.rope)
+    }
+
+    /// Sets the folds to apply once the file is loaded.
+    pub fn restore_folds(&mut self, folds: Vec<(usize, usize)>) {
+        self.restored_folds = folds;
+    }
+
+    /// The ranges of lines hidden in folded regions.
+    pub fn hidden_lines(&self) -> Vec<Range<usize>> {
+        self.decoration.folds.hidden_lines(&self.data.rope)
+    }
+
+    /// The mapping between lines and the visual lines they're drawn on, for
+    /// going through many lines without working out the folds for each.
+    pub fn visual_lines(&self) -> VisualLines {
+        if self.decoration.folds.is_empty() {
+            return VisualLines::default();
+        }
+        VisualLines::new(self.hidden_lines())
+    }
+
+    pub fn is_line_hidden(&self, line: usize) -> bool {
+        !self.decoration.folds.is_empty() && self.visual_lines().is_hidden(line)
+    }
+
+    /// The line `line` is drawn on once folded lines are left out.
+    pub fn visual_line(&self, line: usize) -> usize {
+        if self.decoration.folds.is_empty() {
+            return line;
+        }
+        self.visual_lines().visual_line(line)
+    }
+
+    /// The line drawn on `visual_line`.
+    pub fn actual_line(&self, visual_line: usize) -> usize {
+        if self.decoration.folds.is_empty() {
+            return visual_line;
+        }
+        self.visual_lines().actual_line(visual_line)
+    }
+
+    /// The number of lines drawn, leaving out the folded ones.
+    pub fn num_visual_lines(&self) -> usize {
+        self.num_lines() - self.visual_lines().hidden_len()
+    }
+
     pub fn rev(&self) -> u64 {
         self.data.rev
     }
@@ -322,6 +528,28 @@ impl Buffer {
         self.data.rev = rev;
     }
 
+    /// The code actions asked at `offset`, if they're still valid.
+    pub fn code_actions(&self, offset: usize) -> Option<&CodeActionResponse> {
+        self.code_actions.get(self.rev(), offset)
+    }
+
+    /// Caches the code actions asked at `offset` in the revision `rev`.
+    /// They're dropped if the buffer has been edited since.
+    pub fn set_code_actions(
+        &mut self,
+        rev: u64,
+        offset: usize,
+        actions: CodeActionResponse,
+    ) {
+        if self.rev() == rev {
+            self.code_actions.insert(rev, offset, actions);
+        }
+    }
+
+    pub fn clear_code_actions(&mut self) {
+        self.code_actions.clear();
+    }
+
     pub fn dirty(&self) -> bool {
         self.data.dirty
     }
@@ -359,6 +587,83 @@ impl Buffer {
         self.decoration.syntax = syntax;
     }
 
+    /// The language the buffer is highlighted as.
+    pub fn language(&self) -> Option<LapceLanguage> {
+        self.syntax().map(|syntax| syntax.language)
+    }
+
+    pub fn language_override(&self) -> Option<LanguageOverride> {
+        self.language_override
+    }
+
+    /// The id of the language the proxy serves the buffer as, the same way
+    /// it picks it.
+    pub fn language_id(&self) -> Option<&str> {
+        match (self.language_override, self.content()) {
+            (Some(language), _) => Some(language.language_id()),
+            (None, BufferContent::File(path)) => {
+                language_id_from_path(path.to_str()?)
+            }
+            (None, _) => None,
+        }
+    }
+
+    /// Highlights the buffer as `language`, or as the language detected from
+    /// its path when it's `None`.
+    pub fn set_language(&mut self, language: Option<LanguageOverride>) {
+        self.language_override = language;
+        let syntax = match (language, &self.data.content) {
+            (Some(LanguageOverride::PlainText), _) => None,
+            (Some(LanguageOverride::Language(language)), _) => {
+                Some(Syntax::from_language(language))
+            }
+            (None, BufferContent::File(path)) => Syntax::init(path),
+            (None, _) => None,
+        };
+        self.decoration.syntax = syntax;
+        self.decoration.semantic_styles = None;
+        self.decoration.line_styles.borrow_mut().clear();
+        self.notify_update(None);
+    }
+
+    pub fn encoding(&self) -> &FileEncoding {
+        &self.encoding
+    }
+
+    pub fn set_encoding(&mut self, encoding: FileEncoding) {
+        self.encoding = encoding;
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    pub fn indent_style(&self) -> IndentStyle {
+        self.data.indent_style
+    }
+
+    /// Indents the buffer with `indent_style` from now on, instead of what's
+    /// detected from its content.
+    pub fn set_indent_style(&mut self, indent_style: IndentStyle) {
+        self.indent_override = Some(indent_style);
+        self.data.indent_style = indent_style;
+    }
+
+    /// How many columns a tab is shown as in the buffer.
+    pub fn tab_width(&self, config: &Config) -> usize {
+        self.tab_width_override.unwrap_or(config.editor.tab_width)
+    }
+
+    /// Shows tabs as `tab_width` columns wide in the buffer from now on,
+    /// instead of the configured width.
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width_override = Some(tab_width);
+    }
+
     pub fn histories(&self) -> &im::HashMap<String, Rope> {
         &self.decoration.histories
     }
@@ -386,6 +691,7 @@ impl Buffer {
         EditableBufferData {
             listener: BufferEditListener {
                 decoration: &mut self.decoration,
+                code_actions: &mut self.code_actions,
                 proxy,
             },
             buffer: &mut self.data,
@@ -415,6 +721,10 @@ impl Buffer {
         }
 
         self.code_actions.clear();
+        self.line_ending = LineEnding::detect(&self.data.rope);
+        for (start_line, end_line) in std::mem::take(&mut self.restored_folds) {
+            self.fold(start_line, end_line);
+        }
         let (max_len, max_len_line) = self.get_max_line_len();
         self.data.max_len = max_len;
         self.data.max_len_line = max_len_line;
@@ -425,6 +735,10 @@ impl Buffer {
     }
 
     pub fn detect_indent(&mut self) {
+        if let Some(indent_style) = self.indent_override {
+            self.data.indent_style = indent_style;
+            return;
+        }
         self.data.indent_style = auto_detect_indent_style(&self.data.rope)
             .unwrap_or_else(|| {
                 self.syntax()
@@ -444,10 +758,13 @@ impl Buffer {
             let tab_id = self.decoration.tab_id;
             let version = version.to_string();
             let event_sink = self.decoration.event_sink.clone();
+            // Highlighted the same as the buffer, even if its language was
+            // picked over the detected one.
+            let language = self.language();
             rayon::spawn(move || {
-                if let Some(syntax) =
-                    Syntax::init(&path).map(|s| s.parse(0, content, None))
-                {
+                if let Some(syntax) = language.map(|language| {
+                    Syntax::from_language(language).parse(0, content, None)
+                }) {
                     if let Some(styles) = syntax.styles {
                         let _ = event_sink.submit_command(
                             LAPCE_UI_COMMAND,
@@ -608,6 +925,7 @@ impl Buffer {
                                     LapceUICommand::LoadBuffer {
                                         path,
                                         content: resp.content,
+                                        encoding: resp.encoding,
                                         locations,
                                     },
                                     Target::Widget(tab_id),
@@ -922,16 +1240,26 @@ impl Buffer {
         self.data.indent_on_line(line)
     }
 
+    pub fn trailing_whitespace(&self, line: usize) -> Option<Range<usize>> {
+        self.data.trailing_whitespace(line)
+    }
+
+    pub fn has_mixed_indent(&self, line: usize) -> bool {
+        self.data.has_mixed_indent(line)
+    }
+
     pub fn slice_to_cow(&self, range: Range<usize>) -> Cow<str> {
         self.data.slice_to_cow(range)
     }
 
-    pub fn offset_to_position(&self, offset: usize, tab_width: usize) -> Position {
-        self.data.offset_to_position(offset, tab_width)
+    /// The position of `offset` as language servers count it, in UTF-16
+    /// code units.
+    pub fn offset_to_position(&self, offset: usize) -> Position {
+        self.data.offset_to_position(offset)
     }
 
-    pub fn offset_of_position(&self, pos: &Position, tab_width: usize) -> usize {
-        self.offset_of_line_col(pos.line as usize, pos.character as usize, tab_width)
+    pub fn offset_of_position(&self, pos: &Position) -> usize {
+        self.data.offset_of_position(pos)
     }
 
     pub fn offset_of_line_col(
@@ -1196,10 +1524,11 @@ impl Buffer {
         compare: Option<&str>,
         config: &Config,
     ) -> (usize, ColPosition) {
+        let tab_width = self.tab_width(config);
         let horiz = if let Some(horiz) = horiz {
             *horiz
         } else {
-            let (_, col) = self.offset_to_line_col(offset, config.editor.tab_width);
+            let (_, col) = self.offset_to_line_col(offset, tab_width);
             ColPosition::Col(col)
         };
         match movement {
@@ -1215,8 +1544,7 @@ impl Buffer {
 
                 let new_offset =
                     self.prev_grapheme_offset(offset, count, min_offset);
-                let (_, col) =
-                    self.offset_to_line_col(new_offset, config.editor.tab_width);
+                let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                 (new_offset, ColPosition::Col(col))
             }
             Movement::Right => {
@@ -1231,8 +1559,7 @@ impl Buffer {
                 let new_offset =
                     self.next_grapheme_offset(offset, count, max_offset);
 
-                let (_, col) =
-                    self.offset_to_line_col(new_offset, config.editor.tab_width);
+                let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                 (new_offset, ColPosition::Col(col))
             }
             Movement::Up => {
@@ -1268,17 +1595,16 @@ impl Buffer {
                     }
                     line
                 } else {
-                    line.saturating_sub(count)
+                    self.actual_line(self.visual_line(line).saturating_sub(count))
                 };
 
                 let col = self.line_horiz_col(
                     line,
                     &horiz,
                     mode != Mode::Normal,
-                    config.editor.tab_width,
+                    tab_width,
                 );
-                let new_offset =
-                    self.offset_of_line_col(line, col, config.editor.tab_width);
+                let new_offset = self.offset_of_line_col(line, col, tab_width);
                 (new_offset, horiz)
             }
             Movement::Down => {
@@ -1313,17 +1639,17 @@ impl Buffer {
                     }
                     line
                 } else {
-                    (line + count).min(last_line)
+                    self.actual_line(self.visual_line(line) + count)
+                        .min(last_line)
                 };
 
                 let col = self.line_horiz_col(
                     line,
                     &horiz,
                     mode != Mode::Normal,
-                    config.editor.tab_width,
+                    tab_width,
                 );
-                let new_offset =
-                    self.offset_of_line_col(line, col, config.editor.tab_width);
+                let new_offset = self.offset_of_line_col(line, col, tab_width);
                 (new_offset, horiz)
             }
             Movement::DocumentStart => (0, ColPosition::Start),
@@ -1356,18 +1682,16 @@ impl Buffer {
                     line,
                     &horiz,
                     mode != Mode::Normal,
-                    config.editor.tab_width,
+                    tab_width,
                 );
-                let new_offset =
-                    self.offset_of_line_col(line, col, config.editor.tab_width);
+                let new_offset = self.offset_of_line_col(line, col, tab_width);
                 (new_offset, horiz)
             }
             Movement::Offset(offset) => {
                 let new_offset = *offset;
                 let new_offset =
                     self.data.rope.prev_grapheme_offset(new_offset + 1).unwrap();
-                let (_, col) =
-                    self.offset_to_line_col(new_offset, config.editor.tab_width);
+                let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                 (new_offset, ColPosition::Col(col))
             }
             Movement::WordEndForward => {
@@ -1377,24 +1701,21 @@ impl Buffer {
                 if mode != Mode::Insert {
                     new_offset = self.prev_grapheme_offset(new_offset, 1, 0);
                 }
-                let (_, col) =
-                    self.offset_to_line_col(new_offset, config.editor.tab_width);
+                let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                 (new_offset, ColPosition::Col(col))
             }
             Movement::WordForward => {
                 let new_offset = WordCursor::new(&self.data.rope, offset)
                     .next_boundary()
                     .unwrap_or(offset);
-                let (_, col) =
-                    self.offset_to_line_col(new_offset, config.editor.tab_width);
+                let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                 (new_offset, ColPosition::Col(col))
             }
             Movement::WordBackward => {
                 let new_offset = WordCursor::new(&self.data.rope, offset)
                     .prev_boundary()
                     .unwrap_or(offset);
-                let (_, col) =
-                    self.offset_to_line_col(new_offset, config.editor.tab_width);
+                let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                 (new_offset, ColPosition::Col(col))
             }
             Movement::NextUnmatched(c) => {
@@ -1402,15 +1723,13 @@ impl Buffer {
                     let new_offset = syntax
                         .find_tag(offset, false, &c.to_string())
                         .unwrap_or(offset);
-                    let (_, col) =
-                        self.offset_to_line_col(new_offset, config.editor.tab_width);
+                    let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                     (new_offset, ColPosition::Col(col))
                 } else {
                     let new_offset = WordCursor::new(&self.data.rope, offset)
                         .next_unmatched(*c)
                         .map_or(offset, |new| new - 1);
-                    let (_, col) =
-                        self.offset_to_line_col(new_offset, config.editor.tab_width);
+                    let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                     (new_offset, ColPosition::Col(col))
                 }
             }
@@ -1419,15 +1738,13 @@ impl Buffer {
                     let new_offset = syntax
                         .find_tag(offset, true, &c.to_string())
                         .unwrap_or(offset);
-                    let (_, col) =
-                        self.offset_to_line_col(new_offset, config.editor.tab_width);
+                    let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                     (new_offset, ColPosition::Col(col))
                 } else {
                     let new_offset = WordCursor::new(&self.data.rope, offset)
                         .previous_unmatched(*c)
                         .unwrap_or(offset);
-                    let (_, col) =
-                        self.offset_to_line_col(new_offset, config.editor.tab_width);
+                    let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                     (new_offset, ColPosition::Col(col))
                 }
             }
@@ -1435,15 +1752,13 @@ impl Buffer {
                 if let Some(syntax) = self.syntax() {
                     let new_offset =
                         syntax.find_matching_pair(offset).unwrap_or(offset);
-                    let (_, col) =
-                        self.offset_to_line_col(new_offset, config.editor.tab_width);
+                    let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                     (new_offset, ColPosition::Col(col))
                 } else {
                     let new_offset = WordCursor::new(&self.data.rope, offset)
                         .match_pairs()
                         .unwrap_or(offset);
-                    let (_, col) =
-                        self.offset_to_line_col(new_offset, config.editor.tab_width);
+                    let (_, col) = self.offset_to_line_col(new_offset, tab_width);
                     (new_offset, ColPosition::Col(col))
                 }
             }
@@ -2087,7 +2402,6 @@ pub struct DiffHunk {
 //    }
 //}
 
-#[allow(dead_code)]
 fn language_id_from_path(path: &str) -> Option<&str> {
     let path_buf = PathBuf::from_str(path).ok()?;
     Some(match path_buf.extension()?.to_str()? {
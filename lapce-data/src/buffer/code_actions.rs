@@ -0,0 +1,151 @@
+//! The code actions of a buffer, cached by the offset they were asked at.
+//!
+//! The offsets move along with the edits before them, so the actions stay
+//! around while typing elsewhere in the file. The actions at an offset an
+//! edit touches are dropped, and so are the ones carrying their edit already,
+//! as its ranges are for the revision they were asked in. They're asked for
+//! again the next time they're needed.
+
+use lsp_types::{CodeAction, CodeActionOrCommand, CodeActionResponse};
+use xi_rope::{RopeDelta, Transformer};
+
+#[derive(Clone, Default)]
+pub struct CodeActions {
+    /// The revision the offsets are for
+    rev: u64,
+    actions: im::HashMap<usize, CodeActionResponse>,
+}
+
+impl CodeActions {
+    /// The actions at `offset`, if they're for the revision `rev`.
+    pub fn get(&self, rev: u64, offset: usize) -> Option<&CodeActionResponse> {
+        if self.rev != rev {
+            return None;
+        }
+        self.actions.get(&offset)
+    }
+
+    /// Caches the actions asked at `offset` in the revision `rev`, dropping
+    /// the ones of older revisions the edits didn't carry over.
+    pub fn insert(&mut self, rev: u64, offset: usize, actions: CodeActionResponse) {
+        if self.rev != rev {
+            self.actions.clear();
+            self.rev = rev;
+        }
+        self.actions.insert(offset, actions);
+    }
+
+    pub fn clear(&mut self) {
+        self.actions.clear();
+    }
+
+    /// Moves the actions along with an edit that made the revision `rev`.
+    pub fn apply_delta(&mut self, delta: &RopeDelta, rev: u64) {
+        if self.actions.is_empty() {
+            self.rev = rev;
+            return;
+        }
+        let (interval, _) = delta.summary();
+        let mut transformer = Transformer::new(delta);
+        self.actions = self
+            .actions
+            .iter()
+            .filter(|(offset, actions)| {
+                (interval.end() < **offset || interval.start() > **offset)
+                    && !actions.iter().any(has_edit)
+            })
+            .map(|(offset, actions)| {
+                (transformer.transform(*offset, true), actions.clone())
+            })
+            .collect();
+        self.rev = rev;
+    }
+}
+
+/// Whether the action comes with its edit, rather than a command or an edit
+/// resolved when it's picked.
+fn has_edit(action: &CodeActionOrCommand) -> bool {
+    matches!(
+        action,
+        CodeActionOrCommand::CodeAction(CodeAction { edit: Some(_), .. })
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use xi_rope::{Delta, Interval, Rope};
+
+    use super::*;
+
+    fn actions(title: &str) -> CodeActionResponse {
+        serde_json::from_value(serde_json::json!([{ "title": title }])).unwrap()
+    }
+
+    fn titles(actions: Option<&CodeActionResponse>) -> Option<String> {
+        serde_json::to_value(actions?).unwrap()[0]["title"]
+            .as_str()
+            .map(|title| title.to_string())
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let text = Rope::from("let a = 1;\nlet b = 2;\n");
+        let mut cache = CodeActions::default();
+        cache.insert(0, 4, actions("a"));
+        cache.insert(0, 15, actions("b"));
+
+        // An edit after the first action shifts the second one and keeps both.
+        let delta =
+            Delta::simple_edit(Interval::new(10, 10), Rope::from("  "), text.len());
+        let text = delta.apply(&text);
+        cache.apply_delta(&delta, 1);
+        assert_eq!(Some("a".to_string()), titles(cache.get(1, 4)));
+        assert_eq!(Some("b".to_string()), titles(cache.get(1, 17)));
+        assert!(cache.get(1, 15).is_none());
+        // Nothing is handed out for another revision.
+        assert!(cache.get(0, 4).is_none());
+
+        // An edit at an action drops only that one.
+        let delta =
+            Delta::simple_edit(Interval::new(4, 5), Rope::from("x"), text.len());
+        cache.apply_delta(&delta, 2);
+        assert!(cache.get(2, 4).is_none());
+        assert_eq!(Some("b".to_string()), titles(cache.get(2, 17)));
+
+        // Actions found for an older revision replace nothing.
+        cache.insert(3, 0, actions("c"));
+        assert!(cache.get(3, 17).is_none());
+        assert_eq!(Some("c".to_string()), titles(cache.get(3, 0)));
+    }
+
+    #[test]
+    fn test_apply_delta_drops_edits() {
+        let text = Rope::from("let a = 1;\nlet b = 2;\n");
+        let mut cache = CodeActions::default();
+        cache.insert(0, 4, actions("a"));
+        let with_edit = serde_json::from_value(serde_json::json!([{
+            "title": "b",
+            "edit": {
+                "changes": {
+                    "file:///a.rs": [{
+                        "range": {
+                            "start": { "line": 1, "character": 4 },
+                            "end": { "line": 1, "character": 5 },
+                        },
+                        "newText": "c",
+                    }],
+                },
+            },
+        }]))
+        .unwrap();
+        cache.insert(0, 15, with_edit);
+        assert_eq!(Some("b".to_string()), titles(cache.get(0, 15)));
+
+        // The ranges of the edit would be off after the edit before it.
+        let delta =
+            Delta::simple_edit(Interval::new(0, 0), Rope::from("  "), text.len());
+        cache.apply_delta(&delta, 1);
+        assert_eq!(Some("a".to_string()), titles(cache.get(1, 6)));
+        assert!(cache.get(1, 17).is_none());
+    }
+}
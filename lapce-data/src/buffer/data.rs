@@ -11,7 +11,7 @@ use xi_rope::{multiset::Subset, rope::Rope, DeltaBuilder, RopeDelta};
 use xi_rope::{Cursor, Delta};
 
 use crate::buffer::{
-    char_width, shuffle, shuffle_tombstones, str_col, BufferContent, Contents,
+    position, shuffle, shuffle_tombstones, str_col, BufferContent, Contents,
     EditType, InvalLines, Revision, WordCursor,
 };
 use crate::movement::{ColPosition, SelRegion, Selection};
@@ -140,21 +140,31 @@ impl BufferData {
         indent.to_string()
     }
 
+    /// Returns the offset range of the spaces and tabs at the end of `line`,
+    /// not including the line ending.
+    pub fn trailing_whitespace(&self, line: usize) -> Option<Range<usize>> {
+        let line_start = self.offset_of_line(line);
+        let line_end = self.line_end_offset(line, true);
+        let content = self.slice_to_cow(line_start..line_end);
+        let trimmed = content.trim_end_matches(|c| c == ' ' || c == '\t');
+        if trimmed.len() == content.len() {
+            return None;
+        }
+        Some(line_start + trimmed.len()..line_end)
+    }
+
+    /// Whether the leading indentation of `line` contains both tabs and spaces.
+    pub fn has_mixed_indent(&self, line: usize) -> bool {
+        let indent = self.indent_on_line(line);
+        indent.contains('\t') && indent.contains(' ')
+    }
+
     pub fn offset_to_line_col(
         &self,
         offset: usize,
         tab_width: usize,
     ) -> (usize, usize) {
-        let max = self.len();
-        let offset = if offset > max { max } else { offset };
-        let line = self.line_of_offset(offset);
-        let line_start = self.offset_of_line(line);
-        if offset == line_start {
-            return (line, 0);
-        }
-
-        let col = str_col(&self.slice_to_cow(line_start..offset), tab_width);
-        (line, col)
+        position::offset_to_line_col(&self.rope, offset, tab_width)
     }
 
     pub fn line_end_col(&self, line: usize, caret: bool, tab_width: usize) -> usize {
@@ -194,12 +204,12 @@ impl BufferData {
         self.line_end_offset(line, caret)
     }
 
-    pub fn offset_to_position(&self, offset: usize, tab_width: usize) -> Position {
-        let (line, col) = self.offset_to_line_col(offset, tab_width);
-        Position {
-            line: line as u32,
-            character: col as u32,
-        }
+    pub fn offset_to_position(&self, offset: usize) -> Position {
+        position::offset_to_position(&self.rope, offset)
+    }
+
+    pub fn offset_of_position(&self, pos: &Position) -> usize {
+        position::position_to_offset(&self.rope, pos)
     }
 
     pub fn offset_of_line_col(
@@ -208,32 +218,7 @@ impl BufferData {
         col: usize,
         tab_width: usize,
     ) -> usize {
-        let mut pos = 0;
-        let mut offset = self.offset_of_line(line);
-        for c in self
-            .slice_to_cow(self.offset_of_line(line)..self.offset_of_line(line + 1))
-            .chars()
-        {
-            if c == '\n' {
-                return offset;
-            }
-            let width = if c == '\t' {
-                tab_width - pos % tab_width
-            } else {
-                char_width(c)
-            };
-
-            pos += width;
-            if pos > col {
-                return offset;
-            }
-
-            offset += c.len_utf8();
-            if pos == col {
-                return offset;
-            }
-        }
-        offset
+        position::line_col_to_offset(&self.rope, line, col, tab_width)
     }
 
     pub fn first_non_blank_character_on_line(&self, line: usize) -> usize {
@@ -3,7 +3,6 @@ use lapce_core::syntax::Syntax;
 use lapce_rpc::style::{LineStyles, Style};
 use std::{
     cell::RefCell,
-    path::PathBuf,
     rc::Rc,
     sync::{
         atomic::{self},
@@ -13,9 +12,12 @@ use std::{
 use xi_rope::{rope::Rope, spans::Spans, RopeDelta};
 
 use crate::{
-    buffer::{data::BufferData, rope_diff, BufferContent, LocalBufferKind},
+    buffer::{
+        data::BufferData, folding::Folds, rope_diff, BufferContent, LocalBufferKind,
+    },
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     find::{Find, FindProgress},
+    picker::split_picker_input,
 };
 
 #[derive(Clone)]
@@ -32,10 +34,51 @@ pub struct BufferDecoration {
 
     pub(super) histories: im::HashMap<String, Rope>,
 
+    pub(super) folds: Folds,
+
     pub(super) tab_id: WidgetId,
     pub(super) event_sink: ExtEventSink,
 }
 
+/// Style updates for a buffer that arrived since they were last applied.
+/// A newer update replaces an older one of the same kind, while syntax and
+/// semantic updates are kept apart so neither drops the other.
+#[derive(Default)]
+pub struct PendingStyles {
+    syntax: Option<(u64, Syntax)>,
+    semantic_styles: Option<(u64, Arc<Spans<Style>>)>,
+}
+
+impl PendingStyles {
+    pub fn update_syntax(&mut self, rev: u64, syntax: Syntax) {
+        if self.syntax.as_ref().map(|(r, _)| rev >= *r).unwrap_or(true) {
+            self.syntax = Some((rev, syntax));
+        }
+    }
+
+    pub fn update_semantic_styles(&mut self, rev: u64, styles: Arc<Spans<Style>>) {
+        if self
+            .semantic_styles
+            .as_ref()
+            .map(|(r, _)| rev >= *r)
+            .unwrap_or(true)
+        {
+            self.semantic_styles = Some((rev, styles));
+        }
+    }
+
+    /// The pending syntax and semantic styles computed for `rev`, leaving out
+    /// those that are stale.
+    pub fn take(self, rev: u64) -> (Option<Syntax>, Option<Arc<Spans<Style>>>) {
+        (
+            self.syntax.filter(|(r, _)| *r == rev).map(|(_, s)| s),
+            self.semantic_styles
+                .filter(|(r, _)| *r == rev)
+                .map(|(_, s)| s),
+        )
+    }
+}
+
 impl BufferDecoration {
     pub fn syntax(&self) -> Option<&Syntax> {
         self.syntax.as_ref()
@@ -70,15 +113,41 @@ impl BufferDecoration {
                             Target::Widget(self.tab_id),
                         );
                     }
+                    LocalBufferKind::Replace => {
+                        let _ = self.event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateSearchReplace(s),
+                            Target::Widget(self.tab_id),
+                        );
+                    }
+                    LocalBufferKind::SearchGlobs => {
+                        let _ = self.event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateSearchGlobs(s),
+                            Target::Widget(self.tab_id),
+                        );
+                    }
+                    LocalBufferKind::PluginFilter => {
+                        let _ = self.event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdatePluginFilter(s),
+                            Target::Widget(self.tab_id),
+                        );
+                    }
                     LocalBufferKind::SourceControl => {}
                     LocalBufferKind::Empty => {}
                     LocalBufferKind::FilePicker => {
-                        let pwd = PathBuf::from(s);
+                        let (pwd, filter) = split_picker_input(&s);
                         let _ = self.event_sink.submit_command(
                             LAPCE_UI_COMMAND,
                             LapceUICommand::UpdatePickerPwd(pwd),
                             Target::Widget(self.tab_id),
                         );
+                        let _ = self.event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdatePickerFilter(filter.to_string()),
+                            Target::Widget(self.tab_id),
+                        );
                     }
                     LocalBufferKind::Keymap => {
                         let _ = self.event_sink.submit_command(
@@ -178,3 +247,35 @@ impl BufferDecoration {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use xi_rope::spans::SpansBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_pending_styles() {
+        let syntax = Syntax::init(Path::new("main.rs")).unwrap();
+        let styles = Arc::new(SpansBuilder::new(0).build());
+
+        let mut pending = PendingStyles::default();
+        pending.update_syntax(2, syntax);
+        pending.update_semantic_styles(1, styles.clone());
+        pending.update_semantic_styles(3, styles.clone());
+        // An older update arriving late doesn't replace a newer one.
+        pending.update_semantic_styles(2, styles.clone());
+        let (syntax, semantic_styles) = pending.take(3);
+        assert!(syntax.is_none());
+        assert!(semantic_styles.is_some());
+
+        let mut pending = PendingStyles::default();
+        pending.update_syntax(3, Syntax::init(Path::new("main.rs")).unwrap());
+        pending.update_semantic_styles(3, styles);
+        let (syntax, semantic_styles) = pending.take(3);
+        assert!(syntax.is_some());
+        assert!(semantic_styles.is_some());
+    }
+}
@@ -0,0 +1,250 @@
+//! The folded regions of a buffer and the mapping between the lines of the
+//! buffer and the visual lines the editor draws once they're folded away.
+//!
+//! A folded region keeps its first line visible and hides the lines after it
+//! up to and including its last line. Regions are kept as offsets, so they
+//! move along with the edits around them.
+
+use std::ops::Range;
+
+use xi_rope::{Rope, RopeDelta, Transformer};
+
+/// The offset of the end of the content of `line`, before its line break.
+fn line_end(text: &Rope, line: usize) -> usize {
+    let last_line = text.line_of_offset(text.len());
+    if line < last_line {
+        text.offset_of_line(line + 1) - 1
+    } else {
+        text.len()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Folds {
+    /// The offsets of the ends of the first and the last line of each region
+    regions: Vec<(usize, usize)>,
+}
+
+impl Folds {
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Folds away the lines after `start_line` up to `end_line`.
+    pub fn fold(&mut self, text: &Rope, start_line: usize, end_line: usize) {
+        let last_line = text.line_of_offset(text.len());
+        if end_line <= start_line || end_line > last_line {
+            return;
+        }
+        let region = (line_end(text, start_line), line_end(text, end_line));
+        if !self.regions.contains(&region) {
+            self.regions.push(region);
+            self.regions.sort_unstable();
+        }
+    }
+
+    /// Unfolds the regions that start at or hide `line`, returning whether
+    /// there were any.
+    pub fn unfold(&mut self, text: &Rope, line: usize) -> bool {
+        let len = self.regions.len();
+        self.regions.retain(|(start, end)| {
+            line < text.line_of_offset(*start) || line > text.line_of_offset(*end)
+        });
+        self.regions.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// The first and last line of each folded region.
+    pub fn lines(&self, text: &Rope) -> Vec<(usize, usize)> {
+        self.regions
+            .iter()
+            .map(|(start, end)| {
+                (text.line_of_offset(*start), text.line_of_offset(*end))
+            })
+            .collect()
+    }
+
+    /// Whether a folded region starts at `line`.
+    pub fn is_folded(&self, text: &Rope, line: usize) -> bool {
+        self.regions
+            .iter()
+            .any(|(start, _)| text.line_of_offset(*start) == line)
+    }
+
+    /// The ranges of hidden lines, in order and without overlaps.
+    pub fn hidden_lines(&self, text: &Rope) -> Vec<Range<usize>> {
+        let mut hidden: Vec<Range<usize>> = Vec::new();
+        for (start, end) in self.lines(text) {
+            if end <= start {
+                continue;
+            }
+            let range = start + 1..end + 1;
+            match hidden.last_mut() {
+                Some(last) if range.start <= last.end => {
+                    last.end = last.end.max(range.end);
+                }
+                _ => hidden.push(range),
+            }
+        }
+        hidden
+    }
+
+    /// Moves the regions along with an edit. A region is unfolded when the
+    /// edit touches the lines it hides.
+    pub fn apply_delta(&mut self, delta: &RopeDelta) {
+        if self.regions.is_empty() {
+            return;
+        }
+        let (interval, _) = delta.summary();
+        self.regions.retain(|(start, end)| {
+            interval.end() <= *start || interval.start() > *end
+        });
+        let mut transformer = Transformer::new(delta);
+        for (start, end) in self.regions.iter_mut() {
+            *start = transformer.transform(*start, true);
+            *end = transformer.transform(*end, true);
+        }
+    }
+}
+
+/// The hidden lines of a buffer, worked out once to map the many lines a
+/// paint goes through to the visual lines they're drawn on and back.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VisualLines {
+    hidden: Vec<Range<usize>>,
+}
+
+impl VisualLines {
+    pub fn new(hidden: Vec<Range<usize>>) -> Self {
+        Self { hidden }
+    }
+
+    pub fn visual_line(&self, line: usize) -> usize {
+        visual_line(&self.hidden, line)
+    }
+
+    pub fn actual_line(&self, visual_line: usize) -> usize {
+        actual_line(&self.hidden, visual_line)
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.hidden.iter().any(|range| range.contains(&line))
+    }
+
+    /// The number of lines hidden.
+    pub fn hidden_len(&self) -> usize {
+        self.hidden.iter().map(|range| range.len()).sum()
+    }
+}
+
+/// The visual line `line` is drawn on, which for a hidden line is the one of
+/// the line its region is folded under.
+pub fn visual_line(hidden: &[Range<usize>], line: usize) -> usize {
+    let mut hidden_before = 0;
+    for range in hidden {
+        if range.end <= line {
+            hidden_before += range.len();
+        } else if range.start <= line {
+            return range.start - 1 - hidden_before;
+        } else {
+            break;
+        }
+    }
+    line - hidden_before
+}
+
+/// The line drawn on `visual_line`.
+pub fn actual_line(hidden: &[Range<usize>], visual_line: usize) -> usize {
+    let mut line = visual_line;
+    for range in hidden {
+        if range.start <= line {
+            line += range.len();
+        } else {
+            break;
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use xi_rope::{Delta, Interval};
+
+    use super::*;
+
+    fn text() -> Rope {
+        Rope::from("fn a() {\n    1\n    2\n}\nfn b() {\n    3\n}\n")
+    }
+
+    #[test]
+    fn test_fold_hides_lines() {
+        let text = text();
+        let mut folds = Folds::default();
+        folds.fold(&text, 0, 2);
+        folds.fold(&text, 4, 5);
+        assert_eq!(vec![(0, 2), (4, 5)], folds.lines(&text));
+        assert_eq!(vec![1..3, 5..6], folds.hidden_lines(&text));
+        assert!(folds.is_folded(&text, 4));
+        assert!(!folds.is_folded(&text, 5));
+
+        // Nested regions hide the lines of the outer one.
+        folds.fold(&text, 1, 2);
+        assert_eq!(vec![1..3, 5..6], folds.hidden_lines(&text));
+
+        assert!(folds.unfold(&text, 5));
+        assert!(!folds.unfold(&text, 6));
+        assert_eq!(vec![1..3], folds.hidden_lines(&text));
+    }
+
+    #[test]
+    fn test_visual_line() {
+        let hidden = vec![1..3, 5..6];
+        let lines: Vec<usize> = (0..8).map(|l| visual_line(&hidden, l)).collect();
+        assert_eq!(vec![0, 0, 0, 1, 2, 2, 3, 4], lines);
+        let lines: Vec<usize> = (0..5).map(|l| actual_line(&hidden, l)).collect();
+        assert_eq!(vec![0, 3, 4, 6, 7], lines);
+
+        let visual_lines = VisualLines::new(hidden);
+        assert_eq!(2, visual_lines.visual_line(5));
+        assert_eq!(6, visual_lines.actual_line(3));
+        assert!(visual_lines.is_hidden(2));
+        assert!(!visual_lines.is_hidden(3));
+        assert_eq!(3, visual_lines.hidden_len());
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let text = text();
+        let mut folds = Folds::default();
+        folds.fold(&text, 0, 2);
+        folds.fold(&text, 4, 5);
+
+        // Typing at the end of a first line keeps its region folded.
+        let delta =
+            Delta::simple_edit(Interval::new(8, 8), Rope::from(" "), text.len());
+        let text = delta.apply(&text);
+        folds.apply_delta(&delta);
+        assert_eq!(vec![(0, 2), (4, 5)], folds.lines(&text));
+
+        // A new line above moves the regions down.
+        let delta =
+            Delta::simple_edit(Interval::new(0, 0), Rope::from("\n"), text.len());
+        let text = delta.apply(&text);
+        folds.apply_delta(&delta);
+        assert_eq!(vec![(1, 3), (5, 6)], folds.lines(&text));
+
+        // Editing a hidden line unfolds its region.
+        let offset = text.offset_of_line(6);
+        let delta = Delta::simple_edit(
+            Interval::new(offset, offset + 1),
+            Rope::from(""),
+            text.len(),
+        );
+        let text = delta.apply(&text);
+        folds.apply_delta(&delta);
+        assert_eq!(vec![(1, 3)], folds.lines(&text));
+    }
+}
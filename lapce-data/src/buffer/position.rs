@@ -0,0 +1,209 @@
+//! Conversions between the ways a location in a buffer can be addressed:
+//!
+//! - a byte offset into the UTF-8 rope;
+//! - a `(line, col)` pair, where `col` is the visual column the editor draws
+//!   at: a tab advances to the next tab stop and wide characters, such as CJK
+//!   or emoji, take two columns;
+//! - an LSP [`Position`], whose `character` counts UTF-16 code units from the
+//!   start of the line, as the protocol requires.
+//!
+//! Columns never point into a line ending, so `\r\n` is treated as a single
+//! break and a column past the end of a line resolves to the end of its
+//! content.
+
+use lsp_types::Position;
+use xi_rope::Rope;
+
+use crate::buffer::{char_width, str_col};
+
+/// Returns the offsets of the start and end of the content of `line`, not
+/// including its line ending.
+fn line_content_range(rope: &Rope, line: usize) -> (usize, usize) {
+    let start = rope.offset_of_line(line);
+    let mut end = rope.offset_of_line(line + 1);
+    let content = rope.slice_to_cow(start..end);
+    if content.ends_with("\r\n") {
+        end -= 2;
+    } else if content.ends_with('\n') {
+        end -= 1;
+    }
+    (start, end)
+}
+
+/// Converts a byte offset to a `(line, col)` pair, expanding tabs to
+/// `tab_width`.
+pub fn offset_to_line_col(
+    rope: &Rope,
+    offset: usize,
+    tab_width: usize,
+) -> (usize, usize) {
+    let offset = offset.min(rope.len());
+    let line = rope.line_of_offset(offset);
+    let (start, end) = line_content_range(rope, line);
+    let col = str_col(&rope.slice_to_cow(start..offset.min(end)), tab_width);
+    (line, col)
+}
+
+/// Converts a `(line, col)` pair to a byte offset. A column that falls inside
+/// a tab or a wide character resolves to the start of that character.
+pub fn line_col_to_offset(
+    rope: &Rope,
+    line: usize,
+    col: usize,
+    tab_width: usize,
+) -> usize {
+    let last_line = rope.line_of_offset(rope.len());
+    if line > last_line {
+        return rope.len();
+    }
+    let (start, end) = line_content_range(rope, line);
+    let mut width = 0;
+    let mut offset = start;
+    for c in rope.slice_to_cow(start..end).chars() {
+        let char_width = if c == '\t' {
+            tab_width - width % tab_width
+        } else {
+            char_width(c)
+        };
+        if width + char_width > col {
+            break;
+        }
+        width += char_width;
+        offset += c.len_utf8();
+    }
+    offset
+}
+
+/// Converts a byte offset to an LSP position.
+pub fn offset_to_position(rope: &Rope, offset: usize) -> Position {
+    let offset = offset.min(rope.len());
+    let line = rope.line_of_offset(offset);
+    let (start, end) = line_content_range(rope, line);
+    let character = rope
+        .slice_to_cow(start..offset.min(end))
+        .chars()
+        .map(char::len_utf16)
+        .sum::<usize>();
+    Position {
+        line: line as u32,
+        character: character as u32,
+    }
+}
+
+/// Converts an LSP position to a byte offset. A `character` that falls
+/// between the two halves of a surrogate pair resolves to the start of that
+/// character.
+pub fn position_to_offset(rope: &Rope, position: &Position) -> usize {
+    let line = position.line as usize;
+    let last_line = rope.line_of_offset(rope.len());
+    if line > last_line {
+        return rope.len();
+    }
+    let (start, end) = line_content_range(rope, line);
+    let character = position.character as usize;
+    let mut units = 0;
+    let mut offset = start;
+    for c in rope.slice_to_cow(start..end).chars() {
+        let len = c.len_utf16();
+        if units + len > character {
+            break;
+        }
+        units += len;
+        offset += c.len_utf8();
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn test_position_emoji() {
+        let rope = Rope::from("a😀b\nc");
+        assert_eq!(position(0, 0), offset_to_position(&rope, 0));
+        assert_eq!(position(0, 1), offset_to_position(&rope, 1));
+        assert_eq!(position(0, 3), offset_to_position(&rope, 5));
+        assert_eq!(position(0, 4), offset_to_position(&rope, 6));
+        assert_eq!(position(1, 0), offset_to_position(&rope, 7));
+
+        assert_eq!(5, position_to_offset(&rope, &position(0, 3)));
+        // Inside the surrogate pair of the emoji.
+        assert_eq!(1, position_to_offset(&rope, &position(0, 2)));
+        // Past the end of the line.
+        assert_eq!(6, position_to_offset(&rope, &position(0, 10)));
+        assert_eq!(8, position_to_offset(&rope, &position(1, 1)));
+        assert_eq!(8, position_to_offset(&rope, &position(5, 0)));
+    }
+
+    #[test]
+    fn test_position_cjk() {
+        let rope = Rope::from("中文abc");
+        assert_eq!(position(0, 2), offset_to_position(&rope, 6));
+        assert_eq!(6, position_to_offset(&rope, &position(0, 2)));
+
+        assert_eq!((0, 4), offset_to_line_col(&rope, 6, 4));
+        assert_eq!(6, line_col_to_offset(&rope, 0, 4, 4));
+        // Inside the second wide character.
+        assert_eq!(3, line_col_to_offset(&rope, 0, 3, 4));
+        assert_eq!(7, line_col_to_offset(&rope, 0, 5, 4));
+    }
+
+    #[test]
+    fn test_line_col_tabs() {
+        let rope = Rope::from("\tab\t1");
+        assert_eq!((0, 4), offset_to_line_col(&rope, 1, 4));
+        assert_eq!((0, 6), offset_to_line_col(&rope, 3, 4));
+        assert_eq!((0, 8), offset_to_line_col(&rope, 4, 4));
+        assert_eq!((0, 9), offset_to_line_col(&rope, 5, 4));
+
+        // Inside the first tab.
+        assert_eq!(0, line_col_to_offset(&rope, 0, 2, 4));
+        assert_eq!(1, line_col_to_offset(&rope, 0, 4, 4));
+        // Inside the second tab, which only spans to the next tab stop.
+        assert_eq!(3, line_col_to_offset(&rope, 0, 7, 4));
+        assert_eq!(4, line_col_to_offset(&rope, 0, 8, 4));
+
+        // LSP positions count a tab as a single character.
+        assert_eq!(position(0, 4), offset_to_position(&rope, 4));
+    }
+
+    #[test]
+    fn test_crlf() {
+        let rope = Rope::from("ab\r\ncd\r\n");
+        // Between the `\r` and the `\n`.
+        assert_eq!(position(0, 2), offset_to_position(&rope, 3));
+        assert_eq!(position(1, 0), offset_to_position(&rope, 4));
+        assert_eq!((0, 2), offset_to_line_col(&rope, 3, 4));
+
+        assert_eq!(2, position_to_offset(&rope, &position(0, 5)));
+        assert_eq!(6, position_to_offset(&rope, &position(1, 2)));
+        assert_eq!(8, position_to_offset(&rope, &position(2, 0)));
+        assert_eq!(2, line_col_to_offset(&rope, 0, 10, 4));
+        assert_eq!((2, 0), offset_to_line_col(&rope, 8, 4));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "fn 中文() {\r\n\t😀 = \"é\";\n}\n";
+        let rope = Rope::from(text);
+        let offsets = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .filter(|i| {
+                !(text[..*i].ends_with('\r') && text[*i..].starts_with('\n'))
+            });
+        for offset in offsets {
+            let position = offset_to_position(&rope, offset);
+            assert_eq!(offset, position_to_offset(&rope, &position));
+
+            let (line, col) = offset_to_line_col(&rope, offset, 4);
+            assert_eq!(offset, line_col_to_offset(&rope, line, col, 4));
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use lsp_types::{CodeActionKind, CodeActionOrCommand};
+
+/// The groups code actions are listed in, in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CodeActionGroup {
+    QuickFix,
+    Refactor,
+    Source,
+    Other,
+}
+
+impl CodeActionGroup {
+    /// The group of an action, from the prefix of its kind.
+    pub fn of(action: &CodeActionOrCommand) -> Self {
+        let kind = match action {
+            CodeActionOrCommand::CodeAction(action) => action.kind.as_ref(),
+            CodeActionOrCommand::Command(_) => None,
+        };
+        let kind = match kind {
+            Some(kind) => kind.as_str(),
+            None => return CodeActionGroup::Other,
+        };
+        let is = |group: CodeActionKind| {
+            let group = group.as_str();
+            kind == group
+                || kind
+                    .strip_prefix(group)
+                    .map(|rest| rest.starts_with('.'))
+                    .unwrap_or(false)
+        };
+        if is(CodeActionKind::QUICKFIX) {
+            CodeActionGroup::QuickFix
+        } else if is(CodeActionKind::REFACTOR) {
+            CodeActionGroup::Refactor
+        } else if is(CodeActionKind::SOURCE) {
+            CodeActionGroup::Source
+        } else {
+            CodeActionGroup::Other
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            CodeActionGroup::QuickFix => "Quick Fix",
+            CodeActionGroup::Refactor => "Refactor",
+            CodeActionGroup::Source => "Source Action",
+            CodeActionGroup::Other => "Other",
+        }
+    }
+}
+
+pub fn is_preferred(action: &CodeActionOrCommand) -> bool {
+    match action {
+        CodeActionOrCommand::CodeAction(action) => {
+            action.is_preferred.unwrap_or(false)
+        }
+        CodeActionOrCommand::Command(_) => false,
+    }
+}
+
+/// Sorts code actions by their group, preferred ones first in each, keeping
+/// the order the language server gave them in otherwise.
+pub fn sort_code_actions(actions: &mut [CodeActionOrCommand]) {
+    actions
+        .sort_by_key(|action| (CodeActionGroup::of(action), !is_preferred(action)));
+}
+
+/// A row of the code action list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CodeActionRow {
+    Header(CodeActionGroup),
+    /// The action at this index of the sorted actions
+    Action(usize),
+}
+
+/// The rows listing sorted code actions, with a header starting each group.
+pub fn code_action_rows(actions: &[CodeActionOrCommand]) -> Vec<CodeActionRow> {
+    let mut rows = Vec::new();
+    let mut group = None;
+    for (i, action) in actions.iter().enumerate() {
+        let action_group = CodeActionGroup::of(action);
+        if group != Some(action_group) {
+            group = Some(action_group);
+            rows.push(CodeActionRow::Header(action_group));
+        }
+        rows.push(CodeActionRow::Action(i));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{CodeAction, Command};
+
+    use super::*;
+
+    fn action(
+        title: &str,
+        kind: Option<&str>,
+        preferred: bool,
+    ) -> CodeActionOrCommand {
+        let action: CodeAction = serde_json::from_value(serde_json::json!({
+            "title": title,
+            "kind": kind,
+            "isPreferred": preferred,
+        }))
+        .unwrap();
+        CodeActionOrCommand::CodeAction(action)
+    }
+
+    fn title(action: &CodeActionOrCommand) -> &str {
+        match action {
+            CodeActionOrCommand::CodeAction(action) => &action.title,
+            CodeActionOrCommand::Command(command) => &command.title,
+        }
+    }
+
+    #[test]
+    fn test_code_action_group() {
+        let group = |kind| CodeActionGroup::of(&action("", Some(kind), false));
+        assert_eq!(CodeActionGroup::QuickFix, group("quickfix"));
+        assert_eq!(CodeActionGroup::Refactor, group("refactor.extract"));
+        assert_eq!(CodeActionGroup::Source, group("source.organizeImports"));
+        // Only whole parts of the kind count.
+        assert_eq!(CodeActionGroup::Other, group("refactoring"));
+        assert_eq!(CodeActionGroup::Other, group(""));
+        let command = CodeActionOrCommand::Command(Command::new(
+            "Run".to_string(),
+            "run".to_string(),
+            None,
+        ));
+        assert_eq!(CodeActionGroup::Other, CodeActionGroup::of(&command));
+    }
+
+    #[test]
+    fn test_sort_code_actions_and_rows() {
+        let mut actions = vec![
+            action("Extract into function", Some("refactor.extract"), false),
+            action("Organize imports", Some("source.organizeImports"), false),
+            action("Add missing field", Some("quickfix"), false),
+            action("Inline variable", Some("refactor.inline"), false),
+            action("Import `HashMap`", Some("quickfix"), true),
+        ];
+        sort_code_actions(&mut actions);
+        assert_eq!(
+            vec![
+                "Import `HashMap`",
+                "Add missing field",
+                "Extract into function",
+                "Inline variable",
+                "Organize imports",
+            ],
+            actions.iter().map(title).collect::<Vec<&str>>()
+        );
+
+        assert_eq!(
+            vec![
+                CodeActionRow::Header(CodeActionGroup::QuickFix),
+                CodeActionRow::Action(0),
+                CodeActionRow::Action(1),
+                CodeActionRow::Header(CodeActionGroup::Refactor),
+                CodeActionRow::Action(2),
+                CodeActionRow::Action(3),
+                CodeActionRow::Header(CodeActionGroup::Source),
+                CodeActionRow::Action(4),
+            ],
+            code_action_rows(&actions)
+        );
+    }
+}
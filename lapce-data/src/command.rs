@@ -5,12 +5,18 @@ use druid::{Point, Rect, Selector, Size, WidgetId, WindowId};
 use indexmap::IndexMap;
 use lapce_core::syntax::Syntax;
 use lapce_rpc::{
-    buffer::BufferId, file::FileNodeItem, plugin::PluginDescription,
-    source_control::DiffInfo, style::Style, terminal::TermId,
+    buffer::{BufferId, FileEncoding, SaveErrorKind},
+    file::{FileChangeKind, FileNodeItem, FileOperation, GlobalReplaceResponse},
+    plugin::PluginDescription,
+    source_control::DiffInfo,
+    style::Style,
+    terminal::TermId,
 };
 use lsp_types::{
-    CodeActionResponse, CompletionItem, CompletionResponse, Hover, Location,
-    Position, ProgressParams, PublishDiagnosticsParams, TextEdit,
+    CallHierarchyItem, CodeAction, CodeActionResponse, CompletionItem,
+    CompletionResponse, DocumentSymbol, FoldingRange, Hover, Location, Position,
+    ProgressParams, PublishDiagnosticsParams, Range, SignatureHelp,
+    SymbolInformation, TextEdit, WorkspaceEdit,
 };
 use serde_json::Value;
 use strum::{self, EnumMessage, IntoEnumIterator};
@@ -18,15 +24,19 @@ use strum_macros::{Display, EnumIter, EnumMessage, EnumString};
 use xi_rope::{spans::Spans, Rope};
 
 use crate::{
-    buffer::DiffLines,
-    data::{EditorTabChild, MotionMode, SplitContent},
+    buffer::{DiffLines, LanguageOverride},
+    data::{
+        EditorTabChild, MotionMode, PanelKind, SplitContent, WorkspaceEditStep,
+        WorkspaceEditSummary,
+    },
     editor::EditorLocationNew,
+    hierarchy::{HierarchyChild, HierarchyKind},
     keypress::{KeyMap, KeyPress},
     menu::MenuItem,
     movement::{LinePosition, Movement},
     palette::{NewPaletteItem, PaletteType},
     proxy::ProxyStatus,
-    search::Match,
+    search::{ContextLine, Match},
     split::{SplitDirection, SplitMoveDirection},
     state::LapceWorkspace,
 };
@@ -110,10 +120,63 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Open File")]
     OpenFile,
 
+    #[strum(serialize = "reopen_closed_tab")]
+    #[strum(message = "Reopen Closed Tab")]
+    ReopenClosedTab,
+
+    #[strum(serialize = "close_other_tabs")]
+    #[strum(message = "Close Other Tabs")]
+    CloseOtherTabs,
+
+    #[strum(serialize = "close_tabs_to_right")]
+    #[strum(message = "Close Tabs to the Right")]
+    CloseTabsToRight,
+
+    #[strum(serialize = "reveal_active_file_in_file_explorer")]
+    #[strum(message = "Reveal Active File in File Explorer")]
+    RevealActiveFileInFileExplorer,
+
     #[strum(serialize = "change_theme")]
     #[strum(message = "Change Theme")]
     ChangeTheme,
 
+    #[strum(serialize = "change_language_mode")]
+    #[strum(message = "Change Language Mode")]
+    ChangeLanguageMode,
+
+    /// Reads the file in the active editor again in the encoding passed as
+    /// the data of the command
+    #[strum(serialize = "reopen_with_encoding")]
+    ReopenWithEncoding,
+
+    #[strum(serialize = "change_line_endings_to_lf")]
+    #[strum(message = "Change Line Endings to LF")]
+    ChangeLineEndingsToLf,
+
+    #[strum(serialize = "change_line_endings_to_crlf")]
+    #[strum(message = "Change Line Endings to CRLF")]
+    ChangeLineEndingsToCrlf,
+
+    /// Indents the active file with as many spaces as passed.
+    #[strum(serialize = "indent_using_spaces")]
+    IndentUsingSpaces,
+
+    #[strum(serialize = "indent_using_tabs")]
+    #[strum(message = "Indent Using Tabs")]
+    IndentUsingTabs,
+
+    /// Shows tabs as wide as the columns passed, in the active file.
+    #[strum(serialize = "set_tab_width")]
+    SetTabWidth,
+
+    #[strum(serialize = "convert_indentation_to_spaces")]
+    #[strum(message = "Convert Indentation to Spaces")]
+    ConvertIndentationToSpaces,
+
+    #[strum(serialize = "convert_indentation_to_tabs")]
+    #[strum(message = "Convert Indentation to Tabs")]
+    ConvertIndentationToTabs,
+
     #[strum(serialize = "open_settings")]
     #[strum(message = "Open Settings")]
     OpenSettings,
@@ -150,6 +213,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Go To Previous Tab")]
     PreviousTab,
 
+    #[strum(serialize = "clear_recent_menu_items")]
+    #[strum(message = "Clear Recently Used Menu Items")]
+    ClearRecentMenuItems,
+
     #[strum(serialize = "reload_window")]
     #[strum(message = "Reload Window")]
     ReloadWindow,
@@ -162,6 +229,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Connect to SSH Host")]
     ConnectSshHost,
 
+    #[strum(serialize = "switch_ssh_host")]
+    #[strum(message = "Switch SSH Host")]
+    SwitchSshHost,
+
     #[strum(serialize = "connect_wsl")]
     #[strum(message = "Connect to WSL")]
     ConnectWsl,
@@ -170,6 +241,23 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Disconnect From Remote")]
     DisconnectRemote,
 
+    #[strum(serialize = "reconnect_remote")]
+    #[strum(message = "Reconnect to Remote")]
+    ReconnectRemote,
+
+    #[strum(serialize = "next_problem")]
+    #[strum(message = "Go to Next Problem in Workspace")]
+    NextProblem,
+
+    #[strum(serialize = "copy_diagnostic")]
+    #[strum(message = "Copy Diagnostic Message")]
+    CopyDiagnostic,
+
+    /// Jumps to the position passed in the editor whose raw id is passed
+    /// along with it.
+    #[strum(serialize = "jump_to_symbol")]
+    JumpToSymbol,
+
     #[strum(serialize = "palette.line")]
     PaletteLine,
 
@@ -179,6 +267,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "palette.symbol")]
     PaletteSymbol,
 
+    #[strum(message = "Go to Symbol in Workspace")]
+    #[strum(serialize = "palette.workspace_symbol")]
+    PaletteWorkspaceSymbol,
+
     #[strum(message = "Command Palette")]
     #[strum(serialize = "palette.command")]
     PaletteCommand,
@@ -187,6 +279,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "palette.workspace")]
     PaletteWorkspace,
 
+    #[strum(message = "Remove Stale Recent Workspaces")]
+    #[strum(serialize = "remove_stale_recent_workspaces")]
+    RemoveStaleRecentWorkspaces,
+
     #[strum(serialize = "source_control.checkout_branch")]
     CheckoutBranch,
 
@@ -223,6 +319,15 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_problem_focus")]
     ToggleProblemFocus,
 
+    #[strum(serialize = "toggle_outline_focus")]
+    ToggleOutlineFocus,
+
+    #[strum(serialize = "toggle_call_hierarchy_focus")]
+    ToggleCallHierarchyFocus,
+
+    #[strum(serialize = "toggle_type_hierarchy_focus")]
+    ToggleTypeHierarchyFocus,
+
     #[strum(serialize = "toggle_search_focus")]
     ToggleSearchFocus,
 
@@ -242,17 +347,79 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_problem_visual")]
     ToggleProblemVisual,
 
+    #[strum(serialize = "toggle_outline_visual")]
+    ToggleOutlineVisual,
+
+    #[strum(serialize = "toggle_call_hierarchy_visual")]
+    ToggleCallHierarchyVisual,
+
+    #[strum(serialize = "toggle_type_hierarchy_visual")]
+    ToggleTypeHierarchyVisual,
+
     #[strum(serialize = "toggle_search_visual")]
     ToggleSearchVisual,
 
+    #[strum(message = "Toggle Regex in Global Search")]
+    #[strum(serialize = "toggle_search_regex")]
+    ToggleSearchRegex,
+
+    #[strum(message = "Toggle Whole Word in Global Search")]
+    #[strum(serialize = "toggle_search_whole_word")]
+    ToggleSearchWholeWord,
+
     #[strum(serialize = "focus_editor")]
     FocusEditor,
 
+    #[strum(serialize = "save_all_and_close_tab")]
+    SaveAllAndCloseTab,
+
+    #[strum(serialize = "close_tab_without_saving")]
+    CloseTabWithoutSaving,
+
+    /// Keeps the tab open, and opens the file given as the data if there is
+    /// one
+    #[strum(serialize = "cancel_close_tab")]
+    CancelCloseTab,
+
+    /// Saves the unsaved files, then opens the workspace on the SSH user and
+    /// host given as the data
+    #[strum(serialize = "save_all_and_switch_ssh_host")]
+    SaveAllAndSwitchSshHost,
+
+    #[strum(serialize = "switch_ssh_host_without_saving")]
+    SwitchSshHostWithoutSaving,
+
+    #[strum(serialize = "cancel_switch_ssh_host")]
+    CancelSwitchSshHost,
+
+    /// Saves the unsaved files of the editor views given as the data, then
+    /// closes them
+    #[strum(serialize = "save_and_close_editors")]
+    SaveAndCloseEditors,
+
+    #[strum(serialize = "close_editors_without_saving")]
+    CloseEditorsWithoutSaving,
+
+    #[strum(serialize = "cancel_close_editors")]
+    CancelCloseEditors,
+
     #[strum(serialize = "focus_terminal")]
     FocusTerminal,
 
     #[strum(serialize = "source_control_commit")]
     SourceControlCommit,
+
+    /// Saves the file passed in parameter through the configured sudo helper.
+    #[strum(serialize = "save_elevated")]
+    SaveElevated,
+
+    /// Saves the file passed in parameter to a new path.
+    #[strum(serialize = "save_as")]
+    SaveAs,
+
+    /// Makes the file passed in parameter writable and saves over it.
+    #[strum(serialize = "save_overwrite_permissions")]
+    SaveOverwritePermissions,
 }
 
 #[derive(Display, EnumString, EnumIter, Clone, PartialEq, Debug, EnumMessage)]
@@ -277,6 +444,9 @@ pub enum LapceCommand {
     SelectNextCurrent,
     #[strum(serialize = "select_skip_current")]
     SelectSkipCurrent,
+    #[strum(message = "Swap Selections")]
+    #[strum(serialize = "swap_selections")]
+    SwapSelections,
     #[strum(serialize = "file_explorer")]
     FileExplorer,
     #[strum(serialize = "file_explorer.cancel")]
@@ -441,12 +611,51 @@ pub enum LapceCommand {
     #[strum(serialize = "toggle_code_lens")]
     ToggleCodeLens,
 
+    #[strum(message = "Fold")]
+    #[strum(serialize = "fold")]
+    Fold,
+    #[strum(message = "Unfold")]
+    #[strum(serialize = "unfold")]
+    Unfold,
+    #[strum(message = "Fold All")]
+    #[strum(serialize = "fold_all")]
+    FoldAll,
+    #[strum(message = "Unfold All")]
+    #[strum(serialize = "unfold_all")]
+    UnfoldAll,
+
     #[strum(serialize = "center_of_window")]
     CenterOfWindow,
 
     #[strum(message = "Go to Definition")]
     #[strum(serialize = "goto_definition")]
     GotoDefinition,
+    #[strum(message = "Peek Definition")]
+    #[strum(serialize = "peek_definition")]
+    PeekDefinition,
+    #[strum(message = "Go to Type Definition")]
+    #[strum(serialize = "goto_type_definition")]
+    GotoTypeDefinition,
+    #[strum(message = "Go to Implementation")]
+    #[strum(serialize = "goto_implementation")]
+    GotoImplementation,
+
+    #[strum(message = "Show Hover")]
+    #[strum(serialize = "show_hover")]
+    ShowHover,
+
+    #[strum(message = "Show Incoming Calls")]
+    #[strum(serialize = "show_incoming_calls")]
+    ShowIncomingCalls,
+    #[strum(message = "Show Outgoing Calls")]
+    #[strum(serialize = "show_outgoing_calls")]
+    ShowOutgoingCalls,
+    #[strum(message = "Show Supertypes")]
+    #[strum(serialize = "show_supertypes")]
+    ShowSupertypes,
+    #[strum(message = "Show Subtypes")]
+    #[strum(serialize = "show_subtypes")]
+    ShowSubtypes,
 
     #[strum(serialize = "jump_location_backward")]
     JumpLocationBackward,
@@ -456,15 +665,33 @@ pub enum LapceCommand {
     NextError,
     #[strum(serialize = "previous_error")]
     PreviousError,
+    #[strum(message = "Go to Next Diagnostic")]
+    #[strum(serialize = "next_diagnostic")]
+    NextDiagnostic,
+    #[strum(message = "Go to Previous Diagnostic")]
+    #[strum(serialize = "previous_diagnostic")]
+    PreviousDiagnostic,
     #[strum(message = "Go to Next Difference")]
     #[strum(serialize = "next_diff")]
     NextDiff,
     #[strum(message = "Go to Previous Difference")]
     #[strum(serialize = "previous_diff")]
     PreviousDiff,
+    #[strum(message = "Go to Next Whitespace Issue")]
+    #[strum(serialize = "next_whitespace_issue")]
+    NextWhitespaceIssue,
+    #[strum(message = "Fix Whitespace Issues")]
+    #[strum(serialize = "fix_whitespace_issues")]
+    FixWhitespaceIssues,
     #[strum(serialize = "format_document")]
     #[strum(message = "Format Document")]
     FormatDocument,
+    #[strum(serialize = "format_selection")]
+    #[strum(message = "Format Selection")]
+    FormatSelection,
+    #[strum(serialize = "rename_symbol")]
+    #[strum(message = "Rename Symbol")]
+    RenameSymbol,
     #[strum(message = "Save")]
     #[strum(serialize = "save")]
     Save,
@@ -475,9 +702,9 @@ pub enum LapceCommand {
     #[strum(serialize = "next_unmatched_right_bracket")]
     NextUnmatchedRightBracket,
     #[strum(serialize = "jump_to_next_snippet_placeholder")]
-    JumpToNextSnippetPlaceholder,
+    SnippetNextTabstop,
     #[strum(serialize = "jump_to_prev_snippet_placeholder")]
-    JumpToPrevSnippetPlaceholder,
+    SnippetPrevTabstop,
     #[strum(serialize = "previous_unmatched_left_bracket")]
     PreviousUnmatchedLeftBracket,
     #[strum(serialize = "next_unmatched_right_curly_bracket")]
@@ -500,6 +727,20 @@ pub enum LapceCommand {
     ClearSearch,
     #[strum(serialize = "search_in_view")]
     SearchInView,
+    #[strum(message = "Select Next Search Match")]
+    #[strum(serialize = "select_next_search_match")]
+    SelectNextSearchMatch,
+    #[strum(message = "Select Previous Search Match")]
+    #[strum(serialize = "select_previous_search_match")]
+    SelectPreviousSearchMatch,
+    #[strum(serialize = "file_picker_up")]
+    FilePickerUp,
+    #[strum(serialize = "file_picker_home")]
+    FilePickerHome,
+    #[strum(serialize = "recall_older_search_globs")]
+    RecallOlderSearchGlobs,
+    #[strum(serialize = "recall_newer_search_globs")]
+    RecallNewerSearchGlobs,
     Insert(String),
 }
 
@@ -568,6 +809,7 @@ pub enum LapceUICommand {
     LoadBuffer {
         path: PathBuf,
         content: String,
+        encoding: FileEncoding,
         locations: Vec<(WidgetId, EditorLocationNew)>,
     },
     LoadBufferHead {
@@ -583,19 +825,71 @@ pub enum LapceUICommand {
     },
     HideMenu,
     ShowMenu(Point, Arc<Vec<MenuItem>>),
+    /// Shows the items as the answers to a question, in the middle of the
+    /// window
+    ShowPrompt(Arc<Vec<MenuItem>>),
+    ClearRecentMenuItems,
     UpdateSearch(String),
-    GlobalSearchResult(String, Arc<HashMap<PathBuf, Vec<Match>>>),
+    GlobalSearchResult(
+        String,
+        Arc<HashMap<PathBuf, Vec<Match>>>,
+        Arc<HashMap<PathBuf, Vec<ContextLine>>>,
+    ),
+    UpdateSearchReplace(String),
+    UpdateSearchGlobs(String),
+    RememberSearchGlobs,
+    /// Puts an older (true) or newer recently used glob in the search's glob
+    /// input
+    RecallSearchGlobs(bool),
+    GlobalReplace(String, String),
+    GlobalReplaceResult(String, GlobalReplaceResponse),
     CancelFilePicker,
     SetWorkspace(LapceWorkspace),
+    /// Replaces the current workspace with `path` on another SSH host,
+    /// keeping the editor layout for the files under it
+    OpenRemoteWorkspace {
+        user: String,
+        host: String,
+        path: PathBuf,
+    },
+    /// Sets the window title from the active tab.
+    UpdateWindowTitle,
     SetTheme(String, bool),
+    /// Sets the language of the buffer in the active editor, or detects it
+    /// again when it's `None`.
+    SetLanguage(Option<LanguageOverride>),
+    /// Sets the encoding the file at the path was reopened with.
+    SetEncoding(PathBuf, FileEncoding),
     UpdateKeymap(KeyMap, Vec<KeyPress>),
     OpenFile(PathBuf),
+    /// Opens `path` with the cursor at the zero-based `line` and `column`.
+    OpenFileAt {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+    },
     OpenFileDiff(PathBuf, String),
     CancelCompletion(usize),
     ResolveCompletion(BufferId, u64, usize, Box<CompletionItem>),
     UpdateCompletion(usize, String, CompletionResponse),
     UpdateHover(usize, Hover),
+    UpdateSignature(usize, SignatureHelp),
     UpdateCodeActions(PathBuf, u64, usize, CodeActionResponse),
+    /// The occurrences of the symbol at the cursor offset they were asked for
+    UpdateDocumentHighlights(PathBuf, usize, Vec<Range>),
+    UpdateFoldingRanges(PathBuf, u64, Vec<FoldingRange>),
+    UpdateDocumentSymbols(PathBuf, u64, Vec<DocumentSymbol>),
+    /// More symbols found for the workspace symbol search with the request id
+    UpdateWorkspaceSymbols(usize, Vec<SymbolInformation>),
+    /// The items at the cursor to show the calls or types of, prepared in
+    /// the buffer
+    ShowHierarchy(BufferId, HierarchyKind, Result<Vec<CallHierarchyItem>>),
+    /// The children of the node at the path, in the hierarchy with the id
+    UpdateHierarchyChildren(HierarchyKind, u64, Vec<usize>, Vec<HierarchyChild>),
+    /// The code action of the file, as the language server resolved it
+    CodeActionResolved(PathBuf, Result<Value>),
+    /// Applies the edit of a code action of the file, then runs its command
+    RunCodeAction(PathBuf, Box<CodeAction>),
     CancelPalette,
     ShowCodeActions,
     CancelCodeActions,
@@ -604,11 +898,21 @@ pub enum LapceUICommand {
     Focus,
     EnsureEditorTabActiveVisble,
     FocusSourceControl,
+    /// Shows and focuses a panel, or hides it if it's visible
+    TogglePanel(PanelKind),
     ShowSettings,
     ShowKeybindings,
     FocusEditor,
     RunPalette(Option<PaletteType>),
     RunPaletteReferences(Vec<EditorLocationNew>),
+    RunPaletteCommands(Vec<LapceCommandNew>),
+    /// Asks for the new name of the symbol at `position` in `path`, starting
+    /// from `placeholder`
+    RunPaletteRename {
+        path: PathBuf,
+        position: Position,
+        placeholder: String,
+    },
     UpdatePaletteItems(String, Vec<NewPaletteItem>),
     FilterPaletteItems(String, String, Vec<NewPaletteItem>),
     UpdateKeymapsFilter(String),
@@ -616,9 +920,26 @@ pub enum LapceUICommand {
     UpdateSettingsFilter(String),
     FilterKeymaps(String, Arc<Vec<KeyMap>>, Arc<Vec<LapceCommandNew>>),
     UpdatePickerPwd(PathBuf),
+    UpdatePickerFilter(String),
+    OpenPickerMatch,
+    ToggleFilePickerSelection,
+    FilePickerUp,
+    FilePickerHome,
     UpdatePickerItems(PathBuf, HashMap<PathBuf, FileNodeItem>),
     UpdateExplorerItems(usize, PathBuf, Vec<FileNodeItem>),
+    /// The items read for several directories, and the directories that
+    /// couldn't be read
+    UpdateExplorerItemsBatch(Vec<(PathBuf, Vec<FileNodeItem>)>, Vec<PathBuf>),
+    FileChangeNotification(Vec<(PathBuf, FileChangeKind)>),
+    RevealInFileExplorer(PathBuf),
+    EnsureExplorerItemVisible(PathBuf),
     UpdateInstalledPlugins(HashMap<String, PluginDescription>),
+    UpdateOnTypeFormattingTriggers(String, Vec<String>),
+    UpdateCompletionTriggers(String, Vec<String>),
+    PluginUpdateAvailable(String, String),
+    UpdatePlugin(String),
+    UpdatePluginFilter(String),
+    PluginMarketplaceResults(String, Vec<PluginDescription>),
     UpdatePluginDescriptions(Vec<PluginDescription>),
     UpdateWindowOrigin,
     RequestLayout,
@@ -626,9 +947,13 @@ pub enum LapceUICommand {
     ResetFade,
     //FocusTab,
     CloseTab,
+    /// Closes the tab, asking first whether to save its unsaved files
     CloseTabId(WidgetId),
+    /// Closes the tab without asking
+    ForceCloseTabId(WidgetId),
     FocusTabId(WidgetId),
     SwapTab(usize),
+    ToggleMaximizeEditor,
     NewTab,
     NextTab,
     PreviousTab,
@@ -641,7 +966,27 @@ pub enum LapceUICommand {
     ApplyEditsAndSave(usize, u64, Result<Value>),
     DocumentFormat(PathBuf, u64, Result<Value>),
     DocumentFormatAndSave(PathBuf, u64, Result<Value>),
+    DocumentRangeFormat(PathBuf, u64, Range, Result<Value>),
+    DocumentOnTypeFormat(PathBuf, u64, Value),
+    PrepareRename(PathBuf, u64, usize, Result<Value>),
+    Rename(PathBuf, Position, String),
+    /// Applies edits spanning several files, opening the ones that aren't
+    /// loaded yet and saving them afterwards
+    /// Applies a workspace edit, saving the files edited with `save`
+    ApplyWorkspaceEdit {
+        edit: WorkspaceEdit,
+        save: bool,
+    },
+    /// Continues applying a workspace edit once the proxy made `operation`
+    ResumeWorkspaceEdit {
+        operation: FileOperation,
+        result: Result<(), String>,
+        steps: Vec<WorkspaceEditStep>,
+        summary: WorkspaceEditSummary,
+    },
     BufferSave(PathBuf, u64),
+    BufferSaveFailed(PathBuf, SaveErrorKind, String),
+    SaveAs(PathBuf, PathBuf),
     UpdateSemanticStyles(BufferId, PathBuf, u64, Arc<Spans<Style>>),
     UpdateTerminalTitle(TermId, String),
     UpdateHistoryStyle {
@@ -678,6 +1023,8 @@ pub enum LapceUICommand {
     ForceScrollTo(f64, f64),
     HomeDir(PathBuf),
     ProxyUpdateStatus(ProxyStatus),
+    ReconnectProxy,
+    ShowStatusMessage(String),
     CloseTerminal(TermId),
     SplitTerminal(bool, WidgetId),
     SplitTerminalClose(TermId, WidgetId),
@@ -695,14 +1042,31 @@ pub enum LapceUICommand {
     SplitChangeDirectoin(SplitDirection),
     EditorTabAdd(usize, EditorTabChild),
     EditorTabRemove(usize, bool, bool),
+    /// Closes the given editor views in an editor tab without asking about
+    /// unsaved changes.
+    EditorTabCloseChildren(Vec<WidgetId>),
+    /// Closes the tabs to the right of the given editor view.
+    CloseTabsToRight(WidgetId),
+    /// Closes every tab but the given editor view.
+    CloseOtherTabs(WidgetId),
+    ReopenClosedTab,
     EditorTabSwap(usize, usize),
+    /// Moves a panel in the activity bar to the index, counted before it's
+    /// taken out.
+    MovePanel(PanelKind, usize),
     JumpToPosition(Option<WidgetId>, Position),
     JumpToLine(Option<WidgetId>, usize),
     JumpToLocation(Option<WidgetId>, EditorLocationNew),
     TerminalJumpToLine(i32),
     GoToLocationNew(WidgetId, EditorLocationNew),
     GotoReference(WidgetId, usize, EditorLocationNew),
-    GotoDefinition(WidgetId, usize, EditorLocationNew),
+    /// The definitions, type definitions or implementations found at the
+    /// offset of the editor, gone to directly when there's only one
+    GotoDefinition(WidgetId, usize, Vec<Location>),
+    PeekDefinition(WidgetId, usize, EditorLocationNew),
+    /// Opens where the cursor of the peek is in the editor it was shown over
+    OpenPeek,
+    ClosePeek,
     PaletteReferences(usize, Vec<Location>),
     GotoLocation(Location),
     ActiveFileChanged {
@@ -5,13 +5,18 @@ use druid::{Command, EventCtx, ExtEventSink, Size, Target, WidgetId};
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use itertools::Itertools;
 use lapce_rpc::buffer::BufferId;
-use lsp_types::{CompletionItem, CompletionResponse, Position};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionItemTag, CompletionResponse,
+    CompletionTextEdit, InsertTextFormat, Position, Range,
+};
 use regex::Regex;
 use std::str::FromStr;
+use xi_rope::{RopeDelta, Transformer};
 
 use crate::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
-    movement::Movement,
+    config::CompletionInsertMode,
+    movement::{Movement, SelRegion, Selection},
     proxy::LapceProxy,
 };
 
@@ -134,6 +139,17 @@ impl Snippet {
         self.elements.iter().map(|e| e.text()).join("")
     }
 
+    /// The literal text before the first tabstop or placeholder
+    pub fn prefix(&self) -> String {
+        self.elements
+            .iter()
+            .map_while(|e| match e {
+                SnippetElement::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .join("")
+    }
+
     pub fn tabs(&self, pos: usize) -> Vec<(usize, (usize, usize))> {
         Self::elements_tabs(&self.elements, pos)
     }
@@ -217,7 +233,14 @@ impl SnippetElement {
 impl Display for SnippetElement {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            SnippetElement::Text(text) => f.write_str(text),
+            SnippetElement::Text(text) => {
+                // Escape what the parser would otherwise read as syntax.
+                let text = text
+                    .replace('\\', "\\\\")
+                    .replace('$', "\\$")
+                    .replace('}', "\\}");
+                f.write_str(&text)
+            }
             SnippetElement::PlaceHolder(tab, elements) => {
                 let elements = elements.iter().map(|e| e.to_string()).join("");
                 write!(f, "${{{}:{}}}", tab, elements)
@@ -227,12 +250,221 @@ impl Display for SnippetElement {
     }
 }
 
+/// The tabstops of an inserted snippet, kept up to date with edits so Tab
+/// and Shift+Tab can move between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnippetExpansion {
+    /// The ranges of each tabstop in the order they're visited, which is by
+    /// number with `$0` last. A tabstop used more than once has a range for
+    /// every use.
+    pub ranges: Vec<(usize, Vec<(usize, usize)>)>,
+    /// The index in `ranges` of the tabstop being filled in
+    pub active: usize,
+}
+
+impl SnippetExpansion {
+    /// Builds the expansion from the tabstops given by `Snippet::tabs`. A
+    /// snippet with nothing to visit before `$0` has no expansion.
+    pub fn new(tabs: Vec<(usize, (usize, usize))>) -> Option<Self> {
+        let ranges = Self::group(tabs);
+        if ranges.iter().all(|(tab, _)| *tab == 0) {
+            return None;
+        }
+        Some(Self { ranges, active: 0 })
+    }
+
+    fn group(
+        tabs: Vec<(usize, (usize, usize))>,
+    ) -> Vec<(usize, Vec<(usize, usize)>)> {
+        let mut ranges: Vec<(usize, Vec<(usize, usize)>)> = Vec::new();
+        for (tab, range) in tabs {
+            match ranges.iter_mut().find(|(t, _)| *t == tab) {
+                Some((_, tab_ranges)) => tab_ranges.push(range),
+                None => ranges.push((tab, vec![range])),
+            }
+        }
+        ranges.sort_by_key(|(tab, _)| if *tab == 0 { usize::MAX } else { *tab });
+        ranges
+    }
+
+    /// Replaces the active tabstop with the tabstops of a snippet inserted
+    /// into it. The nested `$0` is dropped so the outer snippet continues.
+    pub fn insert_nested(&mut self, tabs: Vec<(usize, (usize, usize))>) {
+        let nested: Vec<_> = Self::group(tabs)
+            .into_iter()
+            .filter(|(tab, _)| *tab != 0)
+            .collect();
+        if !nested.is_empty() && self.active < self.ranges.len() {
+            self.ranges.splice(self.active..self.active + 1, nested);
+        }
+    }
+
+    pub fn active_ranges(&self) -> &[(usize, usize)] {
+        self.ranges
+            .get(self.active)
+            .map(|(_, ranges)| ranges.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// A selection of every range of the active tabstop
+    pub fn selection(&self) -> Selection {
+        let mut selection = Selection::new();
+        for (start, end) in self.active_ranges() {
+            selection.add_region(SelRegion::new(*start, *end, None));
+        }
+        selection
+    }
+
+    /// Moves to the next tabstop, returning false when there is none left
+    /// to fill in, which is once `$0` or the last tabstop is reached
+    pub fn next(&mut self) -> bool {
+        if self.active + 1 < self.ranges.len() {
+            self.active += 1;
+        }
+        self.active + 1 < self.ranges.len()
+    }
+
+    pub fn prev(&mut self) {
+        self.active = self.active.saturating_sub(1);
+    }
+
+    /// Whether `offset` is within any of the tabstops
+    pub fn contains(&self, offset: usize) -> bool {
+        self.ranges.iter().any(|(_, ranges)| {
+            ranges
+                .iter()
+                .any(|(start, end)| *start <= offset && offset <= *end)
+        })
+    }
+
+    pub fn apply_delta(&mut self, delta: &RopeDelta) {
+        let mut transformer = Transformer::new(delta);
+        for (_, ranges) in self.ranges.iter_mut() {
+            for (start, end) in ranges.iter_mut() {
+                *start = transformer.transform(*start, false);
+                *end = transformer.transform(*end, true);
+            }
+        }
+    }
+}
+
+/// The most labels the acceptance history keeps. The least accepted ones are
+/// dropped to make room for new ones.
+const MAX_HISTORY_LABELS: usize = 1000;
+
+/// The most that accepting an item before can add to its score. It's small
+/// next to the fuzzy scores, so it mostly breaks ties.
+const MAX_HISTORY_BONUS: i64 = 5;
+
+/// Added to the score for each character of the input that matches the start
+/// of a word in the item, so that `fb` ranks `fooBar` above `feedbag`.
+const WORD_START_BONUS: i64 = 10;
+
+/// The bonus for the matched `indices` of the characters in `text` that start
+/// a word, either after a separator like `_` or as an uppercase letter after a
+/// lowercase one.
+fn word_start_bonus(text: &str, indices: &[usize]) -> i64 {
+    let chars = text.chars().collect::<Vec<_>>();
+    let is_word_start = |i: usize| {
+        let c = match chars.get(i) {
+            Some(c) => *c,
+            None => return false,
+        };
+        if i == 0 {
+            return true;
+        }
+        let prev = chars[i - 1];
+        (!prev.is_alphanumeric() && c.is_alphanumeric())
+            || (prev.is_lowercase() && c.is_uppercase())
+    };
+    indices.iter().filter(|i| is_word_start(**i)).count() as i64 * WORD_START_BONUS
+}
+
 #[derive(Clone, PartialEq)]
 pub enum CompletionStatus {
     Inactive,
     Started,
 }
 
+/// A row of the completion list, which is either an item or, when the list
+/// is grouped, the header of a section of items of the same kind.
+pub enum CompletionRow<'a> {
+    Header(Option<CompletionItemKind>),
+    /// An item and its index in `current_items`
+    Item(usize, &'a ScoredCompletionItem),
+}
+
+/// What accepting a completion item with a command attached should do
+#[derive(Debug, PartialEq)]
+pub enum CompletionCommand<'a> {
+    /// Show the signature of the call the item was inserted into
+    TriggerParameterHints,
+    /// Open the completion list again
+    TriggerSuggest,
+    /// Ask the language server to execute the command
+    Execute(&'a lsp_types::Command),
+}
+
+impl<'a> CompletionCommand<'a> {
+    pub fn of(command: &'a lsp_types::Command) -> Self {
+        match command.command.as_str() {
+            "editor.action.triggerParameterHints" => Self::TriggerParameterHints,
+            "editor.action.triggerSuggest" => Self::TriggerSuggest,
+            _ => Self::Execute(command),
+        }
+    }
+}
+
+/// The text accepting a completion item inserts
+#[derive(Debug, PartialEq)]
+pub struct CompletionEdit<'a> {
+    pub text: &'a str,
+    /// The range the server asked to replace, if it gave one
+    pub range: Option<Range>,
+    pub format: InsertTextFormat,
+}
+
+impl<'a> CompletionEdit<'a> {
+    /// With `snippets` off, snippet items insert their label as plain text
+    /// instead, so no tabstops are expanded.
+    pub fn of(
+        item: &'a CompletionItem,
+        insert_mode: CompletionInsertMode,
+        snippets: bool,
+    ) -> Self {
+        let format = item
+            .insert_text_format
+            .unwrap_or(InsertTextFormat::PlainText);
+        if !snippets && format == InsertTextFormat::Snippet {
+            return Self {
+                text: item.label.as_str(),
+                range: None,
+                format: InsertTextFormat::PlainText,
+            };
+        }
+        match &item.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => Self {
+                text: edit.new_text.as_str(),
+                range: Some(edit.range),
+                format,
+            },
+            Some(CompletionTextEdit::InsertAndReplace(edit)) => Self {
+                text: edit.new_text.as_str(),
+                range: Some(match insert_mode {
+                    CompletionInsertMode::Insert => edit.insert,
+                    CompletionInsertMode::Replace => edit.replace,
+                }),
+                format,
+            },
+            None => Self {
+                text: item.insert_text.as_deref().unwrap_or(item.label.as_str()),
+                range: None,
+                format,
+            },
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CompletionData {
     pub id: WidgetId,
@@ -246,8 +478,16 @@ pub struct CompletionData {
     pub input_items: im::HashMap<String, Arc<Vec<ScoredCompletionItem>>>,
     empty: Arc<Vec<ScoredCompletionItem>>,
     pub filtered_items: Arc<Vec<ScoredCompletionItem>>,
+    /// Whether the filtered items were matched against an earlier input.
+    /// They're kept while the items for the current input are on their way,
+    /// so that the list doesn't blank in between.
+    pub stale: bool,
     pub matcher: Arc<SkimMatcherV2>,
     pub size: Size,
+    /// If the unfiltered items are shown in sections by kind
+    pub grouped: bool,
+    /// How often each label was accepted in the workspace
+    pub history: im::HashMap<String, usize>,
 }
 
 impl CompletionData {
@@ -263,8 +503,11 @@ impl CompletionData {
             input: "".to_string(),
             input_items: im::HashMap::new(),
             filtered_items: Arc::new(Vec::new()),
+            stale: false,
             matcher: Arc::new(SkimMatcherV2::default().ignore_case()),
             size: Size::new(400.0, 300.0),
+            grouped: false,
+            history: im::HashMap::new(),
             empty: Arc::new(Vec::new()),
         }
     }
@@ -299,6 +542,40 @@ impl CompletionData {
             .unwrap_or_else(move || self.input_items.get("").unwrap_or(&self.empty))
     }
 
+    /// Whether the current items are shown in sections. Filtered items keep
+    /// their ranked order instead.
+    pub fn is_grouped(&self) -> bool {
+        self.grouped && self.input.is_empty()
+    }
+
+    /// The rows of the list, with a header starting each section when the
+    /// items are grouped
+    pub fn rows(&self) -> Vec<CompletionRow> {
+        let items = self.current_items();
+        let mut rows = Vec::with_capacity(items.len());
+        for (i, item) in items.iter().enumerate() {
+            if self.is_grouped()
+                && (i == 0 || items[i - 1].item.kind != item.item.kind)
+            {
+                rows.push(CompletionRow::Header(item.item.kind));
+            }
+            rows.push(CompletionRow::Item(i, item));
+        }
+        rows
+    }
+
+    /// The row the item at `index` is shown on
+    pub fn row_of_index(&self, index: usize) -> usize {
+        if !self.is_grouped() {
+            return index;
+        }
+        let items = self.current_items();
+        let headers = (0..=index.min(items.len().saturating_sub(1)))
+            .filter(|i| *i == 0 || items[i - 1].item.kind != items[*i].item.kind)
+            .count();
+        index + headers
+    }
+
     pub fn current_item(&self) -> &CompletionItem {
         &self.current_items()[self.index].item
     }
@@ -307,6 +584,36 @@ impl CompletionData {
         self.current_items()[self.index].item.label.as_str()
     }
 
+    /// The part of the selected item's insert text that hasn't been typed
+    /// yet, shown as ghost text at the cursor. Snippets only preview the
+    /// text up to their first tabstop.
+    pub fn preview_text(&self) -> Option<String> {
+        if self.status == CompletionStatus::Inactive || self.input.is_empty() {
+            return None;
+        }
+        let item = &self.current_items().get(self.index)?.item;
+        let text = match &item.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => edit.new_text.as_str(),
+            _ => item.insert_text.as_deref().unwrap_or(item.label.as_str()),
+        };
+        let text = match item.insert_text_format {
+            Some(InsertTextFormat::Snippet) => {
+                Snippet::from_str(text).ok()?.prefix()
+            }
+            _ => text.to_string(),
+        };
+
+        let typed = text.get(..self.input.len())?;
+        if !typed.eq_ignore_ascii_case(&self.input) {
+            return None;
+        }
+        let rest = &text[self.input.len()..];
+        if rest.is_empty() {
+            return None;
+        }
+        Some(rest.to_string())
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn request(
         &self,
@@ -340,16 +647,56 @@ impl CompletionData {
         );
     }
 
+    /// Ask the server to stop working on the requests sent with
+    /// `request_id`, which have been superseded by newer ones
+    pub fn cancel_request(&self, proxy: Arc<LapceProxy>, request_id: usize) {
+        proxy.cancel_completion(request_id, self.buffer_id);
+    }
+
+    /// Count an acceptance of the item with `label`, which ranks it higher
+    /// among matches that score about the same
+    pub fn record_acceptance(&mut self, label: &str) {
+        if !self.history.contains_key(label) {
+            while self.history.len() >= MAX_HISTORY_LABELS {
+                let least = self
+                    .history
+                    .iter()
+                    .min_by(|a, b| a.1.cmp(b.1).then_with(|| a.0.cmp(b.0)))
+                    .map(|(label, _)| label.clone());
+                match least {
+                    Some(least) => self.history.remove(&least),
+                    None => break,
+                };
+            }
+        }
+        *self.history.entry(label.to_string()).or_insert(0) += 1;
+    }
+
+    fn history_bonus(&self, label: &str) -> i64 {
+        self.history
+            .get(label)
+            .map(|count| (*count as i64).min(MAX_HISTORY_BONUS))
+            .unwrap_or(0)
+    }
+
     pub fn cancel(&mut self) {
         if self.status == CompletionStatus::Inactive {
             return;
         }
         self.status = CompletionStatus::Inactive;
         self.input = "".to_string();
-        self.input_items.clear();
+        self.clear_items();
         self.index = 0;
     }
 
+    /// Forgets the items received so far, so that none of them are shown
+    /// for another word.
+    pub fn clear_items(&mut self) {
+        self.input_items.clear();
+        self.filtered_items = Arc::new(Vec::new());
+        self.stale = false;
+    }
+
     pub fn update_input(&mut self, input: String) {
         self.input = input;
         self.index = 0;
@@ -359,22 +706,31 @@ impl CompletionData {
         self.filter_items();
     }
 
+    /// Receive the items for `input`. `buffer_id` and `offset` are where the
+    /// word at the cursor now starts, and the completion is abandoned when
+    /// the cursor has left the word it was requested for.
     pub fn receive(
         &mut self,
         request_id: usize,
         input: String,
         resp: CompletionResponse,
+        buffer_id: BufferId,
+        offset: usize,
     ) {
         if self.status == CompletionStatus::Inactive || self.request_id != request_id
         {
             return;
         }
+        if self.buffer_id != buffer_id || self.offset != offset {
+            self.cancel();
+            return;
+        }
 
         let items = match resp {
             CompletionResponse::Array(items) => items,
             CompletionResponse::List(list) => list.items,
         };
-        let items = items
+        let mut items: Vec<ScoredCompletionItem> = items
             .iter()
             .map(|i| ScoredCompletionItem {
                 item: i.to_owned(),
@@ -384,13 +740,24 @@ impl CompletionData {
                 indices: Vec::new(),
             })
             .collect();
+        if self.grouped {
+            // Sections are in the order their kind first shows up, and the
+            // sort is stable, so the server's order is kept within each.
+            let mut kinds = Vec::new();
+            for item in items.iter() {
+                if !kinds.contains(&item.item.kind) {
+                    kinds.push(item.item.kind);
+                }
+            }
+            items.sort_by_key(|i| kinds.iter().position(|k| *k == i.item.kind));
+        }
 
         self.input_items.insert(input, Arc::new(items));
         self.filter_items();
     }
 
     pub fn filter_items(&mut self) {
-        if self.input.is_empty() {
+        if self.status == CompletionStatus::Inactive || self.input.is_empty() {
             return;
         }
 
@@ -404,13 +771,15 @@ impl CompletionData {
                 if let Some((score, mut indices)) =
                     self.matcher.fuzzy_indices(filter_text, &self.input)
                 {
+                    let bonus = word_start_bonus(filter_text, &indices)
+                        + self.history_bonus(&i.item.label);
                     if shift > 0 {
                         for idx in indices.iter_mut() {
                             *idx += shift;
                         }
                     }
                     let mut item = i.clone();
-                    item.score = score;
+                    item.score = score + bonus;
                     item.label_score = score;
                     item.indices = indices;
                     if let Some(score) =
@@ -430,6 +799,14 @@ impl CompletionData {
                 .then_with(|| b.label_score.cmp(&a.label_score))
                 .then_with(|| a.item.label.len().cmp(&b.item.label.len()))
         });
+        if items.is_empty()
+            && !self.input_items.contains_key(&self.input)
+            && !self.filtered_items.is_empty()
+        {
+            self.stale = true;
+            return;
+        }
+        self.stale = false;
         self.filtered_items = Arc::new(items);
     }
 }
@@ -466,6 +843,20 @@ pub struct ScoredCompletionItem {
     pub indices: Vec<usize>,
 }
 
+impl ScoredCompletionItem {
+    /// Whether the server marked the item as deprecated, through either the
+    /// `deprecated` flag or the `Deprecated` tag.
+    pub fn is_deprecated(&self) -> bool {
+        self.item.deprecated.unwrap_or(false)
+            || self
+                .item
+                .tags
+                .as_ref()
+                .map(|tags| tags.contains(&CompletionItemTag::Deprecated))
+                .unwrap_or(false)
+    }
+}
+
 #[derive(Clone)]
 pub struct CompletionState {
     pub widget_id: WidgetId,
@@ -563,4 +954,421 @@ mod tests {
             parsed.tabs(0)
         );
     }
+
+    #[test]
+    fn test_snippet_escapes() {
+        let s = "cost: \\$${1:5} {a\\}b\\} \\\\";
+        let parsed = Snippet::from_str(s).unwrap();
+        assert_eq!("cost: $5 {a}b} \\", parsed.text());
+        assert_eq!(s, parsed.to_string());
+
+        let reparsed = Snippet::from_str(&parsed.to_string()).unwrap();
+        assert_eq!(parsed.text(), reparsed.text());
+        assert_eq!(parsed.tabs(0), reparsed.tabs(0));
+    }
+
+    #[test]
+    fn test_snippet_expansion() {
+        let snippet = Snippet::from_str("${2:b} ${1:a} $1$0").unwrap();
+        let mut expansion = SnippetExpansion::new(snippet.tabs(0)).unwrap();
+        assert_eq!(
+            vec![
+                (1, vec![(2, 3), (4, 4)]),
+                (2, vec![(0, 1)]),
+                (0, vec![(4, 4)]),
+            ],
+            expansion.ranges
+        );
+        assert_eq!(&[(2, 3), (4, 4)], expansion.active_ranges());
+        assert_eq!(2, expansion.selection().len());
+
+        assert!(expansion.next());
+        assert_eq!(&[(0, 1)], expansion.active_ranges());
+        expansion.prev();
+        assert_eq!(1, expansion.ranges[expansion.active].0);
+        assert!(expansion.next());
+        // Reaching `$0` ends the expansion.
+        assert!(!expansion.next());
+        assert_eq!(&[(4, 4)], expansion.active_ranges());
+
+        let snippet = Snippet::from_str("foo()$0").unwrap();
+        assert_eq!(None, SnippetExpansion::new(snippet.tabs(0)));
+    }
+
+    #[test]
+    fn test_completion_edit_without_snippets() {
+        let mut item =
+            CompletionItem::new_simple("push".to_string(), "".to_string());
+        item.insert_text = Some("push(${1:value})$0".to_string());
+        item.insert_text_format = Some(InsertTextFormat::Snippet);
+
+        let edit = CompletionEdit::of(&item, CompletionInsertMode::Replace, true);
+        assert_eq!("push(${1:value})$0", edit.text);
+        assert_eq!(InsertTextFormat::Snippet, edit.format);
+
+        let edit = CompletionEdit::of(&item, CompletionInsertMode::Replace, false);
+        assert_eq!(
+            CompletionEdit {
+                text: "push",
+                range: None,
+                format: InsertTextFormat::PlainText,
+            },
+            edit
+        );
+    }
+
+    #[test]
+    fn test_completion_edit_insert_mode() {
+        let range = |start, end| Range {
+            start: Position::new(0, start),
+            end: Position::new(0, end),
+        };
+        let mut item =
+            CompletionItem::new_simple("push".to_string(), "".to_string());
+        item.text_edit = Some(CompletionTextEdit::InsertAndReplace(
+            lsp_types::InsertReplaceEdit {
+                new_text: "push".to_string(),
+                insert: range(0, 2),
+                replace: range(0, 6),
+            },
+        ));
+
+        let edit = CompletionEdit::of(&item, CompletionInsertMode::Insert, true);
+        assert_eq!(Some(range(0, 2)), edit.range);
+        let edit = CompletionEdit::of(&item, CompletionInsertMode::Replace, true);
+        assert_eq!(Some(range(0, 6)), edit.range);
+    }
+
+    #[test]
+    fn test_deprecated_item() {
+        let scored = |item: CompletionItem| ScoredCompletionItem {
+            item,
+            index: 0,
+            score: 0,
+            label_score: 0,
+            indices: Vec::new(),
+        };
+
+        let item = CompletionItem::new_simple("old".to_string(), "".to_string());
+        assert!(!scored(item.clone()).is_deprecated());
+
+        let mut flagged = item.clone();
+        flagged.deprecated = Some(true);
+        assert!(scored(flagged).is_deprecated());
+
+        let mut tagged = item;
+        tagged.tags = Some(vec![CompletionItemTag::Deprecated]);
+        assert!(scored(tagged).is_deprecated());
+    }
+
+    #[test]
+    fn test_preview_text() {
+        let mut snippet =
+            CompletionItem::new_simple("push".to_string(), "".to_string());
+        snippet.insert_text = Some("push(${1:value})$0".to_string());
+        snippet.insert_text_format = Some(InsertTextFormat::Snippet);
+        let items = vec![
+            CompletionItem::new_simple("HashMap".to_string(), "".to_string()),
+            snippet,
+        ];
+
+        let mut completion = CompletionData::new();
+        completion.status = CompletionStatus::Started;
+        completion.receive(
+            0,
+            "".to_string(),
+            CompletionResponse::Array(items),
+            BufferId(0),
+            0,
+        );
+        assert_eq!(None, completion.preview_text());
+
+        completion.update_input("hash".to_string());
+        assert_eq!(Some("Map".to_string()), completion.preview_text());
+
+        completion.update_input("pu".to_string());
+        assert_eq!(Some("sh(".to_string()), completion.preview_text());
+
+        completion.cancel();
+        assert_eq!(None, completion.preview_text());
+    }
+
+    #[test]
+    fn test_receive_after_cursor_moved() {
+        let items = vec![CompletionItem::new_simple(
+            "len".to_string(),
+            "".to_string(),
+        )];
+
+        let mut completion = CompletionData::new();
+        completion.status = CompletionStatus::Started;
+        completion.offset = 10;
+        completion.receive(
+            0,
+            "".to_string(),
+            CompletionResponse::Array(items.clone()),
+            BufferId(0),
+            10,
+        );
+        assert_eq!(1, completion.len());
+
+        // The cursor was moved to another word while the request was out.
+        completion.receive(
+            0,
+            "l".to_string(),
+            CompletionResponse::Array(items),
+            BufferId(0),
+            20,
+        );
+        assert_eq!(CompletionStatus::Inactive, completion.status);
+        assert!(completion.input_items.is_empty());
+    }
+
+    #[test]
+    fn test_out_of_order_responses() {
+        let item = |label: &str| {
+            CompletionItem::new_simple(label.to_string(), "".to_string())
+        };
+
+        let mut completion = CompletionData::new();
+        completion.status = CompletionStatus::Started;
+        completion.receive(
+            0,
+            "".to_string(),
+            CompletionResponse::Array(vec![item("len"), item("push")]),
+            BufferId(0),
+            0,
+        );
+        completion.update_input("l".to_string());
+        assert_eq!("len", completion.current());
+        assert!(!completion.stale);
+
+        // Nothing received so far matches, so the last items stay up until
+        // the ones for the new input arrive.
+        completion.update_input("lx".to_string());
+        assert_eq!(1, completion.len());
+        assert!(completion.stale);
+
+        // A response to a superseded request is dropped without blanking.
+        completion.request_id = 1;
+        completion.receive(
+            0,
+            "lx".to_string(),
+            CompletionResponse::Array(vec![item("lxold")]),
+            BufferId(0),
+            0,
+        );
+        assert_eq!("len", completion.current());
+        assert!(completion.stale);
+
+        completion.receive(
+            1,
+            "lx".to_string(),
+            CompletionResponse::Array(vec![item("lxfoo")]),
+            BufferId(0),
+            0,
+        );
+        assert_eq!("lxfoo", completion.current());
+        assert!(!completion.stale);
+
+        // The response for the earlier input arriving last changes nothing.
+        completion.receive(
+            1,
+            "l".to_string(),
+            CompletionResponse::Array(vec![item("lbar")]),
+            BufferId(0),
+            0,
+        );
+        assert_eq!("lxfoo", completion.current());
+
+        completion.cancel();
+        assert!(completion.filtered_items.is_empty());
+        assert!(!completion.stale);
+    }
+
+    #[test]
+    fn test_history_ranking() {
+        let items = vec![
+            CompletionItem::new_simple("len_a".to_string(), "".to_string()),
+            CompletionItem::new_simple("len_b".to_string(), "".to_string()),
+        ];
+
+        let mut completion = CompletionData::new();
+        completion.status = CompletionStatus::Started;
+        completion.receive(
+            0,
+            "".to_string(),
+            CompletionResponse::Array(items),
+            BufferId(0),
+            0,
+        );
+
+        completion.update_input("len".to_string());
+        assert_eq!("len_a", completion.current());
+
+        completion.record_acceptance("len_b");
+        completion.record_acceptance("len_b");
+        completion.update_input("len".to_string());
+        assert_eq!("len_b", completion.current());
+
+        // The bonus is capped, so it can't outweigh a much better match.
+        for _ in 0..100 {
+            completion.record_acceptance("len_b");
+        }
+        assert_eq!(MAX_HISTORY_BONUS, completion.history_bonus("len_b"));
+    }
+
+    #[test]
+    fn test_history_changes_order() {
+        let items = ["get_a", "get_b", "get_c"]
+            .iter()
+            .map(|label| {
+                CompletionItem::new_simple(label.to_string(), "".to_string())
+            })
+            .collect();
+
+        let mut completion = CompletionData::new();
+        completion.status = CompletionStatus::Started;
+        completion.receive(
+            0,
+            "".to_string(),
+            CompletionResponse::Array(items),
+            BufferId(0),
+            0,
+        );
+        let labels = |completion: &CompletionData| {
+            completion
+                .current_items()
+                .iter()
+                .map(|item| item.item.label.clone())
+                .collect::<Vec<String>>()
+        };
+
+        completion.update_input("get".to_string());
+        assert_eq!(vec!["get_a", "get_b", "get_c"], labels(&completion));
+
+        completion.record_acceptance("get_c");
+        completion.record_acceptance("get_c");
+        completion.record_acceptance("get_b");
+        completion.update_input("get".to_string());
+        assert_eq!(vec!["get_c", "get_b", "get_a"], labels(&completion));
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut completion = CompletionData::new();
+        completion.record_acceptance("kept");
+        completion.record_acceptance("kept");
+        for i in 0..MAX_HISTORY_LABELS + 10 {
+            completion.record_acceptance(&format!("label_{}", i));
+        }
+        assert_eq!(MAX_HISTORY_LABELS, completion.history.len());
+        assert_eq!(Some(&2), completion.history.get("kept"));
+        // The newest label has room, made by dropping a least accepted one.
+        let newest = format!("label_{}", MAX_HISTORY_LABELS + 9);
+        assert!(completion.history.contains_key(&newest));
+    }
+
+    #[test]
+    fn test_word_start_ranking() {
+        assert_eq!(2 * WORD_START_BONUS, word_start_bonus("fooBar", &[0, 3]));
+        assert_eq!(2 * WORD_START_BONUS, word_start_bonus("foo_bar", &[0, 4]));
+        assert_eq!(WORD_START_BONUS, word_start_bonus("feedbag", &[0, 4]));
+        assert_eq!(0, word_start_bonus("HTTP", &[1, 2]));
+
+        let items = vec![
+            CompletionItem::new_simple("feedbag".to_string(), "".to_string()),
+            CompletionItem::new_simple("fooBar".to_string(), "".to_string()),
+            CompletionItem::new_simple("fab".to_string(), "".to_string()),
+        ];
+        let mut completion = CompletionData::new();
+        completion.status = CompletionStatus::Started;
+        completion.receive(
+            0,
+            "".to_string(),
+            CompletionResponse::Array(items),
+            BufferId(0),
+            0,
+        );
+
+        completion.update_input("fb".to_string());
+        assert_eq!("fooBar", completion.current());
+    }
+
+    #[test]
+    fn test_grouped_rows() {
+        let item = |label: &str, kind| {
+            let mut item =
+                CompletionItem::new_simple(label.to_string(), "".to_string());
+            item.kind = Some(kind);
+            item
+        };
+        let items = vec![
+            item("len", CompletionItemKind::Method),
+            item("cap", CompletionItemKind::Field),
+            item("push", CompletionItemKind::Method),
+        ];
+
+        let mut completion = CompletionData::new();
+        completion.grouped = true;
+        completion.status = CompletionStatus::Started;
+        completion.receive(
+            0,
+            "".to_string(),
+            CompletionResponse::Array(items),
+            BufferId(0),
+            0,
+        );
+
+        let rows: Vec<String> = completion
+            .rows()
+            .iter()
+            .map(|row| match row {
+                CompletionRow::Header(kind) => format!("{:?}", kind.unwrap()),
+                CompletionRow::Item(_, item) => item.item.label.clone(),
+            })
+            .collect();
+        assert_eq!(vec!["Method", "len", "push", "Field", "cap"], rows);
+
+        // Moving down from the last method skips the header of the fields.
+        completion.next();
+        completion.next();
+        assert_eq!("cap", completion.current());
+        assert_eq!(4, completion.row_of_index(completion.index));
+
+        // Filtering falls back to the flat ranked list.
+        completion.update_input("p".to_string());
+        assert!(completion
+            .rows()
+            .iter()
+            .all(|row| matches!(row, CompletionRow::Item(..))));
+    }
+
+    #[test]
+    fn test_completion_command() {
+        let command = |name: &str| lsp_types::Command {
+            title: String::new(),
+            command: name.to_string(),
+            arguments: None,
+        };
+
+        let hints = command("editor.action.triggerParameterHints");
+        assert_eq!(
+            CompletionCommand::TriggerParameterHints,
+            CompletionCommand::of(&hints)
+        );
+        let suggest = command("editor.action.triggerSuggest");
+        assert_eq!(
+            CompletionCommand::TriggerSuggest,
+            CompletionCommand::of(&suggest)
+        );
+
+        let mut item = CompletionItem::new_simple("foo".to_string(), "".to_string());
+        item.command = Some(command("rust-analyzer.applyActionGroup"));
+        let command = item.command.as_ref().unwrap();
+        assert_eq!(
+            CompletionCommand::Execute(command),
+            CompletionCommand::of(command)
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use std::{io::Write, path::PathBuf};
+use std::{collections::HashMap, io::Write, path::PathBuf};
 
 use anyhow::Result;
 use directories::ProjectDirs;
@@ -6,7 +6,7 @@ use druid::{
     piet::{PietText, Text, TextLayout, TextLayoutBuilder},
     Color, ExtEventSink, FontFamily, Size, Target,
 };
-use hashbrown::HashMap;
+use lapce_core::language::LapceLanguage;
 use serde::{Deserialize, Serialize};
 use structdesc::FieldNames;
 use thiserror::Error;
@@ -40,6 +40,8 @@ impl LapceTheme {
     pub const EDITOR_SELECTION: &'static str = "editor.selection";
     pub const EDITOR_CURRENT_LINE: &'static str = "editor.current_line";
     pub const EDITOR_LINK: &'static str = "editor.link";
+    pub const EDITOR_WHITESPACE_WARNING: &'static str = "editor.whitespace_warning";
+    pub const EDITOR_DOCUMENT_HIGHLIGHT: &'static str = "editor.document_highlight";
 
     pub const SOURCE_CONTROL_ADDED: &'static str = "source_control.added";
     pub const SOURCE_CONTROL_REMOVED: &'static str = "source_control.removed";
@@ -120,6 +122,22 @@ pub struct LapceConfig {
     pub color_theme: String,
     #[field_names(desc = "Set the terminal Shell")]
     pub terminal_shell: String,
+    #[field_names(
+        desc = "Command used to save files that need elevated permissions, e.g. pkexec or sudo -A"
+    )]
+    pub sudo_helper: String,
+    #[field_names(desc = "Show the recently used items at the top of menus")]
+    pub menu_show_recent: bool,
+    #[field_names(
+        desc = "How many lines to show before and after each match in the search panel"
+    )]
+    pub search_context_lines: usize,
+    #[field_names(desc = "Installed plugins that are not started")]
+    pub disabled_plugins: Vec<String>,
+    #[field_names(
+        desc = "Ask whether to save the unsaved files before closing a tab"
+    )]
+    pub confirm_close_unsaved: bool,
 }
 
 #[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
@@ -137,16 +155,112 @@ pub struct EditorConfig {
     pub tab_width: usize,
     #[field_names(desc = "If opened editors are shown in a tab")]
     pub show_tab: bool,
+    #[field_names(
+        desc = "Show the symbols enclosing the cursor in a bar above the editor"
+    )]
+    pub breadcrumbs: bool,
     #[field_names(
         desc = "How long (in ms) it should take before the hover information appears"
     )]
     pub hover_delay: u64,
+    #[field_names(desc = "Highlight whitespace at the end of lines")]
+    pub highlight_trailing_whitespace: bool,
+    #[field_names(desc = "Highlight lines whose indentation mixes tabs and spaces")]
+    pub highlight_mixed_indent: bool,
+    #[field_names(
+        desc = "If Escape closes the topmost popup before changing mode or selection"
+    )]
+    pub escape_closes_overlays: bool,
+    #[field_names(
+        desc = "Request completion again after accepting an item that ends in a trigger character like `.`"
+    )]
+    pub completion_reopen_after_accept: bool,
+    #[field_names(
+        desc = "Show the completion list in sections by kind until something is typed"
+    )]
+    pub completion_group_by_kind: bool,
+    #[field_names(
+        desc = "Whether accepting a completion inserts it before the rest of the word (Insert) or replaces the word (Replace)"
+    )]
+    pub completion_insert_mode: CompletionInsertMode,
+    #[field_names(
+        desc = "Expand snippet completions with tabstops; when off they insert just their label"
+    )]
+    pub completion_snippets: bool,
+    #[field_names(desc = "Set the width of the completion list")]
+    pub completion_width: usize,
+    #[field_names(desc = "Set the height of the completion list")]
+    pub completion_height: usize,
+    #[field_names(
+        desc = "Only go to errors with go to next/previous diagnostic when the file has any"
+    )]
+    pub diagnostic_navigation_prefer_errors: bool,
+    #[field_names(
+        desc = "Only go to errors, not warnings or other diagnostics, with go to next problem in workspace"
+    )]
+    pub next_problem_errors_only: bool,
+    #[field_names(
+        desc = "Save files automatically: off, on_focus_change (when the editor loses focus) or after_delay (once no edit is made for the auto save delay)"
+    )]
+    pub auto_save: AutoSaveMode,
+    #[field_names(
+        desc = "How long (in ms) after the last edit a file is saved when auto save is after_delay"
+    )]
+    pub auto_save_delay: u64,
+    #[field_names(desc = "Format files with the language server when saving them")]
+    pub format_on_save: bool,
+    #[field_names(
+        desc = "Whether files of a language are formatted when saving them, by language name, overriding format on save"
+    )]
+    pub format_on_save_overrides: HashMap<String, bool>,
+    #[field_names(
+        desc = "Format the code around the cursor when typing characters the language server asks for, like `}` or `;`"
+    )]
+    pub format_on_type: bool,
+}
+
+/// What an accepted completion does with the rest of the word after the
+/// cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CompletionInsertMode {
+    Insert,
+    Replace,
+}
+
+impl Default for CompletionInsertMode {
+    fn default() -> Self {
+        CompletionInsertMode::Replace
+    }
+}
+
+/// When files are saved without an explicit save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoSaveMode {
+    Off,
+    OnFocusChange,
+    AfterDelay,
+}
+
+impl Default for AutoSaveMode {
+    fn default() -> Self {
+        AutoSaveMode::Off
+    }
 }
 
 impl EditorConfig {
     pub fn font_family(&self) -> FontFamily {
         FontFamily::new_unchecked(self.font_family.clone())
     }
+
+    /// Whether saving a file in `language` formats it first
+    pub fn formats_on_save(&self, language: Option<LapceLanguage>) -> bool {
+        language
+            .and_then(|language| {
+                self.format_on_save_overrides.get(language.name()).copied()
+            })
+            .unwrap_or(self.format_on_save)
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -163,8 +277,7 @@ impl Theme {
 
     /// Creates a new theme based on the current one, extending it with values parsed from `content`.
     pub fn extend(&self, content: &str) -> Result<Theme> {
-        let theme_colors: std::collections::HashMap<String, String> =
-            toml::from_str(content)?;
+        let theme_colors: HashMap<String, String> = toml::from_str(content)?;
         let mut theme = HashMap::new();
         for (k, v) in theme_colors.iter() {
             if let Some(stripped) = v.strip_prefix('$') {
@@ -398,6 +511,20 @@ impl Config {
         Ok(config)
     }
 
+    /// The config of the default settings, without any of the user's.
+    #[cfg(test)]
+    pub fn default_settings() -> Self {
+        let settings = config::Config::default()
+            .with_merged(config::File::from_str(
+                DEFAULT_SETTINGS,
+                config::FileFormat::Toml,
+            ))
+            .unwrap();
+        let mut config: Config = settings.try_into().unwrap();
+        config.themes = Themes::default();
+        config
+    }
+
     pub fn dir() -> Option<PathBuf> {
         ProjectDirs::from("", "", "Lapce").map(|d| PathBuf::from(d.config_dir()))
     }
@@ -707,6 +834,19 @@ impl Config {
         )
     }
 
+    /// Forgets the recent local workspaces whose folders no longer exist.
+    /// Remote ones are kept, as their folders can't be checked from here.
+    pub fn remove_stale_recent_workspaces() -> Option<()> {
+        let workspaces = Self::recent_workspaces()?
+            .into_iter()
+            .filter(|w| match (&w.kind, w.path.as_ref()) {
+                (LapceWorkspaceType::Local, Some(path)) => path.exists(),
+                _ => true,
+            })
+            .collect();
+        Self::update_recent_workspaces(workspaces)
+    }
+
     pub fn recent_workspaces_file() -> Option<PathBuf> {
         let proj_dirs = ProjectDirs::from("", "", "Lapce")?;
         let _ = std::fs::create_dir_all(proj_dirs.config_dir());
@@ -720,3 +860,30 @@ impl Config {
         Some(path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_on_save() {
+        let mut config = EditorConfig {
+            format_on_save: false,
+            ..Default::default()
+        };
+        config
+            .format_on_save_overrides
+            .insert("rust".to_string(), true);
+        assert!(config.formats_on_save(Some(LapceLanguage::Rust)));
+        assert!(!config.formats_on_save(Some(LapceLanguage::Python)));
+        assert!(!config.formats_on_save(None));
+
+        config.format_on_save = true;
+        config
+            .format_on_save_overrides
+            .insert("python".to_string(), false);
+        assert!(!config.formats_on_save(Some(LapceLanguage::Python)));
+        assert!(config.formats_on_save(Some(LapceLanguage::Go)));
+        assert!(config.formats_on_save(None));
+    }
+}
@@ -1,27 +1,42 @@
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     path::{Path, PathBuf},
     rc::Rc,
     str::FromStr,
     sync::Arc,
     thread,
+    time::Duration,
 };
 
-use anyhow::Result;
-use crossbeam_channel::{unbounded, Receiver, Sender};
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
 use druid::{
-    piet::{PietText, PietTextLayout, Text, TextLayout, TextLayoutBuilder},
-    theme, Command, Data, Env, EventCtx, ExtEventSink, FontFamily, Lens, Point,
-    Rect, Size, Target, Vec2, WidgetId, WindowId,
+    piet::{PietText, Text, TextLayout, TextLayoutBuilder},
+    theme, Application, Command, Data, Env, EventCtx, ExtEventSink, FontFamily,
+    FontWeight, Lens, Point, Rect, Size, Target, Vec2, WidgetId, WindowId,
 };
 
+use lapce_core::indent::{reindent, IndentStyle};
+use lapce_core::language::LapceLanguage;
+use lapce_core::line_ending::LineEnding;
 use lapce_rpc::{
-    file::FileNodeItem, plugin::PluginDescription, source_control::FileDiff,
+    buffer::{BufferId, FileEncoding, RangeFormattingResponse, SaveErrorKind},
+    file::{
+        FileChangeKind, FileNodeItem, FileOperation, GlobalReplaceResponse,
+        SearchOptions,
+    },
+    plugin::PluginDescription,
+    source_control::FileDiff,
     terminal::TermId,
+    Callback,
 };
 use lsp_types::{
-    CodeActionOrCommand, Diagnostic, Position, ProgressToken, TextEdit,
+    CodeAction, CodeActionOrCommand, Diagnostic, DiagnosticSeverity,
+    DocumentChangeOperation, DocumentChanges, FormattingOptions, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, PrepareRenameResponse,
+    ProgressToken, Range, ResourceOp, TextDocumentEdit, TextEdit, Url,
+    WorkspaceEdit,
 };
 use notify::Watcher;
 use serde::{Deserialize, Serialize};
@@ -33,31 +48,36 @@ use crate::{
         data::BufferData, matching_char, matching_pair_direction, Buffer,
         BufferContent, EditType, LocalBufferKind,
     },
+    code_action::{code_action_rows, is_preferred, CodeActionRow},
     command::{
         CommandTarget, EnsureVisiblePosition, LapceCommandNew, LapceUICommand,
         LapceWorkbenchCommand, LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND,
     },
-    completion::CompletionData,
+    completion::{CompletionData, CompletionStatus, SnippetExpansion},
     config::{Config, ConfigWatcher, GetConfig, LapceTheme},
     db::{
-        EditorInfo, EditorTabChildInfo, EditorTabInfo, LapceDb, SplitContentInfo,
-        SplitInfo, TabsInfo, WindowInfo, WorkspaceInfo,
+        EditorInfo, EditorTabChildInfo, EditorTabInfo, LapceDb, PanelInfo,
+        SplitContentInfo, SplitInfo, TabsInfo, WindowInfo, WorkspaceInfo,
     },
     editor::{EditorLocationNew, LapceEditorBufferData, TabRect},
     explorer::FileExplorerData,
     find::Find,
-    hover::HoverData,
+    hierarchy::{HierarchyData, HierarchyKind},
+    hover::{HoverData, HoverStatus},
     keypress::KeyPressData,
-    menu::MenuData,
+    menu::{MenuData, MenuItem},
     movement::{Cursor, CursorMode, Movement, Selection},
+    outline::OutlineData,
     palette::{PaletteData, PaletteType, PaletteViewData},
     panel::PanelPosition,
+    peek::PeekData,
     picker::FilePickerData,
-    plugin::PluginData,
-    problem::ProblemData,
-    proxy::{LapceProxy, ProxyStatus, TermEvent},
-    search::SearchData,
+    plugin::{version_is_newer, PluginData},
+    problem::{diagnostic_copy_text, ProblemData},
+    proxy::{path_from_url, LapceProxy, ProxyStatus, TermEvent},
+    search::{replace_in_line, SearchData},
     settings::LapceSettingsPanelData,
+    signature::{SignatureData, SignatureStatus},
     source_control::SourceControlData,
     split::{SplitDirection, SplitMoveDirection},
     state::{LapceWorkspace, LapceWorkspaceType, VisualMode},
@@ -203,6 +223,8 @@ pub struct LapceWindowData {
     pub db: Arc<LapceDb>,
     pub watcher: Arc<notify::RecommendedWatcher>,
     pub menu: Arc<MenuData>,
+    /// The items last picked from any menu, most recent first
+    pub recent_menu_items: Arc<VecDeque<MenuItem>>,
     /// The size of the window.
     pub size: Size,
     /// The position of the window.
@@ -303,6 +325,7 @@ impl LapceWindowData {
             db,
             watcher: Arc::new(watcher),
             menu: Arc::new(menu),
+            recent_menu_items: Arc::new(VecDeque::new()),
             size: info.size,
             pos: info.pos,
         }
@@ -339,6 +362,202 @@ pub struct EditorDiagnostic {
     pub diagnositc: Diagnostic,
 }
 
+impl EditorDiagnostic {
+    /// Whether the diagnostic covers `offset`, where one with an empty range
+    /// covers the offset it is at.
+    pub fn contains(&self, offset: usize) -> bool {
+        match self.range {
+            Some((start, end)) => start <= offset && offset < end.max(start + 1),
+            None => false,
+        }
+    }
+
+    /// The offsets in the buffer of the diagnostic's start and end positions.
+    pub fn offsets_in(&self, buffer: &Buffer) -> (usize, usize) {
+        (
+            buffer.offset_of_position(&self.diagnositc.range.start),
+            buffer.offset_of_position(&self.diagnositc.range.end),
+        )
+    }
+
+    /// The other locations the diagnostic refers to, like where a value was
+    /// first borrowed, each with the text to show for it.
+    pub fn related(&self) -> Vec<(String, EditorLocationNew)> {
+        self.diagnositc
+            .related_information
+            .iter()
+            .flatten()
+            .map(|related| {
+                let path = path_from_url(&related.location.uri);
+                let start = related.location.range.start;
+                let text = format!(
+                    "{}[{}, {}]: {}",
+                    path.file_name().and_then(|f| f.to_str()).unwrap_or(""),
+                    start.line,
+                    start.character,
+                    related.message
+                );
+                let location = EditorLocationNew {
+                    path,
+                    position: Some(start),
+                    scroll_offset: None,
+                    history: None,
+                };
+                (text, location)
+            })
+            .collect()
+    }
+
+    /// Orders diagnostics from the most severe, with those that have no
+    /// severity last.
+    fn severity_rank(&self) -> usize {
+        match self.diagnositc.severity {
+            Some(DiagnosticSeverity::Error) => 0,
+            Some(DiagnosticSeverity::Warning) => 1,
+            Some(DiagnosticSeverity::Information) => 2,
+            Some(DiagnosticSeverity::Hint) => 3,
+            None => 4,
+        }
+    }
+}
+
+/// The diagnostic that starts nearest after `offset`, or before it if not
+/// `forward`, wrapping around the ends of the file. With `prefer_errors`,
+/// other diagnostics are skipped while there are errors.
+pub fn next_diagnostic(
+    diagnostics: &[EditorDiagnostic],
+    offset: usize,
+    forward: bool,
+    prefer_errors: bool,
+) -> Option<&EditorDiagnostic> {
+    let errors_only = prefer_errors
+        && diagnostics
+            .iter()
+            .any(|d| d.diagnositc.severity == Some(DiagnosticSeverity::Error));
+    let mut diagnostics = diagnostics
+        .iter()
+        .filter_map(|d| Some((d.range?.0, d)))
+        .filter(|(_, d)| {
+            !errors_only || d.diagnositc.severity == Some(DiagnosticSeverity::Error)
+        })
+        .collect::<Vec<_>>();
+    diagnostics.sort_by_key(|(start, _)| *start);
+
+    let next = if forward {
+        diagnostics
+            .iter()
+            .find(|(start, _)| *start > offset)
+            .or_else(|| diagnostics.first())
+    } else {
+        diagnostics
+            .iter()
+            .rev()
+            .find(|(start, _)| *start < offset)
+            .or_else(|| diagnostics.last())
+    };
+    next.map(|(_, d)| *d)
+}
+
+/// The start of the first diagnostic after `from` across all files, ordered by
+/// path and then position, wrapping around to the first one. Files for which
+/// `exists` is false are skipped.
+fn next_problem(
+    diagnostics: &im::HashMap<PathBuf, Arc<Vec<EditorDiagnostic>>>,
+    from: Option<&(PathBuf, Position)>,
+    errors_only: bool,
+    exists: impl Fn(&Path) -> bool,
+) -> Option<(PathBuf, Position)> {
+    let mut problems = diagnostics
+        .iter()
+        .filter(|(path, _)| exists(path))
+        .flat_map(|(path, diagnostics)| {
+            diagnostics
+                .iter()
+                .filter(|d| {
+                    !errors_only
+                        || d.diagnositc.severity == Some(DiagnosticSeverity::Error)
+                })
+                .map(move |d| (path, d.diagnositc.range.start))
+        })
+        .collect::<Vec<_>>();
+    problems.sort();
+    problems.dedup();
+
+    from.and_then(|(from_path, from_position)| {
+        problems
+            .iter()
+            .find(|problem| **problem > (from_path, *from_position))
+    })
+    .or_else(|| problems.first())
+    .map(|(path, position)| ((*path).clone(), *position))
+}
+
+/// Orders `panels` as in `order`, which was saved earlier. Panels that are
+/// not in it keep their place after the ones that are.
+fn sort_panels(panels: &mut [PanelKind], order: &[PanelKind]) {
+    panels.sort_by_key(|kind| {
+        order.iter().position(|k| k == kind).unwrap_or(order.len())
+    });
+}
+
+/// Moves `kind` to `index` in `panels`, counted before it's taken out,
+/// returning whether the order changed.
+fn move_panel(panels: &mut Vec<PanelKind>, kind: PanelKind, index: usize) -> bool {
+    let from = match panels.iter().position(|k| *k == kind) {
+        Some(from) => from,
+        None => return false,
+    };
+    // Either side of the panel is where it already is.
+    let to = if index > from { index - 1 } else { index };
+    let to = to.min(panels.len() - 1);
+    if to == from {
+        return false;
+    }
+    panels.remove(from);
+    panels.insert(to, kind);
+    true
+}
+
+/// Where to place a popup of `size` for the line whose top left corner is at
+/// `line_origin`. It goes below the line, or above it when it would run past
+/// the bottom of `tab_size` and there is more room above, and is moved left
+/// to stay within the right edge.
+fn popup_origin(
+    line_origin: Point,
+    line_height: f64,
+    size: Size,
+    tab_size: Size,
+) -> Point {
+    let mut origin = line_origin + Vec2::new(0.0, line_height);
+    if origin.y + size.height + 1.0 > tab_size.height {
+        let space_below = tab_size.height - origin.y;
+        let space_above = line_origin.y;
+        if space_above > space_below {
+            origin.y = (line_origin.y - size.height).max(0.0);
+        }
+    }
+    if origin.x + size.width + 1.0 > tab_size.width {
+        origin.x = tab_size.width - size.width - 1.0;
+    }
+    if origin.x <= 0.0 {
+        origin.x = 0.0;
+    }
+    origin
+}
+
+/// The diagnostics covering `offset`, most severe first.
+fn diagnostics_at(
+    diagnostics: &[EditorDiagnostic],
+    offset: usize,
+) -> Vec<&EditorDiagnostic> {
+    let mut diagnostics = diagnostics
+        .iter()
+        .filter(|d| d.contains(offset))
+        .collect::<Vec<_>>();
+    diagnostics.sort_by_key(|d| d.severity_rank());
+    diagnostics
+}
+
 #[derive(Clone, Copy, PartialEq, Data, Serialize, Deserialize, Hash, Eq, Debug)]
 pub enum PanelKind {
     FileExplorer,
@@ -347,6 +566,9 @@ pub enum PanelKind {
     Terminal,
     Search,
     Problem,
+    Outline,
+    CallHierarchy,
+    TypeHierarchy,
 }
 
 impl PanelKind {
@@ -358,6 +580,24 @@ impl PanelKind {
             PanelKind::Terminal => "terminal.svg",
             PanelKind::Search => "search.svg",
             PanelKind::Problem => "error.svg",
+            PanelKind::Outline => "symbol-class.svg",
+            PanelKind::CallHierarchy => "symbol-method.svg",
+            PanelKind::TypeHierarchy => "symbol-interface.svg",
+        }
+    }
+
+    /// Where the panel is placed when no position hosts it
+    pub fn default_position(&self) -> PanelPosition {
+        match &self {
+            PanelKind::FileExplorer
+            | PanelKind::SourceControl
+            | PanelKind::Plugin
+            | PanelKind::Outline => PanelPosition::LeftTop,
+            PanelKind::Terminal
+            | PanelKind::Search
+            | PanelKind::Problem
+            | PanelKind::CallHierarchy
+            | PanelKind::TypeHierarchy => PanelPosition::BottomLeft,
         }
     }
 }
@@ -390,6 +630,19 @@ pub struct PanelSize {
     pub right_split: f64,
 }
 
+impl Default for PanelSize {
+    fn default() -> Self {
+        Self {
+            left: 250.0,
+            left_split: 0.5,
+            bottom: 300.0,
+            bottom_split: 0.5,
+            right: 250.0,
+            right_split: 0.5,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct WorkProgress {
     pub token: ProgressToken,
@@ -398,6 +651,46 @@ pub struct WorkProgress {
     pub percentage: Option<u32>,
 }
 
+/// How many closed editors are remembered to be reopened.
+const CLOSED_EDITORS_LIMIT: usize = 20;
+
+/// The horizontal padding around the label of the signature popup.
+pub const SIGNATURE_PADDING: f64 = 5.0;
+
+/// The popups drawn over the editor that a single Escape closes, one at a
+/// time. Context menus are not listed as they live on the window and take
+/// focus while shown, so they handle Escape themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    Hover,
+    Signature,
+    Completion,
+    CodeAction,
+    Peek,
+    FindBar,
+}
+
+impl Overlay {
+    /// The order in which Escape closes the overlays, topmost first.
+    pub const ESCAPE_ORDER: [Overlay; 6] = [
+        Overlay::Hover,
+        Overlay::Signature,
+        Overlay::Completion,
+        Overlay::CodeAction,
+        Overlay::Peek,
+        Overlay::FindBar,
+    ];
+
+    /// The overlay the next Escape closes among the ones `is_open` says are
+    /// open.
+    pub fn topmost(is_open: impl Fn(Overlay) -> bool) -> Option<Overlay> {
+        Self::ESCAPE_ORDER
+            .iter()
+            .copied()
+            .find(|overlay| is_open(*overlay))
+    }
+}
+
 #[derive(Clone, PartialEq, Data)]
 pub enum FocusArea {
     Palette,
@@ -409,6 +702,18 @@ pub enum FocusArea {
 #[derive(Clone)]
 pub enum DragContent {
     EditorTab(WidgetId, usize, EditorTabChild, TabRect),
+    /// A panel icon in the activity bar and its index there
+    Panel(PanelKind, usize),
+}
+
+/// What a tab does once the files being saved are all saved
+#[derive(Clone, Debug, PartialEq)]
+pub enum AfterSave {
+    CloseTab,
+    /// Opens the workspace on another SSH host, as the user and host given
+    SwitchSshHost(String, String),
+    /// Closes the given editor views of an editor tab
+    CloseEditors(WidgetId, Vec<WidgetId>),
 }
 
 #[derive(Clone, Lens)]
@@ -419,11 +724,16 @@ pub struct LapceTabData {
     pub main_split: LapceMainSplitData,
     pub completion: Arc<CompletionData>,
     pub hover: Arc<HoverData>,
+    pub signature: Arc<SignatureData>,
+    pub peek: Arc<PeekData>,
     pub terminal: Arc<TerminalSplitData>,
     pub palette: Arc<PaletteData>,
     pub find: Arc<Find>,
     pub source_control: Arc<SourceControlData>,
     pub problem: Arc<ProblemData>,
+    pub outline: Arc<OutlineData>,
+    pub call_hierarchy: Arc<HierarchyData>,
+    pub type_hierarchy: Arc<HierarchyData>,
     pub search: Arc<SearchData>,
     pub plugin: Arc<PluginData>,
     pub picker: Arc<FilePickerData>,
@@ -445,7 +755,17 @@ pub struct LapceTabData {
     pub focus_area: FocusArea,
     pub db: Arc<LapceDb>,
     pub progresses: im::Vector<WorkProgress>,
+    /// A short message shown in the status bar, e.g. why a command
+    /// couldn't be run.
+    pub status_message: Option<Arc<String>>,
+    /// Whether each panel was shown before the editor was maximized, so
+    /// they can be restored exactly. `Some` while the editor is maximized.
+    pub panels_before_maximize: Option<im::HashMap<PanelPosition, bool>>,
+    /// Where recently closed editors were, most recent last.
+    pub closed_editors: im::Vector<EditorLocationNew>,
     pub drag: Arc<Option<(Vec2, DragContent)>>,
+    /// What to do once the files being saved are all saved
+    pub after_save: Option<AfterSave>,
 }
 
 impl Data for LapceTabData {
@@ -453,6 +773,8 @@ impl Data for LapceTabData {
         self.main_split.same(&other.main_split)
             && self.completion.same(&other.completion)
             && self.hover.same(&other.hover)
+            && self.signature.same(&other.signature)
+            && self.peek.same(&other.peek)
             && self.palette.same(&other.palette)
             && self.workspace.same(&other.workspace)
             && self.source_control.same(&other.source_control)
@@ -467,9 +789,13 @@ impl Data for LapceTabData {
             && self.proxy_status.same(&other.proxy_status)
             && self.find.same(&other.find)
             && self.progresses.ptr_eq(&other.progresses)
+            && self.status_message.same(&other.status_message)
             && self.file_explorer.same(&other.file_explorer)
             && self.plugin.same(&other.plugin)
             && self.problem.same(&other.problem)
+            && self.outline.same(&other.outline)
+            && self.call_hierarchy.same(&other.call_hierarchy)
+            && self.type_hierarchy.same(&other.type_hierarchy)
             && self.search.same(&other.search)
             && self.installed_plugins.same(&other.installed_plugins)
             && self.picker.same(&other.picker)
@@ -509,9 +835,22 @@ impl LapceTabData {
             term_sender.clone(),
             event_sink.clone(),
         ));
+        for name in config.lapce.disabled_plugins.iter() {
+            proxy.disable_plugin(name);
+        }
         let palette = Arc::new(PaletteData::new(proxy.clone()));
-        let completion = Arc::new(CompletionData::new());
+        let mut completion = CompletionData::new();
+        completion.size = Size::new(
+            config.editor.completion_width as f64,
+            config.editor.completion_height as f64,
+        );
+        if workspace.path.is_some() {
+            completion.history =
+                db.get_completion_history(&workspace).unwrap_or_default();
+        }
+        let completion = Arc::new(completion);
         let hover = Arc::new(HoverData::new());
+        let signature = Arc::new(SignatureData::new());
         let source_control = Arc::new(SourceControlData::new());
         let settings = Arc::new(LapceSettingsPanelData::new());
         let plugin = Arc::new(PluginData::new());
@@ -521,7 +860,10 @@ impl LapceTabData {
             proxy.clone(),
             event_sink.clone(),
         ));
-        let search = Arc::new(SearchData::new());
+        let mut search = SearchData::new();
+        search.options = db.get_search_options().unwrap_or_default();
+        search.recent_globs = db.get_recent_search_globs().unwrap_or_default();
+        let search = Arc::new(search);
         let file_picker = Arc::new(FilePickerData::new());
 
         let mut main_split = LapceMainSplitData::new(
@@ -534,6 +876,16 @@ impl LapceTabData {
             Arc::new(workspace.clone()),
             db.clone(),
         );
+        let peek = Arc::new(PeekData::new());
+        main_split.editors.insert(
+            peek.editor_view_id,
+            Arc::new(LapceEditorData::new(
+                Some(peek.editor_view_id),
+                None,
+                BufferContent::Local(LocalBufferKind::Empty),
+                &config,
+            )),
+        );
         main_split.add_editor(
             source_control.editor_view_id,
             None,
@@ -562,6 +914,27 @@ impl LapceTabData {
             &config,
             event_sink.clone(),
         );
+        main_split.add_editor(
+            search.replace_view_id,
+            None,
+            LocalBufferKind::Replace,
+            &config,
+            event_sink.clone(),
+        );
+        main_split.add_editor(
+            search.globs_view_id,
+            None,
+            LocalBufferKind::SearchGlobs,
+            &config,
+            event_sink.clone(),
+        );
+        main_split.add_editor(
+            plugin.filter_view_id,
+            None,
+            LocalBufferKind::PluginFilter,
+            &config,
+            event_sink.clone(),
+        );
         main_split.add_editor(
             file_picker.editor_view_id,
             None,
@@ -573,16 +946,21 @@ impl LapceTabData {
         let terminal = Arc::new(TerminalSplitData::new(proxy.clone()));
         let problem = Arc::new(ProblemData::new());
 
+        let mut left_panels = vec![
+            PanelKind::FileExplorer,
+            PanelKind::SourceControl,
+            PanelKind::Plugin,
+            PanelKind::Outline,
+        ];
+        if let Ok(order) = db.get_panel_order() {
+            sort_panels(&mut left_panels, &order);
+        }
         let mut panels = im::HashMap::new();
         panels.insert(
             PanelPosition::LeftTop,
             Arc::new(PanelData {
-                active: PanelKind::FileExplorer,
-                widgets: vec![
-                    PanelKind::FileExplorer,
-                    PanelKind::SourceControl,
-                    PanelKind::Plugin,
-                ],
+                active: left_panels[0],
+                widgets: left_panels,
                 shown: true,
                 maximized: false,
             }),
@@ -595,11 +973,16 @@ impl LapceTabData {
                     PanelKind::Terminal,
                     PanelKind::Search,
                     PanelKind::Problem,
+                    PanelKind::CallHierarchy,
+                    PanelKind::TypeHierarchy,
                 ],
                 shown: true,
                 maximized: false,
             }),
         );
+        if let Some(info) = workspace_info.as_ref() {
+            PanelInfo::restore(&info.panels, &mut panels);
+        }
         let focus = (*main_split.active).unwrap_or(*main_split.split_id);
         let mut tab = Self {
             id: tab_id,
@@ -609,9 +992,16 @@ impl LapceTabData {
             main_split,
             completion,
             hover,
+            signature,
+            peek,
             terminal,
             plugin,
             problem,
+            outline: Arc::new(OutlineData::new()),
+            call_hierarchy: Arc::new(HierarchyData::new(
+                HierarchyKind::IncomingCalls,
+            )),
+            type_hierarchy: Arc::new(HierarchyData::new(HierarchyKind::Supertypes)),
             search,
             plugins: Arc::new(Vec::new()),
             installed_plugins: Arc::new(HashMap::new()),
@@ -628,20 +1018,17 @@ impl LapceTabData {
             keypress,
             window_origin: Point::ZERO,
             panels,
-            panel_size: PanelSize {
-                left: 250.0,
-                left_split: 0.5,
-                bottom: 300.0,
-                bottom_split: 0.5,
-                right: 250.0,
-                right_split: 0.5,
-            },
+            panel_size: PanelSize::default(),
             panel_active: PanelPosition::LeftTop,
             config,
             focus_area: FocusArea::Editor,
             db,
             progresses: im::Vector::new(),
+            status_message: None,
+            panels_before_maximize: None,
+            closed_editors: im::Vector::new(),
             drag: Arc::new(None),
+            after_save: None,
         };
         tab.start_update_process(event_sink);
         tab
@@ -654,7 +1041,8 @@ impl LapceTabData {
             .get(&self.main_split.split_id)
             .unwrap();
         WorkspaceInfo {
-            split: main_split_data.split_info(self, self.config.editor.tab_width),
+            split: main_split_data.split_info(self),
+            panels: PanelInfo::of(&self.panels),
         }
     }
 
@@ -706,6 +1094,7 @@ impl LapceTabData {
             main_split: self.main_split.clone(),
             completion: self.completion.clone(),
             hover: self.hover.clone(),
+            signature: self.signature.clone(),
             source_control: self.source_control.clone(),
             proxy: self.proxy.clone(),
             find: self.find.clone(),
@@ -731,36 +1120,58 @@ impl LapceTabData {
                 let prev_offset = buffer.prev_code_boundary(offset);
                 let empty_vec = Vec::new();
                 let code_actions =
-                    buffer.code_actions.get(&prev_offset).unwrap_or(&empty_vec);
-
-                let action_text_layouts: Vec<PietTextLayout> = code_actions
-                    .iter()
-                    .map(|code_action| {
-                        let title = match code_action {
-                            CodeActionOrCommand::Command(cmd) => {
-                                cmd.title.to_string()
-                            }
-                            CodeActionOrCommand::CodeAction(action) => {
-                                action.title.to_string()
-                            }
-                        };
-
-                        text.new_text_layout(title)
-                            .font(FontFamily::SYSTEM_UI, 14.0)
-                            .build()
-                            .unwrap()
-                    })
-                    .collect();
+                    buffer.code_actions(prev_offset).unwrap_or(&empty_vec);
 
+                let line_height = self.config.editor.line_height as f64;
+                let rows = code_action_rows(code_actions);
                 let mut width = 0.0;
-                for text_layout in &action_text_layouts {
-                    let line_width = text_layout.size().width + 10.0;
+                for row in &rows {
+                    let text_layout = match row {
+                        CodeActionRow::Header(group) => text
+                            .new_text_layout(group.title())
+                            .font(FontFamily::SYSTEM_UI, 12.0)
+                            .build()
+                            .unwrap(),
+                        CodeActionRow::Action(i) => {
+                            let code_action = &code_actions[*i];
+                            let title = match code_action {
+                                CodeActionOrCommand::Command(cmd) => {
+                                    cmd.title.to_string()
+                                }
+                                CodeActionOrCommand::CodeAction(action) => {
+                                    action.title.to_string()
+                                }
+                            };
+                            let weight = if is_preferred(code_action) {
+                                FontWeight::BOLD
+                            } else {
+                                FontWeight::REGULAR
+                            };
+                            text.new_text_layout(title)
+                                .font(FontFamily::SYSTEM_UI, 14.0)
+                                .default_attribute(weight)
+                                .build()
+                                .unwrap()
+                        }
+                    };
+                    // The icon column is as wide as a line is high.
+                    let line_width = text_layout.size().width + line_height + 10.0;
                     if line_width > width {
                         width = line_width;
                     }
                 }
-                let line_height = self.config.editor.line_height as f64;
-                Size::new(width, code_actions.len() as f64 * line_height)
+                Size::new(width, rows.len() as f64 * line_height)
+            }
+        }
+    }
+
+    /// Moves a panel in the activity bar to `index`, counted before it's
+    /// taken out, and saves the new order.
+    pub fn move_panel(&mut self, kind: PanelKind, index: usize) {
+        if let Some(panel) = self.panels.get_mut(&PanelPosition::LeftTop) {
+            let panel = Arc::make_mut(panel);
+            if move_panel(&mut panel.widgets, kind, index) {
+                let _ = self.db.save_panel_order_async(panel.widgets.clone());
             }
         }
     }
@@ -774,6 +1185,47 @@ impl LapceTabData {
         None
     }
 
+    /// Combine all the running work done progresses into a single line for
+    /// the status bar, e.g. "3 tasks running: Indexing (42%)".
+    ///
+    /// The first progress reporting a percentage is used as the primary task,
+    /// falling back to the oldest one. When more than one progress reports a
+    /// percentage, the shown percentage is their average.
+    pub fn progress_summary(&self) -> Option<String> {
+        let primary = self
+            .progresses
+            .iter()
+            .find(|p| p.percentage.is_some())
+            .or_else(|| self.progresses.front())?;
+
+        let mut text = primary.title.clone();
+        if let Some(message) = primary.message.as_ref() {
+            if !message.is_empty() {
+                text += ": ";
+                text += message;
+            }
+        }
+        if let Some(percentage) = self.progress_percentage() {
+            text += &format!(" ({}%)", percentage);
+        }
+
+        if self.progresses.len() > 1 {
+            text = format!("{} tasks running: {}", self.progresses.len(), text);
+        }
+        Some(text)
+    }
+
+    /// The overall percentage of the running work done progresses, averaged
+    /// over the ones that report a percentage.
+    pub fn progress_percentage(&self) -> Option<u32> {
+        let percentages: Vec<u32> =
+            self.progresses.iter().filter_map(|p| p.percentage).collect();
+        if percentages.is_empty() {
+            return None;
+        }
+        Some(percentages.iter().sum::<u32>() / percentages.len() as u32)
+    }
+
     pub fn update_from_editor_buffer_data(
         &mut self,
         editor_buffer_data: LapceEditorBufferData,
@@ -782,6 +1234,7 @@ impl LapceTabData {
     ) {
         self.completion = editor_buffer_data.completion.clone();
         self.hover = editor_buffer_data.hover.clone();
+        self.signature = editor_buffer_data.signature.clone();
         self.main_split = editor_buffer_data.main_split.clone();
         self.find = editor_buffer_data.find.clone();
         if !editor_buffer_data.editor.same(editor) {
@@ -833,8 +1286,9 @@ impl LapceTabData {
             BufferContent::File(path) => {
                 let buffer = self.main_split.open_files.get(path).unwrap();
                 let offset = editor.cursor.offset();
-                let (line, col) =
-                    buffer.offset_to_line_col(offset, self.config.editor.tab_width);
+                let (line, col) = buffer
+                    .offset_to_line_col(offset, buffer.tab_width(&self.config));
+                let line = buffer.visual_line(line);
                 let width = config.editor_char_width(text);
                 let x = col as f64 * width;
                 let y = (line + 1) as f64 * line_height;
@@ -868,31 +1322,23 @@ impl LapceTabData {
             BufferContent::File(path) => {
                 let buffer = self.main_split.open_files.get(path).unwrap();
                 let offset = self.completion.offset;
-                let (line, col) =
-                    buffer.offset_to_line_col(offset, self.config.editor.tab_width);
+                let (line, col) = buffer
+                    .offset_to_line_col(offset, buffer.tab_width(&self.config));
+                let line = buffer.visual_line(line);
                 let width = config.editor_char_width(text);
                 let x = col as f64 * width - line_height - 5.0;
-                let y = (line + 1) as f64 * line_height;
-                let mut origin = editor.window_origin - self.window_origin.to_vec2()
+                let y = line as f64 * line_height;
+                let line_origin = editor.window_origin
+                    - self.window_origin.to_vec2()
                     + Vec2::new(x, y);
-                if origin.y + self.completion.size.height + 1.0 > tab_size.height {
-                    let height = self
-                        .completion
+                let size = Size::new(
+                    self.completion.size.width,
+                    self.completion
                         .size
                         .height
-                        .min(self.completion.len() as f64 * line_height);
-                    origin.y = editor.window_origin.y - self.window_origin.y
-                        + line as f64 * line_height
-                        - height;
-                }
-                if origin.x + self.completion.size.width + 1.0 > tab_size.width {
-                    origin.x = tab_size.width - self.completion.size.width - 1.0;
-                }
-                if origin.x <= 0.0 {
-                    origin.x = 0.0;
-                }
-
-                origin
+                        .min(self.completion.rows().len() as f64 * line_height),
+                );
+                popup_origin(line_origin, line_height, size, tab_size)
             }
         }
     }
@@ -921,8 +1367,9 @@ impl LapceTabData {
             BufferContent::File(path) => {
                 let buffer = self.main_split.open_files.get(path).unwrap();
                 let offset = self.hover.offset;
-                let (line, col) =
-                    buffer.offset_to_line_col(offset, self.config.editor.tab_width);
+                let (line, col) = buffer
+                    .offset_to_line_col(offset, buffer.tab_width(&self.config));
+                let line = buffer.visual_line(line);
                 let width = config.editor_char_width(text);
                 let x = col as f64 * width - line_height - 5.0;
                 let y = (line + 1) as f64 * line_height;
@@ -946,6 +1393,102 @@ impl LapceTabData {
         }
     }
 
+    /// The size of the signature popup, which fits its label on one line.
+    pub fn signature_size(&self, text: &mut PietText, config: &Config) -> Size {
+        let chars = self
+            .signature
+            .label()
+            .map(|label| label.chars().count())
+            .unwrap_or(0);
+        Size::new(
+            chars as f64 * config.editor_char_width(text) + SIGNATURE_PADDING * 2.0,
+            self.config.editor.line_height as f64,
+        )
+    }
+
+    /// The origin of the signature popup, which sits above the cursor line,
+    /// or below it when there's no room above.
+    pub fn signature_origin(
+        &self,
+        text: &mut PietText,
+        tab_size: Size,
+        config: &Config,
+    ) -> Point {
+        let line_height = self.config.editor.line_height as f64;
+
+        let editor = self.main_split.active_editor();
+        let editor = match editor {
+            Some(editor) => editor,
+            None => return Point::ZERO,
+        };
+
+        match &editor.content {
+            BufferContent::Local(_) => {
+                editor.window_origin - self.window_origin.to_vec2()
+            }
+            BufferContent::Value(_) => {
+                editor.window_origin - self.window_origin.to_vec2()
+            }
+            BufferContent::File(path) => {
+                let buffer = self.main_split.open_files.get(path).unwrap();
+                let offset = self.signature.offset;
+                let (line, col) = buffer
+                    .offset_to_line_col(offset, buffer.tab_width(&self.config));
+                let line = buffer.visual_line(line);
+                let width = config.editor_char_width(text);
+                let size = self.signature_size(text, config);
+                let x = col as f64 * width - SIGNATURE_PADDING;
+                let y = line as f64 * line_height - size.height;
+                let mut origin = editor.window_origin - self.window_origin.to_vec2()
+                    + Vec2::new(x, y);
+                if origin.y < 0.0 {
+                    origin.y += size.height + line_height;
+                }
+                if origin.x + size.width + 1.0 > tab_size.width {
+                    origin.x = tab_size.width - size.width - 1.0;
+                }
+                if origin.x <= 0.0 {
+                    origin.x = 0.0;
+                }
+
+                origin
+            }
+        }
+    }
+
+    /// The size of the peek, as wide as the editor it was opened from.
+    pub fn peek_size(&self) -> Size {
+        let width = self
+            .main_split
+            .editors
+            .get(&self.peek.parent_view_id)
+            .map(|editor| editor.size.borrow().width)
+            .unwrap_or(0.0);
+        self.peek.size(width, self.config.editor.line_height as f64)
+    }
+
+    /// The origin of the peek, below the line it was opened from, or `None`
+    /// when the editor it was opened from is gone.
+    pub fn peek_origin(&self, tab_size: Size) -> Option<Point> {
+        let line_height = self.config.editor.line_height as f64;
+        let editor = self.main_split.editors.get(&self.peek.parent_view_id)?;
+        let path = match &editor.content {
+            BufferContent::File(path) => path,
+            _ => return None,
+        };
+        let buffer = self.main_split.open_files.get(path)?;
+        let offset = self.peek.offset.min(buffer.len());
+        let line = buffer.visual_line(buffer.line_of_offset(offset));
+        let line_origin = editor.window_origin - self.window_origin.to_vec2()
+            + Vec2::new(editor.scroll_offset.x, line as f64 * line_height);
+        Some(popup_origin(
+            line_origin,
+            line_height,
+            self.peek_size(),
+            tab_size,
+        ))
+    }
+
     pub fn palette_view_data(&self) -> PaletteViewData {
         PaletteViewData {
             palette: self.palette.clone(),
@@ -959,61 +1502,344 @@ impl LapceTabData {
         }
     }
 
-    pub fn run_workbench_command(
-        &mut self,
-        ctx: &mut EventCtx,
-        command: &LapceWorkbenchCommand,
+    /// The files open in the tab that have changes that aren't saved
+    pub fn unsaved_files(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .main_split
+            .open_files
+            .iter()
+            .filter(|(_, buffer)| buffer.dirty())
+            .map(|(path, _)| path.clone())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// The unsaved files open in the given editor views
+    pub fn unsaved_editor_files(&self, view_ids: &[WidgetId]) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = view_ids
+            .iter()
+            .filter_map(|view_id| {
+                match &self.main_split.editors.get(view_id)?.content {
+                    BufferContent::File(path) => Some(path),
+                    _ => None,
+                }
+            })
+            .filter(|path| {
+                self.main_split
+                    .open_files
+                    .get(*path)
+                    .map(|buffer| buffer.dirty())
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// The editor tab and its editor views given as the data of a prompt
+    /// answer
+    fn editors_from_data(
+        &self,
         data: Option<serde_json::Value>,
-        _count: Option<usize>,
-        _env: &Env,
-    ) {
-        match command {
-            LapceWorkbenchCommand::CloseFolder => {
-                if self.workspace.path.is_some() {
-                    let mut workspace = (*self.workspace).clone();
-                    workspace.path = None;
-                    let _ = ctx.submit_command(Command::new(
-                        LAPCE_UI_COMMAND,
-                        LapceUICommand::SetWorkspace(workspace),
+    ) -> Option<(WidgetId, Vec<WidgetId>)> {
+        let (editor_tab_id, view_ids) =
+            serde_json::from_value::<(u64, Vec<u64>)>(data?).ok()?;
+        let editor_tab_id = *self
+            .main_split
+            .editor_tabs
+            .keys()
+            .find(|id| id.to_raw() == editor_tab_id)?;
+        let view_ids = self
+            .main_split
+            .editors
+            .keys()
+            .filter(|id| view_ids.contains(&id.to_raw()))
+            .copied()
+            .collect();
+        Some((editor_tab_id, view_ids))
+    }
+
+    /// Runs what was waiting for the files being saved, once none of them
+    /// are left unsaved.
+    pub fn run_after_save(&mut self, ctx: &mut EventCtx) {
+        let waiting = match &self.after_save {
+            Some(AfterSave::CloseEditors(_, view_ids)) => {
+                self.unsaved_editor_files(view_ids)
+            }
+            Some(_) => self.unsaved_files(),
+            None => return,
+        };
+        if !waiting.is_empty() {
+            return;
+        }
+        let (command, target) = match self.after_save.take() {
+            Some(AfterSave::CloseTab) => {
+                (LapceUICommand::ForceCloseTabId(self.id), Target::Auto)
+            }
+            Some(AfterSave::SwitchSshHost(user, host)) => {
+                match self.workspace.path.clone() {
+                    Some(path) => (
+                        LapceUICommand::OpenRemoteWorkspace { user, host, path },
                         Target::Auto,
-                    ));
+                    ),
+                    None => return,
                 }
             }
-            LapceWorkbenchCommand::OpenFolder => {
-                if !self.workspace.kind.is_remote() {
-                    let event_sink = ctx.get_external_handle();
-                    let window_id = self.window_id;
-                    thread::spawn(move || {
-                        let dirs = directories::UserDirs::new();
+            Some(AfterSave::CloseEditors(editor_tab_id, view_ids)) => (
+                LapceUICommand::EditorTabCloseChildren(view_ids),
+                Target::Widget(editor_tab_id),
+            ),
+            None => return,
+        };
+        ctx.submit_command(Command::new(LAPCE_UI_COMMAND, command, target));
+    }
 
-                        let dir = dirs
-                            .as_ref()
-                            .and_then(|u| u.home_dir().to_str())
-                            .unwrap_or(".");
+    fn hide_prompt(&self, ctx: &mut EventCtx) {
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::HideMenu,
+            Target::Auto,
+        ));
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(self.id),
+        ));
+    }
 
-                        if let Some(folder) =
-                            tinyfiledialogs::select_folder_dialog("Open folder", dir)
-                        {
-                            let path = PathBuf::from(folder);
-                            let workspace = LapceWorkspace {
-                                kind: LapceWorkspaceType::Local,
-                                path: Some(path),
-                                last_open: std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_secs(),
-                            };
+    /// Stops or starts an installed plugin, and remembers in the settings
+    /// file whether it's disabled.
+    pub fn toggle_plugin(&mut self, name: &str) {
+        let config = Arc::make_mut(&mut self.config);
+        let disabled = &mut config.lapce.disabled_plugins;
+        if let Some(index) = disabled.iter().position(|n| n == name) {
+            disabled.remove(index);
+            self.proxy.enable_plugin(name);
+        } else {
+            disabled.push(name.to_string());
+            self.proxy.disable_plugin(name);
+        }
+        Config::update_file(
+            "lapce.disabled-plugins",
+            toml::Value::Array(
+                disabled
+                    .iter()
+                    .map(|n| toml::Value::String(n.clone()))
+                    .collect(),
+            ),
+        );
+    }
 
-                            let _ = event_sink.submit_command(
-                                LAPCE_UI_COMMAND,
-                                LapceUICommand::SetWorkspace(workspace),
-                                Target::Window(window_id),
+    /// Replaces the installed plugins, forgetting the updates that are no
+    /// longer newer than what's installed, and looks for new updates.
+    pub fn set_installed_plugins(
+        &mut self,
+        ctx: &mut EventCtx,
+        plugins: HashMap<String, PluginDescription>,
+    ) {
+        self.installed_plugins = Arc::new(plugins);
+        let installed_plugins = self.installed_plugins.clone();
+        Arc::make_mut(&mut self.plugin)
+            .updates
+            .retain(|name, version| {
+                installed_plugins
+                    .get(name)
+                    .map(|plugin| version_is_newer(version, &plugin.version))
+                    .unwrap_or(false)
+            });
+
+        let plugins: Vec<PluginDescription> =
+            self.installed_plugins.values().cloned().collect();
+        let tab_id = self.id;
+        let event_sink = ctx.get_external_handle();
+        thread::spawn(move || {
+            for plugin in plugins {
+                // Plugins whose latest version can't be fetched keep their
+                // current state.
+                if let Ok(latest) =
+                    LapceData::load_plgin_description(&plugin.repository)
+                {
+                    if version_is_newer(&latest.version, &plugin.version) {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::PluginUpdateAvailable(
+                                plugin.name,
+                                latest.version,
+                            ),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn plugin_update_available(&mut self, name: &str, version: &str) {
+        let outdated = self
+            .installed_plugins
+            .get(name)
+            .map(|plugin| version_is_newer(version, &plugin.version))
+            .unwrap_or(false);
+        if outdated {
+            Arc::make_mut(&mut self.plugin)
+                .updates
+                .insert(name.to_string(), version.to_string());
+        }
+    }
+
+    /// Installs the latest version of the plugin over the current one. The
+    /// proxy starts the new version once it's downloaded.
+    pub fn update_plugin(&self, name: &str) {
+        let repository = match self.installed_plugins.get(name) {
+            Some(plugin) => plugin.repository.clone(),
+            None => return,
+        };
+        let proxy = self.proxy.clone();
+        thread::spawn(move || {
+            if let Ok(plugin) = LapceData::load_plgin_description(&repository) {
+                proxy.install_plugin(&plugin);
+            }
+        });
+    }
+
+    /// Searches the plugin registry for the plugin filter. Failed searches
+    /// keep the previous results.
+    pub fn search_plugin_marketplace(&self, ctx: &mut EventCtx) {
+        let query = self.plugin.filter.clone();
+        if query.is_empty() {
+            return;
+        }
+        let tab_id = self.id;
+        let event_sink = ctx.get_external_handle();
+        self.proxy.search_plugins(
+            query.clone(),
+            Box::new(move |result| {
+                if let Ok(value) = result {
+                    if let Ok(plugins) =
+                        serde_json::from_value::<Vec<PluginDescription>>(value)
+                    {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::PluginMarketplaceResults(query, plugins),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Clears the plugin filter and its input, and goes back to listing the
+    /// installed plugins.
+    fn reset_plugin_filter(&mut self) {
+        let plugin = Arc::make_mut(&mut self.plugin);
+        plugin.filter.clear();
+        plugin.marketplace = false;
+        plugin.marketplace_results.clear();
+        let buffer = self
+            .main_split
+            .local_buffers
+            .get_mut(&LocalBufferKind::PluginFilter)
+            .unwrap();
+        Arc::make_mut(buffer).load_content("");
+        let editor = self
+            .main_split
+            .editors
+            .get_mut(&self.plugin.filter_view_id)
+            .unwrap();
+        Arc::make_mut(editor).cursor = if self.config.lapce.modal {
+            Cursor::new(CursorMode::Normal(0), None)
+        } else {
+            Cursor::new(CursorMode::Insert(Selection::caret(0)), None)
+        };
+    }
+
+    /// Connects to the proxy again after the connection was lost, and opens
+    /// the buffers on the new connection with their current content.
+    pub fn reconnect_proxy(&self) {
+        if !matches!(*self.proxy_status, ProxyStatus::Disconnected) {
+            return;
+        }
+
+        self.proxy.reconnect((*self.workspace).clone());
+        for name in self.config.lapce.disabled_plugins.iter() {
+            self.proxy.disable_plugin(name);
+        }
+        for (path, buffer) in self.main_split.open_files.iter() {
+            if buffer.loaded() {
+                self.proxy.reopen_buffer(
+                    buffer.id(),
+                    path.clone(),
+                    buffer.rev(),
+                    buffer.rope().to_string(),
+                );
+                if let Some(language) = buffer.language_override() {
+                    self.proxy
+                        .set_language(buffer.id(), Some(language.language_id()));
+                }
+            }
+        }
+    }
+
+    pub fn run_workbench_command(
+        &mut self,
+        ctx: &mut EventCtx,
+        command: &LapceWorkbenchCommand,
+        data: Option<serde_json::Value>,
+        _count: Option<usize>,
+        _env: &Env,
+    ) {
+        match command {
+            LapceWorkbenchCommand::CloseFolder => {
+                if self.workspace.path.is_some() {
+                    let mut workspace = (*self.workspace).clone();
+                    workspace.path = None;
+                    let _ = ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::SetWorkspace(workspace),
+                        Target::Auto,
+                    ));
+                }
+            }
+            LapceWorkbenchCommand::OpenFolder => {
+                if !self.workspace.kind.is_remote() {
+                    let event_sink = ctx.get_external_handle();
+                    let window_id = self.window_id;
+                    thread::spawn(move || {
+                        let dirs = directories::UserDirs::new();
+
+                        let dir = dirs
+                            .as_ref()
+                            .and_then(|u| u.home_dir().to_str())
+                            .unwrap_or(".");
+
+                        if let Some(folder) =
+                            tinyfiledialogs::select_folder_dialog("Open folder", dir)
+                        {
+                            let path = PathBuf::from(folder);
+                            let workspace = LapceWorkspace {
+                                kind: LapceWorkspaceType::Local,
+                                path: Some(path),
+                                last_open: std::time::SystemTime::now()
+                                    .duration_since(std::time::UNIX_EPOCH)
+                                    .unwrap()
+                                    .as_secs(),
+                            };
+
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::SetWorkspace(workspace),
+                                Target::Window(window_id),
                             );
                         }
                     });
                 } else {
                     let picker = Arc::make_mut(&mut self.picker);
                     picker.active = true;
+                    picker.save_as = None;
                     if let Some(node) = picker.get_file_node(&picker.pwd) {
                         if !node.read {
                             let tab_id = self.id;
@@ -1085,6 +1911,7 @@ impl LapceTabData {
                 } else {
                     let picker = Arc::make_mut(&mut self.picker);
                     picker.active = true;
+                    picker.save_as = None;
                     if let Some(node) = picker.get_file_node(&picker.pwd) {
                         if !node.read {
                             let tab_id = self.id;
@@ -1134,6 +1961,39 @@ impl LapceTabData {
                 config.lapce.modal = false;
                 Config::update_file("lapce.modal", toml::Value::Boolean(false));
             }
+            LapceWorkbenchCommand::ReopenClosedTab => {
+                self.reopen_closed_editor(ctx);
+            }
+            LapceWorkbenchCommand::CloseOtherTabs => {
+                if let Some(editor) = self.main_split.active_editor() {
+                    if let Some(tab_id) = editor.tab_id {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::CloseOtherTabs(editor.view_id),
+                            Target::Widget(tab_id),
+                        ));
+                    }
+                }
+            }
+            LapceWorkbenchCommand::CloseTabsToRight => {
+                if let Some(editor) = self.main_split.active_editor() {
+                    if let Some(tab_id) = editor.tab_id {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::CloseTabsToRight(editor.view_id),
+                            Target::Widget(tab_id),
+                        ));
+                    }
+                }
+            }
+            LapceWorkbenchCommand::RevealActiveFileInFileExplorer => {
+                if let Some(editor) = self.main_split.active_editor() {
+                    if let BufferContent::File(path) = &editor.content {
+                        let path = path.clone();
+                        self.reveal_in_file_explorer(ctx, &path);
+                    }
+                }
+            }
             LapceWorkbenchCommand::ChangeTheme => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1141,6 +2001,53 @@ impl LapceTabData {
                     Target::Widget(self.palette.widget_id),
                 ));
             }
+            LapceWorkbenchCommand::ChangeLanguageMode => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::Language)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
+            LapceWorkbenchCommand::ReopenWithEncoding => {
+                if let Some(encoding) =
+                    data.and_then(|data| serde_json::from_value::<String>(data).ok())
+                {
+                    self.main_split.reopen_with_encoding(ctx, &encoding);
+                }
+            }
+            LapceWorkbenchCommand::ChangeLineEndingsToLf => {
+                self.main_split.change_line_endings(LineEnding::Lf);
+            }
+            LapceWorkbenchCommand::ChangeLineEndingsToCrlf => {
+                self.main_split.change_line_endings(LineEnding::CrLf);
+            }
+            LapceWorkbenchCommand::IndentUsingSpaces => {
+                if let Some(size) = data
+                    .and_then(|data| serde_json::from_value::<u8>(data).ok())
+                    .filter(|size| (1..=8).contains(size))
+                {
+                    self.main_split.set_indent_style(IndentStyle::Spaces(size));
+                }
+            }
+            LapceWorkbenchCommand::IndentUsingTabs => {
+                self.main_split.set_indent_style(IndentStyle::Tabs);
+            }
+            LapceWorkbenchCommand::SetTabWidth => {
+                if let Some(width) = data
+                    .and_then(|data| serde_json::from_value::<usize>(data).ok())
+                    .filter(|width| (1..=8).contains(width))
+                {
+                    self.main_split.set_tab_width(width);
+                }
+            }
+            LapceWorkbenchCommand::ConvertIndentationToSpaces => {
+                let config = self.config.clone();
+                self.main_split.convert_indentation(false, &config);
+            }
+            LapceWorkbenchCommand::ConvertIndentationToTabs => {
+                let config = self.config.clone();
+                self.main_split.convert_indentation(true, &config);
+            }
             LapceWorkbenchCommand::OpenLogFile => {
                 if let Some(path) = Config::log_file() {
                     let editor_view_id = self.main_split.active.clone();
@@ -1228,6 +2135,13 @@ impl LapceTabData {
                     Target::Widget(self.palette.widget_id),
                 ));
             }
+            LapceWorkbenchCommand::PaletteWorkspaceSymbol => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::WorkspaceSymbol)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
             LapceWorkbenchCommand::PaletteCommand => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1242,6 +2156,9 @@ impl LapceTabData {
                     Target::Widget(self.palette.widget_id),
                 ));
             }
+            LapceWorkbenchCommand::RemoveStaleRecentWorkspaces => {
+                Config::remove_stale_recent_workspaces();
+            }
             LapceWorkbenchCommand::NewTab => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1277,6 +2194,13 @@ impl LapceTabData {
                     Target::Global,
                 ));
             }
+            LapceWorkbenchCommand::ClearRecentMenuItems => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ClearRecentMenuItems,
+                    Target::Auto,
+                ));
+            }
             LapceWorkbenchCommand::ReloadWindow => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1321,27 +2245,46 @@ impl LapceTabData {
             }
 
             LapceWorkbenchCommand::ToggleSourceControlVisual => {
-                self.toggle_panel_visual(ctx, PanelKind::SourceControl);
+                self.toggle_panel(ctx, PanelKind::SourceControl);
             }
             LapceWorkbenchCommand::TogglePluginVisual => {
-                self.toggle_panel_visual(ctx, PanelKind::Plugin);
+                self.toggle_panel(ctx, PanelKind::Plugin);
             }
             LapceWorkbenchCommand::ToggleFileExplorerVisual => {
-                self.toggle_panel_visual(ctx, PanelKind::FileExplorer);
+                self.toggle_panel(ctx, PanelKind::FileExplorer);
             }
             LapceWorkbenchCommand::ToggleSearchVisual => {
-                self.toggle_panel_visual(ctx, PanelKind::Search);
+                self.toggle_panel(ctx, PanelKind::Search);
             }
             LapceWorkbenchCommand::ToggleProblemVisual => {
-                self.toggle_panel_visual(ctx, PanelKind::Problem);
+                self.toggle_panel(ctx, PanelKind::Problem);
+            }
+            LapceWorkbenchCommand::ToggleOutlineVisual => {
+                self.toggle_panel(ctx, PanelKind::Outline);
+            }
+            LapceWorkbenchCommand::ToggleCallHierarchyVisual => {
+                self.toggle_panel(ctx, PanelKind::CallHierarchy);
+            }
+            LapceWorkbenchCommand::ToggleTypeHierarchyVisual => {
+                self.toggle_panel(ctx, PanelKind::TypeHierarchy);
+            }
+            LapceWorkbenchCommand::ToggleSearchRegex => {
+                let mut options = self.search.options;
+                options.regex = !options.regex;
+                self.set_search_options(ctx, options);
+            }
+            LapceWorkbenchCommand::ToggleSearchWholeWord => {
+                let mut options = self.search.options;
+                options.whole_word = !options.whole_word;
+                self.set_search_options(ctx, options);
             }
             LapceWorkbenchCommand::ToggleTerminalVisual => {
-                self.toggle_panel_visual(ctx, PanelKind::Terminal);
+                self.toggle_panel(ctx, PanelKind::Terminal);
             }
             LapceWorkbenchCommand::TogglePanelVisual => {
                 if let Some(data) = data {
                     if let Ok(kind) = serde_json::from_value::<PanelKind>(data) {
-                        self.toggle_panel_visual(ctx, kind);
+                        self.toggle_panel(ctx, kind);
                     }
                 }
             }
@@ -1361,6 +2304,15 @@ impl LapceTabData {
             LapceWorkbenchCommand::ToggleProblemFocus => {
                 self.toggle_panel_focus(ctx, PanelKind::Problem);
             }
+            LapceWorkbenchCommand::ToggleOutlineFocus => {
+                self.toggle_panel_focus(ctx, PanelKind::Outline);
+            }
+            LapceWorkbenchCommand::ToggleCallHierarchyFocus => {
+                self.toggle_panel_focus(ctx, PanelKind::CallHierarchy);
+            }
+            LapceWorkbenchCommand::ToggleTypeHierarchyFocus => {
+                self.toggle_panel_focus(ctx, PanelKind::TypeHierarchy);
+            }
             LapceWorkbenchCommand::ToggleTerminalFocus => {
                 self.toggle_panel_focus(ctx, PanelKind::Terminal);
             }
@@ -1427,6 +2379,56 @@ impl LapceTabData {
                     Cursor::new(CursorMode::Insert(Selection::caret(0)), None)
                 };
             }
+            LapceWorkbenchCommand::SaveElevated => {
+                if let Some(data) = data {
+                    if let Ok(path) = serde_json::from_value::<PathBuf>(data) {
+                        self.main_split.save_elevated(
+                            ctx,
+                            &path,
+                            self.config.lapce.sudo_helper.clone(),
+                        );
+                    }
+                }
+            }
+            LapceWorkbenchCommand::SaveOverwritePermissions => {
+                if let Some(data) = data {
+                    if let Ok(path) = serde_json::from_value::<PathBuf>(data) {
+                        self.main_split.save_overwrite_permissions(ctx, &path);
+                    }
+                }
+            }
+            LapceWorkbenchCommand::SaveAs => {
+                let path = match data
+                    .and_then(|data| serde_json::from_value::<PathBuf>(data).ok())
+                {
+                    Some(path) => path,
+                    None => return,
+                };
+                if !self.workspace.kind.is_remote() {
+                    let event_sink = ctx.get_external_handle();
+                    let tab_id = self.id;
+                    thread::spawn(move || {
+                        if let Some(target) = tinyfiledialogs::save_file_dialog(
+                            "Save as",
+                            path.to_str().unwrap_or(""),
+                        ) {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::SaveAs(path, PathBuf::from(target)),
+                                Target::Widget(tab_id),
+                            );
+                        }
+                    });
+                } else {
+                    if let Some(parent) = path.parent() {
+                        self.set_picker_pwd(parent.to_path_buf());
+                    }
+                    let picker = Arc::make_mut(&mut self.picker);
+                    picker.active = true;
+                    picker.save_as = Some(path);
+                    self.read_picker_pwd(ctx);
+                }
+            }
             LapceWorkbenchCommand::CheckoutBranch => {}
             LapceWorkbenchCommand::ConnectSshHost => {
                 ctx.submit_command(Command::new(
@@ -1435,6 +2437,13 @@ impl LapceTabData {
                     Target::Widget(self.palette.widget_id),
                 ));
             }
+            LapceWorkbenchCommand::SwitchSshHost => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::SwitchSshHost)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
             LapceWorkbenchCommand::ConnectWsl => ctx.submit_command(Command::new(
                 LAPCE_UI_COMMAND,
                 LapceUICommand::SetWorkspace(LapceWorkspace {
@@ -1444,6 +2453,44 @@ impl LapceTabData {
                 }),
                 Target::Auto,
             )),
+            LapceWorkbenchCommand::NextProblem => {
+                self.main_split.next_problem(ctx, &self.config);
+            }
+            LapceWorkbenchCommand::CopyDiagnostic => {
+                // Menus pass the text to copy, while from the palette it's the
+                // diagnostics at the cursor.
+                let text = data
+                    .and_then(|data| serde_json::from_value::<String>(data).ok())
+                    .or_else(|| self.main_split.diagnostics_text_at_cursor());
+                if let Some(text) = text {
+                    Application::global().clipboard().put_string(text);
+                }
+            }
+            LapceWorkbenchCommand::JumpToSymbol => {
+                if let Some((view_id, position)) = data.and_then(|data| {
+                    serde_json::from_value::<(u64, Position)>(data).ok()
+                }) {
+                    let view_id = self
+                        .main_split
+                        .editors
+                        .keys()
+                        .find(|id| id.to_raw() == view_id)
+                        .copied();
+                    self.main_split.jump_to_position(
+                        ctx,
+                        view_id,
+                        position,
+                        &self.config,
+                    );
+                }
+            }
+            LapceWorkbenchCommand::ReconnectRemote => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ReconnectProxy,
+                    Target::Widget(self.id),
+                ))
+            }
             LapceWorkbenchCommand::DisconnectRemote => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1455,6 +2502,94 @@ impl LapceTabData {
                     Target::Auto,
                 ))
             }
+            LapceWorkbenchCommand::SaveAllAndCloseTab => {
+                self.hide_prompt(ctx);
+                self.after_save = Some(AfterSave::CloseTab);
+                for path in self.unsaved_files() {
+                    self.main_split.save(ctx, &path, &self.config);
+                }
+                self.run_after_save(ctx);
+            }
+            LapceWorkbenchCommand::SaveAllAndSwitchSshHost => {
+                self.hide_prompt(ctx);
+                if let Some((user, host)) =
+                    data.and_then(|data| serde_json::from_value(data).ok())
+                {
+                    self.after_save = Some(AfterSave::SwitchSshHost(user, host));
+                    for path in self.unsaved_files() {
+                        self.main_split.save(ctx, &path, &self.config);
+                    }
+                    self.run_after_save(ctx);
+                }
+            }
+            LapceWorkbenchCommand::SwitchSshHostWithoutSaving => {
+                self.hide_prompt(ctx);
+                if let (Some((user, host)), Some(path)) = (
+                    data.and_then(|data| serde_json::from_value(data).ok()),
+                    self.workspace.path.clone(),
+                ) {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::OpenRemoteWorkspace { user, host, path },
+                        Target::Auto,
+                    ));
+                }
+            }
+            LapceWorkbenchCommand::CancelSwitchSshHost => {
+                self.hide_prompt(ctx);
+            }
+            LapceWorkbenchCommand::SaveAndCloseEditors => {
+                self.hide_prompt(ctx);
+                if let Some((editor_tab_id, view_ids)) = self.editors_from_data(data)
+                {
+                    for path in self.unsaved_editor_files(&view_ids) {
+                        self.main_split.save(ctx, &path, &self.config);
+                    }
+                    self.after_save =
+                        Some(AfterSave::CloseEditors(editor_tab_id, view_ids));
+                    self.run_after_save(ctx);
+                }
+            }
+            LapceWorkbenchCommand::CloseEditorsWithoutSaving => {
+                self.hide_prompt(ctx);
+                if let Some((editor_tab_id, view_ids)) = self.editors_from_data(data)
+                {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::EditorTabCloseChildren(view_ids),
+                        Target::Widget(editor_tab_id),
+                    ));
+                }
+            }
+            LapceWorkbenchCommand::CancelCloseEditors => {
+                self.hide_prompt(ctx);
+            }
+            LapceWorkbenchCommand::CloseTabWithoutSaving => {
+                self.hide_prompt(ctx);
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ForceCloseTabId(self.id),
+                    Target::Auto,
+                ));
+            }
+            LapceWorkbenchCommand::CancelCloseTab => {
+                self.hide_prompt(ctx);
+                if let Some(path) =
+                    data.and_then(|data| serde_json::from_value(data).ok())
+                {
+                    self.main_split.jump_to_location(
+                        ctx,
+                        None,
+                        EditorLocationNew {
+                            path,
+                            position: None,
+                            scroll_offset: None,
+                            history: None,
+                        },
+                        &self.config,
+                    );
+                }
+            }
         }
     }
 
@@ -1541,7 +2676,39 @@ impl LapceTabData {
         }
     }
 
-    fn is_panel_visible(&self, kind: PanelKind) -> bool {
+    /// The hierarchy shown in `panel`, if it's a hierarchy panel.
+    pub fn hierarchy(&self, panel: PanelKind) -> Option<&Arc<HierarchyData>> {
+        match panel {
+            PanelKind::CallHierarchy => Some(&self.call_hierarchy),
+            PanelKind::TypeHierarchy => Some(&self.type_hierarchy),
+            PanelKind::FileExplorer
+            | PanelKind::SourceControl
+            | PanelKind::Plugin
+            | PanelKind::Terminal
+            | PanelKind::Search
+            | PanelKind::Problem
+            | PanelKind::Outline => None,
+        }
+    }
+
+    pub fn hierarchy_mut(
+        &mut self,
+        panel: PanelKind,
+    ) -> Option<&mut Arc<HierarchyData>> {
+        match panel {
+            PanelKind::CallHierarchy => Some(&mut self.call_hierarchy),
+            PanelKind::TypeHierarchy => Some(&mut self.type_hierarchy),
+            PanelKind::FileExplorer
+            | PanelKind::SourceControl
+            | PanelKind::Plugin
+            | PanelKind::Terminal
+            | PanelKind::Search
+            | PanelKind::Problem
+            | PanelKind::Outline => None,
+        }
+    }
+
+    pub fn is_panel_visible(&self, kind: PanelKind) -> bool {
         for (_, panel) in self.panels.iter() {
             if panel.widgets.contains(&kind) {
                 return panel.active == kind && panel.shown;
@@ -1557,14 +2724,275 @@ impl LapceTabData {
         self.focus_area == FocusArea::Panel(kind) && self.is_panel_visible(kind)
     }
 
-    fn hide_panel(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
-        for (_, panel) in self.panels.iter_mut() {
-            if panel.active == kind {
-                let panel = Arc::make_mut(panel);
-                panel.shown = false;
+    /// The title of the window while this tab is active, e.g.
+    /// `main.rs — lapce [user@host]`.
+    pub fn window_title(&self) -> String {
+        let dir = self.workspace.path.as_ref().map(|p| {
+            let dir = p.file_name().unwrap_or(p.as_os_str()).to_string_lossy();
+            match &self.workspace.kind {
+                LapceWorkspaceType::Local => dir.to_string(),
+                LapceWorkspaceType::RemoteSSH(user, host) => {
+                    format!("{} [{}@{}]", dir, user, host)
+                }
+                LapceWorkspaceType::RemoteWSL => {
+                    format!("{dir} [wsl]")
+                }
+            }
+        });
+        let file_name = self.main_split.active_editor().and_then(|editor| {
+            match &editor.content {
+                BufferContent::File(path) => {
+                    Some(path.file_name()?.to_string_lossy().to_string())
+                }
+                _ => None,
+            }
+        });
+        match (file_name, dir) {
+            (Some(file_name), Some(dir)) => format!("{file_name} — {dir}"),
+            (Some(file_name), None) => format!("{file_name} — Lapce"),
+            (None, Some(dir)) => dir,
+            (None, None) => "Lapce".to_string(),
+        }
+    }
+
+    pub fn is_overlay_open(&self, overlay: Overlay) -> bool {
+        match overlay {
+            Overlay::Hover => {
+                self.hover.status != HoverStatus::Inactive && !self.hover.is_empty()
+            }
+            Overlay::Completion => {
+                self.completion.status != CompletionStatus::Inactive
+                    && !self.completion.is_empty()
+            }
+            Overlay::Signature => {
+                self.signature.status != SignatureStatus::Inactive
+                    && !self.signature.is_empty()
+            }
+            Overlay::CodeAction => self.main_split.show_code_actions,
+            Overlay::Peek => self.peek.visible,
+            Overlay::FindBar => self.find.visual,
+        }
+    }
+
+    /// The overlay the next Escape should close, if any is open.
+    pub fn topmost_overlay(&self) -> Option<Overlay> {
+        Overlay::topmost(|overlay| self.is_overlay_open(overlay))
+    }
+
+    pub fn close_overlay(&mut self, ctx: &mut EventCtx, overlay: Overlay) {
+        match overlay {
+            Overlay::Hover => Arc::make_mut(&mut self.hover).cancel(),
+            Overlay::Completion => Arc::make_mut(&mut self.completion).cancel(),
+            Overlay::Signature => Arc::make_mut(&mut self.signature).cancel(),
+            Overlay::CodeAction => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::CancelCodeActions,
+                    Target::Auto,
+                ));
+            }
+            Overlay::Peek => self.close_peek(ctx),
+            Overlay::FindBar => Arc::make_mut(&mut self.find).visual = false,
+        }
+    }
+
+    /// Shows `location` in the peek below the line of `offset` in the editor
+    /// `editor_view_id`.
+    pub fn show_peek(
+        &mut self,
+        ctx: &mut EventCtx,
+        editor_view_id: WidgetId,
+        offset: usize,
+        location: EditorLocationNew,
+    ) {
+        let peek = Arc::make_mut(&mut self.peek);
+        peek.visible = true;
+        peek.parent_view_id = editor_view_id;
+        peek.offset = offset;
+        let peek_view_id = peek.editor_view_id;
+        let config = self.config.clone();
+        self.main_split
+            .go_to_location(ctx, Some(peek_view_id), location, &config);
+    }
+
+    /// Hides the peek, handing the focus back to the editor it was opened
+    /// from if the peek had it.
+    pub fn close_peek(&mut self, ctx: &mut EventCtx) {
+        if !self.peek.visible {
+            return;
+        }
+        Arc::make_mut(&mut self.peek).visible = false;
+        let parent_view_id = self.peek.parent_view_id;
+        if *self.main_split.active == Some(self.peek.editor_view_id)
+            && self.main_split.editors.contains_key(&parent_view_id)
+        {
+            self.main_split.active = Arc::new(Some(parent_view_id));
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::Focus,
+                Target::Widget(parent_view_id),
+            ));
+        }
+    }
+
+    /// Closes the peek and opens where its cursor is in the editor it was
+    /// opened from.
+    pub fn open_peek(&mut self, ctx: &mut EventCtx) {
+        let editor = match self.main_split.editors.get(&self.peek.editor_view_id) {
+            Some(editor) => editor,
+            None => return,
+        };
+        let location = match &editor.content {
+            BufferContent::File(path) => {
+                let buffer = match self.main_split.open_files.get(path) {
+                    Some(buffer) => buffer,
+                    None => return,
+                };
+                EditorLocationNew {
+                    path: path.clone(),
+                    position: Some(
+                        buffer.offset_to_position(editor.cursor.offset()),
+                    ),
+                    scroll_offset: None,
+                    history: None,
+                }
+            }
+            _ => return,
+        };
+        self.close_peek(ctx);
+        let config = self.config.clone();
+        self.main_split
+            .jump_to_location(ctx, None, location, &config);
+    }
+
+    /// Shows the file explorer with `path` selected, opening the directories
+    /// above it and scrolling it into view.
+    pub fn reveal_in_file_explorer(&mut self, ctx: &mut EventCtx, path: &Path) {
+        self.show_panel(ctx, PanelKind::FileExplorer);
+        Arc::make_mut(&mut self.file_explorer).active_selected =
+            Some(path.to_path_buf());
+        self.expand_file_explorer_to(ctx, path);
+    }
+
+    /// Updates the file explorer after paths changed on disk outside of
+    /// Lapce. Open files that were deleted keep their contents and are marked
+    /// as unsaved.
+    pub fn handle_file_changes(
+        &mut self,
+        ctx: &mut EventCtx,
+        changes: &[(PathBuf, FileChangeKind)],
+    ) {
+        Arc::make_mut(&mut self.file_explorer).refresh_parents(
+            changes.iter().map(|(path, _)| path.as_path()),
+            &self.proxy,
+            ctx.get_external_handle(),
+        );
+        for (path, kind) in changes {
+            if *kind == FileChangeKind::Deleted {
+                self.main_split.mark_deleted(path);
+            }
+        }
+    }
+
+    pub fn expand_file_explorer_to(&mut self, ctx: &mut EventCtx, path: &Path) {
+        let file_explorer = Arc::make_mut(&mut self.file_explorer);
+        if file_explorer.expand_to(path, &self.proxy, ctx.get_external_handle()) {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::EnsureExplorerItemVisible(path.to_path_buf()),
+                Target::Widget(file_explorer.widget_id),
+            ));
+        }
+    }
+
+    /// Remembers the file, cursor and scroll position of an editor that is
+    /// being closed, so that it can be reopened.
+    pub fn remember_closed_editor(&mut self, view_id: WidgetId) {
+        let editor = match self.main_split.editors.get(&view_id) {
+            Some(editor) => editor,
+            None => return,
+        };
+        let path = match &editor.content {
+            BufferContent::File(path) => path,
+            _ => return,
+        };
+        let buffer = match self.main_split.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let position = buffer.offset_to_position(editor.cursor.offset());
+        self.closed_editors.push_back(EditorLocationNew {
+            path: path.clone(),
+            position: Some(position),
+            scroll_offset: Some(editor.scroll_offset),
+            history: None,
+        });
+        if self.closed_editors.len() > CLOSED_EDITORS_LIMIT {
+            self.closed_editors.pop_front();
+        }
+    }
+
+    /// Opens the most recently closed editor that still exists again.
+    pub fn reopen_closed_editor(&mut self, ctx: &mut EventCtx) {
+        while let Some(location) = self.closed_editors.pop_back() {
+            // Remote files can't be checked from here, so those are trusted.
+            if self.workspace.kind.is_remote() || location.path.exists() {
+                self.main_split
+                    .jump_to_location(ctx, None, location, &self.config);
+                return;
+            }
+        }
+    }
+
+    /// Hides every panel to give the editor all the space, or restores the
+    /// panels hidden by the previous call.
+    pub fn toggle_maximize_editor(&mut self, ctx: &mut EventCtx) {
+        let any_shown = self.panels.values().any(|panel| panel.is_shown());
+        match self.panels_before_maximize.take() {
+            // A panel shown since maximizing means the user has moved on, so
+            // maximize again from the current layout instead of restoring.
+            Some(shown) if !any_shown => {
+                for (position, panel) in self.panels.iter_mut() {
+                    if let Some(shown) = shown.get(position) {
+                        Arc::make_mut(panel).shown = *shown;
+                    }
+                }
+            }
+            _ => {
+                if !any_shown {
+                    return;
+                }
+                self.panels_before_maximize = Some(
+                    self.panels
+                        .iter()
+                        .map(|(position, panel)| (position.clone(), panel.shown))
+                        .collect(),
+                );
+                for (_, panel) in self.panels.iter_mut() {
+                    Arc::make_mut(panel).shown = false;
+                }
+                if let Some(active) = *self.main_split.active {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::Focus,
+                        Target::Widget(active),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn hide_panel(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
+        for (_, panel) in self.panels.iter_mut() {
+            if panel.active == kind {
+                let panel = Arc::make_mut(panel);
+                panel.shown = false;
                 break;
             }
         }
+        if kind == PanelKind::Plugin {
+            self.reset_plugin_filter();
+        }
         if let Some(active) = *self.main_split.active {
             ctx.submit_command(Command::new(
                 LAPCE_UI_COMMAND,
@@ -1574,7 +3002,19 @@ impl LapceTabData {
         }
     }
 
-    fn show_panel(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
+    /// Shows and focuses the panel. A panel that no position hosts is added
+    /// to its default one first.
+    pub fn show_panel(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
+        if self.panel_position(kind).is_none() {
+            let empty = Arc::new(PanelData {
+                active: kind,
+                widgets: Vec::new(),
+                shown: false,
+                maximized: false,
+            });
+            let panel = self.panels.entry(kind.default_position()).or_insert(empty);
+            Arc::make_mut(panel).widgets.push(kind);
+        }
         for (_, panel) in self.panels.iter_mut() {
             if panel.widgets.contains(&kind) {
                 let panel = Arc::make_mut(panel);
@@ -1587,6 +3027,9 @@ impl LapceTabData {
                     PanelKind::Terminal => self.terminal.widget_id,
                     PanelKind::Search => self.search.active,
                     PanelKind::Problem => self.problem.widget_id,
+                    PanelKind::Outline => self.outline.widget_id,
+                    PanelKind::CallHierarchy => self.call_hierarchy.widget_id,
+                    PanelKind::TypeHierarchy => self.type_hierarchy.widget_id,
                 };
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1598,7 +3041,9 @@ impl LapceTabData {
         }
     }
 
-    fn toggle_panel_visual(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
+    /// Shows and focuses the panel, or hides it and focuses the editor if it's
+    /// already visible.
+    pub fn toggle_panel(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
         if self.is_panel_visible(kind) {
             self.hide_panel(ctx, kind);
         } else {
@@ -1608,7 +3053,12 @@ impl LapceTabData {
 
     fn toggle_panel_focus(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
         let should_hide = match kind {
-            PanelKind::FileExplorer | PanelKind::Plugin | PanelKind::Problem => {
+            PanelKind::FileExplorer
+            | PanelKind::Plugin
+            | PanelKind::Problem
+            | PanelKind::Outline
+            | PanelKind::CallHierarchy
+            | PanelKind::TypeHierarchy => {
                 // Some panels don't accept focus (yet). Fall back to visibility check
                 // in those cases.
                 self.is_panel_visible(kind)
@@ -1656,7 +3106,7 @@ impl LapceTabData {
 
     pub fn set_picker_pwd(&mut self, pwd: PathBuf) {
         let picker = Arc::make_mut(&mut self.picker);
-        picker.pwd = pwd.clone();
+        picker.set_pwd(pwd.clone());
         if let Some(s) = pwd.to_str() {
             let buffer = self
                 .main_split
@@ -1664,7 +3114,12 @@ impl LapceTabData {
                 .get_mut(&LocalBufferKind::FilePicker)
                 .unwrap();
             let buffer = Arc::make_mut(buffer);
-            buffer.load_content(s);
+            // What's typed after the separator filters the directory
+            if s.ends_with(std::path::MAIN_SEPARATOR) || s.ends_with('/') {
+                buffer.load_content(s);
+            } else {
+                buffer.load_content(&format!("{}{}", s, std::path::MAIN_SEPARATOR));
+            }
             let editor = self
                 .main_split
                 .editors
@@ -1686,6 +3141,214 @@ impl LapceTabData {
             };
         }
     }
+
+    /// Opens the files picked in the file picker, or otherwise its selected
+    /// entry, which is the top match of the filter unless another one was
+    /// clicked. A folder becomes the directory of the picker, and when saving,
+    /// nothing selected saves to the typed name.
+    pub fn open_picker_match(&mut self, ctx: &mut EventCtx) {
+        if self.picker.save_as.is_none() && !self.picker.selected_paths.is_empty() {
+            for path in self.picker.selected_paths.iter() {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::JumpToLocation(
+                        None,
+                        EditorLocationNew {
+                            path: path.clone(),
+                            position: None,
+                            scroll_offset: None,
+                            history: None,
+                        },
+                    ),
+                    Target::Widget(self.id),
+                ));
+            }
+            let picker = Arc::make_mut(&mut self.picker);
+            picker.active = false;
+            picker.selected_paths.clear();
+            return;
+        }
+
+        let selected = self
+            .picker
+            .selected_node()
+            .map(|node| (node.path_buf.clone(), node.is_dir));
+        match selected {
+            Some((path, true)) => {
+                self.set_picker_pwd(path);
+                self.read_picker_pwd(ctx);
+            }
+            selected => {
+                let command = match (self.picker.save_as.clone(), selected) {
+                    (Some(save_as), Some((path, _))) => {
+                        LapceUICommand::SaveAs(save_as, path)
+                    }
+                    (Some(save_as), None) if !self.picker.filter.is_empty() => {
+                        let target = self.picker.pwd.join(&self.picker.filter);
+                        LapceUICommand::SaveAs(save_as, target)
+                    }
+                    (None, Some((path, _))) => LapceUICommand::OpenFile(path),
+                    (_, None) => return,
+                };
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    command,
+                    Target::Widget(self.id),
+                ));
+                let picker = Arc::make_mut(&mut self.picker);
+                picker.active = false;
+                picker.save_as = None;
+            }
+        }
+    }
+
+    /// Changes how the global search matches, saves the choice, and searches
+    /// again with it.
+    pub fn set_search_options(
+        &mut self,
+        ctx: &mut EventCtx,
+        options: SearchOptions,
+    ) {
+        Arc::make_mut(&mut self.search).options = options;
+        let _ = self.db.save_search_options_async(options);
+        self.search_again(ctx);
+    }
+
+    /// Runs the global search again for what's in the search input, after
+    /// something else it depends on changed.
+    pub fn search_again(&self, ctx: &mut EventCtx) {
+        let pattern = self
+            .main_split
+            .local_buffers
+            .get(&LocalBufferKind::Search)
+            .map(|buffer| buffer.rope().to_string())
+            .unwrap_or_default();
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::UpdateSearch(pattern),
+            Target::Widget(self.id),
+        ));
+    }
+
+    /// Adds the globs the search is limited to to the recent ones, and saves
+    /// those if they changed.
+    pub fn remember_search_globs(&mut self) {
+        let search = Arc::make_mut(&mut self.search);
+        if search.remember_globs() {
+            let _ = self
+                .db
+                .save_recent_search_globs_async(search.recent_globs.clone());
+        }
+    }
+
+    /// Fills the glob input with an older or newer one of the recent globs,
+    /// clearing it when going past the newest, and searches with them.
+    pub fn recall_search_globs(&mut self, ctx: &mut EventCtx, older: bool) {
+        let search = Arc::make_mut(&mut self.search);
+        let index = match (search.recent_globs_index, older) {
+            (None, true) => 0,
+            (Some(index), true) => index + 1,
+            (Some(index), false) if index > 0 => index - 1,
+            (Some(_), false) => {
+                search.recent_globs_index = None;
+                search.globs.clear();
+                self.load_search_globs(ctx);
+                return;
+            }
+            (None, false) => return,
+        };
+        if let Some(globs) = search.recent_globs.get(index) {
+            search.globs = globs.clone();
+            search.recent_globs_index = Some(index);
+            self.load_search_globs(ctx);
+        }
+    }
+
+    fn load_search_globs(&mut self, ctx: &mut EventCtx) {
+        let buffer = self
+            .main_split
+            .local_buffers
+            .get_mut(&LocalBufferKind::SearchGlobs)
+            .unwrap();
+        let buffer = Arc::make_mut(buffer);
+        buffer.load_content(&self.search.globs);
+        let editor = self
+            .main_split
+            .editors
+            .get_mut(&self.search.globs_view_id)
+            .unwrap();
+        Arc::make_mut(editor).cursor = if self.config.lapce.modal {
+            Cursor::new(CursorMode::Normal(buffer.line_end_offset(0, false)), None)
+        } else {
+            Cursor::new(
+                CursorMode::Insert(Selection::caret(
+                    buffer.line_end_offset(0, true),
+                )),
+                None,
+            )
+        };
+        self.search_again(ctx);
+    }
+
+    /// Replaces `pattern` with `replacement` in every line the last global
+    /// search matched. Open files are edited and saved, the rest are written
+    /// by the proxy, and a file is left alone if any of its matched lines
+    /// changed since the search.
+    pub fn global_replace(
+        &mut self,
+        ctx: &mut EventCtx,
+        pattern: &str,
+        replacement: &str,
+    ) {
+        let mut replaced = Vec::new();
+        let mut skipped = Vec::new();
+        let mut lines = HashMap::new();
+        for (path, matches) in self.search.matches.clone().iter() {
+            let file_lines: Vec<(usize, String)> = matches
+                .iter()
+                .map(|(line_number, _, line)| (*line_number, line.clone()))
+                .collect();
+            if !self.main_split.open_files.contains_key(path) {
+                lines.insert(path.clone(), file_lines);
+            } else if self.main_split.replace_lines_and_save(
+                ctx,
+                path,
+                &file_lines,
+                pattern,
+                replacement,
+                self.search.options,
+            ) {
+                replaced.push(path.clone());
+            } else {
+                skipped.push(path.clone());
+            }
+        }
+
+        let event_sink = ctx.get_external_handle();
+        let tab_id = self.id;
+        let pattern = pattern.to_string();
+        self.proxy.global_replace(
+            pattern.clone(),
+            replacement.to_string(),
+            self.search.options,
+            lines,
+            Box::new(move |result| {
+                let mut resp = result
+                    .ok()
+                    .and_then(|value| {
+                        serde_json::from_value::<GlobalReplaceResponse>(value).ok()
+                    })
+                    .unwrap_or_default();
+                resp.replaced.extend(replaced);
+                resp.skipped.extend(skipped);
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::GlobalReplaceResult(pattern, resp),
+                    Target::Widget(tab_id),
+                );
+            }),
+        );
+    }
 }
 
 pub struct LapceTabLens(pub WidgetId);
@@ -1759,22 +3422,16 @@ impl SplitContent {
         }
     }
 
-    pub fn content_info(
-        &self,
-        data: &LapceTabData,
-        tab_width: usize,
-    ) -> SplitContentInfo {
+    pub fn content_info(&self, data: &LapceTabData) -> SplitContentInfo {
         match &self {
             SplitContent::EditorTab(widget_id) => {
                 let editor_tab_data =
                     data.main_split.editor_tabs.get(widget_id).unwrap();
-                SplitContentInfo::EditorTab(
-                    editor_tab_data.tab_info(data, tab_width),
-                )
+                SplitContentInfo::EditorTab(editor_tab_data.tab_info(data))
             }
             SplitContent::Split(split_id) => {
                 let split_data = data.main_split.splits.get(split_id).unwrap();
-                SplitContentInfo::Split(split_data.split_info(data, tab_width))
+                SplitContentInfo::Split(split_data.split_info(data))
             }
         }
     }
@@ -1827,13 +3484,13 @@ pub struct SplitData {
 }
 
 impl SplitData {
-    pub fn split_info(&self, data: &LapceTabData, tab_width: usize) -> SplitInfo {
+    pub fn split_info(&self, data: &LapceTabData) -> SplitInfo {
         let info = SplitInfo {
             direction: self.direction,
             children: self
                 .children
                 .iter()
-                .map(|child| child.content_info(data, tab_width))
+                .map(|child| child.content_info(data))
                 .collect(),
         };
         info
@@ -1904,132 +3561,1417 @@ pub struct LapceMainSplitData {
     pub palette_preview_editor: Arc<WidgetId>,
     pub show_code_actions: bool,
     pub current_code_actions: usize,
+    /// Whether a code action chosen without its edit is being resolved
+    pub resolving_code_action: bool,
     pub diagnostics: im::HashMap<PathBuf, Arc<Vec<EditorDiagnostic>>>,
     pub error_count: usize,
     pub warning_count: usize,
+    /// The diagnostic last gone to with go to next problem, which the next
+    /// one is looked for after
+    pub last_problem: Arc<Option<(PathBuf, Position)>>,
     pub workspace: Arc<LapceWorkspace>,
     pub db: Arc<LapceDb>,
+    /// Edits from workspace edits for files that were still loading, applied
+    /// in turn once they have loaded, each with whether the file is saved
+    /// after it
+    pub pending_edits: im::HashMap<PathBuf, Arc<Vec<(Vec<TextEdit>, bool)>>>,
+    /// The characters that trigger formatting as they're typed, by the id of
+    /// the language whose server asked for them
+    pub on_type_formatting_triggers: im::HashMap<String, Arc<Vec<String>>>,
+    /// The characters after which completion is asked for, by the id of the
+    /// language whose server completes after them
+    pub completion_triggers: im::HashMap<String, Arc<Vec<String>>>,
 }
 
-impl LapceMainSplitData {
-    pub fn active_editor(&self) -> Option<&LapceEditorData> {
-        let id = (*self.active)?;
-        Some(self.editors.get(&id)?.as_ref())
+/// What came of asking the language server to format a file.
+#[derive(Debug, PartialEq)]
+pub enum FormatOutcome {
+    Edits(Vec<TextEdit>),
+    /// The file is formatted already
+    NoChanges,
+    /// The file couldn't be formatted, for the given reason
+    Failed(String),
+}
+
+impl FormatOutcome {
+    pub fn of(result: &Result<Value>) -> Self {
+        match result {
+            Ok(Value::Null) => FormatOutcome::NoChanges,
+            Ok(value) => {
+                match serde_json::from_value::<Vec<TextEdit>>(value.clone()) {
+                    Ok(edits) if edits.is_empty() => FormatOutcome::NoChanges,
+                    Ok(edits) => FormatOutcome::Edits(edits),
+                    Err(e) => FormatOutcome::Failed(format!("invalid edits: {}", e)),
+                }
+            }
+            Err(e) => FormatOutcome::Failed(e.to_string()),
+        }
     }
+}
 
-    pub fn editor_buffer(&self, editor_view_id: WidgetId) -> Arc<Buffer> {
-        let editor = self.editors.get(&editor_view_id).unwrap();
-        let buffer = match &editor.content {
-            BufferContent::File(path) => self.open_files.get(path).unwrap().clone(),
-            BufferContent::Local(kind) => {
-                self.local_buffers.get(kind).unwrap().clone()
+/// The options the language server formats `buffer` with, so that it
+/// indents the way the editor does.
+fn formatting_options(buffer: &Buffer, config: &Config) -> FormattingOptions {
+    let (tab_size, insert_spaces) = match buffer.indent_style() {
+        IndentStyle::Spaces(size) => (size as u32, true),
+        IndentStyle::Tabs => (buffer.tab_width(config) as u32, false),
+    };
+    FormattingOptions {
+        tab_size,
+        insert_spaces,
+        ..Default::default()
+    }
+}
+
+/// The edits that are within `range`.
+fn edits_within(edits: Vec<TextEdit>, range: &Range) -> Vec<TextEdit> {
+    edits
+        .into_iter()
+        .filter(|edit| {
+            edit.range.start >= range.start && edit.range.end <= range.end
+        })
+        .collect()
+}
+
+/// A step of applying a workspace edit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorkspaceEditStep {
+    /// Text edits to a file, made for the given version of it if there is one
+    Edit(PathBuf, Option<i32>, Vec<TextEdit>),
+    File(FileOperation),
+}
+
+/// The steps of applying a workspace edit, in the order they're to be taken.
+pub fn workspace_edit_steps(edit: &WorkspaceEdit) -> Vec<WorkspaceEditStep> {
+    let text_edits = |edit: &TextDocumentEdit| {
+        let edits = edit
+            .edits
+            .iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit.clone(),
+                OneOf::Right(edit) => edit.text_edit.clone(),
+            })
+            .collect();
+        WorkspaceEditStep::Edit(
+            path_from_url(&edit.text_document.uri),
+            edit.text_document.version,
+            edits,
+        )
+    };
+    match &edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => {
+            edits.iter().map(text_edits).collect()
+        }
+        Some(DocumentChanges::Operations(operations)) => operations
+            .iter()
+            .map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => text_edits(edit),
+                DocumentChangeOperation::Op(op) => {
+                    WorkspaceEditStep::File(file_operation(op))
+                }
+            })
+            .collect(),
+        None => {
+            let mut changes: Vec<(&Url, &Vec<TextEdit>)> =
+                edit.changes.iter().flatten().collect();
+            changes.sort_by_key(|(url, _)| path_from_url(url));
+            changes
+                .into_iter()
+                .map(|(url, edits)| {
+                    WorkspaceEditStep::Edit(path_from_url(url), None, edits.clone())
+                })
+                .collect()
+        }
+    }
+}
+
+fn file_operation(op: &ResourceOp) -> FileOperation {
+    match op {
+        ResourceOp::Create(create) => {
+            let options = create.options.as_ref();
+            FileOperation::Create {
+                path: path_from_url(&create.uri),
+                overwrite: options.and_then(|o| o.overwrite).unwrap_or(false),
+                ignore_if_exists: options
+                    .and_then(|o| o.ignore_if_exists)
+                    .unwrap_or(false),
             }
-            BufferContent::Value(name) => {
-                self.value_buffers.get(name).unwrap().clone()
+        }
+        ResourceOp::Rename(rename) => {
+            let options = rename.options.as_ref();
+            FileOperation::Rename {
+                from: path_from_url(&rename.old_uri),
+                to: path_from_url(&rename.new_uri),
+                overwrite: options.and_then(|o| o.overwrite).unwrap_or(false),
+                ignore_if_exists: options
+                    .and_then(|o| o.ignore_if_exists)
+                    .unwrap_or(false),
+            }
+        }
+        ResourceOp::Delete(delete) => {
+            let options = delete.options.as_ref();
+            FileOperation::Delete {
+                path: path_from_url(&delete.uri),
+                recursive: options.and_then(|o| o.recursive).unwrap_or(false),
+                ignore_if_not_exists: options
+                    .and_then(|o| o.ignore_if_not_exists)
+                    .unwrap_or(false),
+            }
+        }
+    }
+}
+
+/// The code action from the response to resolving it.
+pub fn resolved_code_action(result: &Result<Value>) -> Result<CodeAction> {
+    match result {
+        Ok(value) => Ok(serde_json::from_value::<CodeAction>(value.clone())?),
+        Err(e) => Err(anyhow!("{}", e)),
+    }
+}
+
+/// What came of applying a workspace edit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorkspaceEditSummary {
+    /// Whether the files edited are saved, which they are after a rename but
+    /// not after a code action
+    pub save: bool,
+    pub edited: Vec<PathBuf>,
+    /// The files edited that already had unsaved changes, which are left
+    /// unsaved
+    pub unsaved: Vec<PathBuf>,
+    /// The files left alone because they changed since the edit was made
+    pub skipped: Vec<PathBuf>,
+    /// Why the rest of the edit wasn't applied, if it wasn't
+    pub failed: Option<String>,
+}
+
+impl WorkspaceEditSummary {
+    pub fn message(&self) -> String {
+        let files = |n: usize| {
+            if n == 1 {
+                "1 file".to_string()
+            } else {
+                format!("{} files", n)
             }
         };
+        let mut message = format!("Edited {}", files(self.edited.len()));
+        if !self.skipped.is_empty() {
+            let names = self
+                .skipped
+                .iter()
+                .map(|path| path.file_name().and_then(|s| s.to_str()).unwrap_or(""))
+                .collect::<Vec<&str>>()
+                .join(", ");
+            message.push_str(&format!(
+                ", skipped {} changed since the edit was made: {}",
+                files(self.skipped.len()),
+                names
+            ));
+        }
+        if let Some(reason) = &self.failed {
+            message.push_str(&format!(", then stopped: {}", reason));
+        }
+        message
+    }
+}
+
+/// Gives the edits of a workspace edit without versions the versions in
+/// `versions`, so that the files changed since are known.
+fn with_versions(
+    mut edit: WorkspaceEdit,
+    versions: &HashMap<PathBuf, i32>,
+) -> WorkspaceEdit {
+    if edit.document_changes.is_some() {
+        return edit;
+    }
+    let document_edits = workspace_edit_steps(&edit)
+        .into_iter()
+        .filter_map(|step| {
+            let (path, edits) = match step {
+                WorkspaceEditStep::Edit(path, _, edits) => (path, edits),
+                WorkspaceEditStep::File(_) => return None,
+            };
+            Some(TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri: Url::from_file_path(&path).ok()?,
+                    version: versions.get(&path).copied(),
+                },
+                edits: edits.into_iter().map(OneOf::Left).collect(),
+            })
+        })
+        .collect();
+    edit.changes = None;
+    edit.document_changes = Some(DocumentChanges::Edits(document_edits));
+    edit
+}
+
+impl LapceMainSplitData {
+    /// The buffer of the open file at `path`, or `None` if it has been closed.
+    /// Responses about a file can arrive after it was closed, for example
+    /// while its content is still loading over a slow remote connection, so a
+    /// missing buffer is logged rather than treated as a bug.
+    pub fn open_file_mut(&mut self, path: &Path) -> Option<&mut Arc<Buffer>> {
+        let buffer = self.open_files.get_mut(path);
+        if buffer.is_none() {
+            log::warn!("{} is no longer open", path.display());
+        }
         buffer
     }
 
-    pub fn document_format(
+    /// Fills in the content of the file at `path` once it has loaded.
+    /// Returns `false` if the file was closed while it was loading, leaving
+    /// nothing more to do with it.
+    pub fn load_buffer(
+        &mut self,
+        path: &Path,
+        content: &str,
+        encoding: FileEncoding,
+    ) -> bool {
+        let buffer = match self.open_file_mut(path) {
+            Some(buffer) => Arc::make_mut(buffer),
+            None => return false,
+        };
+        buffer.load_content(content);
+        buffer.set_encoding(encoding);
+        self.update_diagnostics_range(path);
+        true
+    }
+
+    /// The diagnostics of the file at `path` that cover `offset`, most severe
+    /// first.
+    pub fn diagnostics_at(
+        &self,
+        path: &Path,
+        offset: usize,
+    ) -> Vec<&EditorDiagnostic> {
+        self.diagnostics
+            .get(path)
+            .map(|diagnostics| diagnostics_at(diagnostics, offset))
+            .unwrap_or_default()
+    }
+
+    /// The diagnostics at the cursor of the active editor, one per line, as
+    /// they're copied to the clipboard.
+    fn diagnostics_text_at_cursor(&self) -> Option<String> {
+        let editor = self.active_editor()?;
+        let path = match &editor.content {
+            BufferContent::File(path) => path,
+            _ => return None,
+        };
+        let diagnostics = self.diagnostics_at(path, editor.cursor.offset());
+        if diagnostics.is_empty() {
+            return None;
+        }
+        Some(
+            diagnostics
+                .iter()
+                .map(|d| diagnostic_copy_text(path, &d.diagnositc))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+
+    /// Goes to the diagnostic after the one last gone to, or after the cursor
+    /// of the active editor the first time, across all files in the order of
+    /// their paths, wrapping around after the last one.
+    pub fn next_problem(&mut self, ctx: &mut EventCtx, config: &Config) {
+        let from = (*self.last_problem).clone().or_else(|| {
+            let editor = self.active_editor()?;
+            if let BufferContent::File(path) = &editor.content {
+                let buffer = self.open_files.get(path)?;
+                let position = buffer.offset_to_position(editor.cursor.offset());
+                Some((path.clone(), position))
+            } else {
+                None
+            }
+        });
+        // Diagnostics can linger for files that have since been deleted.
+        let local = !self.workspace.kind.is_remote();
+        let problem = next_problem(
+            &self.diagnostics,
+            from.as_ref(),
+            config.editor.next_problem_errors_only,
+            |path| !local || self.open_files.contains_key(path) || path.exists(),
+        );
+
+        match problem {
+            Some((path, position)) => {
+                self.last_problem = Arc::new(Some((path.clone(), position)));
+                let location = EditorLocationNew {
+                    path,
+                    position: Some(position),
+                    scroll_offset: None,
+                    history: None,
+                };
+                self.jump_to_location(ctx, None, location, config);
+            }
+            None => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowStatusMessage(
+                        "No problems in the workspace".to_string(),
+                    ),
+                    Target::Auto,
+                ));
+            }
+        }
+    }
+
+    pub fn active_editor(&self) -> Option<&LapceEditorData> {
+        let id = (*self.active)?;
+        Some(self.editors.get(&id)?.as_ref())
+    }
+
+    pub fn editor_buffer(&self, editor_view_id: WidgetId) -> Arc<Buffer> {
+        let editor = self.editors.get(&editor_view_id).unwrap();
+        let buffer = match &editor.content {
+            BufferContent::File(path) => self.open_files.get(path).unwrap().clone(),
+            BufferContent::Local(kind) => {
+                self.local_buffers.get(kind).unwrap().clone()
+            }
+            BufferContent::Value(name) => {
+                self.value_buffers.get(name).unwrap().clone()
+            }
+        };
+        buffer
+    }
+
+    /// Applies the edits that format the file, unless it changed since they
+    /// were requested. Fails if the language server couldn't format it.
+    pub fn document_format(
+        &mut self,
+        path: &Path,
+        rev: u64,
+        result: &Result<Value>,
+    ) -> Result<()> {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return Ok(()),
+        };
+        if buffer.rev() != rev {
+            return Ok(());
+        }
+
+        match FormatOutcome::of(result) {
+            FormatOutcome::Edits(edits) => {
+                self.apply_text_edits(path, &edits);
+                Ok(())
+            }
+            FormatOutcome::NoChanges => Ok(()),
+            FormatOutcome::Failed(message) => Err(anyhow!(message)),
+        }
+    }
+
+    /// Applies the edits that format `range` of the file, unless it changed
+    /// since they were requested. Edits outside of the range are left out,
+    /// unless the language server formatted the whole file because it can't
+    /// format ranges.
+    pub fn document_range_format(
+        &mut self,
+        path: &Path,
+        rev: u64,
+        range: &Range,
+        result: &Result<Value>,
+    ) -> Result<()> {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return Ok(()),
+        };
+        if buffer.rev() != rev {
+            return Ok(());
+        }
+
+        let response = match result {
+            Ok(value) => {
+                serde_json::from_value::<RangeFormattingResponse>(value.clone())?
+            }
+            Err(e) => return Err(anyhow!("{}", e)),
+        };
+        match FormatOutcome::of(&Ok(response.edits)) {
+            FormatOutcome::Edits(edits) => {
+                let edits = if response.whole_document {
+                    edits
+                } else {
+                    edits_within(edits, range)
+                };
+                self.apply_text_edits(path, &edits);
+                Ok(())
+            }
+            FormatOutcome::NoChanges => Ok(()),
+            FormatOutcome::Failed(message) => Err(anyhow!(message)),
+        }
+    }
+
+    /// Applies the edits that format the file after a character was typed,
+    /// unless more was typed since.
+    pub fn document_on_type_format(
+        &mut self,
+        path: &Path,
+        rev: u64,
+        result: &Value,
+    ) {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        if buffer.rev() != rev {
+            return;
+        }
+
+        if let FormatOutcome::Edits(edits) = FormatOutcome::of(&Ok(result.clone())) {
+            self.apply_text_edits(path, &edits);
+        }
+    }
+
+    /// The path of the file in the active editor.
+    fn active_file(&self) -> Option<PathBuf> {
+        match &self.active_editor()?.content {
+            BufferContent::File(path) => Some(path.clone()),
+            _ => None,
+        }
+    }
+
+    /// Replaces the line endings of the file in the active editor with
+    /// `line_ending`, which new lines get from then on.
+    pub fn change_line_endings(&mut self, line_ending: LineEnding) {
+        let path = match self.active_file() {
+            Some(path) => path,
+            None => return,
+        };
+        let buffer = match self.open_files.get_mut(&path) {
+            Some(buffer) if buffer.loaded() => buffer,
+            _ => return,
+        };
+        Arc::make_mut(buffer).set_line_ending(line_ending);
+        let edits: Vec<(Selection, &str)> = line_ending
+            .ranges_to_convert(buffer.rope())
+            .into_iter()
+            .map(|range| {
+                (
+                    Selection::region(range.start, range.end),
+                    line_ending.as_str(),
+                )
+            })
+            .collect();
+        if !edits.is_empty() {
+            self.edit(&path, &edits, EditType::Other);
+        }
+    }
+
+    /// Indents the file in the active editor with `indent_style` from now on,
+    /// leaving the indentation it already has.
+    pub fn set_indent_style(&mut self, indent_style: IndentStyle) {
+        let path = match self.active_file() {
+            Some(path) => path,
+            None => return,
+        };
+        if let Some(buffer) = self.open_files.get_mut(&path) {
+            Arc::make_mut(buffer).set_indent_style(indent_style);
+        }
+    }
+
+    /// Shows tabs as `tab_width` columns wide in the file in the active
+    /// editor.
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        let path = match self.active_file() {
+            Some(path) => path,
+            None => return,
+        };
+        if let Some(buffer) = self.open_files.get_mut(&path) {
+            Arc::make_mut(buffer).set_tab_width(tab_width);
+        }
+    }
+
+    /// Rewrites the leading whitespace of the file in the active editor with
+    /// tabs or spaces, a level at a time, and indents with them from then on.
+    pub fn convert_indentation(&mut self, to_tabs: bool, config: &Config) {
+        if let Some(path) = self.active_file() {
+            self.convert_file_indentation(&path, to_tabs, config);
+        }
+    }
+
+    /// Rewrites the leading whitespace of the file at `path`, turning each
+    /// level of indentation into a tab or into the spaces of a level. Tabs
+    /// are then shown as wide as a level was, so the file looks the same and
+    /// converting back gives the spaces it had.
+    fn convert_file_indentation(
+        &mut self,
+        path: &Path,
+        to_tabs: bool,
+        config: &Config,
+    ) {
+        let buffer = match self.open_files.get_mut(path) {
+            Some(buffer) if buffer.loaded() => buffer,
+            _ => return,
+        };
+        let buffer = Arc::make_mut(buffer);
+        let tab_width = buffer.tab_width(config).clamp(1, 8);
+        let (indent_style, level_width) = match (to_tabs, buffer.indent_style()) {
+            (true, IndentStyle::Spaces(n)) => {
+                buffer.set_tab_width(n as usize);
+                (IndentStyle::Tabs, n as usize)
+            }
+            (true, IndentStyle::Tabs) => (IndentStyle::Tabs, tab_width),
+            (false, IndentStyle::Spaces(n)) => (IndentStyle::Spaces(n), n as usize),
+            (false, IndentStyle::Tabs) => {
+                (IndentStyle::Spaces(tab_width as u8), tab_width)
+            }
+        };
+        buffer.set_indent_style(indent_style);
+        let edits = reindent(buffer.rope(), indent_style, level_width);
+        let edits: Vec<(Selection, &str)> = edits
+            .iter()
+            .map(|(range, indent)| {
+                (Selection::region(range.start, range.end), indent.as_str())
+            })
+            .collect();
+        if !edits.is_empty() {
+            self.edit(path, &edits, EditType::Other);
+        }
+    }
+
+    /// Reads the file in the active editor again as `encoding`. Unsaved
+    /// changes would be lost, so they have to be saved first.
+    pub fn reopen_with_encoding(&self, ctx: &mut EventCtx, encoding: &str) {
+        let path = match self.active_file() {
+            Some(path) => path,
+            None => return,
+        };
+        let buffer = match self.open_files.get(&path) {
+            Some(buffer) if buffer.loaded() => buffer,
+            _ => return,
+        };
+        if buffer.dirty() {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ShowStatusMessage(
+                    "Save the file before reopening it with another encoding"
+                        .to_string(),
+                ),
+                Target::Auto,
+            ));
+            return;
+        }
+        let event_sink = ctx.get_external_handle();
+        let tab_id = *self.tab_id;
+        self.proxy.reopen_with_encoding(
+            buffer.id(),
+            encoding,
+            Box::new(move |result| {
+                let command = match result
+                    .map(serde_json::from_value::<FileEncoding>)
+                {
+                    Ok(Ok(encoding)) => LapceUICommand::SetEncoding(path, encoding),
+                    Ok(Err(e)) => LapceUICommand::ShowStatusMessage(format!(
+                        "Can't reopen the file: {e}"
+                    )),
+                    Err(e) => LapceUICommand::ShowStatusMessage(format!(
+                        "Can't reopen the file: {}",
+                        e.get("message")
+                            .and_then(|message| message.as_str())
+                            .unwrap_or("unknown error")
+                    )),
+                };
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    command,
+                    Target::Widget(tab_id),
+                );
+            }),
+        );
+    }
+
+    fn apply_text_edits(&mut self, path: &Path, edits: &[TextEdit]) {
+        let buffer = self.open_files.get_mut(path).unwrap();
+        let edits: Vec<(Selection, &str)> = edits
+            .iter()
+            .map(|edit| {
+                let selection = Selection::region(
+                    buffer.offset_of_position(&edit.range.start),
+                    buffer.offset_of_position(&edit.range.end),
+                );
+                (selection, edit.new_text.as_str())
+            })
+            .collect();
+
+        self.edit(path, &edits, EditType::Other);
+    }
+
+    /// Asks the language server to format the file, waiting a second at
+    /// most, and applies the edits.
+    pub fn format_document(&self, ctx: &mut EventCtx, path: &Path, config: &Config) {
+        self.request_formatting(
+            ctx.get_external_handle(),
+            path,
+            config,
+            |proxy, buffer_id, options, f| {
+                proxy.get_document_formatting(buffer_id, options, f)
+            },
+            LapceUICommand::DocumentFormat,
+        );
+    }
+
+    /// Asks the language server to format `range` of the file, waiting a
+    /// second at most, and applies the edits.
+    pub fn format_range(
+        &self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        range: Range,
+        config: &Config,
+    ) {
+        self.request_formatting(
+            ctx.get_external_handle(),
+            path,
+            config,
+            move |proxy, buffer_id, options, f| {
+                proxy.get_document_range_formatting(buffer_id, range, options, f)
+            },
+            move |path, rev, result| {
+                LapceUICommand::DocumentRangeFormat(path, rev, range, result)
+            },
+        );
+    }
+
+    /// Whether the language server of the buffer's language formats the code
+    /// after `ch` is typed.
+    pub fn formats_on_type(&self, buffer: &Buffer, ch: &str) -> bool {
+        buffer
+            .language_id()
+            .and_then(|id| self.on_type_formatting_triggers.get(id))
+            .map(|characters| characters.iter().any(|c| c == ch))
+            .unwrap_or(false)
+    }
+
+    /// Whether the language server of the buffer's language completes after
+    /// `ch`.
+    pub fn triggers_completion(&self, buffer: &Buffer, ch: &str) -> bool {
+        buffer
+            .language_id()
+            .and_then(|id| self.completion_triggers.get(id))
+            .map(|characters| characters.iter().any(|c| c == ch))
+            .unwrap_or(false)
+    }
+
+    /// Asks the language server to format the file after `ch` was typed at
+    /// `position`. Failures are ignored, as nothing was asked of the user.
+    pub fn format_on_type(
+        &self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        position: Position,
+        ch: &str,
+        config: &Config,
+    ) {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let path = path.to_path_buf();
+        let rev = buffer.rev();
+        let event_sink = ctx.get_external_handle();
+        self.proxy.get_on_type_formatting(
+            buffer.id(),
+            position,
+            ch.to_string(),
+            formatting_options(buffer, config),
+            Box::new(move |result| {
+                if let Ok(value) = result {
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::DocumentOnTypeFormat(path, rev, value),
+                        Target::Auto,
+                    );
+                }
+            }),
+        );
+    }
+
+    /// Saves the file, formatting it first if its language is formatted on
+    /// save.
+    pub fn save(&self, ctx: &mut EventCtx, path: &Path, config: &Config) {
+        self.request_save(ctx.get_external_handle(), path, config);
+    }
+
+    fn request_save(&self, event_sink: ExtEventSink, path: &Path, config: &Config) {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let language = buffer
+            .syntax()
+            .map(|syntax| syntax.language)
+            .or_else(|| LapceLanguage::from_path(path));
+        if config.editor.formats_on_save(language) {
+            self.format_and_save(event_sink, path, config);
+        } else {
+            self.save_unformatted(event_sink, path);
+        }
+    }
+
+    fn save_unformatted(&self, event_sink: ExtEventSink, path: &Path) {
+        if let Some(buffer) = self.open_files.get(path) {
+            let rev = buffer.rev();
+            self.proxy.save(
+                rev,
+                buffer.id(),
+                Self::save_callback(event_sink, path, rev),
+            );
+        }
+    }
+
+    /// Where the symbol at `offset` of the file is, and the name to suggest
+    /// renaming it from, from the response to preparing the rename. `None`
+    /// if the file changed since.
+    pub fn rename_placeholder(
+        &self,
+        path: &Path,
+        rev: u64,
+        offset: usize,
+        result: &Result<Value>,
+    ) -> Result<Option<(Position, String)>> {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) if buffer.rev() == rev => buffer,
+            _ => return Ok(None),
+        };
+        let response = match result {
+            Ok(value) => serde_json::from_value::<Option<PrepareRenameResponse>>(
+                value.clone(),
+            )?,
+            Err(e) => return Err(anyhow!("{}", e)),
+        };
+        let range = match response {
+            Some(PrepareRenameResponse::Range(range)) => range,
+            Some(PrepareRenameResponse::RangeWithPlaceholder {
+                range,
+                placeholder,
+            }) => return Ok(Some((range.start, placeholder))),
+            Some(PrepareRenameResponse::DefaultBehavior { .. }) => {
+                let (start, end) = buffer.select_word(offset);
+                Range::new(
+                    buffer.offset_to_position(start),
+                    buffer.offset_to_position(end),
+                )
+            }
+            None => return Err(anyhow!("there's no symbol to rename here")),
+        };
+        let start = buffer.offset_of_position(&range.start);
+        let end = buffer.offset_of_position(&range.end);
+        let placeholder = buffer.slice_to_cow(start..end).to_string();
+        Ok(Some((range.start, placeholder)))
+    }
+
+    /// Asks the language server for the edit of a code action that came
+    /// without one, unless another action is being resolved already.
+    pub fn resolve_code_action(
+        &mut self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        action: CodeAction,
+    ) {
+        if self.resolving_code_action {
+            return;
+        }
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        self.resolving_code_action = true;
+        let path = path.to_path_buf();
+        let proxy = self.proxy.clone();
+        let buffer_id = buffer.id();
+        let event_sink = ctx.get_external_handle();
+        let tab_id = *self.tab_id;
+        let (sender, receiver) = bounded(1);
+        thread::spawn(move || {
+            proxy.code_action_resolve(
+                buffer_id,
+                action,
+                Box::new(move |result| {
+                    let _ = sender.send(result);
+                }),
+            );
+
+            let result = match receiver.recv_timeout(Duration::from_secs(3)) {
+                Ok(Ok(value)) => Ok(value),
+                Ok(Err(e)) => Err(anyhow!(
+                    "{}",
+                    e.get("message")
+                        .and_then(|message| message.as_str())
+                        .unwrap_or("unknown error")
+                )),
+                Err(_) => Err(anyhow!("the language server took too long")),
+            };
+
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::CodeActionResolved(path, result),
+                Target::Widget(tab_id),
+            );
+        });
+    }
+
+    /// Asks the language server of the file to run `command`.
+    pub fn execute_command(&self, path: &Path, command: &lsp_types::Command) {
+        if let Some(buffer) = self.open_files.get(path) {
+            self.proxy.execute_command(buffer.id(), command);
+        }
+    }
+
+    /// Asks the language server to rename the symbol at `position` to
+    /// `new_name`, and applies the edits to every file it's in. The files
+    /// open now are taken to be the ones the edits are made for.
+    pub fn rename(
+        &self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        position: Position,
+        new_name: String,
+    ) {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let versions: HashMap<PathBuf, i32> = self
+            .open_files
+            .iter()
+            .filter(|(_, buffer)| buffer.loaded())
+            .map(|(path, buffer)| (path.clone(), buffer.rev() as i32))
+            .collect();
+        let event_sink = ctx.get_external_handle();
+        let tab_id = *self.tab_id;
+        self.proxy.rename(
+            buffer.id(),
+            position,
+            new_name,
+            Box::new(move |result| {
+                let command = match result {
+                    Ok(value) => {
+                        match serde_json::from_value::<Option<WorkspaceEdit>>(value)
+                        {
+                            Ok(Some(edit)) => LapceUICommand::ApplyWorkspaceEdit {
+                                edit: with_versions(edit, &versions),
+                                save: true,
+                            },
+                            Ok(None) => LapceUICommand::ShowStatusMessage(
+                                "Nothing to rename".to_string(),
+                            ),
+                            Err(e) => LapceUICommand::ShowStatusMessage(format!(
+                                "Rename failed: invalid edits: {}",
+                                e
+                            )),
+                        }
+                    }
+                    Err(e) => LapceUICommand::ShowStatusMessage(format!(
+                        "Rename failed: {}",
+                        e.get("message")
+                            .and_then(|message| message.as_str())
+                            .unwrap_or("unknown error")
+                    )),
+                };
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    command,
+                    Target::Widget(tab_id),
+                );
+            }),
+        );
+    }
+
+    /// Applies a workspace edit, and with `save` saves the files edited,
+    /// apart from those with unsaved changes already. Files that aren't
+    /// loaded are loaded and edited afterwards, and edits made for an older
+    /// version of a file are skipped. Cursors stay where they were in the
+    /// text around them.
+    ///
+    /// Steps after a change to the files of the workspace are taken once the
+    /// proxy has made it, with the summary coming from
+    /// [`resume_workspace_edit`](Self::resume_workspace_edit).
+    pub fn apply_workspace_edit(
+        &mut self,
+        ctx: &mut EventCtx,
+        edit: &WorkspaceEdit,
+        save: bool,
+    ) -> Option<WorkspaceEditSummary> {
+        self.apply_workspace_edit_steps(
+            ctx.get_external_handle(),
+            workspace_edit_steps(edit),
+            WorkspaceEditSummary {
+                save,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Continues a workspace edit after the proxy made `operation`, stopping
+    /// there if it failed.
+    pub fn resume_workspace_edit(
+        &mut self,
+        ctx: &mut EventCtx,
+        operation: &FileOperation,
+        result: &Result<(), String>,
+        steps: Vec<WorkspaceEditStep>,
+        mut summary: WorkspaceEditSummary,
+    ) -> Option<WorkspaceEditSummary> {
+        if let Err(e) = result {
+            summary.failed = Some(e.to_string());
+            return Some(summary);
+        }
+        match operation {
+            FileOperation::Rename { from, to, .. } => {
+                self.rename_open_paths(from, to);
+            }
+            FileOperation::Delete { path, .. } => {
+                self.mark_deleted(path);
+            }
+            FileOperation::Create { .. } => {}
+        }
+        self.apply_workspace_edit_steps(ctx.get_external_handle(), steps, summary)
+    }
+
+    fn apply_workspace_edit_steps(
+        &mut self,
+        event_sink: ExtEventSink,
+        steps: Vec<WorkspaceEditStep>,
+        mut summary: WorkspaceEditSummary,
+    ) -> Option<WorkspaceEditSummary> {
+        let mut steps = steps.into_iter();
+        while let Some(step) = steps.next() {
+            match step {
+                WorkspaceEditStep::Edit(path, version, edits) => {
+                    self.apply_workspace_text_edits(
+                        event_sink.clone(),
+                        path,
+                        version,
+                        edits,
+                        &mut summary,
+                    );
+                }
+                WorkspaceEditStep::File(operation) => {
+                    let steps: Vec<WorkspaceEditStep> = steps.collect();
+                    let tab_id = *self.tab_id;
+                    self.proxy.apply_file_operation(
+                        operation.clone(),
+                        Box::new(move |result| {
+                            let result = result.map(|_| ()).map_err(|e| {
+                                e.get("message")
+                                    .and_then(|message| message.as_str())
+                                    .unwrap_or("unknown error")
+                                    .to_string()
+                            });
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::ResumeWorkspaceEdit {
+                                    operation,
+                                    result,
+                                    steps,
+                                    summary,
+                                },
+                                Target::Widget(tab_id),
+                            );
+                        }),
+                    );
+                    return None;
+                }
+            }
+        }
+        Some(summary)
+    }
+
+    fn apply_workspace_text_edits(
+        &mut self,
+        event_sink: ExtEventSink,
+        path: PathBuf,
+        version: Option<i32>,
+        edits: Vec<TextEdit>,
+        summary: &mut WorkspaceEditSummary,
+    ) {
+        // Later edits to a file in the same workspace edit are made for the
+        // version it had before the first
+        let edited = summary.edited.contains(&path);
+        match self.open_files.get(&path) {
+            Some(buffer) if buffer.loaded() => {
+                if !edited
+                    && version.map(|v| v as u64 != buffer.rev()).unwrap_or(false)
+                {
+                    summary.skipped.push(path);
+                    return;
+                }
+                if !edited && buffer.dirty() {
+                    summary.unsaved.push(path.clone());
+                }
+                self.apply_text_edits(&path, &edits);
+                if summary.save && !summary.unsaved.contains(&path) {
+                    self.save_unformatted(event_sink, &path);
+                }
+            }
+            Some(_) => {
+                let pending = self
+                    .pending_edits
+                    .entry(path.clone())
+                    .or_insert_with(|| Arc::new(Vec::new()));
+                Arc::make_mut(pending).push((edits, summary.save));
+            }
+            None => {
+                let buffer = Arc::new(Buffer::new(
+                    BufferContent::File(path.clone()),
+                    *self.tab_id,
+                    event_sink.clone(),
+                ));
+                self.open_files.insert(path.clone(), buffer.clone());
+                self.pending_edits
+                    .insert(path.clone(), Arc::new(vec![(edits, summary.save)]));
+                buffer.retrieve_file(
+                    *self.tab_id,
+                    self.proxy.clone(),
+                    event_sink,
+                    vec![],
+                );
+            }
+        }
+        if !edited {
+            summary.edited.push(path);
+        }
+    }
+
+    /// Points the open files under `from`, and the editors showing them, at
+    /// where they are after `from` was moved to `to`.
+    fn rename_open_paths(&mut self, from: &Path, to: &Path) {
+        let renamed: Vec<(PathBuf, PathBuf)> = self
+            .open_files
+            .keys()
+            .filter_map(|path| {
+                let suffix = path.strip_prefix(from).ok()?;
+                if suffix.as_os_str().is_empty() {
+                    Some((path.clone(), to.to_path_buf()))
+                } else {
+                    Some((path.clone(), to.join(suffix)))
+                }
+            })
+            .collect();
+        for (old_path, new_path) in renamed {
+            if let Some(mut buffer) = self.open_files.remove(&old_path) {
+                Arc::make_mut(&mut buffer).set_path(new_path.clone());
+                self.open_files.insert(new_path.clone(), buffer);
+            }
+            if let Some(edits) = self.pending_edits.remove(&old_path) {
+                self.pending_edits.insert(new_path.clone(), edits);
+            }
+            for editor in self.editors.values_mut() {
+                let shows_file = matches!(
+                    &editor.content,
+                    BufferContent::File(path) if path == &old_path
+                );
+                if shows_file {
+                    Arc::make_mut(editor).content =
+                        BufferContent::File(new_path.clone());
+                }
+            }
+        }
+    }
+
+    /// Marks the open files under the deleted `path` as having unsaved
+    /// changes, so that saving them writes them back, and drops the edits
+    /// waiting for them to load.
+    pub fn mark_deleted(&mut self, path: &Path) {
+        for (buffer_path, buffer) in self.open_files.iter_mut() {
+            if buffer_path.starts_with(path) {
+                Arc::make_mut(buffer).set_dirty(true);
+            }
+        }
+        self.pending_edits
+            .retain(|pending_path, _| !pending_path.starts_with(path));
+    }
+
+    /// Applies the edits that were waiting for the file at `path` to load,
+    /// and saves it if any of the workspace edits they came from saves.
+    pub fn apply_pending_edits(&mut self, ctx: &mut EventCtx, path: &Path) {
+        if let Some(edits) = self.pending_edits.remove(path) {
+            for (edits, _) in edits.iter() {
+                self.apply_text_edits(path, edits);
+            }
+            if edits.iter().any(|(_, save)| *save) {
+                self.save_unformatted(ctx.get_external_handle(), path);
+            }
+        }
+    }
+
+    /// Asks the language server to format the file, waiting a second at
+    /// most, and saves it with the edits applied.
+    fn format_and_save(
+        &self,
+        event_sink: ExtEventSink,
+        path: &Path,
+        config: &Config,
+    ) {
+        self.request_formatting(
+            event_sink,
+            path,
+            config,
+            |proxy, buffer_id, options, f| {
+                proxy.get_document_formatting(buffer_id, options, f)
+            },
+            LapceUICommand::DocumentFormatAndSave,
+        );
+    }
+
+    /// Sends the formatting request made by `request`, and submits the
+    /// command made by `command` from the response.
+    fn request_formatting<R, C>(
+        &self,
+        event_sink: ExtEventSink,
+        path: &Path,
+        config: &Config,
+        request: R,
+        command: C,
+    ) where
+        R: FnOnce(&LapceProxy, BufferId, FormattingOptions, Box<dyn Callback>)
+            + Send
+            + 'static,
+        C: FnOnce(PathBuf, u64, Result<Value>) -> LapceUICommand + Send + 'static,
+    {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let path = path.to_path_buf();
+        let proxy = self.proxy.clone();
+        let buffer_id = buffer.id();
+        let rev = buffer.rev();
+        let options = formatting_options(buffer, config);
+        let (sender, receiver) = bounded(1);
+        thread::spawn(move || {
+            request(
+                proxy.as_ref(),
+                buffer_id,
+                options,
+                Box::new(move |result| {
+                    let _ = sender.send(result);
+                }),
+            );
+
+            let result = match receiver.recv_timeout(Duration::from_secs(1)) {
+                Ok(Ok(value)) => Ok(value),
+                Ok(Err(e)) => Err(anyhow!(
+                    "{}",
+                    e.get("message")
+                        .and_then(|message| message.as_str())
+                        .unwrap_or("unknown error")
+                )),
+                Err(_) => Err(anyhow!("the language server took too long")),
+            };
+
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                command(path, rev, result),
+                Target::Auto,
+            );
+        });
+    }
+
+    pub fn document_format_and_save(
+        &mut self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        rev: u64,
+        result: &Result<Value>,
+    ) -> Result<()> {
+        // The file is saved even if formatting failed, just unformatted
+        let formatted = self.document_format(path, rev, result);
+
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return formatted,
+        };
+        let rev = buffer.rev();
+        let buffer_id = buffer.id();
+        self.proxy.save(
+            rev,
+            buffer_id,
+            Self::save_callback(ctx.get_external_handle(), path, rev),
+        );
+        formatted
+    }
+
+    /// Builds the callback for a save request, which marks the buffer clean
+    /// or reports why the save failed.
+    fn save_callback(
+        event_sink: ExtEventSink,
+        path: &Path,
+        rev: u64,
+    ) -> Box<dyn Callback> {
+        let path = path.to_path_buf();
+        Box::new(move |result: Result<Value, Value>| {
+            let command = match result {
+                Ok(_) => LapceUICommand::BufferSave(path, rev),
+                Err(e) => {
+                    let kind = e
+                        .get("data")
+                        .and_then(|data| {
+                            serde_json::from_value::<SaveErrorKind>(data.clone())
+                                .ok()
+                        })
+                        .unwrap_or(SaveErrorKind::Other);
+                    let message = e
+                        .get("message")
+                        .and_then(|message| message.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    LapceUICommand::BufferSaveFailed(path, kind, message)
+                }
+            };
+            let _ =
+                event_sink.submit_command(LAPCE_UI_COMMAND, command, Target::Auto);
+        })
+    }
+
+    pub fn save_elevated(&self, ctx: &mut EventCtx, path: &Path, helper: String) {
+        if let Some(buffer) = self.open_files.get(path) {
+            let rev = buffer.rev();
+            self.proxy.save_elevated(
+                rev,
+                buffer.id(),
+                helper,
+                Self::save_callback(ctx.get_external_handle(), path, rev),
+            );
+        }
+    }
+
+    pub fn save_overwrite_permissions(&self, ctx: &mut EventCtx, path: &Path) {
+        if let Some(buffer) = self.open_files.get(path) {
+            let rev = buffer.rev();
+            self.proxy.save_overwrite_permissions(
+                rev,
+                buffer.id(),
+                Self::save_callback(ctx.get_external_handle(), path, rev),
+            );
+        }
+    }
+
+    /// Replaces `pattern` in the given lines of the open file `path` and
+    /// saves it. Returns false without touching the file if any line no
+    /// longer reads as given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn replace_lines_and_save(
+        &mut self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        lines: &[(usize, String)],
+        pattern: &str,
+        replacement: &str,
+        options: SearchOptions,
+    ) -> bool {
+        let buffer = match self.open_files.get(path) {
+            Some(buffer) => buffer,
+            None => return false,
+        };
+        let mut edits = Vec::new();
+        for (line_number, line) in lines {
+            let line_index = match line_number.checked_sub(1) {
+                Some(index) if index < buffer.num_lines() => index,
+                _ => return false,
+            };
+            if buffer.line_content(line_index) != line.as_str() {
+                return false;
+            }
+            let start = buffer.offset_of_line(line_index);
+            edits.push((
+                Selection::region(start, start + line.len()),
+                replace_in_line(line, pattern, replacement, options).into_owned(),
+            ));
+        }
+        let edits: Vec<(Selection, &str)> = edits
+            .iter()
+            .map(|(selection, text)| (selection.clone(), text.as_str()))
+            .collect();
+        self.edit(path, &edits, EditType::Other);
+
+        if let Some(buffer) = self.open_files.get(path) {
+            let rev = buffer.rev();
+            self.proxy.save(
+                rev,
+                buffer.id(),
+                Self::save_callback(ctx.get_external_handle(), path, rev),
+            );
+        }
+        true
+    }
+
+    /// Writes the buffer of `path` to `target` and opens the new file.
+    pub fn save_as(&self, ctx: &mut EventCtx, path: &Path, target: &Path) {
+        if let Some(buffer) = self.open_files.get(path) {
+            let event_sink = ctx.get_external_handle();
+            let path = path.to_path_buf();
+            let target = target.to_path_buf();
+            self.proxy.save_as(
+                buffer.rev(),
+                buffer.id(),
+                target.clone(),
+                Box::new(move |result: Result<Value, Value>| {
+                    let command = match result {
+                        Ok(_) => LapceUICommand::OpenFile(target),
+                        Err(e) => LapceUICommand::BufferSaveFailed(
+                            path,
+                            SaveErrorKind::Other,
+                            e.get("message")
+                                .and_then(|message| message.as_str())
+                                .unwrap_or("")
+                                .to_string(),
+                        ),
+                    };
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        command,
+                        Target::Auto,
+                    );
+                }),
+            );
+        }
+    }
+
+    /// Replaces the diagnostics of the file at `path`. Its cached code actions
+    /// are dropped, as they were asked for with the diagnostics they replace.
+    pub fn set_diagnostics(
         &mut self,
         path: &Path,
-        rev: u64,
-        result: &Result<Value>,
-        config: &Config,
+        diagnostics: Vec<EditorDiagnostic>,
     ) {
-        let buffer = self.open_files.get(path).unwrap();
-        if buffer.rev() != rev {
-            return;
+        self.diagnostics
+            .insert(path.to_path_buf(), Arc::new(diagnostics));
+        if let Some(buffer) = self.open_files.get_mut(path) {
+            Arc::make_mut(buffer).clear_code_actions();
         }
+        // Without a loaded buffer, the offsets are filled in once it loads.
+        self.update_diagnostics_range(path);
+    }
 
-        if let Ok(res) = result {
-            let edits: Result<Vec<TextEdit>, serde_json::Error> =
-                serde_json::from_value(res.clone());
-            if let Ok(edits) = edits {
-                if !edits.is_empty() {
-                    let buffer = self.open_files.get_mut(path).unwrap();
-
-                    let edits: Vec<(Selection, &str)> = edits
-                        .iter()
-                        .map(|edit| {
-                            let selection = Selection::region(
-                                buffer.offset_of_position(
-                                    &edit.range.start,
-                                    config.editor.tab_width,
-                                ),
-                                buffer.offset_of_position(
-                                    &edit.range.end,
-                                    config.editor.tab_width,
-                                ),
-                            );
-                            (selection, edit.new_text.as_str())
-                        })
-                        .collect();
-
-                    self.edit(path, &edits, EditType::Other, config);
+    /// Places the diagnostics of the file at `path` in its buffer by their
+    /// positions, replacing any offsets they had, if the buffer has loaded.
+    pub fn update_diagnostics_range(&mut self, path: &Path) {
+        if let Some(diagnostics) = self.diagnostics.get_mut(path) {
+            if let Some(buffer) =
+                self.open_files.get(path).filter(|buffer| buffer.loaded())
+            {
+                for diagnostic in Arc::make_mut(diagnostics).iter_mut() {
+                    diagnostic.range = Some(diagnostic.offsets_in(buffer));
                 }
             }
         }
     }
 
-    pub fn document_format_and_save(
-        &mut self,
-        ctx: &mut EventCtx,
-        path: &Path,
-        rev: u64,
-        result: &Result<Value>,
-        config: &Config,
-    ) {
-        self.document_format(path, rev, result, config);
-
-        let buffer = self.open_files.get(path).unwrap();
-        let rev = buffer.rev();
-        let buffer_id = buffer.id();
-        let event_sink = ctx.get_external_handle();
-        let path = PathBuf::from(path);
-        self.proxy.save(
-            rev,
-            buffer_id,
-            Box::new(move |result| {
-                if let Ok(_r) = result {
-                    let _ = event_sink.submit_command(
-                        LAPCE_UI_COMMAND,
-                        LapceUICommand::BufferSave(path, rev),
-                        Target::Auto,
-                    );
-                }
-            }),
-        );
-    }
-
-    fn initiate_diagnositcs_offset(&mut self, path: &Path, config: &Config) {
+    fn initiate_diagnositcs_offset(&mut self, path: &Path) {
         if let Some(diagnostics) = self.diagnostics.get_mut(path) {
             if let Some(buffer) = self.open_files.get(path) {
                 for diagnostic in Arc::make_mut(diagnostics).iter_mut() {
                     if diagnostic.range.is_none() {
-                        diagnostic.range = Some((
-                            buffer.offset_of_position(
-                                &diagnostic.diagnositc.range.start,
-                                config.editor.tab_width,
-                            ),
-                            buffer.offset_of_position(
-                                &diagnostic.diagnositc.range.end,
-                                config.editor.tab_width,
-                            ),
-                        ));
+                        diagnostic.range = Some(diagnostic.offsets_in(buffer));
                     }
                 }
             }
         }
     }
 
-    fn update_diagnositcs_offset(
-        &mut self,
-        path: &Path,
-        delta: &RopeDelta,
-        config: &Config,
-    ) {
+    fn update_diagnositcs_offset(&mut self, path: &Path, delta: &RopeDelta) {
         if let Some(diagnostics) = self.diagnostics.get_mut(path) {
             if let Some(buffer) = self.open_files.get(path) {
                 let mut transformer = Transformer::new(delta);
@@ -2041,13 +4983,12 @@ impl LapceMainSplitData {
                     );
                     diagnostic.range = Some((new_start, new_end));
                     if start != new_start {
-                        diagnostic.diagnositc.range.start = buffer
-                            .offset_to_position(new_start, config.editor.tab_width);
+                        diagnostic.diagnositc.range.start =
+                            buffer.offset_to_position(new_start);
                     }
                     if end != new_end {
-                        diagnostic.diagnositc.range.end = buffer
-                            .offset_to_position(new_end, config.editor.tab_width);
-                        buffer.offset_to_position(new_end, config.editor.tab_width);
+                        diagnostic.diagnositc.range.end =
+                            buffer.offset_to_position(new_end);
                     }
                 }
             }
@@ -2069,9 +5010,8 @@ impl LapceMainSplitData {
         path: &Path,
         edits: &[(impl AsRef<Selection>, &str)],
         edit_type: EditType,
-        config: &Config,
     ) -> Option<RopeDelta> {
-        self.initiate_diagnositcs_offset(path, config);
+        self.initiate_diagnositcs_offset(path);
         let proxy = self.proxy.clone();
         let buffer = self.open_files.get_mut(path)?;
 
@@ -2093,7 +5033,7 @@ impl LapceMainSplitData {
         if move_cursor {
             self.cursor_apply_delta(path, &delta);
         }
-        self.update_diagnositcs_offset(path, &delta, config);
+        self.update_diagnositcs_offset(path, &delta);
         Some(delta)
     }
 
@@ -2340,7 +5280,7 @@ impl LapceMainSplitData {
             Some(location.path.clone()),
             config,
         );
-        editor.save_jump_location(buffer.data(), config.editor.tab_width);
+        editor.save_jump_location(buffer.data());
         self.go_to_location(ctx, Some(editor_view_id), location, config);
         editor_view_id
     }
@@ -2386,6 +5326,7 @@ impl LapceMainSplitData {
                 buffer.scroll_offset =
                     Vec2::new(info.scroll_offset.0, info.scroll_offset.1);
                 buffer.cursor_offset = info.cursor_offset;
+                buffer.restore_folds(info.folds);
             }
             let buffer = Arc::new(buffer);
             self.open_files.insert(path.clone(), buffer.clone());
@@ -2396,17 +5337,21 @@ impl LapceMainSplitData {
                 vec![(editor_view_id, location)],
             );
         } else {
-            let buffer = self.open_files.get_mut(&path).unwrap().clone();
+            let buffer = match self.open_files.get(&path) {
+                Some(buffer) => buffer.clone(),
+                None => return,
+            };
 
             let (offset, scroll_offset) = match &location.position {
                 Some(position) => {
-                    let offset =
-                        buffer.offset_of_position(position, config.editor.tab_width);
-                    let buffer = self.open_files.get_mut(&path).unwrap();
-                    let buffer = Arc::make_mut(buffer);
-                    buffer.cursor_offset = offset;
-                    if let Some(scroll_offset) = location.scroll_offset.as_ref() {
-                        buffer.scroll_offset = *scroll_offset;
+                    let offset = buffer.offset_of_position(position);
+                    if let Some(buffer) = self.open_files.get_mut(&path) {
+                        let buffer = Arc::make_mut(buffer);
+                        buffer.cursor_offset = offset;
+                        if let Some(scroll_offset) = location.scroll_offset.as_ref()
+                        {
+                            buffer.scroll_offset = *scroll_offset;
+                        }
                     }
 
                     (offset, location.scroll_offset.as_ref())
@@ -2478,7 +5423,7 @@ impl LapceMainSplitData {
         } else {
             0
         });
-        let position = buffer.offset_to_position(offset, config.editor.tab_width);
+        let position = buffer.offset_to_position(offset);
         self.jump_to_position(ctx, Some(editor_view_id), position, config);
     }
 }
@@ -2536,11 +5481,16 @@ impl LapceMainSplitData {
             palette_preview_editor: Arc::new(palette_preview_editor),
             show_code_actions: false,
             current_code_actions: 0,
+            resolving_code_action: false,
             diagnostics: im::HashMap::new(),
             error_count: 0,
             warning_count: 0,
+            last_problem: Arc::new(None),
             workspace,
             db,
+            pending_edits: im::HashMap::new(),
+            on_type_formatting_triggers: im::HashMap::new(),
+            completion_triggers: im::HashMap::new(),
         };
 
         if let Some(info) = workspace_info {
@@ -2666,6 +5616,27 @@ impl LapceMainSplitData {
         *split.layout_rect.borrow_mut() = rect;
     }
 
+    /// Removes the editor, and closes the buffer of its file when no other
+    /// editor shows it and it has no unsaved changes.
+    pub fn remove_editor(&mut self, view_id: &WidgetId) {
+        let path = match self.editors.remove(view_id).map(|e| e.content.clone()) {
+            Some(BufferContent::File(path)) => path,
+            _ => return,
+        };
+        let shown = self.editors.values().any(
+            |editor| matches!(&editor.content, BufferContent::File(p) if p == &path),
+        );
+        let buffer = match self.open_files.get(&path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        if shown || buffer.dirty() || self.pending_edits.contains_key(&path) {
+            return;
+        }
+        self.proxy.close_buffer(buffer.id());
+        self.open_files.remove(&path);
+    }
+
     pub fn editor_close(&mut self, ctx: &mut EventCtx, view_id: WidgetId) {
         let editor = self.editors.get(&view_id).unwrap();
         if let BufferContent::File(path) = &editor.content {
@@ -2932,15 +5903,11 @@ impl EditorTabChild {
         }
     }
 
-    pub fn child_info(
-        &self,
-        data: &LapceTabData,
-        tab_width: usize,
-    ) -> EditorTabChildInfo {
+    pub fn child_info(&self, data: &LapceTabData) -> EditorTabChildInfo {
         match &self {
             EditorTabChild::Editor(view_id, _) => {
                 let editor_data = data.main_split.editors.get(view_id).unwrap();
-                EditorTabChildInfo::Editor(editor_data.editor_info(data, tab_width))
+                EditorTabChildInfo::Editor(editor_data.editor_info(data))
             }
         }
     }
@@ -2967,14 +5934,14 @@ pub struct LapceEditorTabData {
 }
 
 impl LapceEditorTabData {
-    pub fn tab_info(&self, data: &LapceTabData, tab_width: usize) -> EditorTabInfo {
+    pub fn tab_info(&self, data: &LapceTabData) -> EditorTabInfo {
         let info = EditorTabInfo {
             active: self.active,
             is_focus: *data.main_split.active_tab == Some(self.widget_id),
             children: self
                 .children
                 .iter()
-                .map(|child| child.child_info(data, tab_width))
+                .map(|child| child.child_info(data))
                 .collect(),
         };
         info
@@ -3010,7 +5977,7 @@ pub struct LapceEditorData {
     pub selection_history: SelectionHistory,
     pub size: Rc<RefCell<Size>>,
     pub window_origin: Point,
-    pub snippet: Option<Vec<(usize, (usize, usize))>>,
+    pub snippet: Option<SnippetExpansion>,
     pub locations: Vec<EditorLocationNew>,
     pub current_location: usize,
     pub last_movement: Movement,
@@ -3073,36 +6040,17 @@ impl LapceEditorData {
         &mut self,
         new_placeholders: Vec<(usize, (usize, usize))>,
     ) {
-        if self.snippet.is_none() {
-            if new_placeholders.len() > 1 {
-                self.snippet = Some(new_placeholders);
-            }
-            return;
-        }
-
-        let placeholders = self.snippet.as_mut().unwrap();
-
-        let mut current = 0;
-        let offset = self.cursor.offset();
-        for (i, (_, (start, end))) in placeholders.iter().enumerate() {
-            if *start <= offset && offset <= *end {
-                current = i;
-                break;
-            }
+        match self.snippet.as_mut() {
+            Some(snippet) => snippet.insert_nested(new_placeholders),
+            None => self.snippet = SnippetExpansion::new(new_placeholders),
         }
-
-        let v = placeholders.split_off(current);
-        placeholders.extend_from_slice(&new_placeholders);
-        placeholders.extend_from_slice(&v[1..]);
     }
 
-    pub fn save_jump_location(&mut self, buffer: &BufferData, tab_width: usize) {
+    pub fn save_jump_location(&mut self, buffer: &BufferData) {
         if let BufferContent::File(path) = buffer.content() {
             let location = EditorLocationNew {
                 path: path.clone(),
-                position: Some(
-                    buffer.offset_to_position(self.cursor.offset(), tab_width),
-                ),
+                position: Some(buffer.offset_to_position(self.cursor.offset())),
                 scroll_offset: Some(self.scroll_offset),
                 history: None,
             };
@@ -3111,13 +6059,13 @@ impl LapceEditorData {
         }
     }
 
-    pub fn editor_info(&self, data: &LapceTabData, tab_width: usize) -> EditorInfo {
+    pub fn editor_info(&self, data: &LapceTabData) -> EditorInfo {
         let info = EditorInfo {
             content: self.content.clone(),
             scroll_offset: (self.scroll_offset.x, self.scroll_offset.y),
             position: if let BufferContent::File(path) = &self.content {
                 let buffer = data.main_split.open_files.get(path).unwrap().clone();
-                Some(buffer.offset_to_position(self.cursor.offset(), tab_width))
+                Some(buffer.offset_to_position(self.cursor.offset()))
             } else {
                 None
             },
@@ -3157,3 +6105,818 @@ fn str_matching_pair(c: &str) -> Option<char> {
 
 #[allow(dead_code)]
 fn progress_term_event() {}
+
+#[cfg(test)]
+impl LapceMainSplitData {
+    /// A main split whose proxy never connects, with the file at `path` open
+    /// but not loaded yet, and the config it uses.
+    pub fn with_file(path: &Path) -> (Self, Config) {
+        let event_sink = druid::AppLauncher::<()>::new().get_external_handle();
+        let tab_id = WidgetId::next();
+        let config = Config::default_settings();
+        let proxy =
+            Arc::new(LapceProxy::new_disconnected(tab_id, event_sink.clone()));
+        let mut main_split = Self::new(
+            tab_id,
+            None,
+            WidgetId::next(),
+            proxy,
+            &config,
+            event_sink.clone(),
+            Arc::new(LapceWorkspace::default()),
+            Arc::new(LapceDb::new_unopened()),
+        );
+        let buffer =
+            Buffer::new(BufferContent::File(path.to_path_buf()), tab_id, event_sink);
+        main_split
+            .open_files
+            .insert(path.to_path_buf(), Arc::new(buffer));
+        (main_split, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::LanguageOverride;
+
+    #[test]
+    fn test_load_buffer() {
+        let path = Path::new("/ws/open.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+
+        // The file was closed while it was loading.
+        let closed = Path::new("/ws/closed.rs");
+        assert!(!main_split.load_buffer(
+            closed,
+            "fn main() {}",
+            FileEncoding::default()
+        ));
+        assert!(!main_split.open_files.contains_key(closed));
+
+        assert!(main_split.load_buffer(
+            path,
+            "fn main() {}",
+            FileEncoding::default()
+        ));
+        let buffer = &main_split.open_files[path];
+        assert!(buffer.loaded());
+        assert_eq!("fn main() {}", buffer.rope().to_string());
+    }
+
+    #[test]
+    fn test_lsp_positions_with_tabs() {
+        let path = Path::new("/ws/tabs.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+        main_split.load_buffer(
+            path,
+            "fn main() {\n\tlet a = \"é\";\n}\n",
+            FileEncoding::default(),
+        );
+        let buffer = &main_split.open_files[path];
+
+        // A tab is one code unit to a language server, not tab_width columns.
+        assert_eq!(Position::new(1, 1), buffer.offset_to_position(13));
+        assert_eq!(13, buffer.offset_of_position(&Position::new(1, 1)));
+        // "é" is two bytes but a single UTF-16 code unit.
+        assert_eq!(Position::new(1, 11), buffer.offset_to_position(24));
+        assert_eq!(24, buffer.offset_of_position(&Position::new(1, 11)));
+    }
+
+    #[test]
+    fn test_save_formats_by_language() {
+        let timeout = std::time::Duration::from_millis(100);
+        let event_sink = druid::AppLauncher::<()>::new().get_external_handle();
+        let path = Path::new("/ws/main.rs");
+        let (main_split, mut config) = LapceMainSplitData::with_file(path);
+
+        config.editor.format_on_save = true;
+        main_split.request_save(event_sink.clone(), path, &config);
+        assert_eq!(
+            vec!["get_document_formatting".to_string()],
+            main_split.proxy.sent_methods(timeout)
+        );
+
+        config
+            .editor
+            .format_on_save_overrides
+            .insert("rust".to_string(), false);
+        main_split.request_save(event_sink, path, &config);
+        assert_eq!(
+            vec!["save".to_string()],
+            main_split.proxy.sent_methods(timeout)
+        );
+    }
+
+    #[test]
+    fn test_formats_on_type() {
+        let path = Path::new("/ws/main.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+        let buffer = main_split.open_files[path].clone();
+        assert!(!main_split.formats_on_type(&buffer, "}"));
+
+        main_split.on_type_formatting_triggers.insert(
+            "rust".to_string(),
+            Arc::new(vec!["}".to_string(), ";".to_string()]),
+        );
+        assert!(main_split.formats_on_type(&buffer, "}"));
+        assert!(main_split.formats_on_type(&buffer, ";"));
+        assert!(!main_split.formats_on_type(&buffer, "a"));
+
+        // The triggers of another language don't apply.
+        let mut buffer = (*buffer).clone();
+        buffer.set_language(Some(LanguageOverride::PlainText));
+        assert!(!main_split.formats_on_type(&buffer, "}"));
+
+        // The edits for a file closed in the meantime are dropped.
+        let closed = Path::new("/ws/closed.rs");
+        main_split.document_on_type_format(closed, 0, &serde_json::json!([]));
+        assert!(!main_split.open_files.contains_key(closed));
+    }
+
+    #[test]
+    fn test_triggers_completion() {
+        let path = Path::new("/ws/main.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+        let buffer = main_split.open_files[path].clone();
+        assert!(!main_split.triggers_completion(&buffer, "."));
+
+        main_split.completion_triggers.insert(
+            "rust".to_string(),
+            Arc::new(vec![".".to_string(), "<".to_string()]),
+        );
+        assert!(main_split.triggers_completion(&buffer, "."));
+        assert!(main_split.triggers_completion(&buffer, "<"));
+        assert!(!main_split.triggers_completion(&buffer, ":"));
+
+        let mut buffer = (*buffer).clone();
+        buffer.set_language(Some(LanguageOverride::PlainText));
+        assert!(!main_split.triggers_completion(&buffer, "."));
+    }
+
+    #[test]
+    fn test_escape_closes_overlays_in_order() {
+        let mut open = vec![
+            Overlay::FindBar,
+            Overlay::Completion,
+            Overlay::Hover,
+            Overlay::CodeAction,
+            Overlay::Signature,
+        ];
+        let mut closed = Vec::new();
+        // Each Escape closes the topmost overlay still open.
+        while let Some(overlay) = Overlay::topmost(|o| open.contains(&o)) {
+            open.retain(|o| *o != overlay);
+            closed.push(overlay);
+        }
+        assert_eq!(
+            vec![
+                Overlay::Hover,
+                Overlay::Signature,
+                Overlay::Completion,
+                Overlay::CodeAction,
+                Overlay::FindBar,
+            ],
+            closed
+        );
+
+        assert_eq!(
+            Some(Overlay::Peek),
+            Overlay::topmost(|o| o == Overlay::Peek || o == Overlay::FindBar)
+        );
+        assert_eq!(None, Overlay::topmost(|_| false));
+    }
+
+    #[test]
+    fn test_format_outcome_edits() {
+        let edit = TextEdit::new(
+            lsp_types::Range::new(Position::new(0, 0), Position::new(0, 2)),
+            "  ".to_string(),
+        );
+        let result = Ok(serde_json::json!([edit]));
+        assert_eq!(FormatOutcome::Edits(vec![edit]), FormatOutcome::of(&result));
+    }
+
+    #[test]
+    fn test_format_closed_file() {
+        let path = Path::new("/ws/open.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+        main_split.load_buffer(path, "a", FileEncoding::default());
+        let edit = TextEdit::new(
+            lsp_types::Range::new(Position::new(0, 0), Position::new(0, 1)),
+            "b".to_string(),
+        );
+        let result = Ok(serde_json::json!([edit]));
+
+        // The file was closed while the language server was formatting it.
+        let closed = Path::new("/ws/closed.rs");
+        assert!(main_split.document_format(closed, 0, &result).is_ok());
+        assert!(!main_split.open_files.contains_key(closed));
+
+        let rev = main_split.open_files[path].rev();
+        assert!(main_split.document_format(path, rev, &result).is_ok());
+        assert_eq!("b", main_split.open_files[path].rope().to_string());
+    }
+
+    #[test]
+    fn test_formatting_options() {
+        let path = Path::new("/ws/main.rs");
+        let (mut main_split, config) = LapceMainSplitData::with_file(path);
+        main_split.load_buffer(path, "", FileEncoding::default());
+        let buffer = Arc::make_mut(main_split.open_files.get_mut(path).unwrap());
+
+        buffer.set_indent_style(IndentStyle::Spaces(2));
+        let options = formatting_options(buffer, &config);
+        assert_eq!((2, true), (options.tab_size, options.insert_spaces));
+
+        buffer.set_indent_style(IndentStyle::Tabs);
+        let options = formatting_options(buffer, &config);
+        assert_eq!(
+            (config.editor.tab_width as u32, false),
+            (options.tab_size, options.insert_spaces)
+        );
+    }
+
+    #[test]
+    fn test_format_outcome_no_changes() {
+        // Servers answer with either no edits or null when nothing changes.
+        let result = Ok(serde_json::json!([]));
+        assert_eq!(FormatOutcome::NoChanges, FormatOutcome::of(&result));
+        assert_eq!(
+            FormatOutcome::NoChanges,
+            FormatOutcome::of(&Ok(Value::Null))
+        );
+    }
+
+    #[test]
+    fn test_format_outcome_failed() {
+        let result = Err(anyhow!("the language server took too long"));
+        assert_eq!(
+            FormatOutcome::Failed("the language server took too long".to_string()),
+            FormatOutcome::of(&result)
+        );
+        let result = Ok(serde_json::json!({ "unexpected": true }));
+        assert!(matches!(
+            FormatOutcome::of(&result),
+            FormatOutcome::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_edits_within() {
+        let edit = |start, end| {
+            TextEdit::new(
+                Range::new(Position::new(start, 0), Position::new(end, 0)),
+                String::new(),
+            )
+        };
+        let range = Range::new(Position::new(2, 0), Position::new(5, 0));
+        let edits = vec![edit(0, 1), edit(2, 3), edit(4, 5), edit(4, 6)];
+        assert_eq!(vec![edit(2, 3), edit(4, 5)], edits_within(edits, &range));
+    }
+
+    #[test]
+    fn test_workspace_edit_steps_multiple_files() {
+        let edit = |line| {
+            TextEdit::new(
+                Range::new(Position::new(line, 0), Position::new(line, 3)),
+                "bar".to_string(),
+            )
+        };
+        let url = |path| Url::from_file_path(path).unwrap();
+
+        let mut changes = HashMap::new();
+        changes.insert(url("/ws/b.rs"), vec![edit(2)]);
+        changes.insert(url("/ws/a.rs"), vec![edit(0), edit(1)]);
+        let workspace_edit = WorkspaceEdit::new(changes);
+        assert_eq!(
+            vec![
+                WorkspaceEditStep::Edit(
+                    PathBuf::from("/ws/a.rs"),
+                    None,
+                    vec![edit(0), edit(1)]
+                ),
+                WorkspaceEditStep::Edit(
+                    PathBuf::from("/ws/b.rs"),
+                    None,
+                    vec![edit(2)]
+                ),
+            ],
+            workspace_edit_steps(&workspace_edit)
+        );
+
+        // Open files get the version the edit was asked for with.
+        let mut versions = HashMap::new();
+        versions.insert(PathBuf::from("/ws/a.rs"), 7);
+        assert_eq!(
+            vec![
+                WorkspaceEditStep::Edit(
+                    PathBuf::from("/ws/a.rs"),
+                    Some(7),
+                    vec![edit(0), edit(1)]
+                ),
+                WorkspaceEditStep::Edit(
+                    PathBuf::from("/ws/b.rs"),
+                    None,
+                    vec![edit(2)]
+                ),
+            ],
+            workspace_edit_steps(&with_versions(workspace_edit, &versions))
+        );
+    }
+
+    #[test]
+    fn test_workspace_edit_steps_create_file() {
+        let edit = TextEdit::new(
+            Range::new(Position::new(0, 0), Position::new(0, 0)),
+            "pub fn new() {}\n".to_string(),
+        );
+        let workspace_edit = serde_json::json!({
+            "documentChanges": [
+                {
+                    "kind": "create",
+                    "uri": "file:///ws/src/new.rs",
+                    "options": { "ignoreIfExists": true },
+                },
+                {
+                    "textDocument": {
+                        "uri": "file:///ws/src/new.rs",
+                        "version": null,
+                    },
+                    "edits": [edit],
+                },
+            ],
+        });
+        let workspace_edit: WorkspaceEdit =
+            serde_json::from_value(workspace_edit).unwrap();
+
+        // The file is created before it's edited.
+        assert_eq!(
+            vec![
+                WorkspaceEditStep::File(FileOperation::Create {
+                    path: PathBuf::from("/ws/src/new.rs"),
+                    overwrite: false,
+                    ignore_if_exists: true,
+                }),
+                WorkspaceEditStep::Edit(
+                    PathBuf::from("/ws/src/new.rs"),
+                    None,
+                    vec![edit]
+                ),
+            ],
+            workspace_edit_steps(&workspace_edit)
+        );
+    }
+
+    #[test]
+    fn test_workspace_edit_saves_only_when_asked() {
+        let timeout = std::time::Duration::from_millis(100);
+        let event_sink = druid::AppLauncher::<()>::new().get_external_handle();
+        let path = Path::new("/ws/main.rs");
+        let loaded = || {
+            let (mut main_split, _) = LapceMainSplitData::with_file(path);
+            main_split.load_buffer(path, "fn a() {}", FileEncoding::default());
+            main_split.proxy.sent_methods(timeout);
+            main_split
+        };
+        let edit = |text: &str| {
+            WorkspaceEditStep::Edit(
+                path.to_path_buf(),
+                None,
+                vec![TextEdit::new(
+                    Range::new(Position::new(0, 3), Position::new(0, 4)),
+                    text.to_string(),
+                )],
+            )
+        };
+
+        // Code actions leave the file unsaved.
+        let mut main_split = loaded();
+        main_split.apply_workspace_edit_steps(
+            event_sink.clone(),
+            vec![edit("b")],
+            WorkspaceEditSummary::default(),
+        );
+        assert_eq!("fn b() {}", main_split.open_files[path].rope().to_string());
+        assert!(!main_split
+            .proxy
+            .sent_methods(timeout)
+            .contains(&"save".to_string()));
+
+        // Renames save it.
+        let mut main_split = loaded();
+        main_split.apply_workspace_edit_steps(
+            event_sink.clone(),
+            vec![edit("c")],
+            WorkspaceEditSummary {
+                save: true,
+                ..Default::default()
+            },
+        );
+        assert!(main_split
+            .proxy
+            .sent_methods(timeout)
+            .contains(&"save".to_string()));
+
+        // Unless it had unsaved changes before, even when it's edited twice.
+        let mut main_split = loaded();
+        Arc::make_mut(main_split.open_files.get_mut(path).unwrap()).set_dirty(true);
+        let summary = main_split.apply_workspace_edit_steps(
+            event_sink,
+            vec![edit("d"), edit("e")],
+            WorkspaceEditSummary {
+                save: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!("fn e() {}", main_split.open_files[path].rope().to_string());
+        assert_eq!(Some(vec![path.to_path_buf()]), summary.map(|s| s.unsaved));
+        assert!(!main_split
+            .proxy
+            .sent_methods(timeout)
+            .contains(&"save".to_string()));
+    }
+
+    #[test]
+    fn test_rename_open_paths() {
+        let path = Path::new("/ws/src/main.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+
+        let paths = |main_split: &LapceMainSplitData| {
+            main_split
+                .open_files
+                .keys()
+                .map(|path| path.to_str().unwrap().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        // Without a trailing separator
+        main_split.rename_open_paths(path, Path::new("/ws/src/lib.rs"));
+        assert_eq!(vec!["/ws/src/lib.rs"], paths(&main_split));
+
+        main_split.rename_open_paths(Path::new("/ws/src"), Path::new("/ws/core"));
+        assert_eq!(vec!["/ws/core/lib.rs"], paths(&main_split));
+    }
+
+    #[test]
+    fn test_mark_deleted() {
+        let path = Path::new("/ws/src/main.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+        main_split.load_buffer(path, "", FileEncoding::default());
+        main_split
+            .pending_edits
+            .insert(PathBuf::from("/ws/src/lib.rs"), Arc::new(Vec::new()));
+
+        main_split.mark_deleted(Path::new("/ws/src"));
+        assert!(main_split.open_files[path].dirty());
+        assert!(main_split.pending_edits.is_empty());
+    }
+
+    #[test]
+    fn test_convert_indentation() {
+        let path = Path::new("/ws/main.rs");
+        let (mut main_split, mut config) = LapceMainSplitData::with_file(path);
+        config.editor.tab_width = 4;
+        main_split.load_buffer(path, "a\n  b\n    c\n", FileEncoding::default());
+        Arc::make_mut(main_split.open_files.get_mut(path).unwrap())
+            .set_indent_style(IndentStyle::Spaces(2));
+        let text = |main_split: &LapceMainSplitData| {
+            let buffer = &main_split.open_files[path];
+            (buffer.rope().to_string(), buffer.indent_style())
+        };
+
+        // Each level becomes a tab, shown as wide as the level was.
+        main_split.convert_file_indentation(path, true, &config);
+        assert_eq!(
+            ("a\n\tb\n\t\tc\n".to_string(), IndentStyle::Tabs),
+            text(&main_split)
+        );
+        assert_eq!(2, main_split.open_files[path].tab_width(&config));
+
+        main_split.convert_file_indentation(path, false, &config);
+        assert_eq!(
+            ("a\n  b\n    c\n".to_string(), IndentStyle::Spaces(2)),
+            text(&main_split)
+        );
+    }
+
+    #[test]
+    fn test_resolved_code_action() {
+        let unresolved: CodeAction = serde_json::from_value(serde_json::json!({
+            "title": "Import `HashMap`",
+            "kind": "quickfix",
+            "data": { "id": 3 },
+        }))
+        .unwrap();
+        assert!(unresolved.edit.is_none());
+
+        let edit = TextEdit::new(
+            Range::new(Position::new(0, 0), Position::new(0, 0)),
+            "use std::collections::HashMap;\n".to_string(),
+        );
+        let resolved = Ok(serde_json::json!({
+            "title": "Import `HashMap`",
+            "kind": "quickfix",
+            "edit": {
+                "changes": { "file:///ws/src/main.rs": [edit] },
+            },
+            "command": { "title": "Reload", "command": "reload" },
+        }));
+        let resolved = resolved_code_action(&resolved).unwrap();
+        assert_eq!(
+            Some("reload"),
+            resolved.command.as_ref().map(|c| c.command.as_str())
+        );
+        let resolved = resolved.edit.unwrap();
+        assert_eq!(
+            vec![WorkspaceEditStep::Edit(
+                PathBuf::from("/ws/src/main.rs"),
+                None,
+                vec![edit]
+            )],
+            workspace_edit_steps(&resolved)
+        );
+
+        let failed = Err(anyhow!("the action is gone"));
+        assert!(resolved_code_action(&failed).is_err());
+    }
+
+    #[test]
+    fn test_diagnostic_offsets_with_tabs() {
+        let path = Path::new("/ws/main.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+        let diagnostic = EditorDiagnostic {
+            range: None,
+            diagnositc: Diagnostic::new_simple(
+                Range::new(Position::new(1, 5), Position::new(1, 6)),
+                "unused variable".to_string(),
+            ),
+        };
+        main_split.set_diagnostics(path, vec![diagnostic]);
+        assert_eq!(None, main_split.diagnostics[path][0].range);
+
+        // Placed once the file loads, counting the tab as a single character.
+        main_split.load_buffer(path, "{\n\tlet a = 1;\n}", FileEncoding::default());
+        assert_eq!(Some((7, 8)), main_split.diagnostics[path][0].range);
+    }
+
+    #[test]
+    fn test_diagnostics_drop_code_actions() {
+        let path = Path::new("/ws/main.rs");
+        let (mut main_split, _) = LapceMainSplitData::with_file(path);
+        main_split.load_buffer(path, "let a = 1;", FileEncoding::default());
+        let buffer = Arc::make_mut(main_split.open_files.get_mut(path).unwrap());
+        let action = CodeActionOrCommand::CodeAction(CodeAction {
+            title: "Remove `a`".to_string(),
+            ..CodeAction::default()
+        });
+        buffer.set_code_actions(buffer.rev(), 4, vec![action]);
+        assert!(buffer.code_actions(4).is_some());
+
+        let diagnostic = EditorDiagnostic {
+            range: None,
+            diagnositc: Diagnostic::new_simple(
+                Range::new(Position::new(0, 4), Position::new(0, 5)),
+                "unused variable".to_string(),
+            ),
+        };
+        main_split.set_diagnostics(path, vec![diagnostic]);
+        assert!(main_split.open_files[path].code_actions(4).is_none());
+        assert_eq!(Some((4, 5)), main_split.diagnostics[path][0].range);
+    }
+
+    #[test]
+    fn test_next_diagnostic() {
+        let diagnostic = |start, severity, message: &str| EditorDiagnostic {
+            range: Some((start, start + 1)),
+            diagnositc: Diagnostic {
+                severity,
+                ..Diagnostic::new_simple(
+                    lsp_types::Range::default(),
+                    message.to_string(),
+                )
+            },
+        };
+        let diagnostics = vec![
+            diagnostic(20, Some(DiagnosticSeverity::Warning), "c"),
+            diagnostic(5, Some(DiagnosticSeverity::Warning), "a"),
+            diagnostic(10, Some(DiagnosticSeverity::Error), "b"),
+        ];
+        let next = |offset, forward, prefer_errors| {
+            next_diagnostic(&diagnostics, offset, forward, prefer_errors)
+                .map(|d| d.diagnositc.message.as_str())
+        };
+
+        assert_eq!(Some("b"), next(5, true, false));
+        assert_eq!(Some("a"), next(10, false, false));
+        // Wrapping around the ends of the file.
+        assert_eq!(Some("a"), next(20, true, false));
+        assert_eq!(Some("c"), next(5, false, false));
+
+        assert_eq!(Some("b"), next(15, true, true));
+        assert_eq!(Some("b"), next(0, false, true));
+
+        assert_eq!(None, next_diagnostic(&[], 0, true, false));
+    }
+
+    #[test]
+    fn test_next_problem() {
+        let diagnostic = |line, severity| EditorDiagnostic {
+            range: None,
+            diagnositc: Diagnostic {
+                severity,
+                ..Diagnostic::new_simple(
+                    lsp_types::Range::new(
+                        Position::new(line, 0),
+                        Position::new(line, 1),
+                    ),
+                    String::new(),
+                )
+            },
+        };
+        let mut diagnostics = im::HashMap::new();
+        diagnostics.insert(
+            PathBuf::from("/ws/b.rs"),
+            Arc::new(vec![
+                diagnostic(3, Some(DiagnosticSeverity::Error)),
+                diagnostic(1, Some(DiagnosticSeverity::Warning)),
+            ]),
+        );
+        diagnostics.insert(
+            PathBuf::from("/ws/a.rs"),
+            Arc::new(vec![diagnostic(7, Some(DiagnosticSeverity::Error))]),
+        );
+        diagnostics.insert(
+            PathBuf::from("/ws/deleted.rs"),
+            Arc::new(vec![diagnostic(0, Some(DiagnosticSeverity::Error))]),
+        );
+        let exists = |path: &Path| !path.ends_with("deleted.rs");
+        let location =
+            |path: &str, line| (PathBuf::from(path), Position::new(line, 0));
+
+        assert_eq!(
+            Some(location("/ws/a.rs", 7)),
+            next_problem(&diagnostics, None, true, exists)
+        );
+        let from = location("/ws/a.rs", 7);
+        assert_eq!(
+            Some(location("/ws/b.rs", 3)),
+            next_problem(&diagnostics, Some(&from), true, exists)
+        );
+        assert_eq!(
+            Some(location("/ws/b.rs", 1)),
+            next_problem(&diagnostics, Some(&from), false, exists)
+        );
+        // Wrapping around after the last one.
+        let from = location("/ws/b.rs", 3);
+        assert_eq!(
+            Some(location("/ws/a.rs", 7)),
+            next_problem(&diagnostics, Some(&from), true, exists)
+        );
+        assert_eq!(None, next_problem(&im::HashMap::new(), None, true, exists));
+    }
+
+    #[test]
+    fn test_panel_order() {
+        use PanelKind::*;
+
+        let mut panels = vec![FileExplorer, SourceControl, Plugin];
+        sort_panels(&mut panels, &[Plugin, FileExplorer]);
+        assert_eq!(vec![Plugin, FileExplorer, SourceControl], panels);
+
+        assert!(!move_panel(&mut panels, Plugin, 1));
+        assert!(move_panel(&mut panels, Plugin, 2));
+        assert_eq!(vec![FileExplorer, Plugin, SourceControl], panels);
+        assert!(move_panel(&mut panels, SourceControl, 0));
+        assert_eq!(vec![SourceControl, FileExplorer, Plugin], panels);
+        assert!(!move_panel(&mut panels, Terminal, 0));
+    }
+
+    #[test]
+    fn test_popup_origin() {
+        let tab_size = Size::new(800.0, 600.0);
+        let size = Size::new(400.0, 300.0);
+
+        let origin = popup_origin(Point::new(100.0, 100.0), 20.0, size, tab_size);
+        assert_eq!(Point::new(100.0, 120.0), origin);
+
+        // Near the bottom it flips above the line.
+        let origin = popup_origin(Point::new(100.0, 500.0), 20.0, size, tab_size);
+        assert_eq!(Point::new(100.0, 200.0), origin);
+
+        // Near the right edge it moves left.
+        let origin = popup_origin(Point::new(700.0, 100.0), 20.0, size, tab_size);
+        assert_eq!(Point::new(399.0, 120.0), origin);
+
+        // With more room below, it stays below even when cut off.
+        let origin = popup_origin(Point::new(-30.0, 250.0), 20.0, size, tab_size);
+        assert_eq!(Point::new(0.0, 270.0), origin);
+    }
+
+    #[test]
+    fn test_diagnostics_at() {
+        let diagnostic = |range, severity, message: &str| EditorDiagnostic {
+            range,
+            diagnositc: Diagnostic {
+                severity,
+                ..Diagnostic::new_simple(
+                    lsp_types::Range::default(),
+                    message.to_string(),
+                )
+            },
+        };
+        let diagnostics = vec![
+            diagnostic(Some((0, 10)), Some(DiagnosticSeverity::Warning), "clippy"),
+            diagnostic(Some((5, 8)), None, "unknown"),
+            diagnostic(Some((4, 6)), Some(DiagnosticSeverity::Error), "rustc"),
+            diagnostic(Some((8, 8)), Some(DiagnosticSeverity::Hint), "empty"),
+            diagnostic(None, Some(DiagnosticSeverity::Error), "unplaced"),
+        ];
+        let messages = |offset| {
+            diagnostics_at(&diagnostics, offset)
+                .iter()
+                .map(|d| d.diagnositc.message.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["rustc", "clippy", "unknown"], messages(5));
+        assert_eq!(vec!["clippy", "empty"], messages(8));
+        assert_eq!(vec!["clippy"], messages(9));
+        assert!(messages(10).is_empty());
+    }
+
+    #[test]
+    fn test_related_diagnostic_locations() {
+        let range = |line| {
+            lsp_types::Range::new(Position::new(line, 4), Position::new(line, 8))
+        };
+        let related = |path: &str, line, message: &str| {
+            lsp_types::DiagnosticRelatedInformation {
+                location: lsp_types::Location {
+                    uri: lsp_types::Url::from_file_path(path).unwrap(),
+                    range: range(line),
+                },
+                message: message.to_string(),
+            }
+        };
+        let mut diagnostic = EditorDiagnostic {
+            range: None,
+            diagnositc: Diagnostic::new_simple(
+                range(10),
+                "cannot borrow `v` as mutable".to_string(),
+            ),
+        };
+        assert!(diagnostic.related().is_empty());
+
+        diagnostic.diagnositc.related_information = Some(vec![
+            related("/ws/src/main.rs", 3, "first borrow here"),
+            related("/ws/src/lib.rs", 7, "defined here"),
+        ]);
+        let related = diagnostic
+            .related()
+            .into_iter()
+            .map(|(text, location)| (text, location.path, location.position))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    "main.rs[3, 4]: first borrow here".to_string(),
+                    PathBuf::from("/ws/src/main.rs"),
+                    Some(Position::new(3, 4)),
+                ),
+                (
+                    "lib.rs[7, 4]: defined here".to_string(),
+                    PathBuf::from("/ws/src/lib.rs"),
+                    Some(Position::new(7, 4)),
+                ),
+            ],
+            related
+        );
+    }
+
+    #[test]
+    fn test_remove_editor_closes_buffer() {
+        let timeout = std::time::Duration::from_millis(100);
+        let path = Path::new("/ws/main.rs");
+        let (mut main_split, config) = LapceMainSplitData::with_file(path);
+        let mut view_ids = Vec::new();
+        for _ in 0..2 {
+            let content = BufferContent::File(path.to_path_buf());
+            let editor = LapceEditorData::new(None, None, content, &config);
+            view_ids.push(editor.view_id);
+            main_split.editors.insert(editor.view_id, Arc::new(editor));
+        }
+
+        // Another editor still shows the file.
+        main_split.remove_editor(&view_ids[0]);
+        assert!(main_split.open_files.contains_key(path));
+        assert!(main_split.proxy.sent_methods(timeout).is_empty());
+
+        main_split.remove_editor(&view_ids[1]);
+        assert!(!main_split.open_files.contains_key(path));
+        assert_eq!(
+            vec!["close_buffer".to_string()],
+            main_split.proxy.sent_methods(timeout)
+        );
+    }
+}
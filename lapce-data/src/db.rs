@@ -4,12 +4,14 @@ use std::{
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Result};
-use crossbeam_channel::{unbounded, Sender};
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
 use directories::ProjectDirs;
 use druid::{ExtEventSink, Point, Rect, Size, Vec2, WidgetId};
+use lapce_rpc::file::SearchOptions;
 use lsp_types::Position;
 use serde::{Deserialize, Serialize};
 
@@ -18,17 +20,27 @@ use crate::{
     config::Config,
     data::{
         EditorTabChild, LapceData, LapceEditorData, LapceEditorTabData,
-        LapceMainSplitData, LapceTabData, LapceWindowData, SplitContent, SplitData,
+        LapceMainSplitData, LapceTabData, LapceWindowData, PanelData, PanelKind,
+        SplitContent, SplitData,
     },
     editor::EditorLocationNew,
+    panel::PanelPosition,
     split::SplitDirection,
     state::LapceWorkspace,
 };
 
+/// How long the completion history waits to be written after it changed, so
+/// that a run of accepted items is written once
+const COMPLETION_HISTORY_SAVE_DELAY: Duration = Duration::from_secs(5);
+
 pub enum SaveEvent {
     Workspace(LapceWorkspace, WorkspaceInfo),
     Tabs(TabsInfo),
     Buffer(BufferInfo),
+    CompletionHistory(LapceWorkspace, im::HashMap<String, usize>),
+    PanelOrder(Vec<PanelKind>),
+    SearchOptions(SearchOptions),
+    RecentSearchGlobs(Vec<String>),
 }
 
 #[derive(Clone)]
@@ -169,6 +181,25 @@ pub struct SplitInfo {
 }
 
 impl SplitInfo {
+    /// Moves the files opened under `from` to the same place under `to`
+    fn rebase(&mut self, from: &Path, to: &Path) {
+        for child in self.children.iter_mut() {
+            match child {
+                SplitContentInfo::EditorTab(editor_tab) => {
+                    for child in editor_tab.children.iter_mut() {
+                        let EditorTabChildInfo::Editor(editor) = child;
+                        if let BufferContent::File(path) = &mut editor.content {
+                            if let Ok(relative) = path.strip_prefix(from) {
+                                *path = to.join(relative);
+                            }
+                        }
+                    }
+                }
+                SplitContentInfo::Split(split) => split.rebase(from, to),
+            }
+        }
+    }
+
     pub fn to_data(
         &self,
         data: &mut LapceMainSplitData,
@@ -207,6 +238,56 @@ impl SplitInfo {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct WorkspaceInfo {
     pub split: SplitInfo,
+    #[serde(default)]
+    pub panels: Vec<PanelInfo>,
+}
+
+/// Which panel is active at a position and whether it's shown
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PanelInfo {
+    pub position: PanelPosition,
+    pub active: PanelKind,
+    pub shown: bool,
+}
+
+impl PanelInfo {
+    pub fn of(panels: &im::HashMap<PanelPosition, Arc<PanelData>>) -> Vec<Self> {
+        panels
+            .iter()
+            .map(|(position, panel)| PanelInfo {
+                position: position.clone(),
+                active: panel.active,
+                shown: panel.shown,
+            })
+            .collect()
+    }
+
+    /// Restores the state saved by `of` to the panels that are still at the
+    /// same position. A position without any panels is never shown.
+    pub fn restore(
+        infos: &[PanelInfo],
+        panels: &mut im::HashMap<PanelPosition, Arc<PanelData>>,
+    ) {
+        for info in infos {
+            if let Some(panel) = panels.get_mut(&info.position) {
+                let panel = Arc::make_mut(panel);
+                if panel.widgets.contains(&info.active) {
+                    panel.active = info.active;
+                }
+                panel.shown = info.shown && !panel.widgets.is_empty();
+            }
+        }
+    }
+}
+
+impl WorkspaceInfo {
+    /// The same layout for a workspace at `to`, with the files opened under
+    /// `from` moved along. Files elsewhere are kept as they are.
+    pub fn rebase(&self, from: &Path, to: &Path) -> WorkspaceInfo {
+        let mut info = self.clone();
+        info.split.rebase(from, to);
+        info
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -228,6 +309,9 @@ pub struct BufferInfo {
     pub path: PathBuf,
     pub scroll_offset: (f64, f64),
     pub cursor_offset: usize,
+    /// The first and last line of each folded region
+    #[serde(default)]
+    pub folds: Vec<(usize, usize)>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -310,8 +394,30 @@ impl LapceDb {
         let db = Self { save_tx, sled_db };
         let local_db = db.clone();
         std::thread::spawn(move || -> Result<()> {
+            let mut history: Option<(LapceWorkspace, im::HashMap<String, usize>)> =
+                None;
+            let mut history_deadline = None;
             loop {
-                let event = save_rx.recv()?;
+                let event = match history_deadline {
+                    Some(deadline) => save_rx.recv_deadline(deadline),
+                    None => {
+                        save_rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                    }
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => {
+                        if let Some((workspace, history)) = history.take() {
+                            let _ = local_db
+                                .insert_completion_history(&workspace, &history);
+                        }
+                        history_deadline = None;
+                        if e == RecvTimeoutError::Disconnected {
+                            return Ok(());
+                        }
+                        continue;
+                    }
+                };
                 match event {
                     SaveEvent::Workspace(workspace, info) => {
                         let _ = local_db.insert_workspace(&workspace, &info);
@@ -322,12 +428,47 @@ impl LapceDb {
                     SaveEvent::Buffer(info) => {
                         let _ = local_db.insert_buffer(&info);
                     }
+                    SaveEvent::CompletionHistory(workspace, new_history) => {
+                        // The history of another workspace can't wait, as it
+                        // would be replaced.
+                        if let Some((pending, pending_history)) = history.take() {
+                            if pending != workspace {
+                                let _ = local_db.insert_completion_history(
+                                    &pending,
+                                    &pending_history,
+                                );
+                            }
+                        }
+                        history = Some((workspace, new_history));
+                        history_deadline.get_or_insert_with(|| {
+                            Instant::now() + COMPLETION_HISTORY_SAVE_DELAY
+                        });
+                    }
+                    SaveEvent::PanelOrder(order) => {
+                        let _ = local_db.insert_panel_order(&order);
+                    }
+                    SaveEvent::SearchOptions(options) => {
+                        let _ = local_db.insert_search_options(&options);
+                    }
+                    SaveEvent::RecentSearchGlobs(globs) => {
+                        let _ = local_db.insert_recent_search_globs(&globs);
+                    }
                 }
             }
         });
         Ok(db)
     }
 
+    /// A database that doesn't store anything.
+    #[cfg(test)]
+    pub fn new_unopened() -> Self {
+        let (save_tx, _) = unbounded();
+        Self {
+            save_tx,
+            sled_db: None,
+        }
+    }
+
     fn get_db(&self) -> Result<&sled::Db> {
         self.sled_db
             .as_ref()
@@ -424,6 +565,116 @@ impl LapceDb {
         Ok(())
     }
 
+    /// How often each completion label was accepted in the workspace
+    pub fn get_completion_history(
+        &self,
+        workspace: &LapceWorkspace,
+    ) -> Result<im::HashMap<String, usize>> {
+        let key = format!("completion_history:{}", workspace);
+        let sled_db = self.get_db()?;
+        let history = sled_db
+            .get(key.as_str())?
+            .ok_or_else(|| anyhow!("can't find completion history"))?;
+        let history = std::str::from_utf8(&history)?;
+        let history = serde_json::from_str(history)?;
+        Ok(history)
+    }
+
+    pub fn save_completion_history_async(
+        &self,
+        workspace: &LapceWorkspace,
+        history: im::HashMap<String, usize>,
+    ) -> Result<()> {
+        self.save_tx
+            .send(SaveEvent::CompletionHistory(workspace.clone(), history))?;
+        Ok(())
+    }
+
+    fn insert_completion_history(
+        &self,
+        workspace: &LapceWorkspace,
+        history: &im::HashMap<String, usize>,
+    ) -> Result<()> {
+        let key = format!("completion_history:{}", workspace);
+        let history = serde_json::to_string(history)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert(key.as_str(), history.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    /// The order of the panels in the activity bar the user dragged them into
+    pub fn get_panel_order(&self) -> Result<Vec<PanelKind>> {
+        let sled_db = self.get_db()?;
+        let order = sled_db
+            .get("panel_order")?
+            .ok_or_else(|| anyhow!("can't find panel order"))?;
+        let order = std::str::from_utf8(&order)?;
+        let order = serde_json::from_str(order)?;
+        Ok(order)
+    }
+
+    pub fn save_panel_order_async(&self, order: Vec<PanelKind>) -> Result<()> {
+        self.save_tx.send(SaveEvent::PanelOrder(order))?;
+        Ok(())
+    }
+
+    fn insert_panel_order(&self, order: &[PanelKind]) -> Result<()> {
+        let order = serde_json::to_string(order)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert("panel_order", order.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    /// The regex and whole word toggles of the global search
+    pub fn get_search_options(&self) -> Result<SearchOptions> {
+        let sled_db = self.get_db()?;
+        let options = sled_db
+            .get("search_options")?
+            .ok_or_else(|| anyhow!("can't find search options"))?;
+        let options = std::str::from_utf8(&options)?;
+        let options = serde_json::from_str(options)?;
+        Ok(options)
+    }
+
+    pub fn save_search_options_async(&self, options: SearchOptions) -> Result<()> {
+        self.save_tx.send(SaveEvent::SearchOptions(options))?;
+        Ok(())
+    }
+
+    fn insert_search_options(&self, options: &SearchOptions) -> Result<()> {
+        let options = serde_json::to_string(options)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert("search_options", options.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    /// The globs the global search was recently limited to, latest first
+    pub fn get_recent_search_globs(&self) -> Result<Vec<String>> {
+        let sled_db = self.get_db()?;
+        let globs = sled_db
+            .get("recent_search_globs")?
+            .ok_or_else(|| anyhow!("can't find recent search globs"))?;
+        let globs = std::str::from_utf8(&globs)?;
+        let globs = serde_json::from_str(globs)?;
+        Ok(globs)
+    }
+
+    pub fn save_recent_search_globs_async(&self, globs: Vec<String>) -> Result<()> {
+        self.save_tx.send(SaveEvent::RecentSearchGlobs(globs))?;
+        Ok(())
+    }
+
+    fn insert_recent_search_globs(&self, globs: &[String]) -> Result<()> {
+        let globs = serde_json::to_string(globs)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert("recent_search_globs", globs.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
     fn insert_tabs(&self, info: &TabsInfo) -> Result<()> {
         let tabs_info = serde_json::to_string(info)?;
         let sled_db = self.get_db()?;
@@ -473,9 +724,35 @@ impl LapceDb {
         let workspace_info = data.workspace_info();
 
         self.insert_workspace(&workspace, &workspace_info)?;
+        // The history may still be waiting to be written with the others.
+        self.insert_completion_history(&workspace, &data.completion.history)?;
         Ok(())
     }
 
+    /// Saves the layout of `data` as the one of `workspace`, unless that
+    /// already has its own, so that opening the same path on another host
+    /// keeps the open editors.
+    pub fn carry_over_workspace(
+        &self,
+        data: &LapceTabData,
+        workspace: &LapceWorkspace,
+    ) -> Result<()> {
+        if self.get_workspace_info(workspace).is_ok() {
+            return Ok(());
+        }
+        let from = data
+            .workspace
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("no workspace path"))?;
+        let to = workspace
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("no workspace path"))?;
+        let info = data.workspace_info().rebase(from, to);
+        self.insert_workspace(workspace, &info)
+    }
+
     pub fn save_workspace_async(&self, data: &LapceTabData) -> Result<()> {
         let workspace = (*data.workspace).clone();
         let workspace_info = data.workspace_info();
@@ -492,6 +769,7 @@ impl LapceDb {
                 path: path.clone(),
                 scroll_offset: (buffer.scroll_offset.x, buffer.scroll_offset.y),
                 cursor_offset: buffer.cursor_offset,
+                folds: buffer.folded_lines(),
             };
             let _ = self.save_tx.send(SaveEvent::Buffer(info));
         }
@@ -529,3 +807,123 @@ impl LapceDb {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(path: &str) -> EditorTabChildInfo {
+        EditorTabChildInfo::Editor(EditorInfo {
+            content: BufferContent::File(PathBuf::from(path)),
+            scroll_offset: (0.0, 0.0),
+            position: None,
+        })
+    }
+
+    fn paths(split: &SplitInfo) -> Vec<PathBuf> {
+        let mut result = Vec::new();
+        for child in split.children.iter() {
+            match child {
+                SplitContentInfo::EditorTab(editor_tab) => {
+                    for child in editor_tab.children.iter() {
+                        let EditorTabChildInfo::Editor(editor) = child;
+                        if let BufferContent::File(path) = &editor.content {
+                            result.push(path.clone());
+                        }
+                    }
+                }
+                SplitContentInfo::Split(split) => result.extend(paths(split)),
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_rebase_workspace_info() {
+        let editor_tab = |children| {
+            SplitContentInfo::EditorTab(EditorTabInfo {
+                active: 0,
+                is_focus: false,
+                children,
+            })
+        };
+        let info = WorkspaceInfo {
+            split: SplitInfo {
+                children: vec![
+                    editor_tab(vec![editor("/home/a/lapce/src/main.rs")]),
+                    SplitContentInfo::Split(SplitInfo {
+                        children: vec![editor_tab(vec![
+                            editor("/home/a/lapce/Cargo.toml"),
+                            editor("/etc/hosts"),
+                        ])],
+                        direction: SplitDirection::Horizontal,
+                    }),
+                ],
+                direction: SplitDirection::Vertical,
+            },
+            panels: Vec::new(),
+        };
+
+        let info =
+            info.rebase(Path::new("/home/a/lapce"), Path::new("/srv/b/lapce"));
+        assert_eq!(
+            vec![
+                PathBuf::from("/srv/b/lapce/src/main.rs"),
+                PathBuf::from("/srv/b/lapce/Cargo.toml"),
+                PathBuf::from("/etc/hosts"),
+            ],
+            paths(&info.split)
+        );
+    }
+
+    #[test]
+    fn test_panel_info_round_trip() {
+        let panel = |active, widgets, shown| {
+            Arc::new(PanelData {
+                active,
+                widgets,
+                shown,
+                maximized: false,
+            })
+        };
+        let mut panels = im::HashMap::new();
+        panels.insert(
+            PanelPosition::LeftTop,
+            panel(
+                PanelKind::FileExplorer,
+                vec![PanelKind::FileExplorer, PanelKind::SourceControl],
+                true,
+            ),
+        );
+        panels.insert(
+            PanelPosition::BottomLeft,
+            panel(
+                PanelKind::Terminal,
+                vec![PanelKind::Terminal, PanelKind::Problem],
+                true,
+            ),
+        );
+        panels.insert(
+            PanelPosition::RightTop,
+            panel(PanelKind::Plugin, Vec::new(), false),
+        );
+
+        let mut saved = panels.clone();
+        Arc::make_mut(saved.get_mut(&PanelPosition::LeftTop).unwrap()).active =
+            PanelKind::SourceControl;
+        Arc::make_mut(saved.get_mut(&PanelPosition::BottomLeft).unwrap()).shown =
+            false;
+        Arc::make_mut(saved.get_mut(&PanelPosition::RightTop).unwrap()).shown = true;
+        let infos = PanelInfo::of(&saved);
+        let json = serde_json::to_string(&infos).unwrap();
+        let infos: Vec<PanelInfo> = serde_json::from_str(&json).unwrap();
+
+        PanelInfo::restore(&infos, &mut panels);
+        let left = &panels[&PanelPosition::LeftTop];
+        assert_eq!(PanelKind::SourceControl, left.active);
+        assert!(left.shown);
+        assert!(!panels[&PanelPosition::BottomLeft].shown);
+        // A position without panels stays hidden.
+        assert!(!panels[&PanelPosition::RightTop].shown);
+    }
+}
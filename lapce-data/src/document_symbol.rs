@@ -0,0 +1,151 @@
+use lsp_types::{DocumentSymbol, DocumentSymbolResponse, Position, Range};
+
+fn contains(outer: &Range, inner: &Range) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// Adds `symbol` as a child of the last symbol in `symbols` whose range
+/// contains it, going down as far as it can.
+fn insert_nested(symbols: &mut Vec<DocumentSymbol>, symbol: DocumentSymbol) {
+    if let Some(parent) = symbols
+        .last_mut()
+        .filter(|parent| contains(&parent.range, &symbol.range))
+    {
+        insert_nested(parent.children.get_or_insert_with(Vec::new), symbol);
+    } else {
+        symbols.push(symbol);
+    }
+}
+
+/// The symbols of a document as a tree. Servers answering with a flat list
+/// get theirs nested by which range contains which.
+pub fn nested_symbols(resp: DocumentSymbolResponse) -> Vec<DocumentSymbol> {
+    let symbols = match resp {
+        DocumentSymbolResponse::Nested(symbols) => return symbols,
+        DocumentSymbolResponse::Flat(symbols) => symbols,
+    };
+    #[allow(deprecated)]
+    let mut symbols: Vec<DocumentSymbol> = symbols
+        .into_iter()
+        .map(|s| DocumentSymbol {
+            name: s.name,
+            detail: None,
+            kind: s.kind,
+            tags: s.tags,
+            deprecated: s.deprecated,
+            range: s.location.range,
+            selection_range: s.location.range,
+            children: None,
+        })
+        .collect();
+    // Outer symbols come before the ones they contain.
+    symbols.sort_by(|a, b| {
+        a.range
+            .start
+            .cmp(&b.range.start)
+            .then(b.range.end.cmp(&a.range.end))
+    });
+    let mut nested = Vec::new();
+    for symbol in symbols {
+        insert_nested(&mut nested, symbol);
+    }
+    nested
+}
+
+/// The chain of symbols enclosing `position`, outermost first, each as the
+/// list of its siblings and its index in it.
+pub fn symbol_path(
+    symbols: &[DocumentSymbol],
+    position: Position,
+) -> Vec<(&[DocumentSymbol], usize)> {
+    let mut path = Vec::new();
+    let mut siblings = symbols;
+    while let Some(index) = siblings
+        .iter()
+        .position(|s| s.range.start <= position && position <= s.range.end)
+    {
+        path.push((siblings, index));
+        siblings = match siblings[index].children.as_ref() {
+            Some(children) => children,
+            None => break,
+        };
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::SymbolKind;
+
+    use super::*;
+
+    fn flat(name: &str, kind: u32, range: (u32, u32)) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "kind": kind,
+            "location": {
+                "uri": "file:///a.rs",
+                "range": {
+                    "start": { "line": range.0, "character": 0 },
+                    "end": { "line": range.1, "character": 1 },
+                },
+            },
+        })
+    }
+
+    fn names(path: &[(&[DocumentSymbol], usize)]) -> Vec<String> {
+        path.iter()
+            .map(|(siblings, i)| siblings[*i].name.clone())
+            .collect()
+    }
+
+    fn symbols() -> Vec<DocumentSymbol> {
+        // Out of order, the way some servers list them.
+        let resp: DocumentSymbolResponse =
+            serde_json::from_value(serde_json::json!([
+                flat("new", 6, (3, 5)),
+                flat("Foo", 23, (0, 1)),
+                flat("impl Foo", 19, (2, 10)),
+                flat("len", 6, (7, 9)),
+                flat("main", 12, (12, 14)),
+            ]))
+            .unwrap();
+        nested_symbols(resp)
+    }
+
+    #[test]
+    fn test_nested_symbols() {
+        let symbols = symbols();
+        assert_eq!(
+            vec!["Foo", "impl Foo", "main"],
+            symbols
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<&str>>()
+        );
+        let children = symbols[1].children.as_ref().unwrap();
+        assert_eq!(
+            vec!["new", "len"],
+            children
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(SymbolKind::Method, children[1].kind);
+        assert!(symbols[2].children.is_none());
+    }
+
+    #[test]
+    fn test_symbol_path() {
+        let symbols = symbols();
+        let path = symbol_path(&symbols, Position::new(8, 4));
+        assert_eq!(vec!["impl Foo", "len"], names(&path));
+        assert_eq!(1, path[1].1);
+        assert_eq!(2, path[1].0.len());
+
+        let path = symbol_path(&symbols, Position::new(6, 0));
+        assert_eq!(vec!["impl Foo"], names(&path));
+
+        assert!(symbol_path(&symbols, Position::new(11, 0)).is_empty());
+    }
+}
@@ -1,27 +1,35 @@
 use crate::buffer::matching_char;
 use crate::buffer::{
-    has_unmatched_pair, BufferContent, DiffLines, EditType, LocalBufferKind,
+    has_unmatched_pair, str_col, BufferContent, DiffLines, EditType, LocalBufferKind,
 };
 use crate::buffer::{matching_pair_direction, Buffer};
 use crate::command::CommandExecuted;
 use crate::command::CommandTarget;
 use crate::command::LapceCommandNew;
 use crate::command::LAPCE_NEW_COMMAND;
-use crate::completion::{CompletionData, CompletionStatus, Snippet};
-use crate::config::Config;
+use crate::completion::{
+    CompletionCommand, CompletionData, CompletionEdit, CompletionStatus, Snippet,
+    SnippetExpansion,
+};
+use crate::config::{CompletionInsertMode, Config};
 use crate::data::MotionMode;
 use crate::data::RegisterKind;
 use crate::data::{
-    EditorDiagnostic, InlineFindDirection, LapceEditorData, LapceMainSplitData,
-    RegisterData, SplitContent,
+    next_diagnostic, EditorDiagnostic, InlineFindDirection, LapceEditorData,
+    LapceMainSplitData, RegisterData, SplitContent,
 };
+use crate::document_symbol::nested_symbols;
 use crate::editor::commands::EditCommandFactory;
 use crate::editor::commands::EditCommandKind;
+use crate::hierarchy::HierarchyKind;
 use crate::hover::HoverData;
 use crate::hover::HoverStatus;
+use crate::hover::HoverTextStyle;
 use crate::movement::CursorMode;
 use crate::movement::InsertDrift;
+use crate::picker::split_picker_input;
 use crate::proxy::path_from_url;
+use crate::signature::{SignatureData, SignatureStatus};
 use crate::{
     command::{LapceCommand, LapceUICommand, LAPCE_UI_COMMAND},
     movement::{Movement, SelRegion, Selection},
@@ -33,7 +41,6 @@ use crate::{find::Find, split::SplitDirection};
 use crate::{keypress::KeyPressFocus, movement::Cursor};
 use crate::{proxy::LapceProxy, source_control::SourceControlData};
 use anyhow::{anyhow, Result};
-use crossbeam_channel::{self, bounded};
 use druid::piet::PietTextLayout;
 use druid::piet::Svg;
 use druid::Modifiers;
@@ -44,19 +51,18 @@ use druid::{
 use druid::{Application, ExtEventSink, MouseEvent};
 pub use lapce_core::syntax::Syntax;
 use lapce_rpc::buffer::BufferId;
-use lsp_types::CompletionTextEdit;
 use lsp_types::{
-    CodeActionResponse, CompletionItem, DiagnosticSeverity, GotoDefinitionResponse,
-    Location, Position,
+    CallHierarchyItem, CodeActionResponse, CompletionItem, DiagnosticSeverity,
+    DocumentHighlight, DocumentSymbolResponse, FoldingRange, GotoDefinitionResponse,
+    Location, Position, Range,
 };
 use serde_json::Value;
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::path::Path;
-use std::thread;
-use std::{collections::HashMap, sync::Arc};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::{iter::Iterator, path::PathBuf};
-use std::{str::FromStr, time::Duration};
 use xi_rope::{RopeDelta, Transformer};
 
 mod commands;
@@ -117,6 +123,18 @@ pub struct EditorLocationNew {
     pub history: Option<String>,
 }
 
+impl EditorLocationNew {
+    /// The start of the range of a location from the language server.
+    pub fn from_lsp(location: &Location) -> Self {
+        Self {
+            path: path_from_url(&location.uri),
+            position: Some(location.range.start),
+            scroll_offset: None,
+            history: None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct EditorLocation {
     pub path: String,
@@ -130,6 +148,7 @@ pub struct LapceEditorBufferData {
     pub buffer: Arc<Buffer>,
     pub completion: Arc<CompletionData>,
     pub hover: Arc<HoverData>,
+    pub signature: Arc<SignatureData>,
     pub main_split: LapceMainSplitData,
     pub source_control: Arc<SourceControlData>,
     pub find: Arc<Find>,
@@ -195,11 +214,9 @@ impl LapceEditorBufferData {
             let path = path.clone();
             let offset = self.editor.cursor.offset();
             let prev_offset = self.buffer.prev_code_boundary(offset);
-            if self.buffer.code_actions.get(&prev_offset).is_none() {
+            if self.buffer.code_actions(prev_offset).is_none() {
                 let buffer_id = self.buffer.id();
-                let position = self
-                    .buffer
-                    .offset_to_position(prev_offset, self.config.editor.tab_width);
+                let position = self.buffer.offset_to_position(prev_offset);
                 let rev = self.buffer.rev();
                 let event_sink = ctx.get_external_handle();
                 self.proxy.get_code_actions(
@@ -228,6 +245,318 @@ impl LapceEditorBufferData {
         }
     }
 
+    /// Asks the language server where the symbol under the cursor occurs,
+    /// unless the cursor is still on one of the occurrences highlighted.
+    pub fn update_document_highlights(&mut self, ctx: &mut EventCtx) {
+        if !self.buffer.loaded() || self.buffer.local() {
+            return;
+        }
+        let path = match self.buffer.content() {
+            BufferContent::File(path) => path.clone(),
+            _ => return,
+        };
+        let offset = self.editor.cursor.offset();
+        if self.buffer.document_highlights_at(offset).is_some() {
+            return;
+        }
+        if self.buffer.document_highlights.is_some() {
+            Arc::make_mut(&mut self.buffer).document_highlights = None;
+        }
+
+        let start_offset = self.buffer.prev_code_boundary(offset);
+        let end_offset = self.buffer.next_code_boundary(offset);
+        let input = self.buffer.slice_to_cow(start_offset..end_offset);
+        if input.trim().is_empty() {
+            return;
+        }
+
+        let position = self.buffer.offset_to_position(offset);
+        let tab_id = *self.main_split.tab_id;
+        let event_sink = ctx.get_external_handle();
+        self.proxy.get_document_highlights(
+            self.buffer.id(),
+            position,
+            Box::new(move |result| {
+                if let Ok(res) = result {
+                    if let Ok(Some(highlights)) =
+                        serde_json::from_value::<Option<Vec<DocumentHighlight>>>(res)
+                    {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateDocumentHighlights(
+                                path,
+                                offset,
+                                highlights.into_iter().map(|h| h.range).collect(),
+                            ),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Asks the language server for the ranges that can be folded, unless
+    /// the ones kept are up to date with the text.
+    pub fn update_folding_ranges(&self, ctx: &mut EventCtx) {
+        if !self.buffer.loaded() || self.buffer.local() {
+            return;
+        }
+        if self.buffer.current_folding_ranges().is_some() {
+            return;
+        }
+        let path = match self.buffer.content() {
+            BufferContent::File(path) => path.clone(),
+            _ => return,
+        };
+        let rev = self.buffer.rev();
+        let tab_id = *self.main_split.tab_id;
+        let event_sink = ctx.get_external_handle();
+        self.proxy.get_folding_ranges(
+            self.buffer.id(),
+            Box::new(move |result| {
+                if let Ok(res) = result {
+                    if let Ok(Some(ranges)) =
+                        serde_json::from_value::<Option<Vec<FoldingRange>>>(res)
+                    {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateFoldingRanges(path, rev, ranges),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Asks the language server for the symbols of the document, unless the
+    /// ones kept are up to date with the text.
+    pub fn update_document_symbols(&self, ctx: &mut EventCtx) {
+        if !self.buffer.loaded() || self.buffer.local() {
+            return;
+        }
+        if self.buffer.current_document_symbols().is_some() {
+            return;
+        }
+        let path = match self.buffer.content() {
+            BufferContent::File(path) => path.clone(),
+            _ => return,
+        };
+        let rev = self.buffer.rev();
+        let tab_id = *self.main_split.tab_id;
+        let event_sink = ctx.get_external_handle();
+        self.proxy.get_document_symbols(
+            self.buffer.id(),
+            Box::new(move |result| {
+                if let Ok(res) = result {
+                    if let Ok(resp) =
+                        serde_json::from_value::<DocumentSymbolResponse>(res)
+                    {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateDocumentSymbols(
+                                path,
+                                rev,
+                                nested_symbols(resp),
+                            ),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Goes to the definition of the symbol at the cursor, or shows it in
+    /// the peek below the cursor line. Going to it from the definition itself
+    /// lists its references instead, and several definitions are listed in
+    /// the palette to pick from.
+    fn go_to_definition(&self, ctx: &mut EventCtx, peek: bool) {
+        let offset = self.editor.cursor.offset();
+        let start_offset = self.buffer.prev_code_boundary(offset);
+        let start_position = self.buffer.offset_to_position(start_offset);
+        let event_sink = ctx.get_external_handle();
+        let buffer_id = self.buffer.id();
+        let position = self.buffer.offset_to_position(offset);
+        let proxy = self.proxy.clone();
+        let editor_view_id = self.editor.view_id;
+        self.proxy.get_definition(
+            offset,
+            buffer_id,
+            position,
+            Box::new(move |result| {
+                if let Ok(res) = result {
+                    if let Ok(resp) =
+                        serde_json::from_value::<GotoDefinitionResponse>(res)
+                    {
+                        let locations = definition_locations(resp);
+                        let location = match locations.first() {
+                            Some(location) => location,
+                            None => return,
+                        };
+                        if peek {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::PeekDefinition(
+                                    editor_view_id,
+                                    offset,
+                                    EditorLocationNew::from_lsp(location),
+                                ),
+                                Target::Auto,
+                            );
+                        } else if locations.len() == 1
+                            && location.range.start == start_position
+                        {
+                            proxy.get_references(
+                                buffer_id,
+                                position,
+                                Box::new(move |result| {
+                                    let _ = process_get_references(
+                                        editor_view_id,
+                                        offset,
+                                        result,
+                                        event_sink,
+                                    );
+                                }),
+                            );
+                        } else {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::GotoDefinition(
+                                    editor_view_id,
+                                    offset,
+                                    locations,
+                                ),
+                                Target::Auto,
+                            );
+                        }
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Goes to the type definition of the symbol at the cursor, or to its
+    /// implementations, listing them in the palette when there are several.
+    fn go_to_type_definition_or_implementation(
+        &self,
+        ctx: &mut EventCtx,
+        implementation: bool,
+    ) {
+        let offset = self.editor.cursor.offset();
+        let position = self.buffer.offset_to_position(offset);
+        let editor_view_id = self.editor.view_id;
+        let event_sink = ctx.get_external_handle();
+        let f = Box::new(move |result: Result<Value, Value>| {
+            let (name, not_found) = if implementation {
+                ("implementation", "No implementation found")
+            } else {
+                ("type definition", "No type definition found")
+            };
+            let command = match result {
+                Ok(res) => {
+                    match serde_json::from_value::<Option<GotoDefinitionResponse>>(
+                        res,
+                    ) {
+                        Ok(resp) => {
+                            let locations =
+                                resp.map(definition_locations).unwrap_or_default();
+                            if locations.is_empty() {
+                                LapceUICommand::ShowStatusMessage(
+                                    not_found.to_string(),
+                                )
+                            } else {
+                                LapceUICommand::GotoDefinition(
+                                    editor_view_id,
+                                    offset,
+                                    locations,
+                                )
+                            }
+                        }
+                        Err(e) => LapceUICommand::ShowStatusMessage(format!(
+                            "Can't go to the {}: {}",
+                            name, e
+                        )),
+                    }
+                }
+                Err(e) => LapceUICommand::ShowStatusMessage(format!(
+                    "Can't go to the {}: {}",
+                    name,
+                    e.get("message")
+                        .and_then(|message| message.as_str())
+                        .unwrap_or("unknown error")
+                )),
+            };
+            let _ =
+                event_sink.submit_command(LAPCE_UI_COMMAND, command, Target::Auto);
+        });
+        if implementation {
+            self.proxy.get_implementation(self.buffer.id(), position, f);
+        } else {
+            self.proxy
+                .get_type_definition(self.buffer.id(), position, f);
+        }
+    }
+
+    /// Asks the language server for the call or type hierarchy of the symbol
+    /// at the cursor, which is shown in its panel.
+    fn show_hierarchy(&self, ctx: &mut EventCtx, kind: HierarchyKind) {
+        if self.buffer.local()
+            || !matches!(self.buffer.content(), BufferContent::File(_))
+        {
+            return;
+        }
+        let buffer_id = self.buffer.id();
+        let position = self.buffer.offset_to_position(self.editor.cursor.offset());
+        let tab_id = *self.main_split.tab_id;
+        let event_sink = ctx.get_external_handle();
+        let f = Box::new(move |result: Result<Value, Value>| {
+            let result = result
+                .map_err(|e| {
+                    anyhow!(
+                        "{}",
+                        e.get("message")
+                            .and_then(|message| message.as_str())
+                            .unwrap_or("unknown error")
+                    )
+                })
+                .and_then(|res| {
+                    // Type hierarchy items have the same fields.
+                    serde_json::from_value::<Option<Vec<CallHierarchyItem>>>(res)
+                        .map_err(|e| anyhow!("{}", e))
+                })
+                .map(|items| items.unwrap_or_default());
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ShowHierarchy(buffer_id, kind, result),
+                Target::Widget(tab_id),
+            );
+        });
+        match kind {
+            HierarchyKind::IncomingCalls | HierarchyKind::OutgoingCalls => {
+                self.proxy.prepare_call_hierarchy(buffer_id, position, f);
+            }
+            HierarchyKind::Supertypes | HierarchyKind::Subtypes => {
+                self.proxy.prepare_type_hierarchy(buffer_id, position, f);
+            }
+        }
+    }
+
+    /// Moves the cursor out of the folded lines it's hidden in, onto the line
+    /// they're folded under.
+    fn reveal_cursor(&mut self, mods: Modifiers) {
+        let line = self.buffer.line_of_offset(self.editor.cursor.offset());
+        if self.buffer.is_line_hidden(line) {
+            let line = self.buffer.actual_line(self.buffer.visual_line(line));
+            let offset = self.buffer.line_end_offset(
+                line,
+                self.editor.cursor.get_mode() != Mode::Normal,
+            );
+            self.do_move(&Movement::Offset(offset), 1, mods);
+        }
+    }
+
     fn set_motion_mode(&mut self, mode: MotionMode) {
         if let Some(m) = &self.editor.motion_mode {
             if m == &mode {
@@ -315,10 +644,7 @@ impl LapceEditorBufferData {
     fn do_move(&mut self, movement: &Movement, count: usize, mods: Modifiers) {
         if movement.is_jump() && movement != &self.editor.last_movement {
             let editor = Arc::make_mut(&mut self.editor);
-            editor.save_jump_location(
-                self.buffer.data(),
-                self.config.editor.tab_width,
-            );
+            editor.save_jump_location(self.buffer.data());
         }
         let editor = Arc::make_mut(&mut self.editor);
         editor.last_movement = movement.clone();
@@ -425,136 +751,209 @@ impl LapceEditorBufferData {
         self.hover.status != HoverStatus::Inactive && !self.hover.is_empty()
     }
 
+    fn has_signature(&self) -> bool {
+        self.signature.status != SignatureStatus::Inactive
+            && !self.signature.is_empty()
+    }
+
+    /// Closes the completion list and returns its selected item. Nothing is
+    /// taken while the list is stale, as the selection was matched against
+    /// an earlier input than what's typed now.
+    fn take_completion_item(&mut self) -> Option<CompletionItem> {
+        if !self.has_completions() || self.completion.stale {
+            return None;
+        }
+        let item = self.completion.current_item().to_owned();
+        self.cancel_completion();
+        Some(item)
+    }
+
+    /// Applies an accepted completion item and records it in the history,
+    /// then runs the command attached to it. Without a command, when enabled
+    /// and the item ends in a trigger character, it requests completion again
+    /// so chained completions like `a.b.c` keep going. It doesn't reopen when
+    /// the item was a snippet that still has tabstops to fill.
+    pub fn accept_completion_item(
+        &mut self,
+        ctx: &mut EventCtx,
+        item: &CompletionItem,
+    ) -> Result<()> {
+        let snippet = self.editor.snippet.clone();
+        match self.apply_accepted_item(item)? {
+            Some(CompletionCommand::TriggerParameterHints) => {
+                self.request_signature(ctx, true);
+                return Ok(());
+            }
+            Some(CompletionCommand::TriggerSuggest) => {
+                self.update_completion(ctx);
+                return Ok(());
+            }
+            // Sent to the server already
+            Some(CompletionCommand::Execute(_)) => return Ok(()),
+            None => {}
+        }
+        if !self.config.editor.completion_reopen_after_accept
+            || self.editor.snippet != snippet
+        {
+            return Ok(());
+        }
+
+        let offset = self.editor.cursor.offset();
+        if offset == 0 {
+            return Ok(());
+        }
+        let char = self.buffer.slice_to_cow(offset - 1..offset).to_string();
+        if self.main_split.triggers_completion(&self.buffer, &char) {
+            self.update_completion(ctx);
+        }
+        Ok(())
+    }
+
+    /// Applies an accepted completion item and records it in the history,
+    /// sending the command attached to it to the server if it's one to
+    /// execute there. Returns the command, so that the client ones can be
+    /// run by the caller.
+    fn apply_accepted_item<'a>(
+        &mut self,
+        item: &'a CompletionItem,
+    ) -> Result<Option<CompletionCommand<'a>>> {
+        let completion = Arc::make_mut(&mut self.completion);
+        completion.record_acceptance(&item.label);
+        let _ = self.main_split.db.save_completion_history_async(
+            &self.main_split.workspace,
+            completion.history.clone(),
+        );
+
+        self.apply_completion_item(item)?;
+        let command = item.command.as_ref().map(CompletionCommand::of);
+        if let Some(CompletionCommand::Execute(command)) = &command {
+            self.proxy.execute_command(self.buffer.id(), command);
+        }
+        Ok(command)
+    }
+
     pub fn apply_completion_item(&mut self, item: &CompletionItem) -> Result<()> {
-        let additional_edit: Option<Vec<_>> =
-            item.additional_text_edits.as_ref().map(|edits| {
+        // The additional edits, such as an import, are positioned in the
+        // buffer from before the completion is inserted.
+        let additional_edits: Vec<(Selection, &str)> = item
+            .additional_text_edits
+            .as_ref()
+            .map(|edits| {
                 edits
                     .iter()
                     .map(|edit| {
                         let selection = Selection::region(
-                            self.buffer.offset_of_position(
-                                &edit.range.start,
-                                self.config.editor.tab_width,
-                            ),
-                            self.buffer.offset_of_position(
-                                &edit.range.end,
-                                self.config.editor.tab_width,
-                            ),
+                            self.buffer.offset_of_position(&edit.range.start),
+                            self.buffer.offset_of_position(&edit.range.end),
                         );
                         (selection, edit.new_text.as_str())
                     })
-                    .collect::<Vec<(Selection, &str)>>()
-            });
-        let additioal_edit: Option<Vec<_>> = additional_edit.as_ref().map(|edits| {
-            edits.iter().map(|(selection, c)| (selection, *c)).collect()
-        });
+                    .collect()
+            })
+            .unwrap_or_default();
 
-        let text_format = item
-            .insert_text_format
-            .unwrap_or(lsp_types::InsertTextFormat::PlainText);
-        if let Some(edit) = &item.text_edit {
-            match edit {
-                CompletionTextEdit::Edit(edit) => {
-                    let offset = self.editor.cursor.offset();
-                    let start_offset = self.buffer.prev_code_boundary(offset);
-                    let end_offset = self.buffer.next_code_boundary(offset);
-                    let edit_start = self.buffer.offset_of_position(
-                        &edit.range.start,
-                        self.config.editor.tab_width,
-                    );
-                    let edit_end = self.buffer.offset_of_position(
-                        &edit.range.end,
-                        self.config.editor.tab_width,
-                    );
-                    let selection = Selection::region(
-                        start_offset.min(edit_start),
-                        end_offset.max(edit_end),
-                    );
-                    match text_format {
-                        lsp_types::InsertTextFormat::PlainText => {
-                            let delta = self.edit(
-                                &[
-                                    &[(&selection, edit.new_text.as_str())][..],
-                                    &additioal_edit.unwrap_or_default()[..],
-                                ]
-                                .concat(),
-                                true,
-                                EditType::InsertChars,
-                            );
-                            let selection = selection.apply_delta(
-                                &delta,
-                                true,
-                                InsertDrift::Default,
-                            );
-                            self.set_cursor_after_change(selection);
-                            return Ok(());
-                        }
-                        lsp_types::InsertTextFormat::Snippet => {
-                            let snippet = Snippet::from_str(&edit.new_text)?;
-                            let text = snippet.text();
-                            let delta = self.edit(
-                                &[
-                                    &[(&selection, text.as_str())][..],
-                                    &additioal_edit.unwrap_or_default()[..],
-                                ]
-                                .concat(),
-                                true,
-                                EditType::InsertChars,
-                            );
-                            let selection = selection.apply_delta(
-                                &delta,
-                                true,
-                                InsertDrift::Default,
-                            );
+        let delta = self.insert_completion_text(item)?;
+        self.apply_additional_text_edits(&additional_edits, &delta);
+        Ok(())
+    }
 
-                            let mut transformer = Transformer::new(&delta);
-                            let offset = transformer
-                                .transform(start_offset.min(edit_start), false);
-                            let snippet_tabs = snippet.tabs(offset);
+    /// Applies the additional edits of a completion item once the completion
+    /// itself has been inserted with `delta`, moving them past it first.
+    fn apply_additional_text_edits(
+        &mut self,
+        edits: &[(Selection, &str)],
+        delta: &RopeDelta,
+    ) {
+        if edits.is_empty() {
+            return;
+        }
+        let edits: Vec<(Selection, &str)> = edits
+            .iter()
+            .map(|(selection, text)| {
+                (
+                    selection.apply_delta(delta, false, InsertDrift::Default),
+                    *text,
+                )
+            })
+            .collect();
+        self.edit_with_command(EditCommandKind::ApplyEdits { edits: &edits });
+    }
 
-                            if snippet_tabs.is_empty() {
-                                self.set_cursor_after_change(selection);
-                                return Ok(());
-                            }
+    /// Inserts the text of a completion item, returning the delta of the edit.
+    fn insert_completion_text(
+        &mut self,
+        item: &CompletionItem,
+    ) -> Result<RopeDelta> {
+        let insert_mode = self.config.editor.completion_insert_mode;
+        let edit = CompletionEdit::of(
+            item,
+            insert_mode,
+            self.config.editor.completion_snippets,
+        );
 
-                            let mut selection = Selection::new();
-                            let (_tab, (start, end)) = &snippet_tabs[0];
-                            let region = SelRegion::new(*start, *end, None);
-                            selection.add_region(region);
-                            self.set_cursor(Cursor::new(
-                                CursorMode::Insert(selection),
-                                None,
-                            ));
-                            Arc::make_mut(&mut self.editor)
-                                .add_snippet_placeholders(snippet_tabs);
-                            return Ok(());
-                        }
-                    }
+        let offset = self.editor.cursor.offset();
+        let start_offset = self.buffer.prev_code_boundary(offset);
+        let end_offset = match insert_mode {
+            CompletionInsertMode::Insert => offset,
+            CompletionInsertMode::Replace => self.buffer.next_code_boundary(offset),
+        };
+        let selection = match edit.range {
+            Some(range) => {
+                let edit_start = self.buffer.offset_of_position(&range.start);
+                let edit_end = self.buffer.offset_of_position(&range.end);
+                Selection::region(
+                    start_offset.min(edit_start),
+                    end_offset.max(edit_end),
+                )
+            }
+            None => Selection::region(start_offset, end_offset),
+        };
+
+        match edit.format {
+            lsp_types::InsertTextFormat::PlainText => {
+                let delta = self.edit(
+                    &[(&selection, edit.text)],
+                    true,
+                    EditType::InsertChars,
+                );
+                let selection =
+                    selection.apply_delta(&delta, true, InsertDrift::Default);
+                self.set_cursor_after_change(selection);
+                Ok(delta)
+            }
+            lsp_types::InsertTextFormat::Snippet => {
+                let snippet = Snippet::from_str(edit.text)?;
+                let text = snippet.text();
+                let delta = self.edit(
+                    &[(&selection, text.as_str())],
+                    true,
+                    EditType::InsertChars,
+                );
+
+                let mut transformer = Transformer::new(&delta);
+                let offset = transformer.transform(selection.min_offset(), false);
+                let selection =
+                    selection.apply_delta(&delta, true, InsertDrift::Default);
+                let snippet_tabs = snippet.tabs(offset);
+
+                if snippet_tabs.is_empty() {
+                    self.set_cursor_after_change(selection);
+                    return Ok(delta);
                 }
-                CompletionTextEdit::InsertAndReplace(_) => (),
+
+                let selection = match SnippetExpansion::new(snippet_tabs.clone()) {
+                    Some(expansion) => expansion.selection(),
+                    None => {
+                        let (_tab, (start, end)) = snippet_tabs[0];
+                        Selection::region(start, end)
+                    }
+                };
+                self.set_cursor(Cursor::new(CursorMode::Insert(selection), None));
+                Arc::make_mut(&mut self.editor)
+                    .add_snippet_placeholders(snippet_tabs);
+                Ok(delta)
             }
         }
-
-        let offset = self.editor.cursor.offset();
-        let start_offset = self.buffer.prev_code_boundary(offset);
-        let end_offset = self.buffer.next_code_boundary(offset);
-        let selection = Selection::region(start_offset, end_offset);
-
-        let delta = self.edit(
-            &[
-                &[(
-                    &selection,
-                    item.insert_text.as_deref().unwrap_or(item.label.as_str()),
-                )][..],
-                &additioal_edit.unwrap_or_default()[..],
-            ]
-            .concat(),
-            true,
-            EditType::InsertChars,
-        );
-        let selection = selection.apply_delta(&delta, true, InsertDrift::Default);
-        self.set_cursor_after_change(selection);
-        Ok(())
     }
 
     pub fn cancel_completion(&mut self) {
@@ -567,6 +966,21 @@ impl LapceEditorBufferData {
         hover.cancel();
     }
 
+    /// Ends the snippet expansion once the cursor leaves its tabstops, such
+    /// as by moving away or typing elsewhere.
+    fn end_snippet_outside_tabstops(&mut self) {
+        if let Some(snippet) = self.editor.snippet.as_ref() {
+            if !snippet.contains(self.editor.cursor.offset()) {
+                Arc::make_mut(&mut self.editor).snippet = None;
+            }
+        }
+    }
+
+    pub fn cancel_signature(&mut self) {
+        let signature = Arc::make_mut(&mut self.signature);
+        signature.cancel();
+    }
+
     fn update_completion(&mut self, ctx: &mut EventCtx) {
         if self.get_mode() != Mode::Insert {
             return;
@@ -591,8 +1005,9 @@ impl LapceEditorBufferData {
                 .slice_to_cow(start_offset - 1..start_offset)
                 .to_string()
         };
+        let triggered = self.main_split.triggers_completion(&self.buffer, &char);
         let completion = Arc::make_mut(&mut self.completion);
-        if input.is_empty() && char != "." && char != ":" {
+        if input.is_empty() && !triggered {
             completion.cancel();
             return;
         }
@@ -603,31 +1018,35 @@ impl LapceEditorBufferData {
         {
             completion.update_input(input.clone());
 
-            if !completion.input_items.contains_key("") {
+            let missing_all = !completion.input_items.contains_key("");
+            let missing_input = !completion.input_items.contains_key(&input);
+            if missing_all || missing_input {
+                // Whatever is still in flight is for an older input.
+                completion.cancel_request(self.proxy.clone(), completion.request_id);
+                completion.request_id += 1;
+            }
+
+            if missing_all {
                 let event_sink = ctx.get_external_handle();
                 completion.request(
                     self.proxy.clone(),
                     completion.request_id,
                     self.buffer.id(),
                     "".to_string(),
-                    self.buffer.offset_to_position(
-                        start_offset,
-                        self.config.editor.tab_width,
-                    ),
+                    self.buffer.offset_to_position(start_offset),
                     completion.id,
                     event_sink,
                 );
             }
 
-            if !completion.input_items.contains_key(&input) {
+            if missing_input {
                 let event_sink = ctx.get_external_handle();
                 completion.request(
                     self.proxy.clone(),
                     completion.request_id,
                     self.buffer.id(),
                     input,
-                    self.buffer
-                        .offset_to_position(offset, self.config.editor.tab_width),
+                    self.buffer.offset_to_position(offset),
                     completion.id,
                     event_sink,
                 );
@@ -636,11 +1055,13 @@ impl LapceEditorBufferData {
             return;
         }
 
+        completion.cancel_request(self.proxy.clone(), completion.request_id);
         completion.buffer_id = self.buffer.id();
         completion.offset = start_offset;
         completion.input = input.clone();
+        completion.grouped = self.config.editor.completion_group_by_kind;
         completion.status = CompletionStatus::Started;
-        completion.input_items.clear();
+        completion.clear_items();
         completion.request_id += 1;
         let event_sink = ctx.get_external_handle();
         completion.request(
@@ -648,8 +1069,7 @@ impl LapceEditorBufferData {
             completion.request_id,
             self.buffer.id(),
             "".to_string(),
-            self.buffer
-                .offset_to_position(start_offset, self.config.editor.tab_width),
+            self.buffer.offset_to_position(start_offset),
             completion.id,
             event_sink.clone(),
         );
@@ -659,8 +1079,7 @@ impl LapceEditorBufferData {
                 completion.request_id,
                 self.buffer.id(),
                 input,
-                self.buffer
-                    .offset_to_position(offset, self.config.editor.tab_width),
+                self.buffer.offset_to_position(offset),
                 completion.id,
                 event_sink,
             );
@@ -696,7 +1115,16 @@ impl LapceEditorBufferData {
         hover.buffer_id = self.buffer.id();
         hover.offset = start_offset;
         hover.status = HoverStatus::Started;
-        Arc::make_mut(&mut hover.items).clear();
+        let style = HoverTextStyle::from_config(&self.config);
+        if let BufferContent::File(path) = self.buffer.content() {
+            hover.set_diagnostics(
+                Some(path),
+                &self.main_split.diagnostics_at(path, offset),
+                &style,
+            );
+        } else {
+            hover.set_diagnostics(None, &[], &style);
+        }
         hover.request_id += 1;
 
         let event_sink = ctx.get_external_handle();
@@ -704,52 +1132,90 @@ impl LapceEditorBufferData {
             self.proxy.clone(),
             hover.request_id,
             self.buffer.id(),
-            self.buffer
-                .offset_to_position(start_offset, self.config.editor.tab_width),
+            self.buffer.offset_to_position(start_offset),
             hover.id,
             event_sink,
         );
     }
 
+    /// Asks the language server to format the code after typing `c`. New
+    /// lines don't go through here, so the edits never compete with the
+    /// indentation the editor gives a new line.
+    fn update_on_type_formatting(&mut self, ctx: &mut EventCtx, c: &str) {
+        if !self.config.editor.format_on_type
+            || self.buffer.local()
+            || !self.main_split.formats_on_type(&self.buffer, c)
+        {
+            return;
+        }
+        if let BufferContent::File(path) = self.buffer.content() {
+            let position =
+                self.buffer.offset_to_position(self.editor.cursor.offset());
+            self.main_split
+                .format_on_type(ctx, path, position, c, &self.config);
+        }
+    }
+
+    /// Requests the signature of the call the cursor is in when `c` opens a
+    /// call or moves to its next argument, and closes it when `c` ends it.
+    fn update_signature(&mut self, ctx: &mut EventCtx, c: &str) {
+        match c {
+            "(" | "," => {}
+            ")" => {
+                self.cancel_signature();
+                return;
+            }
+            _ => return,
+        }
+        self.request_signature(ctx, c == "(");
+    }
+
+    /// Requests the signature at the cursor, clearing the shown one first
+    /// if `clear` is set or it belongs to another buffer.
+    fn request_signature(&mut self, ctx: &mut EventCtx, clear: bool) {
+        if !self.buffer.loaded() {
+            return;
+        }
+        if self.buffer.local() {
+            return;
+        }
+
+        let offset = self.editor.cursor.offset();
+        let signature = Arc::make_mut(&mut self.signature);
+        if clear || signature.buffer_id != self.buffer.id() {
+            signature.signature = None;
+            signature.active_parameter = None;
+        }
+        signature.buffer_id = self.buffer.id();
+        signature.offset = offset;
+        signature.status = SignatureStatus::Started;
+        signature.request_id += 1;
+
+        let event_sink = ctx.get_external_handle();
+        signature.request(
+            self.proxy.clone(),
+            signature.request_id,
+            self.buffer.id(),
+            self.buffer.offset_to_position(offset),
+            signature.id,
+            event_sink,
+        );
+    }
+
+    /// Searches the workspace for `pattern`, with the options set in the
+    /// search panel, and remembers the globs it's limited to.
     pub fn update_global_search(&self, ctx: &mut EventCtx, pattern: String) {
         let tab_id = *self.main_split.tab_id;
         ctx.submit_command(Command::new(
             LAPCE_UI_COMMAND,
-            LapceUICommand::UpdateSearch(pattern.to_string()),
+            LapceUICommand::UpdateSearch(pattern),
+            Target::Widget(tab_id),
+        ));
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::RememberSearchGlobs,
             Target::Widget(tab_id),
         ));
-        if pattern.is_empty() {
-            ctx.submit_command(Command::new(
-                LAPCE_UI_COMMAND,
-                LapceUICommand::GlobalSearchResult(
-                    pattern,
-                    Arc::new(HashMap::new()),
-                ),
-                Target::Widget(tab_id),
-            ));
-        } else {
-            let event_sink = ctx.get_external_handle();
-            self.proxy.global_search(
-                pattern.clone(),
-                Box::new(move |result| {
-                    if let Ok(matches) = result {
-                        if let Ok(matches) = serde_json::from_value::<
-                            HashMap<PathBuf, Vec<(usize, (usize, usize), String)>>,
-                        >(matches)
-                        {
-                            let _ = event_sink.submit_command(
-                                LAPCE_UI_COMMAND,
-                                LapceUICommand::GlobalSearchResult(
-                                    pattern,
-                                    Arc::new(matches),
-                                ),
-                                Target::Widget(tab_id),
-                            );
-                        }
-                    }
-                }),
-            )
-        }
     }
 
     fn insert_new_line(&mut self, ctx: &mut EventCtx, selection: Selection) {
@@ -797,13 +1263,41 @@ impl LapceEditorBufferData {
                     }
                     return;
                 }
+                LocalBufferKind::SearchGlobs => {
+                    let pattern = self
+                        .main_split
+                        .local_buffers
+                        .get(&LocalBufferKind::Search)
+                        .map(|buffer| buffer.rope().to_string())
+                        .unwrap_or_default();
+                    self.update_global_search(ctx, pattern);
+                    return;
+                }
+                LocalBufferKind::PluginFilter => {
+                    return;
+                }
+                LocalBufferKind::Replace => {
+                    let pattern = self
+                        .main_split
+                        .local_buffers
+                        .get(&LocalBufferKind::Search)
+                        .map(|buffer| buffer.rope().to_string())
+                        .unwrap_or_default();
+                    let replacement = self.buffer.rope().to_string();
+                    if !pattern.is_empty() {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::GlobalReplace(pattern, replacement),
+                            Target::Widget(*self.main_split.tab_id),
+                        ));
+                    }
+                    return;
+                }
                 LocalBufferKind::FilePicker => {
-                    let pwd = self.buffer.rope().to_string();
-                    let pwd = PathBuf::from(pwd);
                     let tab_id = *self.main_split.tab_id;
                     ctx.submit_command(Command::new(
                         LAPCE_UI_COMMAND,
-                        LapceUICommand::UpdatePickerPwd(pwd),
+                        LapceUICommand::OpenPickerMatch,
                         Target::Widget(tab_id),
                     ));
                     return;
@@ -812,6 +1306,7 @@ impl LapceEditorBufferData {
             },
         }
 
+        let newline = self.buffer.line_ending().as_str();
         let mut edits = Vec::new();
         let mut extra_edits = Vec::new();
         let mut shift = 0i32;
@@ -837,7 +1332,7 @@ impl LapceEditorBufferData {
             };
 
             let selection = Selection::region(region.min(), region.max());
-            let content = format!("{}{}", "\n", indent);
+            let content = format!("{}{}", newline, indent);
 
             shift -= (region.max() - region.min()) as i32;
             shift += content.len() as i32;
@@ -853,7 +1348,8 @@ impl LapceEditorBufferData {
                                     let selection = Selection::caret(
                                         (region.max() as i32 + shift) as usize,
                                     );
-                                    let content = format!("{}{}", "\n", line_indent);
+                                    let content =
+                                        format!("{}{}", newline, line_indent);
                                     extra_edits.push((selection.clone(), content));
                                 }
                             }
@@ -912,7 +1408,7 @@ impl LapceEditorBufferData {
                     CursorMode::Insert { .. } | CursorMode::Visual { .. } => {
                         self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         )
                     }
                 };
@@ -971,7 +1467,7 @@ impl LapceEditorBufferData {
                     CursorMode::Visual { mode, .. } => {
                         let selection = self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         );
                         let data = match mode {
                             VisualMode::Linewise => data.content.clone(),
@@ -1047,20 +1543,10 @@ impl LapceEditorBufferData {
 
     fn initiate_diagnositcs_offset(&mut self) {
         let buffer = self.buffer.clone();
-        let tab_width = self.config.editor.tab_width;
         if let Some(diagnostics) = self.diagnostics_mut() {
             for diagnostic in diagnostics.iter_mut() {
                 if diagnostic.range.is_none() {
-                    diagnostic.range = Some((
-                        buffer.offset_of_position(
-                            &diagnostic.diagnositc.range.start,
-                            tab_width,
-                        ),
-                        buffer.offset_of_position(
-                            &diagnostic.diagnositc.range.end,
-                            tab_width,
-                        ),
-                    ));
+                    diagnostic.range = Some(diagnostic.offsets_in(&buffer));
                 }
             }
         }
@@ -1068,7 +1554,6 @@ impl LapceEditorBufferData {
 
     fn update_diagnositcs_offset(&mut self, delta: &RopeDelta) {
         let buffer = self.buffer.clone();
-        let tab_width = self.config.editor.tab_width;
         if let Some(diagnostics) = self.diagnostics_mut() {
             for diagnostic in diagnostics.iter_mut() {
                 let mut transformer = Transformer::new(delta);
@@ -1080,11 +1565,11 @@ impl LapceEditorBufferData {
                 diagnostic.range = Some((new_start, new_end));
                 if start != new_start {
                     diagnostic.diagnositc.range.start =
-                        buffer.offset_to_position(new_start, tab_width);
+                        buffer.offset_to_position(new_start);
                 }
                 if end != new_end {
                     diagnostic.diagnositc.range.end =
-                        buffer.offset_to_position(new_end, tab_width);
+                        buffer.offset_to_position(new_end);
                 }
             }
         }
@@ -1102,7 +1587,7 @@ impl LapceEditorBufferData {
                 let data = self
                     .editor
                     .cursor
-                    .yank(self.buffer.data(), self.config.editor.tab_width);
+                    .yank(self.buffer.data(), self.buffer.tab_width(&self.config));
                 let register = Arc::make_mut(&mut self.main_split.register);
                 register.add_delete(data);
             }
@@ -1115,22 +1600,8 @@ impl LapceEditorBufferData {
         let buffer = self.buffer_mut();
         let delta = buffer.editable(&proxy).edit_multiple(edits, edit_type);
         self.inactive_apply_delta(&delta);
-        if let Some(snippet) = self.editor.snippet.clone() {
-            let mut transformer = Transformer::new(&delta);
-            Arc::make_mut(&mut self.editor).snippet = Some(
-                snippet
-                    .iter()
-                    .map(|(tab, (start, end))| {
-                        (
-                            *tab,
-                            (
-                                transformer.transform(*start, false),
-                                transformer.transform(*end, true),
-                            ),
-                        )
-                    })
-                    .collect(),
-            );
+        if let Some(snippet) = Arc::make_mut(&mut self.editor).snippet.as_mut() {
+            snippet.apply_delta(&delta);
         }
 
         self.update_diagnositcs_offset(&delta);
@@ -1144,7 +1615,7 @@ impl LapceEditorBufferData {
     ) -> Option<RopeDelta> {
         let factory = EditCommandFactory {
             cursor: &mut Arc::make_mut(&mut self.editor).cursor,
-            tab_width: self.config.editor.tab_width,
+            tab_width: self.buffer.tab_width(&self.config),
             syntax: self.buffer.syntax().cloned(),
         };
 
@@ -1164,7 +1635,7 @@ impl LapceEditorBufferData {
                 let data = self
                     .editor
                     .cursor
-                    .yank(self.buffer.data(), self.config.editor.tab_width);
+                    .yank(self.buffer.data(), self.buffer.tab_width(&self.config));
                 let register = Arc::make_mut(&mut self.main_split.register);
                 register.add_delete(data);
             }
@@ -1175,22 +1646,8 @@ impl LapceEditorBufferData {
 
         if let Some(delta) = self.execute_edit_command(command) {
             self.inactive_apply_delta(&delta);
-            if let Some(snippet) = self.editor.snippet.clone() {
-                let mut transformer = Transformer::new(&delta);
-                Arc::make_mut(&mut self.editor).snippet = Some(
-                    snippet
-                        .iter()
-                        .map(|(tab, (start, end))| {
-                            (
-                                *tab,
-                                (
-                                    transformer.transform(*start, false),
-                                    transformer.transform(*end, true),
-                                ),
-                            )
-                        })
-                        .collect(),
-                );
+            if let Some(snippet) = Arc::make_mut(&mut self.editor).snippet.as_mut() {
+                snippet.apply_delta(&delta);
             }
 
             self.update_diagnositcs_offset(&delta);
@@ -1261,9 +1718,7 @@ impl LapceEditorBufferData {
             diff_files.sort();
 
             let offset = self.editor.cursor.offset();
-            let position = self
-                .buffer
-                .offset_to_position(offset, self.config.editor.tab_width);
+            let position = self.buffer.offset_to_position(offset);
             let (path, position) =
                 next_in_file_diff_offset(position, buffer_path, &diff_files);
             let location = EditorLocationNew {
@@ -1280,6 +1735,86 @@ impl LapceEditorBufferData {
         }
     }
 
+    fn has_whitespace_issue(&self, line: usize) -> bool {
+        (self.config.editor.highlight_trailing_whitespace
+            && self.buffer.trailing_whitespace(line).is_some())
+            || (self.config.editor.highlight_mixed_indent
+                && self.buffer.has_mixed_indent(line))
+    }
+
+    fn next_whitespace_issue(&mut self) {
+        let offset = self.editor.cursor.offset();
+        let current_line = self.buffer.line_of_offset(offset);
+        let num_lines = self.buffer.last_line() + 1;
+        let line = match (1..num_lines + 1)
+            .map(|i| (current_line + i) % num_lines)
+            .find(|line| self.has_whitespace_issue(*line))
+        {
+            Some(line) => line,
+            None => return,
+        };
+        let offset = match self.buffer.trailing_whitespace(line) {
+            Some(range) if self.config.editor.highlight_trailing_whitespace => {
+                range.start
+            }
+            _ => self.buffer.first_non_blank_character_on_line(line),
+        };
+        let mode = match self.editor.cursor.mode {
+            CursorMode::Insert(_) => CursorMode::Insert(Selection::caret(offset)),
+            _ => CursorMode::Normal(offset),
+        };
+        self.set_cursor(Cursor::new(mode, None));
+    }
+
+    fn fix_whitespace_issues(&mut self) {
+        let tab_width = self.buffer.tab_width(&self.config);
+        let indent_unit = self.buffer.indent_unit();
+        let mut edits = Vec::new();
+        for line in 0..self.buffer.last_line() + 1 {
+            let line_start = self.buffer.offset_of_line(line);
+            if self.config.editor.highlight_trailing_whitespace {
+                if let Some(range) = self.buffer.trailing_whitespace(line) {
+                    let whitespace_only = range.start == line_start;
+                    edits.push((
+                        Selection::region(range.start, range.end),
+                        String::new(),
+                    ));
+                    if whitespace_only {
+                        continue;
+                    }
+                }
+            }
+            if self.config.editor.highlight_mixed_indent
+                && self.buffer.has_mixed_indent(line)
+            {
+                let indent = self.buffer.indent_on_line(line);
+                let width = str_col(&indent, tab_width);
+                let fixed = if indent_unit == "\t" {
+                    format!(
+                        "{}{}",
+                        "\t".repeat(width / tab_width),
+                        " ".repeat(width % tab_width)
+                    )
+                } else {
+                    " ".repeat(width)
+                };
+                edits.push((
+                    Selection::region(line_start, line_start + indent.len()),
+                    fixed,
+                ));
+            }
+        }
+        if edits.is_empty() {
+            return;
+        }
+        let edits = edits
+            .iter()
+            .map(|(selection, content)| (selection, content.as_str()))
+            .collect::<Vec<(&Selection, &str)>>();
+        let delta = self.edit(&edits, false, EditType::Other);
+        Arc::make_mut(&mut self.editor).cursor.apply_delta(&delta);
+    }
+
     fn next_error(&mut self, ctx: &mut EventCtx, _env: &Env) {
         if let BufferContent::File(buffer_path) = self.buffer.content() {
             let mut file_diagnostics = self
@@ -1315,9 +1850,7 @@ impl LapceEditorBufferData {
             file_diagnostics.sort_by(|a, b| a.0.cmp(b.0));
 
             let offset = self.editor.cursor.offset();
-            let position = self
-                .buffer
-                .offset_to_position(offset, self.config.editor.tab_width);
+            let position = self.buffer.offset_to_position(offset);
             let (path, position) =
                 next_in_file_errors_offset(position, buffer_path, &file_diagnostics);
             let location = EditorLocationNew {
@@ -1334,6 +1867,48 @@ impl LapceEditorBufferData {
         }
     }
 
+    /// Goes to the nearest diagnostic in the file after the cursor, or before
+    /// it if not `forward`.
+    fn jump_to_diagnostic(&mut self, ctx: &mut EventCtx, forward: bool) {
+        let path = match self.buffer.content() {
+            BufferContent::File(path) => path.clone(),
+            BufferContent::Local(_) => return,
+        };
+        let offset = self.editor.cursor.offset();
+        let diagnostic = self.main_split.diagnostics.get(&path).and_then(|d| {
+            next_diagnostic(
+                d,
+                offset,
+                forward,
+                self.config.editor.diagnostic_navigation_prefer_errors,
+            )
+        });
+        match diagnostic {
+            Some(diagnostic) => {
+                let location = EditorLocationNew {
+                    path,
+                    position: Some(diagnostic.diagnositc.range.start),
+                    scroll_offset: None,
+                    history: None,
+                };
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::JumpToLocation(None, location),
+                    Target::Auto,
+                ));
+            }
+            None => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowStatusMessage(
+                        "No diagnostics in this file".to_string(),
+                    ),
+                    Target::Auto,
+                ));
+            }
+        }
+    }
+
     fn jump_location_forward(
         &mut self,
         ctx: &mut EventCtx,
@@ -1366,10 +1941,7 @@ impl LapceEditorBufferData {
         }
         if self.editor.current_location >= self.editor.locations.len() {
             let editor = Arc::make_mut(&mut self.editor);
-            editor.save_jump_location(
-                self.buffer.data(),
-                self.config.editor.tab_width,
-            );
+            editor.save_jump_location(self.buffer.data());
             editor.current_location -= 1;
         }
         let editor = Arc::make_mut(&mut self.editor);
@@ -1427,7 +1999,8 @@ impl LapceEditorBufferData {
         let offset = self.editor.cursor.offset();
         let (line, _col) = self
             .buffer
-            .offset_to_line_col(offset, self.config.editor.tab_width);
+            .offset_to_line_col(offset, self.buffer.tab_width(&self.config));
+        let line = self.buffer.visual_line(line);
         let top = self.editor.scroll_offset.y + diff;
         let bottom = top + self.editor.size.borrow().height;
 
@@ -1460,6 +2033,66 @@ impl LapceEditorBufferData {
         ));
     }
 
+    /// Exchange the text of two selected regions, or rotate the text
+    /// through the regions when there are more than two, as a single edit.
+    fn swap_selections(&mut self, ctx: &mut EventCtx) {
+        if self.swap_selection_text().is_err() {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ShowStatusMessage(
+                    "Can't swap overlapping selections".to_string(),
+                ),
+                Target::Auto,
+            ));
+        }
+    }
+
+    /// Does the edit of `swap_selections`, failing without touching the
+    /// buffer when the selected regions overlap.
+    fn swap_selection_text(&mut self) -> Result<()> {
+        let selection = match &self.editor.cursor.mode {
+            CursorMode::Insert(selection) => selection.clone(),
+            _ => return Ok(()),
+        };
+        let mut regions = selection.regions().to_vec();
+        if regions.len() < 2 {
+            return Ok(());
+        }
+        regions.sort_by_key(|r| r.min());
+        if regions.windows(2).any(|w| w[0].max() > w[1].min()) {
+            return Err(anyhow!("overlapping selections"));
+        }
+
+        let contents: Vec<String> = regions
+            .iter()
+            .map(|r| self.buffer.slice_to_cow(r.min()..r.max()).to_string())
+            .collect();
+        let n = regions.len();
+        let selections: Vec<Selection> = regions
+            .iter()
+            .map(|r| Selection::region(r.min(), r.max()))
+            .collect();
+        // Every region gets the text of the region before it, which is a
+        // plain swap when there are only two of them.
+        let edits: Vec<(&Selection, &str)> = selections
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s, contents[(i + n - 1) % n].as_str()))
+            .collect();
+        self.edit(&edits, true, EditType::Other);
+
+        let mut new_selection = Selection::new();
+        let mut shift: isize = 0;
+        for (i, region) in regions.iter().enumerate() {
+            let start = (region.min() as isize + shift) as usize;
+            let new_len = contents[(i + n - 1) % n].len();
+            new_selection.add_region(SelRegion::new(start, start + new_len, None));
+            shift += new_len as isize - contents[i].len() as isize;
+        }
+        self.set_cursor(Cursor::new(CursorMode::Insert(new_selection), None));
+        Ok(())
+    }
+
     fn toggle_visual(&mut self, visual_mode: VisualMode) {
         if !self.config.lapce.modal {
             return;
@@ -1493,7 +2126,7 @@ impl LapceEditorBufferData {
     pub fn current_code_actions(&self) -> Option<&CodeActionResponse> {
         let offset = self.editor.cursor.offset();
         let prev_offset = self.buffer.prev_code_boundary(offset);
-        self.buffer.code_actions.get(&prev_offset)
+        self.buffer.code_actions(prev_offset)
     }
 
     pub fn diagnostics(&self) -> Option<&Arc<Vec<EditorDiagnostic>>> {
@@ -1546,6 +2179,7 @@ impl LapceEditorBufferData {
             (line, config.editor_char_width(text))
         } else {
             let line = (pos.y / config.editor.line_height as f64).floor() as usize;
+            let line = self.buffer.actual_line(line);
             (line, config.editor_char_width(text))
         };
 
@@ -1556,7 +2190,7 @@ impl LapceEditorBufferData {
             let line_end = self.buffer.line_end_col(
                 line,
                 self.editor.cursor.get_mode() != Mode::Normal,
-                config.editor.tab_width,
+                self.buffer.tab_width(config),
             );
 
             let col = (if self.editor.cursor.get_mode() == Mode::Insert {
@@ -1568,7 +2202,7 @@ impl LapceEditorBufferData {
             (line, col)
         };
         self.buffer
-            .offset_of_line_col(line, col, config.editor.tab_width)
+            .offset_of_line_col(line, col, self.buffer.tab_width(config))
     }
 
     pub fn single_click(
@@ -1672,11 +2306,21 @@ impl KeyPressFocus for LapceEditorBufferData {
                 self.editor.content
                     == BufferContent::Local(LocalBufferKind::SourceControl)
             }
+            "file_picker_focus" => {
+                self.editor.content
+                    == BufferContent::Local(LocalBufferKind::FilePicker)
+            }
+            "search_globs_focus" => {
+                self.editor.content
+                    == BufferContent::Local(LocalBufferKind::SearchGlobs)
+            }
             "in_snippet" => self.editor.snippet.is_some(),
             "completion_focus" => self.has_completions(),
             "hover_focus" => self.has_hover(),
             "list_focus" => self.has_completions(),
-            "modal_focus" => self.has_completions() || self.has_hover(),
+            "modal_focus" => {
+                self.has_completions() || self.has_hover() || self.has_signature()
+            }
             _ => false,
         }
     }
@@ -1691,21 +2335,10 @@ impl KeyPressFocus for LapceEditorBufferData {
     ) -> CommandExecuted {
         if let Some(movement) = cmd.move_command(count) {
             self.do_move(&movement, count.unwrap_or(1), mods);
-            if let Some(snippet) = self.editor.snippet.as_ref() {
-                let offset = self.editor.cursor.offset();
-                let mut within_region = false;
-                for (_, (start, end)) in snippet {
-                    if offset >= *start && offset <= *end {
-                        within_region = true;
-                        break;
-                    }
-                }
-                if !within_region {
-                    Arc::make_mut(&mut self.editor).snippet = None;
-                }
-            }
+            self.end_snippet_outside_tabstops();
             self.cancel_completion();
             self.cancel_hover();
+            self.cancel_signature();
             Arc::make_mut(&mut self.editor).motion_mode = None;
             return CommandExecuted::Yes;
         }
@@ -1863,7 +2496,7 @@ impl KeyPressFocus for LapceEditorBufferData {
                             .cursor
                             .edit_selection(
                                 self.buffer.data(),
-                                self.config.editor.tab_width,
+                                self.buffer.tab_width(&self.config),
                             )
                             .regions()
                         {
@@ -1897,13 +2530,13 @@ impl KeyPressFocus for LapceEditorBufferData {
                     CursorMode::Normal(_) | CursorMode::Visual { .. } => {
                         self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         )
                     }
                     CursorMode::Insert(_) => {
                         let selection = self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         );
 
                         self.buffer.update_selection(
@@ -1943,7 +2576,7 @@ impl KeyPressFocus for LapceEditorBufferData {
                 let data = self
                     .editor
                     .cursor
-                    .yank(self.buffer.data(), self.config.editor.tab_width);
+                    .yank(self.buffer.data(), self.buffer.tab_width(&self.config));
                 let register = Arc::make_mut(&mut self.main_split.register);
                 register.add_yank(data);
                 match &self.editor.cursor.mode {
@@ -1964,7 +2597,7 @@ impl KeyPressFocus for LapceEditorBufferData {
                 let data = self
                     .editor
                     .cursor
-                    .yank(self.buffer.data(), self.config.editor.tab_width);
+                    .yank(self.buffer.data(), self.buffer.tab_width(&self.config));
                 Application::global().clipboard().put_string(data.content);
 
                 let selection = if let CursorMode::Insert(mut selection) =
@@ -1983,7 +2616,7 @@ impl KeyPressFocus for LapceEditorBufferData {
                 } else {
                     self.editor.cursor.edit_selection(
                         self.buffer.data(),
-                        self.config.editor.tab_width,
+                        self.buffer.tab_width(&self.config),
                     )
                 };
 
@@ -1998,10 +2631,10 @@ impl KeyPressFocus for LapceEditorBufferData {
                     Arc::make_mut(&mut self.editor).motion_mode =
                         Some(MotionMode::Yank);
                 } else if let Some(MotionMode::Yank) = self.editor.motion_mode {
-                    let data = self
-                        .editor
-                        .cursor
-                        .yank(self.buffer.data(), self.config.editor.tab_width);
+                    let data = self.editor.cursor.yank(
+                        self.buffer.data(),
+                        self.buffer.tab_width(&self.config),
+                    );
                     let register = Arc::make_mut(&mut self.main_split.register);
                     register.add_yank(data);
                 } else {
@@ -2012,7 +2645,7 @@ impl KeyPressFocus for LapceEditorBufferData {
                 let data = self
                     .editor
                     .cursor
-                    .yank(self.buffer.data(), self.config.editor.tab_width);
+                    .yank(self.buffer.data(), self.buffer.tab_width(&self.config));
                 Application::global().clipboard().put_string(data.content);
                 match &self.editor.cursor.mode {
                     CursorMode::Visual {
@@ -2052,13 +2685,13 @@ impl KeyPressFocus for LapceEditorBufferData {
                     CursorMode::Normal(_) | CursorMode::Visual { .. } => {
                         self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         )
                     }
                     CursorMode::Insert(_) => {
                         let selection = self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         );
 
                         self.buffer.update_selection(
@@ -2084,13 +2717,13 @@ impl KeyPressFocus for LapceEditorBufferData {
                     CursorMode::Normal(_) | CursorMode::Visual { .. } => {
                         self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         )
                     }
                     CursorMode::Insert(_) => {
                         let selection = self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         );
 
                         self.buffer.update_selection(
@@ -2116,14 +2749,14 @@ impl KeyPressFocus for LapceEditorBufferData {
                     CursorMode::Normal(_) | CursorMode::Visual { .. } => {
                         self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         )
                     }
                     CursorMode::Insert(_) => {
                         let indent = self.buffer.indent_unit();
                         let selection = self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         );
                         let mut new_selection = Selection::new();
                         for region in selection.regions() {
@@ -2147,7 +2780,7 @@ impl KeyPressFocus for LapceEditorBufferData {
                                         .first_non_blank_character_on_line(line);
                                     let (_, col) = self.buffer.offset_to_line_col(
                                         region.start,
-                                        self.config.editor.tab_width,
+                                        self.buffer.tab_width(&self.config),
                                     );
                                     let count =
                                         if region.start <= nonblank && col > 0 {
@@ -2223,13 +2856,13 @@ impl KeyPressFocus for LapceEditorBufferData {
                     CursorMode::Normal(_) | CursorMode::Visual { .. } => {
                         self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         )
                     }
                     CursorMode::Insert(_) => {
                         let selection = self.editor.cursor.edit_selection(
                             self.buffer.data(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         );
                         let mut new_selection = Selection::new();
                         for region in selection.regions() {
@@ -2261,7 +2894,7 @@ impl KeyPressFocus for LapceEditorBufferData {
             LapceCommand::DeleteForwardAndInsert => {
                 let selection = self.editor.cursor.edit_selection(
                     self.buffer.data(),
-                    self.config.editor.tab_width,
+                    self.buffer.tab_width(&self.config),
                 );
                 let delta = self.edit(&[(&selection, "")], true, EditType::Delete);
                 let selection =
@@ -2462,18 +3095,18 @@ impl KeyPressFocus for LapceEditorBufferData {
                     for region in selection.regions() {
                         let (start_line, _) = self.buffer.offset_to_line_col(
                             region.min(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         );
                         let (end_line, end_col) = self.buffer.offset_to_line_col(
                             region.max(),
-                            self.config.editor.tab_width,
+                            self.buffer.tab_width(&self.config),
                         );
                         for line in start_line..end_line + 1 {
                             let offset = if line == end_line {
                                 self.buffer.offset_of_line_col(
                                     line,
                                     end_col,
-                                    self.config.editor.tab_width,
+                                    self.buffer.tab_width(&self.config),
                                 )
                             } else {
                                 self.buffer.line_end_offset(line, true)
@@ -2631,6 +3264,9 @@ impl KeyPressFocus for LapceEditorBufferData {
                     ));
                 }
             }
+            LapceCommand::SwapSelections => {
+                self.swap_selections(ctx);
+            }
             LapceCommand::SelectUndo => {
                 if let CursorMode::Insert(_) = self.editor.cursor.mode.clone() {
                     self.check_selection_history();
@@ -2648,10 +3284,22 @@ impl KeyPressFocus for LapceEditorBufferData {
                 self.next_error(ctx, env);
             }
             LapceCommand::PreviousError => {}
+            LapceCommand::NextDiagnostic => {
+                self.jump_to_diagnostic(ctx, true);
+            }
+            LapceCommand::PreviousDiagnostic => {
+                self.jump_to_diagnostic(ctx, false);
+            }
             LapceCommand::NextDiff => {
                 self.next_diff(ctx, env);
             }
             LapceCommand::PreviousDiff => {}
+            LapceCommand::NextWhitespaceIssue => {
+                self.next_whitespace_issue();
+            }
+            LapceCommand::FixWhitespaceIssues => {
+                self.fix_whitespace_issues();
+            }
             LapceCommand::ListNext => {
                 let completion = Arc::make_mut(&mut self.completion);
                 completion.next();
@@ -2668,57 +3316,36 @@ impl KeyPressFocus for LapceEditorBufferData {
                 if self.has_hover() {
                     self.cancel_hover();
                 }
+
+                if self.has_signature() {
+                    self.cancel_signature();
+                }
             }
-            LapceCommand::JumpToNextSnippetPlaceholder => {
-                if let Some(snippet) = self.editor.snippet.as_ref() {
-                    let mut current = 0;
-                    let offset = self.editor.cursor.offset();
-                    for (i, (_, (start, end))) in snippet.iter().enumerate() {
-                        if *start <= offset && offset <= *end {
-                            current = i;
-                            break;
-                        }
+            LapceCommand::SnippetNextTabstop => {
+                let editor = Arc::make_mut(&mut self.editor);
+                if let Some(snippet) = editor.snippet.as_mut() {
+                    let active = snippet.next();
+                    let selection = snippet.selection();
+                    if !active {
+                        editor.snippet = None;
                     }
-
-                    let last_placeholder = current + 1 >= snippet.len() - 1;
-
-                    if let Some((_, (start, end))) = snippet.get(current + 1) {
-                        let mut selection = Selection::new();
-                        let region = SelRegion::new(*start, *end, None);
-                        selection.add_region(region);
+                    self.set_cursor(Cursor::new(
+                        CursorMode::Insert(selection),
+                        None,
+                    ));
+                    self.cancel_completion();
+                }
+            }
+            LapceCommand::SnippetPrevTabstop => {
+                let editor = Arc::make_mut(&mut self.editor);
+                if let Some(snippet) = editor.snippet.as_mut() {
+                    if snippet.active > 0 {
+                        snippet.prev();
+                        let selection = snippet.selection();
                         self.set_cursor(Cursor::new(
                             CursorMode::Insert(selection),
                             None,
                         ));
-                    }
-
-                    if last_placeholder {
-                        Arc::make_mut(&mut self.editor).snippet = None;
-                    }
-                    self.cancel_completion();
-                }
-            }
-            LapceCommand::JumpToPrevSnippetPlaceholder => {
-                if let Some(snippet) = self.editor.snippet.as_ref() {
-                    let mut current = 0;
-                    let offset = self.editor.cursor.offset();
-                    for (i, (_, (start, end))) in snippet.iter().enumerate() {
-                        if *start <= offset && offset <= *end {
-                            current = i;
-                            break;
-                        }
-                    }
-
-                    if current > 0 {
-                        if let Some((_, (start, end))) = snippet.get(current - 1) {
-                            let mut selection = Selection::new();
-                            let region = SelRegion::new(*start, *end, None);
-                            selection.add_region(region);
-                            self.set_cursor(Cursor::new(
-                                CursorMode::Insert(selection),
-                                None,
-                            ));
-                        }
                         self.cancel_completion();
                     }
                 }
@@ -2726,7 +3353,7 @@ impl KeyPressFocus for LapceEditorBufferData {
             LapceCommand::ListSelect => {
                 let selection = self.editor.cursor.edit_selection(
                     self.buffer.data(),
-                    self.config.editor.tab_width,
+                    self.buffer.tab_width(&self.config),
                 );
 
                 let count = self.completion.input.len();
@@ -2745,8 +3372,10 @@ impl KeyPressFocus for LapceEditorBufferData {
                     selection
                 };
 
-                let item = self.completion.current_item().to_owned();
-                self.cancel_completion();
+                let item = match self.take_completion_item() {
+                    Some(item) => item,
+                    None => return CommandExecuted::Yes,
+                };
                 if item.data.is_some() {
                     let view_id = self.editor.view_id;
                     let buffer_id = self.buffer.id();
@@ -2778,13 +3407,13 @@ impl KeyPressFocus for LapceEditorBufferData {
                         }),
                     );
                 } else {
-                    let _ = self.apply_completion_item(&item);
+                    let _ = self.accept_completion_item(ctx, &item);
                 }
             }
             LapceCommand::IndentLine => {
                 let selection = self.editor.cursor.edit_selection(
                     self.buffer.data(),
-                    self.config.editor.tab_width,
+                    self.buffer.tab_width(&self.config),
                 );
                 self.edit_with_command(EditCommandKind::IndentLine {
                     selection: Some(selection),
@@ -2793,7 +3422,7 @@ impl KeyPressFocus for LapceEditorBufferData {
             LapceCommand::OutdentLine => {
                 let selection = self.editor.cursor.edit_selection(
                     self.buffer.data(),
-                    self.config.editor.tab_width,
+                    self.buffer.tab_width(&self.config),
                 );
                 self.edit_with_command(EditCommandKind::OutdentLine {
                     selection: Some(selection),
@@ -2803,7 +3432,7 @@ impl KeyPressFocus for LapceEditorBufferData {
                 let mut lines = HashSet::new();
                 let selection = self.editor.cursor.edit_selection(
                     self.buffer.data(),
-                    self.config.editor.tab_width,
+                    self.buffer.tab_width(&self.config),
                 );
                 let comment_token = self
                     .buffer
@@ -2878,6 +3507,23 @@ impl KeyPressFocus for LapceEditorBufferData {
                 Arc::make_mut(&mut self.editor).cursor.apply_delta(&delta);
             }
             LapceCommand::NormalMode => {
+                if let BufferContent::Local(LocalBufferKind::FilePicker) =
+                    self.buffer.content()
+                {
+                    // Escape clears the filter typed after the directory
+                    let input = self.buffer.rope().to_string();
+                    let (_, filter) = split_picker_input(&input);
+                    if !filter.is_empty() {
+                        let selection = Selection::region(
+                            input.len() - filter.len(),
+                            input.len(),
+                        );
+                        let delta =
+                            self.edit(&[(&selection, "")], true, EditType::Delete);
+                        Arc::make_mut(&mut self.editor).cursor.apply_delta(&delta);
+                        return CommandExecuted::Yes;
+                    }
+                }
                 if !self.config.lapce.modal {
                     if let CursorMode::Insert(selection) = &self.editor.cursor.mode {
                         match selection.regions().len() {
@@ -2938,86 +3584,46 @@ impl KeyPressFocus for LapceEditorBufferData {
                 editor.snippet = None;
                 editor.inline_find = None;
                 self.cancel_completion();
+                self.cancel_signature();
             }
             LapceCommand::ToggleCodeLens => {
                 let editor = Arc::make_mut(&mut self.editor);
                 editor.code_lens = !editor.code_lens;
             }
+            LapceCommand::Fold => {
+                let line = self.buffer.line_of_offset(self.editor.cursor.offset());
+                if let Some((start, end)) = self.buffer.folding_range_to_fold(line) {
+                    Arc::make_mut(&mut self.buffer).fold(start, end);
+                    self.reveal_cursor(mods);
+                }
+            }
+            LapceCommand::Unfold => {
+                let line = self.buffer.line_of_offset(self.editor.cursor.offset());
+                Arc::make_mut(&mut self.buffer).unfold(line);
+            }
+            LapceCommand::FoldAll => {
+                if let Some(ranges) = self.buffer.current_folding_ranges().cloned() {
+                    let buffer = Arc::make_mut(&mut self.buffer);
+                    for (start, end) in ranges.iter() {
+                        buffer.fold(*start, *end);
+                    }
+                    self.reveal_cursor(mods);
+                }
+            }
+            LapceCommand::UnfoldAll => {
+                Arc::make_mut(&mut self.buffer).unfold_all();
+            }
             LapceCommand::GotoDefinition => {
-                let offset = self.editor.cursor.offset();
-                let start_offset = self.buffer.prev_code_boundary(offset);
-                let start_position = self
-                    .buffer
-                    .offset_to_position(start_offset, self.config.editor.tab_width);
-                let event_sink = ctx.get_external_handle();
-                let buffer_id = self.buffer.id();
-                let position = self
-                    .buffer
-                    .offset_to_position(offset, self.config.editor.tab_width);
-                let proxy = self.proxy.clone();
-                let editor_view_id = self.editor.view_id;
-                self.proxy.get_definition(
-                    offset,
-                    buffer_id,
-                    position,
-                    Box::new(move |result| {
-                        if let Ok(res) = result {
-                            if let Ok(resp) =
-                                serde_json::from_value::<GotoDefinitionResponse>(res)
-                            {
-                                if let Some(location) = match resp {
-                                    GotoDefinitionResponse::Scalar(location) => {
-                                        Some(location)
-                                    }
-                                    GotoDefinitionResponse::Array(locations) => {
-                                        if !locations.is_empty() {
-                                            Some(locations[0].clone())
-                                        } else {
-                                            None
-                                        }
-                                    }
-                                    GotoDefinitionResponse::Link(
-                                        _location_links,
-                                    ) => None,
-                                } {
-                                    if location.range.start == start_position {
-                                        proxy.get_references(
-                                            buffer_id,
-                                            position,
-                                            Box::new(move |result| {
-                                                let _ = process_get_references(
-                                                    editor_view_id,
-                                                    offset,
-                                                    result,
-                                                    event_sink,
-                                                );
-                                            }),
-                                        );
-                                    } else {
-                                        let _ = event_sink.submit_command(
-                                            LAPCE_UI_COMMAND,
-                                            LapceUICommand::GotoDefinition(
-                                                editor_view_id,
-                                                offset,
-                                                EditorLocationNew {
-                                                    path: path_from_url(
-                                                        &location.uri,
-                                                    ),
-                                                    position: Some(
-                                                        location.range.start,
-                                                    ),
-                                                    scroll_offset: None,
-                                                    history: None,
-                                                },
-                                            ),
-                                            Target::Auto,
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    }),
-                );
+                self.go_to_definition(ctx, false);
+            }
+            LapceCommand::PeekDefinition => {
+                self.go_to_definition(ctx, true);
+            }
+            LapceCommand::GotoTypeDefinition => {
+                self.go_to_type_definition_or_implementation(ctx, false);
+            }
+            LapceCommand::GotoImplementation => {
+                self.go_to_type_definition_or_implementation(ctx, true);
             }
             LapceCommand::SourceControl => {
                 ctx.submit_command(Command::new(
@@ -3037,6 +3643,21 @@ impl KeyPressFocus for LapceEditorBufferData {
                     ));
                 }
             }
+            LapceCommand::ShowHover => {
+                self.update_hover(ctx, self.editor.cursor.offset());
+            }
+            LapceCommand::ShowIncomingCalls => {
+                self.show_hierarchy(ctx, HierarchyKind::IncomingCalls);
+            }
+            LapceCommand::ShowOutgoingCalls => {
+                self.show_hierarchy(ctx, HierarchyKind::OutgoingCalls);
+            }
+            LapceCommand::ShowSupertypes => {
+                self.show_hierarchy(ctx, HierarchyKind::Supertypes);
+            }
+            LapceCommand::ShowSubtypes => {
+                self.show_hierarchy(ctx, HierarchyKind::Subtypes);
+            }
             LapceCommand::ShowCodeActions => {
                 if let Some(actions) = self.current_code_actions() {
                     if !actions.is_empty() {
@@ -3184,6 +3805,52 @@ impl KeyPressFocus for LapceEditorBufferData {
                     }
                 }
             }
+            LapceCommand::SelectNextSearchMatch
+            | LapceCommand::SelectPreviousSearchMatch => {
+                let reverse = cmd == &LapceCommand::SelectPreviousSearchMatch;
+                Arc::make_mut(&mut self.find).visual = true;
+                // Going back starts before the match that's selected
+                let offset = match &self.editor.cursor.mode {
+                    CursorMode::Insert(selection) if reverse => {
+                        selection.min_offset()
+                    }
+                    _ => self.editor.cursor.offset(),
+                };
+                let next = self.find.next(self.buffer.rope(), offset, reverse, true);
+                if let Some((start, end)) = next {
+                    if let CursorMode::Insert(_) = self.editor.cursor.mode {
+                        let editor = Arc::make_mut(&mut self.editor);
+                        editor.save_jump_location(self.buffer.data());
+                        editor.cursor.mode =
+                            CursorMode::Insert(Selection::region(start, end));
+                        editor.cursor.horiz = None;
+                    } else {
+                        self.do_move(&Movement::Offset(start), 1, mods);
+                    }
+                }
+            }
+            LapceCommand::RecallOlderSearchGlobs
+            | LapceCommand::RecallNewerSearchGlobs => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RecallSearchGlobs(
+                        cmd == &LapceCommand::RecallOlderSearchGlobs,
+                    ),
+                    Target::Widget(*self.main_split.tab_id),
+                ));
+            }
+            LapceCommand::FilePickerUp | LapceCommand::FilePickerHome => {
+                let command = if cmd == &LapceCommand::FilePickerUp {
+                    LapceUICommand::FilePickerUp
+                } else {
+                    LapceUICommand::FilePickerHome
+                };
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    command,
+                    Target::Widget(*self.main_split.tab_id),
+                ));
+            }
             LapceCommand::ClearSearch => {
                 Arc::make_mut(&mut self.find).visual = false;
                 let view_id =
@@ -3224,7 +3891,7 @@ impl KeyPressFocus for LapceEditorBufferData {
                 let offset = self.editor.cursor.offset();
                 let (line, _col) = self
                     .buffer
-                    .offset_to_line_col(offset, self.config.editor.tab_width);
+                    .offset_to_line_col(offset, self.buffer.tab_width(&self.config));
                 if line < self.buffer.last_line() {
                     let start = self.buffer.line_end_offset(line, true);
                     let end =
@@ -3239,31 +3906,65 @@ impl KeyPressFocus for LapceEditorBufferData {
             LapceCommand::FormatDocument => {
                 if let BufferContent::File(path) = self.buffer.content() {
                     let path = path.clone();
-                    let proxy = self.proxy.clone();
-                    let buffer_id = self.buffer.id();
+                    self.main_split.format_document(ctx, &path, &self.config);
+                }
+            }
+            LapceCommand::FormatSelection => {
+                if let BufferContent::File(path) = self.buffer.content() {
+                    let path = path.clone();
+                    let selection = self.editor.cursor.edit_selection(
+                        self.buffer.data(),
+                        self.buffer.tab_width(&self.config),
+                    );
+                    // Without a selection there is nothing to narrow the
+                    // formatting down to
+                    if matches!(self.editor.cursor.mode, CursorMode::Normal(_))
+                        || selection.is_caret()
+                    {
+                        self.main_split.format_document(ctx, &path, &self.config);
+                    } else {
+                        let range = Range::new(
+                            self.buffer.offset_to_position(selection.min_offset()),
+                            self.buffer.offset_to_position(selection.max_offset()),
+                        );
+                        self.main_split.format_range(
+                            ctx,
+                            &path,
+                            range,
+                            &self.config,
+                        );
+                    }
+                }
+            }
+            LapceCommand::RenameSymbol => {
+                if let BufferContent::File(path) = self.buffer.content() {
+                    let path = path.clone();
+                    let offset = self.editor.cursor.offset();
                     let rev = self.buffer.rev();
+                    let position = self.buffer.offset_to_position(offset);
                     let event_sink = ctx.get_external_handle();
-                    let (sender, receiver) = bounded(1);
-                    thread::spawn(move || {
-                        proxy.get_document_formatting(
-                            buffer_id,
-                            Box::new(move |result| {
-                                let _ = sender.send(result);
-                            }),
-                        );
-
-                        let result = receiver
-                            .recv_timeout(Duration::from_secs(1))
-                            .map_or_else(
-                                |e| Err(anyhow!("{}", e)),
-                                |v| v.map_err(|e| anyhow!("{:?}", e)),
+                    let tab_id = *self.main_split.tab_id;
+                    self.proxy.prepare_rename(
+                        self.buffer.id(),
+                        position,
+                        Box::new(move |result| {
+                            let result = result.map_err(|e| {
+                                anyhow!(
+                                    "{}",
+                                    e.get("message")
+                                        .and_then(|message| message.as_str())
+                                        .unwrap_or("unknown error")
+                                )
+                            });
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::PrepareRename(
+                                    path, rev, offset, result,
+                                ),
+                                Target::Widget(tab_id),
                             );
-                        let _ = event_sink.submit_command(
-                            LAPCE_UI_COMMAND,
-                            LapceUICommand::DocumentFormat(path, rev, result),
-                            Target::Auto,
-                        );
-                    });
+                        }),
+                    );
                 }
             }
             LapceCommand::Save => {
@@ -3273,32 +3974,7 @@ impl KeyPressFocus for LapceEditorBufferData {
 
                 if let BufferContent::File(path) = self.buffer.content() {
                     let path = path.clone();
-                    let proxy = self.proxy.clone();
-                    let buffer_id = self.buffer.id();
-                    let rev = self.buffer.rev();
-                    let event_sink = ctx.get_external_handle();
-                    let (sender, receiver) = bounded(1);
-                    thread::spawn(move || {
-                        proxy.get_document_formatting(
-                            buffer_id,
-                            Box::new(move |result| {
-                                let _ = sender.send(result);
-                            }),
-                        );
-
-                        let result = receiver
-                            .recv_timeout(Duration::from_secs(1))
-                            .map_or_else(
-                                |e| Err(anyhow!("{}", e)),
-                                |v| v.map_err(|e| anyhow!("{:?}", e)),
-                            );
-
-                        let _ = event_sink.submit_command(
-                            LAPCE_UI_COMMAND,
-                            LapceUICommand::DocumentFormatAndSave(path, rev, result),
-                            Target::Auto,
-                        );
-                    });
+                    self.main_split.save(ctx, &path, &self.config);
                 }
             }
             _ => return CommandExecuted::No,
@@ -3307,12 +3983,31 @@ impl KeyPressFocus for LapceEditorBufferData {
     }
 
     fn receive_char(&mut self, ctx: &mut EventCtx, c: &str) {
+        if c == " "
+            && self.editor.content
+                == BufferContent::Local(LocalBufferKind::FilePicker)
+        {
+            // Before anything is typed after the directory, space picks the
+            // selected file to be opened along with others.
+            let input = self.buffer.rope().to_string();
+            if split_picker_input(&input).1.is_empty() {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ToggleFilePickerSelection,
+                    Target::Widget(*self.main_split.tab_id),
+                ));
+                return;
+            }
+        }
         if self.get_mode() == Mode::Insert {
             self.edit_with_command(EditCommandKind::InsertChars { chars: c });
+            self.end_snippet_outside_tabstops();
 
             self.update_selection_history();
             self.update_completion(ctx);
+            self.update_signature(ctx, c);
             self.cancel_hover();
+            self.update_on_type_formatting(ctx, c);
         } else if let Some(direction) = self.editor.inline_find.clone() {
             self.inline_find(direction.clone(), c);
             let editor = Arc::make_mut(&mut self.editor);
@@ -3419,6 +4114,36 @@ fn str_matching_pair(c: &str) -> Option<char> {
     None
 }
 
+/// The locations of a definition response, links going to the name of
+/// what they link to.
+fn definition_locations(resp: GotoDefinitionResponse) -> Vec<Location> {
+    match resp {
+        GotoDefinitionResponse::Scalar(location) => vec![location],
+        GotoDefinitionResponse::Array(locations) => locations,
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .map(|link| Location {
+                uri: link.target_uri,
+                range: link.target_selection_range,
+            })
+            .collect(),
+    }
+}
+
+/// What going to the definitions at `locations` does: jump to the only one,
+/// or list all of them in the palette.
+pub fn definition_command(locations: &[Location]) -> LapceUICommand {
+    match locations {
+        [location] => LapceUICommand::JumpToLocation(
+            None,
+            EditorLocationNew::from_lsp(location),
+        ),
+        _ => LapceUICommand::RunPaletteReferences(
+            locations.iter().map(EditorLocationNew::from_lsp).collect(),
+        ),
+    }
+}
+
 fn process_get_references(
     editor_view_id: WidgetId,
     offset: usize,
@@ -3454,3 +4179,261 @@ fn process_get_references(
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(uri: &str, line: u32) -> Value {
+        serde_json::json!({
+            "uri": uri,
+            "range": {
+                "start": { "line": line, "character": 4 },
+                "end": { "line": line, "character": 8 },
+            },
+        })
+    }
+
+    fn locations(resp: Value) -> Vec<(String, u32)> {
+        let resp: GotoDefinitionResponse = serde_json::from_value(resp).unwrap();
+        definition_locations(resp)
+            .into_iter()
+            .map(|l| (l.uri.to_string(), l.range.start.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_definition_locations_single() {
+        assert_eq!(
+            vec![("file:///a.rs".to_string(), 3)],
+            locations(location("file:///a.rs", 3))
+        );
+        assert_eq!(
+            vec![("file:///a.rs".to_string(), 3)],
+            locations(serde_json::json!([location("file:///a.rs", 3)]))
+        );
+        assert!(locations(serde_json::json!([])).is_empty());
+    }
+
+    #[test]
+    fn test_definition_locations_multiple() {
+        assert_eq!(
+            vec![
+                ("file:///a.rs".to_string(), 3),
+                ("file:///b.rs".to_string(), 10),
+            ],
+            locations(serde_json::json!([
+                location("file:///a.rs", 3),
+                location("file:///b.rs", 10),
+            ]))
+        );
+
+        // Links go to the name of what they link to.
+        let link = |uri: &str, line: u32| {
+            serde_json::json!({
+                "targetUri": uri,
+                "targetRange": {
+                    "start": { "line": line - 1, "character": 0 },
+                    "end": { "line": line + 5, "character": 1 },
+                },
+                "targetSelectionRange": {
+                    "start": { "line": line, "character": 4 },
+                    "end": { "line": line, "character": 8 },
+                },
+            })
+        };
+        assert_eq!(
+            vec![
+                ("file:///a.rs".to_string(), 3),
+                ("file:///b.rs".to_string(), 10),
+            ],
+            locations(serde_json::json!([
+                link("file:///a.rs", 3),
+                link("file:///b.rs", 10),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_definition_command() {
+        let single: Vec<Location> =
+            serde_json::from_value(serde_json::json!([location("file:///a.rs", 3)]))
+                .unwrap();
+        match definition_command(&single) {
+            LapceUICommand::JumpToLocation(None, location) => {
+                assert_eq!(Path::new("/a.rs"), location.path);
+                assert_eq!(Some(Position::new(3, 4)), location.position);
+            }
+            _ => panic!("a single definition should be jumped to"),
+        }
+
+        let multiple: Vec<Location> = serde_json::from_value(serde_json::json!([
+            location("file:///a.rs", 3),
+            location("file:///b.rs", 10),
+        ]))
+        .unwrap();
+        match definition_command(&multiple) {
+            LapceUICommand::RunPaletteReferences(locations) => {
+                assert_eq!(
+                    vec![
+                        (
+                            Path::new("/a.rs").to_path_buf(),
+                            Some(Position::new(3, 4))
+                        ),
+                        (
+                            Path::new("/b.rs").to_path_buf(),
+                            Some(Position::new(10, 4))
+                        ),
+                    ],
+                    locations
+                        .into_iter()
+                        .map(|l| (l.path, l.position))
+                        .collect::<Vec<_>>()
+                );
+            }
+            _ => panic!("multiple definitions should be listed in the palette"),
+        }
+    }
+
+    /// The editor of a file holding `content`, with the cursor at its end in
+    /// insert mode.
+    fn editor_data(content: &str) -> LapceEditorBufferData {
+        let path = Path::new("/ws/main.rs");
+        let (mut main_split, config) = LapceMainSplitData::with_file(path);
+        main_split.load_buffer(
+            path,
+            content,
+            lapce_rpc::buffer::FileEncoding::default(),
+        );
+        let mut editor = LapceEditorData::new(
+            None,
+            None,
+            BufferContent::File(path.to_path_buf()),
+            &config,
+        );
+        editor.cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(content.len())), None);
+        LapceEditorBufferData {
+            view_id: editor.view_id,
+            editor: Arc::new(editor),
+            buffer: main_split.open_files[path].clone(),
+            completion: Arc::new(CompletionData::new()),
+            hover: Arc::new(HoverData::new()),
+            signature: Arc::new(SignatureData::new()),
+            source_control: Arc::new(SourceControlData::new()),
+            find: Arc::new(Find::new(0)),
+            proxy: main_split.proxy.clone(),
+            config: Arc::new(config),
+            main_split,
+        }
+    }
+
+    #[test]
+    fn test_accepted_completion_runs_command() {
+        let command = |name: &str| lsp_types::Command {
+            title: String::new(),
+            command: name.to_string(),
+            arguments: None,
+        };
+        let timeout = std::time::Duration::from_millis(100);
+
+        let mut data = editor_data("let v = fo");
+        data.proxy.sent_methods(timeout);
+        let mut item = CompletionItem::new_simple("foo".to_string(), "".to_string());
+        item.command = Some(command("rust-analyzer.applyActionGroup"));
+        let accepted = data.apply_accepted_item(&item).unwrap();
+        assert_eq!(
+            Some(CompletionCommand::Execute(item.command.as_ref().unwrap())),
+            accepted
+        );
+        assert_eq!("let v = foo", data.buffer.rope().to_string());
+        assert!(data
+            .proxy
+            .sent_methods(timeout)
+            .contains(&"execute_command".to_string()));
+
+        // Client commands are left to the caller, as they need the UI.
+        let mut data = editor_data("let v = fo");
+        data.proxy.sent_methods(timeout);
+        item.command = Some(command("editor.action.triggerParameterHints"));
+        let accepted = data.apply_accepted_item(&item).unwrap();
+        assert_eq!(Some(CompletionCommand::TriggerParameterHints), accepted);
+        assert!(!data
+            .proxy
+            .sent_methods(timeout)
+            .contains(&"execute_command".to_string()));
+    }
+
+    #[test]
+    fn test_stale_completion_is_not_taken() {
+        let item = |label: &str| {
+            CompletionItem::new_simple(label.to_string(), "".to_string())
+        };
+        let mut data = editor_data("let v = lx");
+        let completion = Arc::make_mut(&mut data.completion);
+        completion.status = CompletionStatus::Started;
+        completion.receive(
+            0,
+            "".to_string(),
+            lsp_types::CompletionResponse::Array(vec![item("len"), item("push")]),
+            BufferId(0),
+            0,
+        );
+        completion.update_input("l".to_string());
+        completion.update_input("lx".to_string());
+        assert!(completion.stale);
+
+        assert!(data.take_completion_item().is_none());
+        assert_ne!(CompletionStatus::Inactive, data.completion.status);
+        assert_eq!("let v = lx", data.buffer.rope().to_string());
+
+        let completion = Arc::make_mut(&mut data.completion);
+        completion.update_input("l".to_string());
+        assert_eq!("len", data.take_completion_item().unwrap().label);
+        assert_eq!(CompletionStatus::Inactive, data.completion.status);
+    }
+
+    #[test]
+    fn test_swap_selections() {
+        let select = |data: &mut LapceEditorBufferData,
+                      ranges: &[(usize, usize)]| {
+            let mut selection = Selection::new();
+            for (start, end) in ranges {
+                selection.add_region(SelRegion::new(*start, *end, None));
+            }
+            data.set_cursor(Cursor::new(CursorMode::Insert(selection), None));
+        };
+        let regions = |data: &LapceEditorBufferData| match &data.editor.cursor.mode {
+            CursorMode::Insert(selection) => selection
+                .regions()
+                .iter()
+                .map(|r| (r.min(), r.max()))
+                .collect::<Vec<_>>(),
+            _ => panic!("the cursor should stay in insert mode"),
+        };
+
+        let mut data = editor_data("let a = foo + barbaz;");
+        select(&mut data, &[(8, 11), (14, 20)]);
+        data.swap_selection_text().unwrap();
+        assert_eq!("let a = barbaz + foo;", data.buffer.rope().to_string());
+        assert_eq!(vec![(8, 14), (17, 20)], regions(&data));
+
+        // Each region takes the text of the one before it.
+        let mut data = editor_data("a, bb, ccc");
+        select(&mut data, &[(0, 1), (3, 5), (7, 10)]);
+        data.swap_selection_text().unwrap();
+        assert_eq!("ccc, a, bb", data.buffer.rope().to_string());
+        assert_eq!(vec![(0, 3), (5, 6), (8, 10)], regions(&data));
+
+        // Adding regions merges overlapping ones, but editing them in place
+        // doesn't.
+        let mut data = editor_data("abcdef");
+        let mut selection = Selection::new();
+        selection.add_region(SelRegion::new(0, 2, None));
+        selection.add_region(SelRegion::new(3, 6, None));
+        selection.regions_mut()[0] = SelRegion::new(0, 4, None);
+        data.set_cursor(Cursor::new(CursorMode::Insert(selection), None));
+        assert!(data.swap_selection_text().is_err());
+        assert_eq!("abcdef", data.buffer.rope().to_string());
+    }
+}
@@ -0,0 +1,68 @@
+use xi_rope::RopeDelta;
+
+use crate::{
+    buffer::{
+        data::{BufferDataListener, EditableBufferData},
+        EditType,
+    },
+    movement::{Cursor, Selection},
+};
+
+/// Applies a batch of edits at once. Every edit is given in offsets of the
+/// buffer before the batch, so earlier edits don't shift later ones.
+pub struct ApplyEditsCommand<'a> {
+    pub(super) cursor: &'a mut Cursor,
+    pub(super) edits: &'a [(Selection, &'a str)],
+}
+
+impl<'a> ApplyEditsCommand<'a> {
+    pub fn execute<L: BufferDataListener>(
+        self,
+        mut buffer: EditableBufferData<'a, L>,
+    ) -> Option<RopeDelta> {
+        if self.edits.is_empty() {
+            return None;
+        }
+
+        let delta = buffer.edit_multiple(self.edits, EditType::InsertChars);
+        self.cursor.apply_delta(&delta);
+        Some(delta)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        editor::commands::{test::MockEditor, EditCommandKind},
+        movement::Selection,
+    };
+
+    #[test]
+    fn apply_edits_inserts_import_above_cursor() {
+        let mut editor = MockEditor::new("fn main() {\n    HashMap<$0>\n}");
+
+        let edits = [(Selection::caret(0), "use std::collections::HashMap;\n\n")];
+        editor.command(EditCommandKind::ApplyEdits { edits: &edits });
+
+        assert_eq!(
+            "use std::collections::HashMap;\n\nfn main() {\n    HashMap<$0>\n}",
+            editor.state()
+        );
+    }
+
+    #[test]
+    fn apply_edits_uses_offsets_from_before_the_batch() {
+        let mut editor = MockEditor::new("mod a;\nfn main() {\n    b<$0>\n}");
+
+        let edits = [
+            (Selection::region(7, 7), "use a::b;\n"),
+            (Selection::region(0, 6), "mod a;\nmod c;"),
+        ];
+        editor.command(EditCommandKind::ApplyEdits { edits: &edits });
+
+        assert_eq!(
+            "mod a;\nmod c;\nuse a::b;\nfn main() {\n    b<$0>\n}",
+            editor.state()
+        );
+    }
+}
@@ -4,9 +4,9 @@ use xi_rope::RopeDelta;
 use crate::{
     buffer::data::{BufferDataListener, EditableBufferData},
     editor::commands::{
-        indent_line::IndentLineCommand, insert_chars::InsertCharsCommand,
-        insert_tab::InsertTabCommand, outdent_line::OutdentLineCommand,
-        redo::RedoCommand, undo::UndoCommand,
+        apply_edits::ApplyEditsCommand, indent_line::IndentLineCommand,
+        insert_chars::InsertCharsCommand, insert_tab::InsertTabCommand,
+        outdent_line::OutdentLineCommand, redo::RedoCommand, undo::UndoCommand,
     },
     movement::{Cursor, CursorMode, Selection},
     state::Mode,
@@ -15,6 +15,7 @@ use crate::{
 #[cfg(test)]
 pub mod test;
 
+pub mod apply_edits;
 pub mod insert_chars;
 pub mod insert_tab;
 pub mod redo;
@@ -81,6 +82,12 @@ impl<'a> EditCommandFactory<'a> {
                     None
                 }
             }
+            EditCommandKind::ApplyEdits { edits } => {
+                Some(EditCommand::ApplyEdits(ApplyEditsCommand {
+                    cursor: self.cursor,
+                    edits,
+                }))
+            }
         }
     }
 }
@@ -93,6 +100,7 @@ pub enum EditCommandKind<'a> {
     Redo,
     IndentLine { selection: Option<Selection> },
     OutdentLine { selection: Option<Selection> },
+    ApplyEdits { edits: &'a [(Selection, &'a str)] },
 }
 
 pub enum EditCommand<'a> {
@@ -102,6 +110,7 @@ pub enum EditCommand<'a> {
     IndentLine(IndentLineCommand<'a>),
     OutdentLine(OutdentLineCommand<'a>),
     InsertChars(InsertCharsCommand<'a>),
+    ApplyEdits(ApplyEditsCommand<'a>),
 }
 
 impl<'a> EditCommand<'a> {
@@ -116,6 +125,7 @@ impl<'a> EditCommand<'a> {
             Self::IndentLine(command) => command.execute(buffer),
             Self::OutdentLine(command) => command.execute(buffer),
             Self::InsertChars(command) => command.execute(buffer),
+            Self::ApplyEdits(command) => command.execute(buffer),
         }
     }
 }
@@ -8,6 +8,7 @@ use druid::{Target, WidgetId};
 
 use include_dir::{include_dir, Dir};
 use lapce_rpc::file::FileNodeItem;
+use parking_lot::Mutex;
 
 use crate::proxy::LapceProxy;
 use crate::state::LapceWorkspace;
@@ -23,6 +24,8 @@ pub struct FileExplorerData {
     pub widget_id: WidgetId,
     pub workspace: Option<FileNodeItem>,
     pub active_selected: Option<PathBuf>,
+    /// The path being revealed while a directory on the way to it is read.
+    pub pending_reveal: Option<PathBuf>,
 
     #[allow(dead_code)]
     count: usize,
@@ -83,6 +86,7 @@ impl FileExplorerData {
                 children_open_count: 0,
             }),
             active_selected: None,
+            pending_reveal: None,
             count: 0,
         }
     }
@@ -113,6 +117,196 @@ impl FileExplorerData {
         )
     }
 
+    /// Opens every directory between the workspace root and `path`. If one
+    /// of them hasn't been read yet, a read is requested and `path` is kept in
+    /// `pending_reveal` to continue once the items arrive. Returns whether
+    /// `path` is now visible in the tree.
+    pub fn expand_to(
+        &mut self,
+        path: &Path,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) -> bool {
+        self.pending_reveal = None;
+        // `node_tree` lists `path` itself first and the workspace root last.
+        let paths = match self.node_tree(path) {
+            Some(paths) => paths,
+            None => return false,
+        };
+        let dirs: Vec<PathBuf> = paths.iter().skip(1).rev().cloned().collect();
+        for (i, dir) in dirs.iter().enumerate() {
+            let node = match self.get_node_mut(dir) {
+                Some(node) => node,
+                None => return false,
+            };
+            if !node.read {
+                // Nothing below an unread directory has been read either, so
+                // read the rest of the way down in one go.
+                self.pending_reveal = Some(path.to_path_buf());
+                self.read_dirs(dirs[i..].to_vec(), proxy, event_sink);
+                return false;
+            }
+            node.open = true;
+        }
+        for path in paths.iter() {
+            self.update_node_count(path);
+        }
+        true
+    }
+
+    /// The row of `path` in the tree, with the workspace root at row 0, or
+    /// `None` if it's inside a closed directory.
+    pub fn item_index(&self, path: &Path) -> Option<usize> {
+        let mut node = self.workspace.as_ref()?;
+        let mut index = 0;
+        while node.path_buf != path {
+            if !node.open {
+                return None;
+            }
+            let mut next = None;
+            for child in node.sorted_children() {
+                index += 1;
+                if path.starts_with(&child.path_buf) {
+                    next = Some(child);
+                    break;
+                }
+                index += child.children_open_count;
+            }
+            node = next?;
+        }
+        Some(index)
+    }
+
+    /// Reads all of `dirs` and sends their items back together in an
+    /// `UpdateExplorerItemsBatch` once the last of them has responded, along
+    /// with the ones that couldn't be read.
+    fn read_dirs(
+        &self,
+        dirs: Vec<PathBuf>,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        let tab_id = self.tab_id;
+        let total = dirs.len();
+        let responses = Arc::new(Mutex::new((0, Vec::new(), Vec::new())));
+        for dir in dirs {
+            let responses = responses.clone();
+            let event_sink = event_sink.clone();
+            proxy.read_dir(
+                &dir.clone(),
+                Box::new(move |result| {
+                    let items = result.ok().and_then(|res| {
+                        serde_json::from_value::<Vec<FileNodeItem>>(res).ok()
+                    });
+                    let mut responses = responses.lock();
+                    responses.0 += 1;
+                    match items {
+                        Some(items) => responses.1.push((dir, items)),
+                        None => responses.2.push(dir),
+                    }
+                    if responses.0 == total {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateExplorerItemsBatch(
+                                std::mem::take(&mut responses.1),
+                                std::mem::take(&mut responses.2),
+                            ),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Re-reads the directories containing `paths` after something in them
+    /// changed on disk, each once, skipping those that haven't been read yet.
+    pub fn refresh_parents<'a>(
+        &mut self,
+        paths: impl Iterator<Item = &'a Path>,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        let mut dirs: Vec<PathBuf> = paths
+            .filter_map(|path| path.parent())
+            .filter(|parent| {
+                self.get_node_mut(parent).map(|node| node.read) == Some(true)
+            })
+            .map(|parent| parent.to_path_buf())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        if !dirs.is_empty() {
+            self.read_dirs(dirs, proxy, event_sink);
+        }
+    }
+
+    /// Fills in the children read for the directory at `path`, opening it on
+    /// its first read, and leaves the counts of its ancestors to the caller.
+    /// Children that were already known keep their own children and state.
+    fn set_items(&mut self, path: &Path, items: &[FileNodeItem]) {
+        if let Some(node) = self.get_node_mut(path) {
+            let mut children = std::mem::take(&mut node.children);
+            node.children = items
+                .iter()
+                .map(|item| {
+                    let item = children
+                        .remove(&item.path_buf)
+                        .filter(|child| child.is_dir == item.is_dir)
+                        .unwrap_or_else(|| item.clone());
+                    (item.path_buf.clone(), item)
+                })
+                .collect();
+            if !node.read {
+                node.open = true;
+            }
+            node.read = true;
+            node.children_open_count = node.children.len();
+        }
+    }
+
+    pub fn update_items(&mut self, path: &Path, items: &[FileNodeItem]) {
+        self.set_items(path, items);
+        if let Some(paths) = self.node_tree(path) {
+            for path in paths.iter() {
+                self.update_node_count(path);
+            }
+        }
+    }
+
+    /// Applies the items read for several directories, parents before their
+    /// children, then updates the count of every directory involved once,
+    /// deepest first. A reveal waiting on one of the `failed` directories is
+    /// given up, as reading it again would only fail again.
+    pub fn update_items_batch(
+        &mut self,
+        batch: &[(PathBuf, Vec<FileNodeItem>)],
+        failed: &[PathBuf],
+    ) {
+        if let Some(path) = self.pending_reveal.as_ref() {
+            if failed.iter().any(|dir| path.starts_with(dir)) {
+                self.pending_reveal = None;
+            }
+        }
+        let mut batch: Vec<&(PathBuf, Vec<FileNodeItem>)> = batch.iter().collect();
+        batch.sort_by_key(|(path, _)| path.components().count());
+        let mut paths = Vec::new();
+        for (path, items) in batch {
+            self.set_items(path, items);
+            paths.extend(self.node_tree(path).unwrap_or_default());
+        }
+        paths.sort_by(|a, b| {
+            b.components()
+                .count()
+                .cmp(&a.components().count())
+                .then_with(|| a.cmp(b))
+        });
+        paths.dedup();
+        for path in paths.iter() {
+            self.update_node_count(path);
+        }
+    }
+
     pub fn get_node_by_index(&mut self, index: usize) -> Option<&mut FileNodeItem> {
         let (_, node) = get_item_children_mut(0, index, self.workspace.as_mut()?);
         node
@@ -182,3 +376,94 @@ pub fn get_item_children_mut(
     }
     (i, None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, is_dir: bool) -> FileNodeItem {
+        FileNodeItem {
+            path_buf: PathBuf::from(path),
+            is_dir,
+            read: false,
+            open: false,
+            children: HashMap::new(),
+            children_open_count: 0,
+        }
+    }
+
+    fn explorer() -> FileExplorerData {
+        FileExplorerData {
+            tab_id: WidgetId::next(),
+            widget_id: WidgetId::next(),
+            workspace: Some(item("/ws", true)),
+            active_selected: None,
+            pending_reveal: None,
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn test_update_items_batch_matches_serial() {
+        let batch = vec![
+            (
+                PathBuf::from("/ws/a/b"),
+                vec![item("/ws/a/b/c", true), item("/ws/a/b/h.rs", false)],
+            ),
+            (
+                PathBuf::from("/ws"),
+                vec![item("/ws/a", true), item("/ws/f.rs", false)],
+            ),
+            (
+                PathBuf::from("/ws/a"),
+                vec![item("/ws/a/b", true), item("/ws/a/g.rs", false)],
+            ),
+        ];
+
+        let mut serial = explorer();
+        for (path, items) in [&batch[1], &batch[2], &batch[0]] {
+            serial.update_items(path, items);
+        }
+        let mut batched = explorer();
+        batched.update_items_batch(&batch, &[]);
+
+        for path in ["/ws", "/ws/a", "/ws/a/b", "/ws/a/b/c"] {
+            let path = Path::new(path);
+            assert_eq!(
+                serial.get_node_mut(path).unwrap().children_open_count,
+                batched.get_node_mut(path).unwrap().children_open_count,
+            );
+        }
+        assert_eq!(
+            6,
+            batched
+                .get_node_mut(Path::new("/ws"))
+                .unwrap()
+                .children_open_count
+        );
+
+        let path = Path::new("/ws/a/b/h.rs");
+        assert_eq!(Some(4), batched.item_index(path));
+        assert_eq!(serial.item_index(path), batched.item_index(path));
+    }
+
+    #[test]
+    fn test_update_items_batch_with_failed_read() {
+        let mut explorer = explorer();
+        explorer.pending_reveal = Some(PathBuf::from("/ws/a/b/h.rs"));
+        let batch = vec![(
+            PathBuf::from("/ws"),
+            vec![item("/ws/a", true), item("/ws/f.rs", false)],
+        )];
+
+        // A failed read of an unrelated directory keeps the reveal going.
+        explorer.update_items_batch(&batch, &[PathBuf::from("/ws/c")]);
+        assert_eq!(Some(PathBuf::from("/ws/a/b/h.rs")), explorer.pending_reveal);
+
+        // The one on the way to the revealed file stops it.
+        explorer.update_items_batch(&batch, &[PathBuf::from("/ws/a")]);
+        assert_eq!(None, explorer.pending_reveal);
+        assert!(explorer.get_node_mut(Path::new("/ws")).unwrap().read);
+        assert!(!explorer.get_node_mut(Path::new("/ws/a")).unwrap().read);
+    }
+}
@@ -0,0 +1,477 @@
+//! The call and type hierarchies, trees whose nodes fetch their children
+//! from the language server the first time they're expanded.
+
+use std::path::PathBuf;
+
+use druid::{ExtEventSink, Target, WidgetId};
+use lapce_rpc::{buffer::BufferId, proxy::TypeHierarchyItem};
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall,
+    Position, Range,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    data::PanelKind,
+    proxy::{path_from_url, LapceProxy},
+};
+
+/// What the children of a node are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HierarchyKind {
+    /// The callers of the item
+    IncomingCalls,
+    /// The functions the item calls
+    OutgoingCalls,
+    /// The types the item extends or implements
+    Supertypes,
+    /// The types extending or implementing the item
+    Subtypes,
+}
+
+impl HierarchyKind {
+    /// The panel the hierarchy is shown in.
+    pub fn panel(&self) -> PanelKind {
+        match self {
+            HierarchyKind::IncomingCalls | HierarchyKind::OutgoingCalls => {
+                PanelKind::CallHierarchy
+            }
+            HierarchyKind::Supertypes | HierarchyKind::Subtypes => {
+                PanelKind::TypeHierarchy
+            }
+        }
+    }
+}
+
+/// An item of the hierarchy and how it relates to the one it's listed under.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HierarchyChild {
+    /// A call or type hierarchy item, which have the same fields
+    pub item: CallHierarchyItem,
+    /// Where the calls are made, in the file of the caller, empty for types
+    pub from_ranges: Vec<Range>,
+}
+
+impl From<CallHierarchyIncomingCall> for HierarchyChild {
+    fn from(call: CallHierarchyIncomingCall) -> Self {
+        Self {
+            item: call.from,
+            from_ranges: call.from_ranges,
+        }
+    }
+}
+
+impl From<CallHierarchyOutgoingCall> for HierarchyChild {
+    fn from(call: CallHierarchyOutgoingCall) -> Self {
+        Self {
+            item: call.to,
+            from_ranges: call.from_ranges,
+        }
+    }
+}
+
+impl From<TypeHierarchyItem> for HierarchyChild {
+    fn from(item: TypeHierarchyItem) -> Self {
+        Self {
+            item,
+            from_ranges: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HierarchyNode {
+    pub item: CallHierarchyItem,
+    /// Where the calls between the item and its parent are made, empty for
+    /// a root and for types
+    pub from_ranges: Vec<Range>,
+    /// The children of the item, `None` until they're fetched
+    pub children: Option<Vec<HierarchyNode>>,
+    pub expanded: bool,
+    /// The item is also one of its ancestors, so it isn't expanded to list
+    /// the same items over and over
+    pub recursive: bool,
+}
+
+impl HierarchyNode {
+    fn new(child: HierarchyChild, recursive: bool) -> Self {
+        Self {
+            item: child.item,
+            from_ranges: child.from_ranges,
+            children: None,
+            expanded: false,
+            recursive,
+        }
+    }
+}
+
+fn same_item(a: &CallHierarchyItem, b: &CallHierarchyItem) -> bool {
+    a.uri == b.uri && a.selection_range == b.selection_range && a.name == b.name
+}
+
+#[derive(Clone)]
+pub struct HierarchyData {
+    pub widget_id: WidgetId,
+    pub split_id: WidgetId,
+    pub tree_widget_id: WidgetId,
+    pub kind: HierarchyKind,
+    /// The buffer the hierarchy was prepared in, whose language server is
+    /// asked for the children
+    pub buffer_id: Option<BufferId>,
+    /// Counts the hierarchies shown, so children fetched for an older one are
+    /// dropped
+    pub id: u64,
+    pub roots: Vec<HierarchyNode>,
+}
+
+impl HierarchyData {
+    pub fn new(kind: HierarchyKind) -> Self {
+        Self {
+            widget_id: WidgetId::next(),
+            split_id: WidgetId::next(),
+            tree_widget_id: WidgetId::next(),
+            kind,
+            buffer_id: None,
+            id: 0,
+            roots: Vec::new(),
+        }
+    }
+
+    /// Starts a new hierarchy from the items the language server prepared.
+    pub fn set_roots(
+        &mut self,
+        buffer_id: BufferId,
+        kind: HierarchyKind,
+        items: Vec<CallHierarchyItem>,
+    ) {
+        self.id += 1;
+        self.buffer_id = Some(buffer_id);
+        self.kind = kind;
+        self.roots = items
+            .into_iter()
+            .map(|item| HierarchyNode::new(HierarchyChild::from(item), false))
+            .collect();
+    }
+
+    pub fn node(&self, path: &[usize]) -> Option<&HierarchyNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.roots.get(*first)?;
+        for i in rest {
+            node = node.children.as_ref()?.get(*i)?;
+        }
+        Some(node)
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> Option<&mut HierarchyNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.roots.get_mut(*first)?;
+        for i in rest {
+            node = node.children.as_mut()?.get_mut(*i)?;
+        }
+        Some(node)
+    }
+
+    /// Expands or collapses the node at `path`, returning whether its
+    /// children have to be fetched to show it expanded.
+    pub fn toggle(&mut self, path: &[usize]) -> bool {
+        let node = match self.node_mut(path) {
+            Some(node) if !node.recursive => node,
+            _ => return false,
+        };
+        node.expanded = !node.expanded;
+        node.expanded && node.children.is_none()
+    }
+
+    /// Sets the children of the node at `path`, marking the ones back to an
+    /// item already in the path so they aren't expanded.
+    pub fn set_children(&mut self, path: &[usize], children: Vec<HierarchyChild>) {
+        let ancestors: Vec<CallHierarchyItem> = (1..=path.len())
+            .filter_map(|i| self.node(&path[..i]).map(|node| node.item.clone()))
+            .collect();
+        if let Some(node) = self.node_mut(path) {
+            let children = children
+                .into_iter()
+                .map(|child| {
+                    let recursive =
+                        ancestors.iter().any(|item| same_item(item, &child.item));
+                    HierarchyNode::new(child, recursive)
+                })
+                .collect();
+            node.children = Some(children);
+        }
+    }
+
+    /// The rows of the expanded tree, each with its depth and the path to
+    /// its node.
+    pub fn rows(&self) -> Vec<(usize, Vec<usize>, &HierarchyNode)> {
+        fn push<'a>(
+            rows: &mut Vec<(usize, Vec<usize>, &'a HierarchyNode)>,
+            nodes: &'a [HierarchyNode],
+            path: &[usize],
+        ) {
+            for (i, node) in nodes.iter().enumerate() {
+                let mut node_path = path.to_vec();
+                node_path.push(i);
+                rows.push((path.len(), node_path.clone(), node));
+                if node.expanded {
+                    if let Some(children) = node.children.as_ref() {
+                        push(rows, children, &node_path);
+                    }
+                }
+            }
+        }
+
+        let mut rows = Vec::new();
+        push(&mut rows, &self.roots, &[]);
+        rows
+    }
+
+    /// Where the node at `path` jumps to: the call it stands for, or the item
+    /// itself for a root or a type.
+    pub fn location(&self, path: &[usize]) -> Option<(PathBuf, Position)> {
+        let node = self.node(path)?;
+        let range = match node.from_ranges.first() {
+            Some(range) => range,
+            None => {
+                return Some((
+                    path_from_url(&node.item.uri),
+                    node.item.selection_range.start,
+                ))
+            }
+        };
+        // The calls are in the file of the caller, which is the parent for
+        // outgoing calls.
+        let uri = match self.kind {
+            HierarchyKind::OutgoingCalls => {
+                &self.node(&path[..path.len() - 1])?.item.uri
+            }
+            HierarchyKind::IncomingCalls
+            | HierarchyKind::Supertypes
+            | HierarchyKind::Subtypes => &node.item.uri,
+        };
+        Some((path_from_url(uri), range.start))
+    }
+
+    /// Fetches the children of the node at `path`, which come back with
+    /// `UpdateHierarchyChildren`.
+    pub fn fetch_children(
+        &self,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+        path: &[usize],
+    ) {
+        let (buffer_id, node) = match (self.buffer_id, self.node(path)) {
+            (Some(buffer_id), Some(node)) => (buffer_id, node),
+            _ => return,
+        };
+        let id = self.id;
+        let kind = self.kind;
+        let path = path.to_vec();
+        let tab_id = proxy.tab_id;
+        let f = Box::new(move |result: Result<Value, Value>| {
+            let children = match result {
+                Ok(res) => parse_children(kind, res),
+                // Still sent so the node doesn't wait for them forever.
+                Err(_) => Vec::new(),
+            };
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::UpdateHierarchyChildren(kind, id, path, children),
+                Target::Widget(tab_id),
+            );
+        });
+        match kind {
+            HierarchyKind::IncomingCalls | HierarchyKind::OutgoingCalls => {
+                proxy.get_call_hierarchy_calls(
+                    buffer_id,
+                    &node.item,
+                    kind == HierarchyKind::OutgoingCalls,
+                    f,
+                );
+            }
+            HierarchyKind::Supertypes | HierarchyKind::Subtypes => {
+                proxy.get_type_hierarchy_types(
+                    buffer_id,
+                    &node.item,
+                    kind == HierarchyKind::Supertypes,
+                    f,
+                );
+            }
+        }
+    }
+}
+
+/// The children in a response to the request for `kind`.
+fn parse_children(kind: HierarchyKind, res: Value) -> Vec<HierarchyChild> {
+    fn parse<T: DeserializeOwned + Into<HierarchyChild>>(
+        res: Value,
+    ) -> Vec<HierarchyChild> {
+        serde_json::from_value::<Option<Vec<T>>>(res)
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .map(T::into)
+            .collect()
+    }
+
+    match kind {
+        HierarchyKind::IncomingCalls => parse::<CallHierarchyIncomingCall>(res),
+        HierarchyKind::OutgoingCalls => parse::<CallHierarchyOutgoingCall>(res),
+        HierarchyKind::Supertypes | HierarchyKind::Subtypes => {
+            parse::<TypeHierarchyItem>(res)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{SymbolKind, Url};
+
+    use super::*;
+
+    fn item(name: &str, line: u32) -> CallHierarchyItem {
+        let range = Range::new(Position::new(line, 0), Position::new(line, 4));
+        CallHierarchyItem {
+            name: name.to_string(),
+            kind: SymbolKind::Function,
+            tags: None,
+            detail: None,
+            uri: Url::parse(&format!("file:///{name}.rs")).unwrap(),
+            range,
+            selection_range: range,
+            data: None,
+        }
+    }
+
+    fn call(name: &str, line: u32, call_line: u32) -> HierarchyChild {
+        HierarchyChild {
+            item: item(name, line),
+            from_ranges: vec![Range::new(
+                Position::new(call_line, 2),
+                Position::new(call_line, 6),
+            )],
+        }
+    }
+
+    fn names(data: &HierarchyData) -> Vec<(usize, String)> {
+        data.rows()
+            .into_iter()
+            .map(|(depth, _, node)| (depth, node.item.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_lazily() {
+        let mut data = HierarchyData::new(HierarchyKind::IncomingCalls);
+        data.set_roots(
+            BufferId(1),
+            HierarchyKind::IncomingCalls,
+            vec![item("a", 0)],
+        );
+        assert!(data.toggle(&[0]));
+        data.set_children(&[0], vec![call("b", 5, 7), call("c", 9, 10)]);
+        assert_eq!(
+            vec![
+                (0, "a".to_string()),
+                (1, "b".to_string()),
+                (1, "c".to_string())
+            ],
+            names(&data)
+        );
+
+        // Fetched calls are kept when collapsing and expanding again.
+        assert!(!data.toggle(&[0]));
+        assert_eq!(vec![(0, "a".to_string())], names(&data));
+        assert!(!data.toggle(&[0]));
+        assert!(data.toggle(&[0, 1]));
+        assert_eq!(vec![1, 1], data.rows()[2].1);
+    }
+
+    #[test]
+    fn test_recursive_calls_are_not_expanded() {
+        let mut data = HierarchyData::new(HierarchyKind::IncomingCalls);
+        data.set_roots(
+            BufferId(1),
+            HierarchyKind::IncomingCalls,
+            vec![item("a", 0)],
+        );
+        data.toggle(&[0]);
+        data.set_children(&[0], vec![call("b", 5, 7)]);
+        data.toggle(&[0, 0]);
+        data.set_children(&[0, 0], vec![call("a", 0, 2), call("b", 5, 6)]);
+
+        assert!(data.node(&[0, 0, 0]).unwrap().recursive);
+        assert!(data.node(&[0, 0, 1]).unwrap().recursive);
+        assert!(!data.toggle(&[0, 0, 0]));
+        assert_eq!(4, data.rows().len());
+    }
+
+    #[test]
+    fn test_location() {
+        let mut data = HierarchyData::new(HierarchyKind::IncomingCalls);
+        data.set_roots(
+            BufferId(1),
+            HierarchyKind::IncomingCalls,
+            vec![item("a", 3)],
+        );
+        data.toggle(&[0]);
+        data.set_children(&[0], vec![call("b", 5, 7)]);
+        assert_eq!(
+            Some((PathBuf::from("/a.rs"), Position::new(3, 0))),
+            data.location(&[0])
+        );
+        // An incoming call is made in the caller.
+        assert_eq!(
+            Some((PathBuf::from("/b.rs"), Position::new(7, 2))),
+            data.location(&[0, 0])
+        );
+
+        // An outgoing one in the item it's listed under.
+        data.kind = HierarchyKind::OutgoingCalls;
+        assert_eq!(
+            Some((PathBuf::from("/a.rs"), Position::new(7, 2))),
+            data.location(&[0, 0])
+        );
+    }
+
+    #[test]
+    fn test_parse_types() {
+        let res = serde_json::to_value(vec![item("b", 5), item("c", 9)]).unwrap();
+        for kind in [HierarchyKind::Supertypes, HierarchyKind::Subtypes] {
+            let children = parse_children(kind, res.clone());
+            assert_eq!(
+                vec![
+                    HierarchyChild::from(item("b", 5)),
+                    HierarchyChild::from(item("c", 9))
+                ],
+                children
+            );
+            assert!(children.iter().all(|child| child.from_ranges.is_empty()));
+            assert!(parse_children(kind, Value::Null).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_type_location() {
+        for kind in [HierarchyKind::Supertypes, HierarchyKind::Subtypes] {
+            let mut data = HierarchyData::new(kind);
+            data.set_roots(BufferId(1), kind, vec![item("a", 3)]);
+            data.toggle(&[0]);
+            let res = serde_json::to_value(vec![item("b", 5)]).unwrap();
+            data.set_children(&[0], parse_children(kind, res));
+
+            // Types jump to themselves, wherever they're listed.
+            assert_eq!(
+                Some((PathBuf::from("/a.rs"), Position::new(3, 0))),
+                data.location(&[0])
+            );
+            assert_eq!(
+                Some((PathBuf::from("/b.rs"), Position::new(5, 0))),
+                data.location(&[0, 0])
+            );
+        }
+    }
+}
@@ -1,22 +1,26 @@
-use std::{ops::Range, sync::Arc};
+use std::{ops::Range, path::Path, sync::Arc};
 
 use druid::{
     piet::TextStorage as PietTextStorage,
     text::{AttributesAdder, RichText, RichTextBuilder, TextStorage},
-    theme, ArcStr, Color, Data, Env, ExtEventSink, FontFamily, FontStyle,
+    theme, ArcStr, Color, Command, Data, Env, ExtEventSink, FontFamily, FontStyle,
     FontWeight, PaintCtx, Point, Rect, RenderContext, Size, Target, TextLayout,
     WidgetId,
 };
+use lapce_core::{language::LapceLanguage, syntax::Syntax};
 use lapce_rpc::buffer::BufferId;
 use lsp_types::{
-    Hover, HoverContents, MarkedString, MarkupContent, MarkupKind, Position,
+    DiagnosticSeverity, Hover, HoverContents, MarkedString, MarkupContent,
+    MarkupKind, Position, Url,
 };
-use pulldown_cmark::Tag;
+use pulldown_cmark::{CodeBlockKind, Tag};
+use xi_rope::Rope;
 
 use crate::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::{Config, LapceTheme},
-    data::LapceTabData,
+    data::{EditorDiagnostic, LapceTabData},
+    problem::diagnostic_copy_text,
     proxy::LapceProxy,
 };
 
@@ -47,6 +51,11 @@ pub struct HoverData {
     pub active_item_index: usize,
     /// The hover items that are currently loaded
     pub items: Arc<Vec<HoverItem>>,
+    /// The messages of the diagnostics at the hovered offset, kept as the
+    /// first item ahead of those the language server returns
+    pub diagnostics: Option<HoverItem>,
+    /// The diagnostics of `diagnostics` as they're copied to the clipboard
+    pub diagnostics_text: Option<String>,
 }
 
 impl HoverData {
@@ -63,6 +72,8 @@ impl HoverData {
 
             active_item_index: 0,
             items: Arc::new(Vec::new()),
+            diagnostics: None,
+            diagnostics_text: None,
         }
     }
 
@@ -102,9 +113,31 @@ impl HoverData {
 
         self.status = HoverStatus::Inactive;
         Arc::make_mut(&mut self.items).clear();
+        self.diagnostics = None;
+        self.diagnostics_text = None;
         self.active_item_index = 0;
     }
 
+    /// Show the messages of the diagnostics at the hovered offset, before
+    /// the language server has responded or if it has nothing to show.
+    pub fn set_diagnostics(
+        &mut self,
+        path: Option<&Path>,
+        diagnostics: &[&EditorDiagnostic],
+        style: &HoverTextStyle,
+    ) {
+        self.diagnostics = HoverItem::from_diagnostics(diagnostics, style);
+        self.diagnostics_text =
+            path.filter(|_| !diagnostics.is_empty()).map(|path| {
+                diagnostics
+                    .iter()
+                    .map(|d| diagnostic_copy_text(path, &d.diagnositc))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+        *Arc::make_mut(&mut self.items) = self.diagnostics.iter().cloned().collect();
+    }
+
     /// Send a request to update the hover at the given position anad file
     pub fn request(
         &self,
@@ -147,9 +180,8 @@ impl HoverData {
             return;
         }
 
-        let items = Arc::make_mut(&mut self.items);
         // Extract the items in the format that we want them to be in
-        *items = match resp.contents {
+        let received = match resp.contents {
             HoverContents::Scalar(text) => {
                 vec![HoverItem::from_marked_string(text, style)]
             }
@@ -161,6 +193,8 @@ impl HoverData {
                 vec![HoverItem::from_markup_content(content, style)]
             }
         };
+        *Arc::make_mut(&mut self.items) =
+            self.diagnostics.iter().cloned().chain(received).collect();
     }
 }
 
@@ -176,20 +210,23 @@ pub struct HoverTextStyle {
     base_font_size: f64,
     link_color: Color,
     blockquote_color: Color,
+    /// For the colors of the code in code blocks
+    config: Arc<Config>,
 }
 impl HoverTextStyle {
     /// Extract the needed data from [`LapceTabData`]
     pub fn from_data(data: &LapceTabData) -> Self {
+        Self::from_config(&data.config)
+    }
+
+    pub fn from_config(config: &Arc<Config>) -> Self {
         Self {
-            base_font_size: data.config.editor.font_size as f64,
-            link_color: data
-                .config
-                .get_color_unchecked(LapceTheme::EDITOR_LINK)
-                .clone(),
-            blockquote_color: data
-                .config
+            base_font_size: config.editor.font_size as f64,
+            link_color: config.get_color_unchecked(LapceTheme::EDITOR_LINK).clone(),
+            blockquote_color: config
                 .get_color_unchecked(LapceTheme::MARKDOWN_BLOCKQUOTE)
                 .clone(),
+            config: config.clone(),
         }
     }
 }
@@ -222,6 +259,56 @@ impl HoverItem {
         }
     }
 
+    /// The messages of the diagnostics, in the order given, as one item.
+    /// The locations each one refers to are listed under it as links that
+    /// jump to them.
+    fn from_diagnostics(
+        diagnostics: &[&EditorDiagnostic],
+        style: &HoverTextStyle,
+    ) -> Option<Self> {
+        if diagnostics.is_empty() {
+            return None;
+        }
+
+        let mut builder = RichTextBuilder::new();
+        for (i, d) in diagnostics.iter().enumerate() {
+            let diagnostic = &d.diagnositc;
+            let severity = match diagnostic.severity {
+                Some(DiagnosticSeverity::Error) => "error: ",
+                Some(DiagnosticSeverity::Warning) => "warning: ",
+                Some(DiagnosticSeverity::Information) => "info: ",
+                Some(DiagnosticSeverity::Hint) => "hint: ",
+                None => "",
+            };
+            let message = match diagnostic.source.as_ref() {
+                Some(source) => {
+                    format!("{}{} ({})", severity, diagnostic.message, source)
+                }
+                None => format!("{}{}", severity, diagnostic.message),
+            };
+            if i > 0 {
+                builder.push("\n");
+            }
+            builder.push(&message);
+            for (text, location) in d.related() {
+                builder.push("\n    ");
+                builder
+                    .push(&text)
+                    .underline(true)
+                    .text_color(style.link_color.clone())
+                    .link(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::JumpToLocation(None, location),
+                        Target::Auto,
+                    ));
+            }
+        }
+        Some(HoverItem::Markdown(MarkdownText::new(
+            builder.build(),
+            Vec::new(),
+        )))
+    }
+
     fn from_marked_string(text: MarkedString, style: &HoverTextStyle) -> Self {
         match text {
             MarkedString::String(text) => {
@@ -359,6 +446,9 @@ fn parse_markdown(text: &str, style: &HoverTextStyle) -> MarkdownText {
 
     let mut code_block_indices = Vec::new();
 
+    // The language and text of the code block being parsed, if in one
+    let mut code_block: Option<(Option<LapceLanguage>, String)> = None;
+
     // Construct the markdown parser. We enable most of the options in order to provide the most
     // compatibility that pulldown_cmark allows.
     let parser = Parser::new_ext(
@@ -372,6 +462,18 @@ fn parse_markdown(text: &str, style: &HoverTextStyle) -> MarkdownText {
     for event in parser {
         match event {
             Event::Start(tag) => {
+                if let Tag::CodeBlock(kind) = &tag {
+                    let language = match kind {
+                        // The fence can have more after the language, like
+                        // "rust,ignore"
+                        CodeBlockKind::Fenced(info) => info
+                            .split(|c: char| c == ',' || c.is_whitespace())
+                            .next()
+                            .and_then(LapceLanguage::from_name),
+                        CodeBlockKind::Indented => None,
+                    };
+                    code_block = Some((language, String::new()));
+                }
                 tag_stack.push((pos, tag));
             }
             Event::End(end_tag) => {
@@ -383,6 +485,15 @@ fn parse_markdown(text: &str, style: &HoverTextStyle) -> MarkdownText {
 
                     if let Tag::CodeBlock(_kind) = &tag {
                         code_block_indices.push(start_offset..pos);
+                        if let Some((Some(language), code)) = code_block.take() {
+                            highlight_code(
+                                &mut builder,
+                                start_offset,
+                                language,
+                                &code,
+                                style,
+                            );
+                        }
                     }
 
                     add_attribute_for_tag(
@@ -402,6 +513,9 @@ fn parse_markdown(text: &str, style: &HoverTextStyle) -> MarkdownText {
             Event::Text(text) => {
                 builder.push(&text);
                 pos += text.len();
+                if let Some((_, code)) = code_block.as_mut() {
+                    code.push_str(&text);
+                }
             }
             Event::Code(text) => {
                 builder.push(&text).font_family(FontFamily::MONOSPACE);
@@ -434,6 +548,35 @@ fn parse_markdown(text: &str, style: &HoverTextStyle) -> MarkdownText {
     MarkdownText::new(builder.build(), code_block_indices)
 }
 
+/// Colors the code of a block that starts at `start` in the builder by its
+/// syntax.
+fn highlight_code(
+    builder: &mut RichTextBuilder,
+    start: usize,
+    language: LapceLanguage,
+    code: &str,
+    style: &HoverTextStyle,
+) {
+    let syntax = Syntax::from_language(language).parse(0, Rope::from(code), None);
+    let styles = match syntax.styles {
+        Some(styles) => styles,
+        None => return,
+    };
+    for (interval, highlight) in styles.iter() {
+        let color = highlight
+            .fg_color
+            .as_ref()
+            .and_then(|name| style.config.get_style_color(name));
+        if let Some(color) = color {
+            builder
+                .add_attributes_for_range(
+                    start + interval.start..start + interval.end,
+                )
+                .text_color(color.clone());
+        }
+    }
+}
+
 fn add_attribute_for_tag(
     tag: &Tag,
     mut attrs: AttributesAdder,
@@ -441,6 +584,7 @@ fn add_attribute_for_tag(
         base_font_size,
         link_color,
         blockquote_color,
+        ..
     }: &HoverTextStyle,
 ) {
     use pulldown_cmark::HeadingLevel;
@@ -464,8 +608,6 @@ fn add_attribute_for_tag(
                 .style(FontStyle::Italic)
                 .text_color(blockquote_color.clone());
         }
-        // TODO: We could use the language paired with treesitter to highlight the code
-        // within code blocks.
         Tag::CodeBlock(_) => {
             attrs.font_family(FontFamily::MONOSPACE);
         }
@@ -476,9 +618,19 @@ fn add_attribute_for_tag(
             attrs.weight(FontWeight::BOLD);
         }
         // TODO: Strikethrough support
-        Tag::Link(_link_type, _target, _title) => {
-            // TODO: Link support
+        Tag::Link(_link_type, target, _title) => {
             attrs.underline(true).text_color(link_color.clone());
+            // Links to files open them
+            if let Some(path) = Url::parse(target)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+            {
+                attrs.link(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::OpenFile(path),
+                    Target::Auto,
+                ));
+            }
         }
         // All other tags are currently ignored
         _ => {}
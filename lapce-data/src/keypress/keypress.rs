@@ -1,7 +1,7 @@
 use std::fmt::Display;
 
 use druid::{
-    piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder},
+    piet::{PietText, PietTextLayout, Text, TextLayout, TextLayoutBuilder},
     FontFamily, Modifiers, PaintCtx, Point, Rect,
 };
 
@@ -34,6 +34,15 @@ impl Display for KeyPress {
     }
 }
 
+fn text_width(text: &mut PietText, s: &str) -> f64 {
+    text.new_text_layout(s.to_string())
+        .font(FontFamily::SYSTEM_UI, 13.0)
+        .build()
+        .unwrap()
+        .size()
+        .width
+}
+
 impl KeyPress {
     pub fn is_char(&self) -> bool {
         let mut mods = self.mods;
@@ -46,13 +55,8 @@ impl KeyPress {
         false
     }
 
-    pub fn paint(
-        &self,
-        ctx: &mut PaintCtx,
-        origin: Point,
-        config: &Config,
-    ) -> (Point, Vec<(Option<Rect>, PietTextLayout, Point)>) {
-        let mut origin = origin;
+    /// The names of the modifiers and the key, which are drawn joined by `+`
+    fn labels(&self) -> Vec<String> {
         let mut keys = Vec::new();
         if self.mods.ctrl() {
             keys.push("Ctrl".to_string());
@@ -85,6 +89,30 @@ impl KeyPress {
                 keys.push(self.key.to_string());
             }
         }
+        keys
+    }
+
+    /// How far `paint` advances the origin, without painting anything
+    pub fn width(&self, text: &mut PietText) -> f64 {
+        let keys = self.labels();
+        let mut width = 0.0;
+        for (i, key) in keys.iter().enumerate() {
+            width += text_width(text, key) + 15.0;
+            if i < keys.len() - 1 {
+                width += text_width(text, "+") + 5.0;
+            }
+        }
+        width
+    }
+
+    pub fn paint(
+        &self,
+        ctx: &mut PaintCtx,
+        origin: Point,
+        config: &Config,
+    ) -> (Point, Vec<(Option<Rect>, PietTextLayout, Point)>) {
+        let mut origin = origin;
+        let keys = self.labels();
 
         let mut items = Vec::new();
         let keys_len = keys.len();
@@ -5,7 +5,7 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::Result;
-use druid::piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder};
+use druid::piet::{PietText, PietTextLayout, Text, TextLayout, TextLayoutBuilder};
 use druid::{Command, KbKey};
 use druid::{
     Env, EventCtx, ExtEventSink, FontFamily, KeyEvent, Modifiers, PaintCtx, Point,
@@ -87,6 +87,15 @@ pub enum Alignment {
 }
 
 impl KeyMap {
+    /// How far `paint` advances the origin, which is the width it is shifted
+    /// left by when aligned to the right
+    pub fn width(&self, text: &mut PietText) -> f64 {
+        self.key
+            .iter()
+            .map(|keypress| keypress.width(text) + 10.0)
+            .sum()
+    }
+
     pub fn paint(
         &self,
         ctx: &mut PaintCtx,
@@ -125,6 +134,20 @@ impl KeyMap {
     }
 }
 
+/// The number of distinct key sequences bound in `keymaps` besides the
+/// first one, which is the one shown next to a command.
+pub fn alternative_keymaps(keymaps: &[KeyMap]) -> usize {
+    match keymaps.first() {
+        Some(first) => keymaps
+            .iter()
+            .map(|keymap| &keymap.key)
+            .filter(|key| *key != &first.key)
+            .unique()
+            .count(),
+        None => 0,
+    }
+}
+
 pub trait KeyPressFocus {
     fn get_mode(&self) -> Mode;
     fn check_condition(&self, condition: &str) -> bool;
@@ -729,7 +752,11 @@ impl<'a> Condition<'a> {
 
 #[cfg(test)]
 mod test {
-    use crate::keypress::{Condition, KeyPressData, KeyPressFocus};
+    use crate::keypress::{
+        alternative_keymaps, Condition, KeyMap, KeyPress, KeyPressData,
+        KeyPressFocus,
+    };
+    use crate::state::Modes;
 
     struct MockFocus {
         accepted_conditions: &'static [&'static str],
@@ -810,4 +837,30 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_alternative_keymaps() {
+        let keymap = |key: &str, modes: Modes| KeyMap {
+            key: KeyPress::parse(key),
+            modes,
+            when: None,
+            command: "open_keyboard_shortcuts".to_string(),
+        };
+
+        assert_eq!(0, alternative_keymaps(&[]));
+        assert_eq!(
+            0,
+            alternative_keymaps(&[keymap("ctrl+k ctrl+s", Modes::empty())])
+        );
+        // The same chord bound in another mode isn't an alternative.
+        assert_eq!(
+            1,
+            alternative_keymaps(&[
+                keymap("ctrl+k ctrl+s", Modes::empty()),
+                keymap("ctrl+k ctrl+s", Modes::NORMAL),
+                keymap("f1", Modes::empty()),
+                keymap("f1", Modes::NORMAL),
+            ])
+        );
+    }
 }
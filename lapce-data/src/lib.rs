@@ -1,13 +1,16 @@
 pub mod buffer;
+pub mod code_action;
 pub mod command;
 pub mod completion;
 pub mod config;
 pub mod container;
 pub mod data;
 pub mod db;
+pub mod document_symbol;
 pub mod editor;
 pub mod explorer;
 pub mod find;
+pub mod hierarchy;
 pub mod hover;
 pub mod keypress;
 pub mod lsp;
@@ -16,6 +19,7 @@ pub mod movement;
 pub mod outline;
 pub mod palette;
 pub mod panel;
+pub mod peek;
 pub mod picker;
 pub mod plugin;
 pub mod problem;
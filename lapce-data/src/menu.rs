@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use std::{collections::VecDeque, sync::Arc};
 
-use druid::{Command, Env, EventCtx, Modifiers, Point, Target, WidgetId};
+use druid::{Command, Env, EventCtx, Modifiers, Point, Size, Target, WidgetId};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 
 use crate::{
     command::{
@@ -11,19 +12,54 @@ use crate::{
     state::Mode,
 };
 
-#[derive(Debug)]
+/// How many of the last used menu items are kept
+pub const MAX_RECENT_MENU_ITEMS: usize = 5;
+
+#[derive(Clone, Debug)]
 pub struct MenuItem {
     pub text: String,
+    /// The char indices of `text` matched by the filter, which are bolded
+    pub indices: Vec<usize>,
     pub command: LapceCommandNew,
 }
 
-#[derive(Clone, Debug)]
+impl MenuItem {
+    fn same_command(&self, other: &MenuItem) -> bool {
+        self.text == other.text
+            && self.command.cmd == other.command.cmd
+            && self.command.data == other.command.data
+    }
+}
+
+/// Records `item` as the most recently used one, moving it to the front if
+/// it was already there and dropping the oldest past the limit.
+pub fn push_recent_menu_item(recent: &mut VecDeque<MenuItem>, item: &MenuItem) {
+    recent.retain(|i| !i.same_command(item));
+    recent.push_front(MenuItem {
+        indices: Vec::new(),
+        ..item.clone()
+    });
+    recent.truncate(MAX_RECENT_MENU_ITEMS);
+}
+
+#[derive(Clone)]
 pub struct MenuData {
     pub active: usize,
     pub widget_id: WidgetId,
     pub origin: Point,
     pub items: Arc<Vec<MenuItem>>,
+    /// The recently used items, which are shown in their own section above
+    /// `items` while no filter is typed
+    pub recent: Arc<Vec<MenuItem>>,
+    /// The text typed while the menu is open
+    pub filter: String,
+    /// The items matching `filter`, which are the ones shown
+    pub filtered_items: Arc<Vec<MenuItem>>,
+    pub matcher: Arc<SkimMatcherV2>,
     pub shown: bool,
+    /// Whether the menu asks a question, in which case the picked item isn't
+    /// remembered as recently used
+    pub prompt: bool,
 }
 
 impl KeyPressFocus for MenuData {
@@ -43,24 +79,33 @@ impl KeyPressFocus for MenuData {
         _mods: Modifiers,
         _env: &Env,
     ) -> CommandExecuted {
-        if let LapceCommand::ModalClose = command {
-            ctx.submit_command(Command::new(
-                LAPCE_UI_COMMAND,
-                LapceUICommand::HideMenu,
-                Target::Auto,
-            ));
-            ctx.submit_command(Command::new(
-                LAPCE_UI_COMMAND,
-                LapceUICommand::Focus,
-                Target::Auto,
-            ));
-            CommandExecuted::Yes
-        } else {
-            CommandExecuted::No
+        match command {
+            LapceCommand::ModalClose => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::HideMenu,
+                    Target::Auto,
+                ));
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::Focus,
+                    Target::Auto,
+                ));
+            }
+            LapceCommand::DeleteBackward => {
+                if self.filter.pop().is_some() {
+                    self.filter_items();
+                }
+            }
+            _ => return CommandExecuted::No,
         }
+        CommandExecuted::Yes
     }
 
-    fn receive_char(&mut self, _ctx: &mut EventCtx, _c: &str) {}
+    fn receive_char(&mut self, _ctx: &mut EventCtx, c: &str) {
+        self.filter.push_str(c);
+        self.filter_items();
+    }
 }
 
 impl MenuData {
@@ -69,9 +114,115 @@ impl MenuData {
             active: 0,
             widget_id: WidgetId::next(),
             items: Arc::new(Vec::new()),
+            recent: Arc::new(Vec::new()),
+            filter: String::new(),
+            filtered_items: Arc::new(Vec::new()),
+            matcher: Arc::new(SkimMatcherV2::default().ignore_case()),
             origin: Point::ZERO,
             shown: false,
+            prompt: false,
+        }
+    }
+
+    /// Shows `items`, with those of `recent` that are among them in the
+    /// recently used section, since the recent items are shared by every
+    /// menu.
+    pub fn show(
+        &mut self,
+        origin: Point,
+        items: Arc<Vec<MenuItem>>,
+        recent: Arc<Vec<MenuItem>>,
+    ) {
+        self.origin = origin;
+        self.recent = Arc::new(
+            recent
+                .iter()
+                .filter(|r| items.iter().any(|item| item.same_command(r)))
+                .cloned()
+                .collect(),
+        );
+        self.items = items;
+        self.filter.clear();
+        self.filter_items();
+        self.shown = true;
+        self.prompt = false;
+    }
+
+    /// Shows `items` as the answers to a question
+    pub fn show_prompt(&mut self, origin: Point, items: Arc<Vec<MenuItem>>) {
+        self.show(origin, items, Arc::new(Vec::new()));
+        self.prompt = true;
+    }
+
+    pub fn hide(&mut self) {
+        self.shown = false;
+        self.recent = Arc::new(Vec::new());
+        self.filter.clear();
+        self.filter_items();
+    }
+
+    /// The number of recently used items at the start of `filtered_items`
+    pub fn recent_len(&self) -> usize {
+        if self.filter.is_empty() {
+            self.recent.len()
+        } else {
+            0
+        }
+    }
+
+    /// The origin the menu is placed at so that it stays inside the window.
+    /// A menu that would overflow the bottom opens upwards from `origin`,
+    /// and one that would overflow the right is shifted left.
+    pub fn clamped_origin(&self, menu_size: Size, window_size: Size) -> Point {
+        let mut origin = self.origin;
+        if origin.y + menu_size.height > window_size.height {
+            origin.y -= menu_size.height;
+            if origin.y < 0.0 {
+                origin.y = (window_size.height - menu_size.height).max(0.0);
+            }
+        }
+        if origin.x + menu_size.width > window_size.width {
+            origin.x = (window_size.width - menu_size.width).max(0.0);
+        }
+        origin
+    }
+
+    /// Recompute the items matching the filter, best match first
+    pub fn filter_items(&mut self) {
+        self.active = 0;
+        if self.filter.is_empty() {
+            self.filtered_items = Arc::new(
+                self.recent
+                    .iter()
+                    .chain(self.items.iter())
+                    .map(|item| MenuItem {
+                        indices: Vec::new(),
+                        ..item.clone()
+                    })
+                    .collect(),
+            );
+            return;
         }
+
+        let mut items: Vec<(i64, MenuItem)> = self
+            .items
+            .iter()
+            .filter_map(|item| {
+                let (score, indices) =
+                    self.matcher.fuzzy_indices(&item.text, &self.filter)?;
+                Some((
+                    score,
+                    MenuItem {
+                        indices,
+                        ..item.clone()
+                    },
+                ))
+            })
+            .collect();
+        // The sort is stable, so equal scores keep the menu's own order.
+        items.sort_by(|a, b| b.0.cmp(&a.0));
+        self.filtered_items =
+            Arc::new(items.into_iter().map(|(_, item)| item).collect());
     }
 }
 
@@ -80,3 +231,133 @@ impl Default for MenuData {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{CommandTarget, LapceCommandNew};
+
+    use super::*;
+
+    fn item(text: &str) -> MenuItem {
+        MenuItem {
+            text: text.to_string(),
+            indices: Vec::new(),
+            command: LapceCommandNew {
+                cmd: text.to_string(),
+                data: None,
+                palette_desc: None,
+                target: CommandTarget::Focus,
+            },
+        }
+    }
+
+    #[test]
+    fn test_filter_items() {
+        let mut menu = MenuData::new();
+        menu.show(
+            Point::ZERO,
+            Arc::new(vec![item("Go to Definition"), item("Command Palette")]),
+            Arc::new(Vec::new()),
+        );
+        assert_eq!(2, menu.filtered_items.len());
+
+        menu.filter.push_str("cp");
+        menu.filter_items();
+        assert_eq!(1, menu.filtered_items.len());
+        assert_eq!("Command Palette", menu.filtered_items[0].text);
+        assert_eq!(vec![0, 8], menu.filtered_items[0].indices);
+
+        menu.hide();
+        assert!(menu.filter.is_empty());
+        assert_eq!(2, menu.filtered_items.len());
+    }
+
+    #[test]
+    fn test_clamped_origin() {
+        let window_size = Size::new(800.0, 600.0);
+        let menu_size = Size::new(300.0, 120.0);
+        let mut menu = MenuData::new();
+
+        menu.origin = Point::new(100.0, 100.0);
+        assert_eq!(
+            Point::new(100.0, 100.0),
+            menu.clamped_origin(menu_size, window_size)
+        );
+
+        // Flips up above the point it was opened at.
+        menu.origin = Point::new(100.0, 550.0);
+        assert_eq!(
+            Point::new(100.0, 430.0),
+            menu.clamped_origin(menu_size, window_size)
+        );
+
+        // Shifts left to end at the right edge of the window.
+        menu.origin = Point::new(700.0, 100.0);
+        assert_eq!(
+            Point::new(500.0, 100.0),
+            menu.clamped_origin(menu_size, window_size)
+        );
+
+        // Too tall to fit either way, so it's kept inside the window.
+        menu.origin = Point::new(0.0, 50.0);
+        assert_eq!(
+            Point::new(0.0, 0.0),
+            menu.clamped_origin(Size::new(300.0, 700.0), window_size)
+        );
+    }
+
+    #[test]
+    fn test_recent_items() {
+        let mut recent = VecDeque::new();
+        for i in 0..MAX_RECENT_MENU_ITEMS + 2 {
+            push_recent_menu_item(&mut recent, &item(&i.to_string()));
+        }
+        assert_eq!(MAX_RECENT_MENU_ITEMS, recent.len());
+        assert_eq!("6", recent[0].text);
+
+        // Using an item again moves it to the front instead of repeating it.
+        push_recent_menu_item(&mut recent, &item("4"));
+        assert_eq!(MAX_RECENT_MENU_ITEMS, recent.len());
+        assert_eq!(
+            vec!["4", "6", "5", "3", "2"],
+            recent.iter().map(|i| i.text.as_str()).collect::<Vec<_>>()
+        );
+
+        let mut menu = MenuData::new();
+        menu.show(
+            Point::ZERO,
+            Arc::new(vec![item("Go to Definition"), item("Command Palette")]),
+            Arc::new(vec![item("Command Palette")]),
+        );
+        assert_eq!(1, menu.recent_len());
+        assert_eq!(3, menu.filtered_items.len());
+        assert_eq!("Command Palette", menu.filtered_items[0].text);
+
+        // The section is hidden while filtering, so items aren't listed twice.
+        menu.filter.push_str("cp");
+        menu.filter_items();
+        assert_eq!(0, menu.recent_len());
+        assert_eq!(1, menu.filtered_items.len());
+
+        menu.hide();
+        assert_eq!(0, menu.recent_len());
+    }
+
+    #[test]
+    fn test_recent_items_of_other_menus() {
+        let mut menu = MenuData::new();
+        menu.show(
+            Point::ZERO,
+            Arc::new(vec![item("Go to Definition"), item("Command Palette")]),
+            Arc::new(vec![item("Reopen with UTF-16LE"), item("Command Palette")]),
+        );
+        assert_eq!(1, menu.recent_len());
+        assert_eq!(
+            vec!["Command Palette", "Go to Definition", "Command Palette"],
+            menu.filtered_items
+                .iter()
+                .map(|i| i.text.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -3,7 +3,6 @@ use xi_rope::{RopeDelta, Transformer};
 
 use crate::{
     buffer::data::BufferData,
-    config::Config,
     data::RegisterData,
     state::{Mode, VisualMode},
 };
@@ -69,6 +68,32 @@ impl Cursor {
         }
     }
 
+    /// The number of cursors, which is more than one when several regions are
+    /// selected in insert mode.
+    pub fn count(&self) -> usize {
+        match &self.mode {
+            CursorMode::Insert(selection) => selection.len(),
+            _ => 1,
+        }
+    }
+
+    /// The number of characters selected by the cursor.
+    pub fn selected_len(&self, buffer: &BufferData, tab_width: usize) -> usize {
+        if self.is_normal() {
+            return 0;
+        }
+        self.edit_selection(buffer, tab_width)
+            .regions()
+            .iter()
+            .map(|region| {
+                buffer
+                    .slice_to_cow(region.min()..region.max())
+                    .chars()
+                    .count()
+            })
+            .sum()
+    }
+
     pub fn current_line(&self, buffer: &BufferData) -> usize {
         buffer.line_of_offset(self.offset())
     }
@@ -210,7 +235,7 @@ impl Cursor {
         &self,
         buffer: &BufferData,
         char_width: f64,
-        config: &Config,
+        tab_width: usize,
     ) -> (f64, f64) {
         let offset = self.offset();
         let _line = buffer.line_of_offset(self.offset());
@@ -220,8 +245,8 @@ impl Cursor {
             buffer.offset_line_end(offset, true),
         );
 
-        let (_, x0) = buffer.offset_to_line_col(offset, config.editor.tab_width);
-        let (_, x1) = buffer.offset_to_line_col(next, config.editor.tab_width);
+        let (_, x0) = buffer.offset_to_line_col(offset, tab_width);
+        let (_, x1) = buffer.offset_to_line_col(next, tab_width);
         let x0 = x0 as f64 * char_width;
         let x1 = x1 as f64 * char_width;
         (x0, x1)
@@ -1,20 +1,116 @@
 use druid::WidgetId;
+use lsp_types::{DocumentSymbol, Position};
 
-pub struct OutlineState {
-    #[allow(dead_code)]
-    widget_id: WidgetId,
+#[derive(Clone)]
+pub struct OutlineData {
+    pub widget_id: WidgetId,
+    pub split_id: WidgetId,
+    pub symbols_widget_id: WidgetId,
 }
 
-impl OutlineState {
+impl OutlineData {
     pub fn new() -> Self {
         Self {
             widget_id: WidgetId::next(),
+            split_id: WidgetId::next(),
+            symbols_widget_id: WidgetId::next(),
         }
     }
 }
 
-impl Default for OutlineState {
+impl Default for OutlineData {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// The rows of the outline of a document, each symbol listed after its
+/// parent with its depth in the tree.
+pub fn outline_rows(symbols: &[DocumentSymbol]) -> Vec<(usize, &DocumentSymbol)> {
+    fn push<'a>(
+        rows: &mut Vec<(usize, &'a DocumentSymbol)>,
+        symbols: &'a [DocumentSymbol],
+        depth: usize,
+    ) {
+        for symbol in symbols {
+            rows.push((depth, symbol));
+            if let Some(children) = symbol.children.as_ref() {
+                push(rows, children, depth + 1);
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    push(&mut rows, symbols, 0);
+    rows
+}
+
+/// The row of the innermost symbol containing `position`.
+pub fn outline_row_at(
+    rows: &[(usize, &DocumentSymbol)],
+    position: Position,
+) -> Option<usize> {
+    // Siblings don't overlap, so the last row containing the position is the
+    // deepest one.
+    rows.iter().rposition(|(_, symbol)| {
+        symbol.range.start <= position && position <= symbol.range.end
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::DocumentSymbolResponse;
+
+    use super::*;
+    use crate::document_symbol::nested_symbols;
+
+    fn symbols() -> Vec<DocumentSymbol> {
+        let symbol = |name: &str, start: u32, end: u32, children| {
+            serde_json::json!({
+                "name": name,
+                "kind": 12,
+                "range": {
+                    "start": { "line": start, "character": 0 },
+                    "end": { "line": end, "character": 1 },
+                },
+                "selectionRange": {
+                    "start": { "line": start, "character": 0 },
+                    "end": { "line": start, "character": 1 },
+                },
+                "children": children,
+            })
+        };
+        let resp: DocumentSymbolResponse =
+            serde_json::from_value(serde_json::json!([
+                symbol(
+                    "a",
+                    0,
+                    10,
+                    serde_json::json!([
+                        symbol("b", 1, 3, serde_json::json!([])),
+                        symbol("c", 5, 9, serde_json::json!([])),
+                    ])
+                ),
+                symbol("d", 12, 14, serde_json::json!([])),
+            ]))
+            .unwrap();
+        nested_symbols(resp)
+    }
+
+    #[test]
+    fn test_outline_rows() {
+        let symbols = symbols();
+        let rows = outline_rows(&symbols);
+        assert_eq!(
+            vec![(0, "a"), (1, "b"), (1, "c"), (0, "d")],
+            rows.iter()
+                .map(|(depth, symbol)| (*depth, symbol.name.as_str()))
+                .collect::<Vec<(usize, &str)>>()
+        );
+
+        assert_eq!(Some(2), outline_row_at(&rows, Position::new(6, 0)));
+        assert_eq!(Some(0), outline_row_at(&rows, Position::new(4, 0)));
+        assert_eq!(Some(3), outline_row_at(&rows, Position::new(13, 0)));
+        assert_eq!(None, outline_row_at(&rows, Position::new(11, 0)));
+    }
+}
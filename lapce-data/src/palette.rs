@@ -6,26 +6,33 @@ use druid::{Data, Env, EventCtx};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use itertools::Itertools;
-use lsp_types::{DocumentSymbolResponse, Range, SymbolKind};
+use lapce_core::language::LapceLanguage;
+use lsp_types::{
+    DocumentSymbolResponse, Position, Range, SymbolInformation, SymbolKind,
+};
 use serde_json;
 use std::cmp::Ordering;
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use strum::IntoEnumIterator;
 use uuid::Uuid;
 
 use crate::{
-    buffer::BufferContent,
+    buffer::{BufferContent, LanguageOverride},
     command::LAPCE_UI_COMMAND,
     command::{CommandExecuted, LapceCommand, LAPCE_NEW_COMMAND},
-    command::{LapceCommandNew, LapceUICommand},
+    command::{
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+    },
     config::Config,
     data::{FocusArea, LapceMainSplitData, LapceTabData, PanelKind},
     editor::EditorLocationNew,
     find::Find,
     keypress::{KeyPressData, KeyPressFocus},
+    menu::MenuItem,
     movement::Movement,
-    proxy::LapceProxy,
+    proxy::{path_from_url, LapceProxy},
     state::LapceWorkspace,
     state::LapceWorkspaceType,
     state::Mode,
@@ -38,11 +45,19 @@ pub enum PaletteType {
     Line,
     GlobalSearch,
     DocumentSymbol,
+    /// Searches the symbols of the whole workspace with the language servers
+    WorkspaceSymbol,
     Workspace,
     Command,
     Reference,
     Theme,
+    /// Picks the language of the buffer in the active editor
+    Language,
     SshHost,
+    /// Picks an SSH host to open the current workspace path on instead
+    SwitchSshHost,
+    /// Asks for the new name of a symbol
+    Rename,
 }
 
 impl PaletteType {
@@ -51,12 +66,16 @@ impl PaletteType {
             PaletteType::File => "".to_string(),
             PaletteType::Line => "/".to_string(),
             PaletteType::DocumentSymbol => "@".to_string(),
+            PaletteType::WorkspaceSymbol => "#".to_string(),
             PaletteType::GlobalSearch => "?".to_string(),
             PaletteType::Workspace => ">".to_string(),
             PaletteType::Command => ":".to_string(),
             PaletteType::Reference => "".to_string(),
             PaletteType::Theme => "".to_string(),
+            PaletteType::Language => "".to_string(),
             PaletteType::SshHost => "".to_string(),
+            PaletteType::SwitchSshHost => "".to_string(),
+            PaletteType::Rename => "".to_string(),
         }
     }
 
@@ -65,6 +84,7 @@ impl PaletteType {
             self,
             PaletteType::Line
                 | PaletteType::DocumentSymbol
+                | PaletteType::WorkspaceSymbol
                 | PaletteType::GlobalSearch
                 | PaletteType::Reference
         )
@@ -96,11 +116,21 @@ pub enum PaletteItemContent {
         range: Range,
         container_name: Option<String>,
     },
+    WorkspaceSymbol {
+        kind: SymbolKind,
+        name: String,
+        container_name: Option<String>,
+        /// The path of the file relative to the workspace
+        path: PathBuf,
+        location: EditorLocationNew,
+    },
     ReferenceLocation(PathBuf, EditorLocationNew),
     Workspace(LapceWorkspace),
     SshHost(String, String),
     Command(LapceCommandNew),
     Theme(String),
+    /// A language to pick for the buffer, or `None` to detect it again
+    Language(Option<LanguageOverride>),
 }
 
 impl PaletteItemContent {
@@ -144,7 +174,8 @@ impl PaletteItemContent {
                     Target::Auto,
                 ));
             }
-            PaletteItemContent::ReferenceLocation(_rel_path, location) => {
+            PaletteItemContent::ReferenceLocation(_rel_path, location)
+            | PaletteItemContent::WorkspaceSymbol { location, .. } => {
                 let editor_id = if preview {
                     Some(preview_editor_id)
                 } else {
@@ -172,6 +203,15 @@ impl PaletteItemContent {
                     Target::Auto,
                 ));
             }
+            PaletteItemContent::Language(language) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::SetLanguage(*language),
+                        Target::Auto,
+                    ));
+                }
+            }
             PaletteItemContent::Command(command) => {
                 if !preview {
                     ctx.submit_command(Command::new(
@@ -275,6 +315,11 @@ pub struct PaletteData {
     pub items: Vec<NewPaletteItem>,
     pub filtered_items: Vec<NewPaletteItem>,
     pub preview_editor: WidgetId,
+    /// The file and position of the symbol being renamed
+    pub rename: Option<(PathBuf, Position)>,
+    /// The id of the latest workspace symbol search, the results of older
+    /// ones are dropped
+    pub workspace_symbol_request: usize,
 }
 
 impl KeyPressFocus for PaletteViewData {
@@ -327,6 +372,53 @@ impl KeyPressFocus for PaletteViewData {
 }
 
 impl PaletteData {
+    /// Adds more results of the workspace symbol search with `request_id`,
+    /// if it's still the latest one.
+    pub fn receive_workspace_symbols(
+        &mut self,
+        ctx: &mut EventCtx,
+        request_id: usize,
+        symbols: &[SymbolInformation],
+        workspace_path: Option<&Path>,
+    ) {
+        if self.palette_type != PaletteType::WorkspaceSymbol
+            || self.workspace_symbol_request != request_id
+            || symbols.is_empty()
+        {
+            return;
+        }
+        let was_empty = self.items.is_empty();
+        self.items.extend(symbols.iter().map(|s| {
+            let full_path = path_from_url(&s.location.uri);
+            let path = workspace_path
+                .and_then(|workspace_path| {
+                    full_path.strip_prefix(workspace_path).ok()
+                })
+                .unwrap_or(&full_path)
+                .to_path_buf();
+            NewPaletteItem {
+                content: PaletteItemContent::WorkspaceSymbol {
+                    kind: s.kind,
+                    name: s.name.clone(),
+                    container_name: s.container_name.clone(),
+                    path,
+                    location: EditorLocationNew {
+                        path: full_path.clone(),
+                        position: Some(s.location.range.start),
+                        scroll_offset: None,
+                        history: None,
+                    },
+                },
+                filter_text: s.name.clone(),
+                score: 0,
+                indices: Vec::new(),
+            }
+        }));
+        if was_empty {
+            self.preview(ctx);
+        }
+    }
+
     pub fn new(proxy: Arc<LapceProxy>) -> Self {
         let (sender, receiver) = unbounded();
         let widget_id = WidgetId::next();
@@ -347,6 +439,8 @@ impl PaletteData {
             items: Vec::new(),
             filtered_items: Vec::new(),
             preview_editor,
+            rename: None,
+            workspace_symbol_request: 0,
         }
     }
 
@@ -359,7 +453,10 @@ impl PaletteData {
     }
 
     pub fn current_items(&self) -> &Vec<NewPaletteItem> {
-        if self.get_input() == "" {
+        // Workspace symbols are filtered by the language servers already.
+        if self.get_input() == ""
+            || self.palette_type == PaletteType::WorkspaceSymbol
+        {
             &self.items
         } else {
             &self.filtered_items
@@ -385,9 +482,13 @@ impl PaletteData {
             PaletteType::File => &self.input,
             PaletteType::Reference => &self.input,
             PaletteType::Theme => &self.input,
+            PaletteType::Language => &self.input,
             PaletteType::SshHost => &self.input,
+            PaletteType::SwitchSshHost => &self.input,
+            PaletteType::Rename => &self.input,
             PaletteType::Line => &self.input[1..],
             PaletteType::DocumentSymbol => &self.input[1..],
+            PaletteType::WorkspaceSymbol => &self.input[1..],
             PaletteType::Workspace => &self.input[1..],
             PaletteType::Command => &self.input[1..],
             PaletteType::GlobalSearch => &self.input[1..],
@@ -405,6 +506,7 @@ impl PaletteViewData {
         palette.palette_type = PaletteType::File;
         palette.items.clear();
         palette.filtered_items.clear();
+        palette.rename = None;
         if ctx.is_focused() {
             ctx.resign_focus();
         }
@@ -441,6 +543,45 @@ impl PaletteViewData {
         palette.preview(ctx);
     }
 
+    /// Runs the command palette with only the given commands, to offer a
+    /// choice between them.
+    pub fn run_commands(
+        &mut self,
+        ctx: &mut EventCtx,
+        commands: &[LapceCommandNew],
+    ) {
+        self.run(ctx, Some(PaletteType::Command));
+        let items: Vec<NewPaletteItem> = commands
+            .iter()
+            .filter_map(|c| {
+                c.palette_desc.as_ref().map(|m| NewPaletteItem {
+                    content: PaletteItemContent::Command(c.clone()),
+                    filter_text: m.to_string(),
+                    score: 0,
+                    indices: vec![],
+                })
+            })
+            .collect();
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.items = items;
+    }
+
+    /// Asks for the new name of the symbol at `position` in `path`, starting
+    /// from `placeholder`.
+    pub fn run_rename(
+        &mut self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        position: Position,
+        placeholder: &str,
+    ) {
+        self.run(ctx, Some(PaletteType::Rename));
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.input = placeholder.to_string();
+        palette.cursor = palette.input.len();
+        palette.rename = Some((path.to_path_buf(), position));
+    }
+
     pub fn run(&mut self, ctx: &mut EventCtx, palette_type: Option<PaletteType>) {
         let palette = Arc::make_mut(&mut self.palette);
         palette.status = PaletteStatus::Started;
@@ -475,11 +616,14 @@ impl PaletteViewData {
             PaletteType::DocumentSymbol => {
                 self.get_document_symbols(ctx);
             }
+            PaletteType::WorkspaceSymbol => {
+                self.get_workspace_symbols(ctx);
+            }
             PaletteType::Workspace => {
                 self.get_workspaces(ctx);
             }
-            PaletteType::Reference => {}
-            PaletteType::SshHost => {
+            PaletteType::Reference | PaletteType::Rename => {}
+            PaletteType::SshHost | PaletteType::SwitchSshHost => {
                 self.get_ssh_hosts(ctx);
             }
             PaletteType::GlobalSearch => {
@@ -492,6 +636,9 @@ impl PaletteViewData {
                 let config = self.config.clone();
                 self.get_themes(ctx, &config);
             }
+            PaletteType::Language => {
+                self.get_languages(ctx);
+            }
         }
     }
 
@@ -516,9 +663,13 @@ impl PaletteViewData {
             PaletteType::File => 0,
             PaletteType::Reference => 0,
             PaletteType::Theme => 0,
+            PaletteType::Language => 0,
             PaletteType::SshHost => 0,
+            PaletteType::SwitchSshHost => 0,
+            PaletteType::Rename => 0,
             PaletteType::Line => 1,
             PaletteType::DocumentSymbol => 1,
+            PaletteType::WorkspaceSymbol => 1,
             PaletteType::Workspace => 1,
             PaletteType::Command => 1,
             PaletteType::GlobalSearch => 1,
@@ -560,6 +711,29 @@ impl PaletteViewData {
                 Target::Widget(*self.main_split.tab_id),
             ));
         }
+        if self.palette.palette_type == PaletteType::Rename {
+            let new_name = self.palette.get_input().trim().to_string();
+            if let Some((path, position)) = self.palette.rename.clone() {
+                if !new_name.is_empty() {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::Rename(path, position, new_name),
+                        Target::Widget(*self.main_split.tab_id),
+                    ));
+                }
+            }
+            self.cancel(ctx);
+            return;
+        }
+        if self.palette.palette_type == PaletteType::SwitchSshHost {
+            if let Some(PaletteItemContent::SshHost(user, host)) =
+                self.palette.get_item().map(|item| item.content.clone())
+            {
+                self.switch_ssh_host(ctx, user, host);
+                self.cancel(ctx);
+                return;
+            }
+        }
         let palette = Arc::make_mut(&mut self.palette);
         if let Some(item) = palette.get_item() {
             if let Some(palette_type) =
@@ -570,7 +744,9 @@ impl PaletteViewData {
                 self.cancel(ctx);
             }
         } else {
-            if self.palette.palette_type == PaletteType::SshHost {
+            if self.palette.palette_type == PaletteType::SshHost
+                || self.palette.palette_type == PaletteType::SwitchSshHost
+            {
                 let input = self.palette.get_input();
                 let splits = input.split('@').collect::<Vec<&str>>();
                 let mut splits = splits.iter().rev();
@@ -579,6 +755,11 @@ impl PaletteViewData {
                     .next()
                     .map(|s| s.to_string())
                     .unwrap_or_else(|| "root".to_string());
+                if self.palette.palette_type == PaletteType::SwitchSshHost {
+                    self.switch_ssh_host(ctx, user, host);
+                    self.cancel(ctx);
+                    return;
+                }
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
                     LapceUICommand::SetWorkspace(LapceWorkspace {
@@ -594,6 +775,77 @@ impl PaletteViewData {
         }
     }
 
+    /// Opens the current workspace path on another SSH host. If there are
+    /// unsaved changes, it asks whether to save them first, switch without
+    /// saving, or stay. Without a path the host is just connected to.
+    fn switch_ssh_host(&self, ctx: &mut EventCtx, user: String, host: String) {
+        let path = match self.workspace.path.clone() {
+            Some(path) => path,
+            None => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::SetWorkspace(LapceWorkspace {
+                        kind: LapceWorkspaceType::RemoteSSH(user, host),
+                        path: None,
+                        last_open: 0,
+                    }),
+                    Target::Auto,
+                ));
+                return;
+            }
+        };
+
+        let dirty = self
+            .main_split
+            .open_files
+            .values()
+            .filter(|buffer| buffer.dirty())
+            .count();
+        if dirty == 0 {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::OpenRemoteWorkspace { user, host, path },
+                Target::Auto,
+            ));
+            return;
+        }
+
+        let item = |text: String, cmd: LapceWorkbenchCommand| MenuItem {
+            text,
+            indices: Vec::new(),
+            command: LapceCommandNew {
+                cmd: cmd.to_string(),
+                data: Some(serde_json::json!([user, host])),
+                palette_desc: None,
+                target: CommandTarget::Workbench,
+            },
+        };
+        let save = if dirty == 1 {
+            "Save and Switch"
+        } else {
+            "Save All and Switch"
+        };
+        let items = vec![
+            item(
+                save.to_string(),
+                LapceWorkbenchCommand::SaveAllAndSwitchSshHost,
+            ),
+            item(
+                format!("Switch to {user}@{host} Without Saving"),
+                LapceWorkbenchCommand::SwitchSshHostWithoutSaving,
+            ),
+            item(
+                "Cancel".to_string(),
+                LapceWorkbenchCommand::CancelSwitchSshHost,
+            ),
+        ];
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowPrompt(Arc::new(items)),
+            Target::Auto,
+        ));
+    }
+
     fn update_palette(&mut self, ctx: &mut EventCtx) {
         let palette = Arc::make_mut(&mut self.palette);
         palette.index = 0;
@@ -602,6 +854,10 @@ impl PaletteViewData {
             self.run(ctx, Some(palette_type));
             return;
         }
+        if self.palette.palette_type == PaletteType::WorkspaceSymbol {
+            // The search is sent once the input stops changing.
+            return;
+        }
         if self.palette.get_input() != "" {
             let _ = self.palette.sender.send((
                 self.palette.run_id.clone(),
@@ -615,7 +871,10 @@ impl PaletteViewData {
 
     fn get_palette_type(&self) -> PaletteType {
         match self.palette.palette_type {
-            PaletteType::Reference | PaletteType::SshHost => {
+            PaletteType::Reference
+            | PaletteType::SshHost
+            | PaletteType::SwitchSshHost
+            | PaletteType::Rename => {
                 return self.palette.palette_type.clone();
             }
             _ => (),
@@ -626,6 +885,7 @@ impl PaletteViewData {
         match self.palette.input {
             _ if self.palette.input.starts_with('/') => PaletteType::Line,
             _ if self.palette.input.starts_with('@') => PaletteType::DocumentSymbol,
+            _ if self.palette.input.starts_with('#') => PaletteType::WorkspaceSymbol,
             _ if self.palette.input.starts_with('>') => PaletteType::Workspace,
             _ if self.palette.input.starts_with(':') => PaletteType::Command,
             _ => PaletteType::File,
@@ -745,6 +1005,28 @@ impl PaletteViewData {
             .collect();
     }
 
+    fn get_languages(&mut self, _ctx: &mut EventCtx) {
+        let palette = Arc::make_mut(&mut self.palette);
+        let languages = LapceLanguage::iter()
+            .sorted_by_key(|language| language.display_name().to_lowercase())
+            .map(LanguageOverride::Language);
+        palette.items = [None, Some(LanguageOverride::PlainText)]
+            .into_iter()
+            .chain(languages.map(Some))
+            .map(|language| {
+                let name = language
+                    .map(|language| language.display_name())
+                    .unwrap_or("Auto Detect");
+                NewPaletteItem {
+                    content: PaletteItemContent::Language(language),
+                    filter_text: name.to_string(),
+                    score: 0,
+                    indices: vec![],
+                }
+            })
+            .collect();
+    }
+
     fn get_commands(&mut self, _ctx: &mut EventCtx) {
         const EXCLUDED_ITEMS: &[&str] = &["palette.command"];
 
@@ -925,6 +1207,49 @@ impl PaletteViewData {
         }
     }
 
+    /// Searches the workspace symbols matching the input, cancelling the
+    /// search for the previous input. The results come in with
+    /// `UpdateWorkspaceSymbols`, in several batches from servers reporting
+    /// partial results.
+    pub fn get_workspace_symbols(&mut self, ctx: &mut EventCtx) {
+        let palette = Arc::make_mut(&mut self.palette);
+        if palette.workspace_symbol_request > 0 {
+            palette
+                .proxy
+                .cancel_workspace_symbols(palette.workspace_symbol_request);
+        }
+        palette.workspace_symbol_request += 1;
+        palette.items.clear();
+        palette.index = 0;
+
+        let request_id = palette.workspace_symbol_request;
+        let query = palette.get_input().to_string();
+        if query.is_empty() {
+            return;
+        }
+        let tab_id = palette.proxy.tab_id;
+        let event_sink = ctx.get_external_handle();
+        palette.proxy.get_workspace_symbols(
+            request_id,
+            query,
+            Box::new(move |result| {
+                if let Ok(res) = result {
+                    let symbols: Option<Vec<SymbolInformation>> =
+                        serde_json::from_value(res).ok().flatten();
+                    if let Some(symbols) = symbols {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateWorkspaceSymbols(
+                                request_id, symbols,
+                            ),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }
+            }),
+        );
+    }
+
     pub fn update_process(
         receiver: Receiver<(String, String, Vec<NewPaletteItem>)>,
         widget_id: WidgetId,
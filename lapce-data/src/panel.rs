@@ -1,10 +1,12 @@
+use serde::{Deserialize, Serialize};
+
 pub enum PanelResizePosition {
     Left,
     LeftSplit,
     Bottom,
 }
 
-#[derive(Eq, PartialEq, Hash, Clone)]
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Serialize, Deserialize)]
 pub enum PanelPosition {
     LeftTop,
     LeftBottom,
@@ -0,0 +1,40 @@
+use druid::{Size, WidgetId};
+
+/// The number of lines of the definition the peek shows at once.
+pub const PEEK_LINES: usize = 12;
+
+/// A definition shown in an editor below the line it was asked from,
+/// without leaving the file.
+#[derive(Clone)]
+pub struct PeekData {
+    /// The editor showing the definition
+    pub editor_view_id: WidgetId,
+    pub visible: bool,
+    /// The editor the peek was opened from
+    pub parent_view_id: WidgetId,
+    /// The offset in the parent editor the definition was asked at
+    pub offset: usize,
+}
+
+impl PeekData {
+    pub fn new() -> Self {
+        Self {
+            editor_view_id: WidgetId::next(),
+            visible: false,
+            parent_view_id: WidgetId::next(),
+            offset: 0,
+        }
+    }
+
+    /// The size of the peek for an editor of `editor_width`, with room for
+    /// its header above the lines.
+    pub fn size(&self, editor_width: f64, line_height: f64) -> Size {
+        Size::new(editor_width, (PEEK_LINES + 1) as f64 * line_height)
+    }
+}
+
+impl Default for PeekData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
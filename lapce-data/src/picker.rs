@@ -1,11 +1,23 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     path::{Path, PathBuf},
 };
 
 use druid::WidgetId;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use lapce_rpc::file::FileNodeItem;
 
+use crate::explorer::get_item_children;
+
+/// An entry of the picker's directory that matches the filter
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilePickerMatch {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// The indices of the matched characters in the file name
+    pub indices: Vec<usize>,
+}
+
 #[derive(Clone)]
 pub struct FilePickerData {
     pub widget_id: WidgetId,
@@ -15,6 +27,16 @@ pub struct FilePickerData {
     pub home: PathBuf,
     pub pwd: PathBuf,
     pub index: usize,
+    /// The file being saved under a new name, when the picker was opened to
+    /// choose a destination rather than a file or folder to open.
+    pub save_as: Option<PathBuf>,
+    /// What's typed after the directory in the path input, narrowing the
+    /// entries of `pwd` down to those matching it
+    pub filter: String,
+    /// The entries of `pwd` matching `filter`, best match first
+    pub filtered_items: Vec<FilePickerMatch>,
+    /// The files picked to be opened together, in the current directory
+    pub selected_paths: BTreeSet<PathBuf>,
 }
 
 impl FilePickerData {
@@ -37,9 +59,21 @@ impl FilePickerData {
             home,
             pwd,
             index: 0,
+            save_as: None,
+            filter: String::new(),
+            filtered_items: Vec::new(),
+            selected_paths: BTreeSet::new(),
         }
     }
 
+    /// Goes to another directory, leaving behind the filter and the files
+    /// picked in the previous one.
+    pub fn set_pwd(&mut self, pwd: PathBuf) {
+        self.pwd = pwd;
+        self.set_filter("");
+        self.selected_paths.clear();
+    }
+
     pub fn set_item_children(
         &mut self,
         path: &Path,
@@ -54,6 +88,81 @@ impl FilePickerData {
         for p in path.ancestors() {
             self.update_node_count(&PathBuf::from(p));
         }
+
+        if path == self.pwd {
+            self.filter_items();
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: &str) {
+        if self.filter == filter {
+            return;
+        }
+        self.filter = filter.to_string();
+        self.filter_items();
+        // Enter opens the top match, but saving goes to the typed name unless
+        // a file is picked explicitly.
+        self.index = if self.filter.is_empty() || self.save_as.is_some() {
+            0
+        } else {
+            1
+        };
+    }
+
+    fn filter_items(&mut self) {
+        if self.filter.is_empty() {
+            self.filtered_items.clear();
+            return;
+        }
+
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut items: Vec<(i64, FilePickerMatch)> = self
+            .get_file_node(&self.pwd)
+            .map(|node| node.sorted_children())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|item| {
+                let name = item.path_buf.file_name()?.to_str()?;
+                let (score, indices) = matcher.fuzzy_indices(name, &self.filter)?;
+                Some((
+                    score,
+                    FilePickerMatch {
+                        path: item.path_buf.clone(),
+                        is_dir: item.is_dir,
+                        indices,
+                    },
+                ))
+            })
+            .collect();
+        // The sort is stable, so equal scores keep folders first.
+        items.sort_by(|a, b| b.0.cmp(&a.0));
+        self.filtered_items = items.into_iter().map(|(_, item)| item).collect();
+    }
+
+    /// The entry at `index`, counting from 1, of the filtered list while there
+    /// is a filter and of the tree under `pwd` otherwise.
+    pub fn node_at(&self, index: usize) -> Option<&FileNodeItem> {
+        if !self.filter.is_empty() {
+            let item = self.filtered_items.get(index.checked_sub(1)?)?;
+            return self.get_file_node(&item.path);
+        }
+        get_item_children(0, index, self.get_file_node(&self.pwd)?).1
+    }
+
+    pub fn selected_node(&self) -> Option<&FileNodeItem> {
+        self.node_at(self.index)
+    }
+
+    /// Adds the file at `index` to the files to open together, or removes it
+    /// if it's already there. Folders can't be picked.
+    pub fn toggle_selected_path(&mut self, index: usize) {
+        let path = match self.node_at(index) {
+            Some(node) if !node.is_dir => node.path_buf.clone(),
+            _ => return,
+        };
+        if !self.selected_paths.remove(&path) {
+            self.selected_paths.insert(path);
+        }
     }
 
     pub fn init_home(&mut self, home: &Path) {
@@ -132,3 +241,119 @@ impl Default for FilePickerData {
         Self::new()
     }
 }
+
+/// Splits the text of the path input into the directory to list, up to the
+/// last separator, and the filter typed after it.
+pub fn split_picker_input(input: &str) -> (PathBuf, &str) {
+    match input.rfind(|c| c == '/' || c == std::path::MAIN_SEPARATOR) {
+        Some(i) => (PathBuf::from(&input[..i + 1]), &input[i + 1..]),
+        None => (PathBuf::from(input), ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(path: &str, is_dir: bool) -> (PathBuf, FileNodeItem) {
+        let item = FileNodeItem {
+            path_buf: PathBuf::from(path),
+            is_dir,
+            read: false,
+            open: false,
+            children: HashMap::new(),
+            children_open_count: 0,
+        };
+        (item.path_buf.clone(), item)
+    }
+
+    #[test]
+    fn test_split_picker_input() {
+        assert_eq!(
+            split_picker_input("/home/user/"),
+            (PathBuf::from("/home/user"), "")
+        );
+        assert_eq!(
+            split_picker_input("/home/user/Doc"),
+            (PathBuf::from("/home/user"), "Doc")
+        );
+        assert_eq!(split_picker_input("/"), (PathBuf::from("/"), ""));
+        assert_eq!(split_picker_input("home"), (PathBuf::from("home"), ""));
+    }
+
+    #[test]
+    fn test_picker_filter() {
+        let mut picker = FilePickerData::new();
+        let home = PathBuf::from("/home/user");
+        picker.init_home(&home);
+        picker.set_item_children(
+            &home,
+            [
+                node("/home/user/Documents", true),
+                node("/home/user/docs.md", false),
+                node("/home/user/notes.txt", false),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        picker.set_filter("doc");
+        let paths = picker
+            .filtered_items
+            .iter()
+            .map(|item| item.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/home/user/Documents"),
+                PathBuf::from("/home/user/docs.md"),
+            ]
+        );
+        assert_eq!(picker.filtered_items[0].indices, vec![0, 1, 2]);
+        assert_eq!(picker.index, 1);
+        assert_eq!(
+            picker.selected_node().map(|node| node.path_buf.clone()),
+            Some(PathBuf::from("/home/user/Documents"))
+        );
+
+        picker.set_filter("");
+        assert!(picker.filtered_items.is_empty());
+        assert_eq!(picker.index, 0);
+    }
+
+    #[test]
+    fn test_picker_selected_paths() {
+        let mut picker = FilePickerData::new();
+        let home = PathBuf::from("/home/user");
+        picker.init_home(&home);
+        picker.set_item_children(
+            &home,
+            [
+                node("/home/user/Documents", true),
+                node("/home/user/docs.md", false),
+                node("/home/user/notes.txt", false),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        // Folders come first, so the files are the second and third rows.
+        picker.toggle_selected_path(1);
+        assert!(picker.selected_paths.is_empty());
+        picker.toggle_selected_path(2);
+        picker.toggle_selected_path(3);
+        assert_eq!(
+            picker.selected_paths.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("/home/user/docs.md"),
+                PathBuf::from("/home/user/notes.txt"),
+            ]
+        );
+        picker.toggle_selected_path(2);
+        assert_eq!(picker.selected_paths.len(), 1);
+
+        picker.set_pwd(PathBuf::from("/home"));
+        assert!(picker.selected_paths.is_empty());
+    }
+}
@@ -1,14 +1,59 @@
+use std::{cmp::Ordering, collections::HashMap};
+
 use druid::WidgetId;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use lapce_rpc::plugin::PluginDescription;
 use strum_macros::Display;
 
+#[derive(Clone)]
 pub struct PluginData {
     pub widget_id: WidgetId,
+    pub split_id: WidgetId,
+    /// The latest versions of the installed plugins that are outdated, by
+    /// plugin name.
+    pub updates: HashMap<String, String>,
+    pub filter_view_id: WidgetId,
+    /// What the listed plugins are narrowed down to
+    pub filter: String,
+    /// Whether the filter searches the plugin registry instead of the
+    /// installed plugins
+    pub marketplace: bool,
+    /// The registry's plugins matching the filter, as found by the proxy
+    pub marketplace_results: Vec<PluginDescription>,
 }
 
 impl PluginData {
     pub fn new() -> Self {
         Self {
             widget_id: WidgetId::next(),
+            split_id: WidgetId::next(),
+            updates: HashMap::new(),
+            filter_view_id: WidgetId::next(),
+            filter: String::new(),
+            marketplace: false,
+            marketplace_results: Vec::new(),
+        }
+    }
+
+    /// The plugins listed in the panel, with the indices of the characters
+    /// in their display names that matched the filter. `registry` is listed
+    /// as it is when searching the marketplace without a filter.
+    pub fn visible_plugins<'a>(
+        &'a self,
+        installed: &'a HashMap<String, PluginDescription>,
+        registry: &'a [PluginDescription],
+    ) -> Vec<(&'a PluginDescription, Vec<usize>)> {
+        if self.marketplace {
+            if self.filter.is_empty() {
+                registry.iter().map(|plugin| (plugin, Vec::new())).collect()
+            } else {
+                filter_plugins(&self.filter, self.marketplace_results.iter())
+            }
+        } else {
+            let mut installed: Vec<&PluginDescription> =
+                installed.values().collect();
+            installed.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+            filter_plugins(&self.filter, installed.into_iter())
         }
     }
 }
@@ -23,5 +68,197 @@ impl Default for PluginData {
 pub enum PluginStatus {
     Installed,
     Install,
-    Upgrade,
+    Update,
+}
+
+/// Whether `latest` is a newer version than `current`. The dot separated
+/// numbers are compared with the missing ones as zeros, and a pre-release
+/// like `1.0.0-beta.2` comes before the version it leads up to.
+pub fn version_is_newer(latest: &str, current: &str) -> bool {
+    let (latest_numbers, latest_pre) = version_parts(latest);
+    let (current_numbers, current_pre) = version_parts(current);
+    let number = |numbers: &[u64], i: usize| numbers.get(i).copied().unwrap_or(0);
+    for i in 0..latest_numbers.len().max(current_numbers.len()) {
+        match number(&latest_numbers, i).cmp(&number(&current_numbers, i)) {
+            Ordering::Equal => {}
+            ordering => return ordering == Ordering::Greater,
+        }
+    }
+    match (latest_pre, current_pre) {
+        (None, Some(_)) => true,
+        (Some(latest), Some(current)) => {
+            pre_release_key(latest) > pre_release_key(current)
+        }
+        _ => false,
+    }
+}
+
+/// The numbers of `version` and its pre-release, leaving out a leading `v`
+/// and the build metadata.
+fn version_parts(version: &str) -> (Vec<u64>, Option<&str>) {
+    let version = version.trim_start_matches('v');
+    let version = version.split('+').next().unwrap_or(version);
+    let (numbers, pre) = match version.split_once('-') {
+        Some((numbers, pre)) => (numbers, Some(pre)),
+        None => (version, None),
+    };
+    let numbers = numbers
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    (numbers, pre)
+}
+
+/// Orders pre-releases by their dot separated identifiers, numbers by value
+/// and before words, which are ordered alphabetically.
+fn pre_release_key(pre: &str) -> Vec<(bool, u64, &str)> {
+    pre.split('.')
+        .map(|id| match id.parse::<u64>() {
+            Ok(n) => (false, n, ""),
+            Err(_) => (true, 0, id),
+        })
+        .collect()
+}
+
+/// Fuzzy matches the plugins' display names and descriptions against
+/// `filter`, best match first.
+fn filter_plugins<'a>(
+    filter: &str,
+    plugins: impl Iterator<Item = &'a PluginDescription>,
+) -> Vec<(&'a PluginDescription, Vec<usize>)> {
+    if filter.is_empty() {
+        return plugins.map(|plugin| (plugin, Vec::new())).collect();
+    }
+
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let mut matches: Vec<(i64, &PluginDescription, Vec<usize>)> = plugins
+        .filter_map(|plugin| {
+            let name = matcher.fuzzy_indices(&plugin.display_name, filter);
+            let description = matcher.fuzzy_match(&plugin.description, filter);
+            match (name, description) {
+                (Some((score, indices)), description) => {
+                    Some((score.max(description.unwrap_or(0)), plugin, indices))
+                }
+                (None, Some(score)) => Some((score, plugin, Vec::new())),
+                (None, None) => None,
+            }
+        })
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0));
+    matches
+        .into_iter()
+        .map(|(_, plugin, indices)| (plugin, indices))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(name: &str, description: &str) -> PluginDescription {
+        PluginDescription {
+            name: name.to_lowercase(),
+            version: "0.1.0".to_string(),
+            display_name: name.to_string(),
+            author: String::new(),
+            description: description.to_string(),
+            repository: String::new(),
+            wasm: String::new(),
+            dir: None,
+            configuration: None,
+        }
+    }
+
+    #[test]
+    fn test_filter_plugins() {
+        let plugins = vec![
+            plugin("Rust", "Rust language support"),
+            plugin("Go", "Go language support"),
+            plugin("Toml", "Syntax for configuration files"),
+        ];
+
+        let matches = filter_plugins("", plugins.iter());
+        assert_eq!(matches.len(), 3);
+
+        let matches = filter_plugins("rst", plugins.iter());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0.display_name, "Rust");
+        assert_eq!(matches[0].1, vec![0, 2, 3]);
+
+        // Matching the description doesn't highlight the name
+        let matches = filter_plugins("config", plugins.iter());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0.display_name, "Toml");
+        assert!(matches[0].1.is_empty());
+    }
+
+    #[test]
+    fn test_visible_plugins() {
+        let registry = vec![
+            plugin("Rust", "Rust language support"),
+            plugin("Go", "Go language support"),
+        ];
+        let local = plugin("Local", "Not in the registry");
+        let installed: HashMap<String, PluginDescription> =
+            [registry[0].clone(), local]
+                .into_iter()
+                .map(|plugin| (plugin.name.clone(), plugin))
+                .collect();
+        let names = |plugins: Vec<(&PluginDescription, Vec<usize>)>| {
+            plugins
+                .into_iter()
+                .map(|(plugin, _)| plugin.display_name.clone())
+                .collect::<Vec<String>>()
+        };
+
+        let mut data = PluginData::new();
+        assert_eq!(
+            names(data.visible_plugins(&installed, &registry)),
+            vec!["Local", "Rust"]
+        );
+
+        data.filter = "loc".to_string();
+        assert_eq!(
+            names(data.visible_plugins(&installed, &registry)),
+            vec!["Local"]
+        );
+
+        // The marketplace lists what the proxy found for the filter.
+        data.marketplace = true;
+        data.filter = "go".to_string();
+        data.marketplace_results = vec![registry[1].clone()];
+        assert_eq!(
+            names(data.visible_plugins(&installed, &registry)),
+            vec!["Go"]
+        );
+
+        data.filter.clear();
+        assert_eq!(
+            names(data.visible_plugins(&installed, &registry)),
+            vec!["Rust", "Go"]
+        );
+    }
+
+    #[test]
+    fn test_version_is_newer() {
+        assert!(version_is_newer("0.2.0", "0.1.9"));
+        assert!(version_is_newer("0.10.0", "0.9.0"));
+        assert!(version_is_newer("1.0.1", "1.0"));
+        assert!(version_is_newer("v1.1.0", "1.0.0"));
+        assert!(!version_is_newer("0.1.0", "0.1.0"));
+        assert!(!version_is_newer("0.1.0", "0.2.0"));
+        assert!(!version_is_newer("1.0.0", "1.0"));
+        assert!(!version_is_newer("1.0", "1.0.0"));
+    }
+
+    #[test]
+    fn test_pre_release_is_older() {
+        assert!(version_is_newer("1.0.0", "1.0.0-beta.2"));
+        assert!(!version_is_newer("1.0.0-beta.2", "1.0.0"));
+        assert!(version_is_newer("1.0.0-beta.11", "1.0.0-beta.2"));
+        assert!(version_is_newer("1.0.0-rc.1", "1.0.0-beta"));
+        assert!(version_is_newer("1.0.0-alpha.1", "1.0.0-alpha"));
+        assert!(version_is_newer("1.0.0-alpha", "0.9.0"));
+        assert!(!version_is_newer("1.0.0+build.2", "1.0.0+build.1"));
+    }
 }
@@ -1,10 +1,16 @@
+use std::path::Path;
+
 use druid::WidgetId;
+use lsp_types::{Diagnostic, NumberOrString};
 
+#[derive(Clone)]
 pub struct ProblemData {
     pub widget_id: WidgetId,
     pub split_id: WidgetId,
     pub error_widget_id: WidgetId,
     pub warning_widget_id: WidgetId,
+    /// Only diagnostics from this source, like "clippy", are listed
+    pub source_filter: Option<String>,
 }
 
 impl ProblemData {
@@ -14,6 +20,24 @@ impl ProblemData {
             split_id: WidgetId::next(),
             error_widget_id: WidgetId::next(),
             warning_widget_id: WidgetId::next(),
+            source_filter: None,
+        }
+    }
+
+    pub fn is_listed(&self, diagnostic: &Diagnostic) -> bool {
+        match self.source_filter.as_ref() {
+            Some(source) => diagnostic.source.as_ref() == Some(source),
+            None => true,
+        }
+    }
+
+    /// Lists only the diagnostics from `source`, or all of them again if
+    /// they're already filtered by it.
+    pub fn toggle_source_filter(&mut self, source: &str) {
+        if self.source_filter.as_deref() == Some(source) {
+            self.source_filter = None;
+        } else {
+            self.source_filter = Some(source.to_string());
         }
     }
 }
@@ -23,3 +47,100 @@ impl Default for ProblemData {
         Self::new()
     }
 }
+
+fn code_text(code: &NumberOrString) -> String {
+    match code {
+        NumberOrString::Number(n) => n.to_string(),
+        NumberOrString::String(s) => s.clone(),
+    }
+}
+
+/// Where a diagnostic comes from and its code, like "[clippy] unused_variables"
+pub fn diagnostic_source_label(diagnostic: &Diagnostic) -> Option<String> {
+    let code = diagnostic.code.as_ref().map(code_text);
+    match (diagnostic.source.as_ref(), code) {
+        (Some(source), Some(code)) => Some(format!("[{}] {}", source, code)),
+        (Some(source), None) => Some(format!("[{}]", source)),
+        (None, Some(code)) => Some(code),
+        (None, None) => None,
+    }
+}
+
+/// The diagnostic as `file:line:col: [source] message (code)`, with the line
+/// and column counted from 1, for pasting into a search or an issue.
+pub fn diagnostic_copy_text(path: &Path, diagnostic: &Diagnostic) -> String {
+    let start = diagnostic.range.start;
+    let mut text = format!(
+        "{}:{}:{}: ",
+        path.display(),
+        start.line + 1,
+        start.character + 1
+    );
+    if let Some(source) = diagnostic.source.as_ref() {
+        text.push_str(&format!("[{}] ", source));
+    }
+    text.push_str(diagnostic.message.trim_end());
+    if let Some(code) = diagnostic.code.as_ref() {
+        text.push_str(&format!(" ({})", code_text(code)));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range};
+
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_source_label() {
+        let mut diagnostic = Diagnostic::new_simple(
+            Range::new(Position::new(0, 0), Position::new(0, 1)),
+            "unused variable".to_string(),
+        );
+        assert_eq!(None, diagnostic_source_label(&diagnostic));
+
+        diagnostic.code = Some(NumberOrString::Number(308));
+        assert_eq!(
+            Some("308".to_string()),
+            diagnostic_source_label(&diagnostic)
+        );
+
+        diagnostic.source = Some("clippy".to_string());
+        diagnostic.code =
+            Some(NumberOrString::String("unused_variables".to_string()));
+        assert_eq!(
+            Some("[clippy] unused_variables".to_string()),
+            diagnostic_source_label(&diagnostic)
+        );
+
+        let mut problem = ProblemData::new();
+        assert!(problem.is_listed(&diagnostic));
+        problem.toggle_source_filter("rustc");
+        assert!(!problem.is_listed(&diagnostic));
+        problem.toggle_source_filter("clippy");
+        assert!(problem.is_listed(&diagnostic));
+        problem.toggle_source_filter("clippy");
+        assert_eq!(None, problem.source_filter);
+    }
+
+    #[test]
+    fn test_diagnostic_copy_text() {
+        let path = Path::new("/ws/src/main.rs");
+        let mut diagnostic = Diagnostic::new_simple(
+            Range::new(Position::new(4, 8), Position::new(4, 9)),
+            "mismatched types\nexpected `u32`, found `&str`\n".to_string(),
+        );
+        assert_eq!(
+            "/ws/src/main.rs:5:9: mismatched types\nexpected `u32`, found `&str`",
+            diagnostic_copy_text(path, &diagnostic)
+        );
+
+        diagnostic.source = Some("rustc".to_string());
+        diagnostic.code = Some(NumberOrString::String("E0308".to_string()));
+        assert_eq!(
+            "/ws/src/main.rs:5:9: [rustc] mismatched types\nexpected `u32`, found `&str` (E0308)",
+            diagnostic_copy_text(path, &diagnostic)
+        );
+    }
+}
@@ -4,7 +4,7 @@ use std::os::windows::process::CommandExt;
 use std::path::Path;
 use std::process::{Command, Stdio};
 use std::thread;
-use std::{path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use anyhow::{anyhow, Result};
 use crossbeam_channel::Receiver;
@@ -15,14 +15,20 @@ use flate2::read::GzDecoder;
 use lapce_proxy::dispatch::Dispatcher;
 use lapce_rpc::buffer::BufferId;
 use lapce_rpc::core::{CoreNotification, CoreRequest};
+use lapce_rpc::file::{FileOperation, SearchOptions};
 use lapce_rpc::plugin::PluginDescription;
+use lapce_rpc::proxy::TypeHierarchyItem;
 use lapce_rpc::source_control::FileDiff;
 use lapce_rpc::terminal::TermId;
 use lapce_rpc::RpcHandler;
 use lapce_rpc::{stdio_transport, Callback};
 use lapce_rpc::{ControlFlow, Handler};
+use lsp_types::CallHierarchyItem;
+use lsp_types::CodeAction;
 use lsp_types::CompletionItem;
+use lsp_types::FormattingOptions;
 use lsp_types::Position;
+use lsp_types::Range;
 use lsp_types::Url;
 use parking_lot::Mutex;
 use serde_json::json;
@@ -57,6 +63,9 @@ pub struct LapceProxy {
     pub tab_id: WidgetId,
     rpc: RpcHandler,
     proxy_receiver: Arc<Receiver<Value>>,
+    /// Stops the thread forwarding messages to the current connection when
+    /// it's dropped
+    forward_shutdown: Arc<Mutex<Option<Sender<()>>>>,
     term_tx: Sender<(TermId, TermEvent)>,
     event_sink: ExtEventSink,
 }
@@ -123,6 +132,16 @@ impl Handler for LapceProxy {
                     Target::Widget(self.tab_id),
                 );
             }
+            WorkspaceSymbolsProgress {
+                request_id,
+                symbols,
+            } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateWorkspaceSymbols(request_id, symbols),
+                    Target::Widget(self.tab_id),
+                );
+            }
             InstalledPlugins { plugins } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -130,6 +149,32 @@ impl Handler for LapceProxy {
                     Target::Widget(self.tab_id),
                 );
             }
+            OnTypeFormattingTriggers {
+                language_id,
+                characters,
+            } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateOnTypeFormattingTriggers(
+                        language_id,
+                        characters,
+                    ),
+                    Target::Widget(self.tab_id),
+                );
+            }
+            CompletionTriggers {
+                language_id,
+                characters,
+            } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateCompletionTriggers(
+                        language_id,
+                        characters,
+                    ),
+                    Target::Widget(self.tab_id),
+                );
+            }
             DiffInfo { diff } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -137,6 +182,13 @@ impl Handler for LapceProxy {
                     Target::Widget(self.tab_id),
                 );
             }
+            FileChanges { changes } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::FileChangeNotification(changes),
+                    Target::Widget(self.tab_id),
+                );
+            }
             UpdateTerminal { term_id, content } => {
                 let _ = self
                     .term_tx
@@ -187,11 +239,30 @@ impl LapceProxy {
             tab_id,
             rpc,
             proxy_receiver: Arc::new(proxy_receiver),
+            forward_shutdown: Arc::new(Mutex::new(None)),
             term_tx,
-            event_sink: event_sink.clone(),
+            event_sink,
         };
+        proxy.connect(workspace);
+        proxy
+    }
+
+    /// Starts a new connection to the proxy after the previous one was lost.
+    /// Requests still waiting for a response from the old connection fail,
+    /// so that answers to them are ignored, and the messages queued while
+    /// disconnected are dropped, as the buffers they're about are opened
+    /// again with their current content.
+    pub fn reconnect(&self, workspace: LapceWorkspace) {
+        self.forward_shutdown.lock().take();
+        for _ in self.proxy_receiver.try_iter() {}
+        self.rpc.clear_pending();
+        self.connect(workspace);
+    }
 
-        let local_proxy = proxy.clone();
+    fn connect(&self, workspace: LapceWorkspace) {
+        let tab_id = self.tab_id;
+        let event_sink = self.event_sink.clone();
+        let local_proxy = self.clone();
         thread::spawn(move || {
             let _ = event_sink.submit_command(
                 LAPCE_UI_COMMAND,
@@ -205,8 +276,29 @@ impl LapceProxy {
                 Target::Widget(tab_id),
             );
         });
+    }
 
-        proxy
+    /// Forwards the messages sent to the proxy to this connection's
+    /// `sender`, until the connection is lost or a newer one is started.
+    fn forward(&self, sender: Sender<Value>) {
+        let (shutdown_sender, shutdown_receiver) =
+            crossbeam_channel::bounded::<()>(0);
+        *self.forward_shutdown.lock() = Some(shutdown_sender);
+        let proxy_receiver = (*self.proxy_receiver).clone();
+        thread::spawn(move || loop {
+            crossbeam_channel::select! {
+                recv(shutdown_receiver) -> _ => return,
+                recv(proxy_receiver) -> msg => {
+                    let msg = match msg {
+                        Ok(msg) => msg,
+                        Err(_) => return,
+                    };
+                    if sender.send(msg).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
     }
 
     fn start(&self, workspace: LapceWorkspace) -> Result<()> {
@@ -214,16 +306,20 @@ impl LapceProxy {
             self.initialize(path.clone());
         }
         let (core_sender, core_receiver) = crossbeam_channel::unbounded();
+        let (writer_sender, writer_receiver) = crossbeam_channel::unbounded();
         match workspace.kind {
             LapceWorkspaceType::Local => {
-                let proxy_receiver = (*self.proxy_receiver).clone();
                 thread::spawn(move || {
                     let dispatcher = Dispatcher::new(core_sender);
-                    let _ = dispatcher.mainloop(proxy_receiver);
+                    let _ = dispatcher.mainloop(writer_receiver);
                 });
             }
             LapceWorkspaceType::RemoteSSH(user, host) => {
-                self.start_remote(SshRemote { user, host }, core_sender)?;
+                self.start_remote(
+                    SshRemote { user, host },
+                    writer_receiver,
+                    core_sender,
+                )?;
             }
             LapceWorkspaceType::RemoteWSL => {
                 let distro = WslDistro::all()?
@@ -231,13 +327,19 @@ impl LapceProxy {
                     .find(|distro| distro.default)
                     .ok_or_else(|| anyhow!("no default distro found"))?
                     .name;
-                self.start_remote(WslRemote { distro }, core_sender)?;
+                self.start_remote(
+                    WslRemote { distro },
+                    writer_receiver,
+                    core_sender,
+                )?;
             }
         }
+        self.forward(writer_sender);
 
         let mut proxy = self.clone();
         let mut handler = self.clone();
         proxy.rpc.mainloop(core_receiver, &mut handler);
+        self.forward_shutdown.lock().take();
 
         Ok(())
     }
@@ -245,6 +347,7 @@ impl LapceProxy {
     fn start_remote(
         &self,
         remote: impl Remote,
+        writer_receiver: Receiver<Value>,
         core_sender: Sender<Value>,
     ) -> Result<()> {
         let proxy_filename = format!("lapce-proxy-{VERSION}");
@@ -305,8 +408,7 @@ impl LapceProxy {
                 .ok_or_else(|| anyhow!("can't find stdout"))?,
         );
 
-        let proxy_receiver = (*self.proxy_receiver).clone();
-        stdio_transport(stdin, proxy_receiver, stdout, core_sender);
+        stdio_transport(stdin, writer_receiver, stdout, core_sender);
 
         Ok(())
     }
@@ -320,6 +422,59 @@ impl LapceProxy {
         )
     }
 
+    pub fn reopen_with_encoding(
+        &self,
+        buffer_id: BufferId,
+        encoding: &str,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "reopen_with_encoding",
+            &json!({
+                "buffer_id": buffer_id,
+                "encoding": encoding,
+            }),
+            f,
+        );
+    }
+
+    pub fn reopen_buffer(
+        &self,
+        buffer_id: BufferId,
+        path: PathBuf,
+        rev: u64,
+        content: String,
+    ) {
+        self.rpc.send_rpc_notification(
+            "reopen_buffer",
+            &json!({
+                "buffer_id": buffer_id,
+                "path": path,
+                "rev": rev,
+                "content": content,
+            }),
+        )
+    }
+
+    pub fn close_buffer(&self, buffer_id: BufferId) {
+        self.rpc.send_rpc_notification(
+            "close_buffer",
+            &json!({
+                "buffer_id": buffer_id,
+            }),
+        )
+    }
+
+    pub fn set_language(&self, buffer_id: BufferId, language_id: Option<&str>) {
+        self.rpc.send_rpc_notification(
+            "set_language",
+            &json!({
+                "buffer_id": buffer_id,
+                "language_id": language_id,
+            }),
+        )
+    }
+
     pub fn terminal_close(&self, term_id: TermId) {
         self.rpc.send_rpc_notification(
             "terminal_close",
@@ -383,6 +538,16 @@ impl LapceProxy {
             .send_rpc_notification("install_plugin", &json!({ "plugin": plugin }));
     }
 
+    pub fn disable_plugin(&self, name: &str) {
+        self.rpc
+            .send_rpc_notification("disable_plugin", &json!({ "name": name }));
+    }
+
+    pub fn enable_plugin(&self, name: &str) {
+        self.rpc
+            .send_rpc_notification("enable_plugin", &json!({ "name": name }));
+    }
+
     pub fn get_buffer_head(
         &self,
         buffer_id: BufferId,
@@ -396,10 +561,52 @@ impl LapceProxy {
         );
     }
 
-    pub fn global_search(&self, pattern: String, f: Box<dyn Callback>) {
+    pub fn global_search(
+        &self,
+        pattern: String,
+        options: SearchOptions,
+        context_lines: usize,
+        globs: Vec<String>,
+        f: Box<dyn Callback>,
+    ) {
         self.rpc.send_rpc_request_async(
             "global_search",
-            &json!({ "pattern": pattern }),
+            &json!({
+                "pattern": pattern,
+                "options": options,
+                "context_lines": context_lines,
+                "globs": globs,
+            }),
+            f,
+        );
+    }
+
+    pub fn search_plugins(&self, query: String, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "search_plugins",
+            &json!({
+                "query": query,
+            }),
+            f,
+        );
+    }
+
+    pub fn global_replace(
+        &self,
+        pattern: String,
+        replacement: String,
+        options: SearchOptions,
+        lines: HashMap<PathBuf, Vec<(usize, String)>>,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "global_replace",
+            &json!({
+                "pattern": pattern,
+                "replacement": replacement,
+                "options": options,
+                "lines": lines,
+            }),
             f,
         );
     }
@@ -439,6 +646,58 @@ impl LapceProxy {
         );
     }
 
+    pub fn save_as(
+        &self,
+        rev: u64,
+        buffer_id: BufferId,
+        path: PathBuf,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "save_as",
+            &json!({
+                "rev": rev,
+                "buffer_id": buffer_id,
+                "path": path,
+            }),
+            f,
+        );
+    }
+
+    pub fn save_elevated(
+        &self,
+        rev: u64,
+        buffer_id: BufferId,
+        helper: String,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "save_elevated",
+            &json!({
+                "rev": rev,
+                "buffer_id": buffer_id,
+                "helper": helper,
+            }),
+            f,
+        );
+    }
+
+    pub fn save_overwrite_permissions(
+        &self,
+        rev: u64,
+        buffer_id: BufferId,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "save_overwrite_permissions",
+            &json!({
+                "rev": rev,
+                "buffer_id": buffer_id,
+            }),
+            f,
+        );
+    }
+
     pub fn get_completion(
         &self,
         request_id: usize,
@@ -457,6 +716,30 @@ impl LapceProxy {
         );
     }
 
+    pub fn cancel_completion(&self, request_id: usize, buffer_id: BufferId) {
+        self.rpc.send_rpc_notification(
+            "cancel_completion",
+            &json!({
+                "request_id": request_id,
+                "buffer_id": buffer_id,
+            }),
+        )
+    }
+
+    pub fn execute_command(
+        &self,
+        buffer_id: BufferId,
+        command: &lsp_types::Command,
+    ) {
+        self.rpc.send_rpc_notification(
+            "execute_command",
+            &json!({
+                "buffer_id": buffer_id,
+                "command": command,
+            }),
+        )
+    }
+
     pub fn completion_resolve(
         &self,
         buffer_id: BufferId,
@@ -473,6 +756,22 @@ impl LapceProxy {
         );
     }
 
+    pub fn code_action_resolve(
+        &self,
+        buffer_id: BufferId,
+        action: CodeAction,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "code_action_resolve",
+            &json!({
+                "buffer_id": buffer_id,
+                "action": action,
+            }),
+            f,
+        );
+    }
+
     pub fn get_hover(
         &self,
         request_id: usize,
@@ -561,6 +860,38 @@ impl LapceProxy {
         );
     }
 
+    pub fn get_type_definition(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_type_definition",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_implementation(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_implementation",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
     pub fn get_document_symbols(&self, buffer_id: BufferId, f: Box<dyn Callback>) {
         self.rpc.send_rpc_request_async(
             "get_document_symbols",
@@ -571,6 +902,41 @@ impl LapceProxy {
         );
     }
 
+    pub fn get_workspace_symbols(
+        &self,
+        request_id: usize,
+        query: String,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_workspace_symbols",
+            &json!({
+                "request_id": request_id,
+                "query": query,
+            }),
+            f,
+        );
+    }
+
+    pub fn cancel_workspace_symbols(&self, request_id: usize) {
+        self.rpc.send_rpc_notification(
+            "cancel_workspace_symbols",
+            &json!({
+                "request_id": request_id,
+            }),
+        )
+    }
+
+    pub fn get_folding_ranges(&self, buffer_id: BufferId, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "get_folding_ranges",
+            &json!({
+                "buffer_id": buffer_id,
+            }),
+            f,
+        );
+    }
+
     pub fn get_code_actions(
         &self,
         buffer_id: BufferId,
@@ -590,12 +956,194 @@ impl LapceProxy {
     pub fn get_document_formatting(
         &self,
         buffer_id: BufferId,
+        options: FormattingOptions,
         f: Box<dyn Callback>,
     ) {
         self.rpc.send_rpc_request_async(
             "get_document_formatting",
             &json!({
                 "buffer_id": buffer_id,
+                "options": options,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_document_range_formatting(
+        &self,
+        buffer_id: BufferId,
+        range: Range,
+        options: FormattingOptions,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_document_range_formatting",
+            &json!({
+                "buffer_id": buffer_id,
+                "range": range,
+                "options": options,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_on_type_formatting(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        ch: String,
+        options: FormattingOptions,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_on_type_formatting",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+                "ch": ch,
+                "options": options,
+            }),
+            f,
+        );
+    }
+
+    pub fn prepare_rename(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "prepare_rename",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    pub fn prepare_call_hierarchy(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "prepare_call_hierarchy",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    /// The calls made to `item`, or the ones it makes when `outgoing`.
+    pub fn get_call_hierarchy_calls(
+        &self,
+        buffer_id: BufferId,
+        item: &CallHierarchyItem,
+        outgoing: bool,
+        f: Box<dyn Callback>,
+    ) {
+        let method = if outgoing {
+            "call_hierarchy_outgoing_calls"
+        } else {
+            "call_hierarchy_incoming_calls"
+        };
+        self.rpc.send_rpc_request_async(
+            method,
+            &json!({
+                "buffer_id": buffer_id,
+                "item": item,
+            }),
+            f,
+        );
+    }
+
+    pub fn prepare_type_hierarchy(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "prepare_type_hierarchy",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    /// The supertypes of `item`, or its subtypes when not `supertypes`.
+    pub fn get_type_hierarchy_types(
+        &self,
+        buffer_id: BufferId,
+        item: &TypeHierarchyItem,
+        supertypes: bool,
+        f: Box<dyn Callback>,
+    ) {
+        let method = if supertypes {
+            "type_hierarchy_supertypes"
+        } else {
+            "type_hierarchy_subtypes"
+        };
+        self.rpc.send_rpc_request_async(
+            method,
+            &json!({
+                "buffer_id": buffer_id,
+                "item": item,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_document_highlights(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_document_highlights",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    pub fn rename(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        new_name: String,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "rename",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+                "new_name": new_name,
+            }),
+            f,
+        );
+    }
+
+    pub fn apply_file_operation(
+        &self,
+        operation: FileOperation,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "apply_file_operation",
+            &json!({
+                "operation": operation,
             }),
             f,
         );
@@ -610,6 +1158,35 @@ impl LapceProxy {
     }
 }
 
+#[cfg(test)]
+impl LapceProxy {
+    /// A proxy that never connects, keeping the messages sent to it for
+    /// tests to look at with `sent_methods`.
+    pub fn new_disconnected(tab_id: WidgetId, event_sink: ExtEventSink) -> Self {
+        let (proxy_sender, proxy_receiver) = crossbeam_channel::unbounded();
+        let (term_tx, _) = crossbeam_channel::unbounded();
+        Self {
+            tab_id,
+            rpc: RpcHandler::new(proxy_sender),
+            proxy_receiver: Arc::new(proxy_receiver),
+            forward_shutdown: Arc::new(Mutex::new(None)),
+            term_tx,
+            event_sink,
+        }
+    }
+
+    /// The methods of the messages sent since the last call, waiting up to
+    /// `timeout` for the first of them.
+    pub fn sent_methods(&self, timeout: std::time::Duration) -> Vec<String> {
+        self.proxy_receiver
+            .recv_timeout(timeout)
+            .into_iter()
+            .chain(self.proxy_receiver.try_iter())
+            .filter_map(|msg| Some(msg.get("method")?.as_str()?.to_string()))
+            .collect()
+    }
+}
+
 fn new_command(program: &str) -> Command {
     #[allow(unused_mut)]
     let mut cmd = Command::new(program);
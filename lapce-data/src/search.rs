@@ -1,15 +1,52 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use druid::WidgetId;
+use lapce_proxy::dispatch::global_search_regex;
+use lapce_rpc::file::SearchOptions;
+use regex::{NoExpand, Regex};
 
 pub type Match = (usize, (usize, usize), String);
+/// A line shown around the matches, with its 1-based number
+pub type ContextLine = (usize, String);
+
+/// A row of a file's results in the search panel
+#[derive(Debug, PartialEq)]
+pub enum SearchRow<'a> {
+    Match(&'a Match),
+    Context(&'a ContextLine),
+    /// Separates hunks of lines that aren't next to each other
+    Gap,
+}
+
+/// How many recently used globs the search remembers
+const MAX_RECENT_GLOBS: usize = 10;
+
 #[derive(Clone)]
 pub struct SearchData {
     pub active: WidgetId,
     pub widget_id: WidgetId,
     pub split_id: WidgetId,
     pub editor_view_id: WidgetId,
+    pub replace_view_id: WidgetId,
     pub matches: Arc<HashMap<PathBuf, Vec<Match>>>,
+    pub context: Arc<HashMap<PathBuf, Vec<ContextLine>>>,
+    /// What the matches are replaced with, previewed in the results until
+    /// they're replaced for real
+    pub replace: String,
+    pub options: SearchOptions,
+    pub globs_view_id: WidgetId,
+    /// Comma separated globs the search is limited to, where the ones
+    /// starting with `!` exclude what they match
+    pub globs: String,
+    /// The globs searched with recently, latest first
+    pub recent_globs: Vec<String>,
+    /// Which of the recent globs was last recalled into the glob input
+    pub recent_globs_index: Option<usize>,
 }
 
 impl SearchData {
@@ -20,8 +57,96 @@ impl SearchData {
             widget_id: WidgetId::next(),
             split_id: WidgetId::next(),
             editor_view_id,
+            replace_view_id: WidgetId::next(),
             matches: Arc::new(HashMap::new()),
+            context: Arc::new(HashMap::new()),
+            replace: String::new(),
+            options: SearchOptions::default(),
+            globs_view_id: WidgetId::next(),
+            globs: String::new(),
+            recent_globs: Vec::new(),
+            recent_globs_index: None,
+        }
+    }
+
+    /// The globs to send with a search request
+    pub fn glob_list(&self) -> Vec<String> {
+        self.globs
+            .split(',')
+            .map(|glob| glob.trim())
+            .filter(|glob| !glob.is_empty())
+            .map(|glob| glob.to_string())
+            .collect()
+    }
+
+    /// Puts the current globs first in the recent ones. Returns false if
+    /// they're empty or already first.
+    pub fn remember_globs(&mut self) -> bool {
+        let globs = self.globs.trim();
+        if globs.is_empty()
+            || self.recent_globs.first().map(|g| g.as_str()) == Some(globs)
+        {
+            return false;
+        }
+        let globs = globs.to_string();
+        self.recent_globs.retain(|g| g != &globs);
+        self.recent_globs.insert(0, globs);
+        self.recent_globs.truncate(MAX_RECENT_GLOBS);
+        true
+    }
+
+    /// The rows of the results for `path`: with context, the matches and the
+    /// lines around them in order, split into hunks where lines are skipped.
+    pub fn rows(&self, path: &Path) -> Vec<SearchRow> {
+        let matches = self.matches.get(path).map(|m| &m[..]).unwrap_or(&[]);
+        let context = self.context.get(path).map(|c| &c[..]).unwrap_or(&[]);
+        search_rows(matches, context)
+    }
+}
+
+fn search_rows<'a>(
+    matches: &'a [Match],
+    context: &'a [ContextLine],
+) -> Vec<SearchRow<'a>> {
+    if context.is_empty() {
+        return matches.iter().map(SearchRow::Match).collect();
+    }
+
+    let mut lines: Vec<(usize, SearchRow)> = matches
+        .iter()
+        .map(|m| (m.0, SearchRow::Match(m)))
+        .chain(context.iter().map(|c| (c.0, SearchRow::Context(c))))
+        .collect();
+    lines.sort_by_key(|(line_number, _)| *line_number);
+
+    let mut rows = Vec::new();
+    let mut last = None;
+    for (line_number, row) in lines {
+        if let Some(last) = last {
+            if line_number > last + 1 {
+                rows.push(SearchRow::Gap);
+            }
         }
+        last = Some(line_number);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Replaces every occurrence of `pattern` in `line`, matched the way the
+/// global search matches it with `options`. In regex mode the replacement
+/// can refer to capture groups.
+pub fn replace_in_line<'a>(
+    line: &'a str,
+    pattern: &str,
+    replacement: &str,
+    options: SearchOptions,
+) -> Cow<'a, str> {
+    let regex = format!("(?i){}", global_search_regex(pattern, options));
+    match Regex::new(&regex) {
+        Ok(matcher) if options.regex => matcher.replace_all(line, replacement),
+        Ok(matcher) => matcher.replace_all(line, NoExpand(replacement)),
+        Err(_) => Cow::Borrowed(line),
     }
 }
 
@@ -30,3 +155,87 @@ impl Default for SearchData {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_list() {
+        let mut search = SearchData::new();
+        search.globs = " src/**, !**/tests/** ,,".to_string();
+        assert_eq!(search.glob_list(), vec!["src/**", "!**/tests/**"]);
+    }
+
+    #[test]
+    fn test_remember_globs() {
+        let mut search = SearchData::new();
+        assert!(!search.remember_globs());
+        for globs in ["src/**", "docs/**", "src/**"] {
+            search.globs = globs.to_string();
+            assert!(search.remember_globs());
+        }
+        assert!(!search.remember_globs());
+        assert_eq!(search.recent_globs, vec!["src/**", "docs/**"]);
+    }
+
+    #[test]
+    fn test_search_rows() {
+        let matches = vec![
+            (2, (0, 3), "foo\n".to_string()),
+            (4, (0, 3), "foo\n".to_string()),
+            (9, (0, 3), "foo\n".to_string()),
+        ];
+        // Without context every match is a row of its own.
+        assert_eq!(
+            search_rows(&matches, &[]),
+            matches.iter().map(SearchRow::Match).collect::<Vec<_>>()
+        );
+
+        let context = vec![
+            (1, "a\n".to_string()),
+            (3, "b\n".to_string()),
+            (5, "c\n".to_string()),
+            (8, "d\n".to_string()),
+        ];
+        assert_eq!(
+            search_rows(&matches, &context),
+            vec![
+                SearchRow::Context(&context[0]),
+                SearchRow::Match(&matches[0]),
+                SearchRow::Context(&context[1]),
+                SearchRow::Match(&matches[1]),
+                SearchRow::Context(&context[2]),
+                SearchRow::Gap,
+                SearchRow::Context(&context[3]),
+                SearchRow::Match(&matches[2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_in_line() {
+        let literal = SearchOptions::default();
+        assert_eq!(
+            replace_in_line("let foo = Foo::new(FOO);\n", "foo", "bar", literal),
+            "let bar = bar::new(bar);\n"
+        );
+        // The replacement is taken as is, and the pattern isn't a regex.
+        assert_eq!(replace_in_line("a.b ab", "a.b", "$1", literal), "$1 ab");
+
+        let regex = SearchOptions {
+            regex: true,
+            whole_word: false,
+        };
+        assert_eq!(replace_in_line("a.b ab", "a(.)b", "${1}x", regex), ".x ab");
+
+        let whole_word = SearchOptions {
+            regex: false,
+            whole_word: true,
+        };
+        assert_eq!(
+            replace_in_line("foo foobar", "foo", "bar", whole_word),
+            "bar foobar"
+        );
+    }
+}
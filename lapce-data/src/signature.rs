@@ -1,65 +1,262 @@
-use lsp_types::ParameterLabel;
-use lsp_types::SignatureHelp;
+use std::sync::Arc;
+
+use druid::{ExtEventSink, Target, WidgetId};
+use lapce_rpc::buffer::BufferId;
+use lsp_types::{ParameterLabel, Position, SignatureHelp, SignatureInformation};
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    proxy::LapceProxy,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Inactive,
+    Started,
+}
 
 #[derive(Clone)]
-pub struct SignatureState {
-    pub offset: Option<usize>,
-    pub signature: Option<SignatureHelp>,
-    pub active: Option<(usize, usize)>,
+pub struct SignatureData {
+    pub id: WidgetId,
+    /// The current request status
+    pub status: SignatureStatus,
+    /// The offset the signature was last requested at
+    pub offset: usize,
+    /// The buffer that this signature is for
+    pub buffer_id: BufferId,
+    /// A counter to keep track of the active requests
+    pub request_id: usize,
+    /// The signature of the call the cursor is in
+    pub signature: Option<Arc<SignatureInformation>>,
+    /// The index of the parameter the cursor is on
+    pub active_parameter: Option<usize>,
 }
 
-impl SignatureState {
+impl SignatureData {
     pub fn new() -> Self {
         Self {
-            offset: None,
+            id: WidgetId::next(),
+            status: SignatureStatus::Inactive,
+            offset: 0,
+            buffer_id: BufferId(0),
+            request_id: 0,
             signature: None,
-            active: None,
+            active_parameter: None,
         }
     }
 
-    pub fn update(&mut self, offset: usize, commas: Vec<usize>) -> Option<bool> {
-        let signature = self.signature.as_ref()?;
-        let params = signature.signatures[0].parameters.as_ref()?;
-        if params.is_empty() {
-            return None;
-        }
-        let mut index = commas.len();
-        for (i, c) in commas.iter().enumerate() {
-            if offset <= *c {
-                index = i;
-                break;
-            }
+    pub fn is_empty(&self) -> bool {
+        self.signature.is_none()
+    }
+
+    /// The label of the signature, such as `fn foo(a: usize, b: &str)`
+    pub fn label(&self) -> Option<&str> {
+        self.signature
+            .as_ref()
+            .map(|signature| signature.label.as_str())
+    }
+
+    /// Cancel the current signature, clearing out held data
+    pub fn cancel(&mut self) {
+        if self.status == SignatureStatus::Inactive {
+            return;
         }
 
-        let label = signature.signatures[0].label.clone();
-        let active = if index >= params.len() {
-            None
-        } else {
-            match &params[index].label {
-                ParameterLabel::Simple(s) => {
-                    let start = label.find(s)?;
-                    Some((start, start + s.len()))
-                }
-                ParameterLabel::LabelOffsets(offsets) => {
-                    Some((offsets[0] as usize, offsets[1] as usize))
+        self.status = SignatureStatus::Inactive;
+        self.signature = None;
+        self.active_parameter = None;
+    }
+
+    /// Send a request for the signature of the call at the given position
+    pub fn request(
+        &self,
+        proxy: Arc<LapceProxy>,
+        request_id: usize,
+        buffer_id: BufferId,
+        position: Position,
+        signature_widget_id: WidgetId,
+        event_sink: ExtEventSink,
+    ) {
+        proxy.get_signature(
+            buffer_id,
+            position,
+            Box::new(move |result| {
+                if let Ok(resp) = result {
+                    // Servers answer with null outside of a call.
+                    let resp = serde_json::from_value::<Option<SignatureHelp>>(resp)
+                        .ok()
+                        .flatten()
+                        .unwrap_or(SignatureHelp {
+                            signatures: Vec::new(),
+                            active_signature: None,
+                            active_parameter: None,
+                        });
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdateSignature(request_id, resp),
+                        Target::Widget(signature_widget_id),
+                    );
                 }
-            }
-        };
+            }),
+        );
+    }
 
-        let changed = self.active != active;
-        self.active = active;
-        Some(changed)
+    /// Receive the result of a signature request
+    pub fn receive(&mut self, request_id: usize, resp: SignatureHelp) {
+        if self.status == SignatureStatus::Inactive || self.request_id != request_id
+        {
+            return;
+        }
+
+        let mut signatures = resp.signatures;
+        if signatures.is_empty() {
+            self.cancel();
+            return;
+        }
+        let index =
+            (resp.active_signature.unwrap_or(0) as usize).min(signatures.len() - 1);
+        let signature = signatures.swap_remove(index);
+        self.active_parameter = signature
+            .active_parameter
+            .or(resp.active_parameter)
+            .map(|i| i as usize);
+        self.signature = Some(Arc::new(signature));
     }
 
-    pub fn clear(&mut self) {
-        self.offset = None;
-        self.signature = None;
-        self.active = None;
+    /// The byte range of the active parameter in the signature label
+    pub fn active_parameter_range(&self) -> Option<(usize, usize)> {
+        let signature = self.signature.as_ref()?;
+        let active = self.active_parameter?;
+        let parameters = signature.parameters.as_ref()?;
+        let label = &signature.label;
+        match &parameters.get(active)?.label {
+            ParameterLabel::Simple(_) => {
+                // A label can appear more than once, like a type shared by
+                // two parameters, so each is looked for after the one before.
+                let mut range = None;
+                let mut from = 0;
+                for parameter in &parameters[..=active] {
+                    let s = match &parameter.label {
+                        ParameterLabel::Simple(s) => s,
+                        ParameterLabel::LabelOffsets(_) => return None,
+                    };
+                    let start = from + label[from..].find(s.as_str())?;
+                    from = start + s.len();
+                    range = Some((start, from));
+                }
+                range
+            }
+            ParameterLabel::LabelOffsets([start, end]) => Some((
+                utf16_offset_to_byte(label, *start as usize)?,
+                utf16_offset_to_byte(label, *end as usize)?,
+            )),
+        }
     }
 }
 
-impl Default for SignatureState {
+impl Default for SignatureData {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Converts an offset in UTF-16 code units, as parameter label offsets are
+/// given, to a byte offset into `s`.
+fn utf16_offset_to_byte(s: &str, offset: usize) -> Option<usize> {
+    let mut units = 0;
+    for (i, c) in s.char_indices() {
+        if units >= offset {
+            return Some(i);
+        }
+        units += c.len_utf16();
+    }
+    if units >= offset {
+        Some(s.len())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::ParameterInformation;
+
+    use super::*;
+
+    fn signature_data(
+        label: &str,
+        parameters: Vec<ParameterLabel>,
+    ) -> SignatureData {
+        let mut data = SignatureData::new();
+        data.status = SignatureStatus::Started;
+        data.receive(
+            0,
+            SignatureHelp {
+                signatures: vec![SignatureInformation {
+                    label: label.to_string(),
+                    documentation: None,
+                    parameters: Some(
+                        parameters
+                            .into_iter()
+                            .map(|label| ParameterInformation {
+                                label,
+                                documentation: None,
+                            })
+                            .collect(),
+                    ),
+                    active_parameter: None,
+                }],
+                active_signature: None,
+                active_parameter: Some(1),
+            },
+        );
+        data
+    }
+
+    #[test]
+    fn test_active_parameter_range() {
+        let label = "fn f(ä: u8, b: &str)";
+        let data = signature_data(
+            label,
+            vec![
+                ParameterLabel::Simple("ä: u8".to_string()),
+                ParameterLabel::Simple("b: &str".to_string()),
+            ],
+        );
+        assert_eq!(Some(1), data.active_parameter);
+        assert_eq!(Some((13, 20)), data.active_parameter_range());
+
+        // The offsets count UTF-16 code units, and `ä` is two bytes long.
+        let data = signature_data(
+            label,
+            vec![
+                ParameterLabel::LabelOffsets([5, 10]),
+                ParameterLabel::LabelOffsets([12, 19]),
+            ],
+        );
+        assert_eq!(Some((13, 20)), data.active_parameter_range());
+
+        // A label that is also an earlier parameter's is found after it.
+        let data = signature_data(
+            "max(int, int)",
+            vec![
+                ParameterLabel::Simple("int".to_string()),
+                ParameterLabel::Simple("int".to_string()),
+            ],
+        );
+        assert_eq!(Some((9, 12)), data.active_parameter_range());
+
+        let mut data = SignatureData::new();
+        data.status = SignatureStatus::Started;
+        data.receive(
+            0,
+            SignatureHelp {
+                signatures: Vec::new(),
+                active_signature: None,
+                active_parameter: None,
+            },
+        );
+        assert_eq!(SignatureStatus::Inactive, data.status);
+        assert!(data.is_empty());
+    }
+}
@@ -1,12 +1,14 @@
 use anyhow::{anyhow, Result};
 use crossbeam_channel::Sender;
-use lapce_rpc::buffer::BufferId;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use lapce_rpc::buffer::{BufferId, FileEncoding};
 use std::ffi::OsString;
 use std::fs;
-use std::fs::File;
+use std::fs::{File, Permissions};
 use std::io::Read;
 use std::io::Write;
 use std::path::PathBuf;
+use std::process::{Command, Stdio};
 use std::{borrow::Cow, path::Path, time::SystemTime};
 
 use lsp_types::*;
@@ -17,6 +19,10 @@ pub struct Buffer {
     pub id: BufferId,
     pub rope: Rope,
     pub path: PathBuf,
+    /// The encoding the file is read and written in
+    pub encoding: &'static Encoding,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
     pub rev: u64,
     pub dirty: bool,
     sender: Sender<(BufferId, u64)>,
@@ -29,17 +35,16 @@ impl Buffer {
         path: PathBuf,
         sender: Sender<(BufferId, u64)>,
     ) -> Buffer {
-        let rope = if let Ok(rope) = load_file(&path) {
-            rope
-        } else {
-            Rope::from("")
-        };
+        let (rope, encoding, bom) =
+            load_file(&path).unwrap_or_else(|_| (Rope::from(""), UTF_8, false));
         let language_id = language_id_from_path(&path).unwrap_or("").to_string();
         let mod_time = get_mod_time(&path);
         Buffer {
             id,
             rope,
             path,
+            encoding,
+            bom,
             language_id,
             rev: 0,
             sender,
@@ -48,11 +53,25 @@ impl Buffer {
         }
     }
 
+    pub fn file_encoding(&self) -> FileEncoding {
+        FileEncoding {
+            name: self.encoding.name().to_string(),
+            bom: self.bom,
+        }
+    }
+
+    /// Sets the language the buffer is served as, going back to the one
+    /// detected from its path when `language_id` is `None`.
+    pub fn set_language_id(&mut self, language_id: Option<String>) {
+        self.language_id = language_id.unwrap_or_else(|| {
+            language_id_from_path(&self.path).unwrap_or("").to_string()
+        });
+    }
+
     pub fn save(&mut self, rev: u64) -> Result<()> {
         if self.rev != rev {
             return Err(anyhow!("not the right rev"));
         }
-        self.dirty = false;
         let tmp_extension = self.path.extension().map_or_else(
             || OsString::from("swp"),
             |ext| {
@@ -63,25 +82,98 @@ impl Buffer {
         );
         let tmp_path = &self.path.with_extension(tmp_extension);
 
+        let bytes = self.file_bytes()?;
         let mut f = File::create(tmp_path)?;
-        for chunk in self.rope.iter_chunks(..self.rope.len()) {
-            f.write_all(chunk.as_bytes())?;
-        }
+        f.write_all(&bytes)?;
         fs::rename(tmp_path, &self.path)?;
+        self.dirty = false;
+        self.mod_time = get_mod_time(&self.path);
+        Ok(())
+    }
+
+    /// Writes a copy of the buffer to `path`, leaving its own file untouched.
+    pub fn save_as(&self, rev: u64, path: &Path) -> Result<()> {
+        if self.rev != rev {
+            return Err(anyhow!("not the right rev"));
+        }
+        let bytes = self.file_bytes()?;
+        let mut f = File::create(path)?;
+        f.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// What an elevated save of the buffer at `rev` writes, which happens
+    /// without holding the buffer while the helper waits for a password.
+    pub fn elevated_save(&self, rev: u64) -> Result<ElevatedSave> {
+        if self.rev != rev {
+            return Err(anyhow!("not the right rev"));
+        }
+        Ok(ElevatedSave {
+            path: self.path.clone(),
+            bytes: self.file_bytes()?,
+        })
+    }
+
+    /// Marks the buffer as saved after its content at `rev` was written to
+    /// its file from elsewhere.
+    pub fn saved_at(&mut self, rev: u64) {
+        if self.rev == rev {
+            self.dirty = false;
+        }
+        self.mod_time = get_mod_time(&self.path);
+    }
+
+    /// Makes the buffer's file writable and overwrites it in place, putting
+    /// the original permissions back afterwards.
+    pub fn save_overwrite_permissions(&mut self, rev: u64) -> Result<()> {
+        if self.rev != rev {
+            return Err(anyhow!("not the right rev"));
+        }
+        let bytes = self.file_bytes()?;
+        let permissions = fs::metadata(&self.path)?.permissions();
+        fs::set_permissions(&self.path, writable_permissions(&permissions))?;
+        let result = File::create(&self.path).and_then(|mut f| f.write_all(&bytes));
+        let _ = fs::set_permissions(&self.path, permissions);
+        result?;
+        self.dirty = false;
         self.mod_time = get_mod_time(&self.path);
         Ok(())
     }
 
+    /// What the file holds once saved, worked out before it's opened for
+    /// writing so a failure leaves it as it was.
+    fn file_bytes(&self) -> Result<Vec<u8>> {
+        encode(&self.rope.to_string(), self.encoding, self.bom)
+    }
+
+    /// Reads the file again after it changed on disk, with what isn't valid in
+    /// its encoding replaced.
     pub fn reload(&mut self) {
-        let rope = if let Ok(rope) = load_file(&self.path) {
-            rope
-        } else {
-            Rope::from("")
-        };
+        let rope = load_file_with_encoding(&self.path, self.encoding)
+            .map(|(rope, _, _)| rope)
+            .unwrap_or_else(|_| Rope::from(""));
+
+        self.rope = rope;
+        self.rev += 1;
+        let _ = self.sender.send((self.id, self.rev));
+    }
 
+    /// Reads the file again as `encoding`, dropping the changes that weren't
+    /// saved.
+    pub fn reload_with_encoding(&mut self, encoding: &str) -> Result<()> {
+        let encoding = Encoding::for_label(encoding.as_bytes())
+            .ok_or_else(|| anyhow!("unknown encoding {encoding}"))?;
+        let (rope, bom, had_errors) = load_file_with_encoding(&self.path, encoding)?;
+        if had_errors {
+            return Err(anyhow!("the file isn't valid {}", encoding.name()));
+        }
         self.rope = rope;
+        self.encoding = encoding;
+        self.bom = bom;
+        self.dirty = false;
         self.rev += 1;
         let _ = self.sender.send((self.id, self.rev));
+        Ok(())
     }
 
     pub fn update(
@@ -125,11 +217,17 @@ impl Buffer {
         (line, offset - self.offset_of_line(line))
     }
 
+    /// The position of `offset` as language servers count it, in UTF-16
+    /// code units.
     pub fn offset_to_position(&self, offset: usize) -> Position {
-        let (line, col) = self.offset_to_line_col(offset);
+        let line = self.line_of_offset(offset);
+        let character = self
+            .slice_to_cow(self.offset_of_line(line)..offset)
+            .encode_utf16()
+            .count();
         Position {
             line: line as u32,
-            character: col as u32,
+            character: character as u32,
         }
     }
 
@@ -146,11 +244,114 @@ impl Buffer {
     }
 }
 
-fn load_file(path: &Path) -> Result<Rope> {
+/// A copy of a buffer to write through a helper, for files the proxy itself
+/// isn't allowed to write.
+pub struct ElevatedSave {
+    path: PathBuf,
+    bytes: Vec<u8>,
+}
+
+impl ElevatedSave {
+    /// Writes the file through `helper` (e.g. `pkexec` or `sudo -A`), which
+    /// can take as long as the user takes to enter their password.
+    pub fn write(&self, helper: &str) -> Result<()> {
+        let mut args = helper.split_whitespace();
+        let program = args
+            .next()
+            .ok_or_else(|| anyhow!("no elevated save helper is configured"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .arg("tee")
+            .arg(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&self.bytes)?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+}
+
+const UTF_8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
     let mut f = File::open(path)?;
     let mut bytes = Vec::new();
     f.read_to_end(&mut bytes)?;
-    Ok(Rope::from(std::str::from_utf8(&bytes)?))
+    Ok(bytes)
+}
+
+/// Reads a file in the encoding its byte order mark names, or as UTF-8 when
+/// it has none.
+fn load_file(path: &Path) -> Result<(Rope, &'static Encoding, bool)> {
+    let bytes = read_file(path)?;
+    if let Some((encoding, bom_len)) = Encoding::for_bom(&bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return Ok((Rope::from(text), encoding, true));
+    }
+    Ok((Rope::from(std::str::from_utf8(&bytes)?), UTF_8, false))
+}
+
+/// Reads a file as `encoding`, returning whether it starts with the byte
+/// order mark of the encoding and whether some of it wasn't valid in the
+/// encoding, which is replaced.
+fn load_file_with_encoding(
+    path: &Path,
+    encoding: &'static Encoding,
+) -> Result<(Rope, bool, bool)> {
+    let bytes = read_file(path)?;
+    let (bytes, bom) = match Encoding::for_bom(&bytes) {
+        Some((bom_encoding, bom_len)) if bom_encoding == encoding => {
+            (&bytes[bom_len..], true)
+        }
+        _ => (&bytes[..], false),
+    };
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    Ok((Rope::from(text), bom, had_errors))
+}
+
+/// The bytes of `text` in `encoding`, after its byte order mark if `bom`,
+/// failing if the encoding can't hold some of its characters.
+fn encode(text: &str, encoding: &'static Encoding, bom: bool) -> Result<Vec<u8>> {
+    // encoding_rs only decodes UTF-16, so its code units are written here.
+    let mut bytes = Vec::new();
+    if encoding == UTF_16LE {
+        if bom {
+            bytes.extend_from_slice(&[0xFF, 0xFE]);
+        }
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+    } else if encoding == UTF_16BE {
+        if bom {
+            bytes.extend_from_slice(&[0xFE, 0xFF]);
+        }
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+    } else {
+        if bom && encoding == UTF_8 {
+            bytes.extend_from_slice(UTF_8_BOM);
+        }
+        let (encoded, _, had_errors) = encoding.encode(text);
+        if had_errors {
+            return Err(anyhow!(
+                "{} can't encode every character of the file",
+                encoding.name()
+            ));
+        }
+        bytes.extend_from_slice(&encoded);
+    }
+    Ok(bytes)
 }
 
 fn language_id_from_path(path: &Path) -> Option<&str> {
@@ -178,7 +379,9 @@ fn get_document_content_changes(
                 start: buffer.offset_to_position(start),
                 end: buffer.offset_to_position(end),
             }),
-            range_length: Some((end - start) as u32),
+            range_length: Some(
+                buffer.slice_to_cow(start..end).encode_utf16().count() as u32,
+            ),
             text,
         };
 
@@ -193,7 +396,9 @@ fn get_document_content_changes(
                 start: buffer.offset_to_position(start),
                 end: end_position,
             }),
-            range_length: Some((end - start) as u32),
+            range_length: Some(
+                buffer.slice_to_cow(start..end).encode_utf16().count() as u32,
+            ),
             text: String::new(),
         };
 
@@ -211,3 +416,39 @@ pub fn get_mod_time<P: AsRef<Path>>(path: P) -> Option<SystemTime> {
         .and_then(|meta| meta.modified())
         .ok()
 }
+
+/// Returns `permissions` with write access granted to the file's owner.
+fn writable_permissions(permissions: &Permissions) -> Permissions {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Permissions::from_mode(permissions.mode() | 0o200)
+    }
+    #[cfg(not(unix))]
+    {
+        let mut permissions = permissions.clone();
+        permissions.set_readonly(false);
+        permissions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::WINDOWS_1252;
+
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            b"caf\xE9".to_vec(),
+            encode("café", WINDOWS_1252, false).unwrap()
+        );
+        assert_eq!(
+            b"\xFF\xFEa\x00".to_vec(),
+            encode("a", UTF_16LE, true).unwrap()
+        );
+        // Saving fails rather than writing what the encoding can't hold.
+        assert!(encode("日本", WINDOWS_1252, false).is_err());
+    }
+}
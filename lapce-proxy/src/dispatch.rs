@@ -1,6 +1,6 @@
 use crate::buffer::{get_mod_time, Buffer};
 use crate::lsp::LspCatalog;
-use crate::plugin::PluginCatalog;
+use crate::plugin::{search_registry, PluginCatalog};
 use crate::terminal::Terminal;
 use alacritty_terminal::event_loop::Msg;
 use alacritty_terminal::term::SizeInfo;
@@ -9,11 +9,17 @@ use crossbeam_channel::{unbounded, Receiver, Sender};
 use directories::BaseDirs;
 use git2::{DiffOptions, Repository};
 use grep_matcher::Matcher;
-use grep_regex::RegexMatcherBuilder;
-use grep_searcher::sinks::UTF8;
-use grep_searcher::SearcherBuilder;
-use lapce_rpc::buffer::{BufferHeadResponse, BufferId, NewBufferResponse};
-use lapce_rpc::file::FileNodeItem;
+use grep_regex::{RegexMatcher, RegexMatcherBuilder};
+use grep_searcher::{Searcher, SearcherBuilder, Sink, SinkContext, SinkMatch};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use lapce_rpc::buffer::{
+    BufferHeadResponse, BufferId, NewBufferResponse, SaveErrorKind,
+};
+use lapce_rpc::file::{
+    FileChangeKind, FileNodeItem, FileOperation, GlobalReplaceResponse,
+    GlobalSearchResponse, SearchOptions,
+};
 use lapce_rpc::proxy::{ProxyNotification, ProxyRequest};
 use lapce_rpc::source_control::{DiffInfo, FileDiff};
 use lapce_rpc::terminal::TermId;
@@ -21,6 +27,7 @@ use lapce_rpc::{self, Call, RequestId, RpcObject};
 use lsp_types::TextDocumentContentChangeEvent;
 use notify::Watcher;
 use parking_lot::Mutex;
+use regex::{NoExpand, Regex};
 use serde_json::json;
 use serde_json::Value;
 use std::collections::HashMap;
@@ -28,12 +35,15 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 use std::{collections::HashSet, io::BufRead};
+use xi_rope::Rope;
 
 #[derive(Clone)]
 pub struct Dispatcher {
     pub sender: Arc<Sender<Value>>,
     pub git_sender: Sender<(BufferId, u64)>,
+    file_change_sender: Sender<(PathBuf, FileChangeKind)>,
     pub workspace: Arc<Mutex<Option<PathBuf>>>,
     pub buffers: Arc<Mutex<HashMap<BufferId, Buffer>>>,
 
@@ -50,6 +60,27 @@ pub struct Dispatcher {
 impl notify::EventHandler for Dispatcher {
     fn handle_event(&mut self, event: notify::Result<notify::Event>) {
         if let Ok(event) = event {
+            for path in event.paths.iter() {
+                // Files saved by replacing them are removed and created again,
+                // so a removal only counts once the path is really gone.
+                let kind = match event.kind {
+                    notify::EventKind::Create(_) => FileChangeKind::Created,
+                    notify::EventKind::Remove(_) if !path.exists() => {
+                        FileChangeKind::Deleted
+                    }
+                    notify::EventKind::Modify(notify::event::ModifyKind::Name(
+                        _,
+                    )) => {
+                        if path.exists() {
+                            FileChangeKind::Renamed
+                        } else {
+                            FileChangeKind::Deleted
+                        }
+                    }
+                    _ => continue,
+                };
+                let _ = self.file_change_sender.send((path.clone(), kind));
+            }
             for path in event.paths.iter() {
                 if let Some(path) = path.to_str() {
                     if let Some(buffer_id) = self.open_files.lock().get(path) {
@@ -118,9 +149,11 @@ impl Dispatcher {
     pub fn new(sender: Sender<Value>) -> Dispatcher {
         let plugins = PluginCatalog::new();
         let (git_sender, git_receiver) = unbounded();
+        let (file_change_sender, file_change_receiver) = unbounded();
         let dispatcher = Dispatcher {
             sender: Arc::new(sender),
             git_sender,
+            file_change_sender,
             workspace: Arc::new(Mutex::new(None)),
             buffers: Arc::new(Mutex::new(HashMap::new())),
             open_files: Arc::new(Mutex::new(HashMap::new())),
@@ -164,6 +197,7 @@ impl Dispatcher {
         });
 
         dispatcher.start_update_process(git_receiver);
+        dispatcher.start_file_change_process(file_change_receiver);
         dispatcher.send_notification("proxy_connected", json!({}));
 
         dispatcher
@@ -226,6 +260,42 @@ impl Dispatcher {
         });
     }
 
+    /// Sends the changes reported by the watcher in batches, once no more
+    /// have come in for a short while, so that e.g. a checkout touching many
+    /// files doesn't make the UI re-read the same directory for each of them.
+    fn start_file_change_process(
+        &self,
+        receiver: Receiver<(PathBuf, FileChangeKind)>,
+    ) {
+        const QUIET_DELAY: Duration = Duration::from_millis(100);
+        const MAX_DELAY: Duration = Duration::from_millis(1000);
+
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            while let Ok((path, kind)) = receiver.recv() {
+                let deadline = Instant::now() + MAX_DELAY;
+                let mut changes = HashMap::new();
+                changes.insert(path, kind);
+                while Instant::now() < deadline {
+                    match receiver.recv_timeout(QUIET_DELAY) {
+                        Ok((path, kind)) => {
+                            changes.insert(path, kind);
+                        }
+                        Err(_) => break,
+                    }
+                }
+                let changes: Vec<(PathBuf, FileChangeKind)> =
+                    changes.into_iter().collect();
+                let _ = sender.send(json!({
+                    "method": "file_changes",
+                    "params": {
+                        "changes": changes,
+                    },
+                }));
+            }
+        });
+    }
+
     pub fn next<R: BufRead>(
         &self,
         reader: &mut R,
@@ -263,6 +333,32 @@ impl Dispatcher {
         let _ = self.sender.send(resp);
     }
 
+    /// Responds to a save request, classifying the failure so the editor can
+    /// offer the right way to recover.
+    fn respond_save(&self, id: RequestId, result: Result<()>) {
+        let e = match result {
+            Ok(()) => {
+                self.respond(id, Ok(json!({})));
+                return;
+            }
+            Err(e) => e,
+        };
+        let kind = match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+            Some(std::io::ErrorKind::PermissionDenied) => {
+                SaveErrorKind::PermissionDenied
+            }
+            _ => SaveErrorKind::Other,
+        };
+        let _ = self.sender.send(json!({
+            "id": id,
+            "error": {
+                "code": 0,
+                "message": format!("{}", e),
+                "data": kind,
+            },
+        }));
+    }
+
     pub fn send_notification(&self, method: &str, params: Value) {
         let _ = self.sender.send(json!({
             "method": method,
@@ -320,6 +416,20 @@ impl Dispatcher {
                     );
                 });
             }
+            DisablePlugin { name } => {
+                let language_ids = self.plugins.lock().disable_plugin(&name);
+                let mut lsp = self.lsp.lock();
+                for language_id in language_ids {
+                    lsp.stop_server(&language_id);
+                }
+            }
+            EnablePlugin { name } => {
+                let catalog = self.plugins.clone();
+                let dispatcher = self.clone();
+                std::thread::spawn(move || {
+                    let _ = catalog.lock().enable_plugin(dispatcher, &name);
+                });
+            }
             NewTerminal {
                 term_id,
                 cwd,
@@ -333,6 +443,73 @@ impl Dispatcher {
                     terminal.run(dispatcher);
                 });
             }
+            CancelCompletion {
+                request_id,
+                buffer_id,
+            } => {
+                let buffers = self.buffers.lock();
+                if let Some(buffer) = buffers.get(&buffer_id) {
+                    self.lsp.lock().cancel_completion(request_id, buffer);
+                }
+            }
+            CancelWorkspaceSymbols { request_id } => {
+                self.lsp.lock().cancel_workspace_symbols(request_id);
+            }
+            ReopenBuffer {
+                buffer_id,
+                path,
+                rev,
+                content,
+            } => {
+                let _ = self
+                    .watcher
+                    .lock()
+                    .as_mut()
+                    .unwrap()
+                    .watch(&path, notify::RecursiveMode::Recursive);
+                self.open_files
+                    .lock()
+                    .insert(path.to_str().unwrap().to_string(), buffer_id);
+                let mut buffer =
+                    Buffer::new(buffer_id, path, self.git_sender.clone());
+                if buffer.rope.to_string() != content {
+                    buffer.rope = Rope::from(content);
+                    buffer.dirty = true;
+                }
+                buffer.rev = rev;
+                self.buffers.lock().insert(buffer_id, buffer);
+                let _ = self.git_sender.send((buffer_id, rev));
+            }
+            SetLanguage {
+                buffer_id,
+                language_id,
+            } => {
+                let mut buffers = self.buffers.lock();
+                if let Some(buffer) = buffers.get_mut(&buffer_id) {
+                    self.lsp.lock().close_buffer(buffer);
+                    buffer.set_language_id(language_id);
+                    // The new server opens the buffer when asked for its
+                    // semantic tokens.
+                    let _ = self.git_sender.send((buffer_id, buffer.rev));
+                }
+            }
+            CloseBuffer { buffer_id } => {
+                if let Some(buffer) = self.buffers.lock().remove(&buffer_id) {
+                    self.lsp.lock().close_buffer(&buffer);
+                    if let Some(path) = buffer.path.to_str() {
+                        self.open_files.lock().remove(path);
+                    }
+                    if let Some(watcher) = self.watcher.lock().as_mut() {
+                        let _ = watcher.unwatch(&buffer.path);
+                    }
+                }
+            }
+            ExecuteCommand { buffer_id, command } => {
+                let buffers = self.buffers.lock();
+                if let Some(buffer) = buffers.get(&buffer_id) {
+                    self.lsp.lock().execute_command(buffer, &command);
+                }
+            }
             TerminalClose { term_id } => {
                 let mut terminals = self.terminals.lock();
                 if let Some(tx) = terminals.remove(&term_id) {
@@ -391,9 +568,10 @@ impl Dispatcher {
                     .insert(path.to_str().unwrap().to_string(), buffer_id);
                 let buffer = Buffer::new(buffer_id, path, self.git_sender.clone());
                 let content = buffer.rope.to_string();
+                let encoding = buffer.file_encoding();
                 self.buffers.lock().insert(buffer_id, buffer);
                 let _ = self.git_sender.send((buffer_id, 0));
-                let resp = NewBufferResponse { content };
+                let resp = NewBufferResponse { content, encoding };
                 let _ = self.sender.send(json!({
                     "id": id,
                     "result": resp,
@@ -414,6 +592,40 @@ impl Dispatcher {
                     }
                 }
             }
+            ReopenWithEncoding {
+                buffer_id,
+                encoding,
+            } => {
+                let mut buffers = self.buffers.lock();
+                let buffer = match buffers.get_mut(&buffer_id) {
+                    Some(buffer) => buffer,
+                    None => {
+                        self.respond(id, Err(anyhow!("the file isn't open")));
+                        return;
+                    }
+                };
+                let result = buffer.reload_with_encoding(&encoding);
+                if result.is_ok() {
+                    self.lsp.lock().update(
+                        buffer,
+                        &TextDocumentContentChangeEvent {
+                            range: None,
+                            range_length: None,
+                            text: buffer.get_document(),
+                        },
+                        buffer.rev,
+                    );
+                    let _ = self.sender.send(json!({
+                        "method": "reload_buffer",
+                        "params": {
+                            "buffer_id": buffer_id,
+                            "rev": buffer.rev,
+                            "new_content": buffer.get_document(),
+                        },
+                    }));
+                }
+                self.respond(id, result.map(|_| json!(buffer.file_encoding())));
+            }
             GetCompletion {
                 buffer_id,
                 position,
@@ -460,6 +672,77 @@ impl Dispatcher {
                 let buffer = buffers.get(&buffer_id).unwrap();
                 self.lsp.lock().get_references(id, buffer, position);
             }
+            PrepareCallHierarchy {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().prepare_call_hierarchy(id, buffer, position);
+            }
+            CallHierarchyIncomingCalls { buffer_id, item } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_call_hierarchy_calls(id, buffer, item, false);
+            }
+            CallHierarchyOutgoingCalls { buffer_id, item } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_call_hierarchy_calls(id, buffer, item, true);
+            }
+            PrepareTypeHierarchy {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().prepare_type_hierarchy(id, buffer, position);
+            }
+            TypeHierarchySupertypes { buffer_id, item } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_type_hierarchy_types(id, buffer, item, true);
+            }
+            TypeHierarchySubtypes { buffer_id, item } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_type_hierarchy_types(id, buffer, item, false);
+            }
+            PrepareRename {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().prepare_rename(id, buffer, position);
+            }
+            GetDocumentHighlights {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_document_highlights(id, buffer, position);
+            }
+            Rename {
+                buffer_id,
+                position,
+                new_name,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().rename(id, buffer, position, new_name);
+            }
             GetDefinition {
                 buffer_id,
                 position,
@@ -471,6 +754,22 @@ impl Dispatcher {
                     .lock()
                     .get_definition(id, request_id, buffer, position);
             }
+            GetTypeDefinition {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().get_type_definition(id, buffer, position);
+            }
+            GetImplementation {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().get_implementation(id, buffer, position);
+            }
             GetCodeActions {
                 buffer_id,
                 position,
@@ -479,15 +778,55 @@ impl Dispatcher {
                 let buffer = buffers.get(&buffer_id).unwrap();
                 self.lsp.lock().get_code_actions(id, buffer, position);
             }
+            CodeActionResolve { buffer_id, action } => {
+                let buffers = self.buffers.lock();
+                match buffers.get(&buffer_id) {
+                    Some(buffer) => {
+                        self.lsp.lock().code_action_resolve(id, buffer, &action)
+                    }
+                    None => self.respond(id, Err(anyhow!("the file was closed"))),
+                }
+            }
             GetDocumentSymbols { buffer_id } => {
                 let buffers = self.buffers.lock();
                 let buffer = buffers.get(&buffer_id).unwrap();
                 self.lsp.lock().get_document_symbols(id, buffer);
             }
-            GetDocumentFormatting { buffer_id } => {
+            GetWorkspaceSymbols { request_id, query } => {
+                self.lsp.lock().get_workspace_symbols(id, request_id, query);
+            }
+            GetFoldingRanges { buffer_id } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().get_folding_ranges(id, buffer);
+            }
+            GetDocumentFormatting { buffer_id, options } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().get_document_formatting(id, buffer, options);
+            }
+            GetDocumentRangeFormatting {
+                buffer_id,
+                range,
+                options,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_document_range_formatting(id, buffer, range, options);
+            }
+            GetOnTypeFormatting {
+                buffer_id,
+                position,
+                ch,
+                options,
+            } => {
                 let buffers = self.buffers.lock();
                 let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_document_formatting(id, buffer);
+                self.lsp
+                    .lock()
+                    .get_on_type_formatting(id, buffer, position, ch, options);
             }
             ReadDir { path } => {
                 let local_dispatcher = self.clone();
@@ -535,54 +874,141 @@ impl Dispatcher {
             Save { rev, buffer_id } => {
                 let mut buffers = self.buffers.lock();
                 let buffer = buffers.get_mut(&buffer_id).unwrap();
-                let resp = buffer.save(rev).map(|_r| json!({}));
+                let resp = buffer.save(rev);
+                self.lsp.lock().save_buffer(buffer);
+                self.respond_save(id, resp);
+            }
+            SaveAs {
+                rev,
+                buffer_id,
+                path,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                let resp = buffer.save_as(rev, &path);
+                self.respond_save(id, resp);
+            }
+            SaveElevated {
+                rev,
+                buffer_id,
+                helper,
+            } => {
+                let save = self
+                    .buffers
+                    .lock()
+                    .get(&buffer_id)
+                    .unwrap()
+                    .elevated_save(rev);
+                // The helper may wait for a password for a while, so other
+                // requests go on in the meantime.
+                let local_dispatcher = self.clone();
+                thread::spawn(move || {
+                    let resp = save.and_then(|save| save.write(&helper));
+                    let mut buffers = local_dispatcher.buffers.lock();
+                    if let Some(buffer) = buffers.get_mut(&buffer_id) {
+                        if resp.is_ok() {
+                            buffer.saved_at(rev);
+                        }
+                        local_dispatcher.lsp.lock().save_buffer(buffer);
+                    }
+                    local_dispatcher.respond_save(id, resp);
+                });
+            }
+            SaveOverwritePermissions { rev, buffer_id } => {
+                let mut buffers = self.buffers.lock();
+                let buffer = buffers.get_mut(&buffer_id).unwrap();
+                let resp = buffer.save_overwrite_permissions(rev);
                 self.lsp.lock().save_buffer(buffer);
-                self.respond(id, resp);
+                self.respond_save(id, resp);
             }
-            GlobalSearch { pattern } => {
+            GlobalSearch {
+                pattern,
+                options,
+                context_lines,
+                globs,
+            } => {
                 if let Some(workspace) = self.workspace.lock().clone() {
                     let local_dispatcher = self.clone();
                     thread::spawn(move || {
-                        let mut matches = HashMap::new();
-                        let pattern = regex::escape(&pattern);
-                        if let Ok(matcher) = RegexMatcherBuilder::new()
-                            .case_insensitive(true)
-                            .build_literals(&[&pattern])
-                        {
-                            let mut searcher = SearcherBuilder::new().build();
-                            for path in ignore::Walk::new(workspace).flatten() {
+                        let mut resp = GlobalSearchResponse::default();
+                        if let (Ok(matcher), Ok(overrides)) = (
+                            global_search_matcher(&pattern, options),
+                            global_search_overrides(&workspace, &globs),
+                        ) {
+                            let mut searcher = SearcherBuilder::new()
+                                .before_context(context_lines)
+                                .after_context(context_lines)
+                                .build();
+                            let walk = WalkBuilder::new(&workspace)
+                                .overrides(overrides)
+                                .build();
+                            for path in walk.flatten() {
                                 if let Some(file_type) = path.file_type() {
                                     if file_type.is_file() {
                                         let path = path.into_path();
-                                        let mut line_matches = Vec::new();
+                                        let mut sink = SearchSink::new(&matcher);
                                         let _ = searcher.search_path(
                                             &matcher,
                                             path.clone(),
-                                            UTF8(|lnum, line| {
-                                                let mymatch = matcher
-                                                    .find(line.as_bytes())?
-                                                    .unwrap();
-                                                line_matches.push((
-                                                    lnum,
-                                                    (mymatch.start(), mymatch.end()),
-                                                    line.to_string(),
-                                                ));
-                                                Ok(true)
-                                            }),
+                                            &mut sink,
                                         );
-                                        if !line_matches.is_empty() {
-                                            matches
-                                                .insert(path.clone(), line_matches);
+                                        if !sink.matches.is_empty() {
+                                            resp.matches
+                                                .insert(path.clone(), sink.matches);
+                                            if !sink.context.is_empty() {
+                                                resp.context
+                                                    .insert(path, sink.context);
+                                            }
                                         }
                                     }
                                 }
                             }
                         }
                         local_dispatcher
-                            .respond(id, Ok(serde_json::to_value(matches).unwrap()));
+                            .respond(id, Ok(serde_json::to_value(resp).unwrap()));
                     });
                 }
             }
+            SearchPlugins { query } => {
+                let catalog = self.plugins.clone();
+                let local_dispatcher = self.clone();
+                thread::spawn(move || {
+                    let result = search_registry(&catalog, &query)
+                        .map(|plugins| serde_json::to_value(plugins).unwrap());
+                    local_dispatcher.respond(id, result);
+                });
+            }
+            GlobalReplace {
+                pattern,
+                replacement,
+                options,
+                lines,
+            } => {
+                let local_dispatcher = self.clone();
+                thread::spawn(move || {
+                    let result =
+                        global_replace(&pattern, &replacement, options, lines)
+                            .map(|resp| serde_json::to_value(resp).unwrap());
+                    local_dispatcher.respond(id, result);
+                });
+            }
+            ApplyFileOperation { operation } => {
+                let result = apply_file_operation(&operation);
+                if let (Ok(()), FileOperation::Rename { from, to, .. }) =
+                    (&result, &operation)
+                {
+                    for buffer in self.buffers.lock().values_mut() {
+                        if let Ok(suffix) = buffer.path.strip_prefix(from) {
+                            buffer.path = if suffix.as_os_str().is_empty() {
+                                to.clone()
+                            } else {
+                                to.join(suffix)
+                            };
+                        }
+                    }
+                }
+                self.respond(id, result.map(|_| Value::Null));
+            }
         }
     }
 }
@@ -596,6 +1022,211 @@ pub struct DiffHunk {
     pub header: String,
 }
 
+/// Collects the lines of a file the global search matched, each with the
+/// first match in it, and the context lines around them.
+struct SearchSink<'a> {
+    matcher: &'a RegexMatcher,
+    matches: Vec<(usize, (usize, usize), String)>,
+    context: Vec<(usize, String)>,
+}
+
+impl<'a> SearchSink<'a> {
+    fn new(matcher: &'a RegexMatcher) -> Self {
+        Self {
+            matcher,
+            matches: Vec::new(),
+            context: Vec::new(),
+        }
+    }
+}
+
+fn sink_line(bytes: &[u8]) -> std::io::Result<&str> {
+    std::str::from_utf8(bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+impl Sink for SearchSink<'_> {
+    type Error = std::io::Error;
+
+    fn matched(
+        &mut self,
+        _searcher: &Searcher,
+        mat: &SinkMatch<'_>,
+    ) -> std::io::Result<bool> {
+        let line = sink_line(mat.bytes())?;
+        if let Some(found) = self.matcher.find(mat.bytes())? {
+            self.matches.push((
+                mat.line_number().unwrap_or(0) as usize,
+                (found.start(), found.end()),
+                line.to_string(),
+            ));
+        }
+        Ok(true)
+    }
+
+    fn context(
+        &mut self,
+        _searcher: &Searcher,
+        context: &SinkContext<'_>,
+    ) -> std::io::Result<bool> {
+        let line = sink_line(context.bytes())?;
+        self.context.push((
+            context.line_number().unwrap_or(0) as usize,
+            line.to_string(),
+        ));
+        Ok(true)
+    }
+}
+
+/// The regex, before case is ignored, that the global search matches
+/// `pattern` with
+pub fn global_search_regex(pattern: &str, options: SearchOptions) -> String {
+    let pattern = if options.regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+    if options.whole_word {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    }
+}
+
+fn global_search_matcher(
+    pattern: &str,
+    options: SearchOptions,
+) -> Result<RegexMatcher> {
+    let matcher = RegexMatcherBuilder::new()
+        .case_insensitive(true)
+        .build(&global_search_regex(pattern, options))?;
+    Ok(matcher)
+}
+
+/// Limits the global search to the paths matching `globs`, relative to
+/// `workspace`. A glob starting with `!` excludes the paths it matches instead.
+fn global_search_overrides(workspace: &Path, globs: &[String]) -> Result<Override> {
+    let mut builder = OverrideBuilder::new(workspace);
+    for glob in globs {
+        builder.add(glob)?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Replaces `pattern`, matched the way the global search does, in the given
+/// lines of each file. A file is skipped if any of its lines no longer has the
+/// text it had when searched. In regex mode the replacement can refer to
+/// capture groups.
+fn global_replace(
+    pattern: &str,
+    replacement: &str,
+    options: SearchOptions,
+    lines: HashMap<PathBuf, Vec<(usize, String)>>,
+) -> Result<GlobalReplaceResponse> {
+    let matcher =
+        Regex::new(&format!("(?i){}", global_search_regex(pattern, options)))?;
+    let mut resp = GlobalReplaceResponse::default();
+    for (path, lines) in lines {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => {
+                resp.skipped.push(path);
+                continue;
+            }
+        };
+        let mut file_lines: Vec<String> = content
+            .split_inclusive('\n')
+            .map(|l| l.to_string())
+            .collect();
+        let unchanged = lines.iter().all(|(line_number, line)| {
+            line_number.checked_sub(1).and_then(|i| file_lines.get(i)) == Some(line)
+        });
+        if !unchanged {
+            resp.skipped.push(path);
+            continue;
+        }
+
+        for (line_number, line) in lines.iter() {
+            file_lines[line_number - 1] = if options.regex {
+                matcher.replace_all(line, replacement).into_owned()
+            } else {
+                matcher
+                    .replace_all(line, NoExpand(replacement))
+                    .into_owned()
+            };
+        }
+        if fs::write(&path, file_lines.concat()).is_ok() {
+            resp.replaced.push(path);
+        } else {
+            resp.skipped.push(path);
+        }
+    }
+    Ok(resp)
+}
+
+/// Makes the change to the files of the workspace that `operation` describes,
+/// creating the missing parent directories of new paths.
+fn apply_file_operation(operation: &FileOperation) -> Result<()> {
+    let skip_existing = |path: &Path, overwrite: bool, ignore_if_exists: bool| {
+        if !path.exists() || overwrite {
+            Ok(false)
+        } else if ignore_if_exists {
+            Ok(true)
+        } else {
+            Err(anyhow!("{} already exists", path.display()))
+        }
+    };
+    match operation {
+        FileOperation::Create {
+            path,
+            overwrite,
+            ignore_if_exists,
+        } => {
+            if skip_existing(path, *overwrite, *ignore_if_exists)? {
+                return Ok(());
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::File::create(path)?;
+        }
+        FileOperation::Rename {
+            from,
+            to,
+            overwrite,
+            ignore_if_exists,
+        } => {
+            if skip_existing(to, *overwrite, *ignore_if_exists)? {
+                return Ok(());
+            }
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(from, to)?;
+        }
+        FileOperation::Delete {
+            path,
+            recursive,
+            ignore_if_not_exists,
+        } => {
+            if !path.exists() {
+                if *ignore_if_not_exists {
+                    return Ok(());
+                }
+                return Err(anyhow!("{} doesn't exist", path.display()));
+            }
+            if !path.is_dir() {
+                fs::remove_file(path)?;
+            } else if *recursive {
+                fs::remove_dir_all(path)?;
+            } else {
+                fs::remove_dir(path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 fn git_commit(
     workspace_path: &Path,
     message: &str,
@@ -833,3 +1464,106 @@ fn file_git_diff(
         line_changes,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_search_matcher() {
+        let line = "let foo = Foobar::new();";
+        let literal =
+            global_search_matcher("fo+", SearchOptions::default()).unwrap();
+        assert!(!literal.is_match(line.as_bytes()).unwrap());
+        assert!(literal.is_match(b"x = fo+;").unwrap());
+
+        let regex = SearchOptions {
+            regex: true,
+            whole_word: false,
+        };
+        let regex = global_search_matcher("fo+", regex).unwrap();
+        let found = regex.find(line.as_bytes()).unwrap().unwrap();
+        assert_eq!(&line[found.start()..found.end()], "foo");
+
+        let whole_word = SearchOptions {
+            regex: false,
+            whole_word: true,
+        };
+        let whole_word = global_search_matcher("foobar", whole_word).unwrap();
+        assert!(whole_word.is_match(line.as_bytes()).unwrap());
+        assert!(!whole_word.is_match(b"let foobars = 1;").unwrap());
+    }
+
+    #[test]
+    fn test_global_search_overrides() {
+        let workspace = Path::new("/workspace");
+        let globs = vec!["src/**".to_string(), "!**/tests/**".to_string()];
+        let overrides = global_search_overrides(workspace, &globs).unwrap();
+        let matched =
+            |path: &str| !overrides.matched(workspace.join(path), false).is_ignore();
+        assert!(matched("src/main.rs"));
+        assert!(!matched("src/tests/main.rs"));
+        assert!(!matched("benches/main.rs"));
+
+        // Only excluding leaves everything else in.
+        let globs = vec!["!**/tests/**".to_string()];
+        let overrides = global_search_overrides(workspace, &globs).unwrap();
+        assert!(!overrides
+            .matched(workspace.join("src/tests/main.rs"), false)
+            .is_ignore());
+    }
+
+    #[test]
+    fn test_search_sink_context() {
+        let content = "a\nfoo\nb\nc\nd\ne\nfoo\n";
+        let matcher =
+            global_search_matcher("foo", SearchOptions::default()).unwrap();
+        let mut searcher = SearcherBuilder::new()
+            .before_context(1)
+            .after_context(1)
+            .build();
+        let mut sink = SearchSink::new(&matcher);
+        searcher
+            .search_slice(&matcher, content.as_bytes(), &mut sink)
+            .unwrap();
+        assert_eq!(
+            sink.matches,
+            vec![
+                (2, (0, 3), "foo\n".to_string()),
+                (7, (0, 3), "foo\n".to_string())
+            ]
+        );
+        let context: Vec<usize> = sink
+            .context
+            .iter()
+            .map(|(line_number, _)| *line_number)
+            .collect();
+        assert_eq!(context, vec![1, 3, 6]);
+    }
+
+    #[test]
+    fn test_apply_file_operation_create() {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-file-operation-{}", std::process::id()));
+        let path = dir.join("src").join("new.rs");
+        let create = |overwrite, ignore_if_exists| FileOperation::Create {
+            path: path.clone(),
+            overwrite,
+            ignore_if_exists,
+        };
+
+        // Missing parent directories are created.
+        apply_file_operation(&create(false, false)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+        fs::write(&path, "fn main() {}").unwrap();
+        assert!(apply_file_operation(&create(false, false)).is_err());
+        apply_file_operation(&create(false, true)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn main() {}");
+        // Overwriting wins over ignoring.
+        apply_file_operation(&create(true, true)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     collections::HashMap,
     io::BufRead,
     io::{BufReader, BufWriter, Write},
+    path::PathBuf,
     process::{self, Child, ChildStdout, Command, Stdio},
     sync::{mpsc::channel, Arc},
     thread,
@@ -14,7 +15,8 @@ use std::os::windows::process::CommandExt;
 use anyhow::{anyhow, Result};
 use jsonrpc_lite::{Id, JsonRpc, Params};
 use lapce_rpc::{
-    buffer::BufferId,
+    buffer::{BufferId, RangeFormattingResponse},
+    proxy::TypeHierarchyItem,
     style::{LineStyle, Style},
     RequestId,
 };
@@ -28,6 +30,9 @@ use crate::dispatch::Dispatcher;
 pub type Callback = Box<dyn Callable>;
 const HEADER_CONTENT_LENGTH: &str = "content-length";
 const HEADER_CONTENT_TYPE: &str = "content-type";
+/// Prefixes the editor's request id in the token partial workspace symbol
+/// results are sent with
+const WORKSPACE_SYMBOL_TOKEN: &str = "lapce/workspace_symbol/";
 
 pub trait Callable: Send {
     fn call(self: Box<Self>, client: &LspClient, result: Result<Value>);
@@ -49,14 +54,24 @@ pub struct LspState {
     writer: Box<dyn Write + Send>,
     process: Child,
     pending: HashMap<u64, Callback>,
+    /// The in flight completion requests, mapped to the editor's request id
+    completion_requests: HashMap<u64, usize>,
+    /// The in flight workspace symbol requests, mapped to the editor's
+    /// request id
+    workspace_symbol_requests: HashMap<u64, usize>,
     pub server_capabilities: Option<ServerCapabilities>,
+    /// Whether the server answers type hierarchy requests, a capability
+    /// `ServerCapabilities` doesn't have a field for yet
+    type_hierarchy_provider: bool,
     pub opened_documents: HashMap<BufferId, Url>,
+    /// The last semantic tokens received for each buffer, which a delta
+    /// request asks for the changes since
+    semantic_tokens: HashMap<BufferId, SemanticTokens>,
     pub is_initialized: bool,
 }
 
 #[derive(Clone)]
 pub struct LspClient {
-    #[allow(dead_code)]
     language_id: String,
     exec_path: String,
     options: Option<Value>,
@@ -95,6 +110,12 @@ impl LspCatalog {
         self.clients.insert(language_id.to_string(), client);
     }
 
+    pub fn stop_server(&mut self, language_id: &str) {
+        if let Some(client) = self.clients.remove(language_id) {
+            client.stop();
+        }
+    }
+
     pub fn new_buffer(
         &self,
         buffer_id: &BufferId,
@@ -108,6 +129,14 @@ impl LspCatalog {
         }
     }
 
+    /// Closes the buffer on the server serving it, before it's handed to
+    /// another one.
+    pub fn close_buffer(&self, buffer: &Buffer) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            client.send_did_close(&buffer.id);
+        }
+    }
+
     pub fn save_buffer(&self, buffer: &Buffer) {
         if let Some(client) = self.clients.get(&buffer.language_id) {
             let uri = client.get_uri(buffer);
@@ -122,36 +151,38 @@ impl LspCatalog {
         let len = buffer.len();
         if let Some(client) = self.clients.get(&buffer.language_id) {
             let uri = client.get_uri(buffer);
-            let local_dispatcher = self.dispatcher.clone().unwrap();
-            client.request_semantic_tokens(uri, move |lsp_client, result| {
-                if let Ok(res) = result {
-                    let buffers = local_dispatcher.buffers.lock();
-                    let buffer = buffers.get(&buffer_id).unwrap();
-                    if buffer.rev != rev {
-                        return;
-                    }
-                    let lsp_state = lsp_client.state.lock();
-                    let semantic_tokens_provider = &lsp_state
-                        .server_capabilities
-                        .as_ref()
-                        .unwrap()
-                        .semantic_tokens_provider;
-                    if let Some(styles) =
-                        format_semantic_styles(buffer, semantic_tokens_provider, res)
+            let publish = move |lsp_client: &LspClient, tokens: SemanticTokens| {
+                lsp_client
+                    .publish_semantic_tokens(buffer_id, path, rev, len, tokens);
+            };
+            let on_full = {
+                let publish = publish.clone();
+                move |lsp_client: &LspClient, result: Result<Value>| {
+                    if let Some(tokens) =
+                        result.ok().and_then(|v| serde_json::from_value(v).ok())
                     {
-                        local_dispatcher.send_notification(
-                            "semantic_styles",
-                            json!({
-                                "rev": rev,
-                                "buffer_id": buffer_id,
-                                "path": path,
-                                "styles": styles,
-                                "len": len,
-                            }),
-                        )
+                        publish(lsp_client, tokens);
                     }
                 }
-            });
+            };
+            if let Some(result_id) = client.semantic_tokens_result_id(&buffer_id) {
+                client.request_semantic_tokens_delta(
+                    uri.clone(),
+                    result_id.clone(),
+                    move |lsp_client, result| {
+                        match lsp_client.apply_semantic_tokens_delta(
+                            &buffer_id, &result_id, result,
+                        ) {
+                            Some(tokens) => publish(lsp_client, tokens),
+                            // The server has dropped the previous result, so
+                            // ask for all the tokens again.
+                            None => lsp_client.request_semantic_tokens(uri, on_full),
+                        }
+                    },
+                );
+            } else {
+                client.request_semantic_tokens(uri, on_full);
+            }
         }
     }
 
@@ -164,42 +195,204 @@ impl LspCatalog {
         }
     }
 
-    pub fn get_document_formatting(&self, id: RequestId, buffer: &Buffer) {
+    /// Searches the symbols of the workspace with every language server
+    /// that can, responding once they all have. Results a server sends
+    /// before it's done are forwarded as they come.
+    pub fn get_workspace_symbols(
+        &self,
+        id: RequestId,
+        request_id: usize,
+        query: String,
+    ) {
+        let clients: Vec<&Arc<LspClient>> = self
+            .clients
+            .values()
+            .filter(|client| client.can_search_workspace_symbols())
+            .collect();
+        if clients.is_empty() {
+            self.dispatcher.as_ref().unwrap().respond(id, Ok(json!([])));
+            return;
+        }
+        let pending = Arc::new(Mutex::new((clients.len(), Vec::new())));
+        for client in clients {
+            let pending = pending.clone();
+            client.request_workspace_symbols(
+                request_id,
+                query.clone(),
+                move |lsp_client, result| {
+                    let mut pending = pending.lock();
+                    if let Ok(Value::Array(symbols)) = result {
+                        pending.1.extend(symbols);
+                    }
+                    pending.0 -= 1;
+                    if pending.0 == 0 {
+                        let symbols = std::mem::take(&mut pending.1);
+                        lsp_client.dispatcher.respond(id, Ok(Value::Array(symbols)));
+                    }
+                },
+            );
+        }
+    }
+
+    pub fn cancel_workspace_symbols(&self, request_id: usize) {
+        for client in self.clients.values() {
+            client.cancel_workspace_symbols(request_id);
+        }
+    }
+
+    pub fn get_folding_ranges(&self, id: RequestId, buffer: &Buffer) {
         if let Some(client) = self.clients.get(&buffer.language_id) {
             let uri = client.get_uri(buffer);
-            client.request_document_formatting(uri, move |lsp_client, result| {
+            client.request_folding_ranges(uri, move |lsp_client, result| {
                 lsp_client.dispatcher.respond(id, result);
             });
         } else {
             self.dispatcher
                 .as_ref()
                 .unwrap()
-                .respond(id, Err(anyhow!("no document formatting")));
+                .respond(id, Err(anyhow!("no language server to fold with")));
+        }
+    }
+
+    pub fn get_document_formatting(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        options: FormattingOptions,
+    ) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            let uri = client.get_uri(buffer);
+            client.request_document_formatting(
+                uri,
+                options,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Ok(Value::Null));
+        }
+    }
+
+    /// Formats the range of the buffer, or the whole buffer if its language
+    /// server can't format ranges.
+    pub fn get_document_range_formatting(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        range: Range,
+        options: FormattingOptions,
+    ) {
+        let respond = move |dispatcher: &Dispatcher,
+                            whole_document: bool,
+                            result: Result<Value>| {
+            let result = result.map(|edits| {
+                json!(RangeFormattingResponse {
+                    whole_document,
+                    edits,
+                })
+            });
+            dispatcher.respond(id, result);
+        };
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            let uri = client.get_uri(buffer);
+            if client.can_format_ranges() {
+                client.request_document_range_formatting(
+                    uri,
+                    range,
+                    options,
+                    move |lsp_client, result| {
+                        respond(&lsp_client.dispatcher, false, result);
+                    },
+                );
+            } else {
+                client.request_document_formatting(
+                    uri,
+                    options,
+                    move |lsp_client, result| {
+                        respond(&lsp_client.dispatcher, true, result);
+                    },
+                );
+            }
+        } else {
+            respond(self.dispatcher.as_ref().unwrap(), false, Ok(Value::Null));
+        }
+    }
+
+    /// Formats the buffer after `ch` was typed at `position`, responding with
+    /// no edits when the language server isn't triggered by `ch`.
+    pub fn get_on_type_formatting(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+        ch: String,
+        options: FormattingOptions,
+    ) {
+        match self.clients.get(&buffer.language_id) {
+            Some(client) if client.formats_on_type(&ch) => {
+                let uri = client.get_uri(buffer);
+                client.request_on_type_formatting(
+                    uri,
+                    position,
+                    ch,
+                    options,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+            }
+            _ => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Ok(Value::Null));
+            }
         }
     }
 
     pub fn get_completion(
         &self,
         id: RequestId,
-        _request_id: usize,
+        request_id: usize,
         buffer: &Buffer,
         position: Position,
     ) {
         if let Some(client) = self.clients.get(&buffer.language_id) {
             let uri = client.get_uri(buffer);
-            client.request_completion(uri, position, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
+            client.request_completion(
+                request_id,
+                uri,
+                position,
+                move |lsp_client, result| {
+                    let mut resp = json!({ "id": id });
+                    match result {
+                        Ok(v) => resp["result"] = v,
+                        Err(e) => {
+                            resp["error"] = json!({
+                                "code": 0,
+                                "message": format!("{}",e),
+                            })
+                        }
                     }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
-            });
+                    let _ = lsp_client.dispatcher.sender.send(resp);
+                },
+            );
+        }
+    }
+
+    pub fn cancel_completion(&self, request_id: usize, buffer: &Buffer) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            client.cancel_completion(request_id);
+        }
+    }
+
+    pub fn execute_command(&self, buffer: &Buffer, command: &lsp_types::Command) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            client.execute_command(command, |_, _| {});
         }
     }
 
@@ -270,6 +463,267 @@ impl LspCatalog {
         }
     }
 
+    /// Finds what would be renamed at `position`. A server that can't tell
+    /// is answered for, asking for the default behavior of renaming the word
+    /// there.
+    pub fn prepare_rename(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        match self.clients.get(&buffer.language_id) {
+            Some(client) if client.can_prepare_rename() => {
+                let uri = client.get_uri(buffer);
+                client.request_prepare_rename(
+                    uri,
+                    position,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+            }
+            Some(_) => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Ok(json!({ "defaultBehavior": true })));
+            }
+            None => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Err(anyhow!("no language server to rename with")));
+            }
+        }
+    }
+
+    pub fn get_type_definition(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        match self.clients.get(&buffer.language_id) {
+            Some(client) if client.can_type_definition() => {
+                let uri = client.get_uri(buffer);
+                client.request_type_definition(
+                    uri,
+                    position,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+            }
+            Some(_) => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!("the language server has no type definitions")),
+                );
+            }
+            None => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!("no language server to get the type from")),
+                );
+            }
+        }
+    }
+
+    pub fn get_implementation(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        match self.clients.get(&buffer.language_id) {
+            Some(client) if client.can_implementation() => {
+                let uri = client.get_uri(buffer);
+                client.request_implementation(
+                    uri,
+                    position,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+            }
+            Some(_) => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!("the language server has no implementations")),
+                );
+            }
+            None => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!(
+                        "no language server to get the implementations from"
+                    )),
+                );
+            }
+        }
+    }
+
+    pub fn prepare_call_hierarchy(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        match self.clients.get(&buffer.language_id) {
+            Some(client) if client.can_call_hierarchy() => {
+                let uri = client.get_uri(buffer);
+                client.request_prepare_call_hierarchy(
+                    uri,
+                    position,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+            }
+            Some(_) => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!("the language server has no call hierarchy")),
+                );
+            }
+            None => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!("no language server to get the calls from")),
+                );
+            }
+        }
+    }
+
+    /// The calls made to `item`, or the ones it makes when `outgoing`.
+    pub fn get_call_hierarchy_calls(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        item: CallHierarchyItem,
+        outgoing: bool,
+    ) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            client.request_call_hierarchy_calls(
+                item,
+                outgoing,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher.as_ref().unwrap().respond(
+                id,
+                Err(anyhow!("no language server to get the calls from")),
+            );
+        }
+    }
+
+    pub fn prepare_type_hierarchy(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        match self.clients.get(&buffer.language_id) {
+            Some(client) if client.can_type_hierarchy() => {
+                let uri = client.get_uri(buffer);
+                client.request_prepare_type_hierarchy(
+                    uri,
+                    position,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+            }
+            Some(_) => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!("the language server has no type hierarchy")),
+                );
+            }
+            None => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!("no language server to get the types from")),
+                );
+            }
+        }
+    }
+
+    /// The supertypes of `item`, or its subtypes when not `supertypes`.
+    pub fn get_type_hierarchy_types(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        item: TypeHierarchyItem,
+        supertypes: bool,
+    ) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            client.request_type_hierarchy_types(
+                item,
+                supertypes,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher.as_ref().unwrap().respond(
+                id,
+                Err(anyhow!("no language server to get the types from")),
+            );
+        }
+    }
+
+    pub fn rename(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+        new_name: String,
+    ) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            let uri = client.get_uri(buffer);
+            client.request_rename(
+                uri,
+                position,
+                new_name,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no language server to rename with")));
+        }
+    }
+
+    pub fn get_document_highlights(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            let uri = client.get_uri(buffer);
+            client.request_document_highlights(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no language server to highlight with")));
+        }
+    }
+
     pub fn get_references(
         &self,
         id: RequestId,
@@ -322,6 +776,30 @@ impl LspCatalog {
         }
     }
 
+    pub fn code_action_resolve(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        action: &CodeAction,
+    ) {
+        if let Some(client) = self.clients.get(&buffer.language_id) {
+            if !client.can_resolve_code_actions() {
+                // There's nothing more to the action than what came with it.
+                let action = serde_json::to_value(action).map_err(|e| anyhow!(e));
+                client.dispatcher.respond(id, action);
+                return;
+            }
+            client.request_code_action_resolve(action, move |lsp_client, result| {
+                lsp_client.dispatcher.respond(id, result);
+            });
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no language server to resolve with")));
+        }
+    }
+
     pub fn get_definition(
         &self,
         id: RequestId,
@@ -386,8 +864,12 @@ impl LspClient {
                 writer,
                 process,
                 pending: HashMap::new(),
+                completion_requests: HashMap::new(),
+                workspace_symbol_requests: HashMap::new(),
                 server_capabilities: None,
+                type_hierarchy_provider: false,
                 opened_documents: HashMap::new(),
+                semantic_tokens: HashMap::new(),
                 is_initialized: false,
             })),
         });
@@ -437,7 +919,9 @@ impl LspClient {
         state.next_id = 0;
         state.pending.clear();
         state.opened_documents.clear();
+        state.semantic_tokens.clear();
         state.server_capabilities = None;
+        state.type_hierarchy_provider = false;
         state.is_initialized = false;
         state.writer = writer;
         state.process = process;
@@ -511,6 +995,18 @@ impl LspClient {
                 );
             }
             "$/progress" => {
+                if let Some((request_id, symbols)) =
+                    workspace_symbol_progress(&params)
+                {
+                    self.dispatcher.send_notification(
+                        "workspace_symbols_progress",
+                        json!({
+                            "request_id": request_id,
+                            "symbols": symbols,
+                        }),
+                    );
+                    return;
+                }
                 self.dispatcher.send_notification(
                     "work_done_progress",
                     json!({
@@ -523,12 +1019,15 @@ impl LspClient {
     }
 
     pub fn handle_response(&self, id: u64, result: Result<Value>) {
-        let callback =
-            {
-                self.state.lock().pending.remove(&id).unwrap_or_else(|| {
-                    panic!("id {} missing from request table", id)
-                })
-            };
+        let callback = {
+            let mut state = self.state.lock();
+            state.completion_requests.remove(&id);
+            state.workspace_symbol_requests.remove(&id);
+            state
+                .pending
+                .remove(&id)
+                .unwrap_or_else(|| panic!("id {} missing from request table", id))
+        };
         callback.call(self, result);
     }
 
@@ -574,13 +1073,20 @@ impl LspClient {
             self.send_initialize(Some(root_url), move |lsp_client, result| {
                 if let Ok(result) = result {
                     {
+                        let type_hierarchy_provider = matches!(
+                            result["capabilities"].get("typeHierarchyProvider"),
+                            Some(Value::Bool(true)) | Some(Value::Object(_))
+                        );
                         let init_result: InitializeResult =
                             serde_json::from_value(result).unwrap();
                         let mut state = lsp_client.state.lock();
                         state.server_capabilities = Some(init_result.capabilities);
+                        state.type_hierarchy_provider = type_hierarchy_provider;
                         state.is_initialized = true;
                     }
                     lsp_client.send_initialized();
+                    lsp_client.send_on_type_formatting_triggers();
+                    lsp_client.send_completion_triggers();
                 }
                 let _ = sender.send(true);
             });
@@ -621,6 +1127,21 @@ impl LspClient {
         self.send_notification("textDocument/didOpen", params);
     }
 
+    pub fn send_did_close(&self, buffer_id: &BufferId) {
+        let uri = {
+            let mut state = self.state.lock();
+            state.semantic_tokens.remove(buffer_id);
+            state.opened_documents.remove(buffer_id)
+        };
+        if let Some(uri) = uri {
+            let params = DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri },
+            };
+            let params = Params::from(serde_json::to_value(params).unwrap());
+            self.send_notification("textDocument/didClose", params);
+        }
+    }
+
     pub fn send_did_save(&self, uri: Url) {
         let params = DidSaveTextDocumentParams {
             text_document: TextDocumentIdentifier { uri },
@@ -693,14 +1214,40 @@ impl LspClient {
                             ],
                         },
                     }),
+                    data_support: Some(true),
+                    resolve_support: Some(CodeActionCapabilityResolveSupport {
+                        properties: vec!["edit".to_string()],
+                    }),
                     ..Default::default()
                 }),
                 semantic_tokens: Some(SemanticTokensClientCapabilities {
+                    requests: SemanticTokensClientCapabilitiesRequests {
+                        range: None,
+                        full: Some(SemanticTokensFullOptions::Delta {
+                            delta: Some(true),
+                        }),
+                    },
+                    ..Default::default()
+                }),
+                rename: Some(RenameClientCapabilities {
+                    prepare_support: Some(true),
+                    ..Default::default()
+                }),
+                folding_range: Some(FoldingRangeClientCapabilities {
+                    line_folding_only: Some(true),
                     ..Default::default()
                 }),
+                call_hierarchy: Some(CallHierarchyClientCapabilities::default()),
 
                 ..Default::default()
             }),
+            workspace: Some(WorkspaceClientCapabilities {
+                workspace_edit: Some(WorkspaceEditClientCapabilities {
+                    document_changes: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
             window: Some(WindowClientCapabilities {
                 work_done_progress: Some(true),
                 show_message: Some(ShowMessageRequestClientCapabilities {
@@ -729,7 +1276,10 @@ impl LspClient {
             locale: None,
         };
 
-        let params = Params::from(serde_json::to_value(init_params).unwrap());
+        let mut params = serde_json::to_value(init_params).unwrap();
+        // Not in `TextDocumentClientCapabilities` yet.
+        params["capabilities"]["textDocument"]["typeHierarchy"] = json!({});
+        let params = Params::from(params);
         self.send_request("initialize", params, Box::new(on_init));
     }
 
@@ -746,23 +1296,225 @@ impl LspClient {
         self.send_request("textDocument/documentSymbol", params, Box::new(cb));
     }
 
-    pub fn request_document_formatting<CB>(&self, document_uri: Url, cb: CB)
-    where
-        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
-    {
+    fn can_resolve_code_actions(&self) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|c| c.code_action_provider.as_ref()),
+            Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                resolve_provider: Some(true),
+                ..
+            }))
+        )
+    }
+
+    fn can_search_workspace_symbols(&self) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|c| c.workspace_symbol_provider.as_ref()),
+            Some(OneOf::Left(true)) | Some(OneOf::Right(_))
+        )
+    }
+
+    /// Searches the symbols of the workspace for `query`, with the partial
+    /// results sent along the way tagged with the editor's `request_id`.
+    pub fn request_workspace_symbols<CB>(
+        &self,
+        request_id: usize,
+        query: String,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = WorkspaceSymbolParams {
+            query,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams {
+                partial_result_token: Some(ProgressToken::String(format!(
+                    "{WORKSPACE_SYMBOL_TOKEN}{request_id}"
+                ))),
+            },
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        let request = {
+            let mut state = self.state.lock();
+            let next_id = state.next_id;
+            state.pending.insert(next_id, Box::new(cb));
+            state.workspace_symbol_requests.insert(next_id, request_id);
+            state.next_id += 1;
+
+            JsonRpc::request_with_params(
+                Id::Num(next_id as i64),
+                "workspace/symbol",
+                params,
+            )
+        };
+
+        self.send_rpc(&to_value(&request).unwrap());
+    }
+
+    /// Asks the server to abort the workspace symbol requests made for the
+    /// editor's `request_id`, which a newer query has superseded.
+    pub fn cancel_workspace_symbols(&self, request_id: usize) {
+        let ids: Vec<u64> = {
+            let mut state = self.state.lock();
+            let ids = state
+                .workspace_symbol_requests
+                .iter()
+                .filter(|(_, r)| **r == request_id)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in &ids {
+                state.workspace_symbol_requests.remove(id);
+            }
+            ids
+        };
+        for id in ids {
+            let params = Params::from(json!({ "id": id }));
+            self.send_notification("$/cancelRequest", params);
+        }
+    }
+
+    pub fn request_folding_ranges<CB>(&self, document_uri: Url, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = FoldingRangeParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/foldingRange", params, Box::new(cb));
+    }
+
+    pub fn request_document_formatting<CB>(
+        &self,
+        document_uri: Url,
+        options: FormattingOptions,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
         let params = DocumentFormattingParams {
             text_document: TextDocumentIdentifier { uri: document_uri },
-            options: FormattingOptions {
-                tab_size: 4,
-                insert_spaces: true,
-                ..Default::default()
-            },
+            options,
             work_done_progress_params: WorkDoneProgressParams::default(),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
         self.send_request("textDocument/formatting", params, Box::new(cb));
     }
 
+    pub fn request_document_range_formatting<CB>(
+        &self,
+        document_uri: Url,
+        range: Range,
+        options: FormattingOptions,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            range,
+            options,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/rangeFormatting", params, Box::new(cb));
+    }
+
+    pub fn request_on_type_formatting<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        ch: String,
+        options: FormattingOptions,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentOnTypeFormattingParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            ch,
+            options,
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/onTypeFormatting", params, Box::new(cb));
+    }
+
+    /// The characters whose typing triggers formatting, as the server sent
+    /// them with its capabilities
+    fn on_type_formatting_triggers(&self) -> Vec<String> {
+        let state = self.state.lock();
+        let options = match state
+            .server_capabilities
+            .as_ref()
+            .and_then(|c| c.document_on_type_formatting_provider.as_ref())
+        {
+            Some(options) => options,
+            None => return Vec::new(),
+        };
+        let mut characters = vec![options.first_trigger_character.clone()];
+        characters.extend(options.more_trigger_character.iter().flatten().cloned());
+        characters
+    }
+
+    fn formats_on_type(&self, ch: &str) -> bool {
+        self.on_type_formatting_triggers().iter().any(|c| c == ch)
+    }
+
+    /// Tells the UI which characters trigger formatting, so it only asks for
+    /// the formatting after typing one of them.
+    fn send_on_type_formatting_triggers(&self) {
+        self.dispatcher.send_notification(
+            "on_type_formatting_triggers",
+            json!({
+                "language_id": self.language_id,
+                "characters": self.on_type_formatting_triggers(),
+            }),
+        );
+    }
+
+    /// Tells the UI which characters the server completes after, so it asks
+    /// for completion again when an accepted item ends in one of them.
+    fn send_completion_triggers(&self) {
+        let characters = self
+            .state
+            .lock()
+            .server_capabilities
+            .as_ref()
+            .and_then(|c| c.completion_provider.as_ref())
+            .and_then(|options| options.trigger_characters.clone())
+            .unwrap_or_default();
+        self.dispatcher.send_notification(
+            "completion_triggers",
+            json!({
+                "language_id": self.language_id,
+                "characters": characters,
+            }),
+        );
+    }
+
+    fn can_format_ranges(&self) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|c| c.document_range_formatting_provider.as_ref()),
+            Some(OneOf::Left(true)) | Some(OneOf::Right(_))
+        )
+    }
+
     pub fn request_semantic_tokens<CB>(&self, document_uri: Url, cb: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
@@ -776,6 +1528,114 @@ impl LspClient {
         self.send_request("textDocument/semanticTokens/full", params, Box::new(cb));
     }
 
+    pub fn request_semantic_tokens_delta<CB>(
+        &self,
+        document_uri: Url,
+        previous_result_id: String,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = SemanticTokensDeltaParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            previous_result_id,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request(
+            "textDocument/semanticTokens/full/delta",
+            params,
+            Box::new(cb),
+        );
+    }
+
+    /// The id of the last semantic tokens received for the buffer, if the
+    /// server can send the changes since them instead of all the tokens.
+    fn semantic_tokens_result_id(&self, buffer_id: &BufferId) -> Option<String> {
+        let state = self.state.lock();
+        let provider = state
+            .server_capabilities
+            .as_ref()?
+            .semantic_tokens_provider
+            .as_ref()?;
+        match semantic_tokens_full_options(provider)? {
+            SemanticTokensFullOptions::Delta { delta: Some(true) } => {
+                state.semantic_tokens.get(buffer_id)?.result_id.clone()
+            }
+            _ => None,
+        }
+    }
+
+    /// The tokens a delta response makes of the ones stored for the buffer,
+    /// or `None` if they can't be made, such as when the server reports an
+    /// error because it no longer has the previous result.
+    fn apply_semantic_tokens_delta(
+        &self,
+        buffer_id: &BufferId,
+        previous_result_id: &str,
+        result: Result<Value>,
+    ) -> Option<SemanticTokens> {
+        let delta: SemanticTokensFullDeltaResult =
+            serde_json::from_value(result.ok()?).ok()?;
+        let (result_id, edits) = match delta {
+            SemanticTokensFullDeltaResult::Tokens(tokens) => return Some(tokens),
+            SemanticTokensFullDeltaResult::TokensDelta(delta) => {
+                (delta.result_id, delta.edits)
+            }
+            SemanticTokensFullDeltaResult::PartialTokensDelta { .. } => return None,
+        };
+        let state = self.state.lock();
+        let previous = state.semantic_tokens.get(buffer_id)?;
+        if previous.result_id.as_deref() != Some(previous_result_id) {
+            return None;
+        }
+        Some(SemanticTokens {
+            result_id,
+            data: apply_semantic_tokens_edits(&previous.data, &edits)?,
+        })
+    }
+
+    /// Stores the tokens for the next delta request and sends the styles they
+    /// make up to the editor, if the buffer hasn't changed since.
+    fn publish_semantic_tokens(
+        &self,
+        buffer_id: BufferId,
+        path: PathBuf,
+        rev: u64,
+        len: usize,
+        tokens: SemanticTokens,
+    ) {
+        let buffers = self.dispatcher.buffers.lock();
+        let buffer = buffers.get(&buffer_id).unwrap();
+        let mut state = self.state.lock();
+        let styles = if buffer.rev == rev {
+            let semantic_tokens_provider = &state
+                .server_capabilities
+                .as_ref()
+                .unwrap()
+                .semantic_tokens_provider;
+            format_semantic_styles(buffer, semantic_tokens_provider, &tokens)
+        } else {
+            None
+        };
+        state.semantic_tokens.insert(buffer_id, tokens);
+        drop(state);
+
+        if let Some(styles) = styles {
+            self.dispatcher.send_notification(
+                "semantic_styles",
+                json!({
+                    "rev": rev,
+                    "buffer_id": buffer_id,
+                    "path": path,
+                    "styles": styles,
+                    "len": len,
+                }),
+            )
+        }
+    }
+
     pub fn request_code_actions<CB>(&self, document_uri: Url, range: Range, cb: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
@@ -814,6 +1674,172 @@ impl LspClient {
         self.send_request("textDocument/references", params, Box::new(cb));
     }
 
+    pub fn request_document_highlights<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentHighlightParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/documentHighlight", params, Box::new(cb));
+    }
+
+    pub fn request_prepare_rename<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            position,
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/prepareRename", params, Box::new(cb));
+    }
+
+    pub fn request_rename<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        new_name: String,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            new_name,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/rename", params, Box::new(cb));
+    }
+
+    fn can_call_hierarchy(&self) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|c| c.call_hierarchy_provider.as_ref()),
+            Some(CallHierarchyServerCapability::Simple(true))
+                | Some(CallHierarchyServerCapability::Options(_))
+        )
+    }
+
+    pub fn request_prepare_call_hierarchy<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = CallHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/prepareCallHierarchy", params, Box::new(cb));
+    }
+
+    pub fn request_call_hierarchy_calls<CB>(
+        &self,
+        item: CallHierarchyItem,
+        outgoing: bool,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let (method, params) = if outgoing {
+            let params = CallHierarchyOutgoingCallsParams {
+                item,
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            };
+            ("callHierarchy/outgoingCalls", serde_json::to_value(params))
+        } else {
+            let params = CallHierarchyIncomingCallsParams {
+                item,
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            };
+            ("callHierarchy/incomingCalls", serde_json::to_value(params))
+        };
+        let params = Params::from(params.unwrap());
+        self.send_request(method, params, Box::new(cb));
+    }
+
+    fn can_type_hierarchy(&self) -> bool {
+        self.state.lock().type_hierarchy_provider
+    }
+
+    pub fn request_prepare_type_hierarchy<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            position,
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/prepareTypeHierarchy", params, Box::new(cb));
+    }
+
+    pub fn request_type_hierarchy_types<CB>(
+        &self,
+        item: TypeHierarchyItem,
+        supertypes: bool,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let method = if supertypes {
+            "typeHierarchy/supertypes"
+        } else {
+            "typeHierarchy/subtypes"
+        };
+        let params = Params::from(json!({ "item": item }));
+        self.send_request(method, params, Box::new(cb));
+    }
+
+    fn can_prepare_rename(&self) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|c| c.rename_provider.as_ref()),
+            Some(OneOf::Right(RenameOptions {
+                prepare_provider: Some(true),
+                ..
+            }))
+        )
+    }
+
     pub fn request_definition<CB>(
         &self,
         document_uri: Url,
@@ -834,8 +1860,73 @@ impl LspClient {
         self.send_request("textDocument/definition", params, Box::new(cb));
     }
 
+    fn can_type_definition(&self) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|c| c.type_definition_provider.as_ref()),
+            Some(TypeDefinitionProviderCapability::Simple(true))
+                | Some(TypeDefinitionProviderCapability::Options(_))
+        )
+    }
+
+    pub fn request_type_definition<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/typeDefinition", params, Box::new(cb));
+    }
+
+    fn can_implementation(&self) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|c| c.implementation_provider.as_ref()),
+            Some(ImplementationProviderCapability::Simple(true))
+                | Some(ImplementationProviderCapability::Options(_))
+        )
+    }
+
+    pub fn request_implementation<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/implementation", params, Box::new(cb));
+    }
+
     pub fn request_completion<CB>(
         &self,
+        request_id: usize,
         document_uri: Url,
         position: Position,
         on_completion: CB,
@@ -852,11 +1943,56 @@ impl LspClient {
             context: None,
         };
         let params = Params::from(serde_json::to_value(completion_params).unwrap());
-        self.send_request(
-            "textDocument/completion",
-            params,
-            Box::new(on_completion),
-        );
+        let request = {
+            let mut state = self.state.lock();
+            let next_id = state.next_id;
+            state.pending.insert(next_id, Box::new(on_completion));
+            state.completion_requests.insert(next_id, request_id);
+            state.next_id += 1;
+
+            JsonRpc::request_with_params(
+                Id::Num(next_id as i64),
+                "textDocument/completion",
+                params,
+            )
+        };
+
+        self.send_rpc(&to_value(&request).unwrap());
+    }
+
+    /// Asks the server to abort the completion requests made for the
+    /// editor's `request_id`, which a newer request has superseded.
+    pub fn cancel_completion(&self, request_id: usize) {
+        let ids: Vec<u64> = {
+            let mut state = self.state.lock();
+            let ids = state
+                .completion_requests
+                .iter()
+                .filter(|(_, r)| **r == request_id)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in &ids {
+                state.completion_requests.remove(id);
+            }
+            ids
+        };
+        for id in ids {
+            let params = Params::from(json!({ "id": id }));
+            self.send_notification("$/cancelRequest", params);
+        }
+    }
+
+    pub fn execute_command<CB>(&self, command: &lsp_types::Command, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = ExecuteCommandParams {
+            command: command.command.clone(),
+            arguments: command.arguments.clone().unwrap_or_default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("workspace/executeCommand", params, Box::new(cb));
     }
 
     pub fn completion_resolve<CB>(
@@ -870,6 +2006,14 @@ impl LspClient {
         self.send_request("completionItem/resolve", params, Box::new(on_result));
     }
 
+    pub fn request_code_action_resolve<CB>(&self, action: &CodeAction, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = Params::from(serde_json::to_value(action).unwrap());
+        self.send_request("codeAction/resolve", params, Box::new(cb));
+    }
+
     pub fn request_hover<CB>(&self, document_uri: Url, position: Position, cb: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
@@ -957,6 +2101,19 @@ pub enum LspHeader {
     ContentLength(usize),
 }
 
+/// The editor's request id and the symbols of a partial workspace symbol
+/// result sent as progress.
+fn workspace_symbol_progress(params: &Params) -> Option<(usize, Value)> {
+    let params = serde_json::to_value(params).ok()?;
+    let request_id = params
+        .get("token")?
+        .as_str()?
+        .strip_prefix(WORKSPACE_SYMBOL_TOKEN)?
+        .parse()
+        .ok()?;
+    Some((request_id, params.get("value")?.clone()))
+}
+
 fn number_from_id(id: &Id) -> u64 {
     match *id {
         Id::Num(n) => n as u64,
@@ -1043,9 +2200,8 @@ pub fn get_change_for_sync_kind(
 fn format_semantic_styles(
     buffer: &Buffer,
     semantic_tokens_provider: &Option<SemanticTokensServerCapabilities>,
-    value: Value,
+    semantic_tokens: &SemanticTokens,
 ) -> Option<Vec<LineStyle>> {
-    let semantic_tokens: SemanticTokens = serde_json::from_value(value).ok()?;
     let semantic_tokens_provider = semantic_tokens_provider.as_ref()?;
     let semantic_lengends = semantic_tokens_lengend(semantic_tokens_provider);
 
@@ -1091,3 +2247,117 @@ fn semantic_tokens_lengend(
         ) => options.semantic_tokens_options.legend.clone(),
     }
 }
+
+fn semantic_tokens_full_options(
+    semantic_tokens_provider: &SemanticTokensServerCapabilities,
+) -> Option<&SemanticTokensFullOptions> {
+    match semantic_tokens_provider {
+        SemanticTokensServerCapabilities::SemanticTokensOptions(options) => {
+            options.full.as_ref()
+        }
+        SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
+            options,
+        ) => options.semantic_tokens_options.full.as_ref(),
+    }
+}
+
+/// Applies the edits of a semantic tokens delta to the previous tokens. The
+/// edits index into the integers the tokens are sent as, five for each token.
+fn apply_semantic_tokens_edits(
+    tokens: &[SemanticToken],
+    edits: &[SemanticTokensEdit],
+) -> Option<Vec<SemanticToken>> {
+    let mut edits = edits.iter().collect::<Vec<_>>();
+    edits.sort_by_key(|edit| edit.start);
+
+    let mut data = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    for edit in edits {
+        if edit.start % 5 != 0 || edit.delete_count % 5 != 0 {
+            return None;
+        }
+        let start = edit.start as usize / 5;
+        let end = start + edit.delete_count as usize / 5;
+        if start < index || end > tokens.len() {
+            return None;
+        }
+        data.extend_from_slice(&tokens[index..start]);
+        if let Some(inserted) = edit.data.as_ref() {
+            data.extend_from_slice(inserted);
+        }
+        index = end;
+    }
+    data.extend_from_slice(&tokens[index..]);
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(delta_line: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start: 0,
+            length: 1,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    fn edit(
+        start: u32,
+        delete_count: u32,
+        data: Option<Vec<SemanticToken>>,
+    ) -> SemanticTokensEdit {
+        SemanticTokensEdit {
+            start,
+            delete_count,
+            data,
+        }
+    }
+
+    #[test]
+    fn test_semantic_tokens_insert() {
+        let tokens = vec![token(0), token(1), token(2)];
+        let edits = [edit(5, 0, Some(vec![token(9)]))];
+        assert_eq!(
+            apply_semantic_tokens_edits(&tokens, &edits),
+            Some(vec![token(0), token(9), token(1), token(2)])
+        );
+    }
+
+    #[test]
+    fn test_semantic_tokens_delete() {
+        let tokens = vec![token(0), token(1), token(2)];
+        let edits = [edit(10, 5, None)];
+        assert_eq!(
+            apply_semantic_tokens_edits(&tokens, &edits),
+            Some(vec![token(0), token(1)])
+        );
+    }
+
+    #[test]
+    fn test_semantic_tokens_replace() {
+        let tokens = vec![token(0), token(1), token(2)];
+        // The edits apply in order of where they start.
+        let edits = [
+            edit(15, 0, Some(vec![token(3)])),
+            edit(0, 5, Some(vec![token(7)])),
+        ];
+        assert_eq!(
+            apply_semantic_tokens_edits(&tokens, &edits),
+            Some(vec![token(7), token(1), token(2), token(3)])
+        );
+
+        // Edits off the token boundaries or past the end are rejected.
+        assert_eq!(
+            apply_semantic_tokens_edits(&tokens, &[edit(3, 0, None)]),
+            None
+        );
+        assert_eq!(
+            apply_semantic_tokens_edits(&tokens, &[edit(10, 10, None)]),
+            None
+        );
+    }
+}
@@ -3,14 +3,16 @@ use home::home_dir;
 use hotwatch::Hotwatch;
 use lapce_rpc::counter::Counter;
 use lapce_rpc::plugin::{PluginDescription, PluginId, PluginInfo};
+use parking_lot::Mutex;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use toml;
@@ -31,6 +33,8 @@ pub(crate) struct PluginEnv {
     wasi_env: WasiEnv,
     desc: PluginDescription,
     dispatcher: Dispatcher,
+    /// The languages of the lsp servers this plugin has started.
+    language_ids: Arc<Mutex<Vec<String>>>,
 }
 
 #[derive(Clone)]
@@ -43,6 +47,9 @@ pub struct PluginCatalog {
     id_counter: Counter,
     pub items: HashMap<PluginName, PluginDescription>,
     plugins: HashMap<PluginName, PluginNew>,
+    disabled: HashSet<PluginName>,
+    /// The plugins in the registry, fetched the first time it's searched
+    registry: Option<Arc<Vec<PluginDescription>>>,
     store: wasmer::Store,
 }
 
@@ -52,6 +59,8 @@ impl PluginCatalog {
             id_counter: Counter::new(),
             items: HashMap::new(),
             plugins: HashMap::new(),
+            disabled: HashSet::new(),
+            registry: None,
             store: wasmer::Store::default(),
         }
     }
@@ -120,14 +129,49 @@ impl PluginCatalog {
             .to_str()
             .ok_or_else(|| anyhow!("path can't to string"))?
             .to_string();
-        let p = self.start_plugin(dispatcher, plugin.clone())?;
         self.items.insert(plugin.name.clone(), plugin.clone());
-        self.plugins.insert(plugin.name, p);
+        if !self.disabled.contains(&plugin.name) {
+            let p = self.start_plugin(dispatcher, plugin.clone())?;
+            self.plugins.insert(plugin.name, p);
+        }
+        Ok(())
+    }
+
+    /// Stops the plugin without removing it, and keeps it from being started
+    /// again until it's enabled. Returns the languages of the lsp servers the
+    /// plugin had started, so that they can be stopped as well.
+    pub fn disable_plugin(&mut self, name: &str) -> Vec<String> {
+        self.disabled.insert(name.to_string());
+        self.plugins
+            .remove(name)
+            .map(|plugin| plugin.env.language_ids.lock().clone())
+            .unwrap_or_default()
+    }
+
+    pub fn enable_plugin(
+        &mut self,
+        dispatcher: Dispatcher,
+        name: &str,
+    ) -> Result<()> {
+        self.disabled.remove(name);
+        if self.plugins.contains_key(name) {
+            return Ok(());
+        }
+        let plugin = self
+            .items
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("plugin {} isn't installed", name))?;
+        let p = self.start_plugin(dispatcher, plugin)?;
+        self.plugins.insert(name.to_string(), p);
         Ok(())
     }
 
     pub fn start_all(&mut self, dispatcher: Dispatcher) {
         for (_, plugin) in self.items.clone().iter() {
+            if self.disabled.contains(&plugin.name) {
+                continue;
+            }
             if let Ok(p) = self.start_plugin(dispatcher.clone(), plugin.clone()) {
                 self.plugins.insert(plugin.name.clone(), p);
             }
@@ -154,6 +198,7 @@ impl PluginCatalog {
             wasi_env,
             desc: plugin_desc.clone(),
             dispatcher,
+            language_ids: Arc::new(Mutex::new(Vec::new())),
         };
         let lapce = lapce_exports(&self.store, &plugin_env);
         let instance = wasmer::Instance::new(&module, &lapce.chain_back(wasi))?;
@@ -254,6 +299,7 @@ fn host_handle_notification(plugin_env: &PluginEnv) {
                     &language_id,
                     options,
                 );
+                plugin_env.language_ids.lock().push(language_id);
             }
             PluginNotification::DownloadFile { url, path } => {
                 let mut resp = ureq::get(&url)
@@ -336,6 +382,58 @@ pub struct PluginHandler {
     dispatcher: Dispatcher,
 }
 
+/// Finds the plugins in the registry matching `query`. The registry is
+/// fetched without holding the catalog's lock, since that can take a while.
+pub fn search_registry(
+    catalog: &Mutex<PluginCatalog>,
+    query: &str,
+) -> Result<Vec<PluginDescription>> {
+    let registry = catalog.lock().registry.clone();
+    let registry = match registry {
+        Some(registry) => registry,
+        None => {
+            let registry = Arc::new(load_registry()?);
+            catalog.lock().registry = Some(registry.clone());
+            registry
+        }
+    };
+    Ok(registry
+        .iter()
+        .filter(|plugin| registry_matches(plugin, query))
+        .cloned()
+        .collect())
+}
+
+fn registry_matches(plugin: &PluginDescription, query: &str) -> bool {
+    let text = format!(
+        "{} {} {}",
+        plugin.name, plugin.display_name, plugin.description
+    )
+    .to_lowercase();
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .all(|word| text.contains(word))
+}
+
+fn load_registry() -> Result<Vec<PluginDescription>> {
+    let repositories: Vec<String> =
+        ureq::get("https://lapce.github.io/plugins.json")
+            .call()?
+            .into_json()?;
+    Ok(repositories
+        .iter()
+        .filter_map(|repository| {
+            let url = format!(
+                "https://raw.githubusercontent.com/{}/master/plugin.toml",
+                repository
+            );
+            let content = ureq::get(&url).call().ok()?.into_string().ok()?;
+            toml::from_str(&content).ok()
+        })
+        .collect())
+}
+
 fn find_all_plugins() -> Vec<PathBuf> {
     let mut plugin_paths = Vec::new();
     let home = home_dir().unwrap();
@@ -365,3 +463,27 @@ fn load_plugin(path: &Path) -> Result<PluginDescription> {
         .to_string();
     Ok(plugin)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_matches() {
+        let plugin = PluginDescription {
+            name: "lapce-rust".to_string(),
+            version: "0.1.0".to_string(),
+            display_name: "Rust".to_string(),
+            author: "Lapce".to_string(),
+            description: "Rust language support for Lapce".to_string(),
+            repository: "lapce/lapce-rust".to_string(),
+            wasm: "lapce-rust.wasm".to_string(),
+            dir: None,
+            configuration: None,
+        };
+        assert!(registry_matches(&plugin, ""));
+        assert!(registry_matches(&plugin, "rust"));
+        assert!(registry_matches(&plugin, "Language RUST"));
+        assert!(!registry_matches(&plugin, "rust python"));
+    }
+}
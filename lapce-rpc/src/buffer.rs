@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::counter::Counter;
 
@@ -12,9 +13,41 @@ impl BufferId {
     }
 }
 
+/// The encoding a file is read and written in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEncoding {
+    /// The name of the encoding, such as `UTF-8` or `windows-1252`
+    pub name: String,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+}
+
+impl FileEncoding {
+    /// The name of the encoding, telling whether there's a byte order mark
+    /// for the ones that can go either way.
+    pub fn display_name(&self) -> String {
+        if self.bom && self.name == "UTF-8" {
+            "UTF-8 with BOM".to_string()
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+impl Default for FileEncoding {
+    fn default() -> Self {
+        Self {
+            name: "UTF-8".to_string(),
+            bom: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewBufferResponse {
     pub content: String,
+    #[serde(default)]
+    pub encoding: FileEncoding,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,3 +55,22 @@ pub struct BufferHeadResponse {
     pub id: String,
     pub content: String,
 }
+
+/// Why the proxy failed to write a buffer to disk, sent as the `data` of the
+/// error response so the editor can offer a way to recover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SaveErrorKind {
+    PermissionDenied,
+    Other,
+}
+
+/// The response to formatting a range of a buffer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeFormattingResponse {
+    /// Whether the language server can't format ranges, so `edits` format the
+    /// whole document instead
+    pub whole_document: bool,
+    /// The edits as the language server sent them
+    pub edits: Value,
+}
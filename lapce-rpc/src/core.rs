@@ -1,10 +1,14 @@
-use lsp_types::{ProgressParams, PublishDiagnosticsParams};
+use lsp_types::{ProgressParams, PublishDiagnosticsParams, SymbolInformation};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, path::PathBuf};
 
 use crate::{
-    buffer::BufferId, file::FileNodeItem, plugin::PluginDescription,
-    source_control::DiffInfo, style::LineStyle, terminal::TermId,
+    buffer::BufferId,
+    file::{FileChangeKind, FileNodeItem},
+    plugin::PluginDescription,
+    source_control::DiffInfo,
+    style::LineStyle,
+    terminal::TermId,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,18 +34,39 @@ pub enum CoreNotification {
     WorkDoneProgress {
         progress: ProgressParams,
     },
+    /// Workspace symbols a language server found before answering the
+    /// request with `request_id`
+    WorkspaceSymbolsProgress {
+        request_id: usize,
+        symbols: Vec<SymbolInformation>,
+    },
     HomeDir {
         path: PathBuf,
     },
     InstalledPlugins {
         plugins: HashMap<String, PluginDescription>,
     },
+    /// The characters that make the language server of `language_id`
+    /// format the code after they're typed
+    OnTypeFormattingTriggers {
+        language_id: String,
+        characters: Vec<String>,
+    },
+    /// The characters after which the language server of `language_id`
+    /// completes
+    CompletionTriggers {
+        language_id: String,
+        characters: Vec<String>,
+    },
     ListDir {
         items: Vec<FileNodeItem>,
     },
     DiffFiles {
         files: Vec<PathBuf>,
     },
+    FileChanges {
+        changes: Vec<(PathBuf, FileChangeKind)>,
+    },
     DiffInfo {
         diff: DiffInfo,
     },
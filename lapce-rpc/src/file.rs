@@ -6,6 +6,16 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
+/// How a path in the workspace changed on disk.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Created,
+    Deleted,
+    /// The path is the new name of a file or directory that was moved.
+    Renamed,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FileNodeItem {
     pub path_buf: PathBuf,
@@ -86,3 +96,56 @@ impl FileNodeItem {
         children
     }
 }
+
+/// How the global search matches its pattern, which is otherwise taken
+/// literally. Matching always ignores case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub whole_word: bool,
+}
+
+/// The lines the global search matched by file, each with its 1-based
+/// number, the range of the first match in it, and its text
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlobalSearchResponse {
+    pub matches: HashMap<PathBuf, Vec<(usize, (usize, usize), String)>>,
+    /// The lines around the matches that didn't match themselves, by file
+    pub context: HashMap<PathBuf, Vec<(usize, String)>>,
+}
+
+/// A change to the files of the workspace, as a workspace edit from a
+/// language server asks for.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOperation {
+    /// Creates an empty file. A file that exists already is emptied with
+    /// `overwrite`, or else left alone with `ignore_if_exists`.
+    Create {
+        path: PathBuf,
+        overwrite: bool,
+        ignore_if_exists: bool,
+    },
+    /// Moves a file or directory, replacing what's at `to` by the same rules
+    /// as creating a file.
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        overwrite: bool,
+        ignore_if_exists: bool,
+    },
+    /// Deletes a file, or a directory and what's in it with `recursive`.
+    Delete {
+        path: PathBuf,
+        recursive: bool,
+        ignore_if_not_exists: bool,
+    },
+}
+
+/// The files a global replace was written to, and those left alone because
+/// they changed after the search ran
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlobalReplaceResponse {
+    pub replaced: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+}
@@ -179,6 +179,19 @@ impl RpcHandler {
         self.send_rpc_request_common(method, params, ResponseHandler::Callback(f));
     }
 
+    /// Fails every request still waiting for a response, such as when the
+    /// connection they were sent over is gone. A response that arrives for
+    /// one of them afterwards is ignored.
+    pub fn clear_pending(&self) {
+        let pending: Vec<ResponseHandler> = {
+            let mut pending = self.pending.lock();
+            pending.drain().map(|(_, rh)| rh).collect()
+        };
+        for rh in pending {
+            rh.invoke(Err(json!("connection closed")));
+        }
+    }
+
     fn handle_response(&self, id: u64, resp: Result<Value, Value>) {
         let handler = {
             let mut pending = self.pending.lock();
@@ -1,14 +1,25 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
-use lsp_types::{CompletionItem, Position};
+use lsp_types::{
+    CallHierarchyItem, CodeAction, Command, CompletionItem, FormattingOptions,
+    Position, Range,
+};
 use serde::{Deserialize, Serialize};
 use xi_rope::RopeDelta;
 
 use crate::{
-    buffer::BufferId, plugin::PluginDescription, source_control::FileDiff,
+    buffer::BufferId,
+    file::{FileOperation, SearchOptions},
+    plugin::PluginDescription,
+    source_control::FileDiff,
     terminal::TermId,
 };
 
+/// An item of a type hierarchy. The version of lsp-types in use doesn't have
+/// type hierarchies yet, but their items have the same fields as the ones of
+/// call hierarchies.
+pub type TypeHierarchyItem = CallHierarchyItem;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "method", content = "params")]
@@ -30,6 +41,12 @@ pub enum ProxyNotification {
     InstallPlugin {
         plugin: PluginDescription,
     },
+    DisablePlugin {
+        name: String,
+    },
+    EnablePlugin {
+        name: String,
+    },
     GitCommit {
         message: String,
         diffs: Vec<FileDiff>,
@@ -46,6 +63,35 @@ pub enum ProxyNotification {
     TerminalClose {
         term_id: TermId,
     },
+    CancelCompletion {
+        request_id: usize,
+        buffer_id: BufferId,
+    },
+    CancelWorkspaceSymbols {
+        request_id: usize,
+    },
+    ExecuteCommand {
+        buffer_id: BufferId,
+        command: Command,
+    },
+    /// Opens a buffer again after reconnecting, with the content and
+    /// revision it has in the editor
+    ReopenBuffer {
+        buffer_id: BufferId,
+        path: PathBuf,
+        rev: u64,
+        content: String,
+    },
+    /// Has the language server of `language_id` serve the buffer, or the
+    /// one of the language detected from its path when it's `None`
+    SetLanguage {
+        buffer_id: BufferId,
+        language_id: Option<String>,
+    },
+    /// Drops the buffer, which no editor shows any more
+    CloseBuffer {
+        buffer_id: BufferId,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,13 +106,41 @@ pub enum ProxyRequest {
         buffer_id: BufferId,
         path: PathBuf,
     },
+    /// Reads the buffer's file again as `encoding`, which is used to write
+    /// it from then on
+    ReopenWithEncoding {
+        buffer_id: BufferId,
+        encoding: String,
+    },
     GetCompletion {
         request_id: usize,
         buffer_id: BufferId,
         position: Position,
     },
+    /// Searches the workspace for `pattern`, returning up to `context_lines`
+    /// lines before and after each match as well. Non-empty `globs` limit the
+    /// search to the paths they match, or skip them if they start with `!`.
     GlobalSearch {
         pattern: String,
+        options: SearchOptions,
+        context_lines: usize,
+        globs: Vec<String>,
+    },
+    /// Replaces `pattern` in the given lines of files that aren't open, each
+    /// line being its 1-based number and the text it had when searched.
+    GlobalReplace {
+        pattern: String,
+        replacement: String,
+        options: SearchOptions,
+        lines: HashMap<PathBuf, Vec<(usize, String)>>,
+    },
+    ApplyFileOperation {
+        operation: FileOperation,
+    },
+    /// Finds the plugins in the registry whose name or description contain
+    /// every word of `query`.
+    SearchPlugins {
+        query: String,
     },
     CompletionResolve {
         buffer_id: BufferId,
@@ -85,20 +159,95 @@ pub enum ProxyRequest {
         buffer_id: BufferId,
         position: Position,
     },
+    GetDocumentHighlights {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    PrepareRename {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    Rename {
+        buffer_id: BufferId,
+        position: Position,
+        new_name: String,
+    },
     GetDefinition {
         request_id: usize,
         buffer_id: BufferId,
         position: Position,
     },
+    GetTypeDefinition {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    GetImplementation {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    /// The items at `position` to show the call hierarchy of
+    PrepareCallHierarchy {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    /// The calls made to `item`, asked the language server of the buffer the
+    /// hierarchy was prepared in
+    CallHierarchyIncomingCalls {
+        buffer_id: BufferId,
+        item: CallHierarchyItem,
+    },
+    /// The calls `item` makes
+    CallHierarchyOutgoingCalls {
+        buffer_id: BufferId,
+        item: CallHierarchyItem,
+    },
+    /// The items at `position` to show the type hierarchy of
+    PrepareTypeHierarchy {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    TypeHierarchySupertypes {
+        buffer_id: BufferId,
+        item: TypeHierarchyItem,
+    },
+    TypeHierarchySubtypes {
+        buffer_id: BufferId,
+        item: TypeHierarchyItem,
+    },
     GetCodeActions {
         buffer_id: BufferId,
         position: Position,
     },
+    /// Fills in what a code action does, for actions that came without it
+    CodeActionResolve {
+        buffer_id: BufferId,
+        action: Box<CodeAction>,
+    },
     GetDocumentSymbols {
         buffer_id: BufferId,
     },
+    /// Searches the symbols of the whole workspace for `query`
+    GetWorkspaceSymbols {
+        request_id: usize,
+        query: String,
+    },
+    GetFoldingRanges {
+        buffer_id: BufferId,
+    },
     GetDocumentFormatting {
         buffer_id: BufferId,
+        options: FormattingOptions,
+    },
+    GetDocumentRangeFormatting {
+        buffer_id: BufferId,
+        range: Range,
+        options: FormattingOptions,
+    },
+    GetOnTypeFormatting {
+        buffer_id: BufferId,
+        position: Position,
+        ch: String,
+        options: FormattingOptions,
     },
     GetFiles {
         path: String,
@@ -110,4 +259,18 @@ pub enum ProxyRequest {
         rev: u64,
         buffer_id: BufferId,
     },
+    SaveAs {
+        rev: u64,
+        buffer_id: BufferId,
+        path: PathBuf,
+    },
+    SaveElevated {
+        rev: u64,
+        buffer_id: BufferId,
+        helper: String,
+    },
+    SaveOverwritePermissions {
+        rev: u64,
+        buffer_id: BufferId,
+    },
 }
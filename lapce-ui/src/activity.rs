@@ -1,23 +1,143 @@
+use std::sync::Arc;
+
 use druid::{
-    BoxConstraints, Command, Cursor, Env, Event, EventCtx, LayoutCtx, LifeCycle,
-    LifeCycleCtx, PaintCtx, Point, RenderContext, Size, Target, UpdateCtx, Widget,
+    kurbo::Line,
+    piet::{Text, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Color, Command, Cursor, Env, Event, EventCtx, FontFamily,
+    LayoutCtx, LifeCycle, LifeCycleCtx, MouseButton, PaintCtx, Point, Rect,
+    RenderContext, Size, Target, UpdateCtx, Widget,
 };
 use lapce_data::{
     command::{
-        CommandTarget, LapceCommandNew, LapceWorkbenchCommand, LAPCE_NEW_COMMAND,
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND,
     },
     config::LapceTheme,
-    data::LapceTabData,
+    data::{DragContent, LapceTabData, PanelKind},
     panel::PanelPosition,
 };
 use serde_json::json;
+
 use crate::svg::get_svg;
 
-pub struct ActivityBar {}
+/// How far an icon has to be moved with the mouse down before it's dragged
+const DRAG_THRESHOLD: f64 = 4.0;
+
+pub struct ActivityBar {
+    mouse_pos: Point,
+    /// The index of the icon the left button went down on and where
+    mouse_down: Option<(usize, Point)>,
+}
 
 impl ActivityBar {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            mouse_pos: Point::ZERO,
+            mouse_down: None,
+        }
+    }
+
+    /// The index an icon dragged to `pos` would be dropped at.
+    fn drop_index(data: &LapceTabData, pos: Point) -> usize {
+        let n = data
+            .panels
+            .get(&PanelPosition::LeftTop)
+            .map(|panel| panel.widgets.len())
+            .unwrap_or(0);
+        ((pos.y / 50.0).round().max(0.0) as usize).min(n)
+    }
+
+    fn start_drag(&mut self, data: &mut LapceTabData, pos: Point) {
+        let (index, down) = match self.mouse_down {
+            Some(mouse_down) => mouse_down,
+            None => return,
+        };
+        if pos.distance(down) < DRAG_THRESHOLD {
+            return;
+        }
+        self.mouse_down = None;
+        let kind = match data
+            .panels
+            .get(&PanelPosition::LeftTop)
+            .and_then(|panel| panel.widgets.get(index))
+        {
+            Some(kind) => *kind,
+            None => return,
+        };
+        let offset = down - Point::new(0.0, 50.0 * index as f64);
+        *Arc::make_mut(&mut data.drag) =
+            Some((offset, DragContent::Panel(kind, index)));
+    }
+}
+
+impl ActivityBar {
+    /// The count shown on the icon of a panel and its color, if it has one.
+    fn badge<'a>(
+        data: &'a LapceTabData,
+        kind: &PanelKind,
+    ) -> Option<(usize, &'a Color)> {
+        let (count, color) = match kind {
+            PanelKind::Problem => {
+                let errors = data.main_split.error_count;
+                let color = if errors > 0 {
+                    LapceTheme::LAPCE_ERROR
+                } else {
+                    LapceTheme::LAPCE_WARN
+                };
+                (errors + data.main_split.warning_count, color)
+            }
+            PanelKind::SourceControl => (
+                data.source_control.file_diffs.len(),
+                LapceTheme::SOURCE_CONTROL_MODIFIED,
+            ),
+            PanelKind::Plugin => {
+                (data.plugin.updates.len(), LapceTheme::SOURCE_CONTROL_ADDED)
+            }
+            _ => return None,
+        };
+        if count == 0 {
+            return None;
+        }
+        Some((count, data.config.get_color_unchecked(color)))
+    }
+
+    fn paint_badge(
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        icon: Rect,
+        count: usize,
+        color: &Color,
+    ) {
+        let text = if count > 99 {
+            "99+".to_string()
+        } else {
+            count.to_string()
+        };
+        let text_layout = ctx
+            .text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, 10.0)
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::PANEL_BACKGROUND)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        let text_size = text_layout.size();
+        let height = text_size.height.max(14.0);
+        let width = (text_size.width + 6.0).max(height);
+        let badge = Size::new(width, height)
+            .to_rect()
+            .with_origin(Point::new(icon.x1 - width / 2.0, icon.y1 - height / 2.0));
+        ctx.fill(badge.to_rounded_rect(height / 2.0), color);
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                badge.x0 + (width - text_size.width) / 2.0,
+                badge.y0 + (height - text_size.height) / 2.0,
+            ),
+        );
     }
 }
 
@@ -39,6 +159,7 @@ impl Widget<LapceTabData> for ActivityBar {
             Event::MouseDown(mouse) => {
                 if mouse.button.is_left() {
                     let index = (mouse.pos.y / 50.0) as usize;
+                    self.mouse_down = Some((index, mouse.pos));
                     if let Some(panel) = data.panels.get_mut(&PanelPosition::LeftTop)
                     {
                         if let Some(kind) = panel.widgets.get(index) {
@@ -72,7 +193,29 @@ impl Widget<LapceTabData> for ActivityBar {
                     }
                 }
             }
+            Event::MouseUp(mouse) => {
+                self.mouse_down = None;
+                if let Some((_, DragContent::Panel(kind, _))) = data.drag.as_ref() {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::MovePanel(
+                            *kind,
+                            Self::drop_index(data, mouse.pos),
+                        ),
+                        Target::Widget(data.id),
+                    ));
+                }
+            }
             Event::MouseMove(mouse) => {
+                self.mouse_pos = mouse.pos;
+                if !mouse.buttons.contains(MouseButton::Left) {
+                    self.mouse_down = None;
+                } else if data.drag.is_none() {
+                    self.start_drag(data, mouse.pos);
+                }
+                if matches!(data.drag.as_ref(), Some((_, DragContent::Panel(..)))) {
+                    ctx.request_paint();
+                }
                 let n = data
                     .panels
                     .get(&PanelPosition::LeftTop)
@@ -99,11 +242,20 @@ impl Widget<LapceTabData> for ActivityBar {
 
     fn update(
         &mut self,
-        _ctx: &mut UpdateCtx,
-        _old_data: &LapceTabData,
-        _data: &LapceTabData,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
         _env: &Env,
     ) {
+        if !old_data.drag.same(&data.drag)
+            || old_data.main_split.error_count != data.main_split.error_count
+            || old_data.main_split.warning_count != data.main_split.warning_count
+            || old_data.source_control.file_diffs.len()
+                != data.source_control.file_diffs.len()
+            || old_data.plugin.updates.len() != data.plugin.updates.len()
+        {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -198,8 +350,23 @@ impl Widget<LapceTabData> for ActivityBar {
                             (size - svg_size) / 2.0 + offset,
                         ));
                 ctx.draw_svg(&svg, rect, Some(&svg_color));
+                if let Some((count, color)) = Self::badge(data, kind) {
+                    Self::paint_badge(ctx, data, rect, count, color);
+                }
                 offset += size;
             }
         }
+
+        // Where a dragged icon would be dropped
+        if ctx.is_hot()
+            && matches!(data.drag.as_ref(), Some((_, DragContent::Panel(..))))
+        {
+            let y = Self::drop_index(data, self.mouse_pos) as f64 * size;
+            ctx.stroke(
+                Line::new(Point::new(0.0, y), Point::new(size, y)),
+                data.config.get_color_unchecked(LapceTheme::EDITOR_CARET),
+                2.0,
+            );
+        }
     }
 }
@@ -1,23 +1,24 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use druid::{
-    BoxConstraints, Command, Data, Env, Event, EventCtx, FontDescriptor, FontFamily,
-    LayoutCtx, LifeCycle, LifeCycleCtx, Modifiers, PaintCtx, Point, Rect,
-    RenderContext, Size, Target, TextLayout, UpdateCtx, Widget,
+    kurbo::Line, BoxConstraints, Command, Data, Env, Event, EventCtx,
+    FontDescriptor, FontFamily, FontWeight, LayoutCtx, LifeCycle, LifeCycleCtx,
+    Modifiers, PaintCtx, Point, Rect, RenderContext, Size, Target, TextLayout,
+    UpdateCtx, Widget,
 };
 use lapce_data::{
-    buffer::{BufferContent, EditType},
+    buffer::BufferContent,
+    code_action::{code_action_rows, is_preferred, CodeActionRow},
     command::{CommandExecuted, LapceCommand, LapceUICommand, LAPCE_UI_COMMAND},
     config::{Config, LapceTheme},
     data::{LapceMainSplitData, LapceTabData},
     keypress::KeyPressFocus,
-    movement::{Movement, Selection},
+    movement::Movement,
     state::Mode,
 };
-use lsp_types::{
-    CodeActionOrCommand, DocumentChangeOperation, DocumentChanges, OneOf, TextEdit,
-    Url, WorkspaceEdit,
-};
+use lsp_types::CodeActionOrCommand;
+
+use crate::svg::code_action_svg;
 
 pub struct CodeAction {}
 
@@ -62,7 +63,7 @@ impl KeyPressFocus for CodeActionData {
                 self.previous(ctx);
             }
             LapceCommand::ListSelect => {
-                self.select();
+                self.select(ctx);
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
                     LapceUICommand::CancelCodeActions,
@@ -90,7 +91,7 @@ impl CodeActionData {
             let prev_offset = buffer.prev_code_boundary(offset);
             let empty_vec = Vec::new();
             let code_actions =
-                buffer.code_actions.get(&prev_offset).unwrap_or(&empty_vec);
+                buffer.code_actions(prev_offset).unwrap_or(&empty_vec);
 
             self.main_split.current_code_actions = Movement::Down.update_index(
                 self.main_split.current_code_actions,
@@ -101,7 +102,7 @@ impl CodeActionData {
         }
     }
 
-    pub fn select(&mut self) {
+    pub fn select(&mut self, ctx: &mut EventCtx) {
         let editor = self.main_split.active_editor();
         let editor = match editor {
             Some(editor) => editor,
@@ -113,7 +114,7 @@ impl CodeActionData {
             let prev_offset = buffer.prev_code_boundary(offset);
             let empty_vec = Vec::new();
             let code_actions =
-                buffer.code_actions.get(&prev_offset).unwrap_or(&empty_vec);
+                buffer.code_actions(prev_offset).unwrap_or(&empty_vec);
 
             let action = match code_actions.get(self.main_split.current_code_actions)
             {
@@ -122,43 +123,25 @@ impl CodeActionData {
             };
 
             match action {
-                CodeActionOrCommand::Command(_cmd) => {}
+                CodeActionOrCommand::Command(command) => {
+                    self.main_split.execute_command(path, command);
+                }
                 CodeActionOrCommand::CodeAction(action) => {
-                    if let Some(edit) = action.edit.as_ref() {
-                        if let Some(edits) = workspce_edits(edit) {
-                            if let Some(edits) =
-                                edits.get(&Url::from_file_path(&path).unwrap())
-                            {
-                                let path = path.clone();
-                                let buffer = self
-                                    .main_split
-                                    .open_files
-                                    .get_mut(&path)
-                                    .unwrap();
-                                let edits: Vec<(Selection, &str)> = edits
-                                    .iter()
-                                    .map(|edit| {
-                                        let selection = Selection::region(
-                                            buffer.offset_of_position(
-                                                &edit.range.start,
-                                                self.config.editor.tab_width,
-                                            ),
-                                            buffer.offset_of_position(
-                                                &edit.range.end,
-                                                self.config.editor.tab_width,
-                                            ),
-                                        );
-                                        (selection, edit.new_text.as_str())
-                                    })
-                                    .collect();
-                                self.main_split.edit(
-                                    &path,
-                                    &edits,
-                                    EditType::Other,
-                                    &self.config,
-                                );
-                            }
-                        }
+                    if action.edit.is_some() {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::RunCodeAction(
+                                path.clone(),
+                                Box::new(action.clone()),
+                            ),
+                            Target::Widget(*self.main_split.tab_id),
+                        ));
+                    } else {
+                        // The server fills in the edit once the action is
+                        // chosen
+                        let action = action.clone();
+                        let path = path.clone();
+                        self.main_split.resolve_code_action(ctx, &path, action);
                     }
                 }
             }
@@ -177,7 +160,7 @@ impl CodeActionData {
             let prev_offset = buffer.prev_code_boundary(offset);
             let empty_vec = Vec::new();
             let code_actions =
-                buffer.code_actions.get(&prev_offset).unwrap_or(&empty_vec);
+                buffer.code_actions(prev_offset).unwrap_or(&empty_vec);
 
             self.main_split.current_code_actions = Movement::Up.update_index(
                 self.main_split.current_code_actions,
@@ -335,90 +318,91 @@ impl Widget<LapceTabData> for CodeAction {
             let prev_offset = buffer.prev_code_boundary(offset);
             let empty_vec = Vec::new();
             let code_actions =
-                buffer.code_actions.get(&prev_offset).unwrap_or(&empty_vec);
-
-            let action_text_layouts: Vec<TextLayout<String>> = code_actions
-                .iter()
-                .map(|code_action| {
-                    let title = match code_action {
-                        CodeActionOrCommand::Command(cmd) => cmd.title.to_string(),
-                        CodeActionOrCommand::CodeAction(action) => {
-                            action.title.to_string()
-                        }
-                    };
-                    let mut text_layout = TextLayout::<String>::from_text(title);
-                    text_layout.set_font(
-                        FontDescriptor::new(FontFamily::SYSTEM_UI).with_size(14.0),
-                    );
-                    text_layout.set_text_color(
-                        data.config
-                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                            .clone(),
-                    );
-                    text_layout.rebuild_if_needed(ctx.text(), env);
-                    text_layout
-                })
-                .collect();
+                buffer.code_actions(prev_offset).unwrap_or(&empty_vec);
 
             let line_height = data.config.editor.line_height as f64;
+            let width = ctx.size().width;
+            let foreground = data
+                .config
+                .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND);
+            let dim = data.config.get_color_unchecked(LapceTheme::EDITOR_DIM);
+
+            for (i, row) in code_action_rows(code_actions).into_iter().enumerate() {
+                let y = i as f64 * line_height;
+                match row {
+                    CodeActionRow::Header(group) => {
+                        if i > 0 {
+                            ctx.stroke(
+                                Line::new(
+                                    Point::new(0.0, y + 0.5),
+                                    Point::new(width, y + 0.5),
+                                ),
+                                dim,
+                                1.0,
+                            );
+                        }
 
-            let line_rect = Rect::ZERO
-                .with_origin(Point::new(
-                    0.0,
-                    data.main_split.current_code_actions as f64 * line_height,
-                ))
-                .with_size(Size::new(ctx.size().width, line_height));
-            ctx.fill(
-                line_rect,
-                data.config
-                    .get_color_unchecked(LapceTheme::EDITOR_BACKGROUND),
-            );
+                        let icon_size = 16.0;
+                        let rect = Size::new(icon_size, icon_size)
+                            .to_rect()
+                            .with_origin(Point::new(
+                                (line_height - icon_size) / 2.0,
+                                y + (line_height - icon_size) / 2.0,
+                            ));
+                        ctx.draw_svg(&code_action_svg(group), rect, Some(dim));
+
+                        let mut text_layout =
+                            TextLayout::<String>::from_text(group.title());
+                        text_layout.set_font(
+                            FontDescriptor::new(FontFamily::SYSTEM_UI)
+                                .with_size(12.0),
+                        );
+                        text_layout.set_text_color(dim.clone());
+                        text_layout.rebuild_if_needed(ctx.text(), env);
+                        let text_height = text_layout.size().height;
+                        text_layout.draw(
+                            ctx,
+                            Point::new(
+                                line_height + 5.0,
+                                y + (line_height - text_height) / 2.0,
+                            ),
+                        );
+                    }
+                    CodeActionRow::Action(index) => {
+                        let code_action = &code_actions[index];
+                        if index == data.main_split.current_code_actions {
+                            ctx.fill(
+                                Rect::ZERO
+                                    .with_origin(Point::new(0.0, y))
+                                    .with_size(Size::new(width, line_height)),
+                                data.config.get_color_unchecked(
+                                    LapceTheme::EDITOR_BACKGROUND,
+                                ),
+                            );
+                        }
 
-            for (i, text_layout) in action_text_layouts.iter().enumerate() {
-                text_layout.draw(ctx, Point::new(5.0, i as f64 * line_height + 5.0));
+                        let title = match code_action {
+                            CodeActionOrCommand::Command(cmd) => {
+                                cmd.title.to_string()
+                            }
+                            CodeActionOrCommand::CodeAction(action) => {
+                                action.title.to_string()
+                            }
+                        };
+                        let mut font = FontDescriptor::new(FontFamily::SYSTEM_UI)
+                            .with_size(14.0);
+                        if is_preferred(code_action) {
+                            font = font.with_weight(FontWeight::BOLD);
+                        }
+                        let mut text_layout = TextLayout::<String>::from_text(title);
+                        text_layout.set_font(font);
+                        text_layout.set_text_color(foreground.clone());
+                        text_layout.rebuild_if_needed(ctx.text(), env);
+                        text_layout
+                            .draw(ctx, Point::new(line_height + 5.0, y + 5.0));
+                    }
+                }
             }
         }
     }
 }
-
-fn workspce_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>> {
-    if let Some(changes) = edit.changes.as_ref() {
-        return Some(changes.clone());
-    }
-
-    let changes = edit.document_changes.as_ref()?;
-    let edits = match changes {
-        DocumentChanges::Edits(edits) => edits
-            .iter()
-            .map(|e| {
-                (
-                    e.text_document.uri.clone(),
-                    e.edits
-                        .iter()
-                        .map(|e| match e {
-                            OneOf::Left(e) => e.clone(),
-                            OneOf::Right(e) => e.text_edit.clone(),
-                        })
-                        .collect(),
-                )
-            })
-            .collect::<HashMap<Url, Vec<TextEdit>>>(),
-        DocumentChanges::Operations(ops) => ops
-            .iter()
-            .filter_map(|o| match o {
-                DocumentChangeOperation::Op(_op) => None,
-                DocumentChangeOperation::Edit(e) => Some((
-                    e.text_document.uri.clone(),
-                    e.edits
-                        .iter()
-                        .map(|e| match e {
-                            OneOf::Left(e) => e.clone(),
-                            OneOf::Right(e) => e.text_edit.clone(),
-                        })
-                        .collect(),
-                )),
-            })
-            .collect::<HashMap<Url, Vec<TextEdit>>>(),
-    };
-    Some(edits)
-}
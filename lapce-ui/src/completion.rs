@@ -1,21 +1,28 @@
-use std::{cmp::Ordering, fmt::Display, sync::Arc};
+use std::{borrow::Cow, cmp::Ordering, fmt::Display, sync::Arc};
 
 use anyhow::Error;
 use druid::{
-    piet::{Text, TextAttribute, TextLayoutBuilder},
-    BoxConstraints, Command, Data, Env, Event, EventCtx, FontFamily, FontWeight,
-    LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size,
-    Target, UpdateCtx, Widget, WidgetId, WidgetPod,
+    kurbo::Line,
+    piet::{Text, TextAttribute, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, FontFamily,
+    FontWeight, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect,
+    RenderContext, Size, Target, UpdateCtx, Widget, WidgetId, WidgetPod,
 };
 use itertools::Itertools;
 use lapce_data::{
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
-    completion::{CompletionData, CompletionStatus, ScoredCompletionItem},
+    command::{
+        CommandTarget, LapceCommand, LapceCommandNew, LapceUICommand,
+        LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND,
+    },
+    completion::{
+        CompletionData, CompletionRow, CompletionStatus, ScoredCompletionItem,
+    },
     config::LapceTheme,
     data::LapceTabData,
 };
-use lsp_types::CompletionItem;
+use lsp_types::{CompletionItem, CompletionItemKind};
 use regex::Regex;
+use serde_json::json;
 use std::str::FromStr;
 
 use crate::{
@@ -243,8 +250,16 @@ pub struct CompletionContainer {
         LapceIdentityWrapper<LapceScrollNew<LapceTabData, CompletionNew>>,
     >,
     content_size: Size,
+    /// The mouse position and list size when a resize drag started, and
+    /// whether it changes the width and the height
+    resize: Option<(Point, Size, bool, bool)>,
 }
 
+/// How close to the right or bottom edge of the list a drag resizes it
+const RESIZE_GRIP: f64 = 6.0;
+const MIN_SIZE: Size = Size::new(200.0, 100.0);
+const MAX_SIZE: Size = Size::new(1200.0, 900.0);
+
 impl CompletionContainer {
     pub fn new(data: &CompletionData) -> Self {
         let completion = LapceIdentityWrapper::wrap(
@@ -256,6 +271,82 @@ impl CompletionContainer {
             completion: WidgetPod::new(completion),
             scroll_id: data.scroll_id,
             content_size: Size::ZERO,
+            resize: None,
+        }
+    }
+
+    /// Whether a drag from `pos` would resize the width and the height.
+    fn resize_edges(&self, pos: Point) -> Option<(bool, bool)> {
+        let size = self.content_size;
+        let width = pos.x >= size.width - RESIZE_GRIP && pos.x <= size.width;
+        let height = pos.y >= size.height - RESIZE_GRIP && pos.y <= size.height;
+        if (width && pos.y <= size.height) || (height && pos.x <= size.width) {
+            Some((width, height))
+        } else {
+            None
+        }
+    }
+
+    fn handle_resize(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+    ) {
+        match event {
+            Event::MouseDown(mouse_event) if mouse_event.button.is_left() => {
+                if let Some((width, height)) = self.resize_edges(mouse_event.pos) {
+                    self.resize =
+                        Some((mouse_event.pos, data.completion.size, width, height));
+                    ctx.set_active(true);
+                    ctx.set_handled();
+                }
+            }
+            Event::MouseMove(mouse_event) => {
+                if let Some((start, size, width, height)) = self.resize {
+                    let delta = mouse_event.pos - start;
+                    let mut new_size = size;
+                    if width {
+                        new_size.width = size.width + delta.x;
+                    }
+                    if height {
+                        new_size.height = size.height + delta.y;
+                    }
+                    let new_size = new_size.clamp(MIN_SIZE, MAX_SIZE).round();
+                    if new_size != data.completion.size {
+                        Arc::make_mut(&mut data.completion).size = new_size;
+                        ctx.request_layout();
+                    }
+                    ctx.set_handled();
+                } else {
+                    match self.resize_edges(mouse_event.pos) {
+                        Some((true, _)) => ctx.set_cursor(&Cursor::ResizeLeftRight),
+                        Some((false, _)) => ctx.set_cursor(&Cursor::ResizeUpDown),
+                        None => ctx.clear_cursor(),
+                    }
+                }
+            }
+            Event::MouseUp(mouse_event) if mouse_event.button.is_left() => {
+                if self.resize.take().is_some() {
+                    ctx.set_active(false);
+                    let size = data.completion.size;
+                    for (key, value) in [
+                        ("editor.completion-width", size.width),
+                        ("editor.completion-height", size.height),
+                    ] {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateSettingsFile(
+                                key.to_string(),
+                                json!(value as usize),
+                            ),
+                            Target::Auto,
+                        ));
+                    }
+                    ctx.set_handled();
+                }
+            }
+            _ => {}
         }
     }
 
@@ -271,7 +362,8 @@ impl CompletionContainer {
             .to_rect()
             .with_origin(Point::new(
                 0.0,
-                data.completion.index as f64 * line_height,
+                data.completion.row_of_index(data.completion.index) as f64
+                    * line_height,
             ));
         if self
             .completion
@@ -305,12 +397,25 @@ impl Widget<LapceTabData> for CompletionContainer {
                 let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
                 match command {
                     LapceUICommand::UpdateCompletion(request_id, input, resp) => {
+                        let cursor = data.main_split.active_editor().map(|editor| {
+                            let buffer =
+                                data.main_split.editor_buffer(editor.view_id);
+                            (
+                                buffer.id(),
+                                buffer.prev_code_boundary(editor.cursor.offset()),
+                            )
+                        });
                         let completion = Arc::make_mut(&mut data.completion);
-                        completion.receive(
-                            *request_id,
-                            input.to_owned(),
-                            resp.to_owned(),
-                        );
+                        match cursor {
+                            Some((buffer_id, offset)) => completion.receive(
+                                *request_id,
+                                input.to_owned(),
+                                resp.to_owned(),
+                                buffer_id,
+                                offset,
+                            ),
+                            None => completion.cancel(),
+                        }
                     }
                     LapceUICommand::CancelCompletion(request_id) => {
                         if data.completion.request_id == *request_id {
@@ -323,7 +428,12 @@ impl Widget<LapceTabData> for CompletionContainer {
             }
             _ => {}
         }
-        self.completion.event(ctx, event, data, env);
+        if data.completion.status != CompletionStatus::Inactive {
+            self.handle_resize(ctx, event, data);
+        }
+        if !ctx.is_handled() {
+            self.completion.event(ctx, event, data, env);
+        }
     }
 
     fn lifecycle(
@@ -379,6 +489,10 @@ impl Widget<LapceTabData> for CompletionContainer {
             ctx.request_layout();
         }
 
+        if old_completion.stale != completion.stale {
+            ctx.request_paint();
+        }
+
         if (old_completion.status == CompletionStatus::Inactive
             && completion.status != CompletionStatus::Inactive)
             || (old_completion.input != completion.input)
@@ -424,6 +538,23 @@ impl Widget<LapceTabData> for CompletionContainer {
                     .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
             );
             self.completion.paint(ctx, data, env);
+
+            // A grip in the corner that can be dragged to resize the list
+            let corner = Point::new(
+                self.content_size.width - 3.0,
+                self.content_size.height - 3.0,
+            );
+            let color = data.config.get_color_unchecked(LapceTheme::EDITOR_DIM);
+            for offset in [4.0, 8.0] {
+                ctx.stroke(
+                    Line::new(
+                        Point::new(corner.x - offset, corner.y),
+                        Point::new(corner.x, corner.y - offset),
+                    ),
+                    color,
+                    1.0,
+                );
+            }
         }
     }
 }
@@ -434,8 +565,103 @@ impl CompletionNew {
     pub fn new() -> Self {
         Self {}
     }
+
+    fn paint_header(
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        kind: Option<CompletionItemKind>,
+        line: usize,
+    ) {
+        let line_height = data.config.editor.line_height as f64;
+        let y = line_height * line as f64;
+        let color = data.config.get_color_unchecked(LapceTheme::EDITOR_DIM);
+
+        if let Some((svg, _)) = completion_svg(kind, &data.config) {
+            let width = 16.0;
+            let height = 16.0;
+            let rect = Size::new(width, height).to_rect().with_origin(Point::new(
+                (line_height - width) / 2.0,
+                (line_height - height) / 2.0 + y,
+            ));
+            ctx.draw_svg(&svg, rect, Some(color));
+        }
+
+        let name = kind
+            .map(|kind| format!("{:?}", kind))
+            .unwrap_or_else(|| "Other".to_string());
+        let text_layout = ctx
+            .text()
+            .new_text_layout(name)
+            .font(FontFamily::SYSTEM_UI, 12.0)
+            .text_color(color.clone())
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                line_height + 5.0,
+                y + (line_height - text_layout.size().height) / 2.0,
+            ),
+        );
+    }
+
+    /// The index of the item on the row at `pos`, if there is one there
+    /// rather than a header or nothing.
+    fn index_at(data: &LapceTabData, pos: Point) -> Option<usize> {
+        if data.completion.status == CompletionStatus::Inactive || pos.y < 0.0 {
+            return None;
+        }
+        let line_height = data.config.editor.line_height as f64;
+        let row = (pos.y / line_height).floor() as usize;
+        match data.completion.rows().get(row)? {
+            CompletionRow::Item(index, _) => Some(*index),
+            CompletionRow::Header(_) => None,
+        }
+    }
+
+    /// The label cut short with an ellipsis to fit in `width`, or all of it if
+    /// it fits.
+    fn fit_label<'a>(
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        label: &'a str,
+        width: f64,
+    ) -> Cow<'a, str> {
+        let font = FontFamily::new_unchecked(data.config.editor.font_family.clone());
+        let font_size = data.config.editor.font_size as f64;
+        let text_layout = ctx
+            .text()
+            .new_text_layout(label.to_string())
+            .font(font.clone(), font_size)
+            .build()
+            .unwrap();
+        if text_layout.size().width <= width {
+            return Cow::Borrowed(label);
+        }
+
+        let ellipsis = ctx
+            .text()
+            .new_text_layout(ELLIPSIS)
+            .font(font, font_size)
+            .build()
+            .unwrap();
+        let hit = text_layout
+            .hit_test_point(Point::new(width - ellipsis.size().width, 0.0));
+        // The hit is at the nearest boundary, which can be past the point.
+        let mut end = hit.idx.min(label.len());
+        while end > 0
+            && (!label.is_char_boundary(end)
+                || text_layout.hit_test_text_position(end).point.x
+                    > width - ellipsis.size().width)
+        {
+            end -= 1;
+        }
+        Cow::Owned(format!("{}{}", &label[..end], ELLIPSIS))
+    }
 }
 
+const ELLIPSIS: &str = "\u{2026}";
+
 impl Default for CompletionNew {
     fn default() -> Self {
         Self::new()
@@ -445,11 +671,40 @@ impl Default for CompletionNew {
 impl Widget<LapceTabData> for CompletionNew {
     fn event(
         &mut self,
-        _ctx: &mut EventCtx,
-        _event: &Event,
-        _data: &mut LapceTabData,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
         _env: &Env,
     ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                if let Some(index) = Self::index_at(data, mouse_event.pos) {
+                    if index != data.completion.index {
+                        Arc::make_mut(&mut data.completion).index = index;
+                    }
+                    ctx.set_handled();
+                }
+            }
+            Event::MouseDown(mouse_event) if mouse_event.button.is_left() => {
+                if let Some(index) = Self::index_at(data, mouse_event.pos) {
+                    Arc::make_mut(&mut data.completion).index = index;
+                    if let Some(editor_view_id) = *data.main_split.active {
+                        ctx.submit_command(Command::new(
+                            LAPCE_NEW_COMMAND,
+                            LapceCommandNew {
+                                cmd: LapceCommand::ListSelect.to_string(),
+                                data: None,
+                                palette_desc: None,
+                                target: CommandTarget::Focus,
+                            },
+                            Target::Widget(editor_view_id),
+                        ));
+                    }
+                }
+                ctx.set_handled();
+            }
+            _ => {}
+        }
     }
 
     fn lifecycle(
@@ -478,7 +733,7 @@ impl Widget<LapceTabData> for CompletionNew {
         _env: &Env,
     ) -> Size {
         let line_height = data.config.editor.line_height as f64;
-        let height = data.completion.len();
+        let height = data.completion.rows().len();
         let height = height as f64 * line_height;
         Size::new(bc.max().width, height)
     }
@@ -491,8 +746,7 @@ impl Widget<LapceTabData> for CompletionNew {
         let rect = ctx.region().bounding_box();
         let size = ctx.size();
 
-        let _input = &data.completion.input;
-        let items: &Vec<ScoredCompletionItem> = data.completion.current_items();
+        let rows = data.completion.rows();
 
         ctx.fill(
             rect,
@@ -504,11 +758,16 @@ impl Widget<LapceTabData> for CompletionNew {
         let end_line = (rect.y1 / line_height).ceil() as usize;
 
         for line in start_line..end_line {
-            if line >= items.len() {
-                break;
-            }
+            let (index, item) = match rows.get(line) {
+                Some(CompletionRow::Item(index, item)) => (*index, *item),
+                Some(CompletionRow::Header(kind)) => {
+                    Self::paint_header(ctx, data, *kind, line);
+                    continue;
+                }
+                None => break,
+            };
 
-            if line == data.completion.index {
+            if index == data.completion.index {
                 ctx.fill(
                     Rect::ZERO
                         .with_origin(Point::new(0.0, line as f64 * line_height))
@@ -518,8 +777,6 @@ impl Widget<LapceTabData> for CompletionNew {
                 );
             }
 
-            let item = &items[line];
-
             let y = line_height * line as f64 + 5.0;
 
             if let Some((svg, color)) = completion_svg(item.item.kind, &data.config)
@@ -546,8 +803,26 @@ impl Widget<LapceTabData> for CompletionNew {
 
             let focus_color =
                 data.config.get_color_unchecked(LapceTheme::EDITOR_FOCUS);
-            let content = item.item.label.as_str();
             let point = Point::new(line_height + 5.0, y);
+            let content = Self::fit_label(
+                ctx,
+                data,
+                item.item.label.as_str(),
+                size.width - point.x - 10.0,
+            );
+            // The matches past the end of a cut label aren't shown.
+            let shown = match &content {
+                Cow::Borrowed(content) => content.len(),
+                Cow::Owned(content) => content.len() - ELLIPSIS.len(),
+            };
+
+            let deprecated = item.is_deprecated();
+            let text_color = if deprecated || data.completion.stale {
+                data.config.get_color_unchecked(LapceTheme::EDITOR_DIM)
+            } else {
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+            };
 
             let mut text_layout = ctx
                 .text()
@@ -558,12 +833,14 @@ impl Widget<LapceTabData> for CompletionNew {
                     ),
                     data.config.editor.font_size as f64,
                 )
-                .text_color(
-                    data.config
-                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                        .clone(),
-                );
-            for i in &item.indices {
+                .text_color(text_color.clone());
+            // The matches of stale items are for an earlier input.
+            let indices: &[usize] = if data.completion.stale {
+                &[]
+            } else {
+                &item.indices
+            };
+            for i in indices.iter().filter(|i| **i < shown) {
                 let i = *i;
                 text_layout = text_layout.range_attribute(
                     i..i + 1,
@@ -576,6 +853,21 @@ impl Widget<LapceTabData> for CompletionNew {
             }
             let text_layout = text_layout.build().unwrap();
             ctx.draw_text(&text_layout, point);
+
+            if deprecated {
+                // The piet version in use has no strikethrough attribute, so
+                // the line is stroked over the label instead.
+                let text_size = text_layout.size();
+                let y = point.y + text_size.height / 2.0;
+                ctx.stroke(
+                    Line::new(
+                        Point::new(point.x, y),
+                        Point::new(point.x + text_size.width, y),
+                    ),
+                    text_color,
+                    1.0,
+                );
+            }
         }
     }
 }
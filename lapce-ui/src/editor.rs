@@ -12,7 +12,10 @@ use druid::{
 };
 use druid::{Data, TimerToken};
 use lapce_data::{
-    buffer::{matching_pair_direction, BufferContent, DiffLines, LocalBufferKind},
+    buffer::{
+        folding::VisualLines, matching_pair_direction, str_col, BufferContent,
+        DiffLines, LocalBufferKind,
+    },
     command::{
         CommandTarget, LapceCommand, LapceCommandNew, LapceUICommand,
         LapceWorkbenchCommand, LAPCE_UI_COMMAND,
@@ -20,6 +23,7 @@ use lapce_data::{
     config::{Config, LapceTheme},
     data::{LapceTabData, PanelData, PanelKind},
     editor::{EditorLocation, LapceEditorBufferData, Syntax},
+    hover::HoverStatus,
     menu::MenuItem,
     movement::{ColPosition, CursorMode, Movement, Selection},
     panel::PanelPosition,
@@ -29,6 +33,7 @@ use lapce_rpc::buffer::BufferId;
 use lsp_types::{DiagnosticSeverity, DocumentChanges, TextEdit, Url, WorkspaceEdit};
 use strum::EnumMessage;
 
+pub mod breadcrumbs;
 pub mod container;
 pub mod diff_split;
 pub mod gutter;
@@ -111,11 +116,17 @@ pub struct LapceEditor {
     /// A timer for listening for when the user has hovered for long enough to trigger showing
     /// of hover info (if there is any)
     mouse_hover_timer: TimerToken,
+    /// Fires when the cursor has rested long enough to ask the language
+    /// server for the occurrences of the symbol under it and the ranges that
+    /// can be folded
+    idle_timer: TimerToken,
 
     text_layouts: HashMap<usize, PietTextLayout>,
 }
 
 impl LapceEditor {
+    const IDLE_DELAY: Duration = Duration::from_millis(300);
+
     pub fn new(view_id: WidgetId) -> Self {
         Self {
             view_id,
@@ -124,6 +135,7 @@ impl LapceEditor {
             last_left_click: None,
             mouse_pos: Point::ZERO,
             mouse_hover_timer: TimerToken::INVALID,
+            idle_timer: TimerToken::INVALID,
             text_layouts: HashMap::new(),
         }
     }
@@ -143,11 +155,13 @@ impl LapceEditor {
                 // TODO: Don't cancel over here, because it would good to allow the user to
                 // select text inside the hover data
                 editor_data.cancel_hover();
+                editor_data.cancel_signature();
             }
             MouseButton::Right => {
                 self.right_click(ctx, editor_data, mouse_event, config);
                 editor_data.cancel_completion();
                 editor_data.cancel_hover();
+                editor_data.cancel_signature();
             }
             MouseButton::Middle => {}
             _ => (),
@@ -195,12 +209,13 @@ impl LapceEditor {
         config: &Config,
     ) {
         editor_data.single_click(ctx, mouse_event, config);
-        let menu_items = vec![
+        let mut menu_items = vec![
             MenuItem {
                 text: LapceCommand::GotoDefinition
                     .get_message()
                     .unwrap()
                     .to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceCommand::GotoDefinition.to_string(),
                     palette_desc: None,
@@ -208,8 +223,61 @@ impl LapceEditor {
                     target: CommandTarget::Focus,
                 },
             },
+            MenuItem {
+                text: LapceCommand::PeekDefinition
+                    .get_message()
+                    .unwrap()
+                    .to_string(),
+                indices: Vec::new(),
+                command: LapceCommandNew {
+                    cmd: LapceCommand::PeekDefinition.to_string(),
+                    palette_desc: None,
+                    data: None,
+                    target: CommandTarget::Focus,
+                },
+            },
+            MenuItem {
+                text: LapceCommand::GotoTypeDefinition
+                    .get_message()
+                    .unwrap()
+                    .to_string(),
+                indices: Vec::new(),
+                command: LapceCommandNew {
+                    cmd: LapceCommand::GotoTypeDefinition.to_string(),
+                    palette_desc: None,
+                    data: None,
+                    target: CommandTarget::Focus,
+                },
+            },
+            MenuItem {
+                text: LapceCommand::GotoImplementation
+                    .get_message()
+                    .unwrap()
+                    .to_string(),
+                indices: Vec::new(),
+                command: LapceCommandNew {
+                    cmd: LapceCommand::GotoImplementation.to_string(),
+                    palette_desc: None,
+                    data: None,
+                    target: CommandTarget::Focus,
+                },
+            },
+            MenuItem {
+                text: LapceCommand::RenameSymbol
+                    .get_message()
+                    .unwrap()
+                    .to_string(),
+                indices: Vec::new(),
+                command: LapceCommandNew {
+                    cmd: LapceCommand::RenameSymbol.to_string(),
+                    palette_desc: None,
+                    data: None,
+                    target: CommandTarget::Focus,
+                },
+            },
             MenuItem {
                 text: "Command Palette".to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceWorkbenchCommand::PaletteCommand.to_string(),
                     palette_desc: None,
@@ -218,6 +286,22 @@ impl LapceEditor {
                 },
             },
         ];
+        if let BufferContent::File(_) = &editor_data.editor.content {
+            menu_items.push(MenuItem {
+                text: LapceWorkbenchCommand::RevealActiveFileInFileExplorer
+                    .get_message()
+                    .unwrap()
+                    .to_string(),
+                indices: Vec::new(),
+                command: LapceCommandNew {
+                    cmd: LapceWorkbenchCommand::RevealActiveFileInFileExplorer
+                        .to_string(),
+                    palette_desc: None,
+                    data: None,
+                    target: CommandTarget::Workbench,
+                },
+            });
+        }
         let point = mouse_event.pos + editor_data.editor.window_origin.to_vec2();
         ctx.submit_command(Command::new(
             LAPCE_UI_COMMAND,
@@ -279,7 +363,8 @@ impl LapceEditor {
                     Size::new(
                         (width * data.buffer.max_len() as f64)
                             .max(editor_size.width),
-                        (line_height * data.buffer.num_lines() as f64 - line_height)
+                        (line_height * data.buffer.num_visual_lines() as f64
+                            - line_height)
                             .max(0.0)
                             + editor_size.height,
                     )
@@ -288,6 +373,9 @@ impl LapceEditor {
             BufferContent::Local(kind) => match kind {
                 LocalBufferKind::FilePicker
                 | LocalBufferKind::Search
+                | LocalBufferKind::Replace
+                | LocalBufferKind::SearchGlobs
+                | LocalBufferKind::PluginFilter
                 | LocalBufferKind::Settings
                 | LocalBufferKind::Keymap => Size::new(
                     editor_size.width.max(width * data.buffer.len() as f64),
@@ -392,7 +480,7 @@ impl LapceEditor {
                             x += char_width - small_char_width;
                         } else if ch == '\t' {
                             x += (char_width - small_char_width)
-                                * data.config.editor.tab_width as f64;
+                                * data.buffer.tab_width(&data.config) as f64;
                         } else {
                             break;
                         }
@@ -483,6 +571,7 @@ impl LapceEditor {
             .unwrap();
         let char_width = text_layout.size().width;
         let y_shift = (line_height - text_layout.size().height) / 2.0;
+        let lines = data.buffer.visual_lines();
 
         if data.editor.content.is_input()
             || (data.editor.compare.is_none() && !data.editor.code_lens)
@@ -490,12 +579,15 @@ impl LapceEditor {
             Self::paint_cursor(
                 data,
                 ctx,
+                &lines,
                 is_focused,
                 self.placeholder.as_ref(),
                 char_width,
                 env,
             );
-            Self::paint_find(data, ctx, char_width, env);
+            Self::paint_document_highlights(data, ctx, &lines, char_width, env);
+            Self::paint_find(data, ctx, &lines, char_width, env);
+            Self::paint_whitespace_warnings(data, ctx, &lines, char_width, env);
         }
         let self_size = ctx.size();
         let rect = ctx.region().bounding_box();
@@ -719,7 +811,8 @@ impl LapceEditor {
             let bounds = [rect.x0, rect.x1];
             let mode = data.editor.cursor.get_mode();
 
-            for line in start_line..end_line + 1 {
+            for visual_line in start_line..end_line + 1 {
+                let line = lines.actual_line(visual_line);
                 if line > last_line {
                     break;
                 }
@@ -747,20 +840,36 @@ impl LapceEditor {
                     font_size,
                     bounds,
                 );
-                ctx.draw_text(
-                    text_layout,
-                    Point::new(
-                        0.0,
-                        line_height * line as f64
-                            + (line_height - text_layout.size().height) / 2.0
-                            + line_padding,
-                    ),
-                );
+                let y = line_height * visual_line as f64
+                    + (line_height - text_layout.size().height) / 2.0
+                    + line_padding;
+                let text_width = text_layout.size().width;
+                ctx.draw_text(text_layout, Point::new(0.0, y));
+
+                if data.buffer.is_folded(line) {
+                    let marker = ctx
+                        .text()
+                        .new_text_layout("\u{22ef}")
+                        .font(data.config.editor.font_family(), font_size as f64)
+                        .text_color(
+                            data.config
+                                .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                                .clone(),
+                        )
+                        .build()
+                        .unwrap();
+                    ctx.draw_text(&marker, Point::new(text_width + char_width, y));
+                }
             }
         }
 
-        Self::paint_snippet(data, ctx);
-        Self::paint_diagnostics(data, ctx);
+        Self::paint_snippet(data, ctx, &lines);
+        if is_focused {
+            Self::paint_completion_preview(
+                data, ctx, &lines, char_width, font_size, env,
+            );
+        }
+        Self::paint_diagnostics(data, ctx, &lines);
         if data.buffer.len() == 0 {
             if let Some(placeholder) = self.placeholder.as_ref() {
                 let text_layout = ctx
@@ -796,11 +905,11 @@ impl LapceEditor {
             CursorMode::Visual { start, end, mode } => {
                 let (start_line, start_col) = data.buffer.offset_to_line_col(
                     *start.min(end),
-                    data.config.editor.tab_width,
+                    data.buffer.tab_width(&data.config),
                 );
                 let (end_line, end_col) = data.buffer.offset_to_line_col(
                     *start.max(end),
-                    data.config.editor.tab_width,
+                    data.buffer.tab_width(&data.config),
                 );
                 if actual_line < start_line || actual_line > end_line {
                     return;
@@ -819,7 +928,7 @@ impl LapceEditor {
                         let max_col = data.buffer.line_end_col(
                             actual_line,
                             false,
-                            data.config.editor.tab_width,
+                            data.buffer.tab_width(&data.config),
                         );
                         let left = start_col.min(end_col);
                         if left > max_col {
@@ -835,14 +944,14 @@ impl LapceEditor {
                             let max_col = data.buffer.line_end_col(
                                 actual_line,
                                 true,
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             );
                             (end_col + 1).min(max_col)
                         } else {
                             data.buffer.line_end_col(
                                 actual_line,
                                 true,
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             ) + 1
                         }
                     }
@@ -850,14 +959,14 @@ impl LapceEditor {
                         data.buffer.line_end_col(
                             actual_line,
                             true,
-                            data.config.editor.tab_width,
+                            data.buffer.tab_width(&data.config),
                         ) + 1
                     }
                     VisualMode::Blockwise => {
                         let max_col = data.buffer.line_end_col(
                             actual_line,
                             true,
-                            data.config.editor.tab_width,
+                            data.buffer.tab_width(&data.config),
                         );
                         let right = match data.editor.cursor.horiz.as_ref() {
                             Some(&ColPosition::End) => max_col,
@@ -902,11 +1011,11 @@ impl LapceEditor {
                         let (start_line, start_col) =
                             data.buffer.offset_to_line_col(
                                 start.min(end),
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             );
                         let (end_line, end_col) = data.buffer.offset_to_line_col(
                             start.max(end),
-                            data.config.editor.tab_width,
+                            data.buffer.tab_width(&data.config),
                         );
                         let left_col = match actual_line {
                             _ if actual_line == start_line => start_col,
@@ -917,14 +1026,14 @@ impl LapceEditor {
                                 let max_col = data.buffer.line_end_col(
                                     actual_line,
                                     true,
-                                    data.config.editor.tab_width,
+                                    data.buffer.tab_width(&data.config),
                                 );
                                 end_col.min(max_col)
                             }
                             _ => data.buffer.line_end_col(
                                 actual_line,
                                 true,
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             ),
                         };
                         let x0 = left_col as f64 * char_width + x_shift;
@@ -943,7 +1052,7 @@ impl LapceEditor {
                         let (caret_actual_line, col) =
                             data.buffer.offset_to_line_col(
                                 region.end(),
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             );
                         if caret_actual_line == actual_line {
                             let x = col as f64 * char_width + x_shift;
@@ -978,7 +1087,7 @@ impl LapceEditor {
                         let (x0, x1) = data.editor.cursor.current_char(
                             data.buffer.data(),
                             char_width,
-                            &data.config,
+                            data.buffer.tab_width(&data.config),
                         );
                         let cursor_width =
                             if x1 > x0 { x1 - x0 } else { char_width };
@@ -999,6 +1108,7 @@ impl LapceEditor {
     fn paint_cursor(
         data: &LapceEditorBufferData,
         ctx: &mut PaintCtx,
+        lines: &VisualLines,
         is_focused: bool,
         placeholder: Option<&String>,
         width: f64,
@@ -1006,29 +1116,39 @@ impl LapceEditor {
     ) {
         let line_height = Self::line_height(data, env);
         let line_padding = Self::line_padding(data, env);
-        let start_line =
-            (data.editor.scroll_offset.y / line_height).floor() as usize;
-        let end_line = ((data.editor.size.borrow().height
-            + data.editor.scroll_offset.y)
-            / line_height)
-            .ceil() as usize;
+        let start_line = lines.actual_line(
+            (data.editor.scroll_offset.y / line_height).floor() as usize,
+        );
+        let end_line = lines.actual_line(
+            ((data.editor.size.borrow().height + data.editor.scroll_offset.y)
+                / line_height)
+                .ceil() as usize,
+        );
         match &data.editor.cursor.mode {
             CursorMode::Normal(offset) => {
                 let line = data.buffer.line_of_offset(*offset);
-                Self::paint_cursor_line(data, ctx, line, is_focused, placeholder);
+                Self::paint_cursor_line(
+                    data,
+                    ctx,
+                    lines,
+                    line,
+                    is_focused,
+                    placeholder,
+                );
 
                 if is_focused {
                     let (x0, x1) = data.editor.cursor.current_char(
                         data.buffer.data(),
                         width,
-                        &data.config,
+                        data.buffer.tab_width(&data.config),
                     );
                     let char_width = if x1 > x0 { x1 - x0 } else { width };
                     ctx.fill(
                         Rect::ZERO
                             .with_origin(Point::new(
                                 x0,
-                                line as f64 * line_height + line_padding,
+                                lines.visual_line(line) as f64 * line_height
+                                    + line_padding,
                             ))
                             .with_size(Size::new(char_width, line_height)),
                         data.config.get_color_unchecked(LapceTheme::EDITOR_CARET),
@@ -1040,14 +1160,15 @@ impl LapceEditor {
                 let paint_end_line = end_line;
                 let (start_line, start_col) = data.buffer.offset_to_line_col(
                     *start.min(end),
-                    data.config.editor.tab_width,
+                    data.buffer.tab_width(&data.config),
                 );
                 let (end_line, end_col) = data.buffer.offset_to_line_col(
                     *start.max(end),
-                    data.config.editor.tab_width,
+                    data.buffer.tab_width(&data.config),
                 );
                 for line in paint_start_line..paint_end_line {
-                    if line < start_line || line > end_line {
+                    if line < start_line || line > end_line || lines.is_hidden(line)
+                    {
                         continue;
                     }
                     let line_content = data.buffer.line_content(line);
@@ -1061,7 +1182,7 @@ impl LapceEditor {
                             let max_col = data.buffer.line_end_col(
                                 line,
                                 false,
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             );
                             let left = start_col.min(end_col);
                             if left > max_col {
@@ -1078,7 +1199,7 @@ impl LapceEditor {
                                 let max_col = data.buffer.line_end_col(
                                     line,
                                     true,
-                                    data.config.editor.tab_width,
+                                    data.buffer.tab_width(&data.config),
                                 );
                                 (end_col + 1).min(max_col)
                             }
@@ -1086,7 +1207,7 @@ impl LapceEditor {
                                 data.buffer.line_end_col(
                                     line,
                                     true,
-                                    data.config.editor.tab_width,
+                                    data.buffer.tab_width(&data.config),
                                 ) + 1
                             }
                         },
@@ -1094,14 +1215,14 @@ impl LapceEditor {
                             data.buffer.line_end_col(
                                 line,
                                 true,
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             ) + 1
                         }
                         VisualMode::Blockwise => {
                             let max_col = data.buffer.line_end_col(
                                 line,
                                 true,
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             );
                             let right = match data.editor.cursor.horiz.as_ref() {
                                 Some(&ColPosition::End) => max_col,
@@ -1113,7 +1234,8 @@ impl LapceEditor {
                     if !line_content.is_empty() {
                         let x1 = right_col as f64 * width;
 
-                        let y0 = line as f64 * line_height + line_padding;
+                        let y0 = lines.visual_line(line) as f64 * line_height
+                            + line_padding;
                         let y1 = y0 + line_height;
                         ctx.fill(
                             Rect::new(x0, y0, x1, y1),
@@ -1128,14 +1250,15 @@ impl LapceEditor {
                         let (x0, x1) = data.editor.cursor.current_char(
                             data.buffer.data(),
                             width,
-                            &data.config,
+                            data.buffer.tab_width(&data.config),
                         );
                         let char_width = if x1 > x0 { x1 - x0 } else { width };
                         ctx.fill(
                             Rect::ZERO
                                 .with_origin(Point::new(
                                     x0,
-                                    line as f64 * line_height + line_padding,
+                                    lines.visual_line(line) as f64 * line_height
+                                        + line_padding,
                                 ))
                                 .with_size(Size::new(char_width, line_height)),
                             data.config
@@ -1162,6 +1285,7 @@ impl LapceEditor {
                         Self::paint_cursor_line(
                             data,
                             ctx,
+                            lines,
                             line,
                             is_focused,
                             placeholder,
@@ -1174,14 +1298,17 @@ impl LapceEditor {
                         let (start_line, start_col) =
                             data.buffer.offset_to_line_col(
                                 start.min(end),
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             );
                         let (end_line, end_col) = data.buffer.offset_to_line_col(
                             start.max(end),
-                            data.config.editor.tab_width,
+                            data.buffer.tab_width(&data.config),
                         );
                         for line in paint_start_line..paint_end_line + 1 {
-                            if line < start_line || line > end_line {
+                            if line < start_line
+                                || line > end_line
+                                || lines.is_hidden(line)
+                            {
                                 continue;
                             }
 
@@ -1197,20 +1324,22 @@ impl LapceEditor {
                                     let max_col = data.buffer.line_end_col(
                                         line,
                                         true,
-                                        data.config.editor.tab_width,
+                                        data.buffer.tab_width(&data.config),
                                     );
                                     end_col.min(max_col)
                                 }
                                 _ => data.buffer.line_end_col(
                                     line,
                                     true,
-                                    data.config.editor.tab_width,
+                                    data.buffer.tab_width(&data.config),
                                 ),
                             };
 
                             if !line_content.is_empty() {
                                 let x1 = right_col as f64 * width;
-                                let y0 = line as f64 * line_height + line_padding;
+                                let y0 = lines.visual_line(line) as f64
+                                    * line_height
+                                    + line_padding;
                                 let y1 = y0 + line_height;
                                 ctx.fill(
                                     Rect::new(x0, y0, x1, y1),
@@ -1227,10 +1356,11 @@ impl LapceEditor {
                     if is_focused {
                         let (line, col) = data.buffer.offset_to_line_col(
                             region.end(),
-                            data.config.editor.tab_width,
+                            data.buffer.tab_width(&data.config),
                         );
                         let x = col as f64 * width;
-                        let y = line as f64 * line_height + line_padding;
+                        let y = lines.visual_line(line) as f64 * line_height
+                            + line_padding;
                         ctx.stroke(
                             Line::new(
                                 Point::new(x, y),
@@ -1249,6 +1379,7 @@ impl LapceEditor {
     fn paint_cursor_line(
         data: &LapceEditorBufferData,
         ctx: &mut PaintCtx,
+        lines: &VisualLines,
         line: usize,
         is_focused: bool,
         placeholder: Option<&String>,
@@ -1263,16 +1394,65 @@ impl LapceEditor {
         let size = ctx.size();
         ctx.fill(
             Rect::ZERO
-                .with_origin(Point::new(0.0, line as f64 * line_height))
+                .with_origin(Point::new(
+                    0.0,
+                    lines.visual_line(line) as f64 * line_height,
+                ))
                 .with_size(Size::new(size.width, line_height)),
             data.config
                 .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
         );
     }
 
+    fn paint_document_highlights(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        lines: &VisualLines,
+        char_width: f64,
+        env: &Env,
+    ) {
+        if data.editor.content.is_input() {
+            return;
+        }
+        let ranges = match data
+            .buffer
+            .document_highlights_at(data.editor.cursor.offset())
+        {
+            Some(ranges) => ranges,
+            None => return,
+        };
+        let line_height = Self::line_height(data, env);
+        let tab_width = data.buffer.tab_width(&data.config);
+        let color = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_DOCUMENT_HIGHLIGHT);
+        for (start, end) in ranges.iter() {
+            let (start_line, start_col) =
+                data.buffer.offset_to_line_col(*start, tab_width);
+            let (end_line, end_col) =
+                data.buffer.offset_to_line_col(*end, tab_width);
+            for line in start_line..end_line + 1 {
+                if lines.is_hidden(line) {
+                    continue;
+                }
+                let left_col = if line == start_line { start_col } else { 0 };
+                let right_col = if line == end_line {
+                    end_col
+                } else {
+                    data.buffer.line_end_col(line, true, tab_width) + 1
+                };
+                let x0 = left_col as f64 * char_width;
+                let x1 = right_col as f64 * char_width;
+                let y0 = lines.visual_line(line) as f64 * line_height;
+                ctx.fill(Rect::new(x0, y0, x1, y0 + line_height), color);
+            }
+        }
+    }
+
     fn paint_find(
         data: &LapceEditorBufferData,
         ctx: &mut PaintCtx,
+        lines: &VisualLines,
         char_width: f64,
         env: &Env,
     ) {
@@ -1283,12 +1463,14 @@ impl LapceEditor {
             return;
         }
         let line_height = Self::line_height(data, env);
-        let start_line =
-            (data.editor.scroll_offset.y / line_height).floor() as usize;
-        let end_line = ((data.editor.size.borrow().height
-            + data.editor.scroll_offset.y)
-            / line_height)
-            .ceil() as usize;
+        let start_line = lines.actual_line(
+            (data.editor.scroll_offset.y / line_height).floor() as usize,
+        );
+        let end_line = lines.actual_line(
+            ((data.editor.size.borrow().height + data.editor.scroll_offset.y)
+                / line_height)
+                .ceil() as usize,
+        );
         let start_offset = data.buffer.offset_of_line(start_line);
         let end_offset = data.buffer.offset_of_line(end_line + 1);
         let cursor_offset = data.editor.cursor.offset();
@@ -1307,11 +1489,14 @@ impl LapceEditor {
                 let active = start <= cursor_offset && cursor_offset <= end;
                 let (start_line, start_col) = data
                     .buffer
-                    .offset_to_line_col(start, data.config.editor.tab_width);
+                    .offset_to_line_col(start, data.buffer.tab_width(&data.config));
                 let (end_line, end_col) = data
                     .buffer
-                    .offset_to_line_col(end, data.config.editor.tab_width);
+                    .offset_to_line_col(end, data.buffer.tab_width(&data.config));
                 for line in start_line..end_line + 1 {
+                    if lines.is_hidden(line) {
+                        continue;
+                    }
                     let left_col = if line == start_line { start_col } else { 0 };
                     let right_col = if line == end_line {
                         end_col
@@ -1319,12 +1504,12 @@ impl LapceEditor {
                         data.buffer.line_end_col(
                             line,
                             true,
-                            data.config.editor.tab_width,
+                            data.buffer.tab_width(&data.config),
                         ) + 1
                     };
                     let x0 = left_col as f64 * char_width;
                     let x1 = right_col as f64 * char_width;
-                    let y0 = line as f64 * line_height;
+                    let y0 = lines.visual_line(line) as f64 * line_height;
                     let y1 = y0 + line_height;
                     let rect = Rect::new(x0, y0, x1, y1);
                     if active {
@@ -1348,29 +1533,102 @@ impl LapceEditor {
         }
     }
 
-    fn paint_snippet(data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
+    fn paint_whitespace_warnings(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        lines: &VisualLines,
+        char_width: f64,
+        env: &Env,
+    ) {
+        if data.editor.content.is_input() {
+            return;
+        }
+        let trailing = data.config.editor.highlight_trailing_whitespace;
+        let mixed = data.config.editor.highlight_mixed_indent;
+        if !trailing && !mixed {
+            return;
+        }
+        let tab_width = data.buffer.tab_width(&data.config);
+        let line_height = Self::line_height(data, env);
+        let start_line = lines.actual_line(
+            (data.editor.scroll_offset.y / line_height).floor() as usize,
+        );
+        let end_line = lines.actual_line(
+            ((data.editor.size.borrow().height + data.editor.scroll_offset.y)
+                / line_height)
+                .ceil() as usize,
+        );
+        let end_line = end_line.min(data.buffer.last_line());
+        let cursor_line = data.buffer.line_of_offset(data.editor.cursor.offset());
+
+        for line in start_line..end_line + 1 {
+            if lines.is_hidden(line) {
+                continue;
+            }
+            let y0 = lines.visual_line(line) as f64 * line_height;
+            // Skip the cursor line so whitespace isn't flagged while typing.
+            if trailing && line != cursor_line {
+                if let Some(range) = data.buffer.trailing_whitespace(line) {
+                    let (_, start_col) =
+                        data.buffer.offset_to_line_col(range.start, tab_width);
+                    let (_, end_col) =
+                        data.buffer.offset_to_line_col(range.end, tab_width);
+                    ctx.fill(
+                        Rect::new(
+                            start_col as f64 * char_width,
+                            y0,
+                            end_col as f64 * char_width,
+                            y0 + line_height,
+                        ),
+                        data.config.get_color_unchecked(
+                            LapceTheme::EDITOR_WHITESPACE_WARNING,
+                        ),
+                    );
+                }
+            }
+            if mixed && data.buffer.has_mixed_indent(line) {
+                let indent = data.buffer.indent_on_line(line);
+                let width = str_col(&indent, tab_width) as f64 * char_width;
+                Self::paint_wave_line(
+                    ctx,
+                    Point::new(0.0, y0 + line_height - 4.0),
+                    width,
+                    data.config.get_color_unchecked(LapceTheme::LAPCE_WARN),
+                );
+            }
+        }
+    }
+
+    fn paint_snippet(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        lines: &VisualLines,
+    ) {
         let line_height = data.config.editor.line_height as f64;
-        let start_line =
-            (data.editor.scroll_offset.y / line_height).floor() as usize;
-        let end_line = ((data.editor.size.borrow().height
-            + data.editor.scroll_offset.y)
-            / line_height)
-            .ceil() as usize;
+        let start_line = lines.actual_line(
+            (data.editor.scroll_offset.y / line_height).floor() as usize,
+        );
+        let end_line = lines.actual_line(
+            ((data.editor.size.borrow().height + data.editor.scroll_offset.y)
+                / line_height)
+                .ceil() as usize,
+        );
         let width = data.config.editor_char_width(ctx.text());
         if let Some(snippet) = data.editor.snippet.as_ref() {
-            for (_, (start, end)) in snippet {
+            for (start, end) in snippet.ranges.iter().flat_map(|(_, r)| r) {
                 let paint_start_line = start_line;
                 let paint_end_line = end_line;
                 let (start_line, start_col) = data.buffer.offset_to_line_col(
                     *start.min(end),
-                    data.config.editor.tab_width,
+                    data.buffer.tab_width(&data.config),
                 );
                 let (end_line, end_col) = data.buffer.offset_to_line_col(
                     *start.max(end),
-                    data.config.editor.tab_width,
+                    data.buffer.tab_width(&data.config),
                 );
                 for line in paint_start_line..paint_end_line {
-                    if line < start_line || line > end_line {
+                    if line < start_line || line > end_line || lines.is_hidden(line)
+                    {
                         continue;
                     }
                     let line_content = data.buffer.line_content(line);
@@ -1385,19 +1643,19 @@ impl LapceEditor {
                             let max_col = data.buffer.line_end_col(
                                 line,
                                 true,
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             );
                             end_col.min(max_col)
                         }
                         _ => data.buffer.line_end_col(
                             line,
                             true,
-                            data.config.editor.tab_width,
+                            data.buffer.tab_width(&data.config),
                         ),
                     };
                     if !line_content.is_empty() {
                         let x1 = right_col as f64 * width;
-                        let y0 = line as f64 * line_height;
+                        let y0 = lines.visual_line(line) as f64 * line_height;
                         let y1 = y0 + line_height;
                         ctx.stroke(
                             Rect::new(x0, y0, x1, y1).inflate(1.0, -0.5),
@@ -1411,14 +1669,78 @@ impl LapceEditor {
         }
     }
 
-    fn paint_diagnostics(data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
+    /// Paints the rest of the selected completion item as dimmed text after
+    /// the cursor. It's skipped when there's text after the cursor that it
+    /// would be drawn over.
+    fn paint_completion_preview(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        lines: &VisualLines,
+        char_width: f64,
+        font_size: usize,
+        env: &Env,
+    ) {
+        if data.completion.buffer_id != data.buffer.id() {
+            return;
+        }
+        let offset = match &data.editor.cursor.mode {
+            CursorMode::Insert(selection) if selection.regions().len() == 1 => {
+                data.editor.cursor.offset()
+            }
+            _ => return,
+        };
+        let preview = match data.completion.preview_text() {
+            Some(preview) => preview,
+            None => return,
+        };
+
+        let line = data.buffer.line_of_offset(offset);
+        let line_end = data.buffer.line_end_offset(line, true);
+        if !data.buffer.slice_to_cow(offset..line_end).trim().is_empty() {
+            return;
+        }
+
+        let line_height = Self::line_height(data, env);
+        let line_padding = Self::line_padding(data, env);
+        let (_, col) = data
+            .buffer
+            .offset_to_line_col(offset, data.buffer.tab_width(&data.config));
+        let text_layout = ctx
+            .text()
+            .new_text_layout(preview)
+            .font(data.config.editor.font_family(), font_size as f64)
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                col as f64 * char_width,
+                line_height * lines.visual_line(line) as f64
+                    + (line_height - text_layout.size().height) / 2.0
+                    + line_padding,
+            ),
+        );
+    }
+
+    fn paint_diagnostics(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        lines: &VisualLines,
+    ) {
         let line_height = data.config.editor.line_height as f64;
-        let start_line =
-            (data.editor.scroll_offset.y / line_height).floor() as usize;
-        let end_line = ((data.editor.size.borrow().height
-            + data.editor.scroll_offset.y)
-            / line_height)
-            .ceil() as usize;
+        let start_line = lines.actual_line(
+            (data.editor.scroll_offset.y / line_height).floor() as usize,
+        );
+        let end_line = lines.actual_line(
+            ((data.editor.size.borrow().height + data.editor.scroll_offset.y)
+                / line_height)
+                .ceil() as usize,
+        );
 
         let width = data.config.editor_char_width(ctx.text());
         let mut current = None;
@@ -1433,8 +1755,7 @@ impl LapceEditor {
                     let start_offset = if let Some(range) = diagnostic.range {
                         range.0
                     } else {
-                        data.buffer
-                            .offset_of_position(&start, data.config.editor.tab_width)
+                        data.buffer.offset_of_position(&start)
                     };
                     if start_offset == cursor_offset {
                         current = Some(diagnostic.clone());
@@ -1446,13 +1767,16 @@ impl LapceEditor {
                         if line > end_line {
                             break;
                         }
+                        if lines.is_hidden(line) {
+                            continue;
+                        }
 
                         let x0 = if line == start.line as usize {
                             start.character as f64 * width
                         } else {
                             let (_, col) = data.buffer.offset_to_line_col(
                                 data.buffer.first_non_blank_character_on_line(line),
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             );
                             col as f64 * width
                         };
@@ -1462,12 +1786,13 @@ impl LapceEditor {
                             (data.buffer.line_end_col(
                                 line,
                                 false,
-                                data.config.editor.tab_width,
+                                data.buffer.tab_width(&data.config),
                             ) + 1) as f64
                                 * width
                         };
-                        let _y1 = (line + 1) as f64 * line_height;
-                        let y0 = (line + 1) as f64 * line_height - 4.0;
+                        let visual_line = lines.visual_line(line);
+                        let _y1 = (visual_line + 1) as f64 * line_height;
+                        let y0 = (visual_line + 1) as f64 * line_height - 4.0;
 
                         let severity = diagnostic
                             .diagnositc
@@ -1544,11 +1869,9 @@ impl LapceEditor {
                     .unwrap_or_else(Vec::new);
 
                 let start = diagnostic.diagnositc.range.start;
+                let line = lines.visual_line(start.line as usize);
                 let rect = Rect::ZERO
-                    .with_origin(Point::new(
-                        0.0,
-                        (start.line + 1) as f64 * line_height,
-                    ))
+                    .with_origin(Point::new(0.0, (line + 1) as f64 * line_height))
                     .with_size(Size::new(
                         data.editor.size.borrow().width,
                         text_height + 20.0,
@@ -1673,6 +1996,21 @@ impl Widget<LapceTabData> for LapceEditor {
                             true,
                             mouse_event.mods.alt(),
                         );
+                    } else if data.hover.status != HoverStatus::Inactive {
+                        // Moving off the hovered word dismisses the hover
+                        let editor_data = data.editor_view_content(self.view_id);
+                        if editor_data.hover.buffer_id == editor_data.buffer.id() {
+                            let offset = editor_data.offset_of_mouse(
+                                ctx.text(),
+                                mouse_event.pos,
+                                &data.config,
+                            );
+                            if editor_data.buffer.prev_code_boundary(offset)
+                                != data.hover.offset
+                            {
+                                Arc::make_mut(&mut data.hover).cancel();
+                            }
+                        }
                     }
                 }
             }
@@ -1722,6 +2060,21 @@ impl Widget<LapceTabData> for LapceEditor {
                 //     _ => {}
                 // }
             }
+            Event::Timer(id) if *id == self.idle_timer => {
+                self.idle_timer = TimerToken::INVALID;
+                let editor =
+                    data.main_split.editors.get(&self.view_id).unwrap().clone();
+                let mut editor_data = data.editor_view_content(self.view_id);
+                let buffer = editor_data.buffer.clone();
+                editor_data.update_document_highlights(ctx);
+                editor_data.update_folding_ranges(ctx);
+                if editor_data.config.editor.breadcrumbs
+                    || data.is_panel_visible(PanelKind::Outline)
+                {
+                    editor_data.update_document_symbols(ctx);
+                }
+                data.update_from_editor_buffer_data(editor_data, &editor, &buffer);
+            }
             Event::Timer(id) => {
                 if self.mouse_hover_timer == *id {
                     let editor =
@@ -1777,7 +2130,7 @@ impl Widget<LapceTabData> for LapceEditor {
 
     fn update(
         &mut self,
-        _ctx: &mut UpdateCtx,
+        ctx: &mut UpdateCtx,
         old_data: &LapceTabData,
         data: &LapceTabData,
         _env: &Env,
@@ -1790,6 +2143,30 @@ impl Widget<LapceTabData> for LapceEditor {
         {
             self.text_layouts.clear();
         }
+        // The outline lists the symbols of the active editor once it's shown.
+        let outline_shown = data.is_panel_visible(PanelKind::Outline)
+            && !old_data.is_panel_visible(PanelKind::Outline)
+            && *data.main_split.active == Some(self.view_id);
+        if outline_shown
+            || data.focus == self.view_id
+                && (editor_data.editor.cursor.offset()
+                    != old_editor_data.editor.cursor.offset()
+                    || editor_data.buffer.rev() != old_editor_data.buffer.rev()
+                    || old_data.focus != self.view_id)
+        {
+            self.idle_timer = ctx.request_timer(Self::IDLE_DELAY);
+        }
+        if editor_data.buffer.document_highlights
+            != old_editor_data.buffer.document_highlights
+        {
+            ctx.request_paint();
+        }
+        if !old_editor_data.buffer.same(&editor_data.buffer)
+            && editor_data.buffer.folded_lines()
+                != old_editor_data.buffer.folded_lines()
+        {
+            ctx.request_layout();
+        }
     }
 
     fn layout(
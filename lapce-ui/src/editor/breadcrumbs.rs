@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use druid::{
+    piet::{Text, TextLayout as TextLayoutTrait, TextLayoutBuilder},
+    BoxConstraints, Command, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle,
+    LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect, RenderContext, Size, Target,
+    UpdateCtx, Widget, WidgetId,
+};
+use lapce_data::{
+    buffer::BufferContent,
+    command::{
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
+    config::LapceTheme,
+    data::LapceTabData,
+    document_symbol::symbol_path,
+    editor::LapceEditorBufferData,
+    menu::MenuItem,
+};
+use lsp_types::DocumentSymbol;
+use serde_json::json;
+
+use crate::svg::symbol_svg_new;
+
+/// The bar above the editor showing the symbols enclosing the cursor.
+pub struct LapceEditorBreadcrumbs {
+    view_id: WidgetId,
+    height: f64,
+    /// The rect of each symbol of the path, outermost first
+    segment_rects: Vec<Rect>,
+}
+
+impl LapceEditorBreadcrumbs {
+    pub fn new(view_id: WidgetId) -> Self {
+        Self {
+            view_id,
+            height: 25.0,
+            segment_rects: Vec::new(),
+        }
+    }
+
+    fn is_shown(&self, data: &LapceTabData) -> bool {
+        let editor = data.main_split.editors.get(&self.view_id).unwrap();
+        data.config.editor.breadcrumbs
+            && editor.compare.is_none()
+            && matches!(editor.content, BufferContent::File(_))
+    }
+
+    /// The symbols enclosing the cursor, each with its siblings and its index
+    /// among them.
+    fn path(data: &LapceEditorBufferData) -> Vec<(&[DocumentSymbol], usize)> {
+        let symbols = match data.buffer.document_symbols.as_ref() {
+            Some((_, symbols)) => symbols,
+            None => return Vec::new(),
+        };
+        let position = data.buffer.offset_to_position(data.editor.cursor.offset());
+        symbol_path(symbols, position)
+    }
+
+    /// Lists the siblings of the clicked symbol to jump between them.
+    fn mouse_down(
+        &self,
+        ctx: &mut EventCtx,
+        mouse_event: &MouseEvent,
+        data: &LapceTabData,
+    ) {
+        let index = match self
+            .segment_rects
+            .iter()
+            .position(|rect| rect.contains(mouse_event.pos))
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let editor_data = data.editor_view_content(self.view_id);
+        let path = Self::path(&editor_data);
+        let (siblings, _) = match path.get(index) {
+            Some(segment) => segment,
+            None => return,
+        };
+        let cmd = LapceWorkbenchCommand::JumpToSymbol;
+        let menu_items = siblings
+            .iter()
+            .map(|symbol| MenuItem {
+                text: symbol.name.clone(),
+                indices: Vec::new(),
+                command: LapceCommandNew {
+                    cmd: cmd.to_string(),
+                    palette_desc: None,
+                    data: Some(json!((
+                        self.view_id.to_raw(),
+                        symbol.selection_range.start
+                    ))),
+                    target: CommandTarget::Workbench,
+                },
+            })
+            .collect();
+        let rect = self.segment_rects[index];
+        let point = Point::new(rect.x0, rect.y1) + ctx.window_origin().to_vec2();
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowMenu(point.round(), Arc::new(menu_items)),
+            Target::Auto,
+        ));
+        ctx.set_handled();
+    }
+}
+
+impl Widget<LapceTabData> for LapceEditorBreadcrumbs {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                if self
+                    .segment_rects
+                    .iter()
+                    .any(|rect| rect.contains(mouse_event.pos))
+                {
+                    ctx.set_cursor(&druid::Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+            }
+            Event::MouseDown(mouse_event) => {
+                self.mouse_down(ctx, mouse_event, data);
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if self.is_shown(old_data) != self.is_shown(data) {
+            ctx.request_layout();
+            return;
+        }
+        let old_editor_data = old_data.editor_view_content(self.view_id);
+        let editor_data = data.editor_view_content(self.view_id);
+        if editor_data.editor.cursor.offset()
+            != old_editor_data.editor.cursor.offset()
+            || editor_data.buffer.document_symbols
+                != old_editor_data.buffer.document_symbols
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        if self.is_shown(data) {
+            Size::new(bc.max().width, self.height)
+        } else {
+            self.segment_rects.clear();
+            Size::new(bc.max().width, 0.0)
+        }
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        self.segment_rects.clear();
+        if !self.is_shown(data) {
+            return;
+        }
+        let size = ctx.size();
+        ctx.fill(
+            size.to_rect(),
+            data.config
+                .get_color_unchecked(LapceTheme::EDITOR_BACKGROUND),
+        );
+
+        let editor_data = data.editor_view_content(self.view_id);
+        let path = Self::path(&editor_data);
+        let icon_size = 13.0;
+        let mut x = 10.0;
+        ctx.with_save(|ctx| {
+            ctx.clip(size.to_rect());
+            for (i, (siblings, index)) in path.iter().enumerate() {
+                if i > 0 {
+                    let text_layout = ctx
+                        .text()
+                        .new_text_layout("\u{25b8}")
+                        .font(FontFamily::SYSTEM_UI, 13.0)
+                        .text_color(
+                            data.config
+                                .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                                .clone(),
+                        )
+                        .build()
+                        .unwrap();
+                    ctx.draw_text(
+                        &text_layout,
+                        Point::new(
+                            x + 5.0,
+                            (self.height - text_layout.size().height) / 2.0,
+                        ),
+                    );
+                    x += text_layout.size().width + 10.0;
+                }
+
+                let symbol = &siblings[*index];
+                let start = x;
+                if let Some(svg) = symbol_svg_new(&symbol.kind) {
+                    let rect = Size::new(icon_size, icon_size)
+                        .to_rect()
+                        .with_origin(Point::new(x, (self.height - icon_size) / 2.0));
+                    ctx.draw_svg(&svg, rect, None);
+                    x += icon_size + 5.0;
+                }
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(symbol.name.clone())
+                    .font(FontFamily::SYSTEM_UI, 13.0)
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                ctx.draw_text(
+                    &text_layout,
+                    Point::new(x, (self.height - text_layout.size().height) / 2.0),
+                );
+                x += text_layout.size().width;
+                self.segment_rects
+                    .push(Rect::new(start, 0.0, x, self.height));
+            }
+        });
+    }
+}
@@ -1,12 +1,16 @@
+use std::{sync::Arc, time::Duration};
+
 use crate::svg::get_svg;
 use druid::{
     piet::{Text, TextLayout, TextLayoutBuilder},
-    BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
-    PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget, WidgetId,
+    BoxConstraints, Command, Env, Event, EventCtx, LayoutCtx, LifeCycle,
+    LifeCycleCtx, MouseButton, PaintCtx, Point, Rect, RenderContext, Size, Target,
+    TimerToken, UpdateCtx, Widget, WidgetId,
 };
 use lapce_data::{
-    buffer::DiffLines,
-    config::LapceTheme,
+    buffer::{BufferContent, DiffLines},
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::{Config, LapceTheme},
     data::LapceTabData,
     editor::{LapceEditorBufferData, Syntax},
 };
@@ -14,25 +18,162 @@ use lapce_data::{
 pub struct LapceEditorGutter {
     view_id: WidgetId,
     width: f64,
+    /// The line the code actions lightbulb is shown on
+    code_actions_line: Option<usize>,
+    code_actions_timer: TimerToken,
 }
 
 impl LapceEditorGutter {
+    /// How long the cursor has to rest before the lightbulb shows up, so it
+    /// doesn't flicker while moving around
+    const CODE_ACTIONS_DELAY: Duration = Duration::from_millis(300);
+
     pub fn new(view_id: WidgetId) -> Self {
         Self {
             view_id,
             width: 0.0,
+            code_actions_line: None,
+            code_actions_timer: TimerToken::INVALID,
+        }
+    }
+
+    /// The visual line of the cursor, if this is the active editor and there
+    /// are code actions at the cursor.
+    fn current_code_actions_line(&self, data: &LapceTabData) -> Option<usize> {
+        if *data.main_split.active != Some(self.view_id)
+            || !data.main_split.editors.contains_key(&self.view_id)
+        {
+            return None;
+        }
+        let data = data.editor_view_content(self.view_id);
+        if data.current_code_actions()?.is_empty() {
+            return None;
         }
+        let offset = data.editor.cursor.offset();
+        let (line, _) = data
+            .buffer
+            .offset_to_line_col(offset, data.buffer.tab_width(&data.config));
+        Some(data.buffer.visual_line(line))
+    }
+
+    fn code_actions_rect(
+        &self,
+        line: usize,
+        scroll_y: f64,
+        char_width: f64,
+        config: &Config,
+    ) -> Rect {
+        let line_height = config.editor.line_height as f64;
+        let width = 16.0;
+        let height = 16.0;
+        Size::new(width, height).to_rect().with_origin(Point::new(
+            self.width + char_width + 3.0,
+            (line_height - height) / 2.0 + line_height * line as f64 - scroll_y,
+        ))
+    }
+
+    /// The rect of the fold arrow on a visual line.
+    fn fold_rect(
+        &self,
+        line: usize,
+        scroll_y: f64,
+        char_width: f64,
+        config: &Config,
+    ) -> Rect {
+        let line_height = config.editor.line_height as f64;
+        let size = 12.0;
+        Size::new(size, size).to_rect().with_origin(Point::new(
+            self.width + char_width + 21.0,
+            (line_height - size) / 2.0 + line_height * line as f64 - scroll_y,
+        ))
+    }
+
+    /// Folds or unfolds the region whose arrow is under `pos`, returning
+    /// whether there was one.
+    fn toggle_fold(
+        &self,
+        data: &mut LapceTabData,
+        pos: Point,
+        char_width: f64,
+    ) -> bool {
+        let editor = match data.main_split.editors.get(&self.view_id) {
+            Some(editor) if editor.compare.is_none() && !editor.code_lens => editor,
+            _ => return false,
+        };
+        let path = match &editor.content {
+            BufferContent::File(path) => path,
+            _ => return false,
+        };
+        let scroll_y = editor.scroll_offset.y;
+        let buffer = match data.main_split.open_files.get_mut(path) {
+            Some(buffer) => buffer,
+            None => return false,
+        };
+        let line_height = data.config.editor.line_height as f64;
+        let visual_line = ((pos.y + scroll_y) / line_height).floor() as usize;
+        let rect = self.fold_rect(visual_line, scroll_y, char_width, &data.config);
+        if !rect.contains(pos) {
+            return false;
+        }
+        let line = buffer.actual_line(visual_line);
+        if buffer.is_folded(line) {
+            Arc::make_mut(buffer).unfold(line);
+        } else if let Some((start, end)) = buffer.folding_range_starting(line) {
+            Arc::make_mut(buffer).fold(start, end);
+        } else {
+            return false;
+        }
+        true
     }
 }
 
 impl Widget<LapceTabData> for LapceEditorGutter {
     fn event(
         &mut self,
-        _ctx: &mut EventCtx,
-        _event: &Event,
-        _data: &mut LapceTabData,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
         _env: &Env,
     ) {
+        match event {
+            Event::MouseDown(mouse_event)
+                if mouse_event.button == MouseButton::Left =>
+            {
+                let char_width = data.config.editor_char_width(ctx.text());
+                if self.toggle_fold(data, mouse_event.pos, char_width) {
+                    ctx.set_handled();
+                    return;
+                }
+                if let Some(line) = self.code_actions_line {
+                    let editor = data.main_split.editors.get(&self.view_id);
+                    let scroll_y = match editor {
+                        Some(editor) => editor.scroll_offset.y,
+                        None => return,
+                    };
+                    let rect = self.code_actions_rect(
+                        line,
+                        scroll_y,
+                        char_width,
+                        &data.config,
+                    );
+                    if rect.contains(mouse_event.pos) {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ShowCodeActions,
+                            Target::Auto,
+                        ));
+                        ctx.set_handled();
+                    }
+                }
+            }
+            Event::Timer(token) if *token == self.code_actions_timer => {
+                self.code_actions_timer = TimerToken::INVALID;
+                self.code_actions_line = self.current_code_actions_line(data);
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+            _ => (),
+        }
     }
 
     fn lifecycle(
@@ -46,11 +187,46 @@ impl Widget<LapceTabData> for LapceEditorGutter {
 
     fn update(
         &mut self,
-        _ctx: &mut UpdateCtx,
-        _old_data: &LapceTabData,
-        _data: &LapceTabData,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
         _env: &Env,
     ) {
+        let line = self.current_code_actions_line(data);
+        if line != self.code_actions_line {
+            // Hide the lightbulb right away, but only show it again once
+            // the cursor stops moving.
+            if self.code_actions_line.take().is_some() {
+                ctx.request_paint();
+            }
+            let cursor = |data: &LapceTabData| {
+                data.main_split
+                    .editors
+                    .get(&self.view_id)
+                    .map(|editor| editor.cursor.offset())
+            };
+            if line.is_some()
+                && (self.code_actions_timer == TimerToken::INVALID
+                    || cursor(old_data) != cursor(data))
+            {
+                self.code_actions_timer =
+                    ctx.request_timer(Self::CODE_ACTIONS_DELAY);
+            }
+        }
+
+        if old_data.main_split.editors.contains_key(&self.view_id)
+            && data.main_split.editors.contains_key(&self.view_id)
+        {
+            let old_buffer = old_data.editor_view_content(self.view_id).buffer;
+            let buffer = data.editor_view_content(self.view_id).buffer;
+            if !old_buffer.same(&buffer)
+                && (old_buffer.folding_ranges != buffer.folding_ranges
+                    || old_buffer.folded_lines() != buffer.folded_lines())
+            {
+                ctx.request_paint();
+            }
+        }
+
         // let old_last_line = old_data.buffer.last_line() + 1;
         // let last_line = data.buffer.last_line() + 1;
         // if old_last_line.to_string().len() != last_line.to_string().len() {
@@ -93,6 +269,9 @@ impl Widget<LapceTabData> for LapceEditorGutter {
         let mut width = self.width + 16.0 + char_width * 2.0;
         if data.editor.compare.is_some() {
             width += self.width + char_width * 2.0;
+        } else {
+            // The column of fold arrows
+            width += 16.0;
         }
         Size::new(width.ceil(), bc.max().height)
     }
@@ -438,29 +617,20 @@ impl LapceEditorGutter {
         data: &LapceEditorBufferData,
         ctx: &mut PaintCtx,
     ) {
-        if let Some(actions) = data.current_code_actions() {
-            if !actions.is_empty() {
-                let line_height = data.config.editor.line_height as f64;
-                let offset = data.editor.cursor.offset();
-                let (line, _) = data
-                    .buffer
-                    .offset_to_line_col(offset, data.config.editor.tab_width);
-                let svg = get_svg("lightbulb.svg").unwrap();
-                let width = 16.0;
-                let height = 16.0;
-                let char_width = data.config.editor_char_width(ctx.text());
-                let rect =
-                    Size::new(width, height).to_rect().with_origin(Point::new(
-                        self.width + char_width + 3.0,
-                        (line_height - height) / 2.0 + line_height * line as f64
-                            - data.editor.scroll_offset.y,
-                    ));
-                ctx.draw_svg(
-                    &svg,
-                    rect,
-                    Some(data.config.get_color_unchecked(LapceTheme::LAPCE_WARN)),
-                );
-            }
+        if let Some(line) = self.code_actions_line {
+            let svg = get_svg("lightbulb.svg").unwrap();
+            let char_width = data.config.editor_char_width(ctx.text());
+            let rect = self.code_actions_rect(
+                line,
+                data.editor.scroll_offset.y,
+                char_width,
+                &data.config,
+            );
+            ctx.draw_svg(
+                &svg,
+                rect,
+                Some(data.config.get_color_unchecked(LapceTheme::LAPCE_WARN)),
+            );
         }
     }
 
@@ -483,11 +653,14 @@ impl LapceEditorGutter {
             let num_lines = (ctx.size().height / line_height).floor() as usize;
             let last_line = data.buffer.last_line();
             let current_line = data.editor.cursor.current_line(data.buffer.data());
+            let lines = data.buffer.visual_lines();
             let char_width = data.config.editor_char_width(ctx.text());
 
             let line_label_length =
                 (last_line + 1).to_string().len() as f64 * char_width;
-            let last_displayed_line = (start_line + num_lines + 1).min(last_line);
+            let last_displayed_line =
+                (start_line + num_lines + 1).min(lines.visual_line(last_line));
+            let current_visual_line = lines.visual_line(current_line);
 
             let sequential_line_numbers = *data.main_split.active
                 != Some(data.view_id)
@@ -495,15 +668,16 @@ impl LapceEditorGutter {
 
             let font_family = data.config.editor.font_family();
 
-            for line in start_line..last_displayed_line {
+            for visual_line in start_line..last_displayed_line {
+                let line = lines.actual_line(visual_line);
                 let line_no = if sequential_line_numbers || line == current_line {
                     line + 1
                 } else {
                     // TODO: after Rust 1.60, this can be replaced with `line.abs_diff(current_line)`
-                    if line > current_line {
-                        line - current_line
+                    if visual_line > current_visual_line {
+                        visual_line - current_visual_line
                     } else {
-                        current_line - line
+                        current_visual_line - visual_line
                     }
                 };
 
@@ -529,15 +703,40 @@ impl LapceEditorGutter {
                 let x = line_label_length as f64 - text_layout.size().width;
 
                 // Vertically centered
-                let y = line_height * line as f64 - scroll_offset.y
+                let y = line_height * visual_line as f64 - scroll_offset.y
                     + (line_height - text_layout.size().height) / 2.0;
 
                 ctx.draw_text(&text_layout, Point::new(x, y));
+
+                let fold_icon = if data.buffer.is_folded(line) {
+                    Some("chevron-right.svg")
+                } else if data.buffer.folding_range_starting(line).is_some() {
+                    Some("chevron-down.svg")
+                } else {
+                    None
+                };
+                if let Some(fold_icon) = fold_icon {
+                    let rect = self.fold_rect(
+                        visual_line,
+                        scroll_offset.y,
+                        char_width,
+                        &data.config,
+                    );
+                    ctx.draw_svg(
+                        &get_svg(fold_icon).unwrap(),
+                        rect,
+                        Some(
+                            data.config.get_color_unchecked(LapceTheme::EDITOR_DIM),
+                        ),
+                    );
+                }
             }
 
             if let Some(changes) = data.buffer.history_changes.get("head") {
-                let end_line =
-                    (scroll_offset.y + rect.height() / line_height).ceil() as usize;
+                let start_line = lines.actual_line(start_line);
+                let end_line = lines.actual_line(
+                    (scroll_offset.y + rect.height() / line_height).ceil() as usize,
+                );
 
                 let mut line = 0;
                 let mut last_change = None;
@@ -581,8 +780,11 @@ impl LapceEditorGutter {
                     if let Some(color) = color.cloned() {
                         let removed_height = 10.0;
                         let x = self.width + char_width;
-                        let mut y =
-                            (line - len) as f64 * line_height - scroll_offset.y;
+                        let start = lines.visual_line(line - len);
+                        // Changes folded away are marked on the line they're
+                        // folded under.
+                        let visual_len = (lines.visual_line(line) - start).max(1);
+                        let mut y = start as f64 * line_height - scroll_offset.y;
                         if len == 0 {
                             y -= removed_height / 2.0;
                         }
@@ -605,7 +807,7 @@ impl LapceEditorGutter {
                                 if len == 0 {
                                     removed_height
                                 } else {
-                                    line_height * len as f64
+                                    line_height * visual_len as f64
                                 },
                             ),
                         );
@@ -7,15 +7,20 @@ use druid::{
 };
 use lapce_data::{
     buffer::{BufferContent, LocalBufferKind},
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
     config::LapceTheme,
     data::{
         DragContent, EditorTabChild, LapceEditorTabData, LapceTabData, SplitContent,
     },
     db::EditorTabChildInfo,
     editor::TabRect,
+    menu::MenuItem,
     split::{SplitDirection, SplitMoveDirection},
 };
+use serde_json::json;
 
 use crate::editor::{
     tab_header::LapceEditorTabHeader, view::editor_tab_child_widget,
@@ -51,19 +56,19 @@ impl LapceEditorTab {
         ctx.children_changed();
 
         let editor_tab = data.main_split.editor_tabs.get(&self.widget_id).unwrap();
-        for child in editor_tab.children.iter() {
-            match child {
-                EditorTabChild::Editor(view_id, _) => {
-                    data.main_split.editors.remove(view_id);
-                }
-            }
+        let split = editor_tab.split;
+        let view_ids: Vec<WidgetId> = editor_tab
+            .children
+            .iter()
+            .map(|child| child.widget_id())
+            .collect();
+        for view_id in view_ids.iter() {
+            data.main_split.remove_editor(view_id);
         }
         ctx.submit_command(Command::new(
             LAPCE_UI_COMMAND,
-            LapceUICommand::SplitRemove(SplitContent::EditorTab(
-                editor_tab.widget_id,
-            )),
-            Target::Widget(editor_tab.split),
+            LapceUICommand::SplitRemove(SplitContent::EditorTab(self.widget_id)),
+            Target::Widget(split),
         ));
     }
 
@@ -122,12 +127,110 @@ impl LapceEditorTab {
         if delete {
             match removed_child {
                 EditorTabChild::Editor(view_id, _) => {
-                    data.main_split.editors.remove(&view_id);
+                    data.remember_closed_editor(view_id);
+                    data.main_split.remove_editor(&view_id);
                 }
             }
         }
     }
 
+    /// Closes the tabs of the given editor views, moving focus to the
+    /// nearest surviving tab if the active one was among them.
+    fn close_children(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        view_ids: &[WidgetId],
+    ) {
+        let editor_tab = data.main_split.editor_tabs.get(&self.widget_id).unwrap();
+        let focus = editor_tab
+            .children
+            .get(editor_tab.active)
+            .map(|child| view_ids.contains(&child.widget_id()))
+            .unwrap_or(false);
+        let indices: Vec<usize> = editor_tab
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| view_ids.contains(&child.widget_id()))
+            .map(|(i, _)| i)
+            .collect();
+        for i in indices.into_iter().rev() {
+            self.remove_child(ctx, data, i, true, false);
+        }
+        if focus && !self.children.is_empty() {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::EnsureEditorTabActiveVisble,
+                Target::Widget(self.widget_id),
+            ));
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::Focus,
+                Target::Widget(self.widget_id),
+            ));
+        }
+    }
+
+    /// Closes the tabs of the given editor views that have no unsaved
+    /// changes, and asks before closing the ones that do.
+    fn close_children_with_confirm(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        view_ids: Vec<WidgetId>,
+    ) {
+        if !data.config.lapce.confirm_close_unsaved {
+            self.close_children(ctx, data, &view_ids);
+            return;
+        }
+        let (dirty, clean): (Vec<WidgetId>, Vec<WidgetId>) = view_ids
+            .into_iter()
+            .partition(|view_id| !data.unsaved_editor_files(&[*view_id]).is_empty());
+
+        self.close_children(ctx, data, &clean);
+
+        let unsaved = data.unsaved_editor_files(&dirty);
+        if unsaved.is_empty() {
+            return;
+        }
+        let editors = json!((
+            self.widget_id.to_raw(),
+            dirty.iter().map(|id| id.to_raw()).collect::<Vec<_>>()
+        ));
+        let item = |text: String, cmd: LapceWorkbenchCommand| MenuItem {
+            text,
+            indices: Vec::new(),
+            command: LapceCommandNew {
+                cmd: cmd.to_string(),
+                data: Some(editors.clone()),
+                palette_desc: None,
+                target: CommandTarget::Workbench,
+            },
+        };
+        let save = if unsaved.len() == 1 {
+            "Save and Close"
+        } else {
+            "Save All and Close"
+        };
+        let items = vec![
+            item(save.to_string(), LapceWorkbenchCommand::SaveAndCloseEditors),
+            item(
+                "Close Without Saving".to_string(),
+                LapceWorkbenchCommand::CloseEditorsWithoutSaving,
+            ),
+            item(
+                "Cancel".to_string(),
+                LapceWorkbenchCommand::CancelCloseEditors,
+            ),
+        ];
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowPrompt(Arc::new(items)),
+            Target::Auto,
+        ));
+    }
+
     fn mouse_up(
         &mut self,
         ctx: &mut EventCtx,
@@ -163,6 +266,16 @@ impl LapceEditorTab {
                             None
                         };
                         match direction {
+                            // Splitting a tab bar off its only tab would just
+                            // put the tab back where it was.
+                            Some(_)
+                                if from_id == &self.widget_id
+                                    && data
+                                        .main_split
+                                        .editor_tabs
+                                        .get(&self.widget_id)
+                                        .map(|tab| tab.children.len() == 1)
+                                        .unwrap_or(false) => {}
                             Some(direction) => {
                                 let (split_direction, shift_current) =
                                     match direction {
@@ -276,6 +389,7 @@ impl LapceEditorTab {
                         }
                     }
                 }
+                DragContent::Panel(..) => {}
             }
         }
     }
@@ -331,6 +445,41 @@ impl Widget<LapceTabData> for LapceEditorTab {
                         self.remove_child(ctx, data, *index, *delete, *focus);
                         return;
                     }
+                    LapceUICommand::EditorTabCloseChildren(view_ids) => {
+                        self.close_children(ctx, data, view_ids);
+                        return;
+                    }
+                    LapceUICommand::CloseTabsToRight(view_id) => {
+                        let editor_tab = data
+                            .main_split
+                            .editor_tabs
+                            .get(&self.widget_id)
+                            .unwrap();
+                        let view_ids = editor_tab
+                            .children
+                            .iter()
+                            .map(|child| child.widget_id())
+                            .skip_while(|id| id != view_id)
+                            .skip(1)
+                            .collect();
+                        self.close_children_with_confirm(ctx, data, view_ids);
+                        return;
+                    }
+                    LapceUICommand::CloseOtherTabs(view_id) => {
+                        let editor_tab = data
+                            .main_split
+                            .editor_tabs
+                            .get(&self.widget_id)
+                            .unwrap();
+                        let view_ids = editor_tab
+                            .children
+                            .iter()
+                            .map(|child| child.widget_id())
+                            .filter(|id| id != view_id)
+                            .collect();
+                        self.close_children_with_confirm(ctx, data, view_ids);
+                        return;
+                    }
                     LapceUICommand::SplitClose => {
                         self.clear_child(ctx, data);
                         return;
@@ -355,7 +504,7 @@ impl Widget<LapceTabData> for LapceEditorTab {
                         {
                             let active = &tab.children[tab.active];
                             let EditorTabChildInfo::Editor(info) =
-                                active.child_info(data, 4);
+                                active.child_info(data);
 
                             if info.content
                                 == BufferContent::Local(LocalBufferKind::Empty)
@@ -463,7 +612,9 @@ impl Widget<LapceTabData> for LapceEditorTab {
         );
 
         self.header.paint(ctx, data, env);
-        if ctx.is_hot() && data.drag.is_some() {
+        if ctx.is_hot()
+            && matches!(&*data.drag, Some((_, DragContent::EditorTab(..))))
+        {
             let width = size.width;
             let header_rect = self.header.layout_rect();
             let header_height = header_rect.height();
@@ -9,11 +9,16 @@ use druid::{
 };
 use lapce_data::{
     buffer::BufferContent,
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
     config::LapceTheme,
     data::{DragContent, EditorTabChild, LapceTabData},
     editor::TabRect,
+    menu::MenuItem,
 };
+use strum::EnumMessage;
 
 use crate::{
     editor::tab::TabRectRenderer,
@@ -111,7 +116,64 @@ impl LapceEditorTabHeaderContent {
                     Some((MouseAction::CloseViaMiddleClick, tab_idx));
                 return;
             }
+
+            if mouse_event.button.is_right() {
+                self.right_click(ctx, data, mouse_event, tab_idx);
+                return;
+            }
+        }
+    }
+
+    fn right_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        mouse_event: &MouseEvent,
+        tab_idx: usize,
+    ) {
+        let editor_tab = data
+            .main_split
+            .editor_tabs
+            .get_mut(&self.widget_id)
+            .unwrap();
+        let editor_tab = Arc::make_mut(editor_tab);
+        editor_tab.active = tab_idx;
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(editor_tab.children[tab_idx].widget_id()),
+        ));
+
+        let mut commands = Vec::new();
+        if editor_tab.children.len() > 1 {
+            commands.push(LapceWorkbenchCommand::CloseOtherTabs);
+        }
+        if tab_idx + 1 < editor_tab.children.len() {
+            commands.push(LapceWorkbenchCommand::CloseTabsToRight);
         }
+        if commands.is_empty() {
+            return;
+        }
+        let menu_items = commands
+            .into_iter()
+            .map(|cmd| MenuItem {
+                text: cmd.get_message().unwrap().to_string(),
+                indices: Vec::new(),
+                command: LapceCommandNew {
+                    cmd: cmd.to_string(),
+                    palette_desc: None,
+                    data: None,
+                    target: CommandTarget::Workbench,
+                },
+            })
+            .collect::<Vec<_>>();
+        let point = mouse_event.pos + ctx.window_origin().to_vec2();
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowMenu(point.round(), Arc::new(menu_items)),
+            Target::Auto,
+        ));
+        ctx.request_paint();
     }
 
     fn mouse_move(
@@ -396,8 +458,20 @@ impl Widget<LapceTabData> for LapceEditorTabHeaderContent {
             tab_rect.paint(ctx, data, self.widget_id, i, size, self.mouse_pos);
         }
 
-        if ctx.is_hot() && data.drag.is_some() {
+        if ctx.is_hot()
+            && matches!(&*data.drag, Some((_, DragContent::EditorTab(..))))
+        {
             let mouse_index = self.drag_target_idx(self.mouse_pos);
+            if let Some((_, DragContent::EditorTab(from_id, from_index, ..))) =
+                &*data.drag
+            {
+                // Either side of the dragged tab is where it already is.
+                if *from_id == self.widget_id
+                    && (mouse_index == *from_index || mouse_index == from_index + 1)
+                {
+                    return;
+                }
+            }
 
             let tab_rect;
             let x = if mouse_index == self.after_last_tab_index() {
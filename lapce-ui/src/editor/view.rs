@@ -1,9 +1,9 @@
-use std::{iter::Iterator, str::FromStr, sync::Arc};
+use std::{iter::Iterator, str::FromStr, sync::Arc, time::Duration};
 
 use druid::{
     piet::PietText, BoxConstraints, Command, Data, Env, Event, EventCtx, LayoutCtx,
     LifeCycle, LifeCycleCtx, Modifiers, PaintCtx, Point, Rect, RenderContext, Size,
-    Target, Vec2, Widget, WidgetExt, WidgetId, WidgetPod,
+    Target, TimerToken, Vec2, Widget, WidgetExt, WidgetId, WidgetPod,
 };
 use lapce_data::{
     buffer::{BufferContent, LocalBufferKind},
@@ -11,7 +11,7 @@ use lapce_data::{
         CommandTarget, EnsureVisiblePosition, LapceCommand, LapceCommandNew,
         LapceUICommand, LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND,
     },
-    config::LapceTheme,
+    config::{AutoSaveMode, LapceTheme},
     data::{EditorTabChild, FocusArea, LapceTabData, PanelData, PanelKind},
     editor::LapceEditorBufferData,
     keypress::KeyPressFocus,
@@ -20,7 +20,8 @@ use lapce_data::{
 
 use crate::{
     editor::{
-        container::LapceEditorContainer, header::LapceEditorHeader, LapceEditor,
+        breadcrumbs::LapceEditorBreadcrumbs, container::LapceEditorContainer,
+        header::LapceEditorHeader, LapceEditor,
     },
     find::FindBox,
 };
@@ -28,8 +29,11 @@ use crate::{
 pub struct LapceEditorView {
     pub view_id: WidgetId,
     pub header: WidgetPod<LapceTabData, LapceEditorHeader>,
+    pub breadcrumbs: WidgetPod<LapceTabData, LapceEditorBreadcrumbs>,
     pub editor: WidgetPod<LapceTabData, LapceEditorContainer>,
     pub find: Option<WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>>,
+    /// Fires when the file is to be saved with the after delay auto save
+    auto_save_timer: TimerToken,
 }
 
 pub fn editor_tab_child_widget(
@@ -48,17 +52,34 @@ impl LapceEditorView {
         find_view_id: Option<WidgetId>,
     ) -> LapceEditorView {
         let header = LapceEditorHeader::new(view_id);
+        let breadcrumbs = LapceEditorBreadcrumbs::new(view_id);
         let editor = LapceEditorContainer::new(view_id);
         let find =
             find_view_id.map(|id| WidgetPod::new(FindBox::new(id, view_id)).boxed());
         Self {
             view_id,
             header: WidgetPod::new(header),
+            breadcrumbs: WidgetPod::new(breadcrumbs),
             editor: WidgetPod::new(editor),
             find,
+            auto_save_timer: TimerToken::INVALID,
         }
     }
 
+    /// The command that saves the file when it's auto saved
+    fn auto_save_command(&self) -> Command {
+        Command::new(
+            LAPCE_NEW_COMMAND,
+            LapceCommandNew {
+                cmd: LapceCommand::Save.to_string(),
+                data: None,
+                palette_desc: None,
+                target: CommandTarget::Focus,
+            },
+            Target::Widget(self.view_id),
+        )
+    }
+
     pub fn hide_header(mut self) -> Self {
         self.header.widget_mut().display = false;
         self
@@ -112,7 +133,11 @@ impl LapceEditorView {
             BufferContent::File(_) => {
                 data.focus_area = FocusArea::Editor;
                 data.main_split.active = Arc::new(Some(self.view_id));
-                data.main_split.active_tab = Arc::new(editor.tab_id);
+                // An editor outside of the tabs, like the peek, leaves files
+                // opening in the tab it was shown over.
+                if editor.tab_id.is_some() {
+                    data.main_split.active_tab = Arc::new(editor.tab_id);
+                }
             }
             BufferContent::Local(kind) => match kind {
                 LocalBufferKind::Keymap => {}
@@ -120,9 +145,14 @@ impl LapceEditorView {
                 LocalBufferKind::FilePicker => {
                     data.focus_area = FocusArea::FilePicker;
                 }
-                LocalBufferKind::Search => {
+                LocalBufferKind::Search
+                | LocalBufferKind::Replace
+                | LocalBufferKind::SearchGlobs => {
                     data.focus_area = FocusArea::Panel(PanelKind::Search);
                 }
+                LocalBufferKind::PluginFilter => {
+                    data.focus_area = FocusArea::Panel(PanelKind::Plugin);
+                }
                 LocalBufferKind::SourceControl => {
                     data.focus_area = FocusArea::Panel(PanelKind::SourceControl);
                     Arc::make_mut(&mut data.source_control).active = self.view_id;
@@ -173,7 +203,7 @@ impl LapceEditorView {
                 }
                 let offset = data.editor.cursor.offset();
                 let line = data.buffer.line_of_offset(offset);
-                let _ = data.apply_completion_item(item);
+                let _ = data.accept_completion_item(ctx, item);
                 let new_offset = data.editor.cursor.offset();
                 let new_line = data.buffer.line_of_offset(new_offset);
                 if line != new_line {
@@ -325,7 +355,7 @@ impl LapceEditorView {
         let offset = data.editor.cursor.offset();
         let (line, col) = data
             .buffer
-            .offset_to_line_col(offset, data.config.editor.tab_width);
+            .offset_to_line_col(offset, data.buffer.tab_width(&data.config));
         let width = data.config.editor_char_width(text);
         let cursor_x = col as f64 * width;
         let line_height = data.config.editor.line_height as f64;
@@ -370,7 +400,7 @@ impl LapceEditorView {
             let line = if let Some(compare) = data.editor.compare.as_ref() {
                 data.buffer.diff_visual_line(compare, line)
             } else {
-                line
+                data.buffer.visual_line(line)
             };
             line as f64 * line_height
         };
@@ -425,6 +455,14 @@ impl Widget<LapceTabData> for LapceEditorView {
                     self.request_focus(ctx, data, true);
                 }
             }
+            Event::Timer(token) if *token == self.auto_save_timer => {
+                ctx.set_handled();
+                self.auto_save_timer = TimerToken::INVALID;
+                if data.config.editor.auto_save == AutoSaveMode::AfterDelay {
+                    ctx.submit_command(self.auto_save_command());
+                }
+                return;
+            }
             _ => (),
         }
 
@@ -491,6 +529,7 @@ impl Widget<LapceTabData> for LapceEditorView {
         data.update_from_editor_buffer_data(editor_data, &editor, &buffer);
 
         self.header.event(ctx, event, data, env);
+        self.breadcrumbs.event(ctx, event, data, env);
         self.editor.event(ctx, event, data, env);
 
         let offset = self.editor.widget().editor.widget().inner().offset();
@@ -525,6 +564,11 @@ impl Widget<LapceTabData> for LapceEditorView {
                     ));
                 }
             }
+            LifeCycle::FocusChanged(false)
+                if data.config.editor.auto_save == AutoSaveMode::OnFocusChange =>
+            {
+                ctx.submit_command(self.auto_save_command());
+            }
             LifeCycle::HotChanged(is_hot) => {
                 self.header.widget_mut().view_is_hot = *is_hot;
                 let editor = data.main_split.editors.get(&self.view_id).unwrap();
@@ -538,6 +582,7 @@ impl Widget<LapceTabData> for LapceEditorView {
             _ => (),
         }
         self.header.lifecycle(ctx, event, data, env);
+        self.breadcrumbs.lifecycle(ctx, event, data, env);
         self.editor.lifecycle(ctx, event, data, env);
     }
 
@@ -632,9 +677,30 @@ impl Widget<LapceTabData> for LapceEditorView {
         if editor_data.buffer.dirty() != old_editor_data.buffer.dirty() {
             ctx.request_paint();
         }
+        // Only the focused view waits to save, so a file shown in several
+        // views is saved once
+        if data.config.editor.auto_save == AutoSaveMode::AfterDelay
+            && data.focus == self.view_id
+            && editor_data.buffer.dirty()
+            && editor_data.buffer.rev() != old_editor_data.buffer.rev()
+        {
+            self.auto_save_timer = ctx.request_timer(Duration::from_millis(
+                data.config.editor.auto_save_delay,
+            ));
+        }
         if editor_data.editor.cursor != old_editor_data.editor.cursor {
             ctx.request_paint();
         }
+        // The highlighted matches follow the find, including the one set by
+        // the global search
+        if !editor_data.find.same(&old_editor_data.find) {
+            ctx.request_paint();
+        }
+        if editor_data.completion.preview_text()
+            != old_editor_data.completion.preview_text()
+        {
+            ctx.request_paint();
+        }
 
         let buffer = &editor_data.buffer;
         let old_buffer = &old_editor_data.buffer;
@@ -665,6 +731,7 @@ impl Widget<LapceTabData> for LapceEditorView {
         {
             ctx.request_paint();
         }
+        self.breadcrumbs.update(ctx, data, env);
         self.editor.update(ctx, data, env);
     }
 
@@ -678,6 +745,17 @@ impl Widget<LapceTabData> for LapceEditorView {
         let self_size = bc.max();
         let header_size = self.header.layout(ctx, bc, data, env);
         self.header.set_origin(ctx, data, env, Point::ZERO);
+        let breadcrumbs_size = self.breadcrumbs.layout(ctx, bc, data, env);
+        self.breadcrumbs.set_origin(
+            ctx,
+            data,
+            env,
+            Point::new(0.0, header_size.height),
+        );
+        let header_size = Size::new(
+            header_size.width,
+            header_size.height + breadcrumbs_size.height,
+        );
         let editor_size = if self_size.height > header_size.height {
             let editor_size =
                 Size::new(self_size.width, self_size.height - header_size.height);
@@ -735,6 +813,7 @@ impl Widget<LapceTabData> for LapceEditorView {
 
         self.editor.paint(ctx, data, env);
         self.header.paint(ctx, data, env);
+        self.breadcrumbs.paint(ctx, data, env);
         if let Some(find) = self.find.as_mut() {
             find.paint(ctx, data, env);
         }
@@ -1,5 +1,8 @@
 use std::sync::Arc;
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use druid::{
     piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
@@ -208,7 +211,10 @@ pub fn get_item_children_mut(
 
 pub struct FileExplorer {
     widget_id: WidgetId,
-    file_list: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    file_list:
+        WidgetPod<LapceTabData, LapceScrollNew<LapceTabData, FileExplorerFileList>>,
+    /// The path to scroll into view once the tree has been laid out.
+    reveal: Option<PathBuf>,
 }
 
 impl FileExplorer {
@@ -216,7 +222,8 @@ impl FileExplorer {
         let file_list = LapceScrollNew::new(FileExplorerFileList::new());
         Self {
             widget_id: data.widget_id,
-            file_list: WidgetPod::new(file_list.boxed()),
+            file_list: WidgetPod::new(file_list),
+            reveal: None,
         }
     }
 
@@ -250,6 +257,16 @@ impl Widget<LapceTabData> for FileExplorer {
         data: &mut LapceTabData,
         env: &Env,
     ) {
+        if let Event::Command(cmd) = event {
+            if let Some(LapceUICommand::EnsureExplorerItemVisible(path)) =
+                cmd.get(LAPCE_UI_COMMAND)
+            {
+                self.reveal = Some(path.clone());
+                ctx.request_layout();
+                ctx.set_handled();
+                return;
+            }
+        }
         self.file_list.event(ctx, event, data, env);
     }
 
@@ -284,6 +301,19 @@ impl Widget<LapceTabData> for FileExplorer {
         self.file_list.layout(ctx, bc, data, env);
         self.file_list
             .set_origin(ctx, data, env, Point::new(0.0, 0.0));
+        if let Some(path) = self.reveal.take() {
+            if let Some(index) = data.file_explorer.item_index(&path) {
+                let line_height = self.file_list.widget().child().line_height;
+                // The workspace root isn't drawn, so its first child is at the top.
+                let rect = Size::new(self_size.width, line_height)
+                    .to_rect()
+                    .with_origin(Point::new(
+                        0.0,
+                        index.saturating_sub(1) as f64 * line_height,
+                    ));
+                self.file_list.widget_mut().scroll_to_visible(rect, env);
+            }
+        }
         self_size
     }
 
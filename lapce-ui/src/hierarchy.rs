@@ -0,0 +1,335 @@
+use std::sync::Arc;
+
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Cursor, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget,
+    WidgetExt,
+};
+use lapce_data::{
+    config::LapceTheme,
+    data::{LapceTabData, PanelKind},
+    editor::EditorLocationNew,
+    hierarchy::{HierarchyData, HierarchyKind, HierarchyNode},
+    split::SplitDirection,
+};
+
+use crate::{
+    panel::{LapcePanel, PanelHeaderKind},
+    svg::{get_svg, symbol_svg_new},
+};
+
+pub fn new_hierarchy_panel(
+    panel: PanelKind,
+    title: &str,
+    data: &HierarchyData,
+) -> LapcePanel {
+    LapcePanel::new(
+        panel,
+        data.widget_id,
+        data.split_id,
+        SplitDirection::Vertical,
+        PanelHeaderKind::Simple(title.to_string()),
+        vec![(
+            data.tree_widget_id,
+            PanelHeaderKind::None,
+            HierarchyContent::new(panel).boxed(),
+            None,
+        )],
+    )
+}
+
+/// The indent of the row of a node at `depth`.
+fn row_x(depth: usize) -> f64 {
+    10.0 + depth as f64 * 15.0
+}
+
+/// Whether the node can be expanded, as far as is known before fetching its
+/// children.
+fn is_expandable(node: &HierarchyNode) -> bool {
+    !node.recursive
+        && node
+            .children
+            .as_ref()
+            .map(|children| !children.is_empty())
+            .unwrap_or(true)
+}
+
+/// The calls or types related to the symbol the hierarchy was asked for, as
+/// a tree whose nodes fetch their own children when expanded.
+pub struct HierarchyContent {
+    panel: PanelKind,
+    mouse_pos: Point,
+    line_height: f64,
+    content_height: f64,
+}
+
+impl HierarchyContent {
+    pub fn new(panel: PanelKind) -> Self {
+        Self {
+            panel,
+            mouse_pos: Point::ZERO,
+            line_height: 25.0,
+            content_height: 0.0,
+        }
+    }
+
+    /// Clicking the arrow of a node expands or collapses it, clicking the
+    /// rest of it jumps to the call or type.
+    fn mouse_down(&self, ctx: &mut EventCtx, pos: Point, data: &mut LapceTabData) {
+        let n = (pos.y / self.line_height).floor() as usize;
+        let hierarchy = match data.hierarchy(self.panel) {
+            Some(hierarchy) => hierarchy,
+            None => return,
+        };
+        let (depth, path) = match hierarchy.rows().get(n) {
+            Some((depth, path, _)) => (*depth, path.clone()),
+            None => return,
+        };
+        if pos.x < row_x(depth) + self.line_height {
+            let proxy = data.proxy.clone();
+            if let Some(hierarchy) = data.hierarchy_mut(self.panel) {
+                let hierarchy = Arc::make_mut(hierarchy);
+                if hierarchy.toggle(&path) {
+                    hierarchy.fetch_children(
+                        &proxy,
+                        ctx.get_external_handle(),
+                        &path,
+                    );
+                }
+            }
+            return;
+        }
+        if let Some((path, position)) = hierarchy.location(&path) {
+            let config = data.config.clone();
+            data.main_split.jump_to_location(
+                ctx,
+                None,
+                EditorLocationNew {
+                    path,
+                    position: Some(position),
+                    scroll_offset: None,
+                    history: None,
+                },
+                &config,
+            );
+        }
+    }
+}
+
+impl Widget<LapceTabData> for HierarchyContent {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                if mouse_event.pos.y < self.content_height {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                self.mouse_down(ctx, mouse_event.pos, data);
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if let LifeCycle::HotChanged(_) = event {
+            ctx.request_paint();
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if let (Some(old), Some(new)) =
+            (old_data.hierarchy(self.panel), data.hierarchy(self.panel))
+        {
+            if !Arc::ptr_eq(old, new) {
+                ctx.request_layout();
+            }
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        self.line_height = data.config.editor.line_height as f64;
+        let rows = data
+            .hierarchy(self.panel)
+            .map(|hierarchy| hierarchy.rows().len())
+            .unwrap_or(0);
+        self.content_height = self.line_height * rows as f64;
+        Size::new(bc.max().width, self.content_height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let line_height = self.line_height;
+        let size = ctx.size();
+        let hierarchy = match data.hierarchy(self.panel) {
+            Some(hierarchy) => hierarchy,
+            None => return,
+        };
+        let rows = hierarchy.rows();
+        if rows.is_empty() {
+            let text = match hierarchy.kind {
+                HierarchyKind::IncomingCalls | HierarchyKind::OutgoingCalls => {
+                    "Show the calls of a symbol from the editor"
+                }
+                HierarchyKind::Supertypes | HierarchyKind::Subtypes => {
+                    "Show the types of a symbol from the editor"
+                }
+            };
+            let text_layout = ctx
+                .text()
+                .new_text_layout(text)
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(10.0, (line_height - text_layout.size().height) / 2.0),
+            );
+            return;
+        }
+
+        if ctx.is_hot() && self.mouse_pos.y < self.content_height {
+            let n = (self.mouse_pos.y / line_height).floor() as usize;
+            ctx.fill(
+                Size::new(size.width, line_height)
+                    .to_rect()
+                    .with_origin(Point::new(0.0, line_height * n as f64)),
+                data.config.get_color_unchecked(LapceTheme::PANEL_HOVERED),
+            );
+        }
+
+        let rect = ctx.region().bounding_box();
+        let min = (rect.y0 / line_height).floor() as usize;
+        let max = (rect.y1 / line_height) as usize + 2;
+        let padding = (line_height - 14.0) / 2.0;
+        for (i, (depth, path, node)) in rows.iter().enumerate() {
+            if i < min {
+                continue;
+            }
+            if i > max {
+                break;
+            }
+            let y = line_height * i as f64;
+            let x = row_x(*depth);
+            if is_expandable(node) {
+                let icon_name = if node.expanded {
+                    "chevron-down.svg"
+                } else {
+                    "chevron-right.svg"
+                };
+                let rect = Size::new(line_height, line_height)
+                    .to_rect()
+                    .with_origin(Point::new(x, y))
+                    .inflate(-padding, -padding);
+                ctx.draw_svg(
+                    &get_svg(icon_name).unwrap(),
+                    rect,
+                    Some(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND),
+                    ),
+                );
+            }
+            let x = x + line_height;
+            if let Some(svg) = symbol_svg_new(&node.item.kind) {
+                let rect = Size::new(line_height, line_height)
+                    .to_rect()
+                    .with_origin(Point::new(x, y))
+                    .inflate(-padding, -padding);
+                ctx.draw_svg(&svg, rect, None);
+            }
+
+            let text_layout = ctx
+                .text()
+                .new_text_layout(node.item.name.clone())
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let text_x = x + line_height;
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    text_x,
+                    y + (line_height - text_layout.size().height) / 2.0,
+                ),
+            );
+
+            // Where the call or type is, and items back up the tree aren't
+            // expanded.
+            let mut hint = match hierarchy.location(path) {
+                Some((file, position)) => format!(
+                    "{}:{}",
+                    file.file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    position.line + 1,
+                ),
+                None => String::new(),
+            };
+            if node.recursive {
+                hint += match hierarchy.kind {
+                    HierarchyKind::IncomingCalls => " (recursive caller)",
+                    HierarchyKind::OutgoingCalls => " (recursive call)",
+                    HierarchyKind::Supertypes => " (recursive supertype)",
+                    HierarchyKind::Subtypes => " (recursive subtype)",
+                };
+            }
+            let text_layout_hint = ctx
+                .text()
+                .new_text_layout(hint)
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout_hint,
+                Point::new(
+                    text_x + text_layout.size().width + 5.0,
+                    y + (line_height - text_layout_hint.size().height) / 2.0,
+                ),
+            );
+        }
+    }
+}
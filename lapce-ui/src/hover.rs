@@ -1,16 +1,22 @@
 use std::sync::Arc;
 
 use druid::{
-    theme, BoxConstraints, Command, Data, Env, Event, EventCtx, LayoutCtx,
+    theme, BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, LayoutCtx,
     LifeCycle, LifeCycleCtx, PaintCtx, Point, RenderContext, Size, Target,
     TextLayout, UpdateCtx, Widget, WidgetId, WidgetPod,
 };
 use lapce_data::{
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
     config::LapceTheme,
     data::LapceTabData,
     hover::{HoverData, HoverStatus, HoverTextStyle, MarkdownText},
+    menu::MenuItem,
 };
+use serde_json::json;
+use strum::EnumMessage;
 
 use crate::scroll::{LapceIdentityWrapper, LapceScrollNew};
 
@@ -81,6 +87,17 @@ impl Widget<LapceTabData> for HoverContainer {
                     ctx.request_paint();
                 }
             }
+            Event::MouseMove(mouse_event)
+                if self.content_size.to_rect().contains(mouse_event.pos)
+                    && data.hover.status != HoverStatus::Inactive
+                    && !data.hover.is_empty() =>
+            {
+                // Keep the editor under the hover from taking the pointer
+                // moving onto it as moving away from the hovered word
+                self.hover.event(ctx, event, data, env);
+                ctx.set_handled();
+                return;
+            }
             _ => {}
         }
         self.hover.event(ctx, event, data, env);
@@ -204,11 +221,62 @@ impl Hover {
 impl Widget<LapceTabData> for Hover {
     fn event(
         &mut self,
-        _ctx: &mut EventCtx,
-        _event: &Event,
-        _data: &mut LapceTabData,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
         _env: &Env,
     ) {
+        let origin = Point::new(Self::STARTING_X, Self::STARTING_Y).to_vec2();
+        match event {
+            Event::MouseMove(mouse_event) => {
+                if self
+                    .active_layout
+                    .link_for_pos(mouse_event.pos - origin)
+                    .is_some()
+                {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+            }
+            Event::MouseDown(mouse_event) if mouse_event.button.is_left() => {
+                // Links jump to the locations diagnostics refer to, in this tab.
+                if let Some(link) =
+                    self.active_layout.link_for_pos(mouse_event.pos - origin)
+                {
+                    ctx.submit_command(
+                        link.command.clone().to(Target::Widget(data.id)),
+                    );
+                    ctx.set_handled();
+                }
+            }
+            Event::MouseDown(mouse_event) if mouse_event.button.is_right() => {
+                // The diagnostics are always the first item
+                let text = match data.hover.diagnostics_text.as_ref() {
+                    Some(text) if data.hover.active_item_index == 0 => text,
+                    _ => return,
+                };
+                let cmd = LapceWorkbenchCommand::CopyDiagnostic;
+                let menu_items = vec![MenuItem {
+                    text: cmd.get_message().unwrap().to_string(),
+                    indices: Vec::new(),
+                    command: LapceCommandNew {
+                        cmd: cmd.to_string(),
+                        palette_desc: None,
+                        data: Some(json!(text)),
+                        target: CommandTarget::Workbench,
+                    },
+                }];
+                let point = mouse_event.pos + ctx.window_origin().to_vec2();
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowMenu(point.round(), Arc::new(menu_items)),
+                    Target::Auto,
+                ));
+                ctx.set_handled();
+            }
+            _ => {}
+        }
     }
 
     fn lifecycle(
@@ -239,8 +307,7 @@ impl Widget<LapceTabData> for Hover {
         // If the active item has changed or we've switched our current item existence status then
         // update the layout
         if old_data.hover.active_item_index != data.hover.active_item_index
-            || old_data.hover.get_current_item().is_some()
-                != data.hover.get_current_item().is_some()
+            || !old_data.hover.items.same(&data.hover.items)
         {
             if let Some(item) = data.hover.get_current_item() {
                 let text = item.as_markdown_text();
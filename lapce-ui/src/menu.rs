@@ -1,17 +1,18 @@
 use std::sync::Arc;
 
 use druid::{
-    piet::{Text, TextLayout, TextLayoutBuilder},
+    kurbo::Line,
+    piet::{PietText, Text, TextAttribute, TextLayout, TextLayoutBuilder},
     BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, FontFamily,
-    LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect,
-    RenderContext, Size, Target, UpdateCtx, Widget, WidgetId,
+    FontWeight, LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point,
+    Rect, RenderContext, Size, Target, UpdateCtx, Widget, WidgetId,
 };
 use lapce_data::{
     command::{LapceUICommand, LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND},
     config::LapceTheme,
     data::LapceWindowData,
-    keypress::Alignment,
-    menu::MenuData,
+    keypress::{alternative_keymaps, Alignment},
+    menu::{push_recent_menu_item, MenuData, MenuItem},
 };
 
 pub struct Menu {
@@ -31,6 +32,59 @@ impl Menu {
         ctx.request_focus();
     }
 
+    /// The y of the first item, which is pushed down by a line showing the
+    /// filter while one is typed, or the header of the recently used items
+    fn items_top(&self, data: &MenuData) -> f64 {
+        if data.filter.is_empty() && data.recent_len() == 0 {
+            0.0
+        } else {
+            self.line_height
+        }
+    }
+
+    fn item_at(&self, data: &MenuData, y: f64) -> Option<usize> {
+        let y = y - self.items_top(data);
+        if y < 0.0 {
+            return None;
+        }
+        let n = (y / self.line_height).floor() as usize;
+        if n < data.filtered_items.len() {
+            Some(n)
+        } else {
+            None
+        }
+    }
+
+    /// The text counting the other key sequences bound to the command,
+    /// shown after the first one
+    fn alternatives_text(alternatives: usize) -> String {
+        format!("+{alternatives}")
+    }
+
+    /// The width taken up by the keybinding painted at the right of an item
+    fn keymap_width(
+        &self,
+        text: &mut PietText,
+        data: &LapceWindowData,
+        item: &MenuItem,
+    ) -> f64 {
+        let keymaps = match data.keypress.command_keymaps.get(&item.command.cmd) {
+            Some(keymaps) if !keymaps.is_empty() => keymaps,
+            _ => return 0.0,
+        };
+        let mut width = keymaps[0].width(text);
+        let alternatives = alternative_keymaps(keymaps);
+        if alternatives > 0 {
+            let text_layout = text
+                .new_text_layout(Self::alternatives_text(alternatives))
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .build()
+                .unwrap();
+            width += text_layout.size().width + 15.0;
+        }
+        width
+    }
+
     fn mouse_move(
         &self,
         ctx: &mut EventCtx,
@@ -39,8 +93,7 @@ impl Menu {
     ) {
         ctx.set_handled();
         ctx.set_cursor(&Cursor::Pointer);
-        let n = (mouse_event.pos.y / self.line_height).floor() as usize;
-        if n < data.menu.items.len() {
+        if let Some(n) = self.item_at(&data.menu, mouse_event.pos.y) {
             Arc::make_mut(&mut data.menu).active = n;
         }
     }
@@ -49,16 +102,25 @@ impl Menu {
         &self,
         ctx: &mut EventCtx,
         mouse_event: &MouseEvent,
-        data: &LapceWindowData,
+        data: &mut LapceWindowData,
     ) {
         ctx.set_handled();
-        let n = (mouse_event.pos.y / self.line_height).floor() as usize;
-        if let Some(item) = data.menu.items.get(n) {
+        let item = self
+            .item_at(&data.menu, mouse_event.pos.y)
+            .and_then(|n| data.menu.filtered_items.get(n));
+        if let Some(item) = item {
             ctx.submit_command(Command::new(
                 LAPCE_NEW_COMMAND,
                 item.command.clone(),
                 Target::Widget(data.active_id),
             ));
+            if !data.menu.prompt {
+                let item = item.clone();
+                push_recent_menu_item(
+                    Arc::make_mut(&mut data.recent_menu_items),
+                    &item,
+                );
+            }
         }
     }
 }
@@ -135,10 +197,18 @@ impl Widget<LapceWindowData> for Menu {
         data: &LapceWindowData,
         _env: &Env,
     ) {
-        if !old_data.menu.items.same(&data.menu.items) {
+        if !old_data.menu.items.same(&data.menu.items)
+            || !old_data.menu.filtered_items.same(&data.menu.filtered_items)
+            || old_data.menu.filter.is_empty() != data.menu.filter.is_empty()
+            || !old_data.menu.recent.same(&data.menu.recent)
+        {
             ctx.request_layout();
         }
 
+        if old_data.menu.filter != data.menu.filter {
+            ctx.request_paint();
+        }
+
         if !old_data.menu.shown != data.menu.shown {
             ctx.request_paint();
         }
@@ -150,14 +220,27 @@ impl Widget<LapceWindowData> for Menu {
 
     fn layout(
         &mut self,
-        _ctx: &mut LayoutCtx,
+        ctx: &mut LayoutCtx,
         _bc: &BoxConstraints,
         data: &LapceWindowData,
         _env: &Env,
     ) -> Size {
-        let height = self.line_height * data.menu.items.len() as f64;
+        let height = self.items_top(&data.menu)
+            + self.line_height * data.menu.filtered_items.len() as f64;
 
-        Size::new(300.0, height)
+        let mut width: f64 = 300.0;
+        for item in data.menu.filtered_items.iter() {
+            let text_layout = ctx
+                .text()
+                .new_text_layout(item.text.clone())
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .build()
+                .unwrap();
+            let keymap_width = self.keymap_width(ctx.text(), data, item);
+            width = width.max(10.0 + text_layout.size().width + 20.0 + keymap_width);
+        }
+
+        Size::new(width, height)
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceWindowData, _env: &Env) {
@@ -168,6 +251,7 @@ impl Widget<LapceWindowData> for Menu {
         if data.menu.items.len() == 0 {
             return;
         }
+        let items_top = self.items_top(&data.menu);
 
         let rect = ctx.size().to_rect();
         let shadow_width = 5.0;
@@ -187,7 +271,7 @@ impl Widget<LapceWindowData> for Menu {
             let line_rect = Rect::ZERO
                 .with_origin(Point::new(
                     0.0,
-                    data.menu.active as f64 * self.line_height,
+                    items_top + data.menu.active as f64 * self.line_height,
                 ))
                 .with_size(Size::new(ctx.size().width, self.line_height));
             ctx.fill(
@@ -196,10 +280,43 @@ impl Widget<LapceWindowData> for Menu {
             );
         }
 
-        for (i, item) in data.menu.items.iter().enumerate() {
+        let recent_len = data.menu.recent_len();
+        if recent_len > 0 {
             let text_layout = ctx
                 .text()
-                .new_text_layout(item.text.clone())
+                .new_text_layout("Recently Used")
+                .font(FontFamily::SYSTEM_UI, 12.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    10.0,
+                    (self.line_height - text_layout.size().height) / 2.0,
+                ),
+            );
+            if recent_len < data.menu.filtered_items.len() {
+                let y = items_top + self.line_height * recent_len as f64;
+                ctx.stroke(
+                    Line::new(
+                        Point::new(0.0, y - 0.5),
+                        Point::new(rect.x1, y - 0.5),
+                    ),
+                    data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                    1.0,
+                );
+            }
+        }
+
+        if !data.menu.filter.is_empty() {
+            let text_layout = ctx
+                .text()
+                .new_text_layout(data.menu.filter.clone())
                 .font(FontFamily::SYSTEM_UI, 13.0)
                 .text_color(
                     data.config
@@ -212,7 +329,52 @@ impl Widget<LapceWindowData> for Menu {
                 &text_layout,
                 Point::new(
                     10.0,
-                    self.line_height * i as f64
+                    (self.line_height - text_layout.size().height) / 2.0,
+                ),
+            );
+            ctx.stroke(
+                Line::new(
+                    Point::new(0.0, items_top - 0.5),
+                    Point::new(rect.x1, items_top - 0.5),
+                ),
+                data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                1.0,
+            );
+        }
+
+        let focus_color = data.config.get_color_unchecked(LapceTheme::EDITOR_FOCUS);
+        for (i, item) in data.menu.filtered_items.iter().enumerate() {
+            let mut text_layout = ctx
+                .text()
+                .new_text_layout(item.text.clone())
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                );
+            for index in &item.indices {
+                let (start, c) = match item.text.char_indices().nth(*index) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let end = start + c.len_utf8();
+                text_layout = text_layout.range_attribute(
+                    start..end,
+                    TextAttribute::TextColor(focus_color.clone()),
+                );
+                text_layout = text_layout.range_attribute(
+                    start..end,
+                    TextAttribute::Weight(FontWeight::BOLD),
+                );
+            }
+            let text_layout = text_layout.build().unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    10.0,
+                    items_top
+                        + self.line_height * i as f64
                         + (self.line_height - text_layout.size().height) / 2.0,
                 ),
             );
@@ -221,10 +383,32 @@ impl Widget<LapceWindowData> for Menu {
                 data.keypress.command_keymaps.get(&item.command.cmd)
             {
                 if !keymaps.is_empty() {
-                    let origin = Point::new(
+                    let mut origin = Point::new(
                         rect.x1,
-                        self.line_height * i as f64 + self.line_height / 2.0,
+                        items_top
+                            + self.line_height * i as f64
+                            + self.line_height / 2.0,
                     );
+                    let alternatives = alternative_keymaps(keymaps);
+                    if alternatives > 0 {
+                        let text_layout = ctx
+                            .text()
+                            .new_text_layout(Self::alternatives_text(alternatives))
+                            .font(FontFamily::SYSTEM_UI, 13.0)
+                            .text_color(
+                                data.config
+                                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                                    .clone(),
+                            )
+                            .build()
+                            .unwrap();
+                        let text_size = text_layout.size();
+                        origin.x -= text_size.width + 15.0;
+                        ctx.draw_text(
+                            &text_layout,
+                            origin - (0.0, text_size.height / 2.0),
+                        );
+                    }
                     keymaps[0].paint(ctx, origin, Alignment::Right, &data.config);
                 }
             }
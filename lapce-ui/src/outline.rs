@@ -1,20 +1,277 @@
-use druid::WidgetId;
+use std::sync::Arc;
 
-pub struct OutlineState {
-    #[allow(dead_code)]
-    widget_id: WidgetId,
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Cursor, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget,
+    WidgetExt,
+};
+use lapce_data::{
+    buffer::BufferContent,
+    config::LapceTheme,
+    data::{LapceTabData, PanelKind},
+    outline::{outline_row_at, outline_rows, OutlineData},
+    split::SplitDirection,
+};
+use lsp_types::{DocumentSymbol, Position};
+
+use crate::{
+    panel::{LapcePanel, PanelHeaderKind},
+    svg::symbol_svg_new,
+};
+
+pub fn new_outline_panel(data: &OutlineData) -> LapcePanel {
+    LapcePanel::new(
+        PanelKind::Outline,
+        data.widget_id,
+        data.split_id,
+        SplitDirection::Vertical,
+        PanelHeaderKind::Simple("Outline".to_string()),
+        vec![(
+            data.symbols_widget_id,
+            PanelHeaderKind::None,
+            OutlineContent::new().boxed(),
+            None,
+        )],
+    )
+}
+
+/// The symbols of the file in the active editor, as a tree.
+pub struct OutlineContent {
+    mouse_pos: Point,
+    line_height: f64,
+    content_height: f64,
 }
 
-impl OutlineState {
+impl OutlineContent {
     pub fn new() -> Self {
         Self {
-            widget_id: WidgetId::next(),
+            mouse_pos: Point::ZERO,
+            line_height: 25.0,
+            content_height: 0.0,
         }
     }
+
+    /// The symbols of the file in the active editor and where its cursor is.
+    fn symbols(data: &LapceTabData) -> Option<(Arc<Vec<DocumentSymbol>>, Position)> {
+        let editor = data.main_split.active_editor()?;
+        let path = match &editor.content {
+            BufferContent::File(path) => path,
+            _ => return None,
+        };
+        let buffer = data.main_split.open_files.get(path)?;
+        let (_, symbols) = buffer.document_symbols.as_ref()?;
+        let position = buffer.offset_to_position(editor.cursor.offset());
+        Some((symbols.clone(), position))
+    }
+
+    fn mouse_down(&self, ctx: &mut EventCtx, pos: Point, data: &mut LapceTabData) {
+        let n = (pos.y / self.line_height).floor() as usize;
+        let position = match Self::symbols(data) {
+            Some((symbols, _)) => match outline_rows(&symbols).get(n) {
+                Some((_, symbol)) => symbol.selection_range.start,
+                None => return,
+            },
+            None => return,
+        };
+        let config = data.config.clone();
+        data.main_split
+            .jump_to_position(ctx, None, position, &config);
+    }
 }
 
-impl Default for OutlineState {
+impl Default for OutlineContent {
     fn default() -> Self {
         Self::new()
     }
 }
+
+impl Widget<LapceTabData> for OutlineContent {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                if mouse_event.pos.y < self.content_height {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                self.mouse_down(ctx, mouse_event.pos, data);
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if let LifeCycle::HotChanged(_) = event {
+            ctx.request_paint();
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        let symbols = Self::symbols(data);
+        let old_symbols = Self::symbols(old_data);
+        let same_symbols = match (&symbols, &old_symbols) {
+            (Some((symbols, _)), Some((old_symbols, _))) => {
+                Arc::ptr_eq(symbols, old_symbols)
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if !same_symbols {
+            ctx.request_layout();
+        } else if symbols.map(|(_, position)| position)
+            != old_symbols.map(|(_, position)| position)
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        self.line_height = data.config.editor.line_height as f64;
+        let n = Self::symbols(data)
+            .map(|(symbols, _)| outline_rows(&symbols).len())
+            .unwrap_or(0);
+        self.content_height = self.line_height * n as f64;
+        Size::new(bc.max().width, self.content_height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let line_height = self.line_height;
+        let size = ctx.size();
+        let (symbols, position) =
+            match Self::symbols(data).filter(|(symbols, _)| !symbols.is_empty()) {
+                Some(symbols) => symbols,
+                None => {
+                    let text_layout = ctx
+                        .text()
+                        .new_text_layout("No symbols found in the document")
+                        .font(FontFamily::SYSTEM_UI, 13.0)
+                        .text_color(
+                            data.config
+                                .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                                .clone(),
+                        )
+                        .build()
+                        .unwrap();
+                    ctx.draw_text(
+                        &text_layout,
+                        Point::new(
+                            10.0,
+                            (line_height - text_layout.size().height) / 2.0,
+                        ),
+                    );
+                    return;
+                }
+            };
+        let rows = outline_rows(&symbols);
+
+        if let Some(n) = outline_row_at(&rows, position) {
+            ctx.fill(
+                Size::new(size.width, line_height)
+                    .to_rect()
+                    .with_origin(Point::new(0.0, line_height * n as f64)),
+                data.config.get_color_unchecked(LapceTheme::PANEL_CURRENT),
+            );
+        }
+        if ctx.is_hot() && self.mouse_pos.y < self.content_height {
+            let n = (self.mouse_pos.y / line_height).floor() as usize;
+            ctx.fill(
+                Size::new(size.width, line_height)
+                    .to_rect()
+                    .with_origin(Point::new(0.0, line_height * n as f64)),
+                data.config.get_color_unchecked(LapceTheme::PANEL_HOVERED),
+            );
+        }
+
+        let rect = ctx.region().bounding_box();
+        let min = (rect.y0 / line_height).floor() as usize;
+        let max = (rect.y1 / line_height) as usize + 2;
+        let padding = (line_height - 14.0) / 2.0;
+        for (i, (depth, symbol)) in rows.iter().enumerate() {
+            if i < min {
+                continue;
+            }
+            if i > max {
+                break;
+            }
+            let y = line_height * i as f64;
+            let x = 10.0 + *depth as f64 * 15.0;
+            if let Some(svg) = symbol_svg_new(&symbol.kind) {
+                let rect = Size::new(line_height, line_height)
+                    .to_rect()
+                    .with_origin(Point::new(x, y))
+                    .inflate(-padding, -padding);
+                ctx.draw_svg(&svg, rect, None);
+            }
+
+            let text_layout = ctx
+                .text()
+                .new_text_layout(symbol.name.clone())
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let text_x = x + line_height;
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    text_x,
+                    y + (line_height - text_layout.size().height) / 2.0,
+                ),
+            );
+
+            if let Some(detail) = symbol.detail.as_ref() {
+                let text_layout_detail = ctx
+                    .text()
+                    .new_text_layout(detail.clone())
+                    .font(FontFamily::SYSTEM_UI, 13.0)
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                ctx.draw_text(
+                    &text_layout_detail,
+                    Point::new(
+                        text_x + text_layout.size().width + 5.0,
+                        y + (line_height - text_layout_detail.size().height) / 2.0,
+                    ),
+                );
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use druid::kurbo::Line;
 use druid::piet::{Svg, TextAttribute, TextLayout};
@@ -9,7 +10,7 @@ use druid::{
     piet::{Text, TextLayoutBuilder},
     BoxConstraints, Command, Data, Env, Event, EventCtx, FontFamily, LayoutCtx,
     LifeCycle, LifeCycleCtx, PaintCtx, Point, RenderContext, Size, Target,
-    UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod, WindowId,
+    TimerToken, UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod, WindowId,
 };
 use lapce_data::config::Config;
 use lapce_data::palette::PaletteItemContent;
@@ -37,9 +38,14 @@ pub enum PaletteIcon {
     None,
 }
 
+/// How long the input has to stay the same before searching the workspace
+/// symbols for it
+const WORKSPACE_SYMBOL_DELAY: Duration = Duration::from_millis(150);
+
 pub struct NewPalette {
     widget_id: WidgetId,
     container: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    workspace_symbol_timer: TimerToken,
 }
 
 impl NewPalette {
@@ -48,6 +54,7 @@ impl NewPalette {
         Self {
             widget_id: data.widget_id,
             container: WidgetPod::new(container).boxed(),
+            workspace_symbol_timer: TimerToken::INVALID,
         }
     }
 }
@@ -81,6 +88,12 @@ impl Widget<LapceTabData> for NewPalette {
                 let mut_keypress = Arc::make_mut(&mut keypress);
                 let mut palette_data = data.palette_view_data();
                 mut_keypress.key_down(ctx, key_event, &mut palette_data, env);
+                if palette_data.palette.palette_type == PaletteType::WorkspaceSymbol
+                    && palette_data.palette.input != data.palette.input
+                {
+                    self.workspace_symbol_timer =
+                        ctx.request_timer(WORKSPACE_SYMBOL_DELAY);
+                }
                 data.palette = palette_data.palette.clone();
                 data.keypress = keypress;
                 data.workspace = palette_data.workspace.clone();
@@ -88,6 +101,15 @@ impl Widget<LapceTabData> for NewPalette {
                 data.find = palette_data.find.clone();
                 ctx.set_handled();
             }
+            Event::Timer(token) if *token == self.workspace_symbol_timer => {
+                self.workspace_symbol_timer = TimerToken::INVALID;
+                if data.palette.palette_type == PaletteType::WorkspaceSymbol {
+                    let mut palette_data = data.palette_view_data();
+                    palette_data.get_workspace_symbols(ctx);
+                    data.palette = palette_data.palette.clone();
+                }
+                ctx.set_handled();
+            }
             Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
                 match command {
@@ -110,6 +132,28 @@ impl Widget<LapceTabData> for NewPalette {
                         data.workspace = palette_data.workspace.clone();
                         data.main_split = palette_data.main_split.clone();
                     }
+                    LapceUICommand::RunPaletteCommands(commands) => {
+                        ctx.request_focus();
+                        let mut palette_data = data.palette_view_data();
+                        palette_data.run_commands(ctx, commands);
+                        data.palette = palette_data.palette.clone();
+                        data.keypress = palette_data.keypress.clone();
+                        data.workspace = palette_data.workspace.clone();
+                        data.main_split = palette_data.main_split.clone();
+                    }
+                    LapceUICommand::RunPaletteRename {
+                        path,
+                        position,
+                        placeholder,
+                    } => {
+                        ctx.request_focus();
+                        let mut palette_data = data.palette_view_data();
+                        palette_data.run_rename(ctx, path, *position, placeholder);
+                        data.palette = palette_data.palette.clone();
+                        data.keypress = palette_data.keypress.clone();
+                        data.workspace = palette_data.workspace.clone();
+                        data.main_split = palette_data.main_split.clone();
+                    }
                     LapceUICommand::CancelPalette => {
                         let mut palette_data = data.palette_view_data();
                         palette_data.cancel(ctx);
@@ -490,11 +534,16 @@ impl Widget<PaletteViewData> for NewPaletteInput {
         let text = data.palette.input.clone();
         let cursor = data.palette.cursor;
 
-        let text_layout = if text.is_empty()
-            && data.palette.palette_type == PaletteType::SshHost
-        {
+        let hint = match data.palette.palette_type {
+            PaletteType::SshHost | PaletteType::SwitchSshHost => {
+                Some("Enter your SSH details, like user@host")
+            }
+            PaletteType::Rename => Some("Enter the new name"),
+            _ => None,
+        };
+        let text_layout = if let Some(hint) = hint.filter(|_| text.is_empty()) {
             ctx.text()
-                .new_text_layout("Enter your SSH details, like user@host")
+                .new_text_layout(hint)
                 .font(FontFamily::SYSTEM_UI, 14.0)
                 .text_color(
                     data.config
@@ -594,6 +643,21 @@ impl NewPaletteContent {
                 PaletteItemContent::Line(_, text) => {
                     (None, text.clone(), indices.to_vec(), "".to_string(), vec![])
                 }
+                PaletteItemContent::WorkspaceSymbol {
+                    kind,
+                    name,
+                    container_name,
+                    path,
+                    ..
+                } => {
+                    let hint = match container_name.as_ref() {
+                        Some(container_name) => {
+                            format!("{container_name} {}", path.to_string_lossy())
+                        }
+                        None => path.to_string_lossy().to_string(),
+                    };
+                    (symbol_svg_new(kind), name.clone(), vec![], hint, vec![])
+                }
                 PaletteItemContent::ReferenceLocation(rel_path, _location) => {
                     Self::file_paint_items(rel_path, indices)
                 }
@@ -628,6 +692,16 @@ impl NewPaletteContent {
                     "".to_string(),
                     vec![],
                 ),
+                PaletteItemContent::Language(language) => (
+                    None,
+                    language
+                        .map(|language| language.display_name())
+                        .unwrap_or("Auto Detect")
+                        .to_string(),
+                    indices.to_vec(),
+                    "".to_string(),
+                    vec![],
+                ),
                 PaletteItemContent::TerminalLine(_line, content) => (
                     None,
                     content.clone(),
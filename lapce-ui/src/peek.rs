@@ -0,0 +1,289 @@
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Command, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, Target, UpdateCtx,
+    Widget, WidgetExt, WidgetPod,
+};
+use lapce_data::{
+    buffer::BufferContent,
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::LapceTabData,
+    peek::PeekData,
+};
+
+use crate::{
+    editor::view::LapceEditorView,
+    svg::{file_svg_new, get_svg},
+    tab::LapceIcon,
+};
+
+/// The definition shown below the line it was asked from, in an editor with
+/// a header naming its file and buttons to open it or close the peek.
+pub struct PeekContainer {
+    header_height: f64,
+    mouse_pos: Point,
+    icons: Vec<LapceIcon>,
+    editor: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+}
+
+impl PeekContainer {
+    pub fn new(data: &PeekData) -> Self {
+        let editor = LapceEditorView::new(data.editor_view_id, None).hide_header();
+        Self {
+            header_height: 25.0,
+            mouse_pos: Point::ZERO,
+            icons: Vec::new(),
+            editor: WidgetPod::new(editor.boxed()),
+        }
+    }
+
+    fn get_icons(&self, width: f64) -> Vec<LapceIcon> {
+        let icon_size = self.header_height;
+        [
+            ("close.svg", LapceUICommand::ClosePeek),
+            ("link.svg", LapceUICommand::OpenPeek),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (icon, command))| LapceIcon {
+            icon: icon.to_string(),
+            rect: Size::new(icon_size, icon_size)
+                .to_rect()
+                .with_origin(Point::new(width - (i + 1) as f64 * icon_size, 0.0)),
+            command: Command::new(LAPCE_UI_COMMAND, command, Target::Auto),
+        })
+        .collect()
+    }
+
+    fn paint_header(&self, ctx: &mut PaintCtx, data: &LapceTabData) {
+        let size = ctx.size();
+        let header_rect = Size::new(size.width, self.header_height).to_rect();
+        ctx.fill(
+            header_rect,
+            data.config
+                .get_color_unchecked(LapceTheme::PANEL_BACKGROUND),
+        );
+
+        let editor = data.main_split.editors.get(&data.peek.editor_view_id);
+        if let Some((path, buffer, editor)) =
+            editor.and_then(|editor| match &editor.content {
+                BufferContent::File(path) => {
+                    Some((path, data.main_split.open_files.get(path)?, editor))
+                }
+                _ => None,
+            })
+        {
+            let mut clip_rect = header_rect;
+            if let Some(icon) = self.icons.last() {
+                clip_rect.x1 = icon.rect.x0;
+            }
+            ctx.with_save(|ctx| {
+                ctx.clip(clip_rect);
+                let svg_size = 13.0;
+                let rect =
+                    Size::new(svg_size, svg_size)
+                        .to_rect()
+                        .with_origin(Point::new(
+                            (self.header_height - svg_size) / 2.0,
+                            (self.header_height - svg_size) / 2.0,
+                        ));
+                ctx.draw_svg(&file_svg_new(path), rect, None);
+
+                let line = buffer.line_of_offset(editor.cursor.offset());
+                let file_name = format!(
+                    "{}:{}",
+                    path.file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    line + 1
+                );
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(file_name)
+                    .font(FontFamily::SYSTEM_UI, 13.0)
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                ctx.draw_text(
+                    &text_layout,
+                    Point::new(
+                        self.header_height,
+                        (self.header_height - text_layout.size().height) / 2.0,
+                    ),
+                );
+
+                let path = match data.workspace.path.as_ref() {
+                    Some(workspace_path) => {
+                        path.strip_prefix(workspace_path).unwrap_or(path)
+                    }
+                    None => path,
+                };
+                let folder = path
+                    .parent()
+                    .map(|folder| folder.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if !folder.is_empty() {
+                    let x = self.header_height + text_layout.size().width + 5.0;
+                    let text_layout = ctx
+                        .text()
+                        .new_text_layout(folder)
+                        .font(FontFamily::SYSTEM_UI, 13.0)
+                        .text_color(
+                            data.config
+                                .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                                .clone(),
+                        )
+                        .build()
+                        .unwrap();
+                    ctx.draw_text(
+                        &text_layout,
+                        Point::new(
+                            x,
+                            (self.header_height - text_layout.size().height) / 2.0,
+                        ),
+                    );
+                }
+            });
+        }
+
+        for icon in self.icons.iter() {
+            if ctx.is_hot() && icon.rect.contains(self.mouse_pos) {
+                ctx.fill(
+                    &icon.rect,
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
+                );
+            }
+            if let Some(svg) = get_svg(&icon.icon) {
+                ctx.draw_svg(
+                    &svg,
+                    icon.rect.inflate(-4.0, -4.0),
+                    Some(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND),
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Where the editor the peek was opened from is, which the peek follows.
+fn parent_rect(data: &LapceTabData) -> Option<Rect> {
+    let editor = data.main_split.editors.get(&data.peek.parent_view_id)?;
+    let size = *editor.size.borrow();
+    Some(size.to_rect().with_origin(editor.window_origin))
+}
+
+impl Widget<LapceTabData> for PeekContainer {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
+    ) {
+        if !data.peek.visible {
+            return;
+        }
+        let mouse_pos = match event {
+            Event::MouseDown(mouse_event)
+            | Event::MouseUp(mouse_event)
+            | Event::MouseMove(mouse_event)
+            | Event::Wheel(mouse_event) => Some(mouse_event.pos),
+            _ => None,
+        };
+        match event {
+            Event::MouseDown(mouse_event)
+                if mouse_event.pos.y < self.header_height
+                    && ctx.size().to_rect().contains(mouse_event.pos) =>
+            {
+                for icon in self.icons.iter() {
+                    if icon.rect.contains(mouse_event.pos) {
+                        ctx.submit_command(icon.command.clone());
+                    }
+                }
+                ctx.set_handled();
+                return;
+            }
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                ctx.request_paint();
+            }
+            _ => {}
+        }
+        self.editor.event(ctx, event, data, env);
+        // Keep the editor under the peek from taking the pointer.
+        if let Some(pos) = mouse_pos {
+            if ctx.size().to_rect().contains(pos) {
+                ctx.set_handled();
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        self.editor.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        if !old_data.peek.same(&data.peek)
+            || (data.peek.visible && parent_rect(old_data) != parent_rect(data))
+        {
+            ctx.request_layout();
+        }
+        self.editor.update(ctx, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        _bc: &BoxConstraints,
+        data: &LapceTabData,
+        env: &Env,
+    ) -> Size {
+        self.header_height = data.config.editor.line_height as f64;
+        let size = data.peek_size();
+        let bc = BoxConstraints::tight(Size::new(
+            size.width,
+            size.height - self.header_height,
+        ));
+        self.editor.layout(ctx, &bc, data, env);
+        self.editor
+            .set_origin(ctx, data, env, Point::new(0.0, self.header_height));
+        self.icons = self.get_icons(size.width);
+        ctx.set_paint_insets((10.0, 10.0, 10.0, 10.0));
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
+        if !data.peek.visible {
+            return;
+        }
+        let rect = ctx.size().to_rect();
+        ctx.blurred_rect(
+            rect,
+            5.0,
+            data.config
+                .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+        );
+        self.paint_header(ctx, data);
+        self.editor.paint(ctx, data, env);
+    }
+}
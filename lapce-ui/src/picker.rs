@@ -1,26 +1,27 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     path::{Path, PathBuf},
     sync::Arc,
 };
 
 use druid::{
     kurbo::Line,
-    piet::{Svg, Text, TextLayout, TextLayoutBuilder},
-    BoxConstraints, Command, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle,
-    LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect, RenderContext, Size, Target,
-    UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod,
+    piet::{Svg, Text, TextAttribute, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Command, Env, Event, EventCtx, FontFamily, FontWeight,
+    LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect,
+    RenderContext, Size, Target, UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod,
 };
 use lapce_data::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::{Config, LapceTheme},
     data::LapceTabData,
+    picker::FilePickerMatch,
 };
 use lapce_rpc::file::FileNodeItem;
 
 use crate::{
     editor::view::LapceEditorView,
-    explorer::{get_item_children, get_item_children_mut},
+    explorer::get_item_children_mut,
     scroll::LapceScrollNew,
     svg::{file_svg_new, get_svg},
     tab::LapceButton,
@@ -460,10 +461,18 @@ impl FilePickerExplorer {
         mouse_event: &MouseEvent,
     ) {
         ctx.set_handled();
-        let picker = Arc::make_mut(&mut data.picker);
-        let pwd = picker.pwd.clone();
         let index =
             ((mouse_event.pos.y + self.line_height) / self.line_height) as usize;
+        if mouse_event.mods.ctrl() || mouse_event.mods.meta() {
+            Arc::make_mut(&mut data.picker).toggle_selected_path(index);
+            return;
+        }
+        if !data.picker.filter.is_empty() {
+            self.filtered_mouse_down(ctx, data, index);
+            return;
+        }
+        let picker = Arc::make_mut(&mut data.picker);
+        let pwd = picker.pwd.clone();
         if let Some(item) = picker.get_file_node_mut(&pwd) {
             let (_, node) = get_item_children_mut(0, index, item);
             if let Some(node) = node {
@@ -584,6 +593,29 @@ impl FilePickerExplorer {
             }
         }
     }
+
+    /// A click selects a match of the filter, and a double click opens it.
+    fn filtered_mouse_down(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        index: usize,
+    ) {
+        if data.picker.node_at(index).is_none() {
+            return;
+        }
+        let double_click = matches!(
+            self.last_left_click,
+            Some((i, t)) if i == index && t.elapsed().as_millis() < 500
+        );
+        Arc::make_mut(&mut data.picker).index = index;
+        if double_click {
+            self.last_left_click = None;
+            data.open_picker_match(ctx);
+        } else {
+            self.last_left_click = Some((index, std::time::Instant::now()));
+        }
+    }
 }
 
 impl Default for FilePickerExplorer {
@@ -606,18 +638,11 @@ impl Widget<LapceTabData> for FilePickerExplorer {
             }
             Event::MouseMove(mouse_event) => {
                 ctx.set_handled();
-                let picker = Arc::make_mut(&mut data.picker);
-                let pwd = picker.pwd.clone();
                 let index = ((mouse_event.pos.y + self.line_height)
                     / self.line_height) as usize;
                 ctx.request_paint();
-                if let Some(item) = picker.get_file_node_mut(&pwd) {
-                    let (_, node) = get_item_children(0, index, item);
-                    if let Some(_node) = node {
-                        ctx.set_cursor(&druid::Cursor::Pointer);
-                    } else {
-                        ctx.clear_cursor();
-                    }
+                if data.picker.node_at(index).is_some() {
+                    ctx.set_cursor(&druid::Cursor::Pointer);
                 } else {
                     ctx.clear_cursor();
                 }
@@ -651,6 +676,16 @@ impl Widget<LapceTabData> for FilePickerExplorer {
         if data.picker.pwd != old_data.picker.pwd {
             ctx.request_layout();
         }
+
+        if data.picker.filter != old_data.picker.filter
+            || data.picker.filtered_items != old_data.picker.filtered_items
+        {
+            ctx.request_layout();
+        }
+
+        if data.picker.selected_paths != old_data.picker.selected_paths {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -660,8 +695,9 @@ impl Widget<LapceTabData> for FilePickerExplorer {
         data: &LapceTabData,
         _env: &Env,
     ) -> Size {
-        let height = if let Some(item) = data.picker.get_file_node(&data.picker.pwd)
-        {
+        let height = if !data.picker.filter.is_empty() {
+            data.picker.filtered_items.len() as f64 * self.line_height
+        } else if let Some(item) = data.picker.get_file_node(&data.picker.pwd) {
             item.children_open_count as f64 * self.line_height
         } else {
             bc.max().height
@@ -680,6 +716,29 @@ impl Widget<LapceTabData> for FilePickerExplorer {
 
         self.toggle_rects.clear();
 
+        if !data.picker.filter.is_empty() {
+            for (i, item) in data.picker.filtered_items.iter().enumerate() {
+                let current = i + 1;
+                if current < min {
+                    continue;
+                }
+                if current > max {
+                    return;
+                }
+                paint_file_match(
+                    ctx,
+                    item,
+                    current,
+                    index,
+                    self.line_height,
+                    width,
+                    &data.picker.selected_paths,
+                    &data.config,
+                );
+            }
+            return;
+        }
+
         if let Some(item) = data.picker.get_file_node(&data.picker.pwd) {
             let mut i = 0;
             for item in item.sorted_children() {
@@ -694,6 +753,7 @@ impl Widget<LapceTabData> for FilePickerExplorer {
                     i + 1,
                     index,
                     None,
+                    &data.picker.selected_paths,
                     &data.config,
                     &mut self.toggle_rects,
                 );
@@ -717,6 +777,7 @@ pub fn paint_file_node_item_by_index(
     current: usize,
     active: usize,
     hovered: Option<usize>,
+    selected: &BTreeSet<PathBuf>,
     config: &Config,
     toggle_rects: &mut HashMap<usize, Rect>,
 ) -> usize {
@@ -727,7 +788,9 @@ pub fn paint_file_node_item_by_index(
         return current + item.children_open_count;
     }
     if current >= min {
-        let background = if current == active {
+        let background = if selected.contains(&item.path_buf) {
+            Some(LapceTheme::EDITOR_SELECTION)
+        } else if current == active {
             Some(LapceTheme::PANEL_CURRENT)
         } else if Some(current) == hovered {
             Some(LapceTheme::PANEL_HOVERED)
@@ -825,6 +888,7 @@ pub fn paint_file_node_item_by_index(
                 i + 1,
                 active,
                 hovered,
+                selected,
                 config,
                 toggle_rects,
             );
@@ -836,6 +900,87 @@ pub fn paint_file_node_item_by_index(
     i
 }
 
+/// Paints a match of the picker's filter at row `current`, counting from 1,
+/// with the matched characters highlighted.
+#[allow(clippy::too_many_arguments)]
+fn paint_file_match(
+    ctx: &mut PaintCtx,
+    item: &FilePickerMatch,
+    current: usize,
+    active: usize,
+    line_height: f64,
+    width: f64,
+    selected: &BTreeSet<PathBuf>,
+    config: &Config,
+) {
+    let y = current as f64 * line_height - line_height;
+    let background = if selected.contains(&item.path) {
+        Some(LapceTheme::EDITOR_SELECTION)
+    } else if current == active {
+        Some(LapceTheme::PANEL_CURRENT)
+    } else {
+        None
+    };
+    if let Some(background) = background {
+        ctx.fill(
+            Rect::ZERO
+                .with_origin(Point::new(0.0, y))
+                .with_size(Size::new(width, line_height)),
+            config.get_color_unchecked(background),
+        );
+    }
+
+    let svg_size = 15.0;
+    let padding = 15.0;
+    let svg = if item.is_dir {
+        get_svg("default_folder.svg").unwrap()
+    } else {
+        file_svg_new(&item.path)
+    };
+    let rect = Size::new(svg_size, svg_size)
+        .to_rect()
+        .with_origin(Point::new(1.0 + 16.0 + padding, y + 4.0));
+    ctx.draw_svg(&svg, rect, None);
+
+    let name = item
+        .path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_string();
+    let focus_color = config.get_color_unchecked(LapceTheme::EDITOR_FOCUS);
+    let mut text_layout = ctx
+        .text()
+        .new_text_layout(name.clone())
+        .font(FontFamily::SYSTEM_UI, 13.0)
+        .text_color(
+            config
+                .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                .clone(),
+        );
+    for index in &item.indices {
+        let (start, c) = match name.char_indices().nth(*index) {
+            Some(c) => c,
+            None => continue,
+        };
+        let end = start + c.len_utf8();
+        text_layout = text_layout.range_attribute(
+            start..end,
+            TextAttribute::TextColor(focus_color.clone()),
+        );
+        text_layout = text_layout
+            .range_attribute(start..end, TextAttribute::Weight(FontWeight::BOLD));
+    }
+    let text_layout = text_layout.build().unwrap();
+    ctx.draw_text(
+        &text_layout,
+        Point::new(
+            38.0 + padding,
+            y + (line_height - text_layout.size().height) / 2.0,
+        ),
+    );
+}
+
 pub struct FilePickerControl {
     buttons: Vec<LapceButton>,
 }
@@ -867,35 +1012,54 @@ impl FilePickerControl {
             {
                 let command = btn.command.get_unchecked(LAPCE_UI_COMMAND);
                 match command {
+                    LapceUICommand::SetWorkspace(_)
+                        if !data.picker.selected_paths.is_empty() =>
+                    {
+                        data.open_picker_match(ctx);
+                    }
                     LapceUICommand::SetWorkspace(workspace) => {
-                        if let Some(item) =
-                            data.picker.get_file_node(&data.picker.pwd)
-                        {
-                            let (_, node) =
-                                get_item_children(0, data.picker.index, item);
-                            if let Some(node) = node {
-                                if node.is_dir {
-                                    let mut workspace = workspace.clone();
-                                    workspace.path = Some(node.path_buf.clone());
-                                    ctx.submit_command(Command::new(
-                                        LAPCE_UI_COMMAND,
-                                        LapceUICommand::SetWorkspace(workspace),
-                                        Target::Auto,
-                                    ));
-                                } else {
-                                    ctx.submit_command(Command::new(
-                                        LAPCE_UI_COMMAND,
-                                        LapceUICommand::OpenFile(
-                                            node.path_buf.clone(),
-                                        ),
-                                        Target::Widget(data.id),
-                                    ));
-                                    let picker = Arc::make_mut(&mut data.picker);
-                                    picker.active = false;
-                                }
+                        if let Some(node) = data.picker.selected_node() {
+                            if node.is_dir {
+                                let mut workspace = workspace.clone();
+                                workspace.path = Some(node.path_buf.clone());
+                                ctx.submit_command(Command::new(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::SetWorkspace(workspace),
+                                    Target::Auto,
+                                ));
+                            } else {
+                                ctx.submit_command(Command::new(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::OpenFile(node.path_buf.clone()),
+                                    Target::Widget(data.id),
+                                ));
+                                let picker = Arc::make_mut(&mut data.picker);
+                                picker.active = false;
                             }
                         }
                     }
+                    LapceUICommand::SaveAs(path, _) => {
+                        let selected_file = data
+                            .picker
+                            .selected_node()
+                            .filter(|node| !node.is_dir)
+                            .map(|node| node.path_buf.clone());
+                        let target = match selected_file {
+                            Some(target) => target,
+                            None => match path.file_name() {
+                                Some(file_name) => data.picker.pwd.join(file_name),
+                                None => return,
+                            },
+                        };
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::SaveAs(path.clone(), target),
+                            Target::Widget(data.id),
+                        ));
+                        let picker = Arc::make_mut(&mut data.picker);
+                        picker.active = false;
+                        picker.save_as = None;
+                    }
                     _ => {
                         ctx.submit_command(btn.command.clone());
                     }
@@ -970,9 +1134,19 @@ impl Widget<LapceTabData> for FilePickerControl {
 
         self.buttons.clear();
         let mut x = self_size.width - gap;
+        let (label, command) = match data.picker.save_as.as_ref() {
+            Some(path) => (
+                "Save",
+                LapceUICommand::SaveAs(path.clone(), data.picker.pwd.clone()),
+            ),
+            None => (
+                "Open",
+                LapceUICommand::SetWorkspace((*data.workspace).clone()),
+            ),
+        };
         let text_layout = ctx
             .text()
-            .new_text_layout("Open")
+            .new_text_layout(label)
             .font(FontFamily::SYSTEM_UI, 13.0)
             .text_color(
                 data.config
@@ -987,11 +1161,7 @@ impl Widget<LapceTabData> for FilePickerControl {
             rect: Size::new(text_size.width + gap * 2.0, button_height)
                 .to_rect()
                 .with_origin(Point::new(x - btn_width, gap)),
-            command: Command::new(
-                LAPCE_UI_COMMAND,
-                LapceUICommand::SetWorkspace((*data.workspace).clone()),
-                Target::Auto,
-            ),
+            command: Command::new(LAPCE_UI_COMMAND, command, Target::Auto),
             text_layout,
         };
         self.buttons.push(btn);
@@ -1,18 +1,30 @@
 use druid::{
-    piet::{Text, TextAttribute, TextLayout as PietTextLayout, TextLayoutBuilder},
-    BoxConstraints, Color, Cursor, Env, Event, EventCtx, FontFamily, FontWeight,
-    LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, RenderContext,
-    Size, UpdateCtx, Widget, WidgetExt, WidgetId,
+    piet::{
+        PietText, Text, TextAttribute, TextLayout as PietTextLayout,
+        TextLayoutBuilder,
+    },
+    BoxConstraints, Color, Command, Cursor, Data, Env, Event, EventCtx, FontFamily,
+    FontWeight, LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point,
+    Rect, RenderContext, Size, Target, TimerToken, UpdateCtx, Widget, WidgetExt,
+    WidgetId,
 };
 use lapce_data::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::LapceTheme,
     data::{LapceTabData, PanelKind},
+    plugin::version_is_newer,
     split::SplitDirection,
 };
 use lapce_rpc::plugin::PluginDescription;
+use std::{sync::Arc, time::Duration};
 use strum_macros::Display;
 
-use crate::panel::{LapcePanel, PanelHeaderKind};
+use crate::{
+    editor::view::LapceEditorView,
+    panel::{LapcePanel, PanelHeaderKind},
+    scroll::LapceScrollNew,
+    split::LapceSplitNew,
+};
 
 pub struct PluginData {
     pub widget_id: WidgetId,
@@ -36,30 +48,69 @@ impl Default for PluginData {
 pub enum PluginStatus {
     Installed,
     Install,
-    Upgrade,
+    Update,
 }
 
 pub struct Plugin {
     line_height: f64,
+    search_timer: TimerToken,
 }
 
 impl Plugin {
+    /// How long the filter has to stay unchanged before the registry is
+    /// searched for it
+    const SEARCH_DELAY: Duration = Duration::from_millis(300);
+
     pub fn new() -> Self {
-        Self { line_height: 25.0 }
+        Self {
+            line_height: 25.0,
+            search_timer: TimerToken::INVALID,
+        }
     }
 
     pub fn new_panel(data: &LapceTabData) -> LapcePanel {
-        let split_id = WidgetId::next();
+        let filter_input = LapceEditorView::new(data.plugin.filter_view_id, None)
+            .hide_header()
+            .hide_gutter()
+            .set_placeholder("Filter plugins".to_string())
+            .padding((15.0, 15.0, 15.0, 5.0));
+        let split = LapceSplitNew::new(data.plugin.split_id)
+            .horizontal()
+            .with_child(filter_input.boxed(), None, 50.0)
+            .with_child(PluginModeBar::new().boxed(), None, 30.0)
+            .with_flex_child(
+                LapceScrollNew::new(Self::new().boxed()).vertical().boxed(),
+                None,
+                1.0,
+            );
         LapcePanel::new(
             PanelKind::Plugin,
             data.plugin.widget_id,
-            split_id,
+            data.plugin.split_id,
             SplitDirection::Vertical,
             PanelHeaderKind::Simple("Plugin".to_string()),
-            vec![(split_id, PanelHeaderKind::None, Self::new().boxed(), None)],
+            vec![(
+                data.plugin.split_id,
+                PanelHeaderKind::None,
+                split.boxed(),
+                None,
+            )],
         )
     }
 
+    fn plugin_at<'a>(
+        &self,
+        data: &'a LapceTabData,
+        y: f64,
+    ) -> Option<&'a PluginDescription> {
+        let index = (y / (self.line_height * 3.0)) as usize;
+        data.plugin
+            .visible_plugins(&data.installed_plugins, &data.plugins)
+            .into_iter()
+            .nth(index)
+            .map(|(plugin, _)| plugin)
+    }
+
     fn hit_test<'a>(
         &self,
         ctx: &mut EventCtx,
@@ -67,16 +118,8 @@ impl Plugin {
         mouse_event: &MouseEvent,
     ) -> Option<(&'a PluginDescription, PluginStatus)> {
         let index = (mouse_event.pos.y / (self.line_height * 3.0)) as usize;
-        let plugin = data.plugins.get(index)?;
-        let status = match data
-            .installed_plugins
-            .get(&plugin.name)
-            .map(|installed| plugin.version.clone() == installed.version.clone())
-        {
-            Some(true) => PluginStatus::Installed,
-            Some(false) => PluginStatus::Upgrade,
-            None => PluginStatus::Install,
-        };
+        let plugin = self.plugin_at(data, mouse_event.pos.y)?;
+        let status = plugin_status(data, plugin);
 
         if status == PluginStatus::Installed {
             return None;
@@ -104,6 +147,74 @@ impl Plugin {
             None
         }
     }
+
+    /// Finds the installed plugin whose enable/disable button is under the
+    /// mouse. The button sits to the left of the status button.
+    fn toggle_hit_test<'a>(
+        &self,
+        ctx: &mut EventCtx,
+        data: &'a LapceTabData,
+        mouse_event: &MouseEvent,
+    ) -> Option<&'a PluginDescription> {
+        let index = (mouse_event.pos.y / (self.line_height * 3.0)) as usize;
+        let plugin = self.plugin_at(data, mouse_event.pos.y)?;
+        let status = plugin_status(data, plugin);
+        if status == PluginStatus::Install {
+            return None;
+        }
+
+        let padding = 10.0;
+        let status_width = button_width(ctx.text(), &status.to_string());
+        let label = toggle_label(data, plugin);
+        let toggle_width = button_width(ctx.text(), label);
+        let x = ctx.size().width - padding - status_width - padding - toggle_width;
+        let y = 3.0 * self.line_height * index as f64 + self.line_height * 2.0;
+        let rect = Rect::new(x, y, x + toggle_width, y + self.line_height);
+        if rect.contains(mouse_event.pos) {
+            Some(plugin)
+        } else {
+            None
+        }
+    }
+}
+
+/// Installed plugins are outdated when a newer version was found in their
+/// repository, or when the plugin list has a newer version.
+fn plugin_status(data: &LapceTabData, plugin: &PluginDescription) -> PluginStatus {
+    match data.installed_plugins.get(&plugin.name) {
+        Some(installed) => {
+            if data.plugin.updates.contains_key(&plugin.name)
+                || version_is_newer(&plugin.version, &installed.version)
+            {
+                PluginStatus::Update
+            } else {
+                PluginStatus::Installed
+            }
+        }
+        None => PluginStatus::Install,
+    }
+}
+
+fn is_disabled(data: &LapceTabData, plugin: &PluginDescription) -> bool {
+    data.config.lapce.disabled_plugins.contains(&plugin.name)
+}
+
+fn toggle_label(data: &LapceTabData, plugin: &PluginDescription) -> &'static str {
+    if is_disabled(data, plugin) {
+        "Enable"
+    } else {
+        "Disable"
+    }
+}
+
+fn button_width(text: &mut PietText, label: &str) -> f64 {
+    let text_padding = 5.0;
+    let text_layout = text
+        .new_text_layout(label.to_string())
+        .font(FontFamily::SYSTEM_UI, 13.0)
+        .build()
+        .unwrap();
+    text_layout.size().width + text_padding * 2.0
 }
 
 impl Default for Plugin {
@@ -122,17 +233,36 @@ impl Widget<LapceTabData> for Plugin {
     ) {
         match event {
             Event::MouseMove(mouse_event) => {
-                if self.hit_test(ctx, data, mouse_event).is_some() {
+                if self.hit_test(ctx, data, mouse_event).is_some()
+                    || self.toggle_hit_test(ctx, data, mouse_event).is_some()
+                {
                     ctx.set_cursor(&Cursor::Pointer);
                 } else {
                     ctx.clear_cursor();
                 }
             }
             Event::MouseDown(mouse_event) => {
-                if let Some((plugin, _)) = self.hit_test(ctx, data, mouse_event) {
-                    data.proxy.install_plugin(plugin);
+                if let Some((plugin, status)) = self.hit_test(ctx, data, mouse_event)
+                {
+                    if status == PluginStatus::Update {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdatePlugin(plugin.name.clone()),
+                            Target::Widget(data.id),
+                        ));
+                    } else {
+                        data.proxy.install_plugin(plugin);
+                    }
+                } else if let Some(plugin) =
+                    self.toggle_hit_test(ctx, data, mouse_event)
+                {
+                    let name = plugin.name.clone();
+                    data.toggle_plugin(&name);
                 }
             }
+            Event::Timer(token) if *token == self.search_timer => {
+                data.search_plugin_marketplace(ctx);
+            }
             _ => (),
         }
     }
@@ -148,21 +278,39 @@ impl Widget<LapceTabData> for Plugin {
 
     fn update(
         &mut self,
-        _ctx: &mut UpdateCtx,
-        _old_data: &LapceTabData,
-        _data: &LapceTabData,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
         _env: &Env,
     ) {
+        if data.plugin.marketplace
+            && (old_data.plugin.filter != data.plugin.filter
+                || !old_data.plugin.marketplace)
+        {
+            self.search_timer = ctx.request_timer(Self::SEARCH_DELAY);
+        }
+        if !old_data.plugins.same(&data.plugins)
+            || !old_data.installed_plugins.same(&data.installed_plugins)
+            || !old_data.plugin.same(&data.plugin)
+            || !old_data.config.same(&data.config)
+        {
+            ctx.request_layout();
+            ctx.request_paint();
+        }
     }
 
     fn layout(
         &mut self,
         _ctx: &mut LayoutCtx,
         bc: &BoxConstraints,
-        _data: &LapceTabData,
+        data: &LapceTabData,
         _env: &Env,
     ) -> Size {
-        bc.max()
+        let n = data
+            .plugin
+            .visible_plugins(&data.installed_plugins, &data.plugins)
+            .len();
+        Size::new(bc.max().width, self.line_height * 3.0 * n as f64)
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
@@ -173,21 +321,37 @@ impl Widget<LapceTabData> for Plugin {
             let viewport = ctx.size().to_rect().inflate(-padding, 0.0);
             ctx.clip(viewport);
 
-            for (i, plugin) in data.plugins.iter().enumerate() {
+            let plugins = data
+                .plugin
+                .visible_plugins(&data.installed_plugins, &data.plugins);
+            for (i, (plugin, indices)) in plugins.into_iter().enumerate() {
                 let y = 3.0 * self.line_height * i as f64;
                 let x = 3.0 * self.line_height;
-                let text_layout = ctx
+                let disabled = data.installed_plugins.contains_key(&plugin.name)
+                    && is_disabled(data, plugin);
+                let (name_color, text_color) = if disabled {
+                    (LapceTheme::EDITOR_DIM, LapceTheme::EDITOR_DIM)
+                } else {
+                    (LapceTheme::EDITOR_FOCUS, LapceTheme::EDITOR_FOREGROUND)
+                };
+                let mut text_layout = ctx
                     .text()
                     .new_text_layout(plugin.display_name.clone())
                     .font(FontFamily::SYSTEM_UI, 14.0)
                     .default_attribute(TextAttribute::Weight(FontWeight::BOLD))
-                    .text_color(
-                        data.config
-                            .get_color_unchecked(LapceTheme::EDITOR_FOCUS)
-                            .clone(),
-                    )
-                    .build()
-                    .unwrap();
+                    .text_color(data.config.get_color_unchecked(name_color).clone());
+                // The matched indices count chars, the text layout's ranges
+                // are in bytes
+                for (i, (start, c)) in plugin.display_name.char_indices().enumerate()
+                {
+                    if indices.contains(&i) {
+                        text_layout = text_layout.range_attribute(
+                            start..start + c.len_utf8(),
+                            TextAttribute::Underline(true),
+                        );
+                    }
+                }
+                let text_layout = text_layout.build().unwrap();
                 ctx.draw_text(
                     &text_layout,
                     Point::new(
@@ -200,11 +364,7 @@ impl Widget<LapceTabData> for Plugin {
                     .text()
                     .new_text_layout(plugin.description.clone())
                     .font(FontFamily::SYSTEM_UI, 13.0)
-                    .text_color(
-                        data.config
-                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                            .clone(),
-                    )
+                    .text_color(data.config.get_color_unchecked(text_color).clone())
                     .build()
                     .unwrap();
                 ctx.draw_text(
@@ -220,11 +380,7 @@ impl Widget<LapceTabData> for Plugin {
                     .text()
                     .new_text_layout(plugin.author.clone())
                     .font(FontFamily::SYSTEM_UI, 13.0)
-                    .text_color(
-                        data.config
-                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                            .clone(),
-                    )
+                    .text_color(data.config.get_color_unchecked(text_color).clone())
                     .build()
                     .unwrap();
                 ctx.draw_text(
@@ -236,15 +392,7 @@ impl Widget<LapceTabData> for Plugin {
                     ),
                 );
 
-                let status = match data
-                    .installed_plugins
-                    .get(&plugin.name)
-                    .map(|installed| installed.version == plugin.version)
-                {
-                    Some(true) => PluginStatus::Installed,
-                    Some(false) => PluginStatus::Upgrade,
-                    None => PluginStatus::Install,
-                };
+                let status = plugin_status(data, plugin);
 
                 let text_layout = ctx
                     .text()
@@ -284,7 +432,177 @@ impl Widget<LapceTabData> for Plugin {
                         y + (self.line_height - text_layout.size().height) / 2.0,
                     ),
                 );
+
+                if status != PluginStatus::Install {
+                    let text_layout = ctx
+                        .text()
+                        .new_text_layout(toggle_label(data, plugin))
+                        .font(FontFamily::SYSTEM_UI, 13.0)
+                        .text_color(
+                            data.config
+                                .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                                .clone(),
+                        )
+                        .build()
+                        .unwrap();
+                    let width = text_layout.size().width + text_padding * 2.0;
+                    let x = x - padding - width;
+                    ctx.stroke(
+                        Size::new(width, self.line_height)
+                            .to_rect()
+                            .with_origin(Point::new(x, y))
+                            .inset(-0.5),
+                        data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                        1.0,
+                    );
+                    ctx.draw_text(
+                        &text_layout,
+                        Point::new(
+                            x + text_padding,
+                            y + (self.line_height - text_layout.size().height) / 2.0,
+                        ),
+                    );
+                }
             }
         });
     }
 }
+
+/// Switches the plugin list between the installed plugins and the registry.
+pub struct PluginModeBar {
+    mouse_pos: Point,
+    modes: Vec<(Rect, bool)>,
+}
+
+impl PluginModeBar {
+    pub fn new() -> Self {
+        Self {
+            mouse_pos: Point::ZERO,
+            modes: Vec::new(),
+        }
+    }
+
+    fn mode_at(&self, pos: Point) -> Option<bool> {
+        self.modes
+            .iter()
+            .find(|(rect, _)| rect.contains(pos))
+            .map(|(_, marketplace)| *marketplace)
+    }
+}
+
+impl Default for PluginModeBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget<LapceTabData> for PluginModeBar {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                if self.mode_at(mouse_event.pos).is_some() {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                if let Some(marketplace) = self.mode_at(mouse_event.pos) {
+                    if marketplace != data.plugin.marketplace {
+                        let plugin = Arc::make_mut(&mut data.plugin);
+                        plugin.marketplace = marketplace;
+                        plugin.marketplace_results.clear();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if let LifeCycle::HotChanged(_) = event {
+            ctx.request_paint();
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if old_data.plugin.marketplace != data.plugin.marketplace {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let size = ctx.size();
+        let mut x = 15.0;
+        self.modes.clear();
+        for (label, marketplace) in [("Installed", false), ("Marketplace", true)] {
+            let on = marketplace == data.plugin.marketplace;
+            let text_layout = ctx
+                .text()
+                .new_text_layout(label)
+                .font(FontFamily::SYSTEM_UI, 12.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(if on {
+                            LapceTheme::EDITOR_FOREGROUND
+                        } else {
+                            LapceTheme::EDITOR_DIM
+                        })
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let text_size = text_layout.size();
+            let rect = Size::new(text_size.width + 10.0, text_size.height + 4.0)
+                .to_rect()
+                .with_origin(Point::new(
+                    x,
+                    (size.height - text_size.height - 4.0) / 2.0,
+                ));
+            if on {
+                ctx.fill(
+                    rect,
+                    data.config.get_color_unchecked(LapceTheme::PANEL_CURRENT),
+                );
+            } else if ctx.is_hot() && rect.contains(self.mouse_pos) {
+                ctx.fill(
+                    rect,
+                    data.config.get_color_unchecked(LapceTheme::PANEL_HOVERED),
+                );
+            }
+            ctx.draw_text(&text_layout, Point::new(rect.x0 + 5.0, rect.y0 + 2.0));
+            x = rect.x1 + 5.0;
+            self.modes.push((rect, marketplace));
+        }
+    }
+}
@@ -1,22 +1,27 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use druid::{
     piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
     BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, FontFamily,
-    LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, RenderContext,
-    Size, Target, UpdateCtx, Widget, WidgetExt,
+    LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect,
+    RenderContext, Size, Target, UpdateCtx, Widget, WidgetExt,
 };
 use itertools::Itertools;
 use lapce_data::{
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
     config::LapceTheme,
     data::{EditorDiagnostic, LapceTabData, PanelKind},
     editor::EditorLocationNew,
-    problem::ProblemData,
-    proxy::path_from_url,
+    menu::MenuItem,
+    problem::{diagnostic_copy_text, diagnostic_source_label, ProblemData},
     split::SplitDirection,
 };
 use lsp_types::DiagnosticSeverity;
+use serde_json::json;
+use strum::EnumMessage;
 
 use crate::{
     panel::{LapcePanel, PanelHeaderKind},
@@ -52,6 +57,9 @@ pub struct ProblemContent {
     mouse_pos: Point,
     line_height: f64,
     content_height: f64,
+    /// Where the source of each painted diagnostic is, to filter by it when
+    /// clicked
+    source_rects: Vec<(Rect, String)>,
 }
 
 impl ProblemContent {
@@ -61,6 +69,7 @@ impl ProblemContent {
             mouse_pos: Point::ZERO,
             line_height: 25.0,
             content_height: 0.0,
+            source_rects: Vec::new(),
         }
     }
 
@@ -75,7 +84,10 @@ impl ProblemContent {
             .filter_map(|(path, diagnostic)| {
                 let diagnostics: Vec<&EditorDiagnostic> = diagnostic
                     .iter()
-                    .filter(|d| d.diagnositc.severity == Some(self.severity))
+                    .filter(|d| {
+                        d.diagnositc.severity == Some(self.severity)
+                            && data.problem.is_listed(&d.diagnositc)
+                    })
                     .collect();
                 if !diagnostics.is_empty() {
                     Some((path, diagnostics))
@@ -124,14 +136,9 @@ impl ProblemContent {
                             None,
                             EditorLocationNew {
                                 path: path.clone(),
-                                position: Some(
-                                    d.range
-                                        .map(|(line, col)| lsp_types::Position {
-                                            line: line as u32,
-                                            character: col as u32,
-                                        })
-                                        .unwrap_or_else(|| d.diagnositc.range.start),
-                                ),
+                                // The range holds buffer offsets, while the
+                                // position is kept up to date with edits.
+                                position: Some(d.diagnositc.range.start),
                                 scroll_offset: None,
                                 history: None,
                             },
@@ -140,29 +147,12 @@ impl ProblemContent {
                     ));
                     return;
                 }
-                for related in d
-                    .diagnositc
-                    .related_information
-                    .as_ref()
-                    .unwrap_or(&Vec::new())
-                {
+                for (_, location) in d.related() {
                     i += 1;
                     if i == n {
                         ctx.submit_command(Command::new(
                             LAPCE_UI_COMMAND,
-                            LapceUICommand::JumpToLocation(
-                                None,
-                                EditorLocationNew {
-                                    path: related
-                                        .location
-                                        .uri
-                                        .to_file_path()
-                                        .unwrap(),
-                                    position: Some(related.location.range.start),
-                                    scroll_offset: None,
-                                    history: None,
-                                },
-                            ),
+                            LapceUICommand::JumpToLocation(None, location),
                             Target::Widget(data.id),
                         ));
                         return;
@@ -172,6 +162,64 @@ impl ProblemContent {
             i += 1;
         }
     }
+
+    /// The diagnostic listed at row `n`, counting the rows of its related
+    /// locations as its own.
+    fn diagnostic_at_row<'a>(
+        &self,
+        data: &'a LapceTabData,
+        n: usize,
+    ) -> Option<(&'a PathBuf, &'a EditorDiagnostic)> {
+        let mut i = 0;
+        for (path, diagnositcs) in self.items(data) {
+            for d in diagnositcs {
+                let rows = d
+                    .diagnositc
+                    .related_information
+                    .as_ref()
+                    .map(|r| r.len())
+                    .unwrap_or(0)
+                    + 1;
+                if n > i && n <= i + rows {
+                    return Some((path, d));
+                }
+                i += rows;
+            }
+            i += 1;
+        }
+        None
+    }
+
+    fn right_click(
+        &self,
+        ctx: &mut EventCtx,
+        mouse_event: &MouseEvent,
+        data: &LapceTabData,
+    ) {
+        let n = (mouse_event.pos.y / self.line_height).floor() as usize;
+        let (path, d) = match self.diagnostic_at_row(data, n) {
+            Some(item) => item,
+            None => return,
+        };
+
+        let cmd = LapceWorkbenchCommand::CopyDiagnostic;
+        let menu_items = vec![MenuItem {
+            text: cmd.get_message().unwrap().to_string(),
+            indices: Vec::new(),
+            command: LapceCommandNew {
+                cmd: cmd.to_string(),
+                palette_desc: None,
+                data: Some(json!(diagnostic_copy_text(path, &d.diagnositc))),
+                target: CommandTarget::Workbench,
+            },
+        }];
+        let point = mouse_event.pos + ctx.window_origin().to_vec2();
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowMenu(point.round(), Arc::new(menu_items)),
+            Target::Auto,
+        ));
+    }
 }
 
 impl Widget<LapceTabData> for ProblemContent {
@@ -195,6 +243,18 @@ impl Widget<LapceTabData> for ProblemContent {
                 ctx.request_paint();
             }
             Event::MouseDown(mouse_event) => {
+                if mouse_event.button.is_right() {
+                    self.right_click(ctx, mouse_event, data);
+                    return;
+                }
+                if let Some((_, source)) = self
+                    .source_rects
+                    .iter()
+                    .find(|(rect, _)| rect.contains(mouse_event.pos))
+                {
+                    Arc::make_mut(&mut data.problem).toggle_source_filter(source);
+                    return;
+                }
                 self.mouse_down(ctx, mouse_event, data);
             }
             _ => {}
@@ -221,6 +281,7 @@ impl Widget<LapceTabData> for ProblemContent {
             .main_split
             .diagnostics
             .same(&old_data.main_split.diagnostics)
+            || !data.problem.same(&old_data.problem)
         {
             ctx.request_layout();
         }
@@ -274,6 +335,7 @@ impl Widget<LapceTabData> for ProblemContent {
             }
         }
 
+        self.source_rects.clear();
         let rect = ctx.region().bounding_box();
         let min = (rect.y0 / line_height).floor() as usize;
         let max = (rect.y1 / line_height) as usize + 2;
@@ -339,29 +401,33 @@ impl Widget<LapceTabData> for ProblemContent {
                 .and_then(|s| s.to_str())
                 .unwrap_or("")
                 .to_string();
-            if !folder.is_empty() {
-                let x = text_layout.size().width + line_height + 5.0;
-
-                let text_layout = ctx
-                    .text()
-                    .new_text_layout(folder)
-                    .font(FontFamily::SYSTEM_UI, 13.0)
-                    .text_color(
-                        data.config
-                            .get_color_unchecked(LapceTheme::EDITOR_DIM)
-                            .clone(),
-                    )
-                    .build()
-                    .unwrap();
-                ctx.draw_text(
-                    &text_layout,
-                    Point::new(
-                        x,
-                        line_height * i as f64
-                            + (line_height - text_layout.size().height) / 2.0,
-                    ),
-                );
-            }
+            // The folder and how many diagnostics the file has
+            let count = diagnositcs.len().to_string();
+            let detail = if folder.is_empty() {
+                count
+            } else {
+                format!("{}  {}", folder, count)
+            };
+            let x = text_layout.size().width + line_height + 5.0;
+            let text_layout = ctx
+                .text()
+                .new_text_layout(detail)
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    x,
+                    line_height * i as f64
+                        + (line_height - text_layout.size().height) / 2.0,
+                ),
+            );
 
             for d in diagnositcs {
                 i += 1;
@@ -406,14 +472,34 @@ impl Widget<LapceTabData> for ProblemContent {
                                 + (line_height - text_layout.size().height) / 2.0,
                         ),
                     );
+
+                    if let Some(label) = diagnostic_source_label(&d.diagnositc) {
+                        let x = 2.0 * line_height + text_layout.size().width + 10.0;
+                        let text_layout = ctx
+                            .text()
+                            .new_text_layout(label)
+                            .font(FontFamily::SYSTEM_UI, 13.0)
+                            .text_color(
+                                data.config
+                                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                                    .clone(),
+                            )
+                            .build()
+                            .unwrap();
+                        let text_size = text_layout.size();
+                        let y = line_height * i as f64
+                            + (line_height - text_size.height) / 2.0;
+                        ctx.draw_text(&text_layout, Point::new(x, y));
+                        if let Some(source) = d.diagnositc.source.as_ref() {
+                            self.source_rects.push((
+                                text_size.to_rect().with_origin(Point::new(x, y)),
+                                source.clone(),
+                            ));
+                        }
+                    }
                 }
 
-                for related in d
-                    .diagnositc
-                    .related_information
-                    .as_ref()
-                    .unwrap_or(&Vec::new())
-                {
+                for (text, _) in d.related() {
                     i += 1;
 
                     if i >= min {
@@ -435,16 +521,6 @@ impl Widget<LapceTabData> for ProblemContent {
                             ),
                         );
 
-                        let text = format!(
-                            "{}[{}, {}]: {}",
-                            path_from_url(&related.location.uri)
-                                .file_name()
-                                .and_then(|f| f.to_str())
-                                .unwrap_or(""),
-                            related.location.range.start.line,
-                            related.location.range.start.character,
-                            related.message
-                        );
                         let text_layout = ctx
                             .text()
                             .new_text_layout(text)
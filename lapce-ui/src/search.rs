@@ -2,14 +2,17 @@ use druid::{
     piet::{Text, TextAttribute, TextLayout as PietTextLayout, TextLayoutBuilder},
     BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, FontFamily,
     FontWeight, LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point,
-    RenderContext, Size, Target, UpdateCtx, Widget, WidgetExt, WidgetId,
+    Rect, RenderContext, Size, Target, UpdateCtx, Widget, WidgetExt, WidgetId,
 };
 use lapce_data::{
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND,
+    },
     config::LapceTheme,
     data::{LapceTabData, PanelKind},
     editor::EditorLocationNew,
-    search::Match,
+    search::{Match, SearchRow},
     split::SplitDirection,
 };
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
@@ -40,10 +43,23 @@ pub fn new_search_panel(data: &LapceTabData) -> LapcePanel {
     let input = LapceEditorView::new(editor_data.view_id, None)
         .hide_header()
         .hide_gutter()
-        .padding((15.0, 15.0));
+        .padding((15.0, 15.0, 15.0, 5.0));
+    let replace_input = LapceEditorView::new(data.search.replace_view_id, None)
+        .hide_header()
+        .hide_gutter()
+        .set_placeholder("Replace".to_string())
+        .padding((15.0, 5.0, 15.0, 5.0));
+    let globs_input = LapceEditorView::new(data.search.globs_view_id, None)
+        .hide_header()
+        .hide_gutter()
+        .set_placeholder("Files, e.g. src/**,!**/tests/**".to_string())
+        .padding((15.0, 5.0, 15.0, 15.0));
     let split = LapceSplitNew::new(data.search.split_id)
         .horizontal()
-        .with_child(input.boxed(), None, 100.0)
+        .with_child(input.boxed(), None, 50.0)
+        .with_child(replace_input.boxed(), None, 40.0)
+        .with_child(globs_input.boxed(), None, 50.0)
+        .with_child(SearchOptionsBar::new().boxed(), None, 30.0)
         .with_flex_child(
             LapceScrollNew::new(SearchContent::new().boxed())
                 .vertical()
@@ -115,6 +131,162 @@ impl Default for SearchData {
     }
 }
 
+/// The toggles for how the search matches, each one a label that's
+/// highlighted while it's on
+pub struct SearchOptionsBar {
+    mouse_pos: Point,
+    toggles: Vec<(Rect, LapceWorkbenchCommand)>,
+}
+
+impl SearchOptionsBar {
+    pub fn new() -> Self {
+        Self {
+            mouse_pos: Point::ZERO,
+            toggles: Vec::new(),
+        }
+    }
+
+    fn toggle_at(&self, pos: Point) -> Option<&LapceWorkbenchCommand> {
+        self.toggles
+            .iter()
+            .find(|(rect, _)| rect.contains(pos))
+            .map(|(_, cmd)| cmd)
+    }
+}
+
+impl Default for SearchOptionsBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget<LapceTabData> for SearchOptionsBar {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                if self.toggle_at(mouse_event.pos).is_some() {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                if let Some(cmd) = self.toggle_at(mouse_event.pos) {
+                    ctx.submit_command(Command::new(
+                        LAPCE_NEW_COMMAND,
+                        LapceCommandNew {
+                            cmd: cmd.to_string(),
+                            data: None,
+                            palette_desc: None,
+                            target: CommandTarget::Workbench,
+                        },
+                        Target::Widget(data.id),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if let LifeCycle::HotChanged(_) = event {
+            ctx.request_paint();
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if old_data.search.options != data.search.options {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let size = ctx.size();
+        let options = data.search.options;
+        let mut x = 15.0;
+        self.toggles.clear();
+        for (label, on, cmd) in [
+            (
+                "Regex",
+                options.regex,
+                LapceWorkbenchCommand::ToggleSearchRegex,
+            ),
+            (
+                "Whole Word",
+                options.whole_word,
+                LapceWorkbenchCommand::ToggleSearchWholeWord,
+            ),
+        ] {
+            let text_layout = ctx
+                .text()
+                .new_text_layout(label)
+                .font(FontFamily::SYSTEM_UI, 12.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(if on {
+                            LapceTheme::EDITOR_FOREGROUND
+                        } else {
+                            LapceTheme::EDITOR_DIM
+                        })
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let text_size = text_layout.size();
+            let rect = Size::new(text_size.width + 10.0, text_size.height + 4.0)
+                .to_rect()
+                .with_origin(Point::new(
+                    x,
+                    (size.height - text_size.height - 4.0) / 2.0,
+                ));
+            if on {
+                ctx.fill(
+                    rect,
+                    data.config.get_color_unchecked(LapceTheme::PANEL_CURRENT),
+                );
+            } else if ctx.is_hot() && rect.contains(self.mouse_pos) {
+                ctx.fill(
+                    rect,
+                    data.config.get_color_unchecked(LapceTheme::PANEL_HOVERED),
+                );
+            }
+            ctx.draw_text(&text_layout, Point::new(rect.x0 + 5.0, rect.y0 + 2.0));
+            x = rect.x1 + 5.0;
+            self.toggles.push((rect, cmd));
+        }
+    }
+}
+
 pub struct SearchContent {
     mouse_pos: Point,
     line_height: f64,
@@ -137,39 +309,79 @@ impl SearchContent {
         let n = (mouse_event.pos.y / self.line_height).floor() as usize;
 
         let mut i = 0;
-        for (path, matches) in data.search.matches.iter() {
-            if matches.len() + 1 + i < n {
-                i += matches.len() + 1;
+        for path in data.search.matches.keys() {
+            let rows = data.search.rows(path);
+            if rows.len() + 1 + i < n {
+                i += rows.len() + 1;
                 continue;
             }
 
-            for (line_number, (start, _end), _line) in matches {
+            for row in rows {
                 i += 1;
-                if i == n {
-                    ctx.submit_command(Command::new(
-                        LAPCE_UI_COMMAND,
-                        LapceUICommand::JumpToLocation(
-                            None,
-                            EditorLocationNew {
-                                path: path.clone(),
-                                position: Some(lsp_types::Position {
-                                    line: *line_number as u32 - 1,
-                                    character: *start as u32,
-                                }),
-                                scroll_offset: None,
-                                history: None,
-                            },
-                        ),
-                        Target::Widget(data.id),
-                    ));
-                    return;
+                if i != n {
+                    continue;
                 }
+                let (line_number, character) = match row {
+                    SearchRow::Match((line_number, (start, _end), _line)) => {
+                        (*line_number, *start)
+                    }
+                    SearchRow::Context((line_number, _line)) => (*line_number, 0),
+                    SearchRow::Gap => return,
+                };
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::JumpToLocation(
+                        None,
+                        EditorLocationNew {
+                            path: path.clone(),
+                            position: Some(lsp_types::Position {
+                                line: line_number as u32 - 1,
+                                character: character as u32,
+                            }),
+                            scroll_offset: None,
+                            history: None,
+                        },
+                    ),
+                    Target::Widget(data.id),
+                ));
+                return;
             }
             i += 1;
         }
     }
 }
 
+impl SearchContent {
+    /// Paints a row that isn't a match, like a context line, dimmed.
+    fn paint_dim_row(
+        &self,
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        i: usize,
+        text: String,
+    ) {
+        let text_layout = ctx
+            .text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, 13.0)
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                self.line_height,
+                self.line_height * i as f64
+                    + (self.line_height - text_layout.size().height) / 2.0,
+            ),
+        );
+    }
+}
+
 impl Default for SearchContent {
     fn default() -> Self {
         Self::new()
@@ -213,9 +425,14 @@ impl Widget<LapceTabData> for SearchContent {
         data: &LapceTabData,
         _env: &Env,
     ) {
-        if !old_data.search.matches.same(&data.search.matches) {
+        if !old_data.search.matches.same(&data.search.matches)
+            || !old_data.search.context.same(&data.search.context)
+        {
             ctx.request_layout();
         }
+        if old_data.search.replace != data.search.replace {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -228,8 +445,8 @@ impl Widget<LapceTabData> for SearchContent {
         let n = data
             .search
             .matches
-            .iter()
-            .map(|(_, matches)| matches.len() + 1)
+            .keys()
+            .map(|path| data.search.rows(path).len() + 1)
             .sum::<usize>();
         let height = self.line_height * n as f64;
         Size::new(bc.max().width, height)
@@ -255,9 +472,10 @@ impl Widget<LapceTabData> for SearchContent {
         let focus_color = data.config.get_color_unchecked(LapceTheme::EDITOR_FOCUS);
         let padding = (self.line_height - 14.0) / 2.0;
         let mut i = 0;
-        for (path, matches) in data.search.matches.iter() {
-            if matches.len() + 1 + i < min {
-                i += matches.len() + 1;
+        for path in data.search.matches.keys() {
+            let rows = data.search.rows(path);
+            if rows.len() + 1 + i < min {
+                i += rows.len() + 1;
                 continue;
             }
 
@@ -326,42 +544,86 @@ impl Widget<LapceTabData> for SearchContent {
                 );
             }
 
-            for (line_number, (start, end), line) in matches {
+            for row in rows {
                 i += 1;
                 if i > max {
                     return;
                 }
+                if i < min {
+                    continue;
+                }
+
+                let (line_number, (start, end), line) = match row {
+                    SearchRow::Match(m) => m,
+                    SearchRow::Context((line_number, line)) => {
+                        let text = format!("{line_number}: {line}");
+                        self.paint_dim_row(ctx, data, i, text);
+                        continue;
+                    }
+                    SearchRow::Gap => {
+                        self.paint_dim_row(ctx, data, i, "\u{22ef}".to_string());
+                        continue;
+                    }
+                };
 
-                if i >= min {
-                    let mut text_layout = ctx
-                        .text()
-                        .new_text_layout(format!("{line_number}: {line}"))
-                        .font(FontFamily::SYSTEM_UI, 13.0)
-                        .text_color(
-                            data.config
-                                .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                                .clone(),
-                        );
-                    let prefix = line_number.to_string().len() + 2;
+                // With a replacement, the line previews the match being
+                // swapped for it.
+                let replace = &data.search.replace;
+                let text = if replace.is_empty() {
+                    format!("{line_number}: {line}")
+                } else {
+                    format!(
+                        "{line_number}: {}{}{}",
+                        &line[..*end],
+                        replace,
+                        &line[*end..]
+                    )
+                };
+                let mut text_layout = ctx
+                    .text()
+                    .new_text_layout(text)
+                    .font(FontFamily::SYSTEM_UI, 13.0)
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                            .clone(),
+                    );
+                let prefix = line_number.to_string().len() + 2;
+                let mut highlights = Vec::new();
+                if replace.is_empty() {
+                    highlights.push((*start..*end, focus_color));
+                } else {
+                    highlights.push((
+                        *start..*end,
+                        data.config
+                            .get_color_unchecked(LapceTheme::SOURCE_CONTROL_REMOVED),
+                    ));
+                    highlights.push((
+                        *end..*end + replace.len(),
+                        data.config
+                            .get_color_unchecked(LapceTheme::SOURCE_CONTROL_ADDED),
+                    ));
+                }
+                for (range, color) in highlights {
+                    let range = range.start + prefix..range.end + prefix;
                     text_layout = text_layout.range_attribute(
-                        *start + prefix..*end + prefix,
-                        TextAttribute::TextColor(focus_color.clone()),
+                        range.clone(),
+                        TextAttribute::TextColor(color.clone()),
                     );
                     text_layout = text_layout.range_attribute(
-                        *start + prefix..*end + prefix,
+                        range,
                         TextAttribute::Weight(FontWeight::BOLD),
                     );
-                    let text_layout = text_layout.build().unwrap();
-                    ctx.draw_text(
-                        &text_layout,
-                        Point::new(
-                            self.line_height,
-                            self.line_height * i as f64
-                                + (self.line_height - text_layout.size().height)
-                                    / 2.0,
-                        ),
-                    );
                 }
+                let text_layout = text_layout.build().unwrap();
+                ctx.draw_text(
+                    &text_layout,
+                    Point::new(
+                        self.line_height,
+                        self.line_height * i as f64
+                            + (self.line_height - text_layout.size().height) / 2.0,
+                    ),
+                );
             }
             i += 1;
         }
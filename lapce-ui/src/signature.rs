@@ -1,65 +1,164 @@
-use lsp_types::ParameterLabel;
-use lsp_types::SignatureHelp;
+use druid::{
+    piet::{Text, TextAttribute, TextLayoutBuilder},
+    BoxConstraints, Data, Env, Event, EventCtx, FontFamily, FontWeight, LayoutCtx,
+    LifeCycle, LifeCycleCtx, PaintCtx, Point, RenderContext, Size, UpdateCtx,
+    Widget, WidgetId,
+};
+use lapce_data::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::{LapceTabData, SIGNATURE_PADDING},
+    signature::{SignatureData, SignatureStatus},
+};
+use std::sync::Arc;
 
-#[derive(Clone)]
-pub struct SignatureState {
-    pub offset: Option<usize>,
-    pub signature: Option<SignatureHelp>,
-    pub active: Option<(usize, usize)>,
+/// Shows the signature of the call the cursor is in, with the active
+/// parameter highlighted.
+pub struct SignatureContainer {
+    id: WidgetId,
 }
 
-impl SignatureState {
-    pub fn new() -> Self {
-        Self {
-            offset: None,
-            signature: None,
-            active: None,
-        }
+impl SignatureContainer {
+    pub fn new(data: &SignatureData) -> Self {
+        Self { id: data.id }
     }
+}
 
-    pub fn update(&mut self, offset: usize, commas: Vec<usize>) -> Option<bool> {
-        let signature = self.signature.as_ref()?;
-        let params = signature.signatures[0].parameters.as_ref()?;
-        if params.is_empty() {
-            return None;
-        }
-        let mut index = commas.len();
-        for (i, c) in commas.iter().enumerate() {
-            if offset <= *c {
-                index = i;
-                break;
+impl Widget<LapceTabData> for SignatureContainer {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.id)
+    }
+
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
+                let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
+                if let LapceUICommand::UpdateSignature(request_id, resp) = command {
+                    let signature = Arc::make_mut(&mut data.signature);
+                    signature.receive(*request_id, resp.to_owned());
+                    ctx.request_layout();
+                }
             }
+            _ => {}
         }
+    }
 
-        let label = signature.signatures[0].label.clone();
-        let active = if index >= params.len() {
-            None
-        } else {
-            match &params[index].label {
-                ParameterLabel::Simple(s) => {
-                    let start = label.find(s)?;
-                    Some((start, start + s.len()))
-                }
-                ParameterLabel::LabelOffsets(offsets) => {
-                    Some((offsets[0] as usize, offsets[1] as usize))
-                }
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        let old_signature = &old_data.signature;
+        let signature = &data.signature;
+
+        if signature.status != SignatureStatus::Inactive {
+            let old_editor = old_data.main_split.active_editor();
+            let old_editor = match old_editor {
+                Some(editor) => editor,
+                None => return,
+            };
+            let editor = data.main_split.active_editor();
+            let editor = match editor {
+                Some(editor) => editor,
+                None => return,
+            };
+            if old_editor.window_origin != editor.window_origin
+                || old_editor.scroll_offset != editor.scroll_offset
+            {
+                ctx.request_layout();
             }
-        };
+        }
 
-        let changed = self.active != active;
-        self.active = active;
-        Some(changed)
+        if old_signature.status != signature.status
+            || old_signature.offset != signature.offset
+            || old_signature.active_parameter != signature.active_parameter
+            || !old_signature.signature.same(&signature.signature)
+        {
+            ctx.request_layout();
+        }
     }
 
-    pub fn clear(&mut self) {
-        self.offset = None;
-        self.signature = None;
-        self.active = None;
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        _bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        ctx.set_paint_insets((10.0, 10.0, 10.0, 10.0));
+        data.signature_size(ctx.text(), &data.config)
     }
-}
 
-impl Default for SignatureState {
-    fn default() -> Self {
-        Self::new()
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        if data.signature.status == SignatureStatus::Inactive {
+            return;
+        }
+        let label = match data.signature.label() {
+            Some(label) => label,
+            None => return,
+        };
+
+        let shadow_width = 5.0;
+        let rect = ctx.size().to_rect();
+        ctx.blurred_rect(
+            rect,
+            shadow_width,
+            data.config
+                .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+        );
+        ctx.fill(
+            rect,
+            data.config
+                .get_color_unchecked(LapceTheme::COMPLETION_BACKGROUND),
+        );
+
+        let mut text_layout = ctx
+            .text()
+            .new_text_layout(label.to_string())
+            .font(
+                FontFamily::new_unchecked(data.config.editor.font_family.clone()),
+                data.config.editor.font_size as f64,
+            )
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                    .clone(),
+            );
+        if let Some((start, end)) = data.signature.active_parameter_range() {
+            text_layout = text_layout
+                .range_attribute(
+                    start..end,
+                    TextAttribute::TextColor(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_FOCUS)
+                            .clone(),
+                    ),
+                )
+                .range_attribute(
+                    start..end,
+                    TextAttribute::Weight(FontWeight::BOLD),
+                );
+        }
+        let text_layout = text_layout.build().unwrap();
+        let line_height = data.config.editor.line_height as f64;
+        let y = (line_height - text_layout.size().height) / 2.0;
+        ctx.draw_text(&text_layout, Point::new(SIGNATURE_PADDING, y));
     }
 }
@@ -1,34 +1,76 @@
+use std::{sync::Arc, time::Duration};
+
 use druid::{
-    piet::{Text, TextLayout, TextLayoutBuilder},
-    Command, Event, EventCtx, FontFamily, MouseEvent, Point, RenderContext, Size,
-    Target, Widget,
+    piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder},
+    Command, Data, Event, EventCtx, FontFamily, LayoutCtx, MouseEvent, Point, Rect,
+    RenderContext, Size, Target, TimerToken, Widget,
 };
 use lapce_data::{
+    buffer::{Buffer, BufferContent},
     command::{
-        CommandTarget, LapceCommandNew, LapceWorkbenchCommand, LAPCE_NEW_COMMAND,
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND,
     },
     config::LapceTheme,
     data::{FocusArea, LapceTabData, PanelKind},
+    menu::MenuItem,
     panel::PanelPosition,
+    proxy::ProxyStatus,
     state::Mode,
 };
+use strum::EnumMessage;
 
 use crate::{svg::get_svg, tab::LapceIcon};
 
+/// What clicking a segment of the status bar does
+enum SegmentClick {
+    Command(Command),
+    Menu(Vec<MenuItem>),
+}
+
+/// A piece of text on the right of the status bar.
+struct StatusSegment {
+    text_layout: PietTextLayout,
+    rect: Rect,
+    click: SegmentClick,
+}
+
+/// The encodings offered to reopen a file with
+const ENCODINGS: &[&str] = &[
+    "UTF-8",
+    "UTF-16LE",
+    "UTF-16BE",
+    "windows-1252",
+    "ISO-8859-2",
+    "windows-1251",
+    "KOI8-R",
+    "GBK",
+    "Big5",
+    "Shift_JIS",
+    "EUC-JP",
+    "EUC-KR",
+];
+
 pub struct LapceStatusNew {
     height: f64,
     panel_icons: Vec<LapceIcon>,
+    segments: Vec<StatusSegment>,
     mouse_pos: Point,
     icon_size: f64,
+    message_timer: TimerToken,
 }
 
 impl LapceStatusNew {
+    const MESSAGE_DURATION: Duration = Duration::from_secs(5);
+
     pub fn new() -> Self {
         Self {
             height: 25.0,
             panel_icons: Vec::new(),
+            segments: Vec::new(),
             mouse_pos: Point::ZERO,
             icon_size: 13.0,
+            message_timer: TimerToken::INVALID,
         }
     }
 
@@ -66,6 +108,13 @@ impl LapceStatusNew {
                     }
                     PanelKind::Search => LapceWorkbenchCommand::ToggleSearchVisual,
                     PanelKind::Problem => LapceWorkbenchCommand::ToggleProblemVisual,
+                    PanelKind::Outline => LapceWorkbenchCommand::ToggleOutlineVisual,
+                    PanelKind::CallHierarchy => {
+                        LapceWorkbenchCommand::ToggleCallHierarchyVisual
+                    }
+                    PanelKind::TypeHierarchy => {
+                        LapceWorkbenchCommand::ToggleTypeHierarchyVisual
+                    }
                 };
 
                 LapceIcon {
@@ -92,13 +141,219 @@ impl LapceStatusNew {
         icons
     }
 
-    fn icon_hit_test(&self, mouse_event: &MouseEvent) -> bool {
-        for icon in self.panel_icons.iter() {
-            if icon.rect.contains(mouse_event.pos) {
-                return true;
-            }
+    /// The file in the active editor, once it's loaded.
+    fn active_buffer(data: &LapceTabData) -> Option<&Buffer> {
+        let editor = data.main_split.active_editor()?;
+        let path = match &editor.content {
+            BufferContent::File(path) => path,
+            _ => return None,
+        };
+        let buffer = data.main_split.open_files.get(path)?;
+        if !buffer.loaded() {
+            return None;
+        }
+        Some(buffer)
+    }
+
+    /// Where the cursor of the active editor is, with how much it selects.
+    fn position_text(data: &LapceTabData) -> Option<String> {
+        let editor = data.main_split.active_editor()?;
+        let buffer = match &editor.content {
+            BufferContent::File(path) => data.main_split.open_files.get(path)?,
+            _ => return None,
+        };
+        let tab_width = buffer.tab_width(&data.config);
+        let (line, col) =
+            buffer.offset_to_line_col(editor.cursor.offset(), tab_width);
+        let mut text = format!("Ln {}, Col {}", line + 1, col + 1);
+        let selected = editor.cursor.selected_len(buffer.data(), tab_width);
+        if selected > 0 {
+            text += &format!(" ({selected} selected)");
+        }
+        let cursors = editor.cursor.count();
+        if cursors > 1 {
+            text += &format!(", {cursors} cursors");
+        }
+        Some(text)
+    }
+
+    /// The texts of the segments about the file in the active editor: its
+    /// language, line ending, encoding, and indentation.
+    fn file_texts(data: &LapceTabData) -> Option<[String; 4]> {
+        let buffer = Self::active_buffer(data)?;
+        let indentation = buffer
+            .indent_style()
+            .display_name(buffer.tab_width(&data.config));
+        Some([
+            buffer
+                .language()
+                .map(|language| language.display_name())
+                .unwrap_or("Plain Text")
+                .to_string(),
+            buffer.line_ending().name().to_string(),
+            buffer.encoding().display_name(),
+            indentation,
+        ])
+    }
+
+    /// The segments on the right of the status bar, laid out from the right
+    /// edge.
+    fn segments(
+        &self,
+        ctx: &mut LayoutCtx,
+        self_size: Size,
+        data: &LapceTabData,
+    ) -> Vec<StatusSegment> {
+        let workbench_command =
+            |cmd: LapceWorkbenchCommand, data: Option<serde_json::Value>| {
+                LapceCommandNew {
+                    cmd: cmd.to_string(),
+                    data,
+                    palette_desc: None,
+                    target: CommandTarget::Workbench,
+                }
+            };
+
+        let mut segments = Vec::new();
+        if data.workspace.kind.is_remote()
+            && matches!(*data.proxy_status, ProxyStatus::Disconnected)
+        {
+            let cmd = LapceWorkbenchCommand::ReconnectRemote;
+            segments.push((
+                cmd.get_message().unwrap().to_string(),
+                SegmentClick::Command(Command::new(
+                    LAPCE_NEW_COMMAND,
+                    workbench_command(cmd, None),
+                    Target::Widget(data.id),
+                )),
+            ));
         }
-        false
+        if let Some([language, line_ending, encoding, indentation]) =
+            Self::file_texts(data)
+        {
+            segments.push((
+                language,
+                SegmentClick::Command(Command::new(
+                    LAPCE_NEW_COMMAND,
+                    workbench_command(
+                        LapceWorkbenchCommand::ChangeLanguageMode,
+                        None,
+                    ),
+                    Target::Widget(data.id),
+                )),
+            ));
+            segments.push((
+                line_ending,
+                SegmentClick::Menu(
+                    [
+                        LapceWorkbenchCommand::ChangeLineEndingsToLf,
+                        LapceWorkbenchCommand::ChangeLineEndingsToCrlf,
+                    ]
+                    .into_iter()
+                    .map(|cmd| MenuItem {
+                        text: cmd.get_message().unwrap().to_string(),
+                        indices: Vec::new(),
+                        command: workbench_command(cmd, None),
+                    })
+                    .collect(),
+                ),
+            ));
+            segments.push((
+                encoding,
+                SegmentClick::Menu(
+                    ENCODINGS
+                        .iter()
+                        .map(|encoding| MenuItem {
+                            text: format!("Reopen with {encoding}"),
+                            indices: Vec::new(),
+                            command: workbench_command(
+                                LapceWorkbenchCommand::ReopenWithEncoding,
+                                Some(serde_json::json!(encoding)),
+                            ),
+                        })
+                        .collect(),
+                ),
+            ));
+            let menu_item = |text: String, cmd, data| MenuItem {
+                text,
+                indices: Vec::new(),
+                command: workbench_command(cmd, data),
+            };
+            let mut items: Vec<MenuItem> = [2, 4, 8]
+                .into_iter()
+                .map(|size| {
+                    menu_item(
+                        format!("Indent Using Spaces: {size}"),
+                        LapceWorkbenchCommand::IndentUsingSpaces,
+                        Some(serde_json::json!(size)),
+                    )
+                })
+                .collect();
+            items.extend([2, 4, 8].into_iter().map(|width| {
+                menu_item(
+                    format!("Tab Size: {width}"),
+                    LapceWorkbenchCommand::SetTabWidth,
+                    Some(serde_json::json!(width)),
+                )
+            }));
+            items.extend(
+                [
+                    LapceWorkbenchCommand::IndentUsingTabs,
+                    LapceWorkbenchCommand::ConvertIndentationToSpaces,
+                    LapceWorkbenchCommand::ConvertIndentationToTabs,
+                ]
+                .into_iter()
+                .map(|cmd| {
+                    menu_item(cmd.get_message().unwrap().to_string(), cmd, None)
+                }),
+            );
+            segments.push((indentation, SegmentClick::Menu(items)));
+        }
+        if let Some(position) = Self::position_text(data) {
+            segments.push((
+                position,
+                SegmentClick::Command(Command::new(
+                    LAPCE_NEW_COMMAND,
+                    workbench_command(LapceWorkbenchCommand::PaletteLine, None),
+                    Target::Widget(data.id),
+                )),
+            ));
+        }
+
+        let mut right = self_size.width;
+        segments
+            .into_iter()
+            .map(|(text, click)| {
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(text)
+                    .font(FontFamily::SYSTEM_UI, 13.0)
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                let width = text_layout.size().width + 20.0;
+                right -= width;
+                StatusSegment {
+                    text_layout,
+                    rect: Size::new(width, self_size.height)
+                        .to_rect()
+                        .with_origin(Point::new(right, 0.0)),
+                    click,
+                }
+            })
+            .collect()
+    }
+
+    fn icon_hit_test(&self, mouse_event: &MouseEvent) -> bool {
+        self.panel_icons
+            .iter()
+            .map(|icon| icon.rect)
+            .chain(self.segments.iter().map(|segment| segment.rect))
+            .any(|rect| rect.contains(mouse_event.pos))
     }
 
     fn mouse_down(&self, ctx: &mut EventCtx, mouse_event: &MouseEvent) {
@@ -107,6 +362,25 @@ impl LapceStatusNew {
                 ctx.submit_command(icon.command.clone());
             }
         }
+        for segment in self.segments.iter() {
+            if !segment.rect.contains(mouse_event.pos) {
+                continue;
+            }
+            match &segment.click {
+                SegmentClick::Command(command) => {
+                    ctx.submit_command(command.clone());
+                }
+                SegmentClick::Menu(items) => {
+                    // The menu opens upwards from the top of the status bar.
+                    let point = ctx.window_origin() + (segment.rect.x0, 0.0).into();
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ShowMenu(point, Arc::new(items.clone())),
+                        Target::Auto,
+                    ));
+                }
+            }
+        }
     }
 }
 
@@ -121,10 +395,14 @@ impl Widget<LapceTabData> for LapceStatusNew {
         &mut self,
         ctx: &mut druid::EventCtx,
         event: &Event,
-        _data: &mut LapceTabData,
+        data: &mut LapceTabData,
         _env: &druid::Env,
     ) {
         match event {
+            Event::Timer(token) if *token == self.message_timer => {
+                self.message_timer = TimerToken::INVALID;
+                data.status_message = None;
+            }
             Event::MouseMove(mouse_event) => {
                 self.mouse_pos = mouse_event.pos;
                 if self.icon_hit_test(mouse_event) {
@@ -162,13 +440,39 @@ impl Widget<LapceTabData> for LapceStatusNew {
             old_data.main_split.active_editor(),
             data.main_split.active_editor(),
         ) {
-            (Some(old_data), Some(data)) => {
-                if old_data.cursor.get_mode() != data.cursor.get_mode() {
+            (Some(old_editor), Some(editor)) => {
+                if old_editor.cursor.get_mode() != editor.cursor.get_mode() {
                     ctx.request_paint();
                 }
+                // Only work out the position again when it could have moved.
+                if (old_editor.cursor != editor.cursor
+                    || old_editor.content != editor.content
+                    || Self::active_buffer(old_data).map(|b| b.rev())
+                        != Self::active_buffer(data).map(|b| b.rev()))
+                    && Self::position_text(old_data) != Self::position_text(data)
+                {
+                    ctx.request_layout();
+                }
             }
             (None, None) => (),
-            _ => ctx.request_paint(),
+            _ => {
+                ctx.request_paint();
+                ctx.request_layout();
+            }
+        }
+
+        if Self::file_texts(old_data) != Self::file_texts(data)
+            || !old_data.config.same(&data.config)
+            || !old_data.proxy_status.same(&data.proxy_status)
+        {
+            ctx.request_layout();
+        }
+
+        if !old_data.status_message.same(&data.status_message) {
+            if data.status_message.is_some() {
+                self.message_timer = ctx.request_timer(Self::MESSAGE_DURATION);
+            }
+            ctx.request_paint();
         }
 
         if old_data.main_split.warning_count != data.main_split.warning_count
@@ -185,13 +489,14 @@ impl Widget<LapceTabData> for LapceStatusNew {
 
     fn layout(
         &mut self,
-        _ctx: &mut druid::LayoutCtx,
+        ctx: &mut LayoutCtx,
         bc: &druid::BoxConstraints,
         data: &LapceTabData,
         _env: &druid::Env,
     ) -> Size {
         let self_size = Size::new(bc.max().width, self.height);
         self.panel_icons = self.panel_icons(self_size, data);
+        self.segments = self.segments(ctx, self_size, data);
         self_size
     }
 
@@ -284,16 +589,10 @@ impl Widget<LapceTabData> for LapceStatusNew {
         );
         left += 10.0 + text_layout.size().width;
 
-        for progress in data.progresses.iter() {
-            let mut text = progress.title.clone();
-            let message = progress.message.clone().unwrap_or_else(|| "".to_string());
-            if !message.is_empty() {
-                text += ": ";
-                text += &message;
-            }
+        if let Some(message) = data.status_message.as_ref() {
             let text_layout = ctx
                 .text()
-                .new_text_layout(text)
+                .new_text_layout(message.to_string())
                 .font(FontFamily::SYSTEM_UI, 13.0)
                 .text_color(
                     data.config
@@ -312,6 +611,81 @@ impl Widget<LapceTabData> for LapceStatusNew {
             left += 10.0 + text_layout.size().width;
         }
 
+        if let Some(summary) = data.progress_summary() {
+            if data.progresses.len() > 1 {
+                let badge_layout = ctx
+                    .text()
+                    .new_text_layout(data.progresses.len().to_string())
+                    .font(FontFamily::SYSTEM_UI, 11.0)
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_BACKGROUND)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                let badge_text_size = badge_layout.size();
+                let badge_height = badge_text_size.height + 2.0;
+                let badge_width = (badge_text_size.width + 8.0).max(badge_height);
+                let badge_rect = Size::new(badge_width, badge_height)
+                    .to_rect()
+                    .with_origin(Point::new(
+                        left + 10.0,
+                        (size.height - badge_height) / 2.0,
+                    ))
+                    .to_rounded_rect(badge_height / 2.0);
+                ctx.fill(
+                    badge_rect,
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND),
+                );
+                ctx.draw_text(
+                    &badge_layout,
+                    Point::new(
+                        left + 10.0 + (badge_width - badge_text_size.width) / 2.0,
+                        (size.height - badge_text_size.height) / 2.0,
+                    ),
+                );
+                left += 10.0 + badge_width;
+            }
+
+            let text_layout = ctx
+                .text()
+                .new_text_layout(summary)
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    left + 10.0,
+                    (size.height - text_layout.size().height) / 2.0,
+                ),
+            );
+        }
+
+        for segment in self.segments.iter() {
+            if segment.rect.contains(self.mouse_pos) {
+                ctx.fill(
+                    segment.rect,
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
+                );
+            }
+            ctx.draw_text(
+                &segment.text_layout,
+                Point::new(
+                    segment.rect.x0 + 10.0,
+                    (size.height - segment.text_layout.size().height) / 2.0,
+                ),
+            );
+        }
+
         let icon_padding = (self.height - self.icon_size) / 2.0;
         for icon in self.panel_icons.iter() {
             if icon.rect.contains(self.mouse_pos) {
@@ -6,7 +6,10 @@ use lazy_static::lazy_static;
 use lsp_types::{CompletionItemKind, SymbolKind};
 use parking_lot::Mutex;
 
-use lapce_data::config::{Config, LOGO};
+use lapce_data::{
+    code_action::CodeActionGroup,
+    config::{Config, LOGO},
+};
 
 pub const ICONS_DIR: Dir = include_dir!("../icons");
 lazy_static! {
@@ -97,6 +100,16 @@ pub fn symbol_svg_new(kind: &SymbolKind) -> Option<Svg> {
     get_svg(&format!("symbol-{}.svg", kind_str))
 }
 
+pub fn code_action_svg(group: CodeActionGroup) -> Svg {
+    let name = match group {
+        CodeActionGroup::QuickFix => "lightbulb-autofix.svg",
+        CodeActionGroup::Refactor => "lightbulb.svg",
+        CodeActionGroup::Source => "symbol-file.svg",
+        CodeActionGroup::Other => "symbol-misc.svg",
+    };
+    get_svg(name).unwrap()
+}
+
 pub fn completion_svg(
     kind: Option<CompletionItemKind>,
     config: &Config,
@@ -1,47 +1,61 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use druid::{
     kurbo::Line,
     piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder},
     BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, FontFamily,
-    InternalLifeCycle, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect,
-    RenderContext, Size, Target, Widget, WidgetExt, WidgetId, WidgetPod,
-    WindowConfig,
+    InternalLifeCycle, KbKey, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point,
+    Rect, RenderContext, Size, Target, TimerToken, Widget, WidgetExt, WidgetId,
+    WidgetPod, WindowConfig,
 };
 use itertools::Itertools;
 use lapce_data::{
-    buffer::LocalBufferKind,
+    buffer::{decoration::PendingStyles, BufferContent, LocalBufferKind},
+    code_action::sort_code_actions,
     command::{
         CommandTarget, LapceCommand, LapceCommandNew, LapceUICommand,
-        LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND,
+        LapceWorkbenchCommand, LAPCE_NEW_COMMAND, LAPCE_UI_COMMAND,
     },
     completion::CompletionStatus,
     config::{Config, LapceTheme},
     data::{
-        DragContent, EditorDiagnostic, FocusArea, LapceTabData, PanelKind,
-        WorkProgress,
+        resolved_code_action, DragContent, EditorDiagnostic, EditorTabChild,
+        FocusArea, LapceTabData, PanelKind, PanelSize, WorkProgress,
     },
-    editor::EditorLocationNew,
+    editor::{definition_command, EditorLocationNew},
+    hierarchy::HierarchyKind,
     hover::HoverStatus,
     keypress::{DefaultKeyPressHandler, KeyPressData},
     movement::{self, CursorMode, Selection},
     palette::PaletteStatus,
     panel::{PanelPosition, PanelResizePosition},
     proxy::path_from_url,
-    state::LapceWorkspaceType,
+    signature::SignatureStatus,
 };
-use lsp_types::DiagnosticSeverity;
+use lapce_rpc::{buffer::SaveErrorKind, file::GlobalSearchResponse};
+use lsp_types::{DiagnosticSeverity, Position};
 use serde::Deserialize;
+use serde_json::json;
 
 use crate::{
     activity::ActivityBar, code_action::CodeAction, completion::CompletionContainer,
-    explorer::FileExplorer, hover::HoverContainer, palette::NewPalette,
+    explorer::FileExplorer, hierarchy::new_hierarchy_panel, hover::HoverContainer,
+    outline::new_outline_panel, palette::NewPalette, peek::PeekContainer,
     picker::FilePicker, plugin::Plugin, problem::new_problem_panel,
     search::new_search_panel, settings::LapceSettingsPanel,
-    source_control::new_source_control_panel, split::split_data_widget,
-    status::LapceStatusNew, terminal::TerminalPanel,
+    signature::SignatureContainer, source_control::new_source_control_panel,
+    split::split_data_widget, status::LapceStatusNew, svg::get_svg,
+    terminal::TerminalPanel,
 };
 
+/// Dragging a panel divider below this size collapses the panel on release.
+const PANEL_COLLAPSE_SIZE: f64 = 30.0;
+
 pub struct LapceIcon {
     pub rect: Rect,
     pub command: Command,
@@ -60,6 +74,8 @@ pub struct LapceTabNew {
     main_split: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     completion: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     hover: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    signature: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    peek: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     palette: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     code_action: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     status: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
@@ -68,10 +84,17 @@ pub struct LapceTabNew {
     panels:
         HashMap<PanelKind, WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>>,
     current_bar_hover: Option<PanelResizePosition>,
+    /// The panel size when the current resize drag started, restored if the
+    /// drag ends up collapsing the panel.
+    resize_start_size: f64,
+    collapse_on_release: bool,
     height: f64,
     main_split_height: f64,
     status_height: f64,
     mouse_pos: Point,
+    /// Style updates waiting for the next frame, keyed by buffer path, so that
+    /// a burst of them while typing is applied once.
+    pending_styles: HashMap<PathBuf, PendingStyles>,
 }
 
 impl LapceTabNew {
@@ -86,6 +109,8 @@ impl LapceTabNew {
         let activity = ActivityBar::new();
         let completion = CompletionContainer::new(&data.completion);
         let hover = HoverContainer::new(&data.hover);
+        let signature = SignatureContainer::new(&data.signature);
+        let peek = PeekContainer::new(&data.peek);
         let palette = NewPalette::new(
             &data.palette,
             data.main_split
@@ -121,6 +146,25 @@ impl LapceTabNew {
         let problem = new_problem_panel(&data.problem);
         panels.insert(PanelKind::Problem, WidgetPod::new(problem.boxed()));
 
+        let outline = new_outline_panel(&data.outline);
+        panels.insert(PanelKind::Outline, WidgetPod::new(outline.boxed()));
+
+        for (panel, title, hierarchy) in [
+            (
+                PanelKind::CallHierarchy,
+                "Call Hierarchy",
+                &data.call_hierarchy,
+            ),
+            (
+                PanelKind::TypeHierarchy,
+                "Type Hierarchy",
+                &data.type_hierarchy,
+            ),
+        ] {
+            let hierarchy = new_hierarchy_panel(panel, title, hierarchy);
+            panels.insert(panel, WidgetPod::new(hierarchy.boxed()));
+        }
+
         let picker = FilePicker::new(data);
 
         let settings = LapceSettingsPanel::new(data);
@@ -131,6 +175,8 @@ impl LapceTabNew {
             main_split: WidgetPod::new(main_split.boxed()),
             completion: WidgetPod::new(completion.boxed()),
             hover: WidgetPod::new(hover.boxed()),
+            signature: WidgetPod::new(signature.boxed()),
+            peek: WidgetPod::new(peek.boxed()),
             code_action: WidgetPod::new(code_action.boxed()),
             picker: WidgetPod::new(picker.boxed()),
             palette: WidgetPod::new(palette.boxed()),
@@ -138,10 +184,34 @@ impl LapceTabNew {
             settings: WidgetPod::new(settings.boxed()),
             panels,
             current_bar_hover: None,
+            resize_start_size: 0.0,
+            collapse_on_release: false,
             height: 0.0,
             main_split_height: 0.0,
             status_height: 0.0,
             mouse_pos: Point::ZERO,
+            pending_styles: HashMap::new(),
+        }
+    }
+
+    fn apply_pending_styles(&mut self, data: &mut LapceTabData) {
+        for (path, pending) in self.pending_styles.drain() {
+            if let Some(buffer) = data.main_split.open_file_mut(&path) {
+                let (syntax, semantic_styles) = pending.take(buffer.rev());
+                if syntax.is_none() && semantic_styles.is_none() {
+                    continue;
+                }
+                let buffer = Arc::make_mut(buffer);
+                if let Some(syntax) = syntax {
+                    buffer.set_syntax(Some(syntax));
+                }
+                if let Some(styles) = semantic_styles {
+                    buffer.set_semantic_styles(Some(styles));
+                    buffer.line_styles().borrow_mut().clear();
+                } else if buffer.semantic_styles().is_none() {
+                    buffer.line_styles().borrow_mut().clear();
+                }
+            }
         }
     }
 
@@ -149,13 +219,69 @@ impl LapceTabNew {
         if let Some(position) = self.current_bar_hover.as_ref() {
             match position {
                 PanelResizePosition::Left => {
-                    data.panel_size.left = (mouse_pos.x - 50.0).round().max(50.0);
+                    let size = (mouse_pos.x - 50.0).round();
+                    self.collapse_on_release = size < PANEL_COLLAPSE_SIZE;
+                    data.panel_size.left = size.max(50.0);
                 }
                 PanelResizePosition::LeftSplit => (),
                 PanelResizePosition::Bottom => {
-                    data.panel_size.bottom =
-                        (self.height - mouse_pos.y.round() - self.status_height)
-                            .max(50.0);
+                    let size =
+                        self.height - mouse_pos.y.round() - self.status_height;
+                    self.collapse_on_release = size < PANEL_COLLAPSE_SIZE;
+                    data.panel_size.bottom = size.max(50.0);
+                }
+            }
+        }
+    }
+
+    fn start_resize(&mut self, data: &LapceTabData, position: &PanelResizePosition) {
+        self.resize_start_size = match position {
+            PanelResizePosition::Left => data.panel_size.left,
+            PanelResizePosition::LeftSplit => data.panel_size.left_split,
+            PanelResizePosition::Bottom => data.panel_size.bottom,
+        };
+        self.collapse_on_release = false;
+    }
+
+    fn reset_panel_size(data: &mut LapceTabData, position: &PanelResizePosition) {
+        let default = PanelSize::default();
+        match position {
+            PanelResizePosition::Left => data.panel_size.left = default.left,
+            PanelResizePosition::LeftSplit => {
+                data.panel_size.left_split = default.left_split
+            }
+            PanelResizePosition::Bottom => data.panel_size.bottom = default.bottom,
+        }
+    }
+
+    /// Hides the panels next to the divider that was dragged below
+    /// `PANEL_COLLAPSE_SIZE`, keeping the size they had before the drag so
+    /// that showing them again restores it.
+    fn collapse_panels(&mut self, ctx: &mut EventCtx, data: &mut LapceTabData) {
+        let positions = match self.current_bar_hover.as_ref() {
+            Some(PanelResizePosition::Left) => {
+                data.panel_size.left = self.resize_start_size;
+                [PanelPosition::LeftTop, PanelPosition::LeftBottom]
+            }
+            Some(PanelResizePosition::Bottom) => {
+                data.panel_size.bottom = self.resize_start_size;
+                [PanelPosition::BottomLeft, PanelPosition::BottomRight]
+            }
+            Some(PanelResizePosition::LeftSplit) | None => return,
+        };
+        for position in positions.iter() {
+            if let Some(panel) = data.panels.get(position) {
+                if panel.is_shown() {
+                    ctx.submit_command(Command::new(
+                        LAPCE_NEW_COMMAND,
+                        LapceCommandNew {
+                            cmd: LapceWorkbenchCommand::HidePanel.to_string(),
+                            data: Some(json!(panel.active)),
+                            palette_desc: None,
+                            target: CommandTarget::Workbench,
+                        },
+                        Target::Widget(data.id),
+                    ));
                 }
             }
         }
@@ -208,10 +334,45 @@ impl LapceTabNew {
         None
     }
 
+    fn format_failed_message(path: &Path, e: &anyhow::Error) -> String {
+        let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        format!("Failed to format {}: {}", file_name, e)
+    }
+
+    /// The ways to recover from a save that failed for lack of permissions.
+    fn save_options(path: &Path, config: &Config) -> Vec<LapceCommandNew> {
+        let data = Some(json!(path));
+        let mut options = Vec::new();
+        if !config.lapce.sudo_helper.trim().is_empty() {
+            options.push(LapceCommandNew {
+                cmd: LapceWorkbenchCommand::SaveElevated.to_string(),
+                data: data.clone(),
+                palette_desc: Some(format!(
+                    "Retry Saving with {}",
+                    config.lapce.sudo_helper.trim()
+                )),
+                target: CommandTarget::Workbench,
+            });
+        }
+        options.push(LapceCommandNew {
+            cmd: LapceWorkbenchCommand::SaveAs.to_string(),
+            data: data.clone(),
+            palette_desc: Some("Save As...".to_string()),
+            target: CommandTarget::Workbench,
+        });
+        options.push(LapceCommandNew {
+            cmd: LapceWorkbenchCommand::SaveOverwritePermissions.to_string(),
+            data,
+            palette_desc: Some("Make File Writable and Save".to_string()),
+            target: CommandTarget::Workbench,
+        });
+        options
+    }
+
     fn paint_drag(&self, ctx: &mut PaintCtx, data: &LapceTabData) {
         if let Some((offset, drag_content)) = data.drag.as_ref() {
             match drag_content {
-                DragContent::EditorTab(_, _, _, tab_rect) => {
+                DragContent::EditorTab(_, _, child, tab_rect) => {
                     let rect = tab_rect.rect.with_origin(self.mouse_pos - *offset);
                     let size = rect.size();
                     let shadow_width = 5.0;
@@ -246,6 +407,52 @@ impl LapceTabNew {
                             rect.y0 + (size.height - text_size.height) / 2.0,
                         ),
                     );
+
+                    let is_dirty = match child {
+                        EditorTabChild::Editor(view_id, _) => {
+                            data.main_split.editor_buffer(*view_id).dirty()
+                        }
+                    };
+                    if is_dirty {
+                        let close_rect = tab_rect.close_rect
+                            + (rect.origin() - tab_rect.rect.origin());
+                        let svg = get_svg("unsaved.svg").unwrap();
+                        ctx.draw_svg(
+                            &svg,
+                            close_rect.inflate(-4.0, -4.0),
+                            Some(
+                                data.config.get_color_unchecked(
+                                    LapceTheme::EDITOR_FOREGROUND,
+                                ),
+                            ),
+                        );
+                    }
+                }
+                DragContent::Panel(kind, _) => {
+                    let size = 50.0;
+                    let rect = Size::new(size, size)
+                        .to_rect()
+                        .with_origin(self.mouse_pos - *offset);
+                    ctx.blurred_rect(
+                        rect,
+                        5.0,
+                        data.config
+                            .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+                    );
+                    ctx.fill(
+                        rect,
+                        data.config
+                            .get_color_unchecked(LapceTheme::PANEL_BACKGROUND),
+                    );
+                    let svg = get_svg(kind.svg_name()).unwrap();
+                    ctx.draw_svg(
+                        &svg,
+                        rect.inflate(-12.5, -12.5),
+                        Some(
+                            data.config
+                                .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND),
+                        ),
+                    );
                 }
             }
         }
@@ -265,11 +472,33 @@ impl Widget<LapceTabData> for LapceTabNew {
         env: &Env,
     ) {
         match event {
+            Event::KeyDown(key_event)
+                if key_event.key == KbKey::Escape
+                    && key_event.mods.is_empty()
+                    && data.config.editor.escape_closes_overlays =>
+            {
+                // Close only the topmost overlay, so that Escape falls through
+                // to changing mode or collapsing the selection once none are
+                // open.
+                if let Some(overlay) = data.topmost_overlay() {
+                    data.close_overlay(ctx, overlay);
+                    ctx.set_handled();
+                }
+            }
             Event::MouseDown(mouse) => {
                 if mouse.button.is_left() {
                     if let Some(position) = self.bar_hit_test(data, mouse.pos) {
-                        self.current_bar_hover = Some(position);
-                        ctx.set_active(true);
+                        // The first click of a double click has already
+                        // started a drag, which its release ends without
+                        // moving the divider.
+                        if mouse.count == 2 {
+                            Self::reset_panel_size(data, &position);
+                            ctx.request_layout();
+                        } else {
+                            self.start_resize(data, &position);
+                            self.current_bar_hover = Some(position);
+                            ctx.set_active(true);
+                        }
                         ctx.set_handled();
                     }
                 }
@@ -277,10 +506,18 @@ impl Widget<LapceTabData> for LapceTabNew {
             Event::MouseUp(mouse) => {
                 if mouse.button.is_left() && ctx.is_active() {
                     ctx.set_active(false);
+                    if self.collapse_on_release {
+                        self.collapse_on_release = false;
+                        self.collapse_panels(ctx, data);
+                        ctx.request_layout();
+                    }
                 }
             }
             Event::MouseMove(mouse) => {
                 self.mouse_pos = mouse.pos;
+                if data.drag.is_some() {
+                    ctx.request_paint();
+                }
                 if ctx.is_active() {
                     self.update_split_point(data, mouse.pos);
                     ctx.request_layout();
@@ -300,6 +537,9 @@ impl Widget<LapceTabData> for LapceTabNew {
                     }
                 }
             }
+            Event::AnimFrame(_) if !self.pending_styles.is_empty() => {
+                self.apply_pending_styles(data);
+            }
             Event::Command(cmd) if cmd.is(LAPCE_NEW_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_NEW_COMMAND);
                 data.run_command(ctx, command, None, env);
@@ -319,18 +559,23 @@ impl Widget<LapceTabData> for LapceTabNew {
                     LapceUICommand::LoadBuffer {
                         path,
                         content,
+                        encoding,
                         locations,
                     } => {
-                        let buffer =
-                            data.main_split.open_files.get_mut(path).unwrap();
-                        Arc::make_mut(buffer).load_content(content);
-                        for (view_id, location) in locations {
-                            data.main_split.go_to_location(
-                                ctx,
-                                Some(*view_id),
-                                location.clone(),
-                                &data.config,
-                            );
+                        if data.main_split.load_buffer(
+                            path,
+                            content,
+                            encoding.clone(),
+                        ) {
+                            data.main_split.apply_pending_edits(ctx, path);
+                            for (view_id, location) in locations {
+                                data.main_split.go_to_location(
+                                    ctx,
+                                    Some(*view_id),
+                                    location.clone(),
+                                    &data.config,
+                                );
+                            }
                         }
                         ctx.set_handled();
                     }
@@ -345,11 +590,18 @@ impl Widget<LapceTabData> for LapceTabNew {
                         }
                         if pattern.is_empty() {
                             Arc::make_mut(&mut data.find).unset();
-                            Arc::make_mut(&mut data.search).matches =
-                                Arc::new(HashMap::new());
+                            let search = Arc::make_mut(&mut data.search);
+                            search.matches = Arc::new(HashMap::new());
+                            search.context = Arc::new(HashMap::new());
                         } else {
                             let find = Arc::make_mut(&mut data.find);
-                            find.set_find(pattern, false, false, false);
+                            let options = data.search.options;
+                            find.set_find(
+                                pattern,
+                                false,
+                                options.regex,
+                                options.whole_word,
+                            );
                             find.visual = true;
                             if data.focus_area == FocusArea::Panel(PanelKind::Search)
                             {
@@ -371,53 +623,95 @@ impl Widget<LapceTabData> for LapceTabNew {
                             let event_sink = ctx.get_external_handle();
                             let tab_id = data.id;
                             data.proxy.global_search(
-                                    pattern.clone(),
-                                    Box::new(move |result| {
-                                        if let Ok(matches) = result {
-                                            if let Ok(matches) =
-                                                serde_json::from_value::<
-                                                    HashMap<
-                                                        PathBuf,
-                                                        Vec<(
-                                                            usize,
-                                                            (usize, usize),
-                                                            String,
-                                                        )>,
-                                                    >,
-                                                >(
-                                                    matches
-                                                )
-                                            {
-                                                let _ = event_sink.submit_command(
-                                                    LAPCE_UI_COMMAND,
-                                                    LapceUICommand::GlobalSearchResult(
-                                                        pattern,
-                                                        Arc::new(matches),
-                                                    ),
-                                                    Target::Widget(tab_id),
-                                                );
-                                            }
+                                pattern.clone(),
+                                data.search.options,
+                                data.config.lapce.search_context_lines,
+                                data.search.glob_list(),
+                                Box::new(move |result| {
+                                    if let Ok(resp) = result {
+                                        if let Ok(resp) = serde_json::from_value::<
+                                            GlobalSearchResponse,
+                                        >(
+                                            resp
+                                        ) {
+                                            let _ = event_sink.submit_command(
+                                                LAPCE_UI_COMMAND,
+                                                LapceUICommand::GlobalSearchResult(
+                                                    pattern,
+                                                    Arc::new(resp.matches),
+                                                    Arc::new(resp.context),
+                                                ),
+                                                Target::Widget(tab_id),
+                                            );
                                         }
-                                    }),
-                                )
+                                    }
+                                }),
+                            )
                         }
                     }
-                    LapceUICommand::GlobalSearchResult(pattern, matches) => {
+                    LapceUICommand::GlobalSearchResult(
+                        pattern,
+                        matches,
+                        context,
+                    ) => {
                         let buffer = data
                             .main_split
                             .local_buffers
                             .get(&LocalBufferKind::Search)
                             .unwrap();
                         if &buffer.rope().to_string() == pattern {
-                            Arc::make_mut(&mut data.search).matches =
-                                matches.clone();
+                            let search = Arc::make_mut(&mut data.search);
+                            search.matches = matches.clone();
+                            search.context = context.clone();
                         }
                     }
+                    LapceUICommand::UpdateSearchReplace(replace) => {
+                        Arc::make_mut(&mut data.search).replace = replace.clone();
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateSearchGlobs(globs) => {
+                        let search = Arc::make_mut(&mut data.search);
+                        search.globs = globs.clone();
+                        search.recent_globs_index = None;
+                        data.search_again(ctx);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::RememberSearchGlobs => {
+                        data.remember_search_globs();
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::RecallSearchGlobs(older) => {
+                        data.recall_search_globs(ctx, *older);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::GlobalReplace(pattern, replacement) => {
+                        data.global_replace(ctx, pattern, replacement);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::GlobalReplaceResult(pattern, resp) => {
+                        data.status_message = Some(Arc::new(
+                            if resp.skipped.is_empty() {
+                                format!("Replaced in {} files", resp.replaced.len())
+                            } else {
+                                format!(
+                                    "Replaced in {} files, skipped {} changed since the search",
+                                    resp.replaced.len(),
+                                    resp.skipped.len()
+                                )
+                            },
+                        ));
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateSearch(pattern.clone()),
+                            Target::Widget(data.id),
+                        ));
+                        ctx.set_handled();
+                    }
                     LapceUICommand::LoadBufferHead { path, id, content } => {
-                        let buffer =
-                            data.main_split.open_files.get_mut(path).unwrap();
-                        let buffer = Arc::make_mut(buffer);
-                        buffer.load_history(id, content.clone());
+                        if let Some(buffer) = data.main_split.open_file_mut(path) {
+                            let buffer = Arc::make_mut(buffer);
+                            buffer.load_history(id, content.clone());
+                        }
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateTerminalTitle(term_id, title) => {
@@ -429,13 +723,23 @@ impl Widget<LapceTabData> for LapceTabNew {
                         }
                     }
                     LapceUICommand::CancelFilePicker => {
-                        Arc::make_mut(&mut data.picker).active = false;
+                        let picker = Arc::make_mut(&mut data.picker);
+                        picker.active = false;
+                        picker.save_as = None;
                         ctx.set_handled();
                     }
                     LapceUICommand::ProxyUpdateStatus(status) => {
                         data.proxy_status = Arc::new(*status);
                         ctx.set_handled();
                     }
+                    LapceUICommand::ReconnectProxy => {
+                        data.reconnect_proxy();
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ShowStatusMessage(message) => {
+                        data.status_message = Some(Arc::new(message.to_string()));
+                        ctx.set_handled();
+                    }
                     LapceUICommand::HomeDir(path) => {
                         Arc::make_mut(&mut data.picker).init_home(path);
                         data.set_picker_pwd(path.clone());
@@ -458,7 +762,46 @@ impl Widget<LapceTabData> for LapceTabNew {
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateInstalledPlugins(plugins) => {
-                        data.installed_plugins = Arc::new(plugins.to_owned());
+                        data.set_installed_plugins(ctx, plugins.to_owned());
+                    }
+                    LapceUICommand::UpdateOnTypeFormattingTriggers(
+                        language_id,
+                        characters,
+                    ) => {
+                        data.main_split.on_type_formatting_triggers.insert(
+                            language_id.clone(),
+                            Arc::new(characters.clone()),
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateCompletionTriggers(
+                        language_id,
+                        characters,
+                    ) => {
+                        data.main_split.completion_triggers.insert(
+                            language_id.clone(),
+                            Arc::new(characters.clone()),
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::PluginUpdateAvailable(name, version) => {
+                        data.plugin_update_available(name, version);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdatePlugin(name) => {
+                        data.update_plugin(name);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdatePluginFilter(filter) => {
+                        Arc::make_mut(&mut data.plugin).filter = filter.clone();
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::PluginMarketplaceResults(query, plugins) => {
+                        if data.plugin.marketplace && &data.plugin.filter == query {
+                            Arc::make_mut(&mut data.plugin).marketplace_results =
+                                plugins.clone();
+                        }
+                        ctx.set_handled();
                     }
                     LapceUICommand::UpdateDiffInfo(diff) => {
                         let source_control = Arc::make_mut(&mut data.source_control);
@@ -501,10 +844,18 @@ impl Widget<LapceTabData> for LapceTabNew {
                                         });
                                     }
                                     lsp_types::WorkDoneProgress::Report(report) => {
+                                        // Update in place so the order of the
+                                        // progresses, and thus the primary task
+                                        // shown in the status bar, stays stable.
                                         for p in data.progresses.iter_mut() {
                                             if p.token == params.token {
-                                                p.message = report.message.clone();
-                                                p.percentage = report.percentage;
+                                                if report.message.is_some() {
+                                                    p.message =
+                                                        report.message.clone();
+                                                }
+                                                if report.percentage.is_some() {
+                                                    p.percentage = report.percentage;
+                                                }
                                             }
                                         }
                                     }
@@ -533,9 +884,7 @@ impl Widget<LapceTabData> for LapceTabNew {
                                 diagnositc: d.clone(),
                             })
                             .collect();
-                        data.main_split
-                            .diagnostics
-                            .insert(path, Arc::new(diagnostics));
+                        data.main_split.set_diagnostics(&path, diagnostics);
 
                         let mut errors = 0;
                         let mut warnings = 0;
@@ -558,30 +907,188 @@ impl Widget<LapceTabData> for LapceTabNew {
                         ctx.set_handled();
                     }
                     LapceUICommand::DocumentFormatAndSave(path, rev, result) => {
-                        data.main_split.document_format_and_save(
+                        if let Err(e) = data
+                            .main_split
+                            .document_format_and_save(ctx, path, *rev, result)
+                        {
+                            data.status_message = Some(Arc::new(
+                                Self::format_failed_message(path, &e),
+                            ));
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::DocumentRangeFormat(
+                        path,
+                        rev,
+                        range,
+                        result,
+                    ) => {
+                        if let Err(e) = data
+                            .main_split
+                            .document_range_format(path, *rev, range, result)
+                        {
+                            data.status_message = Some(Arc::new(
+                                Self::format_failed_message(path, &e),
+                            ));
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::DocumentOnTypeFormat(path, rev, result) => {
+                        data.main_split.document_on_type_format(path, *rev, result);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::PrepareRename(path, rev, offset, result) => {
+                        match data
+                            .main_split
+                            .rename_placeholder(path, *rev, *offset, result)
+                        {
+                            Ok(Some((position, placeholder))) => {
+                                ctx.submit_command(Command::new(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::RunPaletteRename {
+                                        path: path.clone(),
+                                        position,
+                                        placeholder,
+                                    },
+                                    Target::Widget(data.palette.widget_id),
+                                ));
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                data.status_message =
+                                    Some(Arc::new(format!("Can't rename: {}", e)));
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::Rename(path, position, new_name) => {
+                        data.main_split.rename(
                             ctx,
                             path,
-                            *rev,
-                            result,
-                            &data.config,
+                            *position,
+                            new_name.to_string(),
                         );
                         ctx.set_handled();
                     }
-                    LapceUICommand::DocumentFormat(path, rev, result) => {
-                        data.main_split.document_format(
-                            path,
-                            *rev,
+                    LapceUICommand::CodeActionResolved(path, result) => {
+                        data.main_split.resolving_code_action = false;
+                        match resolved_code_action(result) {
+                            Ok(action) => {
+                                ctx.submit_command(Command::new(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::RunCodeAction(
+                                        path.clone(),
+                                        Box::new(action),
+                                    ),
+                                    Target::Widget(data.id),
+                                ));
+                            }
+                            Err(e) => {
+                                data.status_message = Some(Arc::new(format!(
+                                    "Failed to resolve the code action: {}",
+                                    e
+                                )));
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::RunCodeAction(path, action) => {
+                        if let Some(edit) = action.edit.as_ref() {
+                            if let Some(summary) = data
+                                .main_split
+                                .apply_workspace_edit(ctx, edit, false)
+                            {
+                                data.status_message =
+                                    Some(Arc::new(summary.message()));
+                            }
+                        }
+                        if let Some(command) = action.command.as_ref() {
+                            data.main_split.execute_command(path, command);
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ApplyWorkspaceEdit { edit, save } => {
+                        if let Some(summary) =
+                            data.main_split.apply_workspace_edit(ctx, edit, *save)
+                        {
+                            data.status_message = Some(Arc::new(summary.message()));
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ResumeWorkspaceEdit {
+                        operation,
+                        result,
+                        steps,
+                        summary,
+                    } => {
+                        if let Some(summary) = data.main_split.resume_workspace_edit(
+                            ctx,
+                            operation,
                             result,
-                            &data.config,
-                        );
+                            steps.clone(),
+                            summary.clone(),
+                        ) {
+                            data.status_message = Some(Arc::new(summary.message()));
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::DocumentFormat(path, rev, result) => {
+                        if let Err(e) =
+                            data.main_split.document_format(path, *rev, result)
+                        {
+                            data.status_message = Some(Arc::new(
+                                Self::format_failed_message(path, &e),
+                            ));
+                        }
                         ctx.set_handled();
                     }
                     LapceUICommand::BufferSave(path, rev) => {
-                        let buffer =
-                            data.main_split.open_files.get_mut(path).unwrap();
-                        if buffer.rev() == *rev {
-                            Arc::make_mut(buffer).set_dirty(false);
+                        if let Some(buffer) = data.main_split.open_file_mut(path) {
+                            if buffer.rev() == *rev {
+                                Arc::make_mut(buffer).set_dirty(false);
+                            } else {
+                                // The buffer was edited while it was being
+                                // saved, so it still has unsaved changes and
+                                // what was waiting for the save is dropped.
+                                data.after_save = None;
+                            }
                         }
+                        data.run_after_save(ctx);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::BufferSaveFailed(path, kind, message) => {
+                        data.after_save = None;
+                        let file_name =
+                            path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                        match kind {
+                            SaveErrorKind::PermissionDenied => {
+                                data.status_message = Some(Arc::new(format!(
+                                    "Permission denied saving {}",
+                                    file_name
+                                )));
+                                ctx.submit_command(Command::new(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::RunPaletteCommands(
+                                        Self::save_options(path, &data.config),
+                                    ),
+                                    Target::Widget(data.palette.widget_id),
+                                ));
+                            }
+                            SaveErrorKind::Other => {
+                                data.status_message = Some(Arc::new(format!(
+                                    "Failed to save {}: {}",
+                                    file_name, message
+                                )));
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ToggleMaximizeEditor => {
+                        data.toggle_maximize_editor(ctx);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::SaveAs(path, target) => {
+                        data.main_split.save_as(ctx, path, target);
                         ctx.set_handled();
                     }
                     LapceUICommand::LoadBufferAndGoToPosition {
@@ -590,15 +1097,19 @@ impl Widget<LapceTabData> for LapceTabNew {
                         editor_view_id,
                         location,
                     } => {
-                        let buffer =
-                            data.main_split.open_files.get_mut(path).unwrap();
-                        Arc::make_mut(buffer).load_content(content);
-                        data.main_split.go_to_location(
-                            ctx,
-                            Some(*editor_view_id),
-                            location.clone(),
-                            &data.config,
-                        );
+                        if let Some(buffer) = data.main_split.open_file_mut(path) {
+                            Arc::make_mut(buffer).load_content(content);
+                            data.main_split.go_to_location(
+                                ctx,
+                                Some(*editor_view_id),
+                                location.clone(),
+                                &data.config,
+                            );
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::MovePanel(kind, index) => {
+                        data.move_panel(*kind, *index);
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateSettingsFile(key, value) => {
@@ -663,6 +1174,23 @@ impl Widget<LapceTabData> for LapceTabNew {
                         );
                         ctx.set_handled();
                     }
+                    LapceUICommand::OpenFileAt { path, line, column } => {
+                        data.main_split.jump_to_location(
+                            ctx,
+                            None,
+                            EditorLocationNew {
+                                path: path.clone(),
+                                position: Some(Position {
+                                    line: *line as u32,
+                                    character: *column as u32,
+                                }),
+                                scroll_offset: None,
+                                history: None,
+                            },
+                            &data.config,
+                        );
+                        ctx.set_handled();
+                    }
                     LapceUICommand::GoToLocationNew(editor_view_id, location) => {
                         data.main_split.go_to_location(
                             ctx,
@@ -720,6 +1248,39 @@ impl Widget<LapceTabData> for LapceTabNew {
                         ctx.set_handled();
                     }
                     LapceUICommand::GotoDefinition(
+                        editor_view_id,
+                        offset,
+                        locations,
+                    ) => {
+                        if let Some(editor) = data.main_split.active_editor() {
+                            if *editor_view_id == editor.view_id
+                                && *offset == editor.cursor.offset()
+                            {
+                                match definition_command(locations) {
+                                    LapceUICommand::JumpToLocation(
+                                        editor_view_id,
+                                        location,
+                                    ) => {
+                                        data.main_split.jump_to_location(
+                                            ctx,
+                                            editor_view_id,
+                                            location,
+                                            &data.config,
+                                        );
+                                    }
+                                    command => {
+                                        ctx.submit_command(Command::new(
+                                            LAPCE_UI_COMMAND,
+                                            command,
+                                            Target::Widget(data.palette.widget_id),
+                                        ));
+                                    }
+                                }
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::PeekDefinition(
                         editor_view_id,
                         offset,
                         location,
@@ -728,16 +1289,24 @@ impl Widget<LapceTabData> for LapceTabNew {
                             if *editor_view_id == editor.view_id
                                 && *offset == editor.cursor.offset()
                             {
-                                data.main_split.jump_to_location(
+                                data.show_peek(
                                     ctx,
-                                    None,
+                                    *editor_view_id,
+                                    *offset,
                                     location.clone(),
-                                    &data.config,
                                 );
                             }
                         }
                         ctx.set_handled();
                     }
+                    LapceUICommand::OpenPeek => {
+                        data.open_peek(ctx);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ClosePeek => {
+                        data.close_peek(ctx);
+                        ctx.set_handled();
+                    }
                     LapceUICommand::GotoReference(
                         editor_view_id,
                         offset,
@@ -762,11 +1331,148 @@ impl Widget<LapceTabData> for LapceTabNew {
                             data.main_split.open_files.get_mut(path)
                         {
                             if buffer.rev() == *rev {
+                                let mut resp = resp.clone();
+                                sort_code_actions(&mut resp);
                                 Arc::make_mut(buffer)
-                                    .code_actions
-                                    .insert(*offset, resp.clone());
+                                    .set_code_actions(*rev, *offset, resp);
+                            }
+                        }
+                    }
+                    LapceUICommand::UpdateDocumentHighlights(
+                        path,
+                        offset,
+                        ranges,
+                    ) => {
+                        let current = data
+                            .main_split
+                            .active_editor()
+                            .map(|editor| {
+                                editor.content == BufferContent::File(path.clone())
+                                    && editor.cursor.offset() == *offset
+                            })
+                            .unwrap_or(false);
+                        if current {
+                            if let Some(buffer) =
+                                data.main_split.open_files.get_mut(path)
+                            {
+                                let offset_of =
+                                    |position| buffer.offset_of_position(position);
+                                let ranges = ranges
+                                    .iter()
+                                    .map(|range| {
+                                        (
+                                            offset_of(&range.start),
+                                            offset_of(&range.end),
+                                        )
+                                    })
+                                    .collect();
+                                let buffer = Arc::make_mut(buffer);
+                                buffer.document_highlights =
+                                    Some((buffer.rev(), Arc::new(ranges)));
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateFoldingRanges(path, rev, ranges) => {
+                        if let Some(buffer) =
+                            data.main_split.open_files.get_mut(path)
+                        {
+                            if buffer.rev() == *rev {
+                                let ranges = ranges
+                                    .iter()
+                                    .map(|range| {
+                                        (
+                                            range.start_line as usize,
+                                            range.end_line as usize,
+                                        )
+                                    })
+                                    .filter(|(start, end)| end > start)
+                                    .collect();
+                                Arc::make_mut(buffer).folding_ranges =
+                                    Some((*rev, Arc::new(ranges)));
                             }
                         }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateDocumentSymbols(path, rev, symbols) => {
+                        if let Some(buffer) =
+                            data.main_split.open_files.get_mut(path)
+                        {
+                            if buffer.rev() == *rev {
+                                Arc::make_mut(buffer).document_symbols =
+                                    Some((*rev, Arc::new(symbols.clone())));
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ShowHierarchy(buffer_id, kind, result) => {
+                        let panel = kind.panel();
+                        let name = match kind {
+                            HierarchyKind::IncomingCalls
+                            | HierarchyKind::OutgoingCalls => "call hierarchy",
+                            HierarchyKind::Supertypes | HierarchyKind::Subtypes => {
+                                "type hierarchy"
+                            }
+                        };
+                        match result {
+                            Ok(items) if !items.is_empty() => {
+                                let proxy = data.proxy.clone();
+                                if let Some(hierarchy) = data.hierarchy_mut(panel) {
+                                    let hierarchy = Arc::make_mut(hierarchy);
+                                    hierarchy.set_roots(
+                                        *buffer_id,
+                                        *kind,
+                                        items.clone(),
+                                    );
+                                    if hierarchy.toggle(&[0]) {
+                                        hierarchy.fetch_children(
+                                            &proxy,
+                                            ctx.get_external_handle(),
+                                            &[0],
+                                        );
+                                    }
+                                }
+                                data.show_panel(ctx, panel);
+                            }
+                            Ok(_) => {
+                                data.status_message = Some(Arc::new(format!(
+                                    "No {} at the cursor",
+                                    name
+                                )));
+                            }
+                            Err(e) => {
+                                data.status_message = Some(Arc::new(format!(
+                                    "Can't show the {}: {}",
+                                    name, e
+                                )));
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateHierarchyChildren(
+                        kind,
+                        id,
+                        path,
+                        children,
+                    ) => {
+                        let panel = kind.panel();
+                        if let Some(hierarchy) = data.hierarchy_mut(panel) {
+                            if hierarchy.id == *id {
+                                Arc::make_mut(hierarchy)
+                                    .set_children(path, children.clone());
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateWorkspaceSymbols(request_id, symbols) => {
+                        let workspace_path = data.workspace.path.clone();
+                        Arc::make_mut(&mut data.palette).receive_workspace_symbols(
+                            ctx,
+                            *request_id,
+                            symbols,
+                            workspace_path.as_deref(),
+                        );
+                        ctx.set_handled();
                     }
                     LapceUICommand::PaletteReferences(offset, locations) => {
                         if let Some(editor) = data.main_split.active_editor() {
@@ -790,12 +1496,14 @@ impl Widget<LapceTabData> for LapceTabNew {
                         ctx.set_handled();
                     }
                     LapceUICommand::ReloadBuffer(id, rev, new_content) => {
-                        for (_, buffer) in data.main_split.open_files.iter_mut() {
+                        let mut reloaded = None;
+                        for (path, buffer) in data.main_split.open_files.iter_mut() {
                             if buffer.id() == *id {
                                 if buffer.rev() + 1 == *rev {
                                     let buffer = Arc::make_mut(buffer);
                                     buffer.load_content(new_content);
                                     buffer.set_rev(*rev);
+                                    reloaded = Some(path.clone());
 
                                     for (_, editor) in
                                         data.main_split.editors.iter_mut()
@@ -836,16 +1544,17 @@ impl Widget<LapceTabData> for LapceTabNew {
                                 break;
                             }
                         }
+                        if let Some(path) = reloaded {
+                            data.main_split.update_diagnostics_range(&path);
+                        }
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateSemanticStyles(_id, path, rev, styles) => {
-                        let buffer =
-                            data.main_split.open_files.get_mut(path).unwrap();
-                        if buffer.rev() == *rev {
-                            let buffer = Arc::make_mut(buffer);
-                            buffer.set_semantic_styles(Some(styles.clone()));
-                            buffer.line_styles().borrow_mut().clear();
-                        }
+                        self.pending_styles
+                            .entry(path.clone())
+                            .or_default()
+                            .update_semantic_styles(*rev, styles.clone());
+                        ctx.request_anim_frame();
                         ctx.set_handled();
                     }
                     LapceUICommand::ShowCodeActions
@@ -853,28 +1562,9 @@ impl Widget<LapceTabData> for LapceTabNew {
                         self.code_action.event(ctx, event, data, env);
                     }
                     LapceUICommand::Focus => {
-                        let dir = data
-                            .workspace
-                            .path
-                            .as_ref()
-                            .map(|p| {
-                                let dir = p
-                                    .file_name()
-                                    .unwrap_or(p.as_os_str())
-                                    .to_string_lossy();
-                                let dir = match &data.workspace.kind {
-                                    LapceWorkspaceType::Local => dir.to_string(),
-                                    LapceWorkspaceType::RemoteSSH(user, host) => {
-                                        format!("{} [{}@{}]", dir, user, host)
-                                    }
-                                    LapceWorkspaceType::RemoteWSL => {
-                                        format!("{dir} [wsl]")
-                                    }
-                                };
-                                dir
-                            })
-                            .unwrap_or_else(|| "Lapce".to_string());
-                        ctx.configure_window(WindowConfig::default().set_title(dir));
+                        ctx.configure_window(
+                            WindowConfig::default().set_title(data.window_title()),
+                        );
                         ctx.submit_command(Command::new(
                             LAPCE_UI_COMMAND,
                             LapceUICommand::Focus,
@@ -899,6 +1589,10 @@ impl Widget<LapceTabData> for LapceTabNew {
                         }
                         ctx.set_handled();
                     }
+                    LapceUICommand::TogglePanel(kind) => {
+                        data.toggle_panel(ctx, *kind);
+                        ctx.set_handled();
+                    }
                     LapceUICommand::FocusEditor => {
                         if let Some(active) = *data.main_split.active {
                             ctx.submit_command(Command::new(
@@ -909,17 +1603,40 @@ impl Widget<LapceTabData> for LapceTabNew {
                         }
                         ctx.set_handled();
                     }
-                    LapceUICommand::UpdateSyntax { path, rev, syntax } => {
+                    LapceUICommand::SetEncoding(path, encoding) => {
+                        if let Some(buffer) = data.main_split.open_file_mut(path) {
+                            Arc::make_mut(buffer).set_encoding(encoding.clone());
+                        }
                         ctx.set_handled();
-                        let buffer =
-                            data.main_split.open_files.get_mut(path).unwrap();
-                        let buffer = Arc::make_mut(buffer);
-                        if buffer.rev() == *rev {
-                            buffer.set_syntax(Some(syntax.clone()));
-                            if buffer.semantic_styles().is_none() {
-                                buffer.line_styles().borrow_mut().clear();
+                    }
+                    LapceUICommand::SetLanguage(language) => {
+                        if let Some(BufferContent::File(path)) = data
+                            .main_split
+                            .active_editor()
+                            .map(|editor| editor.content.clone())
+                        {
+                            // Styles on their way are for the old language.
+                            self.pending_styles.remove(&path);
+                            if let Some(buffer) =
+                                data.main_split.open_file_mut(&path)
+                            {
+                                let buffer = Arc::make_mut(buffer);
+                                buffer.set_language(*language);
+                                data.proxy.set_language(
+                                    buffer.id(),
+                                    language.map(|language| language.language_id()),
+                                );
                             }
                         }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateSyntax { path, rev, syntax } => {
+                        ctx.set_handled();
+                        self.pending_styles
+                            .entry(path.clone())
+                            .or_default()
+                            .update_syntax(*rev, syntax.clone());
+                        ctx.request_anim_frame();
                     }
                     LapceUICommand::UpdateHistoryChanges {
                         path,
@@ -929,13 +1646,13 @@ impl Widget<LapceTabData> for LapceTabNew {
                         ..
                     } => {
                         ctx.set_handled();
-                        let buffer =
-                            data.main_split.open_files.get_mut(path).unwrap();
-                        Arc::make_mut(buffer).update_history_changes(
-                            *rev,
-                            history,
-                            changes.clone(),
-                        );
+                        if let Some(buffer) = data.main_split.open_file_mut(path) {
+                            Arc::make_mut(buffer).update_history_changes(
+                                *rev,
+                                history,
+                                changes.clone(),
+                            );
+                        }
                     }
                     LapceUICommand::UpdateHistoryStyle {
                         path,
@@ -944,21 +1661,49 @@ impl Widget<LapceTabData> for LapceTabNew {
                         ..
                     } => {
                         ctx.set_handled();
-                        let buffer =
-                            data.main_split.open_files.get_mut(path).unwrap();
-                        Arc::make_mut(buffer)
-                            .history_styles
-                            .insert(history.to_string(), highlights.to_owned());
-                        buffer
-                            .history_line_styles
-                            .borrow_mut()
-                            .insert(history.to_string(), HashMap::new());
+                        if let Some(buffer) = data.main_split.open_file_mut(path) {
+                            Arc::make_mut(buffer)
+                                .history_styles
+                                .insert(history.to_string(), highlights.to_owned());
+                            buffer
+                                .history_line_styles
+                                .borrow_mut()
+                                .insert(history.to_string(), HashMap::new());
+                        }
                     }
                     LapceUICommand::UpdatePickerPwd(path) => {
-                        Arc::make_mut(&mut data.picker).pwd = path.clone();
+                        if path != &data.picker.pwd {
+                            Arc::make_mut(&mut data.picker).set_pwd(path.clone());
+                            data.read_picker_pwd(ctx);
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdatePickerFilter(filter) => {
+                        Arc::make_mut(&mut data.picker).set_filter(filter);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::FilePickerUp => {
+                        // The root has no parent to go up to
+                        if let Some(parent) = data.picker.pwd.parent() {
+                            data.set_picker_pwd(parent.to_path_buf());
+                            data.read_picker_pwd(ctx);
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::FilePickerHome => {
+                        data.set_picker_pwd(data.picker.home.clone());
                         data.read_picker_pwd(ctx);
                         ctx.set_handled();
                     }
+                    LapceUICommand::ToggleFilePickerSelection => {
+                        let picker = Arc::make_mut(&mut data.picker);
+                        picker.toggle_selected_path(picker.index);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::OpenPickerMatch => {
+                        data.open_picker_match(ctx);
+                        ctx.set_handled();
+                    }
                     LapceUICommand::UpdatePickerItems(path, items) => {
                         Arc::make_mut(&mut data.picker)
                             .set_item_children(path, items.clone());
@@ -966,22 +1711,32 @@ impl Widget<LapceTabData> for LapceTabNew {
                     }
                     LapceUICommand::UpdateExplorerItems(_index, path, items) => {
                         let file_explorer = Arc::make_mut(&mut data.file_explorer);
-                        if let Some(node) = file_explorer.get_node_mut(path) {
-                            node.children = items
-                                .iter()
-                                .map(|item| (item.path_buf.clone(), item.clone()))
-                                .collect();
-                            node.read = true;
-                            node.open = true;
-                            node.children_open_count = node.children.len();
+                        file_explorer.update_items(path, items);
+                        if let Some(path) = file_explorer.pending_reveal.clone() {
+                            data.expand_file_explorer_to(ctx, &path);
                         }
-                        if let Some(paths) = file_explorer.node_tree(path) {
-                            for path in paths.iter() {
-                                file_explorer.update_node_count(path);
-                            }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateExplorerItemsBatch(batch, failed) => {
+                        let file_explorer = Arc::make_mut(&mut data.file_explorer);
+                        file_explorer.update_items_batch(batch, failed);
+                        if let Some(path) = file_explorer.pending_reveal.clone() {
+                            data.expand_file_explorer_to(ctx, &path);
                         }
                         ctx.set_handled();
                     }
+                    LapceUICommand::FileChangeNotification(changes) => {
+                        data.handle_file_changes(ctx, changes);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ReopenClosedTab => {
+                        data.reopen_closed_editor(ctx);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::RevealInFileExplorer(path) => {
+                        data.reveal_in_file_explorer(ctx, path);
+                        ctx.set_handled();
+                    }
                     _ => (),
                 }
             }
@@ -992,7 +1747,9 @@ impl Widget<LapceTabData> for LapceTabNew {
         self.palette.event(ctx, event, data, env);
         self.completion.event(ctx, event, data, env);
         self.hover.event(ctx, event, data, env);
+        self.signature.event(ctx, event, data, env);
         self.code_action.event(ctx, event, data, env);
+        self.peek.event(ctx, event, data, env);
         self.main_split.event(ctx, event, data, env);
         self.status.event(ctx, event, data, env);
         for (_, panel) in data.panels.clone().iter() {
@@ -1050,6 +1807,8 @@ impl Widget<LapceTabData> for LapceTabNew {
         self.status.lifecycle(ctx, event, data, env);
         self.completion.lifecycle(ctx, event, data, env);
         self.hover.lifecycle(ctx, event, data, env);
+        self.signature.lifecycle(ctx, event, data, env);
+        self.peek.lifecycle(ctx, event, data, env);
         self.picker.lifecycle(ctx, event, data, env);
         self.settings.lifecycle(ctx, event, data, env);
 
@@ -1098,6 +1857,8 @@ impl Widget<LapceTabData> for LapceTabNew {
         self.main_split.update(ctx, data, env);
         self.completion.update(ctx, data, env);
         self.hover.update(ctx, data, env);
+        self.signature.update(ctx, data, env);
+        self.peek.update(ctx, data, env);
         self.code_action.update(ctx, data, env);
         self.status.update(ctx, data, env);
         self.picker.update(ctx, data, env);
@@ -1355,6 +2116,13 @@ impl Widget<LapceTabData> for LapceTabNew {
             .set_origin(ctx, data, env, main_split_origin);
         self.main_split_height = main_split_size.height;
 
+        if data.peek.visible {
+            if let Some(peek_origin) = data.peek_origin(self_size) {
+                self.peek.layout(ctx, bc, data, env);
+                self.peek.set_origin(ctx, data, env, peek_origin);
+            }
+        }
+
         if data.completion.status != CompletionStatus::Inactive {
             let completion_origin =
                 data.completion_origin(ctx.text(), self_size, &data.config);
@@ -1370,6 +2138,13 @@ impl Widget<LapceTabData> for LapceTabNew {
             self.hover.set_origin(ctx, data, env, hover_origin);
         }
 
+        if data.signature.status != SignatureStatus::Inactive {
+            let signature_origin =
+                data.signature_origin(ctx.text(), self_size, &data.config);
+            self.signature.layout(ctx, bc, data, env);
+            self.signature.set_origin(ctx, data, env, signature_origin);
+        }
+
         if data.main_split.show_code_actions {
             let code_action_origin =
                 data.code_action_origin(ctx.text(), self_size, &data.config);
@@ -1472,8 +2247,10 @@ impl Widget<LapceTabData> for LapceTabNew {
         //     //     .paint_svg(ctx, data, active_index, kind);
         // }
         self.status.paint(ctx, data, env);
+        self.peek.paint(ctx, data, env);
         self.completion.paint(ctx, data, env);
         self.hover.paint(ctx, data, env);
+        self.signature.paint(ctx, data, env);
         self.code_action.paint(ctx, data, env);
         self.palette.paint(ctx, data, env);
         self.picker.paint(ctx, data, env);
@@ -1486,14 +2263,22 @@ pub struct LapceTabHeader {
     pub drag_start: Option<(Point, Point)>,
     pub mouse_pos: Point,
     cross_rect: Rect,
+    cross_hovered: bool,
+    tooltip_timer: TimerToken,
+    show_tooltip: bool,
 }
 
 impl LapceTabHeader {
+    const TOOLTIP_DELAY: Duration = Duration::from_millis(500);
+
     pub fn new() -> Self {
         Self {
             cross_rect: Rect::ZERO,
             drag_start: None,
             mouse_pos: Point::ZERO,
+            cross_hovered: false,
+            tooltip_timer: TimerToken::INVALID,
+            show_tooltip: false,
         }
     }
 
@@ -1501,6 +2286,59 @@ impl LapceTabHeader {
         self.drag_start
             .map(|(drag, origin)| origin + (self.mouse_pos - drag))
     }
+
+    fn close_tooltip(data: &LapceTabData) -> String {
+        let cmd = LapceWorkbenchCommand::CloseTab.to_string();
+        match data
+            .keypress
+            .command_keymaps
+            .get(&cmd)
+            .and_then(|keymaps| keymaps.first())
+        {
+            Some(keymap) => format!(
+                "Close ({})",
+                keymap.key.iter().map(|k| k.to_string()).join(" ")
+            ),
+            None => "Close".to_string(),
+        }
+    }
+
+    fn paint_tooltip(&self, ctx: &mut PaintCtx, data: &LapceTabData) {
+        let text_layout = ctx
+            .text()
+            .new_text_layout(Self::close_tooltip(data))
+            .font(FontFamily::SYSTEM_UI, 12.0)
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        let text_size = text_layout.size();
+        let size = ctx.size();
+        let padding = 5.0;
+        let width = text_size.width + padding * 2.0;
+        let x = (self.cross_rect.center().x - width / 2.0)
+            .min(size.width - width)
+            .max(0.0);
+        let rect = Size::new(width, text_size.height + padding * 2.0)
+            .to_rect()
+            .with_origin(Point::new(x, size.height + 2.0));
+        let shadow = data
+            .config
+            .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW)
+            .clone();
+        let bg = data
+            .config
+            .get_color_unchecked(LapceTheme::HOVER_BACKGROUND)
+            .clone();
+        ctx.paint_with_z_index(1, move |ctx| {
+            ctx.blurred_rect(rect, 5.0, &shadow);
+            ctx.fill(rect, &bg);
+            ctx.draw_text(&text_layout, rect.origin() + (padding, padding));
+        });
+    }
 }
 
 impl Widget<LapceTabData> for LapceTabHeader {
@@ -1520,19 +2358,56 @@ impl Widget<LapceTabData> for LapceTabHeader {
                     }
                     return;
                 }
-                if self.cross_rect.contains(mouse_event.pos) {
+                let cross_hovered = self.cross_rect.contains(mouse_event.pos);
+                if cross_hovered != self.cross_hovered {
+                    self.cross_hovered = cross_hovered;
+                    self.show_tooltip = false;
+                    self.tooltip_timer = if cross_hovered {
+                        ctx.request_timer(Self::TOOLTIP_DELAY)
+                    } else {
+                        TimerToken::INVALID
+                    };
+                    ctx.request_paint();
+                }
+                if cross_hovered {
                     ctx.set_cursor(&druid::Cursor::Pointer);
                 } else {
                     ctx.set_cursor(&druid::Cursor::Arrow);
                 }
             }
+            Event::Timer(token) if *token == self.tooltip_timer => {
+                self.tooltip_timer = TimerToken::INVALID;
+                if self.cross_hovered {
+                    self.show_tooltip = true;
+                    ctx.request_paint();
+                }
+            }
             Event::MouseDown(mouse_event) => {
+                if mouse_event.button.is_middle() {
+                    if ctx.size().to_rect().contains(mouse_event.pos) {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::CloseTabId(data.id),
+                            Target::Auto,
+                        ));
+                    }
+                    return;
+                }
+                if !mouse_event.button.is_left() {
+                    return;
+                }
                 if self.cross_rect.contains(mouse_event.pos) {
                     ctx.submit_command(Command::new(
                         LAPCE_UI_COMMAND,
                         LapceUICommand::CloseTabId(data.id),
                         Target::Auto,
                     ));
+                } else if mouse_event.count == 2 {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ToggleMaximizeEditor,
+                        Target::Widget(data.id),
+                    ));
                 } else {
                     self.drag_start =
                         Some((ctx.to_window(mouse_event.pos), ctx.window_origin()));
@@ -1545,9 +2420,11 @@ impl Widget<LapceTabData> for LapceTabHeader {
                     ));
                 }
             }
-            Event::MouseUp(_mouse_event) => {
-                ctx.set_active(false);
-                self.drag_start = None;
+            Event::MouseUp(mouse_event) => {
+                if mouse_event.button.is_left() {
+                    ctx.set_active(false);
+                    self.drag_start = None;
+                }
             }
             _ => {}
         }
@@ -1560,23 +2437,31 @@ impl Widget<LapceTabData> for LapceTabHeader {
         _data: &LapceTabData,
         _env: &Env,
     ) {
-        if let LifeCycle::HotChanged(_is_hot) = event {
+        if let LifeCycle::HotChanged(is_hot) = event {
+            if !is_hot {
+                self.cross_hovered = false;
+                self.show_tooltip = false;
+                self.tooltip_timer = TimerToken::INVALID;
+            }
             ctx.request_paint();
         }
     }
 
     fn update(
         &mut self,
-        _ctx: &mut druid::UpdateCtx,
-        _old_data: &LapceTabData,
-        _data: &LapceTabData,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
         _env: &Env,
     ) {
+        if old_data.window_title() != data.window_title() {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
         &mut self,
-        _ctx: &mut LayoutCtx,
+        ctx: &mut LayoutCtx,
         bc: &BoxConstraints,
         _data: &LapceTabData,
         _env: &Env,
@@ -1589,32 +2474,16 @@ impl Widget<LapceTabData> for LapceTabHeader {
         self.cross_rect = Size::new(cross_size, cross_size)
             .to_rect()
             .with_origin(origin);
+        // Leave room below the header for the close button tooltip.
+        ctx.set_paint_insets((0.0, 0.0, 0.0, 30.0));
 
         size
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
-        let dir = data
-            .workspace
-            .path
-            .as_ref()
-            .map(|p| {
-                let dir = p.file_name().unwrap_or(p.as_os_str()).to_string_lossy();
-                let dir = match &data.workspace.kind {
-                    LapceWorkspaceType::Local => dir.to_string(),
-                    LapceWorkspaceType::RemoteSSH(user, host) => {
-                        format!("{} [{}@{}]", dir, user, host)
-                    }
-                    LapceWorkspaceType::RemoteWSL => {
-                        format!("{dir} [wsl]")
-                    }
-                };
-                dir
-            })
-            .unwrap_or_else(|| "Lapce".to_string());
         let text_layout = ctx
             .text()
-            .new_text_layout(dir)
+            .new_text_layout(data.window_title())
             .font(FontFamily::SYSTEM_UI, 13.0)
             .text_color(
                 data.config
@@ -1631,30 +2500,34 @@ impl Widget<LapceTabData> for LapceTabHeader {
         ctx.draw_text(&text_layout, Point::new(x, y));
 
         if ctx.is_hot() {
+            let cross_color = if self.cross_hovered {
+                ctx.fill(
+                    self.cross_rect.inflate(4.0, 4.0).to_rounded_rect(3.0),
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
+                );
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOCUS)
+                    .clone()
+            } else {
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                    .clone()
+            };
             let line = Line::new(
                 Point::new(self.cross_rect.x0, self.cross_rect.y0),
                 Point::new(self.cross_rect.x1, self.cross_rect.y1),
             );
-            ctx.stroke(
-                line,
-                &data
-                    .config
-                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                    .clone(),
-                1.0,
-            );
+            ctx.stroke(line, &cross_color, 1.0);
             let line = Line::new(
                 Point::new(self.cross_rect.x1, self.cross_rect.y0),
                 Point::new(self.cross_rect.x0, self.cross_rect.y1),
             );
-            ctx.stroke(
-                line,
-                &data
-                    .config
-                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                    .clone(),
-                1.0,
-            );
+            ctx.stroke(line, &cross_color, 1.0);
+        }
+
+        if self.show_tooltip {
+            self.paint_tooltip(ctx, data);
         }
     }
 }
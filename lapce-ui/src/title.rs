@@ -231,6 +231,7 @@ impl Widget<LapceWindowData> for Title {
                 .get_message()
                 .unwrap()
                 .to_string(),
+            indices: Vec::new(),
             command: LapceCommandNew {
                 cmd: LapceWorkbenchCommand::ConnectSshHost.to_string(),
                 palette_desc: None,
@@ -245,6 +246,7 @@ impl Widget<LapceWindowData> for Title {
                     .get_message()
                     .unwrap()
                     .to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceWorkbenchCommand::ConnectWsl.to_string(),
                     palette_desc: None,
@@ -257,6 +259,7 @@ impl Widget<LapceWindowData> for Title {
         if tab.workspace.kind.is_remote() {
             menu_items.push(MenuItem {
                 text: "Disconnect Remote".to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceWorkbenchCommand::DisconnectRemote.to_string(),
                     palette_desc: None,
@@ -325,6 +328,7 @@ impl Widget<LapceWindowData> for Title {
                     .get_message()
                     .unwrap()
                     .to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceWorkbenchCommand::OpenFolder.to_string(),
                     palette_desc: None,
@@ -337,6 +341,7 @@ impl Widget<LapceWindowData> for Title {
                     .get_message()
                     .unwrap()
                     .to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceWorkbenchCommand::PaletteWorkspace.to_string(),
                     palette_desc: None,
@@ -410,6 +415,7 @@ impl Widget<LapceWindowData> for Title {
                 .iter()
                 .map(|b| MenuItem {
                     text: b.to_string(),
+                    indices: Vec::new(),
                     command: LapceCommandNew {
                         cmd: LapceWorkbenchCommand::CheckoutBranch.to_string(),
                         palette_desc: None,
@@ -456,6 +462,7 @@ impl Widget<LapceWindowData> for Title {
                     .get_message()
                     .unwrap()
                     .to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceWorkbenchCommand::PaletteCommand.to_string(),
                     palette_desc: None,
@@ -468,6 +475,7 @@ impl Widget<LapceWindowData> for Title {
                     .get_message()
                     .unwrap()
                     .to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceWorkbenchCommand::OpenSettings.to_string(),
                     palette_desc: None,
@@ -480,6 +488,7 @@ impl Widget<LapceWindowData> for Title {
                     .get_message()
                     .unwrap()
                     .to_string(),
+                indices: Vec::new(),
                 command: LapceCommandNew {
                     cmd: LapceWorkbenchCommand::OpenKeyboardShortcuts.to_string(),
                     palette_desc: None,
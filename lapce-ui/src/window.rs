@@ -3,15 +3,21 @@ use druid::{
     widget::{LensWrap, WidgetExt},
     BoxConstraints, Command, Env, Event, EventCtx, LayoutCtx, LifeCycle,
     LifeCycleCtx, PaintCtx, Point, RenderContext, Size, Target, Widget, WidgetId,
-    WidgetPod,
+    WidgetPod, WindowConfig,
 };
 use lapce_data::{
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandTarget, LapceCommandNew, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
     config::{Config, LapceTheme},
     data::{LapceTabData, LapceTabLens, LapceWindowData},
-    state::LapceWorkspace,
+    menu::MenuItem,
+    state::{LapceWorkspace, LapceWorkspaceType},
 };
+use serde_json::json;
 use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::sync::Arc;
 
 use crate::{
@@ -20,6 +26,9 @@ use crate::{
     title::Title,
 };
 
+/// How many workspaces are remembered as recently opened
+const MAX_RECENT_WORKSPACES: usize = 50;
+
 pub struct LapceWindowNew {
     pub title: WidgetPod<LapceWindowData, Box<dyn Widget<LapceWindowData>>>,
     pub tabs: Vec<WidgetPod<LapceWindowData, Box<dyn Widget<LapceWindowData>>>>,
@@ -112,6 +121,28 @@ impl LapceWindowNew {
         ctx.request_layout();
     }
 
+    /// Marks `workspace` as the last opened one in the recent workspaces
+    fn add_recent_workspace(workspace: &LapceWorkspace) {
+        let mut workspaces = Config::recent_workspaces().unwrap_or_default();
+
+        let mut exits = false;
+        for w in workspaces.iter_mut() {
+            if w.path == workspace.path && w.kind == workspace.kind {
+                w.last_open = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                exits = true;
+            }
+        }
+        if !exits {
+            workspaces.push(workspace.clone());
+        }
+        workspaces.sort_by_key(|w| -(w.last_open as i64));
+        workspaces.truncate(MAX_RECENT_WORKSPACES);
+        Config::update_recent_workspaces(workspaces);
+    }
+
     pub fn close_index_tab(
         &mut self,
         ctx: &mut EventCtx,
@@ -170,6 +201,92 @@ impl LapceWindowNew {
     pub fn close_tab(&mut self, ctx: &mut EventCtx, data: &mut LapceWindowData) {
         self.close_index_tab(ctx, data, data.active);
     }
+
+    /// Closes the tab, unless it has unsaved files, in which case the user is
+    /// asked whether to save them first.
+    fn request_close_tab(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceWindowData,
+        tab_id: WidgetId,
+    ) {
+        let unsaved = match data.tabs.get(&tab_id) {
+            Some(tab) if data.tabs.len() > 1 => tab.unsaved_files(),
+            _ => return,
+        };
+        if unsaved.is_empty() || !data.config.lapce.confirm_close_unsaved {
+            self.close_tab_id(ctx, data, tab_id);
+            return;
+        }
+
+        // The picked answer is sent to the active tab, so the tab being closed
+        // is made active without taking the focus away from the menu.
+        if tab_id != data.active_id {
+            if let Some(i) = self.tabs.iter().position(|t| t.id() == tab_id) {
+                data.active = i;
+                data.active_id = tab_id;
+                let _ = data.db.save_tabs_async(data);
+            }
+        }
+
+        let item = |text: String, cmd: LapceWorkbenchCommand, path| MenuItem {
+            text,
+            indices: Vec::new(),
+            command: LapceCommandNew {
+                cmd: cmd.to_string(),
+                data: path,
+                palette_desc: None,
+                target: CommandTarget::Workbench,
+            },
+        };
+        let save = if unsaved.len() == 1 {
+            "Save and Close"
+        } else {
+            "Save All and Close"
+        };
+        let mut items = vec![
+            item(
+                save.to_string(),
+                LapceWorkbenchCommand::SaveAllAndCloseTab,
+                None,
+            ),
+            item(
+                "Close Without Saving".to_string(),
+                LapceWorkbenchCommand::CloseTabWithoutSaving,
+                None,
+            ),
+            item(
+                "Cancel".to_string(),
+                LapceWorkbenchCommand::CancelCloseTab,
+                None,
+            ),
+        ];
+        for path in unsaved {
+            items.push(item(
+                format!("Unsaved: {}", path.to_string_lossy()),
+                LapceWorkbenchCommand::CancelCloseTab,
+                Some(json!(path)),
+            ));
+        }
+
+        Self::show_prompt(ctx, data, Arc::new(items));
+    }
+
+    /// Shows `items` as the answers to a question, in the middle of the window
+    fn show_prompt(
+        ctx: &mut EventCtx,
+        data: &mut LapceWindowData,
+        items: Arc<Vec<MenuItem>>,
+    ) {
+        let origin = Point::new(data.size.width / 3.0, data.size.height / 4.0);
+        let menu = Arc::make_mut(&mut data.menu);
+        menu.show_prompt(origin, items);
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(menu.widget_id),
+        ));
+    }
 }
 
 impl Widget<LapceWindowData> for LapceWindowNew {
@@ -239,44 +356,58 @@ impl Widget<LapceWindowData> for LapceWindowNew {
                     LapceUICommand::HideMenu => {
                         ctx.set_handled();
                         let menu = Arc::make_mut(&mut data.menu);
-                        menu.shown = false;
+                        menu.hide();
                     }
                     LapceUICommand::ShowMenu(point, items) => {
                         ctx.set_handled();
+                        let recent = if data.config.lapce.menu_show_recent {
+                            data.recent_menu_items.iter().cloned().collect()
+                        } else {
+                            Vec::new()
+                        };
                         let menu = Arc::make_mut(&mut data.menu);
-                        menu.origin = *point;
-                        menu.items = items.clone();
-                        menu.shown = true;
-                        menu.active = 0;
+                        menu.show(*point, items.clone(), Arc::new(recent));
                         ctx.submit_command(Command::new(
                             LAPCE_UI_COMMAND,
                             LapceUICommand::Focus,
                             Target::Widget(menu.widget_id),
                         ));
                     }
+                    LapceUICommand::ShowPrompt(items) => {
+                        ctx.set_handled();
+                        Self::show_prompt(ctx, data, items.clone());
+                    }
+                    LapceUICommand::ClearRecentMenuItems => {
+                        ctx.set_handled();
+                        data.recent_menu_items = Arc::new(VecDeque::new());
+                    }
+                    LapceUICommand::UpdateWindowTitle => {
+                        ctx.set_handled();
+                        let tab = data.tabs.get(&data.active_id).unwrap();
+                        ctx.configure_window(
+                            WindowConfig::default().set_title(tab.window_title()),
+                        );
+                    }
                     LapceUICommand::SetWorkspace(workspace) => {
-                        let mut workspaces =
-                            Config::recent_workspaces().unwrap_or_default();
-
-                        let mut exits = false;
-                        for w in workspaces.iter_mut() {
-                            if w.path == workspace.path && w.kind == workspace.kind {
-                                w.last_open = std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_secs();
-                                exits = true;
-                            }
-                        }
-                        if !exits {
-                            workspaces.push(workspace.clone());
-                        }
-                        workspaces.sort_by_key(|w| -(w.last_open as i64));
-                        Config::update_recent_workspaces(workspaces);
-
+                        Self::add_recent_workspace(workspace);
                         self.new_tab(ctx, data, workspace.clone(), true);
                         return;
                     }
+                    LapceUICommand::OpenRemoteWorkspace { user, host, path } => {
+                        let workspace = LapceWorkspace {
+                            kind: LapceWorkspaceType::RemoteSSH(
+                                user.to_string(),
+                                host.to_string(),
+                            ),
+                            path: Some(path.clone()),
+                            last_open: 0,
+                        };
+                        let tab = data.tabs.get(&data.active_id).unwrap();
+                        let _ = data.db.carry_over_workspace(tab, &workspace);
+                        Self::add_recent_workspace(&workspace);
+                        self.new_tab(ctx, data, workspace, true);
+                        return;
+                    }
                     LapceUICommand::SetTheme(theme, preview) => {
                         let config = Arc::make_mut(&mut data.config);
                         config.set_theme(theme, *preview);
@@ -293,10 +424,15 @@ impl Widget<LapceWindowData> for LapceWindowNew {
                         return;
                     }
                     LapceUICommand::CloseTab => {
-                        self.close_tab(ctx, data);
+                        let tab_id = data.active_id;
+                        self.request_close_tab(ctx, data, tab_id);
                         return;
                     }
                     LapceUICommand::CloseTabId(tab_id) => {
+                        self.request_close_tab(ctx, data, *tab_id);
+                        return;
+                    }
+                    LapceUICommand::ForceCloseTabId(tab_id) => {
                         self.close_tab_id(ctx, data, *tab_id);
                         return;
                     }
@@ -430,6 +566,13 @@ impl Widget<LapceWindowData> for LapceWindowNew {
         if old_tab.workspace != tab.workspace {
             ctx.request_layout();
         }
+        if old_tab.window_title() != tab.window_title() {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::UpdateWindowTitle,
+                Target::Window(data.window_id),
+            ));
+        }
         for tab in self.tabs.iter_mut() {
             tab.update(ctx, data, env);
         }
@@ -444,8 +587,9 @@ impl Widget<LapceWindowData> for LapceWindowNew {
     ) -> Size {
         let self_size = bc.max();
 
-        self.menu.layout(ctx, bc, data, env);
-        self.menu.set_origin(ctx, data, env, data.menu.origin);
+        let menu_size = self.menu.layout(ctx, bc, data, env);
+        let menu_origin = data.menu.clamped_origin(menu_size, self_size);
+        self.menu.set_origin(ctx, data, env, menu_origin);
 
         let title_size = self.title.layout(ctx, bc, data, env);
         self.title.set_origin(ctx, data, env, Point::ZERO);